@@ -0,0 +1,79 @@
+//! Per-mapper read throughput: a full sweep of CPU $8000-$FFFF and PPU
+//! $0000-$1FFF, the accesses every emulated frame is dominated by.
+//!
+//! Run with `cargo bench -p cathode8-core --bench mappers`.
+
+use std::hint::black_box;
+
+use cathode8_core::nes::cartridge::Cartridge;
+use cathode8_core::nes::mapper::{Mapper, Mirroring, create_mapper};
+use criterion::{Criterion, criterion_group, criterion_main};
+
+const MAPPERS: [(u16, &str); 8] = [
+    (0, "nrom"),
+    (1, "mmc1"),
+    (2, "uxrom"),
+    (3, "cnrom"),
+    (4, "mmc3"),
+    (5, "mmc5"),
+    (7, "axrom"),
+    (69, "fme7"),
+];
+
+fn mapper(mapper_id: u16) -> Box<dyn Mapper> {
+    let (prg_len, chr_len) = if mapper_id == 0 {
+        (0x8000, 0x2000)
+    } else {
+        (0x40000, 0x20000)
+    };
+    create_mapper(Cartridge {
+        mapper_id,
+        submapper_id: 0,
+        mirroring: Mirroring::Vertical,
+        four_screen: false,
+        has_battery_backed_ram: false,
+        prg_rom: (0..prg_len).map(|i| i as u8).collect(),
+        chr_data: (0..chr_len).map(|i| (i >> 8) as u8).collect(),
+        chr_is_ram: false,
+        prg_ram_size: 0x2000,
+        region: None,
+    })
+    .unwrap()
+}
+
+fn cpu_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cpu_read");
+    for (id, name) in MAPPERS {
+        let mut mapper = mapper(id);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut sum = 0u32;
+                for addr in 0x8000..=0xFFFF {
+                    sum = sum.wrapping_add(mapper.cpu_read(black_box(addr)) as u32);
+                }
+                sum
+            })
+        });
+    }
+    group.finish();
+}
+
+fn ppu_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ppu_read");
+    for (id, name) in MAPPERS {
+        let mut mapper = mapper(id);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut sum = 0u32;
+                for addr in 0x0000..0x2000 {
+                    sum = sum.wrapping_add(mapper.ppu_read(black_box(addr)) as u32);
+                }
+                sum
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cpu_reads, ppu_reads);
+criterion_main!(benches);
@@ -0,0 +1,452 @@
+//! RAM-watch autosplitter: a small script of conditions on internal RAM that
+//! starts, splits and resets a speedrun timer, checked once per frame.
+//!
+//! Each script line is `start`, `split` or `reset` followed by one or more
+//! conditions joined with `&&`:
+//!
+//! ```text
+//! # Super Mario Bros.
+//! start $0770 == 1
+//! split $075F changes
+//! split $075F == 7 && $0772 == 3
+//! reset $0770 == 0
+//! ```
+//!
+//! A condition compares an address (`$hex` or decimal) against a value with
+//! `==`, `!=`, `<`, `<=`, `>` or `>=`, or watches it with `changes`,
+//! `increases` or `decreases`. A line fires on the frame its conditions
+//! become true, so a value that stays put doesn't fire again. Split lines are
+//! used in order: only the next one is watched, and the run ends after the
+//! last. The first frame after creating or re-arming the splitter only
+//! records a baseline, so loading a state where a condition already holds
+//! doesn't fire it.
+//!
+//! [`RamTriggers`] uses the same conditions without the run structure: a
+//! list of named lines (`level: $0760 changes`) that each fire on their own,
+//! for things like capturing a practice state on every room change.
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::nes::Nes;
+
+const RAM_SIZE: usize = 0x800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitEvent {
+    Start,
+    /// Index of the split line that fired; the run is over when it is the
+    /// last one.
+    Split(usize),
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Test {
+    Eq(u8),
+    Ne(u8),
+    Lt(u8),
+    Le(u8),
+    Gt(u8),
+    Ge(u8),
+    Changes,
+    Increases,
+    Decreases,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Condition {
+    addr: u16,
+    test: Test,
+}
+
+impl Condition {
+    fn holds(self, now: &[u8], before: &[u8]) -> bool {
+        let value = now[self.addr as usize];
+        let previous = before[self.addr as usize];
+        match self.test {
+            Test::Eq(rhs) => value == rhs,
+            Test::Ne(rhs) => value != rhs,
+            Test::Lt(rhs) => value < rhs,
+            Test::Le(rhs) => value <= rhs,
+            Test::Gt(rhs) => value > rhs,
+            Test::Ge(rhs) => value >= rhs,
+            Test::Changes => value != previous,
+            Test::Increases => value > previous,
+            Test::Decreases => value < previous,
+        }
+    }
+}
+
+/// Conditions that must all hold, plus whether they did last frame.
+#[derive(Debug, Clone)]
+struct Trigger {
+    all: Vec<Condition>,
+    was_true: bool,
+}
+
+impl Trigger {
+    /// True on the frame the conditions start holding.
+    fn rising(&mut self, now: &[u8], before: &[u8]) -> bool {
+        let holds = self.all.iter().all(|cond| cond.holds(now, before));
+        let rising = holds && !self.was_true;
+        self.was_true = holds;
+        rising
+    }
+}
+
+/// Internal RAM as of the previous frame, for `changes` and edge detection.
+#[derive(Debug, Clone, Default)]
+struct RamHistory {
+    previous: Option<Vec<u8>>,
+}
+
+impl RamHistory {
+    /// This frame's and the previous frame's RAM, or `None` on a baseline
+    /// frame, after which `triggers` know which conditions already held.
+    fn advance<'a>(
+        &mut self,
+        peek: impl Fn(u16) -> u8,
+        triggers: impl Iterator<Item = &'a mut Trigger>,
+    ) -> Option<(Vec<u8>, Vec<u8>)> {
+        let now: Vec<u8> = (0..RAM_SIZE as u16).map(peek).collect();
+        match self.previous.replace(now.clone()) {
+            Some(before) => Some((now, before)),
+            None => {
+                for trigger in triggers {
+                    trigger.rising(&now, &now);
+                }
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    Waiting,
+    Running { next_split: usize },
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoSplitter {
+    start: Option<Trigger>,
+    splits: Vec<Trigger>,
+    reset: Option<Trigger>,
+    state: RunState,
+    history: RamHistory,
+}
+
+impl AutoSplitter {
+    pub fn parse(script: &str) -> Result<Self> {
+        let mut splitter = Self {
+            start: None,
+            splits: Vec::new(),
+            reset: None,
+            state: RunState::Waiting,
+            history: RamHistory::default(),
+        };
+        for (index, line) in script.lines().enumerate() {
+            let line = strip_comment(line);
+            if line.is_empty() {
+                continue;
+            }
+            let (kind, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let trigger =
+                parse_trigger(rest).map_err(|err| anyhow!("line {}: {err}", index + 1))?;
+            match kind {
+                "start" if splitter.start.is_none() => splitter.start = Some(trigger),
+                "reset" if splitter.reset.is_none() => splitter.reset = Some(trigger),
+                "start" | "reset" => bail!("line {}: more than one {kind} line", index + 1),
+                "split" => splitter.splits.push(trigger),
+                other => bail!(
+                    "line {}: expected start, split or reset, found {other:?}",
+                    index + 1
+                ),
+            }
+        }
+        if splitter.splits.is_empty() {
+            bail!("the script has no split lines");
+        }
+        Ok(splitter)
+    }
+
+    pub fn split_count(&self) -> usize {
+        self.splits.len()
+    }
+
+    /// Whether a run is in progress: started and not past the last split.
+    pub fn running(&self) -> bool {
+        matches!(self.state, RunState::Running { .. })
+    }
+
+    /// Starts watching for the next split immediately, for runs started by
+    /// hand or with no `start` line.
+    pub fn start_manually(&mut self) {
+        self.state = RunState::Running { next_split: 0 };
+        self.rearm();
+    }
+
+    /// Back to waiting for `start`, e.g. after a manual reset.
+    pub fn reset_manually(&mut self) {
+        self.state = RunState::Waiting;
+        self.rearm();
+    }
+
+    /// Skips the split being watched, matching a manual split.
+    pub fn skip_split(&mut self) {
+        if let RunState::Running { next_split } = self.state {
+            self.state = if next_split + 1 >= self.splits.len() {
+                RunState::Finished
+            } else {
+                RunState::Running {
+                    next_split: next_split + 1,
+                }
+            };
+        }
+    }
+
+    /// Checks the script against RAM after a frame has run.
+    pub fn after_frame(&mut self, nes: &Nes) -> Option<SplitEvent> {
+        self.evaluate(|addr| nes.debug_peek_internal_ram(addr))
+    }
+
+    fn rearm(&mut self) {
+        self.history = RamHistory::default();
+    }
+
+    fn evaluate(&mut self, peek: impl Fn(u16) -> u8) -> Option<SplitEvent> {
+        let triggers = self
+            .start
+            .iter_mut()
+            .chain(self.reset.iter_mut())
+            .chain(self.splits.iter_mut());
+        let (now, before) = self.history.advance(peek, triggers)?;
+
+        let reset_fired = self
+            .reset
+            .as_mut()
+            .is_some_and(|trigger| trigger.rising(&now, &before));
+        let start_fired = self
+            .start
+            .as_mut()
+            .is_some_and(|trigger| trigger.rising(&now, &before));
+        let split_fired: Vec<bool> = self
+            .splits
+            .iter_mut()
+            .map(|trigger| trigger.rising(&now, &before))
+            .collect();
+
+        if reset_fired && self.state != RunState::Waiting {
+            self.state = RunState::Waiting;
+            return Some(SplitEvent::Reset);
+        }
+        match self.state {
+            RunState::Waiting if start_fired => {
+                self.state = RunState::Running { next_split: 0 };
+                Some(SplitEvent::Start)
+            }
+            RunState::Running { next_split } if split_fired[next_split] => {
+                self.skip_split();
+                Some(SplitEvent::Split(next_split))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Named conditions that fire independently of each other.
+#[derive(Debug, Clone)]
+pub struct RamTriggers {
+    named: Vec<(String, Trigger)>,
+    history: RamHistory,
+}
+
+impl RamTriggers {
+    /// One trigger per line, `name: conditions`; without a name the
+    /// conditions themselves are used.
+    pub fn parse(script: &str) -> Result<Self> {
+        let mut named = Vec::new();
+        for (index, line) in script.lines().enumerate() {
+            let line = strip_comment(line);
+            if line.is_empty() {
+                continue;
+            }
+            let (name, conditions) = match line.split_once(':') {
+                Some((name, conditions)) => (name.trim(), conditions),
+                None => (line, line),
+            };
+            let trigger =
+                parse_trigger(conditions).map_err(|err| anyhow!("line {}: {err}", index + 1))?;
+            named.push((name.to_string(), trigger));
+        }
+        if named.is_empty() {
+            bail!("the script has no triggers");
+        }
+        Ok(Self {
+            named,
+            history: RamHistory::default(),
+        })
+    }
+
+    /// Takes a fresh baseline on the next frame, e.g. after loading a state
+    /// so the jump itself doesn't count as a change.
+    pub fn rearm(&mut self) {
+        self.history = RamHistory::default();
+    }
+
+    /// Names of the triggers that fired on the frame that just ran.
+    pub fn after_frame(&mut self, nes: &Nes) -> Vec<&str> {
+        let fired = self.evaluate(|addr| nes.debug_peek_internal_ram(addr));
+        fired
+            .into_iter()
+            .map(|index| self.named[index].0.as_str())
+            .collect()
+    }
+
+    fn evaluate(&mut self, peek: impl Fn(u16) -> u8) -> Vec<usize> {
+        let triggers = self.named.iter_mut().map(|(_, trigger)| trigger);
+        let Some((now, before)) = self.history.advance(peek, triggers) else {
+            return Vec::new();
+        };
+        self.named
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, (_, trigger))| trigger.rising(&now, &before).then_some(index))
+            .collect()
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default().trim()
+}
+
+fn parse_trigger(text: &str) -> Result<Trigger> {
+    let all = text
+        .split("&&")
+        .map(parse_condition)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Trigger {
+        all,
+        was_true: false,
+    })
+}
+
+fn parse_condition(text: &str) -> Result<Condition> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let (addr, test) = match tokens.as_slice() {
+        [addr, watch] => {
+            let test = match *watch {
+                "changes" => Test::Changes,
+                "increases" => Test::Increases,
+                "decreases" => Test::Decreases,
+                other => bail!("unknown watch {other:?}"),
+            };
+            (addr, test)
+        }
+        [addr, op, value] => {
+            let value = u8::try_from(parse_number(value)?)
+                .map_err(|_| anyhow!("value {value} doesn't fit in a byte"))?;
+            let test = match *op {
+                "==" => Test::Eq(value),
+                "!=" => Test::Ne(value),
+                "<" => Test::Lt(value),
+                "<=" => Test::Le(value),
+                ">" => Test::Gt(value),
+                ">=" => Test::Ge(value),
+                other => bail!("unknown operator {other:?}"),
+            };
+            (addr, test)
+        }
+        _ => bail!("expected `$addr op value` or `$addr changes`, found {text:?}"),
+    };
+    let addr = parse_number(addr)?;
+    if addr as usize >= RAM_SIZE {
+        bail!("${addr:04X} is outside internal RAM ($0000-$07FF)");
+    }
+    Ok(Condition {
+        addr: addr as u16,
+        test,
+    })
+}
+
+fn parse_number(text: &str) -> Result<u32> {
+    let parsed = match text.strip_prefix('$') {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| anyhow!("invalid number {text:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(splitter: &mut AutoSplitter, ram: &[(u16, u8)]) -> Option<SplitEvent> {
+        splitter.evaluate(|addr| {
+            ram.iter()
+                .find(|(at, _)| *at == addr)
+                .map_or(0, |(_, value)| *value)
+        })
+    }
+
+    #[test]
+    fn script_starts_splits_in_order_and_resets() {
+        let mut splitter = AutoSplitter::parse(
+            "start $10 == 1 # title screen left\nsplit $20 changes\nsplit $20 >= 3 && $21 == 9\nreset $10 == 0",
+        )
+        .unwrap();
+        assert_eq!(splitter.split_count(), 2);
+
+        assert_eq!(run(&mut splitter, &[]), None, "baseline frame");
+        assert_eq!(run(&mut splitter, &[(0x10, 1)]), Some(SplitEvent::Start));
+        assert_eq!(run(&mut splitter, &[(0x10, 1)]), None);
+        // Only the first split is watched, even though the second holds too.
+        assert_eq!(
+            run(&mut splitter, &[(0x10, 1), (0x20, 3), (0x21, 9)]),
+            Some(SplitEvent::Split(0))
+        );
+        assert_eq!(run(&mut splitter, &[(0x10, 1), (0x20, 3), (0x21, 9)]), None);
+        assert_eq!(run(&mut splitter, &[(0x10, 1), (0x20, 4)]), None);
+        assert_eq!(
+            run(&mut splitter, &[(0x10, 1), (0x20, 4), (0x21, 9)]),
+            Some(SplitEvent::Split(1))
+        );
+        assert!(!splitter.running());
+        assert_eq!(run(&mut splitter, &[]), Some(SplitEvent::Reset));
+        assert_eq!(run(&mut splitter, &[(0x10, 1)]), Some(SplitEvent::Start));
+    }
+
+    #[test]
+    fn named_triggers_fire_independently() {
+        let mut triggers = RamTriggers::parse(
+            "room: $30 changes
+$31 >= 2",
+        )
+        .unwrap();
+        let ram = |room, hp| {
+            move |addr| match addr {
+                0x30 => room,
+                0x31 => hp,
+                _ => 0,
+            }
+        };
+        assert!(triggers.evaluate(ram(1, 5)).is_empty(), "baseline frame");
+        assert!(triggers.evaluate(ram(1, 5)).is_empty());
+        assert_eq!(triggers.evaluate(ram(2, 5)), vec![0]);
+        assert_eq!(triggers.evaluate(ram(2, 0)), Vec::<usize>::new());
+        assert_eq!(triggers.evaluate(ram(3, 2)), vec![0, 1]);
+        assert_eq!(triggers.named[1].0, "$31 >= 2");
+        triggers.rearm();
+        assert!(triggers.evaluate(ram(9, 0)).is_empty());
+    }
+
+    #[test]
+    fn script_errors_name_the_line() {
+        let err = AutoSplitter::parse("split $10 == 1\nsplit $0800 == 1").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+        assert!(AutoSplitter::parse("split $10 ~ 1").is_err());
+        assert!(AutoSplitter::parse("start $10 == 1").is_err());
+        assert!(AutoSplitter::parse("split $10 == 300").is_err());
+    }
+}
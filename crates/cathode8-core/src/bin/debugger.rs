@@ -1,5 +1,5 @@
 use anyhow::Result;
-use cathode8::nes::Nes;
+use cathode8_core::nes::Nes;
 use std::path::Path;
 
 fn main() -> Result<()> {
@@ -19,6 +19,7 @@ fn main() -> Result<()> {
         println!("  regs        - Show CPU registers");
         println!("  mem <addr>  - Show memory at address");
         println!("  ppu         - Show PPU state");
+        println!("  trace <file> - Log instructions in nestest.log format");
         println!("  quit        - Exit debugger");
         return Ok(());
     }
@@ -81,6 +82,8 @@ fn main() -> Result<()> {
                 println!("  ppu        - Show PPU state");
                 println!(" apu         - Show APU state");
                 println!("  mapper     - Show mapper state");
+                println!("  trace <file> - Log instructions in nestest.log format");
+                println!("  trace off  - Stop logging");
                 println!("  quit, q    - Exit debugger");
             }
             "step" | "s" => {
@@ -146,6 +149,18 @@ fn main() -> Result<()> {
             "mapper" => {
                 println!("Mapper: {}", nes.debug_mapper_state());
             }
+            "trace" => match parts.get(1) {
+                Some(&"off") => match nes.stop_cpu_trace() {
+                    Ok(Some(trace)) => println!("Traced {} instructions", trace.traced()),
+                    Ok(None) => println!("Not tracing"),
+                    Err(err) => println!("Trace failed: {err:#}"),
+                },
+                Some(path) => match nes.start_cpu_trace(0, Some(Path::new(path))) {
+                    Ok(()) => println!("Tracing to {path}"),
+                    Err(err) => println!("Trace failed: {err:#}"),
+                },
+                None => println!("Usage: trace <file> | trace off"),
+            },
             "quit" | "q" => {
                 println!("Goodbye!");
                 break;
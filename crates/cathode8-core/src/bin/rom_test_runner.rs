@@ -5,7 +5,8 @@ use std::time::Instant;
 use anyhow::{Context, Result};
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
-use cathode8::nes::Nes;
+use cathode8_core::nes::palette::reference_palette;
+use cathode8_core::nes::{Nes, Region};
 use quick_xml::Reader;
 use quick_xml::events::Event;
 use sha1::{Digest, Sha1};
@@ -52,6 +53,8 @@ struct RunHashes {
     vram_2083: u8,
     vram_2084: u8,
     vram_non_space_count: usize,
+    /// Result code and message from $6000, for ROMs that report there.
+    status_6000: Option<(u8, String)>,
 }
 
 #[derive(Debug, Clone)]
@@ -320,11 +323,33 @@ fn hash_frame_bgra(frame_rgba: &[u8]) -> String {
     BASE64_STANDARD.encode(digest)
 }
 
+/// Reads the status blargg's newer ROMs leave in cartridge RAM: $6001-$6003
+/// hold $DE $B0 $61 once the test has started, $6000 holds $80 while it
+/// runs and then the result code, 0 for a pass, and the message is a
+/// NUL-terminated string from $6004.
+fn read_status_6000(nes: &mut Nes) -> Option<(u8, String)> {
+    let signature = [0x6001, 0x6002, 0x6003].map(|addr| nes.debug_peek_cpu(addr));
+    if signature != [0xDE, 0xB0, 0x61] {
+        return None;
+    }
+    let text = (0x6004..0x7000)
+        .map(|addr| nes.debug_peek_cpu(addr))
+        .take_while(|&byte| byte != 0)
+        .map(char::from)
+        .collect();
+    Some((nes.debug_peek_cpu(0x6000), text))
+}
+
 fn run_single(test: &SuiteTest, cfg: &Config) -> Result<RunHashes> {
     let rom_path = cfg.rom_root.join(&test.filename);
     let mut nes = Nes::new();
     nes.load_rom_from_path(&rom_path)
         .with_context(|| format!("failed to load ROM {}", rom_path.display()))?;
+    // Expected screenshot hashes were captured with the fixed reference colors.
+    nes.set_rgb_palette(reference_palette())?;
+    if test.system.eq_ignore_ascii_case("pal") {
+        nes.set_region(Region::Pal);
+    }
 
     let total_frames = test
         .runframes
@@ -334,6 +359,7 @@ fn run_single(test: &SuiteTest, cfg: &Config) -> Result<RunHashes> {
         nes.run_frame();
     }
 
+    let status_6000 = read_status_6000(&mut nes);
     let frame = nes.frame_buffer();
     let (ppu_ctrl, ppu_mask, ppu_status) = nes.debug_ppu_regs();
     let (ppu_scanline, ppu_cycle) = nes.debug_ppu_scanline_cycle();
@@ -377,12 +403,14 @@ fn run_single(test: &SuiteTest, cfg: &Config) -> Result<RunHashes> {
         vram_2083: nes.debug_peek_vram(0x0083),
         vram_2084: nes.debug_peek_vram(0x0084),
         vram_non_space_count,
+        status_6000,
     })
 }
 
 fn suite_result_pass(test: &SuiteTest, hashes: &RunHashes) -> bool {
     // Blargg VBL/NMI timing ROMs expose result status in RAM ($00F8).
-    test.filename.starts_with("vbl_nmi_timing/") && hashes.ram_f8 == 0x01
+    (test.filename.starts_with("vbl_nmi_timing/") && hashes.ram_f8 == 0x01)
+        || matches!(hashes.status_6000, Some((0, _)))
 }
 
 fn main() -> Result<()> {
@@ -485,3 +513,38 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// blargg's pulse sweep tests from nes-test-roms, which isn't checked in.
+    const SWEEP_ROMS: [&str; 2] = ["apu_sweep/sweep_cutoff.nes", "apu_sweep/sweep_sub.nes"];
+
+    #[test]
+    fn blargg_sweep_roms_report_pass_at_6000() {
+        let cfg = Config {
+            rom_root: Path::new(env!("CARGO_MANIFEST_DIR")).join("../../external/nes-test-roms"),
+            ..Config::default()
+        };
+        for filename in SWEEP_ROMS {
+            let path = cfg.rom_root.join(filename);
+            if !path.exists() {
+                eprintln!("skipping {filename}: {} not found", path.display());
+                continue;
+            }
+            let test = SuiteTest {
+                filename: filename.to_string(),
+                system: "NTSC".to_string(),
+                runframes: 600,
+                tvsha1: String::new(),
+                recordedinput: String::new(),
+            };
+            let hashes = run_single(&test, &cfg).unwrap();
+            let (code, text) = hashes
+                .status_6000
+                .unwrap_or_else(|| panic!("{filename} left no status at $6000"));
+            assert_eq!(code, 0, "{filename} failed: {text}");
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! cc65 debug info (`ld65 --dbgfile game.dbg`): maps CPU addresses to the
+//! source lines that produced them and back, for source-level debugging of
+//! homebrew built with ca65/cc65.
+//!
+//! The file is a list of records, a kind and a tab then fields:
+//!
+//! ```text
+//! file id=0,name="main.s",size=1204,mtime=0x6512A0B1,mod=0
+//! line id=12,file=0,line=40,span=7
+//! seg id=1,name="CODE",start=0x00C000,size=0x0200,addrsize=absolute,type=ro,oname="game.nes",ooffs=16
+//! span id=7,seg=1,start=4,size=3
+//! ```
+//!
+//! A line covers one or more spans, and a span is a byte range of a
+//! segment. Banked games put several segments at the same CPU address, so
+//! [`DebugInfo::locate`] checks the bytes in the span against the ROM image
+//! to find which bank is mapped in.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::nes::Nes;
+
+/// iNES header size, for turning `ooffs` file offsets into PRG offsets.
+const INES_HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LineKind {
+    /// A C line (cc65 `type=1`), listed first when several lines share an
+    /// address.
+    External,
+    Assembler,
+    /// A line expanded from a macro.
+    Macro,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+    /// Index into [`DebugInfo::files`].
+    pub file: usize,
+    /// 1-based, as in the source file.
+    pub line: u32,
+    pub kind: LineKind,
+}
+
+/// One byte range a line assembled to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    addr: u16,
+    size: u16,
+    /// Offset of the first byte in PRG ROM, for ROM segments.
+    prg_offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    files: Vec<String>,
+    lines: Vec<SourceLine>,
+    /// `(line index, span)` pairs, sorted by address.
+    spans: Vec<(usize, Span)>,
+    /// `(file, line)` of every line that produced code.
+    code_lines: HashSet<(usize, u32)>,
+}
+
+impl DebugInfo {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut files = HashMap::new();
+        let mut segments = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut lines = Vec::new();
+        for (number, record) in text.lines().enumerate() {
+            let Some((kind, fields)) = record.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let fields = parse_fields(fields);
+            let number = number + 1;
+            let field = |key: &str| {
+                fields
+                    .get(key)
+                    .copied()
+                    .ok_or_else(|| anyhow!("line {number}: {kind} record without {key}"))
+            };
+            let int = |key: &str| -> Result<u64> {
+                parse_int(field(key)?).with_context(|| format!("line {number}: bad {key}"))
+            };
+            match kind {
+                "file" => {
+                    files.insert(int("id")?, field("name")?.to_string());
+                }
+                "seg" => {
+                    let ooffs = match fields.get("ooffs") {
+                        Some(value) => Some(parse_int(value)?),
+                        None => None,
+                    };
+                    segments.insert(int("id")?, (int("start")?, ooffs));
+                }
+                "span" => {
+                    spans.insert(int("id")?, (int("seg")?, int("start")?, int("size")?));
+                }
+                "line" => {
+                    // Lines without code (comments, labels alone) have no span.
+                    let Some(span_ids) = fields.get("span") else {
+                        continue;
+                    };
+                    let kind = match fields.get("type").copied() {
+                        Some("1") => LineKind::External,
+                        Some("2") => LineKind::Macro,
+                        _ => LineKind::Assembler,
+                    };
+                    let span_ids = span_ids
+                        .split('+')
+                        .map(parse_int)
+                        .collect::<Result<Vec<_>>>()?;
+                    lines.push((int("file")?, int("line")? as u32, kind, span_ids));
+                }
+                _ => {}
+            }
+        }
+
+        // Files are referred to by id; keep them in id order.
+        let mut file_ids: Vec<u64> = files.keys().copied().collect();
+        file_ids.sort_unstable();
+        let file_index: HashMap<u64, usize> = file_ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| (id, index))
+            .collect();
+        let mut info = Self {
+            files: file_ids.iter().map(|id| files[id].clone()).collect(),
+            ..Self::default()
+        };
+        for (file, line, kind, span_ids) in lines {
+            let Some(&file) = file_index.get(&file) else {
+                continue;
+            };
+            let index = info.lines.len();
+            info.lines.push(SourceLine { file, line, kind });
+            info.code_lines.insert((file, line));
+            for id in span_ids {
+                let Some(&(seg, start, size)) = spans.get(&id) else {
+                    continue;
+                };
+                let Some(&(seg_start, ooffs)) = segments.get(&seg) else {
+                    continue;
+                };
+                let prg_offset = ooffs
+                    .and_then(|ooffs| ooffs.checked_sub(INES_HEADER_LEN as u64))
+                    .map(|offset| (offset + start) as usize);
+                let span = Span {
+                    addr: (seg_start + start) as u16,
+                    size: size as u16,
+                    prg_offset,
+                };
+                info.spans.push((index, span));
+            }
+        }
+        info.spans.sort_by_key(|(_, span)| span.addr);
+        Ok(info)
+    }
+
+    /// Source file names as the assembler saw them, usually relative to
+    /// the directory the build ran in.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Lines whose code covers `addr` in the bank currently mapped in,
+    /// C lines first, then assembler lines, then macro expansions.
+    pub fn locate(&self, nes: &mut Nes, addr: u16) -> Vec<&SourceLine> {
+        let mut found: Vec<&SourceLine> = self
+            .spans
+            .iter()
+            .take_while(|(_, span)| span.addr <= addr)
+            .filter(|(_, span)| addr - span.addr < span.size && span_is_mapped(nes, span))
+            .map(|&(line, _)| &self.lines[line])
+            .collect();
+        found.sort_by_key(|line| (line.kind, line.file, line.line));
+        found.dedup();
+        found
+    }
+
+    /// First address of each span `line` of `file` assembled to, for
+    /// setting breakpoints. Banked code can give several.
+    pub fn addresses(&self, file: usize, line: u32) -> Vec<u16> {
+        let mut addrs: Vec<u16> = self
+            .spans
+            .iter()
+            .filter(|&&(index, _)| {
+                let source = &self.lines[index];
+                source.file == file && source.line == line
+            })
+            .map(|(_, span)| span.addr)
+            .collect();
+        addrs.sort_unstable();
+        addrs.dedup();
+        addrs
+    }
+
+    /// Whether any code came from `line` of `file`.
+    pub fn has_code(&self, file: usize, line: u32) -> bool {
+        self.code_lines.contains(&(file, line))
+    }
+}
+
+/// Whether the bytes at the span's CPU address are the ones it assembled,
+/// i.e. its bank is mapped in. Spans outside ROM can't be checked.
+fn span_is_mapped(nes: &mut Nes, span: &Span) -> bool {
+    let Some(offset) = span.prg_offset else {
+        return true;
+    };
+    let Some(expected) = nes
+        .mapper
+        .as_mut()
+        .and_then(|mapper| {
+            mapper
+                .prg_rom_mut()
+                .get(offset..offset + span.size as usize)
+        })
+        .map(<[u8]>::to_vec)
+    else {
+        return false;
+    };
+    expected
+        .iter()
+        .enumerate()
+        .all(|(index, &byte)| nes.debug_peek_cpu(span.addr.wrapping_add(index as u16)) == byte)
+}
+
+/// `key=value,key="quoted, value"` into a map, quotes stripped.
+fn parse_fields(text: &str) -> HashMap<&str, &str> {
+    let mut fields = HashMap::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, after) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+        } else {
+            after.split_once(',').unwrap_or((after, ""))
+        };
+        fields.insert(key.trim(), value);
+        rest = after.trim_start_matches(',');
+    }
+    fields
+}
+
+fn parse_int(text: &str) -> Result<u64> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| anyhow!("bad number {text:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two 16 KiB banks both assembled for $C000; NROM-256 maps the second
+    /// one there.
+    const DBG: &str = "version\tmajor=2,minor=0\n\
+file\tid=0,name=\"src/main, v2.s\",size=100,mtime=0x1,mod=0\n\
+seg\tid=0,name=\"BANK0\",start=0x00C000,size=0x0003,addrsize=absolute,type=ro,oname=\"t.nes\",ooffs=16\n\
+seg\tid=1,name=\"BANK1\",start=0x00C000,size=0x0005,addrsize=absolute,type=ro,oname=\"t.nes\",ooffs=16400\n\
+span\tid=0,seg=1,start=0,size=2\n\
+span\tid=1,seg=1,start=2,size=3\n\
+span\tid=2,seg=0,start=0,size=3\n\
+line\tid=0,file=0,line=3\n\
+line\tid=1,file=0,line=4,span=0\n\
+line\tid=2,file=0,line=5,span=1\n\
+line\tid=3,file=0,line=9,type=2,span=1\n\
+line\tid=4,file=0,line=20,span=2\n";
+
+    #[test]
+    fn maps_addresses_to_lines_in_the_mapped_bank() {
+        let info = DebugInfo::parse(DBG).unwrap();
+        assert_eq!(info.files(), ["src/main, v2.s"]);
+        assert_eq!(info.addresses(0, 5), [0xC002]);
+        assert!(!info.has_code(0, 3));
+
+        let mut rom = vec![0u8; 16 + 0x8000 + 0x2000];
+        rom[..6].copy_from_slice(b"NES\x1A\x02\x01");
+        rom[16..19].copy_from_slice(&[0xA9, 0x01, 0xEA]);
+        rom[16 + 0x4000..16 + 0x4005].copy_from_slice(&[0xE6, 0x10, 0x4C, 0x00, 0xC0]);
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        let lines = |nes: &mut Nes, addr| -> Vec<u32> {
+            info.locate(nes, addr)
+                .iter()
+                .map(|line| line.line)
+                .collect()
+        };
+        assert_eq!(lines(&mut nes, 0xC003), [5, 9]);
+        assert_eq!(lines(&mut nes, 0xC000), [4]);
+    }
+}
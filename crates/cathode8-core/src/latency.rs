@@ -0,0 +1,165 @@
+//! Input latency harness: presses one button synthetically and counts the
+//! emulated frames until the game latches it and reads it back through
+//! $4016.
+//!
+//! The probe only covers the emulated side of the pipeline. Frontends add
+//! their own share (how long an input waits before it is handed to
+//! [`Nes::set_controller_state`]) on top of [`InputLatency`].
+
+use crate::nes::Nes;
+
+/// Frames to hold the button released first, so the press is a clean edge
+/// even if the player was holding it.
+const RELEASE_FRAMES: u32 = 2;
+/// Give up when a game doesn't read the button for this long, e.g. because
+/// it only polls controllers on some screens.
+const TIMEOUT_FRAMES: u32 = 120;
+
+/// Emulated part of the input latency, in frames counted from the frame the
+/// press was first fed to the console (0 = the game saw it that same frame).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLatency {
+    /// Frames until a $4016 strobe latched the pressed button.
+    pub latch_frames: u32,
+    /// Frames until the game shifted the button out of $4016.
+    pub read_frames: u32,
+    /// Extra frames from run-ahead. Always 0 until run-ahead exists; kept so
+    /// reports list every stage of the pipeline.
+    pub run_ahead_frames: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeStatus {
+    Running,
+    Done(InputLatency),
+    /// The game never read the button within the timeout.
+    TimedOut,
+}
+
+/// Drives one measurement. Feed every frame's input through
+/// [`InputLatencyProbe::input`] and call [`InputLatencyProbe::after_frame`]
+/// once the frame has run.
+#[derive(Debug, Clone)]
+pub struct InputLatencyProbe {
+    button: u8,
+    frame: u32,
+    latch_frames: Option<u32>,
+    status: ProbeStatus,
+}
+
+impl InputLatencyProbe {
+    pub fn new(button: u8) -> Self {
+        Self {
+            button,
+            frame: 0,
+            latch_frames: None,
+            status: ProbeStatus::Running,
+        }
+    }
+
+    pub fn status(&self) -> ProbeStatus {
+        self.status
+    }
+
+    /// Whether [`InputLatencyProbe::input`] holds the button down this frame.
+    pub fn pressing(&self) -> bool {
+        self.frame >= RELEASE_FRAMES
+    }
+
+    /// The controller state to use this frame: `live` with the probed button
+    /// first forced up, then held down.
+    pub fn input(&self, live: u8) -> u8 {
+        if self.pressing() {
+            live | self.button
+        } else {
+            live & !self.button
+        }
+    }
+
+    pub fn after_frame(&mut self, nes: &mut Nes) -> ProbeStatus {
+        let activity = nes.take_controller1_activity();
+        if self.status != ProbeStatus::Running {
+            return self.status;
+        }
+        let Some(pressed_frames) = self.frame.checked_sub(RELEASE_FRAMES) else {
+            self.frame += 1;
+            return self.status;
+        };
+
+        if self.latch_frames.is_none() && activity.latched & self.button != 0 {
+            self.latch_frames = Some(pressed_frames);
+        }
+        if let Some(latch_frames) = self.latch_frames
+            && activity.read & self.button != 0
+        {
+            self.status = ProbeStatus::Done(InputLatency {
+                latch_frames,
+                read_frames: pressed_frames,
+                run_ahead_frames: 0,
+            });
+        } else if pressed_frames >= TIMEOUT_FRAMES {
+            self.status = ProbeStatus::TimedOut;
+        }
+        self.frame += 1;
+        self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::{BUTTON_A, BUTTON_START};
+
+    /// NROM-128 image that polls controller 1 in its NMI handler and never
+    /// otherwise touches $4016.
+    fn polling_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[0..6].copy_from_slice(b"NES\x1A\x01\x01");
+        let program: &[u8] = &[
+            0xA9, 0x80, // LDA #$80
+            0x8D, 0x00, 0x20, // STA $2000 (enable NMI)
+            0x4C, 0x05, 0xC0, // loop: JMP loop
+            // NMI at $C008
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA2, 0x08, // LDX #$08
+            0xAD, 0x16, 0x40, // read: LDA $4016
+            0xCA, // DEX
+            0xD0, 0xFA, // BNE read
+            0x40, // RTI
+        ];
+        rom[16..16 + program.len()].copy_from_slice(program);
+        let vectors = 16 + 0x3FFA;
+        rom[vectors..vectors + 6].copy_from_slice(&[0x08, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+        rom
+    }
+
+    fn measure(nes: &mut Nes, button: u8) -> ProbeStatus {
+        let mut probe = InputLatencyProbe::new(button);
+        loop {
+            nes.set_controller_state(probe.input(0));
+            nes.run_frame();
+            match probe.after_frame(nes) {
+                ProbeStatus::Running => {}
+                status => return status,
+            }
+        }
+    }
+
+    #[test]
+    fn nmi_polling_game_sees_press_within_a_frame() {
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&polling_rom()).unwrap();
+        nes.run_frame();
+
+        for button in [BUTTON_A, BUTTON_START] {
+            let ProbeStatus::Done(latency) = measure(&mut nes, button) else {
+                panic!("button {button:02X} was never read");
+            };
+            assert!(latency.latch_frames <= 1, "{latency:?}");
+            assert_eq!(latency.read_frames, latency.latch_frames);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Cathode-8 emulation core: CPU, PPU, APU, mappers and cartridge loading,
+//! with no windowing or audio-device dependencies. Frontends drive [`Nes`]
+//! one frame at a time and consume its frame buffer and audio samples.
+//!
+//! ```
+//! use cathode8_core::{BUTTON_A, BUTTON_START, Nes};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! // Any iNES image works; this one is a blank NROM cartridge whose reset
+//! // vector points at an endless `JMP $C000`.
+//! let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+//! rom[..6].copy_from_slice(b"NES\x1A\x01\x01");
+//! rom[16..19].copy_from_slice(&[0x4C, 0x00, 0xC0]);
+//! rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+//!
+//! let mut nes = Nes::new();
+//! nes.set_audio_sample_rate(48_000);
+//! nes.load_rom_from_bytes(&rom)?;
+//!
+//! for frame in 0..60 {
+//!     // Buttons are a bitmask, sampled when the game strobes $4016.
+//!     let buttons = if frame % 2 == 0 { BUTTON_A | BUTTON_START } else { 0 };
+//!     nes.set_controller_state(buttons);
+//!     nes.run_frame();
+//!
+//!     // 256x240 RGBA, ready for a texture upload.
+//!     let frame_buffer = nes.frame_buffer();
+//!     assert_eq!(frame_buffer.len(), 256 * 240 * 4);
+//!     // Roughly 800 samples per frame at 48 kHz.
+//!     let _samples: Vec<f32> = nes.take_audio_samples();
+//! }
+//! assert_eq!(nes.debug_counters().frame_count, 60);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod autosplit;
+pub mod cc65_dbg;
+pub mod latency;
+pub mod nes;
+pub mod probe;
+pub mod session;
+
+pub use nes::{
+    BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START,
+    BUTTON_UP, Nes, Region,
+};
@@ -1,15 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 
-const CPU_CLOCK_HZ: f64 = 1_789_772.727_272_727_3;
+use super::region::Region;
+
 const DEFAULT_SAMPLE_RATE: u32 = 48_000;
 
-const LENGTH_TABLE: [u8; 32] = [
+pub(super) const LENGTH_TABLE: [u8; 32] = [
     10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
     192, 24, 72, 26, 16, 28, 32, 30,
 ];
 
-const DUTY_TABLE: [[u8; 8]; 4] = [
+pub(super) const DUTY_TABLE: [[u8; 8]; 4] = [
     [0, 1, 0, 0, 0, 0, 0, 0],
     [0, 1, 1, 0, 0, 0, 0, 0],
     [0, 1, 1, 1, 1, 0, 0, 0],
@@ -24,22 +25,158 @@ const TRI_TABLE: [u8; 32] = [
 const NOISE_PERIOD_TABLE: [u16; 16] = [
     4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
 ];
+const PAL_NOISE_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
 
 const DMC_RATE_TABLE: [u16; 16] = [
     428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
 ];
+const PAL_DMC_RATE_TABLE: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// Frame counter step positions, in CPU cycles after a $4017 write lands.
+struct FrameSequence {
+    q1: u32,
+    q2_h2: u32,
+    q3: u32,
+    /// 4-step mode raises IRQ on `irq`, `q4_h4_irq` and `reset4`.
+    irq: u32,
+    q4_h4_irq: u32,
+    reset4: u32,
+    q4_h4_5step: u32,
+    reset5: u32,
+}
+
+const NTSC_FRAME_SEQUENCE: FrameSequence = FrameSequence {
+    q1: 7_457,
+    q2_h2: 14_913,
+    q3: 22_371,
+    irq: 29_828,
+    q4_h4_irq: 29_829,
+    reset4: 29_830,
+    q4_h4_5step: 37_281,
+    reset5: 37_282,
+};
+
+const PAL_FRAME_SEQUENCE: FrameSequence = FrameSequence {
+    q1: 8_313,
+    q2_h2: 16_627,
+    q3: 24_939,
+    irq: 33_252,
+    q4_h4_irq: 33_253,
+    reset4: 33_254,
+    q4_h4_5step: 41_565,
+    reset5: 41_566,
+};
+
+/// A cartridge sound chip, for picking the mixer's filters. VRC6 and
+/// Namco 163 audio isn't emulated yet; they get entries with their own
+/// defaults once it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpansionChip {
+    Mmc5,
+    Sunsoft5b,
+    Vrc7,
+}
+
+impl ExpansionChip {
+    pub const ALL: [ExpansionChip; 3] = [
+        ExpansionChip::Mmc5,
+        ExpansionChip::Sunsoft5b,
+        ExpansionChip::Vrc7,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExpansionChip::Mmc5 => "MMC5 pulses and PCM",
+            ExpansionChip::Sunsoft5b => "Sunsoft 5B",
+            ExpansionChip::Vrc7 => "VRC7 FM",
+        }
+    }
+
+    /// What the chip's own board does to its output before it reaches the
+    /// console's mixer. The MMC5 sits right on the audio line; the 5B's
+    /// output goes through an amplifier that takes the edge off its square
+    /// waves, and the VRC7's through an RC stage that leaves its FM much
+    /// duller than the raw OPLL.
+    pub fn default_filter(self) -> ExpansionFilter {
+        match self {
+            ExpansionChip::Mmc5 => ExpansionFilter::default(),
+            ExpansionChip::Sunsoft5b => ExpansionFilter {
+                high_pass_hz: None,
+                low_pass_hz: Some(12_000.0),
+            },
+            ExpansionChip::Vrc7 => ExpansionFilter {
+                high_pass_hz: None,
+                low_pass_hz: Some(6_000.0),
+            },
+        }
+    }
+}
+
+/// One-pole filters on a sound chip's output, ahead of the console's own
+/// output filters. `None` leaves that side unfiltered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExpansionFilter {
+    pub high_pass_hz: Option<f32>,
+    pub low_pass_hz: Option<f32>,
+}
+
+/// The filter for every [`ExpansionChip`], indexed in [`ExpansionChip::ALL`]
+/// order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExpansionFilters([ExpansionFilter; ExpansionChip::ALL.len()]);
+
+impl Default for ExpansionFilters {
+    fn default() -> Self {
+        Self(ExpansionChip::ALL.map(ExpansionChip::default_filter))
+    }
+}
+
+impl ExpansionFilters {
+    pub fn get(&self, chip: ExpansionChip) -> ExpansionFilter {
+        self.0[chip as usize]
+    }
+
+    pub fn get_mut(&mut self, chip: ExpansionChip) -> &mut ExpansionFilter {
+        &mut self.0[chip as usize]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+struct ExpansionFilterState {
+    hp_a: Option<f32>,
+    hp_prev_in: f32,
+    hp_prev_out: f32,
+    lp_a: Option<f32>,
+    lp_prev_out: f32,
+}
 
-const FC_4STEP_Q1: u32 = 7_457;
-const FC_4STEP_Q2_H2: u32 = 14_913;
-const FC_4STEP_Q3: u32 = 22_371;
-const FC_4STEP_Q4_H4_IRQ: u32 = 29_829;
-const FC_4STEP_RESET: u32 = 29_830;
+impl ExpansionFilterState {
+    fn new(filter: ExpansionFilter, dt: f32) -> Self {
+        Self {
+            hp_a: filter.high_pass_hz.map(|hz| high_pass_alpha(hz, dt)),
+            lp_a: filter.low_pass_hz.map(|hz| low_pass_alpha(hz, dt)),
+            ..Self::default()
+        }
+    }
 
-const FC_5STEP_Q1: u32 = 7_457;
-const FC_5STEP_Q2_H2: u32 = 14_913;
-const FC_5STEP_Q3: u32 = 22_371;
-const FC_5STEP_Q4_H4: u32 = 37_281;
-const FC_5STEP_RESET: u32 = 37_282;
+    fn apply(&mut self, mut sample: f32) -> f32 {
+        if let Some(a) = self.hp_a {
+            let out = a * (self.hp_prev_out + sample - self.hp_prev_in);
+            self.hp_prev_in = sample;
+            self.hp_prev_out = out;
+            sample = out;
+        }
+        if let Some(a) = self.lp_a {
+            self.lp_prev_out += a * (sample - self.lp_prev_out);
+            sample = self.lp_prev_out;
+        }
+        sample
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Apu {
@@ -58,9 +195,12 @@ pub struct Apu {
     frame_counter_write_delay: u8,
 
     cpu_cycle: u64,
+    region: Region,
     sample_rate: u32,
     sample_phase: f64,
     samples: Vec<f32>,
+    samples_per_frame: Option<u32>,
+    frame_sample_start: usize,
 
     hp90_prev_in: f32,
     hp90_prev_out: f32,
@@ -71,6 +211,15 @@ pub struct Apu {
     lp14k_prev_out: f32,
     lp14k_a: f32,
     dmc_dma_request: Option<u16>,
+    /// Last value written to each of $4000-$4017, for starting a VGM log
+    /// mid-song.
+    registers: [u8; 0x18],
+    /// Cartridge audio, set by the console every cycle.
+    expansion_output: f32,
+    expansion_chip: Option<ExpansionChip>,
+    expansion_filters: ExpansionFilters,
+    /// Coefficients follow the settings; the history is in save states.
+    expansion_filter: ExpansionFilterState,
 }
 
 impl Apu {
@@ -95,9 +244,12 @@ impl Default for Apu {
             frame_counter_write_value: 0,
             frame_counter_write_delay: 0,
             cpu_cycle: 0,
+            region: Region::Ntsc,
             sample_rate: DEFAULT_SAMPLE_RATE,
             sample_phase: 0.0,
             samples: Vec::with_capacity(2048),
+            samples_per_frame: None,
+            frame_sample_start: 0,
             hp90_prev_in: 0.0,
             hp90_prev_out: 0.0,
             hp90_a: 0.0,
@@ -107,6 +259,11 @@ impl Default for Apu {
             lp14k_prev_out: 0.0,
             lp14k_a: 0.0,
             dmc_dma_request: None,
+            registers: [0; 0x18],
+            expansion_output: 0.0,
+            expansion_chip: None,
+            expansion_filters: ExpansionFilters::default(),
+            expansion_filter: ExpansionFilterState::default(),
         };
         apu.update_filter_coeffs();
         apu
@@ -114,12 +271,15 @@ impl Default for Apu {
 }
 
 impl Apu {
-    pub fn reset(&mut self) {
+    /// Power-on state: every register cleared and the frame counter started
+    /// as if $4017 were written with $00.
+    pub fn power_on(&mut self) {
         self.pulse1 = PulseChannel::new(true);
         self.pulse2 = PulseChannel::new(false);
         self.triangle = TriangleChannel::new();
         self.noise = NoiseChannel::new();
         self.dmc = DmcChannel::new();
+        self.set_region(self.region);
         self.frame_counter = 0;
         self.frame_mode_5_step = false;
         self.frame_irq_inhibit = false;
@@ -130,12 +290,38 @@ impl Apu {
         self.cpu_cycle = 0;
         self.sample_phase = 0.0;
         self.samples.clear();
+        self.frame_sample_start = 0;
         self.hp90_prev_in = 0.0;
         self.hp90_prev_out = 0.0;
         self.hp440_prev_in = 0.0;
         self.hp440_prev_out = 0.0;
         self.lp14k_prev_out = 0.0;
         self.dmc_dma_request = None;
+        self.registers = [0; 0x18];
+        self.write_frame_counter(0x00);
+    }
+
+    /// Sets the noise LFSR's power-on contents. Only the low 15 bits are
+    /// used, and zero (which would never clock out a bit) becomes 1.
+    pub fn seed_noise(&mut self, value: u16) {
+        self.noise.shift_register = (value & 0x7FFF).max(1);
+    }
+
+    /// Console reset. Unlike power-on, only $4015 is cleared, silencing every
+    /// channel; the other registers, the triangle phase and the low bit of
+    /// the DMC output level survive, and the frame counter restarts a few
+    /// cycles later as if the last $4017 value were written again.
+    pub fn reset(&mut self) {
+        self.write_status(0x00);
+        self.dmc.output_level &= 0x01;
+        self.dmc_dma_request = None;
+        self.frame_irq_flag = false;
+        let last_mode = if self.frame_counter_write_pending {
+            self.frame_counter_write_value
+        } else {
+            (u8::from(self.frame_mode_5_step) << 7) | (u8::from(self.frame_irq_inhibit) << 6)
+        };
+        self.write_frame_counter(last_mode);
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: u32) {
@@ -147,7 +333,53 @@ impl Apu {
         self.sample_rate
     }
 
+    /// With `Some(n)`, every emulated frame yields exactly `n` samples: the
+    /// samples taken during the frame are resampled to that count when the
+    /// frame ends. Frame lengths in CPU cycles vary (odd-frame skip, the
+    /// instruction that straddles vblank), so the free-running default
+    /// produces a frame-to-frame jitter that A/V dumps can't tolerate.
+    pub fn set_samples_per_frame(&mut self, samples_per_frame: Option<u32>) {
+        self.samples_per_frame = samples_per_frame.map(|n| n.max(1));
+        self.frame_sample_start = self.samples.len();
+    }
+
+    pub fn samples_per_frame(&self) -> Option<u32> {
+        self.samples_per_frame
+    }
+
+    /// Marks a frame boundary; see [`Apu::set_samples_per_frame`].
+    /// Closes the frame's audio, returning how many samples it produced.
+    pub fn end_frame(&mut self) -> usize {
+        let start = self.frame_sample_start.min(self.samples.len());
+        if let Some(target) = self.samples_per_frame {
+            let frame = self.samples.split_off(start);
+            resample_linear(&frame, target as usize, &mut self.samples);
+        }
+        self.frame_sample_start = self.samples.len();
+        self.samples.len() - start
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.noise.pal = region.uses_pal_apu();
+        self.dmc.pal = region.uses_pal_apu();
+    }
+
+    fn frame_sequence(&self) -> &'static FrameSequence {
+        if self.region.uses_pal_apu() {
+            &PAL_FRAME_SEQUENCE
+        } else {
+            &NTSC_FRAME_SEQUENCE
+        }
+    }
+
     pub fn write_register(&mut self, addr: u16, value: u8) {
+        if let Some(register) = self
+            .registers
+            .get_mut(usize::from(addr.wrapping_sub(0x4000)))
+        {
+            *register = value;
+        }
         match addr {
             0x4000 => self.pulse1.write_control(value),
             0x4001 => self.pulse1.write_sweep(value),
@@ -179,6 +411,21 @@ impl Apu {
     }
 
     pub fn read_status(&mut self) -> u8 {
+        let mut status = self.active_channels();
+        if self.frame_irq_flag {
+            status |= 0x40;
+        }
+        if self.dmc.irq_flag {
+            status |= 0x80;
+        }
+
+        self.frame_irq_flag = false;
+        status
+    }
+
+    /// The channel bits of $4015 as a read would return them: pulse 1,
+    /// pulse 2, triangle and noise with length left, and DMC playing.
+    pub(crate) fn active_channels(&self) -> u8 {
         let mut status = 0u8;
         if self.pulse1.length_counter > 0 {
             status |= 0x01;
@@ -195,17 +442,14 @@ impl Apu {
         if self.dmc.playback_active() {
             status |= 0x10;
         }
-        if self.frame_irq_flag {
-            status |= 0x40;
-        }
-        if self.dmc.irq_flag {
-            status |= 0x80;
-        }
-
-        self.frame_irq_flag = false;
         status
     }
 
+    /// The last value written to each register from $4000 to $4017.
+    pub(crate) fn last_register_writes(&self) -> &[u8; 0x18] {
+        &self.registers
+    }
+
     pub fn irq_pending(&self) -> bool {
         self.frame_irq_flag || self.dmc.irq_flag
     }
@@ -213,6 +457,7 @@ impl Apu {
     pub fn tick(&mut self) {
         self.cpu_cycle = self.cpu_cycle.wrapping_add(1);
 
+        let mut frame_counter_reset = false;
         if self.frame_counter_write_pending {
             if self.frame_counter_write_delay > 0 {
                 self.frame_counter_write_delay = self.frame_counter_write_delay.saturating_sub(1);
@@ -220,6 +465,7 @@ impl Apu {
             if self.frame_counter_write_delay == 0 {
                 self.apply_frame_counter_write(self.frame_counter_write_value);
                 self.frame_counter_write_pending = false;
+                frame_counter_reset = true;
             }
         }
 
@@ -235,18 +481,48 @@ impl Apu {
             self.dmc_dma_request = Some(self.dmc.current_dma_addr());
         }
 
-        self.clock_frame_counter();
+        // The cycle the delayed $4017 write lands on is step 0 of the new
+        // sequence, so the step table is measured from the following cycle.
+        if !frame_counter_reset {
+            self.clock_frame_counter();
+        }
 
+        let cpu_clock_hz = self.region.cpu_clock_hz();
         self.sample_phase += self.sample_rate as f64;
-        while self.sample_phase >= CPU_CLOCK_HZ {
-            self.sample_phase -= CPU_CLOCK_HZ;
-            let mixed = self.mix_sample();
+        while self.sample_phase >= cpu_clock_hz {
+            self.sample_phase -= cpu_clock_hz;
+            let expansion = self.expansion_filter.apply(self.expansion_output);
+            let mixed = self.mix_sample() + expansion;
             let filtered = self.apply_output_filters(mixed);
             self.samples.push(filtered);
         }
     }
 
+    /// The cartridge's audio level for this cycle, mixed in with the APU
+    /// channels. See `Mapper::audio_output`.
+    pub fn set_expansion_output(&mut self, level: f32) {
+        self.expansion_output = level;
+    }
+
+    /// The sound chip on the loaded cartridge, whose filter from
+    /// [`Apu::set_expansion_filters`] applies to the expansion output.
+    pub fn set_expansion_chip(&mut self, chip: Option<ExpansionChip>) {
+        self.expansion_chip = chip;
+        self.expansion_output = 0.0;
+        self.update_filter_coeffs();
+    }
+
+    pub fn set_expansion_filters(&mut self, filters: ExpansionFilters) {
+        self.expansion_filters = filters;
+        self.update_filter_coeffs();
+    }
+
+    pub fn expansion_filters(&self) -> ExpansionFilters {
+        self.expansion_filters
+    }
+
     pub fn take_samples(&mut self) -> Vec<f32> {
+        self.frame_sample_start = 0;
         std::mem::take(&mut self.samples)
     }
 
@@ -320,34 +596,36 @@ impl Apu {
 
     fn clock_frame_counter(&mut self) {
         self.frame_counter = self.frame_counter.wrapping_add(1);
+        let seq = self.frame_sequence();
 
         if self.frame_mode_5_step {
             match self.frame_counter {
-                FC_5STEP_Q1 | FC_5STEP_Q3 => self.clock_quarter_frame(),
-                FC_5STEP_Q2_H2 | FC_5STEP_Q4_H4 => {
+                step if step == seq.q1 || step == seq.q3 => self.clock_quarter_frame(),
+                step if step == seq.q2_h2 || step == seq.q4_h4_5step => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 }
-                FC_5STEP_RESET => {
+                step if step == seq.reset5 => {
                     self.frame_counter = 0;
                 }
                 _ => {}
             }
         } else {
             match self.frame_counter {
-                FC_4STEP_Q1 | FC_4STEP_Q3 => self.clock_quarter_frame(),
-                FC_4STEP_Q2_H2 => {
+                step if step == seq.q1 || step == seq.q3 => self.clock_quarter_frame(),
+                step if step == seq.q2_h2 => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 }
-                FC_4STEP_Q4_H4_IRQ => {
+                step if step == seq.irq && !self.frame_irq_inhibit => self.frame_irq_flag = true,
+                step if step == seq.q4_h4_irq => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                     if !self.frame_irq_inhibit {
                         self.frame_irq_flag = true;
                     }
                 }
-                FC_4STEP_RESET => {
+                step if step == seq.reset4 => {
                     if !self.frame_irq_inhibit {
                         self.frame_irq_flag = true;
                     }
@@ -401,6 +679,11 @@ impl Apu {
         self.hp90_a = high_pass_alpha(90.0, dt);
         self.hp440_a = high_pass_alpha(440.0, dt);
         self.lp14k_a = low_pass_alpha(14_000.0, dt);
+        let filter = self
+            .expansion_chip
+            .map(|chip| self.expansion_filters.get(chip))
+            .unwrap_or_default();
+        self.expansion_filter = ExpansionFilterState::new(filter, dt);
     }
 
     fn apply_output_filters(&mut self, mut sample: f32) -> f32 {
@@ -435,6 +718,24 @@ impl Apu {
         let dmc_dma = self.dmc_dma_request.unwrap_or(0);
         writer.write_all(&dmc_dma.to_le_bytes())?;
 
+        // Where the next output sample falls and the output filters'
+        // history, so the audio after a load matches the audio after the
+        // save sample for sample.
+        writer.write_all(&self.sample_phase.to_le_bytes())?;
+        for filter in [
+            self.hp90_prev_in,
+            self.hp90_prev_out,
+            self.hp440_prev_in,
+            self.hp440_prev_out,
+            self.lp14k_prev_out,
+            self.expansion_filter.hp_prev_in,
+            self.expansion_filter.hp_prev_out,
+            self.expansion_filter.lp_prev_out,
+        ] {
+            writer.write_all(&filter.to_le_bytes())?;
+        }
+        writer.write_all(&self.registers)?;
+
         self.pulse1.save_state(writer)?;
         self.pulse2.save_state(writer)?;
         self.triangle.save_state(writer)?;
@@ -470,6 +771,23 @@ impl Apu {
         let dmc_dma = u16::from_le_bytes(dma_buf);
         self.dmc_dma_request = if dmc_dma == 0 { None } else { Some(dmc_dma) };
 
+        reader.read_exact(&mut buf64)?;
+        self.sample_phase = f64::from_le_bytes(buf64);
+        for filter in [
+            &mut self.hp90_prev_in,
+            &mut self.hp90_prev_out,
+            &mut self.hp440_prev_in,
+            &mut self.hp440_prev_out,
+            &mut self.lp14k_prev_out,
+            &mut self.expansion_filter.hp_prev_in,
+            &mut self.expansion_filter.hp_prev_out,
+            &mut self.expansion_filter.lp_prev_out,
+        ] {
+            reader.read_exact(&mut buf32)?;
+            *filter = f32::from_le_bytes(buf32);
+        }
+        reader.read_exact(&mut self.registers)?;
+
         self.pulse1.load_state(reader)?;
         self.pulse2.load_state(reader)?;
         self.triangle.load_state(reader)?;
@@ -535,7 +853,7 @@ impl PulseChannel {
             envelope_divider: 0,
             envelope_decay: 0,
             sweep_enabled: false,
-            sweep_period: 1,
+            sweep_period: 0,
             sweep_negate: false,
             sweep_shift: 0,
             sweep_reload: false,
@@ -554,7 +872,8 @@ impl PulseChannel {
 
     fn write_sweep(&mut self, value: u8) {
         self.sweep_enabled = (value & 0x80) != 0;
-        self.sweep_period = ((value >> 4) & 0x07) + 1;
+        // Divider reload value P; the divider itself counts P+1 half frames.
+        self.sweep_period = (value >> 4) & 0x07;
         self.sweep_negate = (value & 0x08) != 0;
         self.sweep_shift = value & 0x07;
         self.sweep_reload = true;
@@ -629,14 +948,18 @@ impl PulseChannel {
     }
 
     fn apply_sweep(&mut self) {
-        if self.sweep_shift == 0 {
+        if self.sweep_shift == 0 || self.sweep_muting() {
             return;
         }
+        self.timer_period = self.sweep_target_period();
+    }
 
-        let target = self.sweep_target_period();
-        if target <= 0x07FF {
-            self.timer_period = target;
-        }
+    /// The sweep unit silences the channel whenever the current period is below 8
+    /// or the continuously computed target overflows 11 bits. This applies even
+    /// with the sweep disabled or a shift of 0 (where the target is 2x period).
+    /// Negated targets can never overflow, so they never mute.
+    fn sweep_muting(&self) -> bool {
+        self.timer_period < 8 || (!self.sweep_negate && self.sweep_target_period() > 0x07FF)
     }
 
     fn output(&self) -> u8 {
@@ -646,12 +969,7 @@ impl PulseChannel {
         if DUTY_TABLE[self.duty as usize][self.duty_step as usize] == 0 {
             return 0;
         }
-        if self.timer_period < 8 {
-            return 0;
-        }
-
-        let target = self.sweep_target_period();
-        if target > 0x07FF {
+        if self.sweep_muting() {
             return 0;
         }
 
@@ -663,34 +981,30 @@ impl PulseChannel {
     }
 
     fn sweep_target_period(&self) -> u16 {
-        if self.sweep_shift == 0 {
-            return self.timer_period;
-        }
         let change = self.timer_period >> self.sweep_shift;
         if self.sweep_negate {
+            // Pulse 1 negates with one's complement, pulse 2 with two's complement.
+            // A negative result behaves as 0 rather than wrapping into a mute.
             let extra = if self.channel1 { 1 } else { 0 };
-            self.timer_period.wrapping_sub(change + extra)
+            self.timer_period.saturating_sub(change + extra)
         } else {
-            self.timer_period.wrapping_add(change)
+            self.timer_period + change
         }
     }
 
     pub fn save_state(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
-        let flags: u8 = (self.enabled as u8)
-            | ((self.channel1 as u8) << 1)
-            | ((self.duty) << 2)
-            | ((self.duty_step) << 5)
-            | ((self.length_halt as u8) << 7);
-        writer.write_all(&[flags])?;
+        let flags: u8 =
+            (self.enabled as u8) | ((self.channel1 as u8) << 1) | ((self.length_halt as u8) << 2);
+        writer.write_all(&[flags, self.duty, self.duty_step])?;
 
         writer.write_all(&self.timer_period.to_le_bytes())?;
         writer.write_all(&self.timer_counter.to_le_bytes())?;
         writer.write_all(&[self.length_counter])?;
 
-        let env: u8 =
-            self.constant_volume as u8 | ((self.volume) << 1) | ((self.envelope_period) << 5);
-        writer.write_all(&[env])?;
         writer.write_all(&[
+            self.constant_volume as u8,
+            self.volume,
+            self.envelope_period,
             self.envelope_start as u8,
             self.envelope_divider,
             self.envelope_decay,
@@ -712,9 +1026,11 @@ impl PulseChannel {
         reader.read_exact(&mut flags)?;
         self.enabled = (flags[0] & 0x01) != 0;
         self.channel1 = (flags[0] & 0x02) != 0;
-        self.duty = (flags[0] >> 2) & 0x07;
-        self.duty_step = (flags[0] >> 5) & 0x07;
-        self.length_halt = (flags[0] & 0x80) != 0;
+        self.length_halt = (flags[0] & 0x04) != 0;
+        let mut duty_buf = [0u8; 2];
+        reader.read_exact(&mut duty_buf)?;
+        self.duty = duty_buf[0];
+        self.duty_step = duty_buf[1];
 
         let mut buf16 = [0u8; 2];
         reader.read_exact(&mut buf16)?;
@@ -726,17 +1042,14 @@ impl PulseChannel {
         reader.read_exact(&mut len_buf)?;
         self.length_counter = len_buf[0];
 
-        let mut env_buf = [0u8; 1];
+        let mut env_buf = [0u8; 6];
         reader.read_exact(&mut env_buf)?;
-        self.constant_volume = (env_buf[0] & 0x01) != 0;
-        self.volume = (env_buf[0] >> 1) & 0x1F;
-        self.envelope_period = (env_buf[0] >> 5) & 0x1F;
-
-        let mut env2_buf = [0u8; 3];
-        reader.read_exact(&mut env2_buf)?;
-        self.envelope_start = env2_buf[0] != 0;
-        self.envelope_divider = env2_buf[1];
-        self.envelope_decay = env2_buf[2];
+        self.constant_volume = env_buf[0] != 0;
+        self.volume = env_buf[1];
+        self.envelope_period = env_buf[2];
+        self.envelope_start = env_buf[3] != 0;
+        self.envelope_divider = env_buf[4];
+        self.envelope_decay = env_buf[5];
 
         let mut sweep_buf = [0u8; 2];
         reader.read_exact(&mut sweep_buf)?;
@@ -893,6 +1206,8 @@ struct NoiseChannel {
     envelope_decay: u8,
 
     mode: bool,
+    /// Use the 2A07 period table.
+    pal: bool,
     timer_period: u16,
     timer_counter: u16,
     shift_register: u16,
@@ -911,6 +1226,7 @@ impl NoiseChannel {
             envelope_divider: 0,
             envelope_decay: 0,
             mode: false,
+            pal: false,
             timer_period: NOISE_PERIOD_TABLE[0],
             timer_counter: 0,
             shift_register: 1,
@@ -928,7 +1244,12 @@ impl NoiseChannel {
 
     fn write_period(&mut self, value: u8) {
         self.mode = (value & 0x80) != 0;
-        self.timer_period = NOISE_PERIOD_TABLE[(value & 0x0F) as usize];
+        let table = if self.pal {
+            &PAL_NOISE_PERIOD_TABLE
+        } else {
+            &NOISE_PERIOD_TABLE
+        };
+        self.timer_period = table[(value & 0x0F) as usize];
     }
 
     fn write_length(&mut self, value: u8) {
@@ -1050,6 +1371,8 @@ struct DmcChannel {
     irq_flag: bool,
     loop_flag: bool,
     rate_index: u8,
+    /// Use the 2A07 rate table.
+    pal: bool,
     timer_period: u16,
     timer_counter: u16,
     output_level: u8,
@@ -1063,6 +1386,10 @@ struct DmcChannel {
     silence: bool,
     dma_pending: bool,
     dma_delay: u8,
+    /// Delta the output unit applied on the current CPU cycle. Only lives
+    /// until the next timer clock, so it is never part of a save state.
+    #[serde(skip)]
+    step_this_cycle: i8,
 }
 
 impl DmcChannel {
@@ -1073,6 +1400,7 @@ impl DmcChannel {
             irq_flag: false,
             loop_flag: false,
             rate_index: 0,
+            pal: false,
             timer_period: DMC_RATE_TABLE[0],
             timer_counter: DMC_RATE_TABLE[0],
             output_level: 0,
@@ -1086,6 +1414,7 @@ impl DmcChannel {
             silence: true,
             dma_pending: false,
             dma_delay: 0,
+            step_this_cycle: 0,
         }
     }
 
@@ -1096,14 +1425,34 @@ impl DmcChannel {
         }
         self.loop_flag = (value & 0x40) != 0;
         self.rate_index = value & 0x0F;
-        self.timer_period = DMC_RATE_TABLE[self.rate_index as usize];
+        let table = if self.pal {
+            &PAL_DMC_RATE_TABLE
+        } else {
+            &DMC_RATE_TABLE
+        };
+        self.timer_period = table[self.rate_index as usize];
         if self.timer_counter == 0 || self.timer_counter > self.timer_period {
             self.timer_counter = self.timer_period;
         }
     }
 
+    /// A $4011 write. The APU has already been clocked for the cycle the
+    /// CPU writes on, so the new level is heard from the next cycle on and
+    /// later output unit steps move up or down from it, which is what raw
+    /// PCM playback relies on. If the output unit stepped on this same
+    /// cycle, the step is applied on top of the loaded value rather than
+    /// being lost.
     fn write_output_level(&mut self, value: u8) {
         self.output_level = value & 0x7F;
+        self.apply_step(self.step_this_cycle);
+    }
+
+    fn apply_step(&mut self, step: i8) {
+        match step {
+            2 if self.output_level <= 125 => self.output_level += 2,
+            -2 if self.output_level >= 2 => self.output_level -= 2,
+            _ => {}
+        }
     }
 
     fn write_sample_addr(&mut self, value: u8) {
@@ -1165,13 +1514,12 @@ impl DmcChannel {
 
     fn clock_output_unit(&mut self) {
         if !self.silence {
-            if (self.shift_register & 0x01) != 0 {
-                if self.output_level <= 125 {
-                    self.output_level = self.output_level.saturating_add(2);
-                }
-            } else if self.output_level >= 2 {
-                self.output_level = self.output_level.saturating_sub(2);
-            }
+            self.step_this_cycle = if (self.shift_register & 0x01) != 0 {
+                2
+            } else {
+                -2
+            };
+            self.apply_step(self.step_this_cycle);
         }
 
         self.shift_register >>= 1;
@@ -1195,6 +1543,7 @@ impl DmcChannel {
     }
 
     fn clock_timer(&mut self) {
+        self.step_this_cycle = 0;
         if self.dma_pending && self.dma_delay > 0 {
             self.dma_delay = self.dma_delay.saturating_sub(1);
         }
@@ -1232,8 +1581,10 @@ impl DmcChannel {
         writer.write_all(&[self.sample_addr, self.sample_length])?;
         writer.write_all(&self.current_addr.to_le_bytes())?;
         writer.write_all(&self.bytes_remaining.to_le_bytes())?;
-        let sample_buffer = self.sample_buffer.unwrap_or(0);
-        writer.write_all(&[sample_buffer])?;
+        writer.write_all(&[
+            self.sample_buffer.is_some() as u8,
+            self.sample_buffer.unwrap_or(0),
+        ])?;
         writer.write_all(&[self.shift_register])?;
         writer.write_all(&[self.bits_remaining])?;
         writer.write_all(&[self.silence as u8])?;
@@ -1274,13 +1625,9 @@ impl DmcChannel {
         reader.read_exact(&mut buf16)?;
         self.bytes_remaining = u16::from_le_bytes(buf16);
 
-        let mut sample_buffer = [0u8; 1];
+        let mut sample_buffer = [0u8; 2];
         reader.read_exact(&mut sample_buffer)?;
-        self.sample_buffer = if sample_buffer[0] == 0 {
-            None
-        } else {
-            Some(sample_buffer[0])
-        };
+        self.sample_buffer = (sample_buffer[0] != 0).then_some(sample_buffer[1]);
 
         let mut shift_buf = [0u8; 1];
         reader.read_exact(&mut shift_buf)?;
@@ -1305,3 +1652,430 @@ impl DmcChannel {
         Ok(())
     }
 }
+
+/// Appends `count` samples spanning the same time as `input` to `output`.
+fn resample_linear(input: &[f32], count: usize, output: &mut Vec<f32>) {
+    let Some(&last) = input.last() else {
+        output.extend(std::iter::repeat_n(0.0, count));
+        return;
+    };
+    let step = input.len() as f64 / count as f64;
+    output.extend((0..count).map(|i| {
+        let pos = ((i as f64 + 0.5) * step - 0.5).max(0.0);
+        let index = pos as usize;
+        let frac = (pos - index as f64) as f32;
+        let a = input[index.min(input.len() - 1)];
+        let b = input.get(index + 1).copied().unwrap_or(last);
+        a + (b - a) * frac
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes $4017 and returns the number of ticks until the write lands.
+    fn write_frame_counter_and_settle(apu: &mut Apu, value: u8) -> u32 {
+        apu.write_register(0x4017, value);
+        let mut ticks = 0;
+        while apu.frame_counter_write_pending {
+            apu.tick();
+            ticks += 1;
+        }
+        ticks
+    }
+
+    fn tick_n(apu: &mut Apu, cycles: u32) {
+        for _ in 0..cycles {
+            apu.tick();
+        }
+    }
+
+    #[test]
+    fn frame_counter_write_delay_depends_on_cpu_parity() {
+        let mut apu = Apu::new();
+        assert_eq!(apu.cpu_cycle & 1, 0);
+        assert_eq!(write_frame_counter_and_settle(&mut apu, 0x00), 3);
+        assert_eq!(apu.frame_counter, 0);
+
+        apu.tick();
+        assert_eq!(apu.cpu_cycle & 1, 0);
+        apu.tick();
+        assert_eq!(write_frame_counter_and_settle(&mut apu, 0x00), 4);
+        assert_eq!(apu.frame_counter, 0);
+    }
+
+    #[test]
+    fn four_step_irq_flag_set_on_last_three_cycles() {
+        let mut apu = Apu::new();
+        write_frame_counter_and_settle(&mut apu, 0x00);
+
+        tick_n(&mut apu, NTSC_FRAME_SEQUENCE.irq - 1);
+        assert!(!apu.frame_irq_flag);
+        apu.tick();
+        assert!(apu.frame_irq_flag);
+
+        for _ in 0..2 {
+            apu.frame_irq_flag = false;
+            apu.tick();
+            assert!(apu.frame_irq_flag);
+        }
+
+        apu.frame_irq_flag = false;
+        apu.tick();
+        assert!(!apu.frame_irq_flag);
+        assert_eq!(apu.frame_counter, 1);
+    }
+
+    #[test]
+    fn four_step_half_frame_lands_on_grid_after_write() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4015, 0x01);
+        apu.write_register(0x4000, 0x00);
+        apu.write_register(0x4003, 0x00);
+        let loaded = apu.pulse1.length_counter;
+        write_frame_counter_and_settle(&mut apu, 0x00);
+
+        tick_n(&mut apu, NTSC_FRAME_SEQUENCE.q2_h2 - 1);
+        assert_eq!(apu.pulse1.length_counter, loaded);
+        apu.tick();
+        assert_eq!(apu.pulse1.length_counter, loaded - 1);
+    }
+
+    #[test]
+    fn five_step_write_clocks_half_frame_after_delay() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4015, 0x01);
+        apu.write_register(0x4000, 0x00);
+        apu.write_register(0x4003, 0x00);
+        let loaded = apu.pulse1.length_counter;
+
+        apu.write_register(0x4017, 0x80);
+        tick_n(&mut apu, 2);
+        assert_eq!(apu.pulse1.length_counter, loaded);
+        apu.tick();
+        assert!(!apu.frame_counter_write_pending);
+        assert_eq!(apu.pulse1.length_counter, loaded - 1);
+
+        tick_n(&mut apu, NTSC_FRAME_SEQUENCE.q2_h2);
+        assert_eq!(apu.pulse1.length_counter, loaded - 2);
+        tick_n(
+            &mut apu,
+            NTSC_FRAME_SEQUENCE.reset5 - NTSC_FRAME_SEQUENCE.q2_h2,
+        );
+        assert!(!apu.frame_irq_flag);
+    }
+
+    #[test]
+    fn pal_four_step_irq_uses_2a07_sequence() {
+        let mut apu = Apu::new();
+        apu.set_region(Region::Pal);
+        write_frame_counter_and_settle(&mut apu, 0x00);
+
+        tick_n(&mut apu, NTSC_FRAME_SEQUENCE.irq);
+        assert!(!apu.frame_irq_flag);
+        tick_n(
+            &mut apu,
+            PAL_FRAME_SEQUENCE.irq - NTSC_FRAME_SEQUENCE.irq - 1,
+        );
+        assert!(!apu.frame_irq_flag);
+        apu.tick();
+        assert!(apu.frame_irq_flag);
+    }
+
+    #[test]
+    fn region_selects_noise_and_dmc_tables() {
+        let mut apu = Apu::new();
+        apu.write_register(0x400E, 0x0F);
+        apu.write_register(0x4010, 0x0F);
+        assert_eq!(apu.noise.timer_period, 4068);
+        assert_eq!(apu.dmc.timer_period, 54);
+
+        apu.set_region(Region::Pal);
+        apu.write_register(0x400E, 0x0F);
+        apu.write_register(0x4010, 0x0F);
+        assert_eq!(apu.noise.timer_period, 3778);
+        assert_eq!(apu.dmc.timer_period, 50);
+
+        apu.set_region(Region::Dendy);
+        apu.write_register(0x400E, 0x0F);
+        assert_eq!(apu.noise.timer_period, 4068);
+    }
+
+    /// Clocks the noise shift register until it returns to where it
+    /// started; the timer reloads every clock with period 0.
+    fn noise_sequence_length(mode: bool, start: u16) -> usize {
+        let mut noise = NoiseChannel::new();
+        noise.mode = mode;
+        noise.timer_period = 0;
+        noise.shift_register = start;
+        (1..=0x8000)
+            .find(|_| {
+                noise.clock_timer();
+                noise.shift_register == start
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn noise_short_mode_repeats_every_93_steps() {
+        assert_eq!(noise_sequence_length(false, 1), 32767);
+        assert_eq!(noise_sequence_length(true, 1), 93);
+        // Mode 1 splits the other states into loops of 93 and one of 31,
+        // depending on where the register was when the mode was switched.
+        let lengths: std::collections::BTreeSet<usize> = (1..0x8000)
+            .step_by(97)
+            .map(|start| noise_sequence_length(true, start))
+            .collect();
+        assert_eq!(lengths, [31, 93].into());
+    }
+
+    #[test]
+    fn noise_short_mode_output_is_periodic() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4015, 0x08);
+        apu.write_register(0x400C, 0x3F);
+        apu.write_register(0x400E, 0x80);
+        apu.write_register(0x400F, 0x00);
+        let mut levels = Vec::new();
+        for _ in 0..93 * 2 {
+            levels.push(apu.noise.output());
+            // The shift register steps when the timer reloads.
+            while {
+                apu.noise.clock_timer();
+                apu.noise.timer_counter != apu.noise.timer_period
+            } {}
+        }
+        assert_eq!(levels[..93], levels[93..]);
+        assert!(levels.contains(&15) && levels.contains(&0));
+    }
+
+    #[test]
+    fn loaded_state_produces_identical_audio() {
+        let mut apu = Apu::new();
+        apu.set_expansion_chip(Some(ExpansionChip::Vrc7));
+        apu.set_expansion_output(0.2);
+        apu.write_register(0x4015, 0x1F);
+        apu.write_register(0x4000, 0xB5);
+        apu.write_register(0x4001, 0xA3);
+        apu.write_register(0x4002, 0x80);
+        apu.write_register(0x4003, 0x01);
+        apu.write_register(0x4008, 0x40);
+        apu.write_register(0x400A, 0x20);
+        apu.write_register(0x400B, 0x02);
+        apu.write_register(0x400C, 0x07);
+        apu.write_register(0x400E, 0x83);
+        apu.write_register(0x400F, 0x10);
+        apu.write_register(0x4011, 0x40);
+        tick_n(&mut apu, 12_345);
+        apu.take_samples();
+        // Odd volume, a late duty step with the length halted, and a DMC
+        // buffer holding zero.
+        apu.pulse1.volume = 5;
+        apu.pulse1.envelope_period = 5;
+        apu.pulse1.duty_step = 6;
+        apu.dmc.sample_buffer = Some(0);
+
+        let mut state = Vec::new();
+        apu.save_state(&mut state).unwrap();
+        let mut loaded = Apu::new();
+        loaded.set_expansion_chip(Some(ExpansionChip::Vrc7));
+        loaded.load_state(&mut state.as_slice()).unwrap();
+        let mut reloaded = Vec::new();
+        loaded.save_state(&mut reloaded).unwrap();
+        assert_eq!(state, reloaded);
+
+        // The chip's level drops, leaving its low-pass to settle.
+        apu.set_expansion_output(-0.1);
+        loaded.set_expansion_output(-0.1);
+        tick_n(&mut apu, 30_000);
+        tick_n(&mut loaded, 30_000);
+        assert_eq!(apu.take_samples(), loaded.take_samples());
+        assert_eq!(apu.noise.shift_register, loaded.noise.shift_register);
+    }
+
+    fn audible_pulse(channel1: bool, period: u16) -> PulseChannel {
+        let mut pulse = PulseChannel::new(channel1);
+        pulse.enabled = true;
+        pulse.length_counter = 10;
+        pulse.write_control(0xDF);
+        pulse.timer_period = period;
+        pulse.duty_step = 0;
+        pulse
+    }
+
+    #[test]
+    fn sweep_shift_zero_mutes_when_doubled_period_overflows() {
+        let mut pulse = audible_pulse(true, 0x03FF);
+        pulse.write_sweep(0x00);
+        assert_eq!(pulse.output(), 15);
+
+        pulse.timer_period = 0x0400;
+        assert_eq!(pulse.output(), 0);
+
+        // Enabling the sweep with shift 0 never changes the period.
+        pulse.write_sweep(0x80);
+        pulse.clock_length_and_sweep();
+        assert_eq!(pulse.timer_period, 0x0400);
+    }
+
+    #[test]
+    fn sweep_negate_with_shift_zero_does_not_mute() {
+        for channel1 in [true, false] {
+            let mut pulse = audible_pulse(channel1, 0x0100);
+            pulse.write_sweep(0x88);
+            assert_eq!(pulse.sweep_target_period(), 0);
+            assert_eq!(pulse.output(), 15);
+            pulse.clock_length_and_sweep();
+            assert_eq!(pulse.timer_period, 0x0100);
+        }
+    }
+
+    #[test]
+    fn sweep_negate_differs_between_pulse_channels() {
+        let mut pulse1 = audible_pulse(true, 0x0100);
+        let mut pulse2 = audible_pulse(false, 0x0100);
+        pulse1.write_sweep(0x89);
+        pulse2.write_sweep(0x89);
+        assert_eq!(pulse1.sweep_target_period(), 0x007F);
+        assert_eq!(pulse2.sweep_target_period(), 0x0080);
+    }
+
+    #[test]
+    fn sweep_low_period_mutes_and_blocks_updates() {
+        let mut pulse = audible_pulse(true, 7);
+        pulse.write_sweep(0x81);
+        assert_eq!(pulse.output(), 0);
+        pulse.clock_length_and_sweep();
+        assert_eq!(pulse.timer_period, 7);
+    }
+
+    #[test]
+    fn sweep_divider_updates_every_p_plus_one_half_frames() {
+        let mut pulse = audible_pulse(false, 0x0100);
+        // P=2, shift=4: a freshly written divider fires on the first half frame,
+        // then the period grows by period/16 every third half frame.
+        pulse.write_sweep(0xA4);
+        let mut periods = Vec::new();
+        for _ in 0..7 {
+            pulse.clock_length_and_sweep();
+            periods.push(pulse.timer_period);
+        }
+        assert_eq!(
+            periods,
+            vec![0x0110, 0x0110, 0x0110, 0x0121, 0x0121, 0x0121, 0x0133]
+        );
+    }
+
+    #[test]
+    fn frame_locked_sampling_emits_exact_count_per_frame() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(48_000);
+        apu.set_samples_per_frame(Some(800));
+        for cycles in [29_780, 29_781, 29_790, 12] {
+            tick_n(&mut apu, cycles);
+            apu.end_frame();
+            assert_eq!(apu.take_samples().len(), 800, "{cycles} cycles");
+        }
+
+        tick_n(&mut apu, 29_780);
+        apu.end_frame();
+        tick_n(&mut apu, 29_781);
+        apu.end_frame();
+        assert_eq!(apu.take_samples().len(), 1600);
+    }
+
+    #[test]
+    fn expansion_filter_follows_the_loaded_chip() {
+        // Peak-to-peak output for a 4.5 kHz square on the expansion input.
+        fn swing(chip: Option<ExpansionChip>, filters: ExpansionFilters) -> f32 {
+            let mut apu = Apu::new();
+            apu.set_expansion_filters(filters);
+            apu.set_expansion_chip(chip);
+            for cycle in 0..40_000u32 {
+                apu.set_expansion_output(if cycle / 200 % 2 == 0 { 0.0 } else { 0.2 });
+                apu.tick();
+            }
+            let samples = apu.take_samples().split_off(400);
+            let max = samples.iter().copied().fold(f32::MIN, f32::max);
+            let min = samples.iter().copied().fold(f32::MAX, f32::min);
+            max - min
+        }
+
+        let defaults = ExpansionFilters::default();
+        let unfiltered = swing(Some(ExpansionChip::Mmc5), defaults);
+        let vrc7 = swing(Some(ExpansionChip::Vrc7), defaults);
+        assert!(vrc7 < unfiltered * 0.9, "{vrc7} vs {unfiltered}");
+
+        let mut flat = defaults;
+        *flat.get_mut(ExpansionChip::Vrc7) = ExpansionFilter::default();
+        assert_eq!(swing(Some(ExpansionChip::Vrc7), flat), unfiltered);
+        assert_eq!(swing(None, defaults), unfiltered);
+    }
+
+    #[test]
+    fn reset_clears_4015_but_keeps_frame_counter_mode() {
+        let mut apu = Apu::new();
+        write_frame_counter_and_settle(&mut apu, 0xC0);
+        apu.write_register(0x4015, 0x0F);
+        apu.write_register(0x4000, 0x3F);
+        apu.write_register(0x4003, 0x08);
+        apu.write_register(0x4011, 0x45);
+        assert_eq!(apu.read_status() & 0x01, 0x01);
+
+        apu.reset();
+        assert_eq!(apu.read_status() & 0x1F, 0);
+        assert_eq!(apu.dmc.output_level, 0x01);
+        assert_eq!(apu.pulse1.volume, 0x0F);
+        while apu.frame_counter_write_pending {
+            apu.tick();
+        }
+        assert!(apu.frame_mode_5_step);
+        assert!(apu.frame_irq_inhibit);
+
+        apu.power_on();
+        while apu.frame_counter_write_pending {
+            apu.tick();
+        }
+        assert!(!apu.frame_mode_5_step);
+        assert!(!apu.frame_irq_inhibit);
+    }
+
+    /// Ticks until the DMC output unit steps, feeding every DMA fetch `byte`.
+    fn tick_to_dmc_step(apu: &mut Apu, byte: u8) {
+        for _ in 0..10_000 {
+            apu.tick();
+            if apu.take_dmc_dma_request().is_some() {
+                apu.complete_dmc_dma(byte);
+            }
+            if apu.dmc.step_this_cycle != 0 {
+                return;
+            }
+        }
+        panic!("DMC output unit never stepped");
+    }
+
+    #[test]
+    fn dmc_steps_from_level_written_while_playing() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4010, 0x4F);
+        apu.write_register(0x4011, 0x20);
+        apu.write_register(0x4015, 0x10);
+        tick_to_dmc_step(&mut apu, 0xFF);
+        assert_eq!(apu.dmc.output_level, 0x22);
+
+        // Written between steps: the next step counts up from the new level.
+        apu.tick();
+        apu.write_register(0x4011, 0x40);
+        assert_eq!(apu.dmc.output_level, 0x40);
+        tick_to_dmc_step(&mut apu, 0xFF);
+        assert_eq!(apu.dmc.output_level, 0x42);
+
+        // Written on the cycle the unit steps: the step is not lost.
+        tick_to_dmc_step(&mut apu, 0xFF);
+        apu.write_register(0x4011, 0x10);
+        assert_eq!(apu.dmc.output_level, 0x12);
+        apu.write_register(0x4011, 0x7F);
+        assert_eq!(apu.dmc.output_level, 0x7F);
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result, anyhow, bail};
 use std::{fs, path::Path};
 
+use super::Region;
 use super::mapper::Mirroring;
 
 #[derive(Debug, Clone)]
@@ -14,6 +15,9 @@ pub struct Cartridge {
     pub chr_data: Vec<u8>,
     pub chr_is_ram: bool,
     pub prg_ram_size: usize,
+    /// Timing the header says the game was made for. `None` when the header
+    /// doesn't say, or the game runs on any region.
+    pub region: Option<Region>,
 }
 
 impl Cartridge {
@@ -51,6 +55,22 @@ impl Cartridge {
             Mirroring::Horizontal
         };
 
+        // NES 2.0 has a dedicated CPU/PPU timing field. iNES 1.0 only has a
+        // PAL bit, and a clear bit is too often just an unset one to count as
+        // "NTSC".
+        let region = if is_nes2 {
+            match bytes[12] & 0x03 {
+                0 => Some(Region::Ntsc),
+                1 => Some(Region::Pal),
+                3 => Some(Region::Dendy),
+                _ => None,
+            }
+        } else if bytes[9] & 0x01 != 0 {
+            Some(Region::Pal)
+        } else {
+            None
+        };
+
         let trainer_present = (flags6 & 0x04) != 0;
         let has_battery_backed_ram = (flags6 & 0x02) != 0;
 
@@ -125,6 +145,72 @@ impl Cartridge {
             chr_data,
             chr_is_ram,
             prg_ram_size,
+            region,
         })
     }
 }
+
+/// Applies an IPS patch to a whole ROM file (iNES header included, as IPS
+/// offsets count from the start of the file). Records past the end grow the
+/// image; the optional truncation length after `EOF` is honored.
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    let mut patch = patch
+        .strip_prefix(b"PATCH")
+        .ok_or_else(|| anyhow!("not an IPS patch (missing PATCH header)"))?;
+    let truncated = || anyhow!("IPS patch is truncated");
+    let mut out = rom.to_vec();
+    loop {
+        let (offset, rest) = patch.split_first_chunk::<3>().ok_or_else(truncated)?;
+        patch = rest;
+        if offset == b"EOF" {
+            if let Some(length) = patch.first_chunk::<3>() {
+                let length = u32::from_be_bytes([0, length[0], length[1], length[2]]);
+                out.truncate(length as usize);
+            }
+            return Ok(out);
+        }
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let (size, rest) = patch.split_first_chunk::<2>().ok_or_else(truncated)?;
+        patch = rest;
+        let data = match u16::from_be_bytes(*size) {
+            0 => {
+                let (count, rest) = patch.split_first_chunk::<2>().ok_or_else(truncated)?;
+                let (&value, rest) = rest.split_first().ok_or_else(truncated)?;
+                patch = rest;
+                vec![value; u16::from_be_bytes(*count) as usize]
+            }
+            size => {
+                let size = size as usize;
+                if patch.len() < size {
+                    return Err(truncated());
+                }
+                let (data, rest) = patch.split_at(size);
+                patch = rest;
+                data.to_vec()
+            }
+        };
+        let end = offset + data.len();
+        if out.len() < end {
+            out.resize(end, 0);
+        }
+        out[offset..end].copy_from_slice(&data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ips_records_runs_and_growth() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0x7F]);
+        patch.extend_from_slice(b"EOF");
+        let patched = apply_ips(&[0; 4], &patch).unwrap();
+        assert_eq!(patched, [0, 0xAA, 0xBB, 0, 0, 0x7F, 0x7F, 0x7F]);
+
+        assert!(apply_ips(&[0; 4], b"PATCH\x00\x00").is_err());
+        assert!(apply_ips(&[0; 4], b"NOPE").is_err());
+    }
+}
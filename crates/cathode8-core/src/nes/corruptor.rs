@@ -0,0 +1,138 @@
+//! Corruptor: deliberate, reproducible damage to the loaded PRG or CHR copy
+//! for glitch hunting. Each pass picks `count` random offsets in a range
+//! from a seed and changes them; the returned undo record restores exactly
+//! those bytes. The ROM file on disk is never touched.
+
+use anyhow::{Result, anyhow, bail};
+
+use super::Nes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptRegion {
+    Prg,
+    Chr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptMode {
+    /// Flips one random bit.
+    BitFlip,
+    /// Replaces the byte with a random one.
+    Randomize,
+    /// Adds the amount, wrapping.
+    Increment(u8),
+    /// Copies the byte found `distance` bytes away (wrapping inside the
+    /// range), as it was before the pass.
+    Shift(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corruption {
+    pub region: CorruptRegion,
+    /// Byte range inside the region, end exclusive. Clamped to its size.
+    pub start: usize,
+    pub end: usize,
+    pub count: usize,
+    pub mode: CorruptMode,
+    pub seed: u64,
+}
+
+/// The bytes one pass changed, with their old values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptionUndo {
+    region: CorruptRegion,
+    original: Vec<(usize, u8)>,
+}
+
+impl CorruptionUndo {
+    pub fn changed_bytes(&self) -> usize {
+        self.original.len()
+    }
+}
+
+impl Nes {
+    pub fn corrupt(&mut self, corruption: &Corruption) -> Result<CorruptionUndo> {
+        let data = self.corrupt_target(corruption.region)?;
+        let end = corruption.end.min(data.len());
+        if corruption.start >= end {
+            bail!(
+                "empty range {:#X}..{:#X} ({} bytes available)",
+                corruption.start,
+                corruption.end,
+                data.len()
+            );
+        }
+        let range = corruption.start..end;
+        let before = data[range.clone()].to_vec();
+        let mut rng = Xorshift::new(corruption.seed);
+        let mut original = Vec::with_capacity(corruption.count);
+        for _ in 0..corruption.count {
+            let offset = range.start + rng.below(range.len());
+            let value = data[offset];
+            data[offset] = match corruption.mode {
+                CorruptMode::BitFlip => value ^ (1 << rng.below(8)),
+                CorruptMode::Randomize => rng.next() as u8,
+                CorruptMode::Increment(amount) => value.wrapping_add(amount),
+                CorruptMode::Shift(distance) => {
+                    let relative = (offset - range.start) as i64 + i64::from(distance);
+                    before[relative.rem_euclid(range.len() as i64) as usize]
+                }
+            };
+            original.push((offset, value));
+        }
+        Ok(CorruptionUndo {
+            region: corruption.region,
+            original,
+        })
+    }
+
+    /// Restores what `undo` recorded. Undo passes newest first when several
+    /// overlap.
+    pub fn undo_corruption(&mut self, undo: &CorruptionUndo) -> Result<()> {
+        let data = self.corrupt_target(undo.region)?;
+        // Reverse order so a byte hit twice ends on its first old value.
+        for &(offset, value) in undo.original.iter().rev() {
+            if let Some(byte) = data.get_mut(offset) {
+                *byte = value;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn corrupt_region_len(&mut self, region: CorruptRegion) -> usize {
+        self.corrupt_target(region).map_or(0, |data| data.len())
+    }
+
+    fn corrupt_target(&mut self, region: CorruptRegion) -> Result<&mut [u8]> {
+        let mapper = self
+            .mapper
+            .as_mut()
+            .ok_or_else(|| anyhow!("no cartridge loaded"))?;
+        Ok(match region {
+            CorruptRegion::Prg => mapper.prg_rom_mut(),
+            CorruptRegion::Chr => mapper.chr_mut(),
+        })
+    }
+}
+
+/// xorshift64*: plenty for picking offsets, and the same seed always gives
+/// the same pass.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift can't leave.
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
@@ -15,6 +15,7 @@ impl Nes {
         }
 
         self.cpu_step_in_progress = true;
+        self.instruction_pc = self.pc;
 
         if self.pending_nmi {
             self.pending_nmi = false;
@@ -32,83 +33,74 @@ impl Nes {
             return 7;
         }
 
+        if self.cpu_trace.is_some() {
+            self.trace_instruction();
+        }
         let opcode_pc = self.pc;
         let opcode = self.fetch_byte();
+        let cycles = DISPATCH[opcode as usize](self, opcode, opcode_pc);
 
-        match opcode {
-            0x8A => {
-                self.a = self.x;
-                self.update_zn(self.a);
-                self.total_cycles += 2;
-                self.cpu_step_in_progress = false;
-                return 2;
-            }
-            0x9A => {
-                self.sp = self.x;
-                self.total_cycles += 2;
-                self.cpu_step_in_progress = false;
-                return 2;
-            }
-            0xAA => {
-                self.x = self.a;
-                self.update_zn(self.x);
-                self.total_cycles += 2;
-                self.cpu_step_in_progress = false;
-                return 2;
-            }
-            0xBA => {
-                self.x = self.sp;
-                self.update_zn(self.x);
-                self.total_cycles += 2;
-                self.cpu_step_in_progress = false;
-                return 2;
-            }
-            0xCA => {
-                self.x = self.x.wrapping_sub(1);
-                self.update_zn(self.x);
-                self.total_cycles += 2;
-                self.cpu_step_in_progress = false;
-                return 2;
-            }
-            0xEA => {
-                self.total_cycles += 2;
-                self.cpu_step_in_progress = false;
-                return 2;
-            }
-            _ => {}
-        }
+        self.total_cycles += cycles as u64;
+        self.cpu_step_in_progress = false;
+        cycles
+    }
 
-        // Two-byte unofficial NOPs used by test ROMs for timing.
-        if matches!(opcode, 0x80 | 0x82 | 0x89 | 0xC2 | 0xE2) {
-            self.fetch_byte();
-            self.total_cycles += 2;
-            self.cpu_step_in_progress = false;
-            return 2;
-        }
+    fn op_txa(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        self.a = self.x;
+        self.update_zn(self.a);
+        2
+    }
 
-        if let Some(cycles) = self.exec_unofficial(opcode, opcode_pc) {
-            self.total_cycles += cycles as u64;
-            self.cpu_step_in_progress = false;
-            return cycles;
-        }
+    fn op_txs(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        self.sp = self.x;
+        2
+    }
 
-        let cc = opcode & 0x03;
-        let aaa = opcode >> 5;
-        let bbb = (opcode >> 2) & 0x07;
+    fn op_tax(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        self.x = self.a;
+        self.update_zn(self.x);
+        2
+    }
 
-        let cycles = match cc {
-            0x01 => self.exec_group1(opcode, aaa, bbb, opcode_pc),
-            0x02 => self.exec_group2(opcode, aaa, bbb, opcode_pc),
-            0x03 => {
-                self.note_unknown_opcode(opcode, opcode_pc);
-                2
-            }
-            _ => self.exec_group0(opcode, opcode_pc),
-        };
+    fn op_tsx(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        self.x = self.sp;
+        self.update_zn(self.x);
+        2
+    }
 
-        self.total_cycles += cycles as u64;
-        self.cpu_step_in_progress = false;
-        cycles
+    fn op_dex(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        self.x = self.x.wrapping_sub(1);
+        self.update_zn(self.x);
+        2
+    }
+
+    fn op_nop(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        2
+    }
+
+    /// Two-byte unofficial NOPs used by test ROMs for timing.
+    fn op_nop_imm(&mut self, _opcode: u8, _opcode_pc: u16) -> u32 {
+        self.fetch_byte();
+        2
+    }
+
+    fn op_unofficial(&mut self, opcode: u8, opcode_pc: u16) -> u32 {
+        self.exec_unofficial(opcode, opcode_pc).unwrap_or_else(|| {
+            self.note_unknown_opcode(opcode, opcode_pc);
+            2
+        })
+    }
+
+    fn op_group0(&mut self, opcode: u8, opcode_pc: u16) -> u32 {
+        self.exec_group0(opcode, opcode_pc)
+    }
+
+    fn op_group1(&mut self, opcode: u8, opcode_pc: u16) -> u32 {
+        self.exec_group1(opcode, opcode >> 5, (opcode >> 2) & 0x07, opcode_pc)
+    }
+
+    fn op_group2(&mut self, opcode: u8, opcode_pc: u16) -> u32 {
+        self.exec_group2(opcode, opcode >> 5, (opcode >> 2) & 0x07, opcode_pc)
     }
 
     fn exec_group1(&mut self, opcode: u8, aaa: u8, bbb: u8, opcode_pc: u16) -> u32 {
@@ -650,7 +642,7 @@ impl Nes {
                 self.push_u16(self.pc);
                 self.push((self.p | FLAG_BREAK) | FLAG_UNUSED);
                 self.set_flag(FLAG_INTERRUPT, true);
-                self.pc = self.read_u16(0xFFFE);
+                self.pc = self.read_vector(0xFFFE);
                 7
             }
             0x08 => {
@@ -1069,6 +1061,41 @@ impl Nes {
     }
 }
 
+/// Executes one opcode (already fetched) and returns its cycle count.
+type OpHandler = fn(&mut Nes, u8, u16) -> u32;
+
+/// Opcode -> handler, resolved at compile time so the hot path is a single
+/// indexed call instead of decoding the `aaa bbb cc` fields and probing the
+/// unofficial opcodes on every instruction.
+static DISPATCH: [OpHandler; 256] = build_dispatch();
+
+const fn build_dispatch() -> [OpHandler; 256] {
+    let mut table: [OpHandler; 256] = [Nes::op_group0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let opcode = index as u8;
+        table[index] = match opcode {
+            0x8A => Nes::op_txa,
+            0x9A => Nes::op_txs,
+            0xAA => Nes::op_tax,
+            0xBA => Nes::op_tsx,
+            0xCA => Nes::op_dex,
+            0xEA => Nes::op_nop,
+            0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => Nes::op_nop_imm,
+            // Unofficial stores that sit outside the cc = 3 column.
+            0x9C | 0x9E => Nes::op_unofficial,
+            _ => match opcode & 0x03 {
+                0x01 => Nes::op_group1,
+                0x02 => Nes::op_group2,
+                0x03 => Nes::op_unofficial,
+                _ => Nes::op_group0,
+            },
+        };
+        index += 1;
+    }
+    table
+}
+
 #[derive(Clone, Copy)]
 enum RmwOp {
     Asl,
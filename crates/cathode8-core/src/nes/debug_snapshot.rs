@@ -0,0 +1,140 @@
+use std::fmt;
+
+use super::Nes;
+use super::ppu::PpuDebugCounters;
+use super::{NesDebugCounters, Region};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub pc: u16,
+}
+
+/// Debug state of the whole machine captured in one call, so a frontend can
+/// take it once per frame instead of calling a dozen `debug_*` getters. Its
+/// [`Display`](fmt::Display) output is the plain-text report used for bug
+/// reports.
+#[derive(Debug, Clone)]
+pub struct DebugSnapshot {
+    pub region: Region,
+    pub cpu: CpuRegisters,
+    pub pending_nmi: bool,
+    pub pending_irq: bool,
+    pub dma_cycles: u32,
+    pub total_cycles: u64,
+    pub nmi_serviced: u64,
+    pub scanline: i16,
+    pub cycle: i16,
+    pub counters: NesDebugCounters,
+    pub ppu: PpuDebugCounters,
+    pub mapper: String,
+    /// Newest first.
+    pub recent_events: Vec<String>,
+}
+
+impl Nes {
+    /// Captures a [`DebugSnapshot`] including up to `recent_events` of the
+    /// newest debug events.
+    pub fn debug_snapshot(&self, recent_events: usize) -> DebugSnapshot {
+        let (scanline, cycle) = self.ppu.debug_scanline_cycle();
+        DebugSnapshot {
+            region: self.region,
+            cpu: CpuRegisters {
+                a: self.a,
+                x: self.x,
+                y: self.y,
+                p: self.p,
+                sp: self.sp,
+                pc: self.pc,
+            },
+            pending_nmi: self.pending_nmi,
+            pending_irq: self.pending_irq,
+            dma_cycles: self.dma_cycles,
+            total_cycles: self.total_cycles,
+            nmi_serviced: self.nmi_serviced_count,
+            scanline,
+            cycle,
+            counters: self.debug,
+            ppu: self.ppu.debug_counters(),
+            mapper: self.debug_mapper_state(),
+            recent_events: self.debug_recent_events(recent_events),
+        }
+    }
+}
+
+impl fmt::Display for DebugSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let CpuRegisters { a, x, y, p, sp, pc } = self.cpu;
+        let debug = &self.counters;
+        let ppu = &self.ppu;
+        writeln!(
+            f,
+            "CPU A={a:02X} X={x:02X} Y={y:02X} P={p:02X} SP={sp:02X} PC={pc:04X} | pending_nmi={} pending_irq={} dma_cycles={}",
+            self.pending_nmi, self.pending_irq, self.dma_cycles
+        )?;
+        writeln!(
+            f,
+            "Core region={} frames={} cpu_steps={} cycles={} reads={} writes={} dma_transfers={} nmi_serviced={} irq_serviced={}",
+            self.region.label(),
+            debug.frame_count,
+            debug.cpu_steps,
+            self.total_cycles,
+            debug.cpu_reads,
+            debug.cpu_writes,
+            debug.dma_transfers,
+            self.nmi_serviced,
+            debug.irq_serviced_count
+        )?;
+        writeln!(
+            f,
+            "Bus reads ram={} ppu={} apu/io={} cart={} | writes ram={} ppu={} apu/io={} cart={} | last read=${:04X} last write=${:04X}:${:02X}",
+            debug.cpu_reads_ram,
+            debug.cpu_reads_ppu_regs,
+            debug.cpu_reads_apu_io,
+            debug.cpu_reads_cart,
+            debug.cpu_writes_ram,
+            debug.cpu_writes_ppu_regs,
+            debug.cpu_writes_apu_io,
+            debug.cpu_writes_cart,
+            debug.last_cpu_read_addr,
+            debug.last_cpu_write_addr,
+            debug.last_cpu_write_value
+        )?;
+        writeln!(
+            f,
+            "PPU sl={} cy={} ticks={} vblank_entries={} nmi_edges={} nmi_fired={} sprite_overflow={} last_ovf=({}, {}) status_reads={} last_status_read=({}, {}) pattern_rw={}/{} nametable_rw={}/{} palette_rw={}/{} last_rw=${:04X}/${:04X}",
+            self.scanline,
+            self.cycle,
+            ppu.ticks,
+            ppu.vblank_entries,
+            ppu.nmi_edges,
+            ppu.nmi_fired,
+            ppu.sprite_overflow_events,
+            ppu.sprite_overflow_last_scanline,
+            ppu.sprite_overflow_last_cycle,
+            ppu.status_reads,
+            ppu.status_read_last_scanline,
+            ppu.status_read_last_cycle,
+            ppu.pattern_reads,
+            ppu.pattern_writes,
+            ppu.nametable_reads,
+            ppu.nametable_writes,
+            ppu.palette_reads,
+            ppu.palette_writes,
+            ppu.last_read_addr,
+            ppu.last_write_addr
+        )?;
+        write!(f, "Mapper detail: {}", self.mapper)?;
+        if !self.recent_events.is_empty() {
+            write!(f, "\nRecent events:")?;
+            for event in &self.recent_events {
+                write!(f, "\n  {event}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! 6502 disassembler for debugger views and trace logs, unofficial opcodes
+//! included. Output follows the usual assembler syntax (`LDA ($10),Y`,
+//! branch targets as absolute addresses).
+
+use super::Nes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Imp,
+    Acc,
+    Imm,
+    Zp,
+    Zpx,
+    Zpy,
+    Abs,
+    Abx,
+    Aby,
+    Ind,
+    Izx,
+    Izy,
+    Rel,
+}
+
+impl Mode {
+    /// Operand bytes after the opcode.
+    pub fn operand_len(self) -> usize {
+        match self {
+            Imp | Acc => 0,
+            Imm | Zp | Zpx | Zpy | Izx | Izy | Rel => 1,
+            Abs | Abx | Aby | Ind => 2,
+        }
+    }
+}
+
+use Mode::{Abs, Abx, Aby, Acc, Imm, Imp, Ind, Izx, Izy, Rel, Zp, Zpx, Zpy};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: u16,
+    /// Opcode and operand bytes, 1-3 of them.
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub mode: Mode,
+    /// Not one of the 151 documented opcodes.
+    pub unofficial: bool,
+}
+
+impl Instruction {
+    /// Decodes the instruction whose opcode is `bytes[0]`; missing operand
+    /// bytes read as 0.
+    pub fn decode(addr: u16, bytes: &[u8]) -> Self {
+        let opcode = bytes.first().copied().unwrap_or(0);
+        let mode = MODES[opcode as usize];
+        let mnemonic = MNEMONICS[opcode as usize];
+        let mut encoded = vec![0; 1 + mode.operand_len()];
+        for (slot, &byte) in encoded.iter_mut().zip(bytes) {
+            *slot = byte;
+        }
+        let unofficial = match mnemonic {
+            "NOP" => opcode != 0xEA,
+            "SBC" => opcode == 0xEB,
+            _ => UNOFFICIAL.contains(&mnemonic),
+        };
+        Self {
+            addr,
+            bytes: encoded,
+            mnemonic,
+            mode,
+            unofficial,
+        }
+    }
+
+    /// Where the next instruction in memory starts.
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// The operand as written in source: `#$10`, `$0300,X`, `($10),Y`...
+    pub fn operand(&self) -> String {
+        let byte = self.bytes.get(1).copied().unwrap_or(0);
+        let word = u16::from_le_bytes([byte, self.bytes.get(2).copied().unwrap_or(0)]);
+        match self.mode {
+            Imp => String::new(),
+            Acc => "A".to_string(),
+            Imm => format!("#${byte:02X}"),
+            Zp => format!("${byte:02X}"),
+            Zpx => format!("${byte:02X},X"),
+            Zpy => format!("${byte:02X},Y"),
+            Abs => format!("${word:04X}"),
+            Abx => format!("${word:04X},X"),
+            Aby => format!("${word:04X},Y"),
+            Ind => format!("(${word:04X})"),
+            Izx => format!("(${byte:02X},X)"),
+            Izy => format!("(${byte:02X}),Y"),
+            Rel => format!(
+                "${:04X}",
+                self.next_addr().wrapping_add_signed(i16::from(byte as i8))
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operand = self.operand();
+        if operand.is_empty() {
+            f.write_str(self.mnemonic)
+        } else {
+            write!(f, "{} {operand}", self.mnemonic)
+        }
+    }
+}
+
+impl Nes {
+    /// Decodes the instruction at `addr` through [`Nes::debug_peek_cpu`].
+    pub fn debug_disassemble(&mut self, addr: u16) -> Instruction {
+        let bytes: Vec<u8> = (0..3)
+            .map(|offset| self.debug_peek_cpu(addr.wrapping_add(offset)))
+            .collect();
+        Instruction::decode(addr, &bytes)
+    }
+}
+
+const UNOFFICIAL: [&str; 18] = [
+    "SLO", "RLA", "SRE", "RRA", "SAX", "LAX", "DCP", "ISC", "ANC", "ALR", "ARR", "XAA", "AXS",
+    "AHX", "SHY", "SHX", "TAS", "LAS",
+];
+
+const MNEMONICS: [&str; 256] = [
+    "BRK", "ORA", "KIL", "SLO", "NOP", "ORA", "ASL", "SLO", "PHP", "ORA", "ASL", "ANC", "NOP",
+    "ORA", "ASL", "SLO", "BPL", "ORA", "KIL", "SLO", "NOP", "ORA", "ASL", "SLO", "CLC", "ORA",
+    "NOP", "SLO", "NOP", "ORA", "ASL", "SLO", "JSR", "AND", "KIL", "RLA", "BIT", "AND", "ROL",
+    "RLA", "PLP", "AND", "ROL", "ANC", "BIT", "AND", "ROL", "RLA", "BMI", "AND", "KIL", "RLA",
+    "NOP", "AND", "ROL", "RLA", "SEC", "AND", "NOP", "RLA", "NOP", "AND", "ROL", "RLA", "RTI",
+    "EOR", "KIL", "SRE", "NOP", "EOR", "LSR", "SRE", "PHA", "EOR", "LSR", "ALR", "JMP", "EOR",
+    "LSR", "SRE", "BVC", "EOR", "KIL", "SRE", "NOP", "EOR", "LSR", "SRE", "CLI", "EOR", "NOP",
+    "SRE", "NOP", "EOR", "LSR", "SRE", "RTS", "ADC", "KIL", "RRA", "NOP", "ADC", "ROR", "RRA",
+    "PLA", "ADC", "ROR", "ARR", "JMP", "ADC", "ROR", "RRA", "BVS", "ADC", "KIL", "RRA", "NOP",
+    "ADC", "ROR", "RRA", "SEI", "ADC", "NOP", "RRA", "NOP", "ADC", "ROR", "RRA", "NOP", "STA",
+    "NOP", "SAX", "STY", "STA", "STX", "SAX", "DEY", "NOP", "TXA", "XAA", "STY", "STA", "STX",
+    "SAX", "BCC", "STA", "KIL", "AHX", "STY", "STA", "STX", "SAX", "TYA", "STA", "TXS", "TAS",
+    "SHY", "STA", "SHX", "AHX", "LDY", "LDA", "LDX", "LAX", "LDY", "LDA", "LDX", "LAX", "TAY",
+    "LDA", "TAX", "LAX", "LDY", "LDA", "LDX", "LAX", "BCS", "LDA", "KIL", "LAX", "LDY", "LDA",
+    "LDX", "LAX", "CLV", "LDA", "TSX", "LAS", "LDY", "LDA", "LDX", "LAX", "CPY", "CMP", "NOP",
+    "DCP", "CPY", "CMP", "DEC", "DCP", "INY", "CMP", "DEX", "AXS", "CPY", "CMP", "DEC", "DCP",
+    "BNE", "CMP", "KIL", "DCP", "NOP", "CMP", "DEC", "DCP", "CLD", "CMP", "NOP", "DCP", "NOP",
+    "CMP", "DEC", "DCP", "CPX", "SBC", "NOP", "ISC", "CPX", "SBC", "INC", "ISC", "INX", "SBC",
+    "NOP", "SBC", "CPX", "SBC", "INC", "ISC", "BEQ", "SBC", "KIL", "ISC", "NOP", "SBC", "INC",
+    "ISC", "SED", "SBC", "NOP", "ISC", "NOP", "SBC", "INC", "ISC",
+];
+const MODES: [Mode; 256] = [
+    Imp, Izx, Imp, Izx, Zp, Zp, Zp, Zp, Imp, Imm, Acc, Imm, Abs, Abs, Abs, Abs, Rel, Izy, Imp, Izy,
+    Zpx, Zpx, Zpx, Zpx, Imp, Aby, Imp, Aby, Abx, Abx, Abx, Abx, Abs, Izx, Imp, Izx, Zp, Zp, Zp, Zp,
+    Imp, Imm, Acc, Imm, Abs, Abs, Abs, Abs, Rel, Izy, Imp, Izy, Zpx, Zpx, Zpx, Zpx, Imp, Aby, Imp,
+    Aby, Abx, Abx, Abx, Abx, Imp, Izx, Imp, Izx, Zp, Zp, Zp, Zp, Imp, Imm, Acc, Imm, Abs, Abs, Abs,
+    Abs, Rel, Izy, Imp, Izy, Zpx, Zpx, Zpx, Zpx, Imp, Aby, Imp, Aby, Abx, Abx, Abx, Abx, Imp, Izx,
+    Imp, Izx, Zp, Zp, Zp, Zp, Imp, Imm, Acc, Imm, Ind, Abs, Abs, Abs, Rel, Izy, Imp, Izy, Zpx, Zpx,
+    Zpx, Zpx, Imp, Aby, Imp, Aby, Abx, Abx, Abx, Abx, Imm, Izx, Imm, Izx, Zp, Zp, Zp, Zp, Imp, Imm,
+    Imp, Imm, Abs, Abs, Abs, Abs, Rel, Izy, Imp, Izy, Zpx, Zpx, Zpy, Zpy, Imp, Aby, Imp, Aby, Abx,
+    Abx, Aby, Aby, Imm, Izx, Imm, Izx, Zp, Zp, Zp, Zp, Imp, Imm, Imp, Imm, Abs, Abs, Abs, Abs, Rel,
+    Izy, Imp, Izy, Zpx, Zpx, Zpy, Zpy, Imp, Aby, Imp, Aby, Abx, Abx, Aby, Aby, Imm, Izx, Imm, Izx,
+    Zp, Zp, Zp, Zp, Imp, Imm, Imp, Imm, Abs, Abs, Abs, Abs, Rel, Izy, Imp, Izy, Zpx, Zpx, Zpx, Zpx,
+    Imp, Aby, Imp, Aby, Abx, Abx, Abx, Abx, Imm, Izx, Imm, Izx, Zp, Zp, Zp, Zp, Imp, Imm, Imp, Imm,
+    Abs, Abs, Abs, Abs, Rel, Izy, Imp, Izy, Zpx, Zpx, Zpx, Zpx, Imp, Aby, Imp, Aby, Abx, Abx, Abx,
+    Abx,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_operands_and_flags_unofficial_opcodes() {
+        let text = |bytes: &[u8]| Instruction::decode(0xC000, bytes).to_string();
+        assert_eq!(text(&[0xB1, 0x10]), "LDA ($10),Y");
+        assert_eq!(text(&[0x6C, 0x34, 0x12]), "JMP ($1234)");
+        assert_eq!(text(&[0x0A]), "ASL A");
+        assert_eq!(text(&[0xD0, 0xFE]), "BNE $C000");
+        assert_eq!(text(&[0x10, 0x10]), "BPL $C012");
+        assert!(!Instruction::decode(0, &[0xEA]).unofficial);
+        assert!(Instruction::decode(0, &[0xA7, 0x00]).unofficial);
+        assert!(Instruction::decode(0, &[0xEB, 0x00]).unofficial);
+        assert_eq!(Instruction::decode(0xFFFF, &[0x8D]).next_addr(), 0x0002);
+    }
+}
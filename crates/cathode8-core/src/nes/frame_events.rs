@@ -0,0 +1,122 @@
+//! Frame-complete notifications.
+//!
+//! Consumers that live on other threads (encoders, netplay, a threaded
+//! frontend) subscribe with [`Nes::subscribe_frames`] and receive a
+//! [`FrameEvent`] over a channel each time a frame finishes, instead of
+//! polling the getters after every [`Nes::run_frame`].
+//!
+//! Each frame also lists its [`RasterEvent`]s: serviced NMIs and IRQs and the
+//! sprite 0 hit, with the PPU position they happened at, for overlays that
+//! make raster timing visible.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+
+use super::Nes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterEventKind {
+    Nmi,
+    Irq,
+    Sprite0Hit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterEvent {
+    pub kind: RasterEventKind,
+    pub scanline: i16,
+    pub cycle: i16,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameEvent {
+    /// Frames completed since power-on, counting this one.
+    pub frame_no: u64,
+    /// RGBA copy of the frame, shared by all subscribers. Frames run with
+    /// [`Nes::run_frame_skip_render`] carry the last frame that was drawn.
+    pub buffer_handle: Arc<[u8]>,
+    /// Audio samples the frame produced, waiting in
+    /// [`Nes::take_audio_samples`].
+    pub audio_len: usize,
+    /// The frame's interrupts and sprite 0 hit, in order.
+    pub raster_events: Vec<RasterEvent>,
+}
+
+impl Nes {
+    /// Returns a receiver that gets a [`FrameEvent`] for every frame
+    /// completed from now on. Dropping the receiver unsubscribes it.
+    ///
+    /// ```
+    /// # let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+    /// # rom[..6].copy_from_slice(b"NES\x1A\x01\x01");
+    /// # rom[16..19].copy_from_slice(&[0x4C, 0x00, 0xC0]);
+    /// # rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+    /// let mut nes = cathode8_core::Nes::new();
+    /// nes.load_rom_from_bytes(&rom).unwrap();
+    /// let frames = nes.subscribe_frames();
+    ///
+    /// let encoder = std::thread::spawn(move || {
+    ///     frames.iter().map(|event| event.buffer_handle.len()).sum::<usize>()
+    /// });
+    /// for _ in 0..3 {
+    ///     nes.run_frame();
+    /// }
+    /// drop(nes); // closes the channel
+    /// assert_eq!(encoder.join().unwrap(), 3 * 256 * 240 * 4);
+    /// ```
+    pub fn subscribe_frames(&mut self) -> Receiver<FrameEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.frame_subscribers.push(sender);
+        receiver
+    }
+
+    /// Raster events of the last completed frame. NMIs land on the frame
+    /// whose vblank they start, IRQs and sprite 0 hit where they occurred.
+    pub fn raster_events(&self) -> &[RasterEvent] {
+        &self.frame_raster_events
+    }
+
+    pub(crate) fn record_raster_event(&mut self, kind: RasterEventKind) {
+        let (scanline, cycle) = self.ppu.debug_scanline_cycle();
+        self.pending_raster_events.push(RasterEvent {
+            kind,
+            scanline,
+            cycle,
+        });
+    }
+
+    /// Closes the frame's event list; the sprite 0 hit comes from the PPU's
+    /// sprite capture so it doesn't need a hook in the pixel pipeline.
+    pub(super) fn end_raster_frame(&mut self) {
+        if let Some((scanline, cycle)) = self.ppu.sprite_capture().sprite0_hit {
+            let at = self
+                .pending_raster_events
+                .iter()
+                .position(|event| (event.scanline, event.cycle) > (scanline, cycle))
+                .unwrap_or(self.pending_raster_events.len());
+            self.pending_raster_events.insert(
+                at,
+                RasterEvent {
+                    kind: RasterEventKind::Sprite0Hit,
+                    scanline,
+                    cycle,
+                },
+            );
+        }
+        self.frame_raster_events = std::mem::take(&mut self.pending_raster_events);
+    }
+
+    pub(super) fn publish_frame(&mut self, audio_len: usize) {
+        if self.frame_subscribers.is_empty() {
+            return;
+        }
+        let event = FrameEvent {
+            frame_no: self.debug.frame_count,
+            buffer_handle: Arc::from(self.ppu.frame_buffer()),
+            audio_len,
+            raster_events: self.frame_raster_events.clone(),
+        };
+        self.frame_subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,341 @@
+//! Golden frame hashes for small homebrew-style test carts, one per common
+//! mapper. Each cart is assembled here rather than shipped as a binary, so the
+//! tests run offline and carry no licensing baggage: it uploads a palette,
+//! fills a nametable, exercises the mapper's bank switching (and, for MMC3,
+//! a mid-frame scanline IRQ) and then spins with rendering on.
+//!
+//! The generated carts are backed up by real homebrew ROMs listed in
+//! test-fixtures/roms/manifest.txt, which scripts/fetch-test-roms.sh
+//! downloads; ROMs that haven't been fetched are skipped.
+//!
+//! A changed hash means rendering output changed. If the change is an
+//! intended accuracy fix, check the new frame by eye (e.g. dump it with the
+//! PPU viewer) and update the constant.
+
+use std::fs;
+use std::path::Path;
+
+use super::Nes;
+
+const PRG_SIZE: usize = 0x8000;
+/// Program origin; the code is copied to the top 8 KB of every 16 KB bank so
+/// it is mapped at reset whatever the mapper's power-on banking.
+const ORIGIN: u16 = 0xE000;
+const PALETTE_ADDR: u16 = 0xF200;
+const IRQ_ADDR: u16 = 0xF300;
+const NMI_ADDR: u16 = 0xF380;
+const HASHED_FRAME: u64 = 10;
+
+const PALETTE: [u8; 32] = [
+    0x0F, 0x01, 0x11, 0x21, 0x0F, 0x06, 0x16, 0x26, 0x0F, 0x09, 0x19, 0x29, 0x0F, 0x04, 0x14, 0x24,
+    0x0F, 0x02, 0x12, 0x22, 0x0F, 0x07, 0x17, 0x27, 0x0F, 0x0A, 0x1A, 0x2A, 0x0F, 0x05, 0x15, 0x25,
+];
+
+/// Minimal 6502 assembler: absolute/immediate emitters and backward branches.
+struct Asm {
+    code: Vec<u8>,
+}
+
+impl Asm {
+    fn new() -> Self {
+        Self { code: Vec::new() }
+    }
+
+    fn pc(&self) -> u16 {
+        ORIGIN + self.code.len() as u16
+    }
+
+    fn op(&mut self, bytes: &[u8]) -> &mut Self {
+        self.code.extend_from_slice(bytes);
+        self
+    }
+
+    fn abs(&mut self, opcode: u8, addr: u16) -> &mut Self {
+        let [lo, hi] = addr.to_le_bytes();
+        self.op(&[opcode, lo, hi])
+    }
+
+    /// `LDA #value` / `STA addr`.
+    fn store(&mut self, addr: u16, value: u8) -> &mut Self {
+        self.op(&[0xA9, value]).abs(0x8D, addr)
+    }
+
+    fn branch_to(&mut self, opcode: u8, target: u16) -> &mut Self {
+        let offset = target as i32 - (self.pc() as i32 + 2);
+        self.op(&[opcode, offset as i8 as u8])
+    }
+
+    /// Five serial writes of `value` to an MMC1 register.
+    fn mmc1_write(&mut self, register: u16, value: u8) -> &mut Self {
+        for bit in 0..5 {
+            self.store(register, (value >> bit) & 1);
+        }
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Board {
+    Nrom,
+    Mmc1,
+    Uxrom,
+    Cnrom,
+    Mmc3,
+    Axrom,
+}
+
+impl Board {
+    fn mapper(self) -> u8 {
+        match self {
+            Board::Nrom => 0,
+            Board::Mmc1 => 1,
+            Board::Uxrom => 2,
+            Board::Cnrom => 3,
+            Board::Mmc3 => 4,
+            Board::Axrom => 7,
+        }
+    }
+
+    /// 8 KB CHR-ROM banks, or 0 for CHR-RAM.
+    fn chr_banks(self) -> u8 {
+        match self {
+            Board::Nrom => 1,
+            Board::Mmc1 | Board::Cnrom => 4,
+            Board::Mmc3 => 8,
+            Board::Uxrom | Board::Axrom => 0,
+        }
+    }
+
+    fn setup(self, asm: &mut Asm) {
+        match self {
+            Board::Nrom => {}
+            Board::Mmc1 => {
+                // Reset the shifter, then vertical mirroring, PRG mode 3 and
+                // 4 KB CHR mode; show CHR bank 3 at $0000.
+                asm.store(0x8000, 0x80);
+                asm.mmc1_write(0x8000, 0x1E);
+                asm.mmc1_write(0xA000, 3);
+            }
+            Board::Uxrom => {
+                asm.store(0x8000, 1);
+            }
+            Board::Cnrom => {
+                asm.store(0x8000, 2);
+            }
+            Board::Mmc3 => {
+                // 2 KB CHR banks 10 and 12 for the background table; each
+                // frame's NMI re-arms an IRQ 100 scanlines down that swaps
+                // bank 0 in for the rest of the frame.
+                asm.store(0x8000, 0).store(0x8001, 10);
+                asm.store(0x8000, 1).store(0x8001, 12);
+                asm.store(0xA000, 0);
+                asm.store(0xC000, 99).store(0xC001, 0).store(0xE001, 0);
+                asm.op(&[0x58]); // CLI
+            }
+            Board::Axrom => {
+                // Single-screen upper nametable.
+                asm.store(0x8000, 0x10);
+            }
+        }
+    }
+
+    fn image(self) -> Vec<u8> {
+        let mut asm = Asm::new();
+        asm.op(&[0x78, 0xD8, 0xA2, 0xFF, 0x9A]); // SEI; CLD; LDX #$FF; TXS
+        asm.store(0x4017, 0x40); // no APU frame IRQ
+        asm.store(0x2000, 0).store(0x2001, 0);
+        for _ in 0..2 {
+            let wait = asm.pc();
+            asm.abs(0x2C, 0x2002).branch_to(0x10, wait); // BIT $2002; BPL
+        }
+        self.setup(&mut asm);
+
+        if self.chr_banks() == 0 {
+            // Fill CHR-RAM with byte = offset ^ page.
+            asm.store(0x2006, 0x00).store(0x2006, 0x00);
+            asm.op(&[0xA9, 0x00, 0x85, 0x00]); // LDA #0; STA $00
+            let outer = asm.pc();
+            asm.op(&[0xA0, 0x00]); // LDY #0
+            let inner = asm.pc();
+            asm.op(&[0x98, 0x45, 0x00]).abs(0x8D, 0x2007); // TYA; EOR $00; STA $2007
+            asm.op(&[0xC8]).branch_to(0xD0, inner); // INY; BNE
+            asm.op(&[0xE6, 0x00, 0xA5, 0x00, 0xC9, 0x20]); // INC $00; LDA $00; CMP #$20
+            asm.branch_to(0xD0, outer);
+        }
+
+        asm.store(0x2006, 0x3F).store(0x2006, 0x00);
+        asm.op(&[0xA2, 0x00]); // LDX #0
+        let palette = asm.pc();
+        asm.abs(0xBD, PALETTE_ADDR).abs(0x8D, 0x2007); // LDA pal,X; STA $2007
+        asm.op(&[0xE8, 0xE0, 0x20]).branch_to(0xD0, palette); // INX; CPX #32; BNE
+
+        // Four pages of nametable and attributes, byte = its low address.
+        asm.store(0x2006, 0x20).store(0x2006, 0x00);
+        asm.op(&[0xA0, 0x04, 0xA2, 0x00]); // LDY #4; LDX #0
+        let nametable = asm.pc();
+        asm.op(&[0x8A]).abs(0x8D, 0x2007); // TXA; STA $2007
+        asm.op(&[0xE8]).branch_to(0xD0, nametable); // INX; BNE
+        asm.op(&[0x88]).branch_to(0xD0, nametable); // DEY; BNE
+
+        // Scrolled halfway so the second nametable, and with it the
+        // board's mirroring, is on screen too.
+        asm.store(0x2005, 128).store(0x2005, 0);
+        // NMI on, sprites from $1000 so MMC3 sees one A12 rise per scanline.
+        asm.store(0x2000, 0x88).store(0x2001, 0x1E);
+        let spin = asm.pc();
+        asm.abs(0x4C, spin);
+
+        let mut irq = Asm::new();
+        if let Board::Mmc3 = self {
+            irq.op(&[0x48]); // PHA
+            irq.store(0x8000, 0).store(0x8001, 0).store(0xE000, 0);
+            irq.op(&[0x68]); // PLA
+        }
+        irq.op(&[0x40]); // RTI
+
+        let mut nmi = Asm::new();
+        if let Board::Mmc3 = self {
+            nmi.op(&[0x48]); // PHA
+            nmi.store(0x8000, 0).store(0x8001, 10);
+            nmi.store(0xC001, 0).store(0xE001, 0);
+            nmi.op(&[0x68]); // PLA
+        }
+        nmi.op(&[0x40]); // RTI
+
+        let mut bank = vec![0xFF; 0x4000];
+        let code_at = |addr: u16| (addr - 0xC000) as usize;
+        bank[code_at(ORIGIN)..][..asm.code.len()].copy_from_slice(&asm.code);
+        bank[code_at(PALETTE_ADDR)..][..PALETTE.len()].copy_from_slice(&PALETTE);
+        bank[code_at(IRQ_ADDR)..][..irq.code.len()].copy_from_slice(&irq.code);
+        bank[code_at(NMI_ADDR)..][..nmi.code.len()].copy_from_slice(&nmi.code);
+        let [nmi_lo, nmi_hi] = NMI_ADDR.to_le_bytes();
+        let [irq_lo, irq_hi] = IRQ_ADDR.to_le_bytes();
+        let [reset_lo, reset_hi] = ORIGIN.to_le_bytes();
+        bank[0x3FFA..].copy_from_slice(&[nmi_lo, nmi_hi, reset_lo, reset_hi, irq_lo, irq_hi]);
+
+        let chr_len = self.chr_banks() as usize * 0x2000;
+        let mut rom = Vec::with_capacity(16 + PRG_SIZE + chr_len);
+        let mapper = self.mapper();
+        rom.extend_from_slice(&[
+            b'N',
+            b'E',
+            b'S',
+            0x1A,
+            (PRG_SIZE / 0x4000) as u8,
+            self.chr_banks(),
+            (mapper << 4) | 0x01,
+            mapper & 0xF0,
+        ]);
+        rom.resize(16, 0);
+        for _ in 0..PRG_SIZE / 0x4000 {
+            rom.extend_from_slice(&bank);
+        }
+        rom.extend((0..chr_len).map(|i| {
+            let tile_row = (i & 0x0F) as u8;
+            let tile = (i >> 4) as u8;
+            let bank = (i >> 11) as u8;
+            tile.wrapping_mul(7) ^ tile_row.wrapping_mul(0x25) ^ bank.wrapping_mul(0x3B)
+        }));
+        rom
+    }
+}
+
+/// FNV-1a over the RGBA frame.
+fn frame_hash(frame: &[u8]) -> u64 {
+    frame.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+fn render(board: Board) -> (u64, usize) {
+    let mut nes = Nes::new();
+    nes.load_rom_from_bytes(&board.image()).unwrap();
+    while nes.debug_counters().frame_count < HASHED_FRAME {
+        nes.run_frame();
+    }
+    let frame = nes.frame_buffer();
+    let mut colors: Vec<&[u8]> = frame.chunks_exact(4).collect();
+    colors.sort_unstable();
+    colors.dedup();
+    (frame_hash(frame), colors.len())
+}
+
+fn assert_golden(board: Board, expected: u64) {
+    let (hash, colors) = render(board);
+    assert!(
+        colors >= 4,
+        "mapper {} frame is nearly blank",
+        board.mapper()
+    );
+    assert_eq!(
+        hash,
+        expected,
+        "mapper {} frame {HASHED_FRAME} hash changed: {hash:#018X}",
+        board.mapper()
+    );
+}
+
+#[test]
+fn nrom_frame_hash() {
+    assert_golden(Board::Nrom, 0x341E_C340_0426_0802);
+}
+
+#[test]
+fn mmc1_frame_hash() {
+    assert_golden(Board::Mmc1, 0x385C_9EAB_62E0_EBF1);
+}
+
+#[test]
+fn uxrom_frame_hash() {
+    assert_golden(Board::Uxrom, 0x118C_1A6F_3223_659D);
+}
+
+#[test]
+fn cnrom_frame_hash() {
+    assert_golden(Board::Cnrom, 0x2428_BB02_0D13_C30F);
+}
+
+#[test]
+fn mmc3_frame_hash() {
+    assert_golden(Board::Mmc3, 0xC940_362F_ACD3_B437);
+}
+
+#[test]
+fn axrom_frame_hash() {
+    assert_golden(Board::Axrom, 0xF372_E7DA_2136_0C35);
+}
+
+#[test]
+fn fixture_roms_match_their_golden_frames() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-fixtures/roms");
+    let manifest = fs::read_to_string(dir.join("manifest.txt")).unwrap();
+    for line in manifest.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [url, _sha256, frame, expected] = fields[..] else {
+            assert!(fields.is_empty(), "bad manifest line: {line}");
+            continue;
+        };
+        let name = url.rsplit('/').next().unwrap();
+        let Ok(rom) = fs::read(dir.join(name)) else {
+            eprintln!("skipping {name}: not fetched, see scripts/fetch-test-roms.sh");
+            continue;
+        };
+        assert!(
+            dir.join(format!("{name}.LICENSE")).exists(),
+            "{name} has no license file"
+        );
+        let frame: u64 = frame.parse().unwrap();
+        let expected = u64::from_str_radix(expected.trim_start_matches("0x"), 16).unwrap();
+
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        while nes.debug_counters().frame_count < frame {
+            nes.run_frame();
+        }
+        let hash = frame_hash(nes.frame_buffer());
+        assert_eq!(
+            hash, expected,
+            "{name} frame {frame} hash changed: {hash:#018X}"
+        );
+    }
+}
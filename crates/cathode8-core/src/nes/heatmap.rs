@@ -0,0 +1,132 @@
+//! Per-address CPU bus access counts, bucketed and accumulated over a window
+//! of frames.
+//!
+//! This is the fine-grained counterpart of the per-region totals in
+//! [`super::NesDebugCounters`]: the same reads and writes are counted, but
+//! per address bucket, so hot loops, register hammering or accesses to
+//! unmapped space show up at the address they happen.
+
+/// Read and write counts per bucket of CPU address space.
+///
+/// Counts accumulate for `window_frames` frames; the finished window is then
+/// published through [`AccessHeatmap::reads`]/[`AccessHeatmap::writes`] and a
+/// new one starts, so a viewer always shows a complete, stable window.
+#[derive(Debug, Clone)]
+pub struct AccessHeatmap {
+    bucket_shift: u32,
+    window_frames: u32,
+    frames: u32,
+    pending_reads: Vec<u32>,
+    pending_writes: Vec<u32>,
+    last_reads: Vec<u32>,
+    last_writes: Vec<u32>,
+}
+
+impl AccessHeatmap {
+    /// `bucket_size` is rounded up to a power of two between 1 and 4096
+    /// bytes; `window_frames` of 0 is treated as 1.
+    pub fn new(bucket_size: usize, window_frames: u32) -> Self {
+        let bucket_shift = bucket_size
+            .clamp(1, 0x1000)
+            .next_power_of_two()
+            .trailing_zeros();
+        let buckets = 0x10000 >> bucket_shift;
+        Self {
+            bucket_shift,
+            window_frames: window_frames.max(1),
+            frames: 0,
+            pending_reads: vec![0; buckets],
+            pending_writes: vec![0; buckets],
+            last_reads: vec![0; buckets],
+            last_writes: vec![0; buckets],
+        }
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        1 << self.bucket_shift
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.pending_reads.len()
+    }
+
+    pub fn window_frames(&self) -> u32 {
+        self.window_frames
+    }
+
+    /// First CPU address covered by `bucket`.
+    pub fn bucket_addr(&self, bucket: usize) -> u16 {
+        (bucket << self.bucket_shift) as u16
+    }
+
+    /// Read counts of the last completed window, one per bucket.
+    pub fn reads(&self) -> &[u32] {
+        &self.last_reads
+    }
+
+    /// Write counts of the last completed window, one per bucket.
+    pub fn writes(&self) -> &[u32] {
+        &self.last_writes
+    }
+
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        for counts in [
+            &mut self.pending_reads,
+            &mut self.pending_writes,
+            &mut self.last_reads,
+            &mut self.last_writes,
+        ] {
+            counts.fill(0);
+        }
+    }
+
+    pub(crate) fn record_read(&mut self, addr: u16) {
+        let bucket = (addr as usize) >> self.bucket_shift;
+        self.pending_reads[bucket] = self.pending_reads[bucket].saturating_add(1);
+    }
+
+    pub(crate) fn record_write(&mut self, addr: u16) {
+        let bucket = (addr as usize) >> self.bucket_shift;
+        self.pending_writes[bucket] = self.pending_writes[bucket].saturating_add(1);
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.frames += 1;
+        if self.frames < self.window_frames {
+            return;
+        }
+        self.frames = 0;
+        std::mem::swap(&mut self.pending_reads, &mut self.last_reads);
+        std::mem::swap(&mut self.pending_writes, &mut self.last_writes);
+        self.pending_reads.fill(0);
+        self.pending_writes.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_publish_once_window_completes() {
+        let mut heatmap = AccessHeatmap::new(0x100, 2);
+        assert_eq!(heatmap.buckets(), 256);
+
+        heatmap.record_read(0x2002);
+        heatmap.record_read(0x20FF);
+        heatmap.record_write(0x4014);
+        heatmap.end_frame();
+        assert_eq!(heatmap.reads()[0x20], 0);
+
+        heatmap.record_read(0x2002);
+        heatmap.end_frame();
+        assert_eq!(heatmap.reads()[0x20], 3);
+        assert_eq!(heatmap.writes()[0x40], 1);
+        assert_eq!(heatmap.bucket_addr(0x40), 0x4000);
+
+        heatmap.end_frame();
+        heatmap.end_frame();
+        assert_eq!(heatmap.reads()[0x20], 0);
+    }
+}
@@ -0,0 +1,338 @@
+//! Pluggable input sources.
+//!
+//! An [`InputProvider`] installed with [`Nes::set_input_provider`] is polled
+//! each time the game strobes $4016, instead of the frontend pushing state
+//! with [`Nes::set_controller_state`] before every frame. Movies, netplay,
+//! scripts and the GUI can then all feed the core through one interface, and
+//! input is sampled at the moment the game actually latches it.
+//!
+//! What is plugged into each of the two ports is a [`ControllerPort`]; it
+//! decides which data lines a read of $4016/$4017 drives and what the serial
+//! stream looks like after the strobe, so games that probe for a Four Score
+//! or a light gun see the signatures they expect.
+//!
+//! The Zapper senses light from the frame buffer around its aim point. The
+//! emulated frame is always whole, but a frontend's mouse sampling is not:
+//! with a high refresh host display or a variable number of frames run per
+//! host frame, the one frame a game flashes its targets on can land between
+//! aim updates. [`Nes::set_zapper_persistence`] keeps a lit aim point
+//! reading as light for a few more frames to cover that.
+
+use serde::{Deserialize, Serialize};
+
+use super::Nes;
+
+/// Controllers a provider reports. Players 3 and 4 are only heard through a
+/// [`ControllerPort::FourScore`].
+pub const INPUT_PORTS: usize = 4;
+
+/// Four Score signature bytes, shifted out after both controllers on $4016
+/// and $4017 respectively. Games read them MSB first, as $10 and $20.
+const FOUR_SCORE_SIGNATURES: [u32; 2] = [0x08, 0x04];
+
+/// Longest Zapper light persistence, in frames.
+pub const MAX_ZAPPER_PERSISTENCE: u8 = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerPort {
+    Unplugged,
+    /// Standard controller: eight buttons on D0, then 1s.
+    #[default]
+    Gamepad,
+    /// Light gun: light sense on D3 (0 when light is seen), trigger on D4.
+    Zapper,
+    /// NES Arkanoid controller: fire on D3, the knob on D4 as eight inverted
+    /// bits, MSB first.
+    Paddle,
+    /// One side of a Four Score: this port's controller, then controller 3
+    /// (port 1) or 4 (port 2), then the adapter's signature, all on D0.
+    FourScore,
+    /// Vs. System light gun: a serial report on D0 like a controller, with
+    /// an always-set bit 4, light on bit 6 and the trigger on bit 7.
+    VsZapper,
+}
+
+impl ControllerPort {
+    pub const ALL: [ControllerPort; 6] = [
+        ControllerPort::Unplugged,
+        ControllerPort::Gamepad,
+        ControllerPort::Zapper,
+        ControllerPort::Paddle,
+        ControllerPort::FourScore,
+        ControllerPort::VsZapper,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ControllerPort::Unplugged => "Unplugged",
+            ControllerPort::Gamepad => "Controller",
+            ControllerPort::Zapper => "Zapper",
+            ControllerPort::Paddle => "Arkanoid paddle",
+            ControllerPort::FourScore => "Four Score",
+            ControllerPort::VsZapper => "Vs. Zapper",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZapperInput {
+    /// NES pixel the gun points at; negative when off-screen.
+    pub x: i16,
+    pub y: i16,
+    pub trigger: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PaddleInput {
+    /// Raw knob value. Arkanoid expects roughly $62 (left) to $F2 (right).
+    pub position: u8,
+    pub button: bool,
+}
+
+/// ```
+/// use cathode8_core::BUTTON_RIGHT;
+/// use cathode8_core::nes::Nes;
+/// use cathode8_core::nes::input::{INPUT_PORTS, InputProvider};
+///
+/// /// Holds Right on controller 1 for the first 30 frames.
+/// struct Script;
+///
+/// impl InputProvider for Script {
+///     fn poll(&mut self, frame: u64) -> [u8; INPUT_PORTS] {
+///         let port1 = if frame < 30 { BUTTON_RIGHT } else { 0 };
+///         [port1, 0, 0, 0]
+///     }
+/// }
+///
+/// let mut nes = Nes::new();
+/// let previous = nes.set_input_provider(Box::new(Script));
+/// assert!(previous.is_none());
+/// ```
+pub trait InputProvider {
+    /// Buttons held on each port (same bit layout as [`Nes::set_controller_state`])
+    /// for emulated frame `frame`. Games may strobe more than once a frame, so
+    /// this can be called repeatedly with the same frame number.
+    fn poll(&mut self, frame: u64) -> [u8; INPUT_PORTS];
+
+    /// Zapper position and trigger, or `None` to leave the current state.
+    fn zapper(&mut self, _frame: u64) -> Option<ZapperInput> {
+        None
+    }
+
+    /// Paddle knob and button, or `None` to leave the current state.
+    fn paddle(&mut self, _frame: u64) -> Option<PaddleInput> {
+        None
+    }
+}
+
+/// Fixed input on every poll, handy for tests and simple scripts.
+impl InputProvider for [u8; INPUT_PORTS] {
+    fn poll(&mut self, _frame: u64) -> [u8; INPUT_PORTS] {
+        *self
+    }
+}
+
+impl Nes {
+    /// Installs `provider` as the source of controller input, returning the
+    /// previous one. While installed, it overrides state set directly.
+    pub fn set_input_provider(
+        &mut self,
+        provider: Box<dyn InputProvider>,
+    ) -> Option<Box<dyn InputProvider>> {
+        self.input_provider.replace(provider)
+    }
+
+    /// Removes the input provider; state set directly is used again.
+    pub fn take_input_provider(&mut self) -> Option<Box<dyn InputProvider>> {
+        self.input_provider.take()
+    }
+
+    /// What is plugged into ports 1 and 2. Defaults to a controller and a
+    /// Zapper.
+    pub fn controller_ports(&self) -> [ControllerPort; 2] {
+        self.ports
+    }
+
+    pub fn set_controller_ports(&mut self, ports: [ControllerPort; 2]) {
+        self.ports = ports;
+        if self.controller_strobe {
+            self.latch_controllers();
+        }
+    }
+
+    /// Buttons held on controllers 1 to 4 at once.
+    pub fn set_controller_states(&mut self, states: [u8; INPUT_PORTS]) {
+        let [port1, port2, port3, port4] = states;
+        self.controller_state = port1;
+        self.controller2_state = port2;
+        self.controller_extra_states = [port3, port4];
+        if self.controller_strobe {
+            self.latch_controllers();
+        }
+    }
+
+    /// Extra frames a lit aim point keeps reading as light. 0 (the default)
+    /// senses only what is on screen now.
+    pub fn zapper_persistence(&self) -> u8 {
+        self.zapper_persistence
+    }
+
+    /// Sets the light persistence, clamped to [`MAX_ZAPPER_PERSISTENCE`].
+    /// Games that check for darkness on the frame before their target flash
+    /// will see the previous frame's light, so keep this as low as the
+    /// frontend allows.
+    pub fn set_zapper_persistence(&mut self, frames: u8) {
+        self.zapper_persistence = frames.min(MAX_ZAPPER_PERSISTENCE);
+    }
+
+    pub fn paddle_state(&self) -> PaddleInput {
+        self.paddle
+    }
+
+    pub fn set_paddle_state(&mut self, paddle: PaddleInput) {
+        self.paddle = paddle;
+        if self.controller_strobe {
+            self.latch_controllers();
+        }
+    }
+
+    /// Asks the provider for the current frame's input, at strobe time.
+    pub(super) fn poll_input_provider(&mut self) {
+        let Some(provider) = self.input_provider.as_mut() else {
+            return;
+        };
+        let frame = self.debug.frame_count;
+        let [port1, port2, port3, port4] = provider.poll(frame);
+        let zapper = provider.zapper(frame);
+        if let Some(paddle) = provider.paddle(frame) {
+            self.paddle = paddle;
+        }
+        self.controller_state = port1;
+        self.controller2_state = port2;
+        self.controller_extra_states = [port3, port4];
+        if let Some(zapper) = zapper {
+            self.set_zapper_state(zapper.x, zapper.y, zapper.trigger);
+        }
+    }
+
+    /// Samples the aim point at the end of a frame for light persistence.
+    pub(super) fn record_zapper_light(&mut self) {
+        let lit = self.ppu.zapper_light_sensed(self.zapper_x, self.zapper_y);
+        self.zapper_lit_frames = (self.zapper_lit_frames << 1) | u32::from(lit);
+    }
+
+    fn zapper_light(&self) -> bool {
+        let window = (1u32 << self.zapper_persistence) - 1;
+        self.ppu.zapper_light_sensed(self.zapper_x, self.zapper_y)
+            || self.zapper_lit_frames & window != 0
+    }
+
+    /// The serial stream `port` shifts out after a strobe.
+    pub(super) fn port_latch_value(&self, port: usize) -> u32 {
+        let own = [self.controller_state, self.controller2_state][port] as u32;
+        match self.ports[port] {
+            ControllerPort::Gamepad => own | 0xFFFF_FF00,
+            ControllerPort::FourScore => {
+                let chained = self.controller_extra_states[port] as u32;
+                own | (chained << 8) | (FOUR_SCORE_SIGNATURES[port] << 16) | 0xFF00_0000
+            }
+            ControllerPort::Paddle => (!self.paddle.position).reverse_bits() as u32,
+            ControllerPort::VsZapper => {
+                0x10 | (u32::from(self.zapper_light()) << 6) | (u32::from(self.zapper_trigger) << 7)
+            }
+            ControllerPort::Zapper | ControllerPort::Unplugged => 0,
+        }
+    }
+
+    /// A read of $4016 (`port` 0) or $4017 (`port` 1), shifting the serial
+    /// stream unless the strobe is held.
+    pub(super) fn read_port(&mut self, port: usize) -> u8 {
+        let serial = (self.port_shift[port] & 0x01) as u8;
+        if !self.controller_strobe {
+            self.port_shift[port] = (self.port_shift[port] >> 1) | 0x8000_0000;
+        }
+        let data = match self.ports[port] {
+            ControllerPort::Gamepad | ControllerPort::FourScore | ControllerPort::VsZapper => {
+                serial
+            }
+            ControllerPort::Zapper => {
+                (u8::from(!self.zapper_light()) << 3) | (u8::from(self.zapper_trigger) << 4)
+            }
+            ControllerPort::Paddle => (u8::from(self.paddle.button) << 3) | (serial << 4),
+            ControllerPort::Unplugged => 0,
+        };
+        0x40 | data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder {
+        polls: std::rc::Rc<std::cell::RefCell<Vec<u64>>>,
+    }
+
+    impl InputProvider for Recorder {
+        fn poll(&mut self, frame: u64) -> [u8; INPUT_PORTS] {
+            self.polls.borrow_mut().push(frame);
+            [0x81, 0x02, 0, 0]
+        }
+    }
+
+    #[test]
+    fn provider_is_polled_on_strobe() {
+        let mut nes = Nes::new();
+        nes.set_controller_ports([ControllerPort::Gamepad; 2]);
+        let polls = std::rc::Rc::default();
+        nes.set_input_provider(Box::new(Recorder {
+            polls: std::rc::Rc::clone(&polls),
+        }));
+
+        nes.cpu_write(0x4016, 1);
+        nes.cpu_write(0x4016, 0);
+        let port1: Vec<u8> = (0..8).map(|_| nes.cpu_read(0x4016) & 1).collect();
+        let port2: Vec<u8> = (0..8).map(|_| nes.cpu_read(0x4017) & 1).collect();
+
+        assert_eq!(*polls.borrow(), vec![0]);
+        assert_eq!(port1, vec![1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(port2, vec![0, 1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn four_score_sends_both_controllers_and_signature() {
+        let mut nes = Nes::new();
+        nes.set_controller_ports([ControllerPort::FourScore; 2]);
+        nes.set_controller_states([0x01, 0x02, 0x80, 0x40]);
+        nes.cpu_write(0x4016, 1);
+        nes.cpu_write(0x4016, 0);
+
+        let read = |nes: &mut Nes, addr: u16| -> u32 {
+            (0..24).fold(0, |bits, i| bits | (((nes.cpu_read(addr) & 1) as u32) << i))
+        };
+        let port1 = read(&mut nes, 0x4016);
+        let port2 = read(&mut nes, 0x4017);
+        assert_eq!(port1, 0x01 | (0x80 << 8) | (0x08 << 16));
+        assert_eq!(port2, 0x02 | (0x40 << 8) | (0x04 << 16));
+        assert_eq!(nes.cpu_read(0x4016) & 1, 1);
+    }
+
+    #[test]
+    fn vs_zapper_reports_trigger_and_persisted_light() {
+        let mut nes = Nes::new();
+        nes.set_controller_ports([ControllerPort::VsZapper, ControllerPort::Gamepad]);
+        nes.set_zapper_state(-1, -1, true);
+        let report = |nes: &mut Nes| -> u8 {
+            nes.cpu_write(0x4016, 1);
+            nes.cpu_write(0x4016, 0);
+            (0..8).fold(0, |bits, i| bits | ((nes.cpu_read(0x4016) & 1) << i))
+        };
+        assert_eq!(report(&mut nes), 0x90);
+
+        // Lit two frames ago: only seen once persistence covers it.
+        nes.zapper_lit_frames = 0b10;
+        assert_eq!(report(&mut nes), 0x90);
+        nes.set_zapper_persistence(2);
+        assert_eq!(report(&mut nes), 0xD0);
+    }
+}
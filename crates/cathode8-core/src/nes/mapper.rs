@@ -0,0 +1,8449 @@
+use anyhow::{Result, bail};
+use std::io::{self, Read, Write};
+
+use super::apu::ExpansionChip;
+use super::cartridge::Cartridge;
+use super::mmc5_audio::Mmc5Audio;
+use super::state_field::{StateField, enum_state_field, state_fields};
+use super::sunsoft5b::Sunsoft5b;
+use super::vrc7::Vrc7;
+
+pub const DOCUMENTED_MAPPER_COUNT: u16 = 560;
+pub const DOCUMENTED_MAPPER_MAX_ID: u16 = DOCUMENTED_MAPPER_COUNT - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    OneScreenLower,
+    OneScreenUpper,
+    FourScreen,
+}
+
+enum_state_field!(Mirroring {
+    Horizontal,
+    Vertical,
+    OneScreenLower,
+    OneScreenUpper,
+    FourScreen,
+});
+
+impl Mirroring {
+    pub fn label(self) -> &'static str {
+        match self {
+            Mirroring::Horizontal => "Horizontal",
+            Mirroring::Vertical => "Vertical",
+            Mirroring::OneScreenLower => "One-screen (lower)",
+            Mirroring::OneScreenUpper => "One-screen (upper)",
+            Mirroring::FourScreen => "Four-screen",
+        }
+    }
+
+    /// 1 KiB VRAM page backing logical nametable `table` (0-3).
+    pub fn vram_page(self, table: usize) -> u8 {
+        match self {
+            Mirroring::Horizontal => (table >> 1) as u8 & 1,
+            Mirroring::Vertical => table as u8 & 1,
+            Mirroring::OneScreenLower => 0,
+            Mirroring::OneScreenUpper => 1,
+            Mirroring::FourScreen => table as u8 & 3,
+        }
+    }
+}
+
+/// Memory a logical nametable is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NametableSource {
+    /// 1 KiB page of console VRAM; pages 2 and 3 are cartridge VRAM on
+    /// four-screen boards.
+    Vram(u8),
+    /// MMC5 expansion RAM.
+    ExRam,
+    /// MMC5 fill-mode tile and attribute.
+    Fill,
+    /// 1 KiB CHR bank, e.g. Namco 163 CHR-ROM nametables.
+    Chr(u16),
+}
+
+/// What the PPU's fetches are for, passed to [`Mapper::set_fetch_phase`]
+/// while it renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchPhase {
+    /// Background tile `tile` of visible line `line`. Tiles 0 and 1 are
+    /// fetched at the end of the line before, 2-33 across the line itself.
+    Background { tile: u8, line: u8 },
+    /// Sprite patterns, from dot 257 until the next background fetch.
+    /// Accesses through $2007 outside rendering fall here too.
+    Sprites,
+}
+
+impl std::fmt::Display for NametableSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NametableSource::Vram(page) => write!(f, "VRAM {page}"),
+            NametableSource::ExRam => write!(f, "ExRAM"),
+            NametableSource::Fill => write!(f, "Fill"),
+            NametableSource::Chr(bank) => write!(f, "CHR ${bank:02X}"),
+        }
+    }
+}
+
+pub trait Mapper {
+    fn cpu_read(&mut self, addr: u16) -> u8;
+    fn cpu_write(&mut self, addr: u16, value: u8);
+    /// Reads in the expansion area at $4020-$5FFF. Most boards don't drive
+    /// the bus there and the CPU sees open bus; boards with registers or
+    /// RAM in the area return `Some`. Writes there go to
+    /// [`Mapper::cpu_write`] like any other.
+    fn cpu_read_expansion(&mut self, _addr: u16) -> Option<u8> {
+        None
+    }
+    /// Whether a read at $6000-$FFFF finds a chip. Multicarts sold with
+    /// fewer PRG chips than their board has room for leave the banks on the
+    /// missing ones floating, and the CPU sees open bus there.
+    fn prg_drives_bus(&self, _addr: u16) -> bool {
+        true
+    }
+    fn ppu_read(&mut self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, value: u8);
+    fn mirroring(&self) -> Mirroring;
+    /// Bank registers, cartridge RAM, CHR RAM, IRQ counters and sound chip
+    /// state, for save states and rewind. ROM isn't stored: a state only
+    /// loads into the game it was saved from.
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()>;
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()>;
+    fn tick_cpu_cycle(&mut self) {}
+    fn tick_ppu_cycle(&mut self) {}
+    fn ppu_nametable_read(&mut self, _addr: u16, _vram: &[u8; 4096]) -> Option<u8> {
+        None
+    }
+    fn ppu_nametable_write(&mut self, _addr: u16, _value: u8, _vram: &mut [u8; 4096]) -> bool {
+        false
+    }
+    fn notify_ppu_read_addr(&mut self, _addr: u16) {}
+    /// Called as the PPU starts each background tile's fetches and again as
+    /// it moves on to sprites, for boards that feed the two differently.
+    fn set_fetch_phase(&mut self, _phase: FetchPhase) {}
+    fn notify_ppu_write_addr(&mut self, _addr: u16) {}
+    fn irq_pending(&self) -> bool {
+        false
+    }
+    fn clear_irq(&mut self) {}
+    fn debug_peek_chr(&self, _addr: u16) -> u8 {
+        0
+    }
+    /// The whole CHR ROM or RAM, every bank, for dumps.
+    fn chr_data(&self) -> &[u8] {
+        &[]
+    }
+    /// CHR RAM for edits from outside the PPU; `None` for CHR ROM.
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+    /// The loaded copy of PRG ROM, for tools that deliberately alter it.
+    /// Mappers only cache bank offsets, so changed bytes are seen on the
+    /// next read.
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    /// CHR ROM or RAM, like [`Mapper::prg_rom_mut`].
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    /// Work RAM at $6000-$7FFF, which battery boards keep across power-off.
+    fn prg_ram(&self) -> &[u8] {
+        &[]
+    }
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    /// Whether the CPU wrote PRG RAM since the last call, clearing the flag.
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        false
+    }
+    /// The sample a speech chip on the board was told to play since the
+    /// last call. Jaleco's uPD7756 keeps its ADPCM in a mask ROM that ROM
+    /// dumps don't include, so frontends can only play it from a sample
+    /// pack.
+    fn take_sample_trigger(&mut self) -> Option<u8> {
+        None
+    }
+    /// Level of the board's own sound chip, in the units of the APU mix it
+    /// is added to. Boards without one are silent.
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+    fn debug_state(&self) -> String {
+        String::new()
+    }
+    /// What logical nametable `table` (0-3) currently maps to. Mappers that
+    /// override [`Mapper::ppu_nametable_read`] must override this as well.
+    fn nametable_source(&self, table: usize) -> NametableSource {
+        NametableSource::Vram(self.mirroring().vram_page(table))
+    }
+    /// Byte `offset` of an [`NametableSource::ExRam`] or
+    /// [`NametableSource::Fill`] table, which the board supplies itself.
+    fn debug_peek_nametable(&self, _table: usize, _offset: usize) -> u8 {
+        0
+    }
+}
+
+/// A board with an implementation of its own, as listed in the frontend's
+/// supported boards window. Mapper IDs missing here run on the generic
+/// mapper, which only gets the simplest games going.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardInfo {
+    pub mapper_id: u16,
+    pub name: &'static str,
+    /// NES 2.0 submappers the implementation tells apart. Empty when it
+    /// ignores the submapper.
+    pub submappers: &'static [u8],
+    /// The cartridge sound chip that plays, if the board has one.
+    pub audio: Option<ExpansionChip>,
+    /// What is known not to work yet, empty if nothing.
+    pub notes: &'static str,
+}
+
+const fn board(
+    mapper_id: u16,
+    name: &'static str,
+    submappers: &'static [u8],
+    audio: Option<ExpansionChip>,
+    notes: &'static str,
+) -> BoardInfo {
+    BoardInfo {
+        mapper_id,
+        name,
+        submappers,
+        audio,
+        notes,
+    }
+}
+
+/// Every board [`create_mapper`] builds its own mapper for, by mapper ID.
+pub const SUPPORTED_BOARDS: &[BoardInfo] = &[
+    board(0, "NROM", &[], None, ""),
+    board(1, "MMC1", &[], None, ""),
+    board(2, "UxROM", &[], None, ""),
+    board(3, "CNROM", &[], None, ""),
+    board(4, "MMC3", &[], None, ""),
+    board(5, "MMC5", &[], Some(ExpansionChip::Mmc5), ""),
+    board(7, "AxROM", &[], None, ""),
+    board(9, "MMC2", &[], None, ""),
+    board(10, "MMC4", &[], None, ""),
+    board(11, "Color Dreams", &[], None, ""),
+    board(16, "Bandai FCG", &[4, 5], None, ""),
+    board(
+        18,
+        "Jaleco SS88006",
+        &[],
+        None,
+        "Speech samples are not played",
+    ),
+    board(
+        19,
+        "Namco 163",
+        &[],
+        None,
+        "Namco 163 wavetable audio is not emulated",
+    ),
+    board(24, "Konami VRC6a", &[], None, "VRC6 audio is not emulated"),
+    board(25, "Konami VRC4b/d", &[], None, ""),
+    board(26, "Konami VRC6b", &[], None, "VRC6 audio is not emulated"),
+    board(32, "Irem G-101", &[1], None, ""),
+    board(33, "Taito TC0190", &[], None, ""),
+    board(34, "BNROM / NINA-001", &[1, 2], None, ""),
+    board(38, "Bit Corp PCI556", &[], None, ""),
+    board(39, "Study & Game 32-in-1", &[], None, ""),
+    board(40, "NTDEC 2722", &[], None, ""),
+    board(42, "FDS conversion (Ai Senshi Nicol)", &[], None, ""),
+    board(46, "Rumble Station", &[], None, ""),
+    board(48, "Taito TC0690", &[], None, ""),
+    board(50, "N-32 (SMB2j rev. A)", &[], None, ""),
+    board(51, "11-in-1 Ball Games", &[], None, ""),
+    board(66, "GxROM", &[], None, ""),
+    board(67, "Sunsoft-3", &[], None, ""),
+    board(
+        69,
+        "FME-7 / Sunsoft 5B",
+        &[],
+        Some(ExpansionChip::Sunsoft5b),
+        "",
+    ),
+    board(70, "Bandai 74161", &[], None, ""),
+    board(71, "Camerica", &[1], None, ""),
+    board(
+        72,
+        "Jaleco JF-17",
+        &[],
+        None,
+        "Speech samples are not played",
+    ),
+    board(79, "AVE NINA-03/06", &[], None, ""),
+    board(80, "Taito X1-005", &[], None, ""),
+    board(82, "Taito X1-017", &[], None, ""),
+    board(85, "Konami VRC7", &[1, 2], Some(ExpansionChip::Vrc7), ""),
+    board(
+        86,
+        "Jaleco JF-13",
+        &[],
+        None,
+        "Speech samples are not played",
+    ),
+    board(89, "Sunsoft-2 (Sunsoft-3 board)", &[], None, ""),
+    board(
+        92,
+        "Jaleco JF-19",
+        &[],
+        None,
+        "Speech samples are not played",
+    ),
+    board(93, "Sunsoft-2 (Sunsoft-3R board)", &[], None, ""),
+    board(94, "HVC-UN1ROM", &[], None, ""),
+    board(97, "Irem TAM-S1", &[], None, ""),
+    board(113, "HES NTD-8", &[], None, ""),
+    board(146, "Sachen 3015", &[], None, ""),
+    board(152, "Bandai 74161 (one-screen)", &[], None, ""),
+    board(159, "Bandai LZ93D50 + 24C01", &[], None, ""),
+    board(180, "UNROM (AND)", &[], None, ""),
+    board(228, "Action 52", &[], None, ""),
+    board(235, "150-in-1", &[], None, ""),
+];
+
+/// The registry entry for `mapper_id`, if it has its own implementation.
+pub fn board_info(mapper_id: u16) -> Option<&'static BoardInfo> {
+    SUPPORTED_BOARDS
+        .iter()
+        .find(|board| board.mapper_id == mapper_id)
+}
+
+pub fn mapper_name(mapper_id: u16) -> &'static str {
+    if let Some(board) = board_info(mapper_id) {
+        return board.name;
+    }
+    match mapper_id {
+        15 => "100-in-1",
+        21 => "Konami VRC4a",
+        22 => "Konami VRC2a",
+        23 => "Konami VRC2b/VRC4e",
+        37 => "PAL-ZZ",
+        47 => "MMC3 variant",
+        52 => "MMC3 variant",
+        225 => "72-in-1",
+        232 => "Quattro",
+        342 => "COOLGIRL",
+        365 => "NES 2.0 Mapper 365",
+        _ if mapper_id <= DOCUMENTED_MAPPER_MAX_ID => "Documented Mapper (generic)",
+        _ => "Unsupported",
+    }
+}
+
+pub fn create_mapper(cart: Cartridge) -> Result<Box<dyn Mapper>> {
+    let mapper: Box<dyn Mapper> = match cart.mapper_id {
+        0 => Box::new(Mapper0::new(cart)),
+        1 => Box::new(Mapper1::new(cart)),
+        2 => Box::new(Mapper2::new(cart)),
+        3 => Box::new(Mapper3::new(cart)),
+        4 => Box::new(Mapper4::new(cart)),
+        5 => Box::new(Mapper5::new(cart)),
+        7 => Box::new(Mapper7::new(cart)),
+        9 => Box::new(Mapper9::new(cart)),
+        10 => Box::new(Mapper10::new(cart)),
+        11 => Box::new(Mapper11::new(cart)),
+        16 | 159 => Box::new(Mapper16::new(cart)),
+        18 => Box::new(Mapper18::new(cart)),
+        19 => Box::new(Mapper19::new(cart)),
+        24 => Box::new(Mapper24::new(cart)),
+        25 => Box::new(Mapper25::new(cart)),
+        26 => Box::new(Mapper26::new(cart)),
+        32 => Box::new(Mapper32::new(cart)),
+        33 | 48 => Box::new(Mapper33::new(cart)),
+        34 | 39 => Box::new(Mapper34::new(cart)),
+        38 => Box::new(Mapper38::new(cart)),
+        40 | 50 => Box::new(Mapper40::new(cart)),
+        42 => Box::new(Mapper42::new(cart)),
+        46 => Box::new(Mapper46::new(cart)),
+        51 => Box::new(Mapper51::new(cart)),
+        69 => Box::new(Mapper69::new(cart)),
+        70 | 152 => Box::new(Mapper70::new(cart)),
+        66 => Box::new(Mapper66::new(cart)),
+        67 => Box::new(Mapper67::new(cart)),
+        71 => Box::new(Mapper71::new(cart)),
+        72 | 92 => Box::new(Mapper72::new(cart)),
+        79 | 113 | 146 => Box::new(Mapper79::new(cart)),
+        80 | 82 => Box::new(Mapper80::new(cart)),
+        85 => Box::new(Mapper85::new(cart)),
+        86 => Box::new(Mapper86::new(cart)),
+        89 | 93 | 94 | 97 | 180 => Box::new(Mapper89::new(cart)),
+        228 => Box::new(Mapper228::new(cart)),
+        235 => Box::new(Mapper235::new(cart)),
+        id if id <= DOCUMENTED_MAPPER_MAX_ID => Box::new(GenericMapper::new(cart)),
+        id => {
+            bail!(
+                "mapper {id} exceeds max supported ({}). Try increasing DOCUMENTED_MAPPER_MAX_ID",
+                DOCUMENTED_MAPPER_MAX_ID
+            );
+        }
+    };
+    Ok(mapper)
+}
+
+/// Start of `bank` in a region of `len` bytes switched in `window`-byte
+/// banks, wrapping bank numbers past the end of the chip. Mappers resolve
+/// these when a bank register changes so each access is a single add; this
+/// relies on ROM and RAM sizes being whole multiples of the window, which
+/// the header's 16 KB PRG / 8 KB CHR units guarantee.
+fn bank_base(bank: usize, window: usize, len: usize) -> usize {
+    bank % (len / window).max(1) * window
+}
+
+struct GenericMapper {
+    mapper_id: u16,
+    submapper_id: u8,
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    mirroring: Mirroring,
+    prg_bank_select: u8,
+    chr_bank_select: u8,
+}
+
+impl GenericMapper {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            mapper_id: cart.mapper_id,
+            submapper_id: cart.submapper_id,
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            mirroring: cart.mirroring,
+            prg_bank_select: 0,
+            chr_bank_select: 0,
+        }
+    }
+
+    fn prg_bank_count_16k(&self) -> usize {
+        (self.prg_rom.len() / 0x4000).max(1)
+    }
+
+    fn chr_bank_count_8k(&self) -> usize {
+        (self.chr.len() / 0x2000).max(1)
+    }
+
+    fn read_prg_16k(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count_16k();
+        self.prg_rom[(bank * 0x4000 + offset) % self.prg_rom.len()]
+    }
+}
+
+impl Mapper for GenericMapper {
+    state_fields!(chr if chr_is_ram, prg_ram, mirroring, prg_bank_select, chr_bank_select);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xBFFF => {
+                let bank = self.prg_bank_select as usize % self.prg_bank_count_16k();
+                self.read_prg_16k(bank, addr as usize - 0x8000)
+            }
+            0xC000..=0xFFFF => {
+                let last = self.prg_bank_count_16k().saturating_sub(1);
+                self.read_prg_16k(last, addr as usize - 0xC000)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0xFFFF => {
+                self.prg_bank_select = value & 0x1F;
+                self.chr_bank_select = (value >> 4) & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let bank = (self.chr_bank_select as usize) % self.chr_bank_count_8k();
+        let offset = (addr as usize) & 0x1FFF;
+        let idx = bank * 0x2000 + offset;
+        self.chr[idx % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let bank = (self.chr_bank_select as usize) % self.chr_bank_count_8k();
+            let offset = (addr as usize) & 0x1FFF;
+            let idx = bank * 0x2000 + offset;
+            let mapped = idx % self.chr.len();
+            self.chr[mapped] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[(addr as usize) % self.chr.len()]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "generic mapper={} submapper={} prg_bank=${:02X} chr_bank=${:02X}",
+            self.mapper_id, self.submapper_id, self.prg_bank_select, self.chr_bank_select
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper0 {
+    prg_rom: Vec<u8>,
+    /// $8000-$FFFF to PRG offset: 16 KB boards repeat at $C000.
+    prg_mask: usize,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    mirroring: Mirroring,
+}
+
+impl Mapper0 {
+    fn new(cart: Cartridge) -> Self {
+        let prg_ram_size = cart.prg_ram_size.max(8 * 1024);
+        let prg_mask = if cart.prg_rom.len() <= 0x4000 {
+            0x3FFF
+        } else {
+            0x7FFF
+        };
+        Self {
+            prg_rom: cart.prg_rom,
+            prg_mask,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; prg_ram_size],
+            prg_ram_dirty: false,
+            mirroring: cart.mirroring,
+        }
+    }
+}
+
+impl Mapper for Mapper0 {
+    state_fields!(chr if chr_is_ram, prg_ram);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xFFFF => self.prg_rom[addr as usize & self.prg_mask],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+            self.prg_ram[idx] = value;
+            self.prg_ram_dirty = true;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = addr as usize % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper1 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+
+    shift_register: u8,
+    control: u8,
+    chr_bank0: u8,
+    chr_bank1: u8,
+    /// $E000: PRG bank in bits 0-3, PRG RAM disabled by bit 4.
+    prg_bank: u8,
+    /// CPU cycles since the last write to the serial port, saturating.
+    cycles_since_write: u8,
+    /// Resolved offsets of the 16 KB PRG windows and 4 KB CHR windows, and
+    /// of the 8 KB PRG RAM bank.
+    prg_bases: [usize; 2],
+    chr_bases: [usize; 2],
+    prg_ram_base: usize,
+}
+
+impl Mapper1 {
+    fn new(cart: Cartridge) -> Self {
+        let mut mapper = Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            shift_register: 0x10,
+            control: 0x0C,
+            chr_bank0: 0,
+            chr_bank1: 0,
+            prg_bank: 0,
+            cycles_since_write: u8::MAX,
+            prg_bases: [0; 2],
+            chr_bases: [0; 2],
+            prg_ram_base: 0,
+        };
+        mapper.update_banks();
+        mapper
+    }
+
+    fn update_banks(&mut self) {
+        // SUROM and SXROM have 512 KB of PRG and take the 256 KB half from
+        // bit 4 of the CHR bank; their CHR is 8 KB of RAM, which doesn't
+        // need it. The $E000 bank and the fixed bank both work inside it.
+        let prg_len = self.prg_rom.len().min(0x40000);
+        let outer = if self.prg_rom.len() > 0x40000 {
+            usize::from(self.chr_bank0 >> 4 & 1) * 0x40000
+        } else {
+            0
+        };
+        let bank = usize::from(self.prg_bank & 0x0F);
+        let [low, high] = match (self.control >> 2) & 0x03 {
+            0 | 1 => {
+                let base = (bank & !1) * 0x4000;
+                [base % prg_len, (base + 0x4000) % prg_len]
+            }
+            2 => [0, bank_base(bank, 0x4000, prg_len)],
+            _ => [
+                bank_base(bank, 0x4000, prg_len),
+                (prg_len / 0x4000).saturating_sub(1) * 0x4000,
+            ],
+        };
+        self.prg_bases = [outer + low, outer + high];
+
+        // SOROM's 16 KB of PRG RAM is banked by bit 3 of the CHR bank,
+        // SXROM's 32 KB by bits 2-3.
+        let ram_bank = match self.prg_ram.len() / 0x2000 {
+            0 | 1 => 0,
+            2 => self.chr_bank0 >> 3 & 1,
+            _ => self.chr_bank0 >> 2 & 3,
+        };
+        self.prg_ram_base = bank_base(usize::from(ram_bank), 0x2000, self.prg_ram.len());
+
+        let chr_len = self.chr.len();
+        self.chr_bases = if (self.control & 0x10) == 0 {
+            let base = bank_base(self.chr_bank0 as usize & 0x1E, 0x1000, chr_len);
+            [base, (base + 0x1000) % chr_len]
+        } else {
+            [
+                bank_base(self.chr_bank0 as usize, 0x1000, chr_len),
+                bank_base(self.chr_bank1 as usize, 0x1000, chr_len),
+            ]
+        };
+    }
+
+    fn write_shift_register(&mut self, addr: u16, value: u8) {
+        // The serial port ignores a write on the cycle right after another,
+        // so read-modify-write instructions only land their first write.
+        // Bill & Ted's Excellent Adventure resets the port with INC $FFFF.
+        let consecutive = self.cycles_since_write == 1;
+        self.cycles_since_write = 0;
+        if consecutive {
+            return;
+        }
+
+        if (value & 0x80) != 0 {
+            self.shift_register = 0x10;
+            self.control |= 0x0C;
+            self.update_banks();
+            return;
+        }
+
+        let commit = (self.shift_register & 0x01) != 0;
+        self.shift_register >>= 1;
+        self.shift_register |= (value & 0x01) << 4;
+
+        if commit {
+            let data = self.shift_register;
+            match addr {
+                0x8000..=0x9FFF => self.control = data,
+                0xA000..=0xBFFF => self.chr_bank0 = data,
+                0xC000..=0xDFFF => self.chr_bank1 = data,
+                0xE000..=0xFFFF => self.prg_bank = data,
+                _ => {}
+            }
+            self.shift_register = 0x10;
+            self.update_banks();
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        self.chr_bases[addr >> 12] + (addr & 0x0FFF)
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+
+    fn prg_ram_index(&self, addr: u16) -> usize {
+        (self.prg_ram_base + (addr as usize & 0x1FFF)) % self.prg_ram.len()
+    }
+}
+
+impl Mapper for Mapper1 {
+    state_fields!(chr if chr_is_ram, prg_ram, shift_register, control, chr_bank0, chr_bank1, prg_bank, cycles_since_write, prg_bases, chr_bases, prg_ram_base);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[self.prg_ram_index(addr)],
+            0x8000..=0xFFFF => {
+                let window = (addr as usize >> 14) & 0x01;
+                self.prg_rom[self.prg_bases[window] + (addr as usize & 0x3FFF)]
+            }
+            _ => 0,
+        }
+    }
+
+    fn prg_drives_bus(&self, addr: u16) -> bool {
+        addr >= 0x8000 || self.prg_ram_enabled()
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                let idx = self.prg_ram_index(addr);
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0xFFFF => self.write_shift_register(addr, value),
+            _ => {}
+        }
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        self.cycles_since_write = self.cycles_since_write.saturating_add(1);
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let idx = self.read_chr(addr);
+        self.chr[idx]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.read_chr(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::OneScreenLower,
+            1 => Mirroring::OneScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper2 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    bank_select: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper2 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            bank_select: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_banks(&self) -> usize {
+        (self.prg_rom.len() / 0x4000).max(1)
+    }
+
+    fn read_prg(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_banks();
+        self.prg_rom[bank * 0x4000 + offset]
+    }
+}
+
+impl Mapper for Mapper2 {
+    state_fields!(chr if chr_is_ram, prg_ram, bank_select);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xBFFF => self.read_prg(self.bank_select as usize, addr as usize - 0x8000),
+            0xC000..=0xFFFF => self.read_prg(self.prg_banks() - 1, addr as usize - 0xC000),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0xFFFF => {
+                self.bank_select = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = addr as usize % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper3 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    chr_bank_select: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper3 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            chr_bank_select: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_read(&self, addr: u16) -> u8 {
+        let mut idx = addr as usize - 0x8000;
+        if self.prg_rom.len() == 0x4000 {
+            idx %= 0x4000;
+        }
+        self.prg_rom[idx]
+    }
+
+    fn chr_bank_count(&self) -> usize {
+        (self.chr.len() / 0x2000).max(1)
+    }
+}
+
+impl Mapper for Mapper3 {
+    state_fields!(chr if chr_is_ram, prg_ram, chr_bank_select);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xFFFF => self.prg_read(addr),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0xFFFF => self.chr_bank_select = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let bank = (self.chr_bank_select as usize) % self.chr_bank_count();
+        let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+        self.chr[idx % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let bank = (self.chr_bank_select as usize) % self.chr_bank_count();
+            let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+            let mapped = idx % self.chr.len();
+            self.chr[mapped] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper7 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    prg_bank_select: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper7 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            prg_bank_select: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_bank_count_32k(&self) -> usize {
+        (self.prg_rom.len() / 0x8000).max(1)
+    }
+}
+
+impl Mapper for Mapper7 {
+    state_fields!(chr if chr_is_ram, prg_ram, prg_bank_select, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xFFFF => {
+                let bank = (self.prg_bank_select as usize) % self.prg_bank_count_32k();
+                let offset = (addr as usize) & 0x7FFF;
+                let idx = bank * 0x8000 + offset;
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0xFFFF => {
+                self.prg_bank_select = value & 0x0F;
+                self.mirroring = if (value & 0x10) != 0 {
+                    Mirroring::OneScreenUpper
+                } else {
+                    Mirroring::OneScreenLower
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr as usize) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = (addr as usize) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[(addr as usize) % self.chr.len()]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "AxROM prg_bank=${:02X} prg_32k_banks={} mirroring={:?}",
+            self.prg_bank_select,
+            self.prg_bank_count_32k(),
+            self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper10 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    prg_bank: u8,
+    chr_fd_0000: u8,
+    chr_fe_0000: u8,
+    chr_fd_1000: u8,
+    chr_fe_1000: u8,
+    latch0_is_fe: bool,
+    latch1_is_fe: bool,
+    mirroring: Mirroring,
+}
+
+impl Mapper10 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            prg_bank: 0,
+            chr_fd_0000: 0,
+            chr_fe_0000: 0,
+            chr_fd_1000: 0,
+            chr_fe_1000: 0,
+            latch0_is_fe: true,
+            latch1_is_fe: true,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_bank_count_16k(&self) -> usize {
+        (self.prg_rom.len() / 0x4000).max(1)
+    }
+
+    fn read_prg_16k(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count_16k();
+        self.prg_rom[(bank * 0x4000 + offset) % self.prg_rom.len()]
+    }
+
+    fn chr_bank_count_4k(&self) -> usize {
+        (self.chr.len() / 0x1000).max(1)
+    }
+
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let bank = if addr < 0x1000 {
+            if self.latch0_is_fe {
+                self.chr_fe_0000
+            } else {
+                self.chr_fd_0000
+            }
+        } else if self.latch1_is_fe {
+            self.chr_fe_1000
+        } else {
+            self.chr_fd_1000
+        } as usize
+            % self.chr_bank_count_4k();
+
+        bank * 0x1000 + (addr as usize & 0x0FFF)
+    }
+
+    fn update_latches(&mut self, addr: u16) {
+        match addr {
+            0x0FD8 => self.latch0_is_fe = false,
+            0x0FE8 => self.latch0_is_fe = true,
+            0x1FD8..=0x1FDF => self.latch1_is_fe = false,
+            0x1FE8..=0x1FEF => self.latch1_is_fe = true,
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mapper10 {
+    state_fields!(chr if chr_is_ram, prg_ram, prg_bank, chr_fd_0000, chr_fe_0000, chr_fd_1000, chr_fe_1000, latch0_is_fe, latch1_is_fe, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xBFFF => self.read_prg_16k(self.prg_bank as usize, addr as usize - 0x8000),
+            0xC000..=0xFFFF => {
+                let last = self.prg_bank_count_16k().saturating_sub(1);
+                self.read_prg_16k(last, addr as usize - 0xC000)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0xA000..=0xAFFF => self.prg_bank = value & 0x0F,
+            0xB000..=0xBFFF => self.chr_fd_0000 = value & 0x1F,
+            0xC000..=0xCFFF => self.chr_fe_0000 = value & 0x1F,
+            0xD000..=0xDFFF => self.chr_fd_1000 = value & 0x1F,
+            0xE000..=0xEFFF => self.chr_fe_1000 = value & 0x1F,
+            0xF000..=0xFFFF => {
+                self.mirroring = if (value & 0x01) == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let idx = self.map_chr_addr(addr & 0x1FFF) % self.chr.len();
+        self.chr[idx]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.map_chr_addr(addr & 0x1FFF) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn notify_ppu_read_addr(&mut self, addr: u16) {
+        self.update_latches(addr & 0x1FFF);
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "MMC4 prg=${:02X} latch0_fe={} latch1_fe={} chr_fd=({:02X},{:02X}) chr_fe=({:02X},{:02X})",
+            self.prg_bank,
+            self.latch0_is_fe,
+            self.latch1_is_fe,
+            self.chr_fd_0000,
+            self.chr_fd_1000,
+            self.chr_fe_0000,
+            self.chr_fe_1000
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mapper5PrgTarget {
+    Rom,
+    Ram,
+}
+
+enum_state_field!(Mapper5PrgTarget { Rom, Ram });
+
+struct Mapper5 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    exram: [u8; 0x400],
+    nametable_map: [u8; 4],
+    prg_mode: u8,
+    chr_mode: u8,
+    exram_mode: u8,
+    fill_tile: u8,
+    fill_attr: u8,
+    prg_ram_protect_1: u8,
+    prg_ram_protect_2: u8,
+    prg_regs: [u8; 5],
+    chr_regs: [u16; 12],
+    chr_upper_bits: u8,
+    irq_scanline_compare: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline_counter: u8,
+    last_nametable_probe: u16,
+    repeated_nametable_reads: u8,
+    scanline_detect_armed: bool,
+    cpu_cycles_since_ppu_read: u8,
+    mul_a: u8,
+    mul_b: u8,
+    /// Resolved chip and offset for each 8 KB window from $6000 up, and
+    /// offsets of the eight 1 KB CHR windows, updated on bank writes.
+    prg_windows: [(Mapper5PrgTarget, usize); 5],
+    chr_bases: [usize; 8],
+    audio: Mmc5Audio,
+    /// Vertical split: $5200 control, $5201 scroll and $5202 4 KB CHR bank.
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+    fetch_phase: FetchPhase,
+}
+
+impl Mapper5 {
+    fn new(cart: Cartridge) -> Self {
+        let mut chr_regs = [0u16; 12];
+        for (idx, reg) in chr_regs.iter_mut().enumerate() {
+            *reg = idx as u16;
+        }
+
+        let mut mapper = Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            exram: [0; 0x400],
+            nametable_map: Self::default_nametable_map(cart.mirroring),
+            prg_mode: 3,
+            chr_mode: 3,
+            exram_mode: 0,
+            fill_tile: 0,
+            fill_attr: 0,
+            prg_ram_protect_1: 0,
+            prg_ram_protect_2: 0,
+            prg_regs: [0, 0, 0, 0, 0xFF],
+            chr_regs,
+            chr_upper_bits: 0,
+            irq_scanline_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline_counter: 0,
+            last_nametable_probe: 0,
+            repeated_nametable_reads: 0,
+            scanline_detect_armed: false,
+            cpu_cycles_since_ppu_read: 3,
+            mul_a: 0,
+            mul_b: 0,
+            prg_windows: [(Mapper5PrgTarget::Ram, 0); 5],
+            chr_bases: [0; 8],
+            audio: Mmc5Audio::default(),
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            fetch_phase: FetchPhase::Sprites,
+        };
+        mapper.update_banks();
+        mapper
+    }
+
+    fn default_nametable_map(mirroring: Mirroring) -> [u8; 4] {
+        match mirroring {
+            Mirroring::Horizontal => [0, 0, 1, 1],
+            Mirroring::Vertical => [0, 1, 0, 1],
+            Mirroring::OneScreenLower => [0, 0, 0, 0],
+            Mirroring::OneScreenUpper => [1, 1, 1, 1],
+            Mirroring::FourScreen => [0, 1, 0, 1],
+        }
+    }
+
+    fn update_banks(&mut self) {
+        for window in 0..self.prg_windows.len() {
+            let addr = 0x6000 + window as u16 * 0x2000;
+            if let Some((target, bank, _)) = self.map_prg_addr(addr) {
+                let len = match target {
+                    Mapper5PrgTarget::Rom => self.prg_rom.len(),
+                    Mapper5PrgTarget::Ram => self.prg_ram.len(),
+                };
+                self.prg_windows[window] = (target, bank_base(bank, 0x2000, len));
+            }
+        }
+        for slot in 0..self.chr_bases.len() {
+            let bank = self.chr_bank_1k(slot);
+            self.chr_bases[slot] = bank_base(bank, 0x0400, self.chr.len());
+        }
+    }
+
+    fn prg_ram_write_enabled(&self) -> bool {
+        (self.prg_ram_protect_1 & 0x03) == 0x02 && (self.prg_ram_protect_2 & 0x03) == 0x01
+    }
+
+    fn decode_window_bank(reg: u8, window_size_kb: u8, window_offset: usize) -> usize {
+        match window_size_kb {
+            8 => (reg & 0x7F) as usize,
+            16 => ((reg & 0x7E) as usize) + ((window_offset >> 13) & 0x01),
+            32 => ((reg & 0x7C) as usize) + ((window_offset >> 13) & 0x03),
+            _ => 0,
+        }
+    }
+
+    fn map_prg_addr(&self, addr: u16) -> Option<(Mapper5PrgTarget, usize, usize)> {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            let bank = (self.prg_regs[0] & 0x7F) as usize;
+            let offset = (addr as usize) - 0x6000;
+            return Some((Mapper5PrgTarget::Ram, bank, offset));
+        }
+
+        if !(0x8000..=0xFFFF).contains(&addr) {
+            return None;
+        }
+
+        let (reg, window_size_kb, window_offset, allows_ram, force_rom) = match self.prg_mode & 0x03
+        {
+            0 => (self.prg_regs[4], 32, (addr as usize) - 0x8000, false, true),
+            1 => {
+                if addr < 0xC000 {
+                    (self.prg_regs[2], 16, (addr as usize) - 0x8000, true, false)
+                } else {
+                    (self.prg_regs[4], 16, (addr as usize) - 0xC000, false, true)
+                }
+            }
+            2 => {
+                if addr < 0xC000 {
+                    (self.prg_regs[2], 16, (addr as usize) - 0x8000, true, false)
+                } else if addr < 0xE000 {
+                    (self.prg_regs[3], 8, (addr as usize) - 0xC000, true, false)
+                } else {
+                    (self.prg_regs[4], 8, (addr as usize) - 0xE000, false, true)
+                }
+            }
+            _ => {
+                if addr < 0xA000 {
+                    (self.prg_regs[1], 8, (addr as usize) - 0x8000, true, false)
+                } else if addr < 0xC000 {
+                    (self.prg_regs[2], 8, (addr as usize) - 0xA000, true, false)
+                } else if addr < 0xE000 {
+                    (self.prg_regs[3], 8, (addr as usize) - 0xC000, true, false)
+                } else {
+                    (self.prg_regs[4], 8, (addr as usize) - 0xE000, false, true)
+                }
+            }
+        };
+
+        let target = if force_rom {
+            Mapper5PrgTarget::Rom
+        } else if allows_ram && (reg & 0x80) == 0 {
+            Mapper5PrgTarget::Ram
+        } else {
+            Mapper5PrgTarget::Rom
+        };
+
+        let bank = Self::decode_window_bank(reg, window_size_kb, window_offset);
+        let offset = window_offset & 0x1FFF;
+        Some((target, bank, offset))
+    }
+
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        self.chr_bases[addr >> 10] + (addr & 0x03FF)
+    }
+
+    /// 1 KB CHR bank in pattern table window `slot` (0-7).
+    fn chr_bank_1k(&self, slot: usize) -> usize {
+        match self.chr_mode & 0x03 {
+            0 => {
+                let base = self.chr_regs[7] as usize * 8;
+                base + slot
+            }
+            1 => {
+                let reg = if slot < 4 {
+                    self.chr_regs[3]
+                } else {
+                    self.chr_regs[7]
+                };
+                let base = reg as usize * 4;
+                base + (slot & 0x03)
+            }
+            2 => {
+                let reg = match slot {
+                    0 | 1 => self.chr_regs[1],
+                    2 | 3 => self.chr_regs[3],
+                    4 | 5 => self.chr_regs[5],
+                    _ => self.chr_regs[7],
+                };
+                let base = reg as usize * 2;
+                base + (slot & 0x01)
+            }
+            _ => self.chr_regs[slot] as usize,
+        }
+    }
+
+    /// ExRAM column and split Y of the background tile being fetched, if it
+    /// falls in the split region. $5200 bit 6 puts the region right of the
+    /// threshold tile instead of left; ExRAM modes 2 and 3 turn the split
+    /// off, as the PPU can't see ExRAM then.
+    fn split_position(&self) -> Option<(usize, usize)> {
+        let FetchPhase::Background { tile, line } = self.fetch_phase else {
+            return None;
+        };
+        if self.split_control & 0x80 == 0 || self.exram_mode >= 2 {
+            return None;
+        }
+        let right_side = self.split_control & 0x40 != 0;
+        if (tile >= self.split_control & 0x1F) != right_side {
+            return None;
+        }
+        let y = (usize::from(self.split_scroll) + usize::from(line)) % 240;
+        Some((usize::from(tile & 0x1F), y))
+    }
+
+    fn fill_attribute_byte(&self) -> u8 {
+        let bits = self.fill_attr & 0x03;
+        bits | (bits << 2) | (bits << 4) | (bits << 6)
+    }
+
+    fn clock_scanline_detector(&mut self) {
+        if !self.in_frame {
+            self.in_frame = true;
+            self.scanline_counter = 0;
+            return;
+        }
+
+        self.scanline_counter = self.scanline_counter.wrapping_add(1);
+        if self.irq_scanline_compare != 0 && self.scanline_counter == self.irq_scanline_compare {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Mapper5 {
+    state_fields!(chr if chr_is_ram, prg_ram, exram, nametable_map, prg_mode, chr_mode, exram_mode, fill_tile, fill_attr, prg_ram_protect_1, prg_ram_protect_2, prg_regs, chr_regs, chr_upper_bits, irq_scanline_compare, irq_enabled, irq_pending, in_frame, scanline_counter, last_nametable_probe, repeated_nametable_reads, scanline_detect_armed, cpu_cycles_since_ppu_read, mul_a, mul_b, prg_windows, chr_bases, audio, split_control, split_scroll, split_bank);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x5C00..=0x5FFF => self.exram[(addr as usize) - 0x5C00],
+            0x5010 => self.audio.read_pcm_status(),
+            0x5015 => self.audio.read_status(),
+            0x5204 => {
+                let status = ((self.irq_pending as u8) << 7) | ((self.in_frame as u8) << 6);
+                self.irq_pending = false;
+                status
+            }
+            0x5205 => {
+                let product = (self.mul_a as u16) * (self.mul_b as u16);
+                (product & 0xFF) as u8
+            }
+            0x5206 => {
+                let product = (self.mul_a as u16) * (self.mul_b as u16);
+                (product >> 8) as u8
+            }
+            0x6000..=0xFFFF => {
+                let (target, base) = self.prg_windows[(addr as usize - 0x6000) >> 13];
+                let idx = base + (addr as usize & 0x1FFF);
+                let value = match target {
+                    Mapper5PrgTarget::Rom => self.prg_rom[idx],
+                    Mapper5PrgTarget::Ram => self.prg_ram[idx],
+                };
+                if (0x8000..=0xBFFF).contains(&addr) {
+                    self.audio.observe_prg_read(value);
+                }
+                value
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_read_expansion(&mut self, addr: u16) -> Option<u8> {
+        matches!(addr, 0x5010 | 0x5015 | 0x5204..=0x5206 | 0x5C00..=0x5FFF)
+            .then(|| self.cpu_read(addr))
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5015 => self.audio.write(addr, value),
+            0x5100 => {
+                self.prg_mode = value & 0x03;
+                self.update_banks();
+            }
+            0x5101 => {
+                self.chr_mode = value & 0x03;
+                self.update_banks();
+            }
+            0x5102 => self.prg_ram_protect_1 = value,
+            0x5103 => self.prg_ram_protect_2 = value,
+            0x5104 => self.exram_mode = value & 0x03,
+            0x5105 => {
+                for (idx, slot) in self.nametable_map.iter_mut().enumerate() {
+                    *slot = (value >> (idx * 2)) & 0x03;
+                }
+            }
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_attr = value & 0x03,
+            0x5113..=0x5117 => {
+                self.prg_regs[(addr - 0x5113) as usize] = value;
+                self.update_banks();
+            }
+            0x5120..=0x512B => {
+                let idx = (addr as usize) - 0x5120;
+                self.chr_regs[idx] = ((self.chr_upper_bits as u16) << 8) | value as u16;
+                self.update_banks();
+            }
+            0x5130 => self.chr_upper_bits = value & 0x03,
+            0x5200 => self.split_control = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_bank = value,
+            0x5203 => self.irq_scanline_compare = value,
+            0x5204 => self.irq_enabled = (value & 0x80) != 0,
+            0x5205 => self.mul_a = value,
+            0x5206 => self.mul_b = value,
+            0x5C00..=0x5FFF if self.exram_mode != 3 => {
+                self.exram[(addr as usize) - 0x5C00] = value;
+            }
+            0x6000..=0xFFFF => {
+                if !self.prg_ram_write_enabled() {
+                    return;
+                }
+                let (target, base) = self.prg_windows[(addr as usize - 0x6000) >> 13];
+                if target == Mapper5PrgTarget::Ram {
+                    self.prg_ram[base + (addr as usize & 0x1FFF)] = value;
+                    self.prg_ram_dirty = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        if let Some((_, y)) = self.split_position() {
+            // The split's patterns come from its own 4 KB bank, at its own
+            // fine Y.
+            let base = bank_base(self.split_bank as usize, 0x1000, self.chr.len());
+            let offset = (addr as usize & 0x0FF8) | (y & 0x07);
+            return self.chr[(base + offset) % self.chr.len()];
+        }
+        self.chr[self.map_chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.map_chr_addr(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn ppu_nametable_read(&mut self, addr: u16, vram: &[u8; 4096]) -> Option<u8> {
+        let mirrored = 0x2000 + ((addr - 0x2000) % 0x1000);
+        let table = ((mirrored - 0x2000) / 0x400) as usize;
+        let offset = ((mirrored - 0x2000) % 0x400) as usize;
+
+        if let Some((column, y)) = self.split_position() {
+            let row = y / 8;
+            if offset < 0x3C0 {
+                return Some(self.exram[row * 32 + column]);
+            }
+            // The PPU picks the quadrant from its own scroll, so every
+            // quadrant gets the split tile's bits.
+            let attr = self.exram[0x3C0 + (row / 4) * 8 + column / 4];
+            let shift = (row & 0x02) << 1 | (column & 0x02);
+            return Some(((attr >> shift) & 0x03) * 0x55);
+        }
+
+        let value = match self.nametable_map[table] & 0x03 {
+            0 | 1 => {
+                let page = (self.nametable_map[table] & 0x01) as usize;
+                vram[page * 0x400 + offset]
+            }
+            2 => {
+                if self.exram_mode >= 2 {
+                    0
+                } else {
+                    self.exram[offset]
+                }
+            }
+            _ => {
+                if offset < 0x3C0 {
+                    self.fill_tile
+                } else {
+                    self.fill_attribute_byte()
+                }
+            }
+        };
+
+        Some(value)
+    }
+
+    fn ppu_nametable_write(&mut self, addr: u16, value: u8, vram: &mut [u8; 4096]) -> bool {
+        let mirrored = 0x2000 + ((addr - 0x2000) % 0x1000);
+        let table = ((mirrored - 0x2000) / 0x400) as usize;
+        let offset = ((mirrored - 0x2000) % 0x400) as usize;
+
+        match self.nametable_map[table] & 0x03 {
+            0 | 1 => {
+                let page = (self.nametable_map[table] & 0x01) as usize;
+                vram[page * 0x400 + offset] = value;
+            }
+            2 if self.exram_mode != 3 => {
+                self.exram[offset] = value;
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::FourScreen
+    }
+
+    fn nametable_source(&self, table: usize) -> NametableSource {
+        match self.nametable_map[table] & 0x03 {
+            page @ (0 | 1) => NametableSource::Vram(page),
+            2 => NametableSource::ExRam,
+            _ => NametableSource::Fill,
+        }
+    }
+
+    fn debug_peek_nametable(&self, table: usize, offset: usize) -> u8 {
+        match self.nametable_source(table) {
+            NametableSource::ExRam if self.exram_mode < 2 => self.exram[offset],
+            NametableSource::Fill if offset < 0x3C0 => self.fill_tile,
+            NametableSource::Fill => self.fill_attribute_byte(),
+            _ => 0,
+        }
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.map_chr_addr(addr)]
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        self.audio.tick();
+        self.cpu_cycles_since_ppu_read = self.cpu_cycles_since_ppu_read.saturating_add(1).min(3);
+        if self.cpu_cycles_since_ppu_read >= 3 {
+            self.in_frame = false;
+            self.scanline_counter = 0;
+            self.irq_pending = false;
+            self.scanline_detect_armed = false;
+            self.repeated_nametable_reads = 0;
+        }
+    }
+
+    fn set_fetch_phase(&mut self, phase: FetchPhase) {
+        self.fetch_phase = phase;
+    }
+
+    fn notify_ppu_read_addr(&mut self, addr: u16) {
+        self.cpu_cycles_since_ppu_read = 0;
+
+        if self.scanline_detect_armed {
+            self.clock_scanline_detector();
+            self.scanline_detect_armed = false;
+        }
+
+        if (0x2000..=0x3EFF).contains(&addr) {
+            let probe = 0x2000 + ((addr - 0x2000) % 0x1000);
+            if probe < 0x3000 {
+                if probe == self.last_nametable_probe {
+                    self.repeated_nametable_reads = self.repeated_nametable_reads.saturating_add(1);
+                } else {
+                    self.last_nametable_probe = probe;
+                    self.repeated_nametable_reads = 1;
+                }
+
+                if self.repeated_nametable_reads >= 3 {
+                    self.scanline_detect_armed = true;
+                    self.repeated_nametable_reads = 0;
+                }
+                return;
+            }
+        }
+
+        self.repeated_nametable_reads = 0;
+    }
+
+    fn irq_pending(&self) -> bool {
+        (self.irq_pending && self.irq_enabled) || self.audio.irq_pending()
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "MMC5 prg_mode={} chr_mode={} exram_mode={} prg=[{:02X},{:02X},{:02X},{:02X},{:02X}] nt=[{},{},{},{}] scanline={}/{} irq={}/{}",
+            self.prg_mode,
+            self.chr_mode,
+            self.exram_mode,
+            self.prg_regs[0],
+            self.prg_regs[1],
+            self.prg_regs[2],
+            self.prg_regs[3],
+            self.prg_regs[4],
+            self.nametable_map[0],
+            self.nametable_map[1],
+            self.nametable_map[2],
+            self.nametable_map[3],
+            self.scanline_counter,
+            self.irq_scanline_compare,
+            self.irq_pending,
+            self.irq_enabled
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper19 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    chr_nt_banks: [u8; 12],
+    prg_bank_8000: u8,
+    prg_bank_a000: u8,
+    prg_bank_c000: u8,
+    disable_chrram_low: bool,
+    disable_chrram_high: bool,
+    ram_write_protect: u8,
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    ciram_shadow: [u8; 0x800],
+    internal_ram: [u8; 128],
+    internal_addr: u8,
+    internal_auto_inc: bool,
+}
+
+impl Mapper19 {
+    fn new(cart: Cartridge) -> Self {
+        let mut chr_nt_banks = [0u8; 12];
+        for (idx, bank) in chr_nt_banks.iter_mut().enumerate() {
+            *bank = idx as u8;
+        }
+
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            chr_nt_banks,
+            prg_bank_8000: 0,
+            prg_bank_a000: 1,
+            prg_bank_c000: 2,
+            disable_chrram_low: false,
+            disable_chrram_high: false,
+            ram_write_protect: 0x4F,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            ciram_shadow: [0; 0x800],
+            internal_ram: [0; 128],
+            internal_addr: 0,
+            internal_auto_inc: false,
+        }
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn chr_bank_count_1k(&self) -> usize {
+        (self.chr.len() / 0x0400).max(1)
+    }
+
+    fn read_prg_rom_8k(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count_8k();
+        self.prg_rom[(bank * 0x2000 + offset) % self.prg_rom.len()]
+    }
+
+    fn map_chr_bank(&self, bank: u8, offset: usize) -> usize {
+        let mapped = (bank as usize) % self.chr_bank_count_1k();
+        mapped * 0x0400 + offset
+    }
+
+    fn pattern_slot_uses_ciram(&self, slot: usize, bank: u8) -> bool {
+        if bank < 0xE0 {
+            return false;
+        }
+        if slot < 4 {
+            !self.disable_chrram_low
+        } else {
+            !self.disable_chrram_high
+        }
+    }
+
+    fn ciram_index(bank: u8, offset: usize) -> usize {
+        ((bank as usize) & 0x01) * 0x0400 + offset
+    }
+
+    fn prg_ram_write_enabled_for_addr(&self, addr: u16) -> bool {
+        let key = (self.ram_write_protect >> 4) & 0x0F;
+        if key != 0x04 {
+            return false;
+        }
+        let window = ((addr - 0x6000) / 0x0800) as usize;
+        ((self.ram_write_protect >> window) & 0x01) == 0
+    }
+
+    fn read_internal_ram(&mut self) -> u8 {
+        let idx = (self.internal_addr & 0x7F) as usize;
+        let value = self.internal_ram[idx];
+        if self.internal_auto_inc {
+            self.internal_addr = (self.internal_addr.wrapping_add(1)) & 0x7F;
+        }
+        value
+    }
+
+    fn write_internal_ram(&mut self, value: u8) {
+        let idx = (self.internal_addr & 0x7F) as usize;
+        self.internal_ram[idx] = value;
+        if self.internal_auto_inc {
+            self.internal_addr = (self.internal_addr.wrapping_add(1)) & 0x7F;
+        }
+    }
+}
+
+impl Mapper for Mapper19 {
+    state_fields!(chr if chr_is_ram, prg_ram, chr_nt_banks, prg_bank_8000, prg_bank_a000, prg_bank_c000, disable_chrram_low, disable_chrram_high, ram_write_protect, irq_counter, irq_enabled, irq_pending, ciram_shadow, internal_ram, internal_addr, internal_auto_inc);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4800..=0x4FFF => self.read_internal_ram(),
+            0x5000..=0x57FF => (self.irq_counter & 0x00FF) as u8,
+            0x5800..=0x5FFF => {
+                ((self.irq_enabled as u8) << 7) | ((self.irq_counter >> 8) as u8 & 0x7F)
+            }
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0x9FFF => {
+                self.read_prg_rom_8k(self.prg_bank_8000 as usize, addr as usize - 0x8000)
+            }
+            0xA000..=0xBFFF => {
+                self.read_prg_rom_8k(self.prg_bank_a000 as usize, addr as usize - 0xA000)
+            }
+            0xC000..=0xDFFF => {
+                self.read_prg_rom_8k(self.prg_bank_c000 as usize, addr as usize - 0xC000)
+            }
+            0xE000..=0xFFFF => {
+                let last = self.prg_bank_count_8k().saturating_sub(1);
+                self.read_prg_rom_8k(last, addr as usize - 0xE000)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_read_expansion(&mut self, addr: u16) -> Option<u8> {
+        (addr >= 0x4800).then(|| self.cpu_read(addr))
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4800..=0x4FFF => self.write_internal_ram(value),
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | value as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (((value as u16) & 0x7F) << 8);
+                self.irq_enabled = (value & 0x80) != 0;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_write_enabled_for_addr(addr) => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0xDFFF => {
+                let idx = ((addr - 0x8000) / 0x0800) as usize;
+                if idx < self.chr_nt_banks.len() {
+                    self.chr_nt_banks[idx] = value;
+                }
+            }
+            0xE000..=0xE7FF => {
+                self.prg_bank_8000 = value & 0x3F;
+            }
+            0xE800..=0xEFFF => {
+                self.prg_bank_a000 = value & 0x3F;
+                self.disable_chrram_low = (value & 0x40) != 0;
+                self.disable_chrram_high = (value & 0x80) != 0;
+            }
+            0xF000..=0xF7FF => {
+                self.prg_bank_c000 = value & 0x3F;
+            }
+            0xF800..=0xFFFF => {
+                self.ram_write_protect = value;
+                self.internal_addr = value & 0x7F;
+                self.internal_auto_inc = (value & 0x80) != 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let slot = ((addr as usize) & 0x1FFF) / 0x0400;
+        let offset = (addr as usize) & 0x03FF;
+        let bank = self.chr_nt_banks[slot];
+
+        if self.pattern_slot_uses_ciram(slot, bank) {
+            let idx = Self::ciram_index(bank, offset);
+            self.ciram_shadow[idx]
+        } else {
+            let idx = self.map_chr_bank(bank, offset);
+            self.chr[idx % self.chr.len()]
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        let slot = ((addr as usize) & 0x1FFF) / 0x0400;
+        let offset = (addr as usize) & 0x03FF;
+        let bank = self.chr_nt_banks[slot];
+
+        if self.pattern_slot_uses_ciram(slot, bank) {
+            let idx = Self::ciram_index(bank, offset);
+            self.ciram_shadow[idx] = value;
+            return;
+        }
+
+        if self.chr_is_ram {
+            let idx = self.map_chr_bank(bank, offset) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn ppu_nametable_read(&mut self, addr: u16, vram: &[u8; 4096]) -> Option<u8> {
+        let mirrored = 0x2000 + ((addr - 0x2000) % 0x1000);
+        let slot = ((mirrored - 0x2000) / 0x0400) as usize;
+        let offset = ((mirrored - 0x2000) % 0x0400) as usize;
+        let bank = self.chr_nt_banks[8 + slot];
+
+        if bank >= 0xE0 {
+            let idx = Self::ciram_index(bank, offset);
+            self.ciram_shadow[idx] = vram[idx];
+            Some(vram[idx])
+        } else {
+            let idx = self.map_chr_bank(bank, offset);
+            Some(self.chr[idx % self.chr.len()])
+        }
+    }
+
+    fn ppu_nametable_write(&mut self, addr: u16, value: u8, vram: &mut [u8; 4096]) -> bool {
+        let mirrored = 0x2000 + ((addr - 0x2000) % 0x1000);
+        let slot = ((mirrored - 0x2000) / 0x0400) as usize;
+        let offset = ((mirrored - 0x2000) % 0x0400) as usize;
+        let bank = self.chr_nt_banks[8 + slot];
+
+        if bank >= 0xE0 {
+            let idx = Self::ciram_index(bank, offset);
+            vram[idx] = value;
+            self.ciram_shadow[idx] = value;
+        } else if self.chr_is_ram {
+            let idx = self.map_chr_bank(bank, offset) % self.chr.len();
+            self.chr[idx] = value;
+        }
+
+        true
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::FourScreen
+    }
+
+    fn nametable_source(&self, table: usize) -> NametableSource {
+        let bank = self.chr_nt_banks[8 + table];
+        if bank >= 0xE0 {
+            NametableSource::Vram(bank & 0x01)
+        } else {
+            NametableSource::Chr(bank as u16)
+        }
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if !self.irq_enabled || self.irq_pending {
+            return;
+        }
+
+        if self.irq_counter < 0x7FFF {
+            self.irq_counter = self.irq_counter.wrapping_add(1);
+            if self.irq_counter == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "Namco163 prg=[{:02X},{:02X},{:02X},FF] nt=[{:02X},{:02X},{:02X},{:02X}] irq={:04X}/{}{} wp={:02X}",
+            self.prg_bank_8000,
+            self.prg_bank_a000,
+            self.prg_bank_c000,
+            self.chr_nt_banks[8],
+            self.chr_nt_banks[9],
+            self.chr_nt_banks[10],
+            self.chr_nt_banks[11],
+            self.irq_counter,
+            self.irq_enabled,
+            if self.irq_pending { " pending" } else { "" },
+            self.ram_write_protect
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Sunsoft FME-7 and 5B: a command register at $8000 and its parameter at
+/// $A000 switch four 8 KB PRG banks and eight 1 KB CHR banks and run a
+/// 16-bit CPU-cycle IRQ counter. The 5B adds its audio chip at $C000 and
+/// $E000, which FME-7 games never write, so one mapper serves both.
+struct Mapper69 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    mirroring: Mirroring,
+    command: u8,
+    chr_banks: [u8; 8],
+    prg_banks: [u8; 3],
+    prg_bank_6000: u8,
+    map_6000_to_ram: bool,
+    ram_enable: bool,
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_pending: bool,
+    audio: Sunsoft5b,
+}
+
+impl Mapper69 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            mirroring: cart.mirroring,
+            command: 0,
+            chr_banks: [0, 1, 2, 3, 4, 5, 6, 7],
+            prg_banks: [0, 1, 2],
+            prg_bank_6000: 0,
+            map_6000_to_ram: true,
+            ram_enable: true,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_pending: false,
+            audio: Sunsoft5b::default(),
+        }
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn chr_bank_count_1k(&self) -> usize {
+        (self.chr.len() / 0x0400).max(1)
+    }
+
+    fn prg_ram_bank_count_8k(&self) -> usize {
+        (self.prg_ram.len() / 0x2000).max(1)
+    }
+
+    fn read_prg_8k(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count_8k();
+        let idx = bank * 0x2000 + offset;
+        self.prg_rom[idx % self.prg_rom.len()]
+    }
+
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let slot = ((addr as usize) & 0x1FFF) / 0x0400;
+        let bank = (self.chr_banks[slot] as usize) % self.chr_bank_count_1k();
+        bank * 0x0400 + ((addr as usize) & 0x03FF)
+    }
+
+    fn write_command_param(&mut self, value: u8) {
+        match self.command & 0x0F {
+            0x0..=0x7 => self.chr_banks[(self.command & 0x07) as usize] = value,
+            0x8 => {
+                self.prg_bank_6000 = value & 0x3F;
+                self.map_6000_to_ram = (value & 0x40) != 0;
+                self.ram_enable = (value & 0x80) != 0;
+            }
+            0x9 => self.prg_banks[0] = value & 0x3F,
+            0xA => self.prg_banks[1] = value & 0x3F,
+            0xB => self.prg_banks[2] = value & 0x3F,
+            0xC => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            }
+            0xD => {
+                // On FME-7, writes to IRQ control acknowledge pending IRQ.
+                self.irq_pending = false;
+                self.irq_enabled = (value & 0x01) != 0;
+                self.irq_counter_enabled = (value & 0x80) != 0;
+            }
+            0xE => {
+                self.irq_counter = (self.irq_counter & 0xFF00) | value as u16;
+            }
+            0xF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mapper69 {
+    state_fields!(chr if chr_is_ram, prg_ram, mirroring, command, chr_banks, prg_banks, prg_bank_6000, map_6000_to_ram, ram_enable, irq_counter, irq_enabled, irq_counter_enabled, irq_pending, audio);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let offset = (addr as usize) - 0x6000;
+                if self.map_6000_to_ram {
+                    if !self.ram_enable {
+                        return 0;
+                    }
+                    let bank = (self.prg_bank_6000 as usize) % self.prg_ram_bank_count_8k();
+                    let idx = bank * 0x2000 + offset;
+                    self.prg_ram[idx % self.prg_ram.len()]
+                } else {
+                    self.read_prg_8k(self.prg_bank_6000 as usize, offset)
+                }
+            }
+            0x8000..=0x9FFF => self.read_prg_8k(self.prg_banks[0] as usize, addr as usize - 0x8000),
+            0xA000..=0xBFFF => self.read_prg_8k(self.prg_banks[1] as usize, addr as usize - 0xA000),
+            0xC000..=0xDFFF => self.read_prg_8k(self.prg_banks[2] as usize, addr as usize - 0xC000),
+            0xE000..=0xFFFF => {
+                let last = self.prg_bank_count_8k().saturating_sub(1);
+                self.read_prg_8k(last, addr as usize - 0xE000)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.map_6000_to_ram && self.ram_enable => {
+                let offset = (addr as usize) - 0x6000;
+                let bank = (self.prg_bank_6000 as usize) % self.prg_ram_bank_count_8k();
+                let idx = bank * 0x2000 + offset;
+                let mapped = idx % self.prg_ram.len();
+                self.prg_ram[mapped] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_command_param(value),
+            0xC000..=0xDFFF => self.audio.select(value),
+            0xE000..=0xFFFF => self.audio.write(value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let idx = self.map_chr_addr(addr) % self.chr.len();
+        self.chr[idx]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.map_chr_addr(addr) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        self.audio.tick();
+        if !self.irq_counter_enabled {
+            return;
+        }
+        let previous = self.irq_counter;
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if previous == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "FME7 cmd={} prg=[{:02X},{:02X},{:02X}] 6000={:02X} ram={} en={} irq={:04X}/{}{}",
+            self.command,
+            self.prg_banks[0],
+            self.prg_banks[1],
+            self.prg_banks[2],
+            self.prg_bank_6000,
+            self.map_6000_to_ram,
+            self.ram_enable,
+            self.irq_counter,
+            self.irq_counter_enabled,
+            if self.irq_pending { " pending" } else { "" }
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper9 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    prg_bank: u8,
+    chr_fd_0000: u8,
+    chr_fe_0000: u8,
+    chr_fd_1000: u8,
+    chr_fe_1000: u8,
+    latch0_is_fe: bool,
+    latch1_is_fe: bool,
+    mirroring: Mirroring,
+}
+
+impl Mapper9 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            prg_bank: 0,
+            chr_fd_0000: 0,
+            chr_fe_0000: 0,
+            chr_fd_1000: 0,
+            chr_fe_1000: 0,
+            latch0_is_fe: true,
+            latch1_is_fe: true,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn read_prg_8k(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count_8k();
+        self.prg_rom[(bank * 0x2000 + offset) % self.prg_rom.len()]
+    }
+
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let bank = if addr < 0x1000 {
+            if self.latch0_is_fe {
+                self.chr_fe_0000
+            } else {
+                self.chr_fd_0000
+            }
+        } else if self.latch1_is_fe {
+            self.chr_fe_1000
+        } else {
+            self.chr_fd_1000
+        } as usize;
+
+        let base = bank * 0x1000;
+        let offset = (addr as usize) & 0x0FFF;
+        (base + offset) % self.chr.len()
+    }
+
+    fn update_latches(&mut self, addr: u16) {
+        // MMC2 latch trigger addresses selected by PPU pattern fetches.
+        match addr {
+            0x0FD8 => self.latch0_is_fe = false,
+            0x0FE8 => self.latch0_is_fe = true,
+            0x1FD8..=0x1FDF => self.latch1_is_fe = false,
+            0x1FE8..=0x1FEF => self.latch1_is_fe = true,
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mapper9 {
+    state_fields!(chr if chr_is_ram, prg_ram, prg_bank, chr_fd_0000, chr_fe_0000, chr_fd_1000, chr_fe_1000, latch0_is_fe, latch1_is_fe, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0x9FFF => self.read_prg_8k(self.prg_bank as usize, addr as usize - 0x8000),
+            0xA000..=0xBFFF => {
+                let banks = self.prg_bank_count_8k();
+                self.read_prg_8k(banks.saturating_sub(3), addr as usize - 0xA000)
+            }
+            0xC000..=0xDFFF => {
+                let banks = self.prg_bank_count_8k();
+                self.read_prg_8k(banks.saturating_sub(2), addr as usize - 0xC000)
+            }
+            0xE000..=0xFFFF => {
+                let banks = self.prg_bank_count_8k();
+                self.read_prg_8k(banks.saturating_sub(1), addr as usize - 0xE000)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0xA000..=0xAFFF => self.prg_bank = value & 0x0F,
+            0xB000..=0xBFFF => self.chr_fd_0000 = value & 0x1F,
+            0xC000..=0xCFFF => self.chr_fe_0000 = value & 0x1F,
+            0xD000..=0xDFFF => self.chr_fd_1000 = value & 0x1F,
+            0xE000..=0xEFFF => self.chr_fe_1000 = value & 0x1F,
+            0xF000..=0xFFFF => {
+                self.mirroring = if (value & 0x01) == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let idx = self.map_chr_addr(addr & 0x1FFF);
+        self.chr[idx]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.map_chr_addr(addr & 0x1FFF);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn notify_ppu_read_addr(&mut self, addr: u16) {
+        self.update_latches(addr & 0x1FFF);
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper66 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper66 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_bank_count_32k(&self) -> usize {
+        (self.prg_rom.len() / 0x8000).max(1)
+    }
+
+    fn chr_bank_count_8k(&self) -> usize {
+        (self.chr.len() / 0x2000).max(1)
+    }
+}
+
+impl Mapper for Mapper66 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let bank = (self.prg_bank as usize) % self.prg_bank_count_32k();
+                let offset = (addr as usize) & 0x7FFF;
+                let idx = bank * 0x8000 + offset;
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if (0x8000..=0xFFFF).contains(&addr) {
+            self.chr_bank = value & 0x03;
+            self.prg_bank = (value >> 4) & 0x03;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let bank = (self.chr_bank as usize) % self.chr_bank_count_8k();
+        let offset = (addr as usize) & 0x1FFF;
+        let idx = bank * 0x2000 + offset;
+        self.chr[idx % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let bank = (self.chr_bank as usize) % self.chr_bank_count_8k();
+            let offset = (addr as usize) & 0x1FFF;
+            let idx = bank * 0x2000 + offset;
+            let mapped = idx % self.chr.len();
+            self.chr[mapped] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Sunsoft-3: 16 KB PRG at $8000 with the last bank fixed, four 2 KB CHR
+/// banks, and a 16-bit IRQ counter loaded by two writes to $C800, high
+/// byte first. Registers decode A15-A11 and sit at $x800. Once enabled
+/// the counter decrements every CPU cycle and raises the IRQ when it
+/// wraps from $0000, stopping itself there.
+struct Mapper67 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank: u8,
+    chr_banks: [u8; 4],
+    mirroring: Mirroring,
+    irq_counter: u16,
+    /// The next $C800 write is the low byte.
+    irq_write_low: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl Mapper67 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_banks: [0; 4],
+            mirroring: cart.mirroring,
+            irq_counter: 0,
+            irq_write_low: false,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        let base = bank_base(self.chr_banks[addr >> 11] as usize, 0x0800, self.chr.len());
+        (base + (addr & 0x07FF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper67 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_banks, mirroring, irq_counter, irq_write_low, irq_enabled, irq_pending);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let last = (self.prg_rom.len() / 0x4000).max(1) - 1;
+        let (bank, offset) = match addr {
+            0x8000..=0xBFFF => (self.prg_bank as usize, addr as usize - 0x8000),
+            0xC000..=0xFFFF => (last, addr as usize - 0xC000),
+            _ => return 0,
+        };
+        self.prg_rom[(bank_base(bank, 0x4000, self.prg_rom.len()) + offset) % self.prg_rom.len()]
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr & 0xF800 {
+            0x8000 => self.irq_pending = false,
+            0x8800 | 0x9800 | 0xA800 | 0xB800 => {
+                self.chr_banks[((addr - 0x8800) >> 12) as usize] = value;
+            }
+            0xC800 => {
+                self.irq_counter = if self.irq_write_low {
+                    (self.irq_counter & 0xFF00) | u16::from(value)
+                } else {
+                    (self.irq_counter & 0x00FF) | (u16::from(value) << 8)
+                };
+                self.irq_write_low = !self.irq_write_low;
+            }
+            0xD800 => {
+                self.irq_enabled = value & 0x10 != 0;
+                self.irq_write_low = false;
+            }
+            0xE800 => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            }
+            0xF800 => self.prg_bank = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0xFFFF {
+            self.irq_enabled = false;
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "Sunsoft-3 prg_bank=${:02X} chr=[{:02X},{:02X},{:02X},{:02X}] irq_counter=${:04X} en={} pending={}",
+            self.prg_bank,
+            self.chr_banks[0],
+            self.chr_banks[1],
+            self.chr_banks[2],
+            self.chr_banks[3],
+            self.irq_counter,
+            self.irq_enabled,
+            self.irq_pending
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Color Dreams: GxROM's single register with the nibbles the other way
+/// round, PRG 32 KB bank in bits 0-1 and CHR 8 KB bank in bits 4-7. Bits
+/// 2-3 drove the lockout defeat and do nothing here.
+struct Mapper11 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper11 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let base = bank_base(self.chr_bank as usize, 0x2000, self.chr.len());
+        (base + (addr as usize & 0x1FFF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper11 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let base = bank_base(self.prg_bank as usize, 0x8000, self.prg_rom.len());
+                self.prg_rom[(base + (addr as usize & 0x7FFF)) % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if (0x8000..=0xFFFF).contains(&addr) {
+            self.prg_bank = value & 0x03;
+            self.chr_bank = value >> 4;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "Color Dreams prg_bank=${:02X} chr_bank=${:02X}",
+            self.prg_bank, self.chr_bank
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Rumble Station 15-in-1: Color Dreams games behind an outer register at
+/// $6000-$7FFF (CHR high bits 4-7, PRG high bits 0-3) that picks the game.
+/// The inner register at $8000-$FFFF holds PRG bit 0 in bit 0 and CHR bits
+/// 0-2 in bits 4-6.
+struct Mapper46 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    outer: u8,
+    inner: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper46 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            outer: 0,
+            inner: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_bank(&self) -> usize {
+        (((self.outer & 0x0F) << 1) | (self.inner & 0x01)) as usize
+    }
+
+    fn chr_bank(&self) -> usize {
+        (((self.outer >> 4) << 3) | ((self.inner >> 4) & 0x07)) as usize
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let base = bank_base(self.chr_bank(), 0x2000, self.chr.len());
+        (base + (addr as usize & 0x1FFF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper46 {
+    state_fields!(chr if chr_is_ram, outer, inner, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let base = bank_base(self.prg_bank(), 0x8000, self.prg_rom.len());
+                self.prg_rom[(base + (addr as usize & 0x7FFF)) % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.outer = value,
+            0x8000..=0xFFFF => self.inner = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "Rumble Station outer=${:02X} inner=${:02X} prg_bank=${:02X} chr_bank=${:02X}",
+            self.outer,
+            self.inner,
+            self.prg_bank(),
+            self.chr_bank()
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Irem G-101: two switchable 8 KB PRG banks and eight 1 KB CHR banks.
+/// Bit 1 of $9000 swaps the $8000 bank with the fixed second-to-last one
+/// at $C000, and bit 0 picks vertical or horizontal mirroring. Submapper 1
+/// (Major League) ties the nametables to one-screen and has no mirroring
+/// bit.
+struct Mapper32 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u8; 8],
+    mirroring: Mirroring,
+    fixed_mirroring: bool,
+}
+
+impl Mapper32 {
+    fn new(cart: Cartridge) -> Self {
+        let fixed_mirroring = cart.submapper_id == 1;
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_banks: [0, 1],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            mirroring: if fixed_mirroring {
+                Mirroring::OneScreenLower
+            } else {
+                cart.mirroring
+            },
+            fixed_mirroring,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let second_last = (self.prg_rom.len() / 0x2000).max(2) - 2;
+        let (first, third) = if self.prg_swap {
+            (second_last, self.prg_banks[0] as usize)
+        } else {
+            (self.prg_banks[0] as usize, second_last)
+        };
+        let bank = match addr {
+            0x8000..=0x9FFF => first,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize,
+            0xC000..=0xDFFF => third,
+            _ => second_last + 1,
+        };
+        (bank_base(bank, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        let base = bank_base(self.chr_banks[addr >> 10] as usize, 0x0400, self.chr.len());
+        (base + (addr & 0x03FF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper32 {
+    state_fields!(chr if chr_is_ram, prg_banks, prg_swap, chr_banks, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x8FFF => self.prg_banks[0] = value & 0x1F,
+            0x9000..=0x9FFF => {
+                self.prg_swap = value & 0x02 != 0;
+                if !self.fixed_mirroring {
+                    self.mirroring = if value & 0x01 == 0 {
+                        Mirroring::Vertical
+                    } else {
+                        Mirroring::Horizontal
+                    };
+                }
+            }
+            0xA000..=0xAFFF => self.prg_banks[1] = value & 0x1F,
+            0xB000..=0xBFFF => self.chr_banks[(addr & 0x07) as usize] = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "G-101 prg=[{:02X},{:02X}] swap={} chr={:02X?} mirroring={:?}",
+            self.prg_banks[0], self.prg_banks[1], self.prg_swap, self.chr_banks, self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// CPU cycles the TC0690's IRQ line lags behind the counter reaching
+/// zero. Games written for it (The Flintstones, Don Doko Don 2) split the
+/// screen a few pixels late otherwise.
+const TC0690_IRQ_DELAY: u8 = 4;
+
+/// Taito TC0190 (mapper 33) and its successor TC0690 (mapper 48): two
+/// switchable 8 KB PRG banks at $8000 and $A000 with the last two fixed,
+/// two 2 KB and four 1 KB CHR banks. Registers decode A15, A14, A13, A1
+/// and A0. The TC0190 keeps mirroring in bit 6 of $8000; the TC0690 moves
+/// it to $E000 and adds an MMC3-style scanline IRQ at $C000-$C003.
+struct Mapper33 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    tc0690: bool,
+    prg_banks: [u8; 2],
+    /// 2 KB banks at $0000 and $0800, then 1 KB banks at $1000-$1C00.
+    chr_banks: [u8; 6],
+    mirroring: Mirroring,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    /// CPU cycles until a counted-out IRQ reaches the CPU.
+    irq_delay: u8,
+    last_a12: bool,
+    a12_low_cycles: u8,
+}
+
+impl Mapper33 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            tc0690: cart.mapper_id == 48,
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_banks: [0, 1],
+            chr_banks: [0; 6],
+            mirroring: cart.mirroring,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            irq_delay: 0,
+            last_a12: false,
+            a12_low_cycles: 0,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let count = (self.prg_rom.len() / 0x2000).max(1);
+        let bank = match addr {
+            0x8000..=0x9FFF => self.prg_banks[0] as usize,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize,
+            0xC000..=0xDFFF => count.saturating_sub(2),
+            _ => count - 1,
+        };
+        (bank_base(bank, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        let base = if addr < 0x1000 {
+            bank_base(self.chr_banks[addr >> 11] as usize, 0x0800, self.chr.len()) + (addr & 0x07FF)
+        } else {
+            bank_base(
+                self.chr_banks[2 + ((addr - 0x1000) >> 10)] as usize,
+                0x0400,
+                self.chr.len(),
+            ) + (addr & 0x03FF)
+        };
+        base % self.chr.len()
+    }
+
+    fn set_mirroring(&mut self, value: u8) {
+        self.mirroring = if value & 0x40 == 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+    }
+
+    fn monitor_ppu_a12(&mut self, addr: u16) {
+        if !self.tc0690 {
+            return;
+        }
+        let a12 = (addr & 0x1000) != 0;
+        if !a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        } else {
+            if !self.last_a12 && self.a12_low_cycles >= 8 {
+                self.clock_irq_counter();
+            }
+            self.a12_low_cycles = 0;
+        }
+        self.last_a12 = a12;
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_delay = TC0690_IRQ_DELAY;
+        }
+    }
+}
+
+impl Mapper for Mapper33 {
+    state_fields!(chr if chr_is_ram, prg_banks, chr_banks, mirroring, irq_latch, irq_counter, irq_reload, irq_enabled, irq_pending, irq_delay, last_a12, a12_low_cycles);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr & 0xE003 {
+            0x8000 => {
+                self.prg_banks[0] = value & 0x3F;
+                if !self.tc0690 {
+                    self.set_mirroring(value);
+                }
+            }
+            0x8001 => self.prg_banks[1] = value & 0x3F,
+            0x8002 => self.chr_banks[0] = value,
+            0x8003 => self.chr_banks[1] = value,
+            0xA000..=0xA003 => self.chr_banks[2 + (addr & 0x03) as usize] = value,
+            0xC000 if self.tc0690 => self.irq_latch = value ^ 0xFF,
+            0xC001 if self.tc0690 => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xC002 if self.tc0690 => self.irq_enabled = true,
+            0xC003 if self.tc0690 => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+                self.irq_delay = 0;
+            }
+            0xE000 if self.tc0690 => self.set_mirroring(value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if self.irq_delay > 0 {
+            self.irq_delay -= 1;
+            if self.irq_delay == 0 {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn notify_ppu_read_addr(&mut self, addr: u16) {
+        self.monitor_ppu_a12(addr);
+    }
+
+    fn notify_ppu_write_addr(&mut self, addr: u16) {
+        self.monitor_ppu_a12(addr);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "{} prg=[{:02X},{:02X}] chr=[{:02X},{:02X},{:02X},{:02X},{:02X},{:02X}] irq_latch=${:02X} irq_counter=${:02X} en={} pending={}",
+            if self.tc0690 { "TC0690" } else { "TC0190" },
+            self.prg_banks[0],
+            self.prg_banks[1],
+            self.chr_banks[0],
+            self.chr_banks[1],
+            self.chr_banks[2],
+            self.chr_banks[3],
+            self.chr_banks[4],
+            self.chr_banks[5],
+            self.irq_latch,
+            self.irq_counter,
+            self.irq_enabled,
+            self.irq_pending
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Mapper 34 covers two unrelated boards that both switch 32 KB of PRG:
+/// Nintendo's BNROM, banked by writes to $8000-$FFFF with unbanked CHR
+/// RAM, and AVE's NINA-001, with registers at $7FFD-$7FFF in front of its
+/// work RAM and two 4 KB CHR ROM banks. Submapper 1 is NINA-001 and 2 is
+/// BNROM; otherwise more than 8 KB of CHR means NINA-001. Mapper 39 (Study
+/// & Game 32-in-1) is BNROM with more PRG and uses the same path.
+struct Mapper34 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    nina001: bool,
+    prg_bank: u8,
+    chr_banks: [u8; 2],
+    mirroring: Mirroring,
+}
+
+impl Mapper34 {
+    fn new(cart: Cartridge) -> Self {
+        let nina001 = cart.mapper_id == 34
+            && match cart.submapper_id {
+                1 => true,
+                2 => false,
+                _ => cart.chr_data.len() > 0x2000,
+            };
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            nina001,
+            prg_bank: 0,
+            chr_banks: [0, 1],
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        if self.nina001 {
+            let bank = self.chr_banks[addr >> 12] as usize;
+            bank_base(bank, 0x1000, self.chr.len()) + (addr & 0x0FFF)
+        } else {
+            addr % self.chr.len()
+        }
+    }
+}
+
+impl Mapper for Mapper34 {
+    state_fields!(chr if chr_is_ram, prg_ram, prg_bank, chr_banks, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xFFFF => {
+                let base = bank_base(self.prg_bank as usize, 0x8000, self.prg_rom.len());
+                self.prg_rom[(base + (addr as usize & 0x7FFF)) % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                // NINA-001's registers sit on top of RAM, which takes the
+                // write too.
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+                if self.nina001 {
+                    match addr {
+                        0x7FFD => self.prg_bank = value & 0x01,
+                        0x7FFE => self.chr_banks[0] = value & 0x0F,
+                        0x7FFF => self.chr_banks[1] = value & 0x0F,
+                        _ => {}
+                    }
+                }
+            }
+            0x8000..=0xFFFF if !self.nina001 => self.prg_bank = value,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        if self.nina001 {
+            format!(
+                "NINA-001 prg_bank=${:02X} chr_banks=${:02X}/${:02X}",
+                self.prg_bank, self.chr_banks[0], self.chr_banks[1]
+            )
+        } else {
+            format!("BNROM prg_bank=${:02X}", self.prg_bank)
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Bit Corp's UNL-PCI556 (Crime Busters): one register at $7000-$7FFF,
+/// PRG 32 KB bank in bits 0-1 and CHR 8 KB bank in bits 2-3. There is no
+/// work RAM.
+struct Mapper38 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper38 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let base = bank_base(self.chr_bank as usize, 0x2000, self.chr.len());
+        (base + (addr as usize & 0x1FFF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper38 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let base = bank_base(self.prg_bank as usize, 0x8000, self.prg_rom.len());
+                self.prg_rom[(base + (addr as usize & 0x7FFF)) % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if (0x7000..=0x7FFF).contains(&addr) {
+            self.prg_bank = value & 0x03;
+            self.chr_bank = (value >> 2) & 0x03;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "PCI556 prg_bank=${:02X} chr_bank=${:02X}",
+            self.prg_bank, self.chr_bank
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Bootleg boards that put the Famicom Disk System's Super Mario Bros. 2
+/// on a cartridge. The disk image's 8 KB pieces sit in fixed windows
+/// ($6000-$7FFF included) around one switchable bank at $C000, and a
+/// 12-bit CPU cycle counter stands in for the FDS timer IRQ: once enabled
+/// it raises the IRQ 4096 cycles later and stops.
+///
+/// Mapper 40 (NTDEC 2722) selects the $C000 bank at $E000-$FFFF, resets
+/// the timer at $8000-$9FFF and starts it at $A000-$BFFF. Mapper 50 (N-32)
+/// has both registers in the $4020-$5FFF area, with a scrambled bank
+/// number.
+struct Mapper40 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    n32: bool,
+    prg_bank: u8,
+    mirroring: Mirroring,
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+}
+
+impl Mapper40 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            n32: cart.mapper_id == 50,
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            mirroring: cart.mirroring,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+        }
+    }
+
+    /// 8 KB banks at $6000, $8000, $A000, $C000 and $E000.
+    fn prg_banks(&self) -> [u8; 5] {
+        if self.n32 {
+            [15, 8, 9, self.prg_bank, 11]
+        } else {
+            [6, 4, 5, self.prg_bank, 7]
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = self.prg_banks()[(addr as usize - 0x6000) >> 13] as usize;
+        (bank_base(bank, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+
+    fn set_irq_enabled(&mut self, enabled: bool) {
+        self.irq_enabled = enabled;
+        if !enabled {
+            self.irq_counter = 0;
+            self.irq_pending = false;
+        }
+    }
+}
+
+impl Mapper for Mapper40 {
+    state_fields!(chr if chr_is_ram, prg_bank, mirroring, irq_enabled, irq_counter, irq_pending);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if self.n32 {
+            match addr & 0xD160 {
+                0x4020 => {
+                    self.prg_bank = (value & 0x08) | ((value & 0x01) << 2) | ((value >> 1) & 0x03);
+                }
+                0x4120 => self.set_irq_enabled(value & 0x01 != 0),
+                _ => {}
+            }
+            return;
+        }
+        match addr {
+            0x8000..=0x9FFF => self.set_irq_enabled(false),
+            0xA000..=0xBFFF => self.irq_enabled = true,
+            0xE000..=0xFFFF => self.prg_bank = value & 0x07,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr as usize & 0x1FFF) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = (addr as usize & 0x1FFF) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        self.irq_counter += 1;
+        if self.irq_counter == 0x1000 {
+            self.irq_enabled = false;
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[(addr as usize & 0x1FFF) % self.chr.len()]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "{} prg_bank=${:02X} irq_en={} irq_counter={} pending={}",
+            if self.n32 { "N-32" } else { "NTDEC 2722" },
+            self.prg_bank,
+            self.irq_enabled,
+            self.irq_counter,
+            self.irq_pending
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// AVE NINA-03 and NINA-06: one register in the $4100-$5FFF range, PRG
+/// 32 KB bank in bit 3 and CHR 8 KB bank in bits 0-2. The board decodes
+/// A14, A13 and A8 only, so it answers wherever those are 1, 0, 1. Mapper
+/// 146 (Sachen 3015) is the same board. Mapper 113 (HES multicarts) widens
+/// the register to PRG bits 3-5, CHR bits 0-2 plus bit 6 as CHR bit 3, and
+/// vertical mirroring in bit 7.
+struct Mapper79 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    hes: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper79 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            hes: cart.mapper_id == 113,
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let base = bank_base(self.chr_bank as usize, 0x2000, self.chr.len());
+        (base + (addr as usize & 0x1FFF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper79 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let base = bank_base(self.prg_bank as usize, 0x8000, self.prg_rom.len());
+                self.prg_rom[(base + (addr as usize & 0x7FFF)) % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr & 0xE100 != 0x4100 {
+            return;
+        }
+        if self.hes {
+            self.prg_bank = (value >> 3) & 0x07;
+            self.chr_bank = (value & 0x07) | ((value >> 3) & 0x08);
+            self.mirroring = if value & 0x80 != 0 {
+                Mirroring::Vertical
+            } else {
+                Mirroring::Horizontal
+            };
+        } else {
+            self.prg_bank = (value >> 3) & 0x01;
+            self.chr_bank = value & 0x07;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "{} prg_bank=${:02X} chr_bank=${:02X}",
+            if self.hes { "HES" } else { "NINA-03/06" },
+            self.prg_bank,
+            self.chr_bank
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Values that unlock the X1-005's RAM and each of the X1-017's three
+/// RAM blocks; anything else write-protects and hides them.
+const X1_005_RAM_KEY: u8 = 0xA3;
+const X1_017_RAM_KEYS: [u8; 3] = [0xCA, 0x69, 0x84];
+
+/// Taito X1-005 (mapper 80) and X1-017 (mapper 82): three switchable 8 KB
+/// PRG banks with the last fixed, two 2 KB and four 1 KB CHR banks, all set
+/// through registers at $7EF0-$7EFF. Each chip holds battery RAM behind
+/// protection registers that only open it for a magic value: 128 bytes at
+/// $7F00 on the X1-005, 5 KB at $6000-$73FF in three blocks on the
+/// X1-017. The X1-017 can also swap the 2 KB and 1 KB CHR halves and takes
+/// PRG bank numbers from bits 2-7.
+struct Mapper80 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    x1_017: bool,
+    prg_banks: [u8; 3],
+    /// 2 KB banks in 1 KB units (bit 0 ignored), then four 1 KB banks.
+    chr_banks: [u8; 6],
+    chr_invert: bool,
+    mirroring: Mirroring,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    ram_enable: [u8; 3],
+}
+
+impl Mapper80 {
+    fn new(cart: Cartridge) -> Self {
+        let x1_017 = cart.mapper_id == 82;
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            x1_017,
+            prg_banks: [0, 1, 2],
+            chr_banks: [0, 2, 4, 5, 6, 7],
+            chr_invert: false,
+            mirroring: cart.mirroring,
+            prg_ram: vec![0; if x1_017 { 0x1400 } else { 0x80 }],
+            prg_ram_dirty: false,
+            ram_enable: [0; 3],
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x8000..=0x9FFF => self.prg_banks[0] as usize,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize,
+            0xC000..=0xDFFF => self.prg_banks[2] as usize,
+            _ => (self.prg_rom.len() / 0x2000).max(1) - 1,
+        };
+        (bank_base(bank, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let mut addr = addr as usize & 0x1FFF;
+        if self.chr_invert {
+            addr ^= 0x1000;
+        }
+        let base = if addr < 0x1000 {
+            let bank = (self.chr_banks[addr >> 11] >> 1) as usize;
+            bank_base(bank, 0x0800, self.chr.len()) + (addr & 0x07FF)
+        } else {
+            let bank = self.chr_banks[2 + ((addr >> 10) & 3)] as usize;
+            bank_base(bank, 0x0400, self.chr.len()) + (addr & 0x03FF)
+        };
+        base % self.chr.len()
+    }
+
+    /// Index into the battery RAM for an unlocked address.
+    fn ram_index(&self, addr: u16) -> Option<usize> {
+        if !self.x1_017 {
+            return (addr >= 0x7F00 && self.ram_enable[0] == X1_005_RAM_KEY)
+                .then_some(addr as usize & 0x7F);
+        }
+        let block = match addr {
+            0x6000..=0x67FF => 0,
+            0x6800..=0x6FFF => 1,
+            0x7000..=0x73FF => 2,
+            _ => return None,
+        };
+        (self.ram_enable[block] == X1_017_RAM_KEYS[block]).then_some(addr as usize - 0x6000)
+    }
+
+    fn write_register(&mut self, reg: u16, value: u8) {
+        if self.x1_017 {
+            match reg {
+                0x0..=0x5 => self.chr_banks[reg as usize] = value,
+                0x6 => {
+                    self.mirroring = if value & 0x01 == 0 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                    self.chr_invert = value & 0x02 != 0;
+                }
+                0x7..=0x9 => self.ram_enable[reg as usize - 0x7] = value,
+                0xA..=0xC => self.prg_banks[reg as usize - 0xA] = value >> 2,
+                // $7EFD-$7EFF: an IRQ no released game uses.
+                _ => {}
+            }
+        } else {
+            match reg {
+                0x0..=0x5 => self.chr_banks[reg as usize] = value,
+                0x6 | 0x7 => {
+                    self.mirroring = if value & 0x01 == 0 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                }
+                0x8 | 0x9 => self.ram_enable[0] = value,
+                _ => self.prg_banks[(reg as usize - 0xA) / 2] = value,
+            }
+        }
+    }
+}
+
+impl Mapper for Mapper80 {
+    state_fields!(chr if chr_is_ram, prg_banks, chr_banks, chr_invert, mirroring, prg_ram, ram_enable);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.ram_index(addr).map_or(0, |idx| self.prg_ram[idx]),
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x7EF0..=0x7EFF => self.write_register(addr & 0x0F, value),
+            0x6000..=0x7FFF => {
+                if let Some(idx) = self.ram_index(addr) {
+                    self.prg_ram[idx] = value;
+                    self.prg_ram_dirty = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "{} prg={:02X?} chr={:02X?} invert={} ram_enable={:02X?} mirroring={:?}",
+            if self.x1_017 { "X1-017" } else { "X1-005" },
+            self.prg_banks,
+            self.chr_banks,
+            self.chr_invert,
+            self.ram_enable,
+            self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+}
+
+/// Writes that start a sample on the Jaleco boards' uPD7756 speech chip:
+/// bits 5-4 = 10, with the sample number below them.
+fn jaleco_sample_trigger(value: u8, sample_mask: u8) -> Option<u8> {
+    (value & 0x30 == 0x20).then_some(value & sample_mask)
+}
+
+/// Jaleco JF-13 (mapper 86): 32 KB PRG and 8 KB CHR chosen by one register
+/// at $6000-$6FFF, PRG in bits 4-5 and CHR in bits 0-1 with bit 6 as CHR
+/// bit 2. $7000-$7FFF drives the speech chip the Moero!! Pro Yakyuu games
+/// call the plays with.
+struct Mapper86 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+    sample_trigger: Option<u8>,
+}
+
+impl Mapper86 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+            mirroring: cart.mirroring,
+            sample_trigger: None,
+        }
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        bank_base(self.chr_bank as usize, 0x2000, self.chr.len()) + (addr as usize & 0x1FFF)
+    }
+}
+
+impl Mapper for Mapper86 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let base = bank_base(self.prg_bank as usize, 0x8000, self.prg_rom.len());
+                self.prg_rom[(base + (addr as usize & 0x7FFF)) % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x6FFF => {
+                self.prg_bank = (value >> 4) & 0x03;
+                self.chr_bank = (value & 0x03) | ((value >> 4) & 0x04);
+            }
+            0x7000..=0x7FFF => {
+                if let Some(sample) = jaleco_sample_trigger(value, 0x1F) {
+                    self.sample_trigger = Some(sample);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr) % self.chr.len()]
+    }
+
+    fn take_sample_trigger(&mut self) -> Option<u8> {
+        self.sample_trigger.take()
+    }
+
+    fn debug_state(&self) -> String {
+        format!("JF-13 prg={} chr={}", self.prg_bank, self.chr_bank)
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Jaleco JF-17 (mapper 72) and JF-19 (mapper 92): one register across
+/// $8000-$FFFF. A rising bit 7 latches the low bits as the 16 KB PRG bank
+/// and a rising bit 6 as the 8 KB CHR bank. The JF-17 switches $8000 with
+/// the last bank fixed at $C000; the JF-19 fixes the first bank at $8000
+/// and switches $C000. Bits 5-4 drive the speech chip on the boards that
+/// have one.
+struct Mapper72 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    jf19: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    /// Bits 7-6 of the last write, for edge detection.
+    latch_bits: u8,
+    mirroring: Mirroring,
+    sample_trigger: Option<u8>,
+}
+
+impl Mapper72 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            jf19: cart.mapper_id == 92,
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+            latch_bits: 0,
+            mirroring: cart.mirroring,
+            sample_trigger: None,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let last = (self.prg_rom.len() / 0x4000).max(1) - 1;
+        let bank = match (addr < 0xC000, self.jf19) {
+            (true, false) | (false, true) => self.prg_bank as usize,
+            (true, true) => 0,
+            (false, false) => last,
+        };
+        (bank_base(bank, 0x4000, self.prg_rom.len()) + (addr as usize & 0x3FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        (bank_base(self.chr_bank as usize, 0x2000, self.chr.len()) + (addr as usize & 0x1FFF))
+            % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper72 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, latch_bits, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            return;
+        }
+        let rising = value & !self.latch_bits;
+        if rising & 0x80 != 0 {
+            self.prg_bank = value & 0x0F;
+        }
+        if rising & 0x40 != 0 {
+            self.chr_bank = value & 0x0F;
+        }
+        self.latch_bits = value & 0xC0;
+        if let Some(sample) = jaleco_sample_trigger(value, 0x0F) {
+            self.sample_trigger = Some(sample);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn take_sample_trigger(&mut self) -> Option<u8> {
+        self.sample_trigger.take()
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "{} prg={} chr={}",
+            if self.jf19 { "JF-19" } else { "JF-17" },
+            self.prg_bank,
+            self.chr_bank
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Discrete boards that switch 16 KB of PRG like UxROM but lay the window
+/// out or decode the register differently:
+///
+/// - 89, Sunsoft-2 on the Sunsoft-3 board: `CPPP MCCC`, 8 KB CHR in C
+///   (bit 7 is CHR bit 3) and one-screen mirroring in M.
+/// - 93, Sunsoft-2 on the Sunsoft-3R board: `.PPP ...E`, E enabling CHR
+///   RAM.
+/// - 94, HVC-UN1ROM: PRG in bits 2-4.
+/// - 97, Irem TAM-S1: the last bank is fixed at $8000 and $C000 switches,
+///   with mirroring in bits 6-7. Only $8000-$BFFF decodes.
+/// - 180, UNROM with a 74HC08: the first bank is fixed at $8000 and
+///   $C000 switches.
+struct Mapper89 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mapper_id: u16,
+    prg_bank: u8,
+    chr_bank: u8,
+    chr_enabled: bool,
+    mirroring: Mirroring,
+}
+
+impl Mapper89 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            mapper_id: cart.mapper_id,
+            prg_bank: 0,
+            chr_bank: 0,
+            chr_enabled: true,
+            mirroring: cart.mirroring,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let last = (self.prg_rom.len() / 0x4000).max(1) - 1;
+        let prg = self.prg_bank as usize;
+        let (low, high) = match self.mapper_id {
+            97 => (last, prg),
+            180 => (0, prg),
+            _ => (prg, last),
+        };
+        let bank = if addr < 0xC000 { low } else { high };
+        (bank_base(bank, 0x4000, self.prg_rom.len()) + (addr as usize & 0x3FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        (bank_base(self.chr_bank as usize, 0x2000, self.chr.len()) + (addr as usize & 0x1FFF))
+            % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper89 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, chr_enabled, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            return;
+        }
+        match self.mapper_id {
+            89 => {
+                self.prg_bank = (value >> 4) & 0x07;
+                self.chr_bank = (value & 0x07) | ((value >> 4) & 0x08);
+                self.mirroring = if value & 0x08 == 0 {
+                    Mirroring::OneScreenLower
+                } else {
+                    Mirroring::OneScreenUpper
+                };
+            }
+            93 => {
+                self.prg_bank = (value >> 4) & 0x07;
+                self.chr_enabled = value & 0x01 != 0;
+            }
+            94 => self.prg_bank = (value >> 2) & 0x07,
+            97 if addr < 0xC000 => {
+                self.prg_bank = value & 0x0F;
+                self.mirroring = match value >> 6 {
+                    0 => Mirroring::OneScreenLower,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::Vertical,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            }
+            97 => {}
+            _ => self.prg_bank = value & 0x07,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        if self.chr_enabled {
+            self.chr[self.chr_index(addr)]
+        } else {
+            0
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram && self.chr_enabled {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "mapper={} prg={} chr={} chr_enabled={} mirroring={:?}",
+            self.mapper_id, self.prg_bank, self.chr_bank, self.chr_enabled, self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Jaleco SS88006: three 8 KB PRG banks with the last fixed, eight 1 KB
+/// CHR banks and 8 KB of PRG RAM. Bank numbers are written a nibble at a
+/// time, low nibble at even addresses and high at odd ones, with registers
+/// decoded by A15-A12 and A1-A0. The IRQ counter reloads from four nibbles
+/// and decrements every CPU cycle, counting in its low 4, 8, 12 or all 16
+/// bits, and fires when that part reaches zero.
+struct Mapper18 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    prg_ram_enabled: bool,
+    prg_ram_writable: bool,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    mirroring: Mirroring,
+    irq_reload: u16,
+    irq_counter: u16,
+    /// Counter bits that count: $000F, $00FF, $0FFF or $FFFF.
+    irq_mask: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl Mapper18 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            prg_ram_enabled: false,
+            prg_ram_writable: false,
+            prg_banks: [0, 1, 2],
+            chr_banks: [0; 8],
+            mirroring: cart.mirroring,
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_mask: 0xFFFF,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x8000..=0x9FFF => self.prg_banks[0] as usize,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize,
+            0xC000..=0xDFFF => self.prg_banks[2] as usize,
+            _ => (self.prg_rom.len() / 0x2000).max(1) - 1,
+        };
+        (bank_base(bank, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        let base = bank_base(self.chr_banks[addr >> 10] as usize, 0x0400, self.chr.len());
+        (base + (addr & 0x03FF)) % self.chr.len()
+    }
+}
+
+/// Replaces the low (`high == false`) or high nibble of `bank`.
+fn set_nibble(bank: &mut u8, high: bool, value: u8) {
+    *bank = if high {
+        (*bank & 0x0F) | (value << 4)
+    } else {
+        (*bank & 0xF0) | (value & 0x0F)
+    };
+}
+
+impl Mapper for Mapper18 {
+    state_fields!(chr if chr_is_ram, prg_ram, prg_ram_enabled, prg_ram_writable, prg_banks, chr_banks, mirroring, irq_reload, irq_counter, irq_mask, irq_enabled, irq_pending);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => {
+                self.prg_ram[(addr as usize - 0x6000) % self.prg_ram.len()]
+            }
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            if (0x6000..=0x7FFF).contains(&addr) && self.prg_ram_enabled && self.prg_ram_writable {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            return;
+        }
+        let odd = addr & 0x01 != 0;
+        match addr & 0xF003 {
+            0x8000..=0x8003 => {
+                let bank = &mut self.prg_banks[(addr as usize >> 1) & 1];
+                set_nibble(bank, odd, value & if odd { 0x03 } else { 0x0F });
+            }
+            0x9000 | 0x9001 => {
+                set_nibble(
+                    &mut self.prg_banks[2],
+                    odd,
+                    value & if odd { 0x03 } else { 0x0F },
+                );
+            }
+            0x9002 => {
+                self.prg_ram_enabled = value & 0x01 != 0;
+                self.prg_ram_writable = value & 0x02 != 0;
+            }
+            0xA000..=0xDFFF => {
+                let slot = ((addr as usize - 0xA000) >> 11 & 0x06) | ((addr as usize >> 1) & 1);
+                set_nibble(&mut self.chr_banks[slot], odd, value & 0x0F);
+            }
+            0xE000..=0xE003 => {
+                let shift = (addr & 0x03) * 4;
+                self.irq_reload =
+                    (self.irq_reload & !(0x0F << shift)) | (u16::from(value & 0x0F) << shift);
+            }
+            0xF000 => {
+                self.irq_counter = self.irq_reload;
+                self.irq_pending = false;
+            }
+            0xF001 => {
+                self.irq_enabled = value & 0x01 != 0;
+                self.irq_mask = if value & 0x08 != 0 {
+                    0x000F
+                } else if value & 0x04 != 0 {
+                    0x00FF
+                } else if value & 0x02 != 0 {
+                    0x0FFF
+                } else {
+                    0xFFFF
+                };
+                self.irq_pending = false;
+            }
+            0xF002 => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Horizontal,
+                    1 => Mirroring::Vertical,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            }
+            // $F003 drives the speech chip on the few boards that have one.
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        let counted = self.irq_counter.wrapping_sub(1) & self.irq_mask;
+        self.irq_counter = (self.irq_counter & !self.irq_mask) | counted;
+        if counted == 0 {
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "SS88006 prg={:02X?} chr={:02X?} irq_counter=${:04X} mask=${:04X} en={} pending={} mirroring={:?}",
+            self.prg_banks,
+            self.chr_banks,
+            self.irq_counter,
+            self.irq_mask,
+            self.irq_enabled,
+            self.irq_pending,
+            self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+}
+
+/// FDS conversion board (mapper 42; Ai Senshi Nicol, Mario Baby): the last
+/// 32 KB of PRG fixed at $8000, an 8 KB PRG bank at $6000 where the disk
+/// game kept its RAM-loaded code, and an 8 KB CHR bank. The IRQ counter is
+/// a free-running 15-bit counter of CPU cycles that holds the line low
+/// while bits 13 and 14 are both set; disabling it clears the count.
+struct Mapper42 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_bank_6000: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+    irq_enabled: bool,
+    irq_counter: u16,
+}
+
+impl Mapper42 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_bank_6000: 0,
+            chr_bank: 0,
+            mirroring: cart.mirroring,
+            irq_enabled: false,
+            irq_counter: 0,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let (base, offset) = if addr < 0x8000 {
+            let base = bank_base(self.prg_bank_6000 as usize, 0x2000, self.prg_rom.len());
+            (base, addr as usize & 0x1FFF)
+        } else {
+            let last = (self.prg_rom.len() / 0x8000).max(1) - 1;
+            (
+                bank_base(last, 0x8000, self.prg_rom.len()),
+                addr as usize & 0x7FFF,
+            )
+        };
+        (base + offset) % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        (bank_base(self.chr_bank as usize, 0x2000, self.chr.len()) + (addr as usize & 0x1FFF))
+            % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper42 {
+    state_fields!(chr if chr_is_ram, prg_bank_6000, chr_bank, mirroring, irq_enabled, irq_counter);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr & 0xE003 {
+            0x8000 => self.chr_bank = value & 0x0F,
+            0xE000 => self.prg_bank_6000 = value & 0x0F,
+            0xE001 => {
+                self.mirroring = if value & 0x08 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            0xE002 => {
+                self.irq_enabled = value & 0x02 != 0;
+                if !self.irq_enabled {
+                    self.irq_counter = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if self.irq_enabled {
+            self.irq_counter = (self.irq_counter + 1) & 0x7FFF;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_counter & 0x6000 == 0x6000
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_counter = 0;
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "mapper42 6000={:02X} chr={:02X} irq_counter=${:04X} en={} mirroring={:?}",
+            self.prg_bank_6000, self.chr_bank, self.irq_counter, self.irq_enabled, self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// 11-in-1 Ball Games (mapper 51): an outer bank register at $8000-$FFFF
+/// and a mode register at $6000-$7FFF. Bit 1 of the mode picks a 32 KB
+/// bank at $8000, otherwise two 16 KB banks whose first takes its low bit
+/// from mode bit 4 (also set by writes to $C000-$FFFF). $6000-$7FFF shows
+/// an 8 KB bank from the same block, and mode $12 mirrors horizontally.
+struct Mapper51 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    bank: u8,
+    mode: u8,
+}
+
+impl Mapper51 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            bank: 0,
+            mode: 0x02,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = self.bank as usize;
+        let bank_8k = match addr {
+            0x6000..=0x7FFF if self.mode & 0x02 != 0 => (bank << 2) | 0x23,
+            0x6000..=0x7FFF => (bank << 2) | 0x2F,
+            _ if self.mode & 0x02 != 0 => (bank << 2) | ((addr as usize >> 13) & 0x03),
+            0x8000..=0xBFFF => {
+                (((bank << 1) | usize::from(self.mode >> 4)) << 1) | ((addr as usize >> 13) & 1)
+            }
+            _ => (((bank << 1) | 7) << 1) | ((addr as usize >> 13) & 1),
+        };
+        (bank_base(bank_8k, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+}
+
+impl Mapper for Mapper51 {
+    state_fields!(chr if chr_is_ram, bank, mode);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.mode = value & 0x12,
+            0x8000..=0xFFFF => {
+                self.bank = value & 0x0F;
+                if addr >= 0xC000 {
+                    self.mode = (self.mode & 0x02) | (value & 0x10);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr as usize & 0x1FFF) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = (addr as usize & 0x1FFF) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.mode == 0x12 {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        }
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[(addr as usize & 0x1FFF) % self.chr.len()]
+    }
+
+    fn debug_state(&self) -> String {
+        format!("mapper51 bank={:02X} mode={:02X}", self.bank, self.mode)
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Active Enterprises' Action 52 and Cheetahmen II (mapper 228): the
+/// register is the address written to $8000-$FFFF plus the data's low two
+/// bits. A0-A3 with D0-D1 pick the 8 KB CHR bank, A5 selects 16 KB PRG
+/// mode, A6-A10 the 16 KB PRG bank and A11-A12 the 512 KB PRG chip it is
+/// on, and A13 the mirroring. Action 52 was built with chips 0, 1 and 3,
+/// so in its 1.5 MB dump chip 3 follows chip 1 and chip 2 reads as open
+/// bus. Four nibbles of RAM across $4020-$5FFF hold the menu's state.
+struct Mapper228 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    latch: u16,
+    chr_low: u8,
+    ram: [u8; 4],
+}
+
+impl Mapper228 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            latch: 0,
+            chr_low: 0,
+            ram: [0; 4],
+        }
+    }
+
+    /// `None` where the selected chip isn't fitted.
+    fn prg_index(&self, addr: u16) -> Option<usize> {
+        let mut chip = usize::from(self.latch >> 11 & 0x03);
+        if self.prg_rom.len() == 3 * 0x80000 {
+            match chip {
+                2 => return None,
+                3 => chip = 2,
+                _ => {}
+            }
+        }
+        let mut bank = chip << 5 | usize::from(self.latch >> 6 & 0x1F);
+        if self.latch & 0x20 == 0 {
+            bank = (bank & !1) | usize::from(addr >= 0xC000);
+        }
+        Some(
+            (bank_base(bank, 0x4000, self.prg_rom.len()) + (addr as usize & 0x3FFF))
+                % self.prg_rom.len(),
+        )
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let bank = usize::from(self.latch & 0x0F) << 2 | usize::from(self.chr_low);
+        (bank_base(bank, 0x2000, self.chr.len()) + (addr as usize & 0x1FFF)) % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper228 {
+    state_fields!(chr if chr_is_ram, latch, chr_low, ram);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_index(addr).map_or(0, |idx| self.prg_rom[idx]),
+            _ => 0,
+        }
+    }
+
+    fn cpu_read_expansion(&mut self, addr: u16) -> Option<u8> {
+        Some(self.ram[usize::from(addr & 0x03)])
+    }
+
+    fn prg_drives_bus(&self, addr: u16) -> bool {
+        addr < 0x8000 || self.prg_index(addr).is_some()
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4020..=0x5FFF => self.ram[usize::from(addr & 0x03)] = value & 0x0F,
+            0x8000..=0xFFFF => {
+                self.latch = addr;
+                self.chr_low = value & 0x03;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.latch & 0x2000 != 0 {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        }
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "mapper228 latch=${:04X} chr_low={} ram={:X?}",
+            self.latch, self.chr_low, self.ram
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Golden Game 150-in-1 and other mapper 235 multicarts: the address
+/// written to $8000-$FFFF holds everything. A0-A4 pick a 32 KB PRG bank on
+/// the 1 MB chip chosen by A8-A9; A11 switches to 16 KB banks, A12 picking
+/// the half that fills both windows. A10 selects one-screen mirroring,
+/// otherwise A13 picks horizontal over vertical. Smaller carts leave chips
+/// off the board and banks on them read as open bus. CHR is 8 KB of RAM.
+struct Mapper235 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    latch: u16,
+}
+
+impl Mapper235 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            latch: 0,
+        }
+    }
+
+    /// `None` where the selected chip isn't fitted.
+    fn prg_index(&self, addr: u16) -> Option<usize> {
+        let bank = usize::from((self.latch & 0x0300) >> 3 | (self.latch & 0x1F));
+        if bank >= (self.prg_rom.len() / 0x8000).max(1) {
+            return None;
+        }
+        let offset = if self.latch & 0x0800 != 0 {
+            usize::from(self.latch >> 12 & 1) * 0x4000 + (addr as usize & 0x3FFF)
+        } else {
+            addr as usize & 0x7FFF
+        };
+        Some((bank * 0x8000 + offset) % self.prg_rom.len())
+    }
+}
+
+impl Mapper for Mapper235 {
+    state_fields!(chr if chr_is_ram, latch);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_index(addr).map_or(0, |idx| self.prg_rom[idx]),
+            _ => 0,
+        }
+    }
+
+    fn prg_drives_bus(&self, addr: u16) -> bool {
+        addr < 0x8000 || self.prg_index(addr).is_some()
+    }
+
+    fn cpu_write(&mut self, addr: u16, _value: u8) {
+        if addr >= 0x8000 {
+            self.latch = addr;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr as usize & 0x1FFF) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = (addr as usize & 0x1FFF) % self.chr.len();
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.latch & 0x0400 != 0 {
+            Mirroring::OneScreenLower
+        } else if self.latch & 0x2000 != 0 {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        }
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[(addr as usize & 0x1FFF) % self.chr.len()]
+    }
+
+    fn debug_state(&self) -> String {
+        format!("mapper235 latch=${:04X}", self.latch)
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Bandai 74161 boards: one register across $8000-$FFFF with the 16 KB PRG
+/// bank at $8000 in the high nibble and the 8 KB CHR bank in the low one,
+/// the last PRG bank fixed at $C000. Mapper 70 has three PRG bits plus a
+/// fourth; mapper 152 turns bit 7 into one-screen mirroring.
+struct Mapper70 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    one_screen: bool,
+    prg_bank: u8,
+    chr_bank: u8,
+    mirroring: Mirroring,
+}
+
+impl Mapper70 {
+    fn new(cart: Cartridge) -> Self {
+        let one_screen = cart.mapper_id == 152;
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            one_screen,
+            prg_bank: 0,
+            chr_bank: 0,
+            mirroring: if one_screen {
+                Mirroring::OneScreenLower
+            } else {
+                cart.mirroring
+            },
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = if addr < 0xC000 {
+            self.prg_bank as usize
+        } else {
+            (self.prg_rom.len() / 0x4000).max(1) - 1
+        };
+        (bank_base(bank, 0x4000, self.prg_rom.len()) + (addr as usize & 0x3FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        (bank_base(self.chr_bank as usize, 0x2000, self.chr.len()) + (addr as usize & 0x1FFF))
+            % self.chr.len()
+    }
+}
+
+impl Mapper for Mapper70 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_bank, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            return;
+        }
+        self.chr_bank = value & 0x0F;
+        if self.one_screen {
+            self.prg_bank = (value >> 4) & 0x07;
+            self.mirroring = if value & 0x80 == 0 {
+                Mirroring::OneScreenLower
+            } else {
+                Mirroring::OneScreenUpper
+            };
+        } else {
+            self.prg_bank = value >> 4;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "Bandai 74161 prg={} chr={} mirroring={:?}",
+            self.prg_bank, self.chr_bank, self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EepromMode {
+    Idle,
+    /// 24C02 only: the 1010xxxR byte after a start.
+    Device,
+    /// The word address; on the 24C01 it also carries R/W in bit 7.
+    Address,
+    Write,
+    Read,
+    /// The chip pulls SDA low for the ninth clock of a byte it took.
+    Ack,
+    /// The ninth clock of a byte read out, when the CPU acks or not.
+    ReadAck,
+}
+
+/// Serial EEPROM on Bandai's LZ93D50 boards: the 256-byte 24C02, spoken to
+/// over I2C, or the 128-byte 24C01, which skips the device byte and sends
+/// its address and data LSB first. The CPU bit-bangs SCL and SDA through a
+/// register and reads SDA back. Bits are taken on SCL rising and the chip
+/// changes its output after SCL falls.
+struct Eeprom {
+    data: Vec<u8>,
+    x24c01: bool,
+    mode: EepromMode,
+    next_mode: EepromMode,
+    /// Bits of the current byte shifted in or out.
+    bit: u8,
+    shift: u8,
+    address: u8,
+    /// Whether the ninth clock of an ack has gone high.
+    ack_clocked: bool,
+    scl: bool,
+    sda: bool,
+    out: bool,
+    dirty: bool,
+}
+
+enum_state_field!(EepromMode {
+    Idle,
+    Device,
+    Address,
+    Write,
+    Read,
+    Ack,
+    ReadAck,
+});
+
+impl StateField for Eeprom {
+    state_fields!(
+        data,
+        mode,
+        next_mode,
+        bit,
+        shift,
+        address,
+        ack_clocked,
+        scl,
+        sda,
+        out
+    );
+}
+
+impl Eeprom {
+    fn new(x24c01: bool) -> Self {
+        Self {
+            data: vec![0xFF; if x24c01 { 0x80 } else { 0x100 }],
+            x24c01,
+            mode: EepromMode::Idle,
+            next_mode: EepromMode::Idle,
+            bit: 0,
+            shift: 0,
+            address: 0,
+            ack_clocked: false,
+            scl: false,
+            sda: false,
+            out: true,
+            dirty: false,
+        }
+    }
+
+    /// SDA as the chip drives it; high when it lets the line float.
+    fn sda_out(&self) -> bool {
+        self.out
+    }
+
+    fn set_lines(&mut self, scl: bool, sda: bool) {
+        let (old_scl, old_sda) = (self.scl, self.sda);
+        self.scl = scl;
+        self.sda = sda;
+        if old_scl && scl {
+            if old_sda && !sda {
+                self.begin_byte(if self.x24c01 {
+                    EepromMode::Address
+                } else {
+                    EepromMode::Device
+                });
+            } else if !old_sda && sda {
+                self.mode = EepromMode::Idle;
+                self.out = true;
+            }
+        } else if !old_scl && scl {
+            self.clock_rise(sda);
+        } else if old_scl && !scl {
+            self.clock_fall();
+        }
+    }
+
+    fn begin_byte(&mut self, mode: EepromMode) {
+        self.mode = mode;
+        self.bit = 0;
+        self.shift = 0;
+        self.out = true;
+        if mode == EepromMode::Read {
+            self.shift = self.data[self.address as usize % self.data.len()];
+            self.out = self.output_bit();
+        }
+    }
+
+    /// The bit of the byte being read that goes out next.
+    fn output_bit(&self) -> bool {
+        let index = if self.x24c01 { self.bit } else { 7 - self.bit };
+        self.shift & (1 << index) != 0
+    }
+
+    fn clock_rise(&mut self, sda: bool) {
+        match self.mode {
+            EepromMode::Idle => {}
+            EepromMode::Device | EepromMode::Address | EepromMode::Write => {
+                if self.x24c01 {
+                    self.shift |= u8::from(sda) << self.bit;
+                } else {
+                    self.shift = (self.shift << 1) | u8::from(sda);
+                }
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.take_byte();
+                }
+            }
+            EepromMode::Read => {
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.mode = EepromMode::ReadAck;
+                    self.ack_clocked = false;
+                }
+            }
+            EepromMode::Ack => self.ack_clocked = true,
+            EepromMode::ReadAck => {
+                self.ack_clocked = true;
+                // No ack from the CPU ends the read.
+                self.next_mode = if sda {
+                    EepromMode::Idle
+                } else {
+                    self.address = self.address.wrapping_add(1);
+                    EepromMode::Read
+                };
+            }
+        }
+    }
+
+    fn clock_fall(&mut self) {
+        match self.mode {
+            EepromMode::Read => self.out = self.output_bit(),
+            EepromMode::Ack | EepromMode::ReadAck if self.ack_clocked => {
+                self.begin_byte(self.next_mode);
+            }
+            EepromMode::Ack => self.out = false,
+            EepromMode::ReadAck => self.out = true,
+            _ => {}
+        }
+    }
+
+    /// Acts on a byte the CPU finished sending and acks it, unless it was
+    /// a 24C02 device byte for another chip.
+    fn take_byte(&mut self) {
+        let byte = self.shift;
+        self.next_mode = match self.mode {
+            EepromMode::Device if byte & 0xF0 != 0xA0 => {
+                self.mode = EepromMode::Idle;
+                return;
+            }
+            EepromMode::Device if byte & 0x01 != 0 => EepromMode::Read,
+            EepromMode::Device => EepromMode::Address,
+            EepromMode::Address if self.x24c01 => {
+                self.address = byte & 0x7F;
+                if byte & 0x80 != 0 {
+                    EepromMode::Read
+                } else {
+                    EepromMode::Write
+                }
+            }
+            EepromMode::Address => {
+                self.address = byte;
+                EepromMode::Write
+            }
+            _ => {
+                let len = self.data.len();
+                self.data[self.address as usize % len] = byte;
+                self.address = self.address.wrapping_add(1);
+                self.dirty = true;
+                EepromMode::Write
+            }
+        };
+        self.mode = EepromMode::Ack;
+        self.ack_clocked = false;
+    }
+}
+
+/// Bandai FCG-1/2 and LZ93D50 (mapper 16, and 159 for the LZ93D50 with a
+/// 24C01): eight 1 KB CHR banks, a 16 KB PRG bank at $8000 with the last
+/// fixed, and a 16-bit IRQ counter that decrements every CPU cycle and
+/// fires on reaching zero. The FCG chips take registers at $6000-$7FFF
+/// and load the counter directly; the LZ93D50 takes them at $8000-$FFFF,
+/// loads a latch, and copies it to the counter when the IRQ is enabled.
+/// Submapper 0 doesn't say which, so it decodes both and does both. The
+/// LZ93D50 also bit-bangs a serial EEPROM, kept as battery RAM.
+struct Mapper16 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    decode_6000: bool,
+    decode_8000: bool,
+    prg_bank: u8,
+    chr_banks: [u8; 8],
+    mirroring: Mirroring,
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    eeprom: Option<Eeprom>,
+}
+
+impl Mapper16 {
+    fn new(cart: Cartridge) -> Self {
+        let (decode_6000, decode_8000, eeprom) = match (cart.mapper_id, cart.submapper_id) {
+            (159, _) => (false, true, Some(Eeprom::new(true))),
+            (_, 4) => (true, false, None),
+            (_, 5) => (false, true, Some(Eeprom::new(false))),
+            _ => (true, true, Some(Eeprom::new(false))),
+        };
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            decode_6000,
+            decode_8000,
+            prg_bank: 0,
+            chr_banks: [0; 8],
+            mirroring: cart.mirroring,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            eeprom,
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = if addr < 0xC000 {
+            self.prg_bank as usize
+        } else {
+            (self.prg_rom.len() / 0x4000).max(1) - 1
+        };
+        (bank_base(bank, 0x4000, self.prg_rom.len()) + (addr as usize & 0x3FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        let base = bank_base(self.chr_banks[addr >> 10] as usize, 0x0400, self.chr.len());
+        (base + (addr & 0x03FF)) % self.chr.len()
+    }
+
+    fn write_register(&mut self, reg: u16, value: u8) {
+        match reg {
+            0x0..=0x7 => self.chr_banks[reg as usize] = value,
+            0x8 => self.prg_bank = value & 0x0F,
+            0x9 => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            }
+            0xA => {
+                self.irq_enabled = value & 0x01 != 0;
+                self.irq_pending = false;
+                if self.decode_8000 {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            0xB | 0xC => {
+                let shift = if reg == 0xB { 0 } else { 8 };
+                let set = |word: u16| (word & !(0xFF << shift)) | (u16::from(value) << shift);
+                self.irq_latch = set(self.irq_latch);
+                if self.decode_6000 {
+                    self.irq_counter = set(self.irq_counter);
+                }
+            }
+            0xD => {
+                if let Some(eeprom) = self.eeprom.as_mut() {
+                    eeprom.set_lines(value & 0x20 != 0, value & 0x40 != 0);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mapper16 {
+    state_fields!(chr if chr_is_ram, prg_bank, chr_banks, mirroring, irq_counter, irq_latch, irq_enabled, irq_pending, eeprom);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self
+                .eeprom
+                .as_ref()
+                .map_or(0, |eeprom| u8::from(eeprom.sda_out()) << 4),
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        let decoded = match addr {
+            0x6000..=0x7FFF => self.decode_6000,
+            0x8000..=0xFFFF => self.decode_8000,
+            _ => false,
+        };
+        if decoded {
+            self.write_register(addr & 0x0F, value);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0 {
+            self.irq_pending = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "Bandai FCG prg={:02X} chr={:02X?} irq_counter=${:04X} latch=${:04X} en={} pending={} mirroring={:?}",
+            self.prg_bank,
+            self.chr_banks,
+            self.irq_counter,
+            self.irq_latch,
+            self.irq_enabled,
+            self.irq_pending,
+            self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.eeprom
+            .as_ref()
+            .map_or(&[][..], |eeprom| eeprom.data.as_slice())
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        match self.eeprom.as_mut() {
+            Some(eeprom) => &mut eeprom.data,
+            None => &mut [],
+        }
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        self.eeprom
+            .as_mut()
+            .is_some_and(|eeprom| std::mem::take(&mut eeprom.dirty))
+    }
+}
+
+struct Mapper71 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    bank_select: u8,
+    bank_mask: u8,
+    mirroring: Mirroring,
+    submapper_id: u8,
+    mirroring_control_supported: bool,
+    debug_bank_write_count: u64,
+    debug_mirroring_write_count: u64,
+    debug_last_bank_write_addr: u16,
+    debug_last_bank_value: u8,
+    debug_last_mirroring_value: u8,
+}
+
+impl Mapper71 {
+    fn new(cart: Cartridge) -> Self {
+        // Mapper 71 uses CHR-RAM in hardware; honor writes even if a dump
+        // incorrectly advertises CHR-ROM.
+        let mut chr = cart.chr_data;
+        if chr.len() < 0x2000 {
+            chr.resize(0x2000, 0);
+        }
+
+        // NES 2.0 submapper 1 (Fire Hawk) has 3-bit banking and
+        // one-screen mirroring control at $9000-$9FFF.
+        let bank_mask = if cart.submapper_id == 1 { 0x07 } else { 0x0F };
+        Self {
+            prg_rom: cart.prg_rom,
+            chr,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            bank_select: 0,
+            bank_mask,
+            mirroring: cart.mirroring,
+            submapper_id: cart.submapper_id,
+            mirroring_control_supported: cart.submapper_id == 1,
+            debug_bank_write_count: 0,
+            debug_mirroring_write_count: 0,
+            debug_last_bank_write_addr: 0,
+            debug_last_bank_value: 0,
+            debug_last_mirroring_value: 0,
+        }
+    }
+
+    fn prg_bank_count_16k(&self) -> usize {
+        (self.prg_rom.len() / 0x4000).max(1)
+    }
+
+    fn read_prg_16k(&self, bank: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count_16k();
+        self.prg_rom[(bank * 0x4000 + offset) % self.prg_rom.len()]
+    }
+}
+
+impl Mapper for Mapper71 {
+    state_fields!(chr, prg_ram, bank_select, mirroring);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xBFFF => self.read_prg_16k(self.bank_select as usize, addr as usize - 0x8000),
+            0xC000..=0xFFFF => {
+                let last = self.prg_bank_count_16k().saturating_sub(1);
+                self.read_prg_16k(last, addr as usize - 0xC000)
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x9000..=0x9FFF if self.mirroring_control_supported => {
+                self.mirroring = if (value & 0x10) != 0 {
+                    Mirroring::OneScreenUpper
+                } else {
+                    Mirroring::OneScreenLower
+                };
+                self.debug_mirroring_write_count = self.debug_mirroring_write_count.wrapping_add(1);
+                self.debug_last_mirroring_value = value;
+            }
+            0xC000..=0xFFFF => {
+                self.bank_select = value & self.bank_mask;
+                self.debug_bank_write_count = self.debug_bank_write_count.wrapping_add(1);
+                self.debug_last_bank_write_addr = addr;
+                self.debug_last_bank_value = value;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr as usize) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        let idx = (addr as usize) % self.chr.len();
+        self.chr[idx] = value;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[(addr as usize) % self.chr.len()]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "submapper={} bank_select=${:02X} bank_mask=${:02X} prg_16k_banks={} chr_ram_kib={} mirroring={:?} bank_writes={} mirror_writes={} last_bank=${:04X}:${:02X} last_mirror=${:02X}",
+            self.submapper_id,
+            self.bank_select,
+            self.bank_mask,
+            self.prg_bank_count_16k(),
+            self.chr.len() / 1024,
+            self.mirroring,
+            self.debug_bank_write_count,
+            self.debug_mirroring_write_count,
+            self.debug_last_bank_write_addr,
+            self.debug_last_bank_value,
+            self.debug_last_mirroring_value
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.chr)
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper4 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    bank_select: u8,
+    bank_regs: [u8; 8],
+    mirroring: Mirroring,
+    four_screen: bool,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    last_a12: bool,
+    a12_low_cycles: u8,
+    debug_a12_high_samples: u64,
+    debug_irq_clocks: u64,
+    /// Resolved offsets of the four 8 KB PRG windows and eight 1 KB CHR
+    /// windows, updated on bank writes.
+    prg_bases: [usize; 4],
+    chr_bases: [usize; 8],
+}
+
+impl Mapper4 {
+    fn new(cart: Cartridge) -> Self {
+        let mut mapper = Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            bank_select: 0,
+            bank_regs: [0; 8],
+            mirroring: cart.mirroring,
+            four_screen: cart.four_screen,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            last_a12: false,
+            a12_low_cycles: 0,
+            debug_a12_high_samples: 0,
+            debug_irq_clocks: 0,
+            prg_bases: [0; 4],
+            chr_bases: [0; 8],
+        };
+        mapper.update_banks();
+        mapper
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn update_banks(&mut self) {
+        let prg_len = self.prg_rom.len();
+        let second_last = self.prg_bank_count_8k().saturating_sub(2);
+        let last = self.prg_bank_count_8k() - 1;
+        let r6 = self.bank_regs[6] as usize;
+        let r7 = self.bank_regs[7] as usize;
+        let prg_banks = if (self.bank_select & 0x40) == 0 {
+            [r6, r7, second_last, last]
+        } else {
+            [second_last, r7, r6, last]
+        };
+        for (base, bank) in self.prg_bases.iter_mut().zip(prg_banks) {
+            *base = bank_base(bank, 0x2000, prg_len);
+        }
+
+        let chr_banks = self.chr_banks();
+        let chr_len = self.chr.len();
+        for (base, bank) in self.chr_bases.iter_mut().zip(chr_banks) {
+            *base = bank_base(bank as usize, 0x0400, chr_len);
+        }
+    }
+
+    /// 1 KB CHR bank in each of the eight pattern table windows.
+    fn chr_banks(&self) -> [u8; 8] {
+        let r0 = self.bank_regs[0] & 0xFE;
+        let r1 = self.bank_regs[1] & 0xFE;
+        let r2 = self.bank_regs[2];
+        let r3 = self.bank_regs[3];
+        let r4 = self.bank_regs[4];
+        let r5 = self.bank_regs[5];
+
+        if (self.bank_select & 0x80) == 0 {
+            [
+                r0,
+                r0.wrapping_add(1),
+                r1,
+                r1.wrapping_add(1),
+                r2,
+                r3,
+                r4,
+                r5,
+            ]
+        } else {
+            [
+                r2,
+                r3,
+                r4,
+                r5,
+                r0,
+                r0.wrapping_add(1),
+                r1,
+                r1.wrapping_add(1),
+            ]
+        }
+    }
+
+    fn map_chr_addr(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        self.chr_bases[addr >> 10] + (addr & 0x03FF)
+    }
+
+    fn clock_irq_counter(&mut self) {
+        self.debug_irq_clocks = self.debug_irq_clocks.wrapping_add(1);
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn monitor_ppu_a12(&mut self, addr: u16) {
+        // MMC3 IRQ counter clocks on filtered A12 rising edges.
+        let a12 = (addr & 0x1000) != 0;
+        if a12 {
+            self.debug_a12_high_samples = self.debug_a12_high_samples.wrapping_add(1);
+        }
+
+        if !a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        } else if !self.last_a12 && self.a12_low_cycles >= 8 {
+            self.clock_irq_counter();
+            self.a12_low_cycles = 0;
+        } else if a12 {
+            self.a12_low_cycles = 0;
+        }
+
+        self.last_a12 = a12;
+    }
+}
+
+impl Mapper for Mapper4 {
+    state_fields!(chr if chr_is_ram, prg_ram, bank_select, bank_regs, mirroring, irq_latch, irq_counter, irq_reload, irq_enabled, irq_pending, last_a12, a12_low_cycles, prg_bases, chr_bases);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0xFFFF => {
+                let window = (addr as usize >> 13) & 0x03;
+                self.prg_rom[self.prg_bases[window] + (addr as usize & 0x1FFF)]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0x9FFF => {
+                if (addr & 1) == 0 {
+                    self.bank_select = value;
+                } else {
+                    let target = (self.bank_select & 0x07) as usize;
+                    self.bank_regs[target] = if target <= 1 { value & 0xFE } else { value };
+                }
+                self.update_banks();
+            }
+            0xA000..=0xBFFF if (addr & 1) == 0 && !self.four_screen => {
+                self.mirroring = if (value & 1) == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            0xC000..=0xDFFF => {
+                if (addr & 1) == 0 {
+                    self.irq_latch = value;
+                } else {
+                    self.irq_reload = true;
+                }
+            }
+            0xE000..=0xFFFF => {
+                if (addr & 1) == 0 {
+                    self.irq_enabled = false;
+                    self.irq_pending = false;
+                } else {
+                    self.irq_enabled = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.map_chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let mapped = self.map_chr_addr(addr);
+            self.chr[mapped] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.four_screen {
+            Mirroring::FourScreen
+        } else {
+            self.mirroring
+        }
+    }
+
+    fn notify_ppu_read_addr(&mut self, addr: u16) {
+        self.monitor_ppu_a12(addr);
+    }
+
+    fn notify_ppu_write_addr(&mut self, addr: u16) {
+        self.monitor_ppu_a12(addr);
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "MMC3 bank_select=${:02X} prg=[{:02X},{:02X}] chr=[{:02X},{:02X},{:02X},{:02X},{:02X},{:02X}] irq_latch=${:02X} irq_counter=${:02X} reload={} en={} pending={} a12_low={} last_a12={} a12_high_samples={} irq_clocks={}",
+            self.bank_select,
+            self.bank_regs[6],
+            self.bank_regs[7],
+            self.bank_regs[0],
+            self.bank_regs[1],
+            self.bank_regs[2],
+            self.bank_regs[3],
+            self.bank_regs[4],
+            self.bank_regs[5],
+            self.irq_latch,
+            self.irq_counter,
+            self.irq_reload,
+            self.irq_enabled,
+            self.irq_pending,
+            self.a12_low_cycles,
+            self.last_a12,
+            self.debug_a12_high_samples,
+            self.debug_irq_clocks
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper24 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    mirroring: Mirroring,
+    prg_banks: [u8; 4],
+    chr_banks: [u8; 8],
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+    control: u8,
+}
+
+impl Mapper24 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            mirroring: cart.mirroring,
+            prg_banks: [0, 1, 0xFE, 0xFF],
+            chr_banks: [0; 8],
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            control: 0xC0,
+        }
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn chr_bank_count_1k(&self) -> usize {
+        (self.chr.len() / 0x0400).max(1)
+    }
+}
+
+impl Mapper for Mapper24 {
+    state_fields!(chr if chr_is_ram, prg_ram, mirroring, prg_banks, chr_banks, irq_enabled, irq_counter, irq_pending, control);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0x9FFF => {
+                let bank = self.prg_banks[0] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xA000..=0xBFFF => {
+                let bank = self.prg_banks[1] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xC000..=0xDFFF => {
+                let bank = self.prg_banks[2] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xE000..=0xFFFF => {
+                let bank = self.prg_banks[3] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0x8FFF => {
+                let reg = addr & 0x0F;
+                match reg {
+                    0x0 => self.prg_banks[0] = value & 0x0F,
+                    0x2 => self.prg_banks[1] = value & 0x0F,
+                    0x4 => self.prg_banks[2] = value & 0x0F,
+                    0x6 => self.prg_banks[3] = value & 0x0F,
+                    0x8 => {
+                        self.control = value;
+                        self.mirroring = if (value & 0x01) != 0 {
+                            Mirroring::Vertical
+                        } else {
+                            Mirroring::Horizontal
+                        };
+                    }
+                    0xA => {
+                        self.irq_counter = (self.irq_counter & 0xFF00) | (value as u16);
+                    }
+                    0xE => self.irq_enabled = (value & 0x01) != 0,
+                    _ => {}
+                }
+            }
+            0x9000..=0x9FFF => {
+                let reg = addr & 0x0F;
+                match reg {
+                    0x0 => self.chr_banks[0] = value,
+                    0x2 => self.chr_banks[1] = value,
+                    0x4 => self.chr_banks[2] = value,
+                    0x6 => self.chr_banks[3] = value,
+                    0x8 => self.chr_banks[4] = value,
+                    0xA => self.chr_banks[5] = value,
+                    0xC => self.chr_banks[6] = value,
+                    0xE => self.chr_banks[7] = value,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        if addr < 0x2000 {
+            let bank = (self.chr_banks[(addr >> 10) as usize] as usize) % self.chr_bank_count_1k();
+            let idx = bank * 0x0400 + (addr as usize & 0x03FF);
+            self.chr[idx % self.chr.len()]
+        } else {
+            0
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x2000 && self.chr_is_ram {
+            let chr_len = self.chr.len();
+            let bank = (self.chr_banks[(addr >> 10) as usize] as usize) % self.chr_bank_count_1k();
+            let idx = (bank * 0x0400 + (addr as usize & 0x03FF)) % chr_len;
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if self.irq_enabled {
+            if self.irq_counter == 0 {
+                self.irq_counter = 0xFFFF;
+                self.irq_pending = true;
+            } else {
+                self.irq_counter = self.irq_counter.wrapping_sub(1);
+            }
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper25 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    mirroring: Mirroring,
+    prg_banks: [u8; 4],
+    chr_banks: [u8; 8],
+    irq_enabled: bool,
+    irq_counter: u8,
+    irq_pending: bool,
+    control: u8,
+}
+
+impl Mapper25 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            mirroring: cart.mirroring,
+            prg_banks: [0, 1, 0xFE, 0xFF],
+            chr_banks: [0; 8],
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            control: 0xC0,
+        }
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn chr_bank_count_1k(&self) -> usize {
+        (self.chr.len() / 0x0400).max(1)
+    }
+}
+
+impl Mapper for Mapper25 {
+    state_fields!(chr if chr_is_ram, prg_ram, mirroring, prg_banks, chr_banks, irq_enabled, irq_counter, irq_pending, control);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0x9FFF => {
+                let bank = self.prg_banks[0] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xA000..=0xBFFF => {
+                let bank = self.prg_banks[1] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xC000..=0xDFFF => {
+                let bank = self.prg_banks[2] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xE000..=0xFFFF => {
+                let bank = self.prg_banks[3] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0x8FFF => {
+                let reg = addr & 0x0F;
+                match reg {
+                    0x0 => self.prg_banks[0] = value & 0x0F,
+                    0x2 => self.prg_banks[1] = value & 0x0F,
+                    0x4 => self.prg_banks[2] = value & 0x0F,
+                    0x6 => self.prg_banks[3] = value & 0x0F,
+                    0x8 => {
+                        self.control = value;
+                        self.mirroring = if (value & 0x01) != 0 {
+                            Mirroring::Vertical
+                        } else {
+                            Mirroring::Horizontal
+                        };
+                    }
+                    0xA => self.irq_counter = value,
+                    0xE => self.irq_enabled = (value & 0x01) != 0,
+                    _ => {}
+                }
+            }
+            0x9000..=0x9FFF => {
+                let reg = addr & 0x0F;
+                match reg {
+                    0x0 => self.chr_banks[0] = value,
+                    0x2 => self.chr_banks[1] = value,
+                    0x4 => self.chr_banks[2] = value,
+                    0x6 => self.chr_banks[3] = value,
+                    0x8 => self.chr_banks[4] = value,
+                    0xA => self.chr_banks[5] = value,
+                    0xC => self.chr_banks[6] = value,
+                    0xE => self.chr_banks[7] = value,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        if addr < 0x2000 {
+            let bank = (self.chr_banks[(addr >> 10) as usize] as usize) % self.chr_bank_count_1k();
+            let idx = bank * 0x0400 + (addr as usize & 0x03FF);
+            self.chr[idx % self.chr.len()]
+        } else {
+            0
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x2000 && self.chr_is_ram {
+            let chr_len = self.chr.len();
+            let bank = (self.chr_banks[(addr >> 10) as usize] as usize) % self.chr_bank_count_1k();
+            let idx = (bank * 0x0400 + (addr as usize & 0x03FF)) % chr_len;
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if self.irq_enabled {
+            if self.irq_counter == 0 {
+                self.irq_counter = 0xFF;
+                self.irq_pending = true;
+            } else {
+                self.irq_counter = self.irq_counter.wrapping_sub(1);
+            }
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+struct Mapper26 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    mirroring: Mirroring,
+    prg_banks: [u8; 4],
+    chr_banks: [u8; 8],
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+    control: u8,
+}
+
+impl Mapper26 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            mirroring: cart.mirroring,
+            prg_banks: [0, 1, 0xFE, 0xFF],
+            chr_banks: [0; 8],
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            control: 0xC0,
+        }
+    }
+
+    fn prg_bank_count_8k(&self) -> usize {
+        (self.prg_rom.len() / 0x2000).max(1)
+    }
+
+    fn chr_bank_count_1k(&self) -> usize {
+        (self.chr.len() / 0x0400).max(1)
+    }
+}
+
+impl Mapper for Mapper26 {
+    state_fields!(chr if chr_is_ram, prg_ram, mirroring, prg_banks, chr_banks, irq_enabled, irq_counter, irq_pending, control);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx]
+            }
+            0x8000..=0x9FFF => {
+                let bank = self.prg_banks[0] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xA000..=0xBFFF => {
+                let bank = self.prg_banks[1] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xC000..=0xDFFF => {
+                let bank = self.prg_banks[2] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            0xE000..=0xFFFF => {
+                let bank = self.prg_banks[3] as usize % self.prg_bank_count_8k();
+                let idx = bank * 0x2000 + (addr as usize & 0x1FFF);
+                self.prg_rom[idx % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            0x8000..=0x8FFF => {
+                let reg = addr & 0x0F;
+                match reg {
+                    0x0 => self.prg_banks[0] = value & 0x0F,
+                    0x2 => self.prg_banks[1] = value & 0x0F,
+                    0x4 => self.prg_banks[2] = value & 0x0F,
+                    0x6 => self.prg_banks[3] = value & 0x0F,
+                    0x8 => {
+                        self.control = value;
+                        self.mirroring = if (value & 0x01) != 0 {
+                            Mirroring::Vertical
+                        } else {
+                            Mirroring::Horizontal
+                        };
+                    }
+                    0xA => {
+                        self.irq_counter = (self.irq_counter & 0xFF00) | (value as u16);
+                    }
+                    0xE => self.irq_enabled = (value & 0x01) != 0,
+                    _ => {}
+                }
+            }
+            0x9000..=0x9FFF => {
+                let reg = addr & 0x0F;
+                match reg {
+                    0x0 => self.chr_banks[0] = value,
+                    0x2 => self.chr_banks[1] = value,
+                    0x4 => self.chr_banks[2] = value,
+                    0x6 => self.chr_banks[3] = value,
+                    0x8 => self.chr_banks[4] = value,
+                    0xA => self.chr_banks[5] = value,
+                    0xC => self.chr_banks[6] = value,
+                    0xE => self.chr_banks[7] = value,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        if addr < 0x2000 {
+            let bank = (self.chr_banks[(addr >> 10) as usize] as usize) % self.chr_bank_count_1k();
+            let idx = bank * 0x0400 + (addr as usize & 0x03FF);
+            self.chr[idx % self.chr.len()]
+        } else {
+            0
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x2000 && self.chr_is_ram {
+            let chr_len = self.chr.len();
+            let bank = (self.chr_banks[(addr >> 10) as usize] as usize) % self.chr_bank_count_1k();
+            let idx = (bank * 0x0400 + (addr as usize & 0x03FF)) % chr_len;
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        if self.irq_enabled {
+            if self.irq_counter == 0 {
+                self.irq_counter = 0xFFFF;
+                self.irq_pending = true;
+            } else {
+                self.irq_counter = self.irq_counter.wrapping_sub(1);
+            }
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+/// Konami VRC7 (Lagrange Point, Tiny Toon Adventures 2): three 8 KB PRG
+/// banks with the last fixed, eight 1 KB CHR banks, 8 KB of PRG RAM and
+/// the VRC IRQ counter, which counts CPU cycles or, through a prescaler,
+/// scanlines. Registers are decoded by A15-A12 and one low line, A4 on
+/// VRC7a boards and A3 on VRC7b, so both are folded into one. VRC7a adds
+/// the FM audio chip at $9010 and $9030.
+struct Mapper85 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: Vec<u8>,
+    prg_ram_dirty: bool,
+    prg_ram_enabled: bool,
+    mirroring: Mirroring,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    irq_latch: u8,
+    irq_counter: u8,
+    /// CPU cycles, in thirds, until the next scanline clock.
+    irq_prescaler: i16,
+    irq_enabled: bool,
+    irq_enable_after_ack: bool,
+    irq_cycle_mode: bool,
+    irq_pending: bool,
+    audio: Vrc7,
+}
+
+impl Mapper85 {
+    fn new(cart: Cartridge) -> Self {
+        Self {
+            prg_rom: cart.prg_rom,
+            chr: cart.chr_data,
+            chr_is_ram: cart.chr_is_ram,
+            prg_ram: vec![0; cart.prg_ram_size.max(8 * 1024)],
+            prg_ram_dirty: false,
+            prg_ram_enabled: false,
+            mirroring: cart.mirroring,
+            prg_banks: [0, 1, 2],
+            chr_banks: [0; 8],
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_prescaler: 341,
+            irq_enabled: false,
+            irq_enable_after_ack: false,
+            irq_cycle_mode: false,
+            irq_pending: false,
+            audio: Vrc7::default(),
+        }
+    }
+
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x8000..=0x9FFF => self.prg_banks[0] as usize,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize,
+            0xC000..=0xDFFF => self.prg_banks[2] as usize,
+            _ => (self.prg_rom.len() / 0x2000).max(1) - 1,
+        };
+        (bank_base(bank, 0x2000, self.prg_rom.len()) + (addr as usize & 0x1FFF))
+            % self.prg_rom.len()
+    }
+
+    fn chr_index(&self, addr: u16) -> usize {
+        let addr = addr as usize & 0x1FFF;
+        let base = bank_base(self.chr_banks[addr >> 10] as usize, 0x0400, self.chr.len());
+        (base + (addr & 0x03FF)) % self.chr.len()
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+}
+
+impl Mapper for Mapper85 {
+    state_fields!(chr if chr_is_ram, prg_ram, prg_ram_enabled, mirroring, prg_banks, chr_banks, irq_latch, irq_counter, irq_prescaler, irq_enabled, irq_enable_after_ack, irq_cycle_mode, irq_pending, audio);
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => {
+                self.prg_ram[(addr as usize - 0x6000) % self.prg_ram.len()]
+            }
+            0x8000..=0xFFFF => self.prg_rom[self.prg_index(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            if (0x6000..=0x7FFF).contains(&addr) && self.prg_ram_enabled {
+                let idx = (addr as usize - 0x6000) % self.prg_ram.len();
+                self.prg_ram[idx] = value;
+                self.prg_ram_dirty = true;
+            }
+            return;
+        }
+        let odd = addr & 0x18 != 0;
+        match (addr & 0xF000, odd) {
+            _ if addr & 0xF030 == 0x9010 => self.audio.select(value),
+            _ if addr & 0xF030 == 0x9030 => self.audio.write(value),
+            (0x8000, false) => self.prg_banks[0] = value & 0x3F,
+            (0x8000, true) => self.prg_banks[1] = value & 0x3F,
+            (0x9000, false) => self.prg_banks[2] = value & 0x3F,
+            (0xA000..=0xD000, _) => {
+                let slot = ((addr as usize - 0xA000) >> 11 & 0x06) | usize::from(odd);
+                self.chr_banks[slot] = value;
+            }
+            (0xE000, false) => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+                self.audio.set_muted(value & 0x40 != 0);
+                self.prg_ram_enabled = value & 0x80 != 0;
+            }
+            (0xE000, true) => self.irq_latch = value,
+            (0xF000, false) => {
+                self.irq_enable_after_ack = value & 0x01 != 0;
+                self.irq_enabled = value & 0x02 != 0;
+                self.irq_cycle_mode = value & 0x04 != 0;
+                self.irq_pending = false;
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_latch;
+                    self.irq_prescaler = 341;
+                }
+            }
+            (0xF000, true) => {
+                self.irq_pending = false;
+                self.irq_enabled = self.irq_enable_after_ack;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_is_ram {
+            let idx = self.chr_index(addr);
+            self.chr[idx] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_cpu_cycle(&mut self) {
+        self.audio.tick();
+        if !self.irq_enabled {
+            return;
+        }
+        if self.irq_cycle_mode {
+            self.clock_irq_counter();
+            return;
+        }
+        // A scanline is 341 PPU dots, three to a CPU cycle.
+        self.irq_prescaler -= 3;
+        if self.irq_prescaler <= 0 {
+            self.irq_prescaler += 341;
+            self.clock_irq_counter();
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn clear_irq(&mut self) {
+        self.irq_pending = false;
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn debug_peek_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn debug_state(&self) -> String {
+        format!(
+            "VRC7 prg={:02X?} chr={:02X?} ram={} irq={:02X}/{:02X} en={} cycle_mode={} pending={} mirroring={:?}",
+            self.prg_banks,
+            self.chr_banks,
+            self.prg_ram_enabled,
+            self.irq_counter,
+            self.irq_latch,
+            self.irq_enabled,
+            self.irq_cycle_mode,
+            self.irq_pending,
+            self.mirroring
+        )
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn chr_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_is_ram.then_some(&mut self.chr[..])
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.prg_ram_dirty)
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom
+    }
+
+    fn chr_mut(&mut self) -> &mut [u8] {
+        &mut self.chr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::ppu::Ppu;
+
+    fn patterned_banks(total_size: usize, bank_size: usize) -> Vec<u8> {
+        let mut data = vec![0u8; total_size];
+        for (bank, chunk) in data.chunks_mut(bank_size).enumerate() {
+            chunk.fill((bank as u8).wrapping_add(1));
+        }
+        data
+    }
+
+    fn make_cart(
+        mapper_id: u16,
+        submapper_id: u8,
+        prg_rom: Vec<u8>,
+        chr_data: Vec<u8>,
+        chr_is_ram: bool,
+    ) -> Cartridge {
+        Cartridge {
+            mapper_id,
+            submapper_id,
+            mirroring: Mirroring::Horizontal,
+            four_screen: false,
+            has_battery_backed_ram: false,
+            prg_rom,
+            chr_data,
+            chr_is_ram,
+            prg_ram_size: 8 * 1024,
+            region: None,
+        }
+    }
+
+    /// Runs a real PPU over an MMC3 for one frame and returns the scanline
+    /// counter clocks its A12 line produced.
+    fn run_mmc3_frame_a12_clocks(ctrl: u8) -> u64 {
+        let prg = patterned_banks(4 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper4::new(make_cart(4, 0, prg, chr, false));
+        let mut ppu = Ppu::new();
+
+        ppu.cpu_write_register(0x2000, ctrl, &mut mapper);
+        ppu.cpu_write_register(0x2001, 0x18, &mut mapper);
+
+        for _ in 0..341 * 262 {
+            ppu.tick(&mut mapper);
+        }
+
+        mapper.debug_irq_clocks
+    }
+
+    /// Builds the PPU pattern/nametable fetch addresses for one visible scanline
+    /// in hardware order: 32 BG tiles (cycles 1-256), 8 sprite slots (257-320),
+    /// 2 prefetched BG tiles (321-336) and the two dummy nametable reads.
+    /// Empty sprite slots fetch tile $FF, as the real PPU does.
+    fn mmc3_scanline_fetches(ctrl: u8, sprite_tiles: &[u8]) -> Vec<u16> {
+        let bg_base = if (ctrl & 0x10) != 0 { 0x1000 } else { 0x0000 };
+        let sprite_base = if (ctrl & 0x08) != 0 { 0x1000 } else { 0x0000 };
+        let tall_sprites = (ctrl & 0x20) != 0;
+        let mut fetches = Vec::with_capacity(170);
+
+        let push_bg_tile = |fetches: &mut Vec<u16>| {
+            fetches.extend_from_slice(&[0x2000, 0x23C0, bg_base, bg_base | 0x0008]);
+        };
+
+        for _ in 0..32 {
+            push_bg_tile(&mut fetches);
+        }
+
+        for slot in 0..8 {
+            let tile = sprite_tiles.get(slot).copied().unwrap_or(0xFF);
+            let pattern = if tall_sprites {
+                (((tile & 0x01) as u16) << 12) | (((tile & 0xFE) as u16) << 4)
+            } else {
+                sprite_base | ((tile as u16) << 4)
+            };
+            fetches.extend_from_slice(&[0x2000, 0x2000, pattern, pattern | 0x0008]);
+        }
+
+        for _ in 0..2 {
+            push_bg_tile(&mut fetches);
+        }
+        fetches.extend_from_slice(&[0x2000, 0x2000]);
+        fetches
+    }
+
+    fn mmc3_with_irq_latch(latch: u8) -> Mapper4 {
+        let prg = patterned_banks(4 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper4::new(make_cart(4, 0, prg, chr, false));
+        mapper.cpu_write(0xC000, latch);
+        mapper.cpu_write(0xC001, 0x00);
+        mapper.cpu_write(0xE001, 0x00);
+        mapper
+    }
+
+    fn run_mmc3_scanlines(mapper: &mut Mapper4, ctrl: u8, sprite_tiles: &[u8], scanlines: u32) {
+        let fetches = mmc3_scanline_fetches(ctrl, sprite_tiles);
+        for _ in 0..scanlines {
+            for &addr in &fetches {
+                mapper.notify_ppu_read_addr(addr);
+            }
+        }
+    }
+
+    #[test]
+    fn mapper2_keeps_last_bank_fixed() {
+        let prg = patterned_banks(3 * 0x4000, 0x4000);
+        let chr = vec![0; 0x2000];
+        let mut mapper = Mapper2::new(make_cart(2, 0, prg, chr, false));
+
+        mapper.cpu_write(0x8000, 1);
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+        assert_eq!(mapper.cpu_read(0xC000), 3);
+    }
+
+    #[test]
+    fn mapper3_switches_chr_bank() {
+        let prg = patterned_banks(0x8000, 0x4000);
+        let chr = patterned_banks(2 * 0x2000, 0x2000);
+        let mut mapper = Mapper3::new(make_cart(3, 0, prg, chr, false));
+
+        mapper.cpu_write(0x8000, 1);
+        assert_eq!(mapper.ppu_read(0x0000), 2);
+    }
+
+    #[test]
+    fn mapper3_allows_chr_ram_writes_when_present() {
+        let prg = patterned_banks(0x8000, 0x4000);
+        let chr = vec![0; 2 * 0x2000];
+        let mut mapper = Mapper3::new(make_cart(3, 0, prg, chr, true));
+
+        mapper.cpu_write(0x8000, 1);
+        mapper.ppu_write(0x0010, 0xAB);
+        assert_eq!(mapper.ppu_read(0x0010), 0xAB);
+    }
+
+    #[test]
+    fn mapper67_banks_and_counts_the_irq_down_from_two_writes() {
+        let prg = patterned_banks(8 * 0x4000, 0x4000);
+        let chr = patterned_banks(8 * 0x0800, 0x0800);
+        let mut mapper = Mapper67::new(make_cart(67, 0, prg, chr, false));
+        mapper.cpu_write(0xF800, 2);
+        mapper.cpu_write(0xB800, 5);
+        mapper.cpu_write(0xE800, 1);
+        assert_eq!(mapper.cpu_read(0x8000), 3);
+        assert_eq!(mapper.cpu_read(0xC000), 8);
+        assert_eq!(mapper.ppu_read(0x1800), 6);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+        mapper.cpu_write(0xC800, 0x01);
+        mapper.cpu_write(0xC800, 0x02);
+        mapper.cpu_write(0xD800, 0x10);
+        // $0102 cycles to reach zero, one more to wrap.
+        for _ in 0..0x0102 {
+            mapper.tick_cpu_cycle();
+        }
+        assert!(!mapper.irq_pending());
+        mapper.tick_cpu_cycle();
+        assert!(mapper.irq_pending());
+        mapper.cpu_write(0x8000, 0);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn mapper11_uses_low_bits_for_prg_and_high_nibble_for_chr() {
+        let prg = patterned_banks(4 * 0x8000, 0x8000);
+        let chr = patterned_banks(16 * 0x2000, 0x2000);
+        let mut mapper = Mapper11::new(make_cart(11, 0, prg, chr, false));
+        mapper.cpu_write(0x8000, 0x52);
+        assert_eq!(mapper.cpu_read(0x8000), 3);
+        assert_eq!(mapper.ppu_read(0x0000), 6);
+    }
+
+    #[test]
+    fn mapper46_combines_outer_and_inner_banks() {
+        let prg = patterned_banks(8 * 0x8000, 0x8000);
+        let chr = patterned_banks(32 * 0x2000, 0x2000);
+        let mut mapper = Mapper46::new(make_cart(46, 0, prg, chr, false));
+        mapper.cpu_write(0x6000, 0x32);
+        mapper.cpu_write(0x8000, 0x51);
+        // PRG (2 << 1) | 1, CHR (3 << 3) | 5.
+        assert_eq!(mapper.cpu_read(0x8000), 6);
+        assert_eq!(mapper.ppu_read(0x0000), 30);
+    }
+
+    #[test]
+    fn mapper32_swaps_prg_and_fixes_major_league_mirroring() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper32::new(make_cart(32, 0, prg.clone(), chr.clone(), false));
+        mapper.cpu_write(0x8000, 3);
+        mapper.cpu_write(0xA000, 4);
+        mapper.cpu_write(0xB005, 2);
+        let windows = [0x8000, 0xA000, 0xC000, 0xE000];
+        assert_eq!(windows.map(|addr| mapper.cpu_read(addr)), [4, 5, 7, 8]);
+        assert_eq!(mapper.ppu_read(0x1400), 3);
+        mapper.cpu_write(0x9000, 0x03);
+        assert_eq!(windows.map(|addr| mapper.cpu_read(addr)), [7, 5, 4, 8]);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+        let mut major_league = Mapper32::new(make_cart(32, 1, prg, chr, false));
+        major_league.cpu_write(0x9000, 0x01);
+        assert_eq!(major_league.mirroring(), Mirroring::OneScreenLower);
+    }
+
+    #[test]
+    fn mapper33_and_48_bank_and_delay_the_tc0690_irq() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(16 * 0x0400, 0x0400);
+        let mut tc0190 = Mapper33::new(make_cart(33, 0, prg.clone(), chr.clone(), false));
+        tc0190.cpu_write(0x8000, 0x43);
+        tc0190.cpu_write(0x8001, 0x02);
+        tc0190.cpu_write(0x8003, 0x02);
+        tc0190.cpu_write(0xA002, 0x09);
+        assert_eq!(tc0190.cpu_read(0x8000), 4);
+        assert_eq!(tc0190.cpu_read(0xA000), 3);
+        assert_eq!(tc0190.cpu_read(0xC000), 7);
+        assert_eq!(tc0190.cpu_read(0xE000), 8);
+        // 2 KB bank 2 is 1 KB banks 4-5.
+        assert_eq!(tc0190.ppu_read(0x0C00), 6);
+        assert_eq!(tc0190.ppu_read(0x1800), 10);
+        assert_eq!(tc0190.mirroring(), Mirroring::Horizontal);
+
+        let mut tc0690 = Mapper33::new(make_cart(48, 0, prg, chr, false));
+        tc0690.cpu_write(0x8000, 0x41);
+        assert_eq!(tc0690.cpu_read(0x8000), 2);
+        assert_eq!(tc0690.mirroring(), Mirroring::Horizontal);
+        tc0690.cpu_write(0xE000, 0x00);
+        assert_eq!(tc0690.mirroring(), Mirroring::Vertical);
+
+        // Latch written inverted: $FE counts one line.
+        tc0690.cpu_write(0xC000, 0xFE);
+        tc0690.cpu_write(0xC001, 0x00);
+        tc0690.cpu_write(0xC002, 0x00);
+        for line in 0..2 {
+            for _ in 0..8 {
+                tc0690.notify_ppu_read_addr(0x0000);
+            }
+            tc0690.notify_ppu_read_addr(0x1000);
+            assert!(!tc0690.irq_pending(), "line {line}");
+        }
+        for _ in 0..TC0690_IRQ_DELAY - 1 {
+            tc0690.tick_cpu_cycle();
+        }
+        assert!(!tc0690.irq_pending());
+        tc0690.tick_cpu_cycle();
+        assert!(tc0690.irq_pending());
+        tc0690.cpu_write(0xC003, 0x00);
+        assert!(!tc0690.irq_pending());
+    }
+
+    #[test]
+    fn mapper34_tells_bnrom_from_nina001() {
+        let prg = patterned_banks(4 * 0x8000, 0x8000);
+        let mut bnrom = Mapper34::new(make_cart(34, 0, prg.clone(), vec![0; 0x2000], true));
+        bnrom.cpu_write(0x8000, 3);
+        assert_eq!(bnrom.cpu_read(0x8000), 4);
+        bnrom.cpu_write(0x7FFD, 0);
+        assert_eq!(bnrom.cpu_read(0xFFFF), 4);
+
+        let chr = patterned_banks(8 * 0x1000, 0x1000);
+        let mut nina = Mapper34::new(make_cart(34, 0, prg, chr, false));
+        nina.cpu_write(0x8000, 1);
+        assert_eq!(nina.cpu_read(0x8000), 1);
+        nina.cpu_write(0x7FFD, 1);
+        nina.cpu_write(0x7FFE, 5);
+        nina.cpu_write(0x7FFF, 2);
+        assert_eq!(nina.cpu_read(0x8000), 2);
+        assert_eq!(nina.ppu_read(0x0000), 6);
+        assert_eq!(nina.ppu_read(0x1000), 3);
+        // The registers are also RAM.
+        assert_eq!(nina.cpu_read(0x7FFE), 5);
+    }
+
+    #[test]
+    fn mapper39_is_bnrom_with_chr_ram() {
+        let prg = patterned_banks(8 * 0x8000, 0x8000);
+        let mut mapper = Mapper34::new(make_cart(39, 0, prg, vec![0; 0x2000], true));
+        mapper.cpu_write(0x8000, 5);
+        assert_eq!(mapper.cpu_read(0x8000), 6);
+        assert_eq!(mapper.cpu_read(0xFFFF), 6);
+        mapper.cpu_write(0xC123, 7);
+        assert_eq!(mapper.cpu_read(0x8000), 8);
+        // No NINA-001 registers in front of the RAM.
+        mapper.cpu_write(0x7FFD, 0);
+        assert_eq!(mapper.cpu_read(0x8000), 8);
+
+        mapper.ppu_write(0x1ABC, 0x5A);
+        assert_eq!(mapper.ppu_read(0x1ABC), 0x5A);
+    }
+
+    #[test]
+    fn mapper38_switches_prg_and_chr_from_7000() {
+        let prg = patterned_banks(4 * 0x8000, 0x8000);
+        let chr = patterned_banks(4 * 0x2000, 0x2000);
+        let mut mapper = Mapper38::new(make_cart(38, 0, prg, chr, false));
+        mapper.cpu_write(0x8000, 0x0F);
+        assert_eq!(mapper.cpu_read(0x8000), 1);
+        mapper.cpu_write(0x7000, 0b1001);
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+        assert_eq!(mapper.ppu_read(0x0000), 3);
+    }
+
+    #[test]
+    fn mapper40_and_50_map_fixed_windows_and_time_the_irq() {
+        let prg = patterned_banks(16 * 0x2000, 0x2000);
+        let mut ntdec = Mapper40::new(make_cart(40, 0, prg.clone(), vec![0; 0x2000], true));
+        ntdec.cpu_write(0xE000, 0x02);
+        assert_eq!(
+            [0x6000, 0x8000, 0xA000, 0xC000, 0xE000].map(|addr| ntdec.cpu_read(addr)),
+            [7, 5, 6, 3, 8]
+        );
+        ntdec.cpu_write(0xA000, 0);
+        for _ in 0..0x0FFF {
+            ntdec.tick_cpu_cycle();
+        }
+        assert!(!ntdec.irq_pending());
+        ntdec.tick_cpu_cycle();
+        assert!(ntdec.irq_pending());
+        ntdec.cpu_write(0x8000, 0);
+        assert!(!ntdec.irq_pending());
+
+        let mut n32 = Mapper40::new(make_cart(50, 0, prg, vec![0; 0x2000], true));
+        // Bits 0-3 of the register are bank bits 2, 0, 1 and 3.
+        n32.cpu_write(0x4020, 0b1011);
+        assert_eq!(n32.cpu_read(0xC000), 14);
+        assert_eq!(n32.cpu_read(0x6000), 16);
+        n32.cpu_write(0x4120, 1);
+        for _ in 0..0x1000 {
+            n32.tick_cpu_cycle();
+        }
+        assert!(n32.irq_pending());
+    }
+
+    #[test]
+    fn mapper79_and_113_decode_registers_below_6000() {
+        let prg = patterned_banks(8 * 0x8000, 0x8000);
+        let chr = patterned_banks(16 * 0x2000, 0x2000);
+        let mut nina = Mapper79::new(make_cart(79, 0, prg.clone(), chr.clone(), false));
+        nina.cpu_write(0x4100, 0x0D);
+        assert_eq!(nina.cpu_read(0x8000), 2);
+        assert_eq!(nina.ppu_read(0x0000), 6);
+        // A8 clear: not the register.
+        nina.cpu_write(0x4200, 0x00);
+        assert_eq!(nina.cpu_read(0x8000), 2);
+        nina.cpu_write(0x5FFF, 0x00);
+        assert_eq!(nina.cpu_read(0x8000), 1);
+
+        let mut hes = Mapper79::new(make_cart(113, 0, prg, chr, false));
+        hes.cpu_write(0x4100, 0xDA);
+        // PRG bits 3-5 = 3, CHR bit 6 set over bits 0-2 = 2.
+        assert_eq!(hes.cpu_read(0x8000), 4);
+        assert_eq!(hes.ppu_read(0x0000), 11);
+        assert_eq!(hes.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn mapper146_decodes_like_nina_03() {
+        let prg = patterned_banks(2 * 0x8000, 0x8000);
+        let chr = patterned_banks(8 * 0x2000, 0x2000);
+        let mut sachen = Mapper79::new(make_cart(146, 0, prg, chr, false));
+        sachen.cpu_write(0x4100, 0x0B);
+        assert_eq!(sachen.cpu_read(0x8000), 2);
+        assert_eq!(sachen.ppu_read(0x0000), 4);
+        // Mirrors wherever A14, A13 and A8 are 1, 0, 1.
+        sachen.cpu_write(0x5F00, 0x05);
+        assert_eq!(sachen.cpu_read(0x8000), 1);
+        assert_eq!(sachen.ppu_read(0x0000), 6);
+        // A8 clear, or A13 set at $6100, isn't the register.
+        sachen.cpu_write(0x4000, 0x0F);
+        sachen.cpu_write(0x6100, 0x0F);
+        assert_eq!(sachen.cpu_read(0x8000), 1);
+        assert_eq!(sachen.ppu_read(0x0000), 6);
+        // No HES bits: bit 6 isn't CHR and bit 7 isn't mirroring.
+        sachen.cpu_write(0x4100, 0xC1);
+        assert_eq!(sachen.ppu_read(0x0000), 2);
+        assert_eq!(sachen.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn taito_x1_banks_and_ram_protection() {
+        let prg = patterned_banks(16 * 0x2000, 0x2000);
+        let chr = patterned_banks(32 * 0x0400, 0x0400);
+        let mut x1_005 = Mapper80::new(make_cart(80, 0, prg.clone(), chr.clone(), false));
+        x1_005.cpu_write(0x7EFB, 0x03);
+        x1_005.cpu_write(0x7EF1, 0x05);
+        x1_005.cpu_write(0x7EF5, 0x09);
+        assert_eq!(x1_005.cpu_read(0x8000), 4);
+        assert_eq!(x1_005.cpu_read(0xE000), 16);
+        assert_eq!(x1_005.ppu_read(0x0C00), 6);
+        assert_eq!(x1_005.ppu_read(0x1C00), 10);
+        x1_005.cpu_write(0x7F05, 0x42);
+        assert_eq!(x1_005.cpu_read(0x7F05), 0);
+        x1_005.cpu_write(0x7EF8, 0xA3);
+        x1_005.cpu_write(0x7F05, 0x42);
+        assert_eq!(x1_005.cpu_read(0x7F85), 0x42);
+        assert!(x1_005.take_prg_ram_dirty());
+
+        let mut x1_017 = Mapper80::new(make_cart(82, 0, prg, chr, false));
+        x1_017.cpu_write(0x7EFA, 0x0C);
+        x1_017.cpu_write(0x7EF0, 0x04);
+        x1_017.cpu_write(0x7EF2, 0x01);
+        x1_017.cpu_write(0x7EF6, 0x03);
+        assert_eq!(x1_017.cpu_read(0x8000), 4);
+        assert_eq!(x1_017.mirroring(), Mirroring::Vertical);
+        // Inverted: the 1 KB banks sit at $0000, the 2 KB ones at $1000.
+        assert_eq!(x1_017.ppu_read(0x0000), 2);
+        assert_eq!(x1_017.ppu_read(0x1400), 6);
+        x1_017.cpu_write(0x7EF8, 0x69);
+        x1_017.cpu_write(0x6000, 0x11);
+        x1_017.cpu_write(0x6800, 0x22);
+        assert_eq!(x1_017.cpu_read(0x6000), 0);
+        assert_eq!(x1_017.cpu_read(0x6800), 0x22);
+        assert_eq!(x1_017.prg_ram()[0x0800], 0x22);
+    }
+
+    #[test]
+    fn jaleco_jf_boards_latch_banks_and_report_samples() {
+        let prg = patterned_banks(8 * 0x4000, 0x4000);
+        let chr = patterned_banks(8 * 0x2000, 0x2000);
+        let mut jf13 = Mapper86::new(make_cart(86, 0, prg.clone(), chr.clone(), false));
+        jf13.cpu_write(0x6000, 0x61);
+        // 32 KB bank 2 starts with 16 KB bank 4; CHR 1 | 4.
+        assert_eq!(jf13.cpu_read(0x8000), 5);
+        assert_eq!(jf13.ppu_read(0x0000), 6);
+        jf13.cpu_write(0x7000, 0x23);
+        assert_eq!(jf13.take_sample_trigger(), Some(3));
+        assert_eq!(jf13.take_sample_trigger(), None);
+
+        let mut jf17 = Mapper72::new(make_cart(72, 0, prg.clone(), chr.clone(), false));
+        jf17.cpu_write(0x8000, 0x83);
+        jf17.cpu_write(0x8000, 0x85);
+        // Bit 7 stayed high, so the second write latched nothing.
+        assert_eq!(jf17.cpu_read(0x8000), 4);
+        assert_eq!(jf17.cpu_read(0xC000), 8);
+        jf17.cpu_write(0x8000, 0x42);
+        assert_eq!(jf17.ppu_read(0x0000), 3);
+
+        let mut jf19 = Mapper72::new(make_cart(92, 0, prg, chr, false));
+        jf19.cpu_write(0x8000, 0x83);
+        assert_eq!(jf19.cpu_read(0x8000), 1);
+        assert_eq!(jf19.cpu_read(0xC000), 4);
+    }
+
+    #[test]
+    fn uxrom_variants_place_the_switchable_window() {
+        let prg = patterned_banks(8 * 0x4000, 0x4000);
+        let chr = patterned_banks(16 * 0x2000, 0x2000);
+        let mapper = |id: u16| Mapper89::new(make_cart(id, 0, prg.clone(), chr.clone(), false));
+
+        let mut sunsoft2 = mapper(89);
+        sunsoft2.cpu_write(0x8000, 0xAD);
+        assert_eq!(sunsoft2.cpu_read(0x8000), 3);
+        assert_eq!(sunsoft2.cpu_read(0xC000), 8);
+        assert_eq!(sunsoft2.ppu_read(0x0000), 14);
+        assert_eq!(sunsoft2.mirroring(), Mirroring::OneScreenUpper);
+
+        let mut un1rom = mapper(94);
+        un1rom.cpu_write(0x8000, 0x14);
+        assert_eq!(un1rom.cpu_read(0x8000), 6);
+
+        let mut tam_s1 = mapper(97);
+        tam_s1.cpu_write(0x8000, 0x82);
+        tam_s1.cpu_write(0xC000, 0x05);
+        assert_eq!(tam_s1.cpu_read(0x8000), 8);
+        assert_eq!(tam_s1.cpu_read(0xC000), 3);
+        assert_eq!(tam_s1.mirroring(), Mirroring::Vertical);
+
+        let mut and_unrom = mapper(180);
+        and_unrom.cpu_write(0x8000, 0x03);
+        assert_eq!(and_unrom.cpu_read(0x8000), 1);
+        assert_eq!(and_unrom.cpu_read(0xC000), 4);
+    }
+
+    #[test]
+    fn bandai_74161_boards_switch_prg_and_chr() {
+        let prg = patterned_banks(16 * 0x4000, 0x4000);
+        let chr = patterned_banks(16 * 0x2000, 0x2000);
+        let mut mapper70 = Mapper70::new(make_cart(70, 0, prg.clone(), chr.clone(), false));
+        mapper70.cpu_write(0x8000, 0x93);
+        assert_eq!(mapper70.cpu_read(0x8000), 10);
+        assert_eq!(mapper70.cpu_read(0xC000), 16);
+        assert_eq!(mapper70.ppu_read(0x0000), 4);
+
+        let mut mapper152 = Mapper70::new(make_cart(152, 0, prg, chr, false));
+        mapper152.cpu_write(0x8000, 0x93);
+        assert_eq!(mapper152.cpu_read(0x8000), 2);
+        assert_eq!(mapper152.mirroring(), Mirroring::OneScreenUpper);
+    }
+
+    #[test]
+    fn bandai_fcg_irq_counts_cpu_cycles() {
+        let prg = patterned_banks(8 * 0x4000, 0x4000);
+        let chr = patterned_banks(16 * 0x0400, 0x0400);
+        let mut fcg = Mapper16::new(make_cart(16, 4, prg.clone(), chr.clone(), false));
+        fcg.cpu_write(0x6008, 0x02);
+        fcg.cpu_write(0x6003, 0x07);
+        // The FCG only decodes $6000-$7FFF.
+        fcg.cpu_write(0x8008, 0x05);
+        assert_eq!(fcg.cpu_read(0x8000), 3);
+        assert_eq!(fcg.ppu_read(0x0C00), 8);
+        fcg.cpu_write(0x600B, 0x03);
+        fcg.cpu_write(0x600C, 0x00);
+        fcg.cpu_write(0x600A, 0x01);
+        fcg.tick_cpu_cycle();
+        fcg.tick_cpu_cycle();
+        assert!(!fcg.irq_pending());
+        fcg.tick_cpu_cycle();
+        assert!(fcg.irq_pending());
+
+        let mut lz93d50 = Mapper16::new(make_cart(16, 5, prg, chr, false));
+        lz93d50.cpu_write(0x800B, 0x02);
+        lz93d50.cpu_write(0x800C, 0x00);
+        lz93d50.tick_cpu_cycle();
+        // The latch reaches the counter only when the IRQ is enabled.
+        lz93d50.cpu_write(0x800A, 0x01);
+        lz93d50.tick_cpu_cycle();
+        assert!(!lz93d50.irq_pending());
+        lz93d50.tick_cpu_cycle();
+        assert!(lz93d50.irq_pending());
+        lz93d50.cpu_write(0x800A, 0x00);
+        assert!(!lz93d50.irq_pending());
+    }
+
+    /// Bit-bangs a Bandai board's EEPROM through $800D and $6000.
+    struct EepromBus<'a>(&'a mut Mapper16);
+
+    impl EepromBus<'_> {
+        fn lines(&mut self, scl: u8, sda: u8) {
+            self.0.cpu_write(0x800D, (scl << 5) | (sda << 6));
+        }
+
+        fn start(&mut self) {
+            self.lines(0, 1);
+            self.lines(1, 1);
+            self.lines(1, 0);
+            self.lines(0, 0);
+        }
+
+        fn stop(&mut self) {
+            self.lines(0, 0);
+            self.lines(1, 0);
+            self.lines(1, 1);
+        }
+
+        fn clock(&mut self, sda: u8) -> u8 {
+            self.lines(0, sda);
+            self.lines(1, sda);
+            let out = (self.0.cpu_read(0x6000) >> 4) & 1;
+            self.lines(0, sda);
+            out
+        }
+
+        /// Sends a byte and returns whether the chip acked it.
+        fn send(&mut self, byte: u8, lsb_first: bool) -> bool {
+            for index in 0..8 {
+                let bit = if lsb_first { index } else { 7 - index };
+                self.clock((byte >> bit) & 1);
+            }
+            self.clock(1) == 0
+        }
+
+        fn receive(&mut self, lsb_first: bool, ack: bool) -> u8 {
+            let mut byte = 0;
+            for index in 0..8 {
+                let bit = if lsb_first { index } else { 7 - index };
+                byte |= self.clock(1) << bit;
+            }
+            self.clock(u8::from(!ack));
+            byte
+        }
+    }
+
+    #[test]
+    fn bandai_eeproms_store_and_read_back_bytes() {
+        let prg = patterned_banks(2 * 0x4000, 0x4000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut x24c02 = Mapper16::new(make_cart(16, 5, prg.clone(), chr.clone(), false));
+        let mut bus = EepromBus(&mut x24c02);
+        bus.start();
+        assert!(bus.send(0xA0, false));
+        assert!(bus.send(0x12, false));
+        assert!(bus.send(0x5A, false));
+        assert!(bus.send(0xC3, false));
+        bus.stop();
+        bus.start();
+        assert!(bus.send(0xA0, false));
+        assert!(bus.send(0x12, false));
+        bus.start();
+        assert!(bus.send(0xA1, false));
+        assert_eq!(bus.receive(false, true), 0x5A);
+        assert_eq!(bus.receive(false, false), 0xC3);
+        bus.stop();
+        // Another device's address goes unanswered.
+        bus.start();
+        assert!(!bus.send(0xB0, false));
+        bus.stop();
+        assert!(x24c02.take_prg_ram_dirty());
+        assert_eq!(x24c02.prg_ram()[0x12..0x14], [0x5A, 0xC3]);
+
+        let mut x24c01 = Mapper16::new(make_cart(159, 0, prg, chr, false));
+        let mut bus = EepromBus(&mut x24c01);
+        bus.start();
+        assert!(bus.send(0x05, true));
+        assert!(bus.send(0x81, true));
+        bus.stop();
+        bus.start();
+        assert!(bus.send(0x85, true));
+        assert_eq!(bus.receive(true, false), 0x81);
+        bus.stop();
+        assert_eq!(x24c01.prg_ram().len(), 128);
+    }
+
+    #[test]
+    fn ss88006_nibble_banks_and_irq_width() {
+        let prg = patterned_banks(32 * 0x2000, 0x2000);
+        let chr = patterned_banks(32 * 0x0400, 0x0400);
+        let mut mapper = Mapper18::new(make_cart(18, 0, prg, chr, false));
+        mapper.cpu_write(0x8002, 0x05);
+        mapper.cpu_write(0x8003, 0x01);
+        assert_eq!(mapper.cpu_read(0xA000), 22);
+        mapper.cpu_write(0xB002, 0x03);
+        mapper.cpu_write(0xB003, 0x01);
+        assert_eq!(mapper.ppu_read(0x0C00), 20);
+
+        mapper.cpu_write(0xE000, 0x02);
+        mapper.cpu_write(0xE001, 0x01);
+        mapper.cpu_write(0xF000, 0x00);
+        // 4-bit counting: only the low nibble, 2, counts down.
+        mapper.cpu_write(0xF001, 0x09);
+        mapper.tick_cpu_cycle();
+        assert!(!mapper.irq_pending());
+        mapper.tick_cpu_cycle();
+        assert!(mapper.irq_pending());
+        mapper.cpu_write(0xF001, 0x00);
+        assert!(!mapper.irq_pending());
+
+        mapper.cpu_write(0x6000, 0x42);
+        assert_eq!(mapper.cpu_read(0x6000), 0);
+        mapper.cpu_write(0x9002, 0x03);
+        mapper.cpu_write(0x6000, 0x42);
+        assert_eq!(mapper.cpu_read(0x6000), 0x42);
+    }
+
+    #[test]
+    fn fds_conversion_and_ball_games_banking() {
+        let prg = patterned_banks(16 * 0x2000, 0x2000);
+        let chr = patterned_banks(4 * 0x2000, 0x2000);
+        let mut nicol = Mapper42::new(make_cart(42, 0, prg, chr, false));
+        nicol.cpu_write(0xE000, 0x03);
+        nicol.cpu_write(0x8000, 0x02);
+        assert_eq!(nicol.cpu_read(0x6000), 4);
+        assert_eq!(nicol.cpu_read(0x8000), 13);
+        assert_eq!(nicol.ppu_read(0x0000), 3);
+        nicol.cpu_write(0xE002, 0x02);
+        for _ in 0..0x6000 - 1 {
+            nicol.tick_cpu_cycle();
+        }
+        assert!(!nicol.irq_pending());
+        nicol.tick_cpu_cycle();
+        assert!(nicol.irq_pending());
+        nicol.cpu_write(0xE002, 0x00);
+        assert!(!nicol.irq_pending());
+
+        let prg = patterned_banks(64 * 0x2000, 0x2000);
+        let mut ball = Mapper51::new(make_cart(51, 0, prg, vec![0; 0x2000], true));
+        ball.cpu_write(0x8000, 0x01);
+        // 32 KB mode: bank 1 at $8000, 8 KB bank $27 at $6000.
+        assert_eq!(ball.cpu_read(0x8000), 5);
+        assert_eq!(ball.cpu_read(0xE000), 8);
+        assert_eq!(ball.cpu_read(0x6000), 0x28);
+        ball.cpu_write(0x6000, 0x10);
+        ball.cpu_write(0xC000, 0x12);
+        // 16 KB mode: 16 KB banks 5 and 7 from the same block.
+        assert_eq!(ball.cpu_read(0x8000), 11);
+        assert_eq!(ball.cpu_read(0xC000), 15);
+        assert_eq!(ball.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn vrc7_banking_irq_and_audio_ports() {
+        let prg = patterned_banks(16 * 0x2000, 0x2000);
+        let chr = patterned_banks(16 * 0x0400, 0x0400);
+        let mut mapper = Mapper85::new(make_cart(85, 0, prg, chr, false));
+        mapper.cpu_write(0x8010, 0x03);
+        mapper.cpu_write(0x9000, 0x05);
+        assert_eq!(mapper.cpu_read(0xA000), 4);
+        assert_eq!(mapper.cpu_read(0xC000), 6);
+        assert_eq!(mapper.cpu_read(0xE000), 16);
+        // VRC7b boards select odd registers with A3.
+        mapper.cpu_write(0xB008, 0x09);
+        assert_eq!(mapper.ppu_read(0x0C00), 10);
+
+        // The audio ports leave the PRG bank at $9000 alone.
+        mapper.cpu_write(0x9010, 0x30);
+        mapper.cpu_write(0x9030, 0x30);
+        mapper.cpu_write(0x9010, 0x10);
+        mapper.cpu_write(0x9030, 0xF4);
+        mapper.cpu_write(0x9010, 0x20);
+        mapper.cpu_write(0x9030, 0x18);
+        assert_eq!(mapper.cpu_read(0xC000), 6);
+        for _ in 0..36 * 200 {
+            mapper.tick_cpu_cycle();
+        }
+        assert_ne!(mapper.audio_output(), 0.0);
+
+        mapper.cpu_write(0xE010, 0xFE);
+        mapper.cpu_write(0xF000, 0x06);
+        mapper.tick_cpu_cycle();
+        assert!(!mapper.irq_pending());
+        mapper.tick_cpu_cycle();
+        assert!(mapper.irq_pending());
+        mapper.cpu_write(0xF010, 0x00);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn action52_and_mapper235_banking_with_missing_chips() {
+        let prg = patterned_banks(3 * 0x80000, 0x4000);
+        let chr = patterned_banks(64 * 0x2000, 0x2000);
+        let mut action52 = Mapper228::new(make_cart(228, 0, prg, chr, false));
+        // Chip 3, 16 KB bank 5, 16 KB mode, CHR bank $0B.
+        action52.cpu_write(0x9962, 0x03);
+        assert_eq!(action52.cpu_read(0x8000), 64 + 6);
+        assert_eq!(action52.cpu_read(0xC000), 64 + 6);
+        assert_eq!(action52.ppu_read(0x0000), 12);
+        assert_eq!(action52.mirroring(), Mirroring::Vertical);
+        action52.cpu_write(0xA000 | 0x0080, 0x00);
+        assert_eq!(action52.cpu_read(0x8000), 3);
+        assert_eq!(action52.cpu_read(0xC000), 4);
+        assert_eq!(action52.mirroring(), Mirroring::Horizontal);
+        // Chip 2 isn't fitted.
+        action52.cpu_write(0x9000, 0x00);
+        assert!(!action52.prg_drives_bus(0x8000));
+        action52.cpu_write(0x5FF1, 0xA7);
+        assert_eq!(action52.cpu_read_expansion(0x4021), Some(0x07));
+
+        let prg = patterned_banks(32 * 0x8000, 0x4000);
+        let mut multicart = Mapper235::new(make_cart(235, 0, prg, vec![0; 0x2000], true));
+        multicart.cpu_write(0x8003, 0x00);
+        assert_eq!(multicart.cpu_read(0x8000), 7);
+        assert_eq!(multicart.cpu_read(0xC000), 8);
+        multicart.cpu_write(0xB803, 0x00);
+        assert_eq!(multicart.cpu_read(0x8000), 8);
+        assert_eq!(multicart.cpu_read(0xC000), 8);
+        assert_eq!(multicart.mirroring(), Mirroring::Horizontal);
+        multicart.cpu_write(0x8403, 0x00);
+        assert_eq!(multicart.mirroring(), Mirroring::OneScreenLower);
+        // A 1 MB cart has nothing behind the second chip.
+        multicart.cpu_write(0x8100, 0x00);
+        assert!(!multicart.prg_drives_bus(0x8000));
+    }
+
+    #[test]
+    fn supported_boards_match_the_mappers_create_mapper_builds() {
+        for mapper_id in 0..=DOCUMENTED_MAPPER_MAX_ID {
+            let prg = patterned_banks(0x10000, 0x2000);
+            let cart = make_cart(mapper_id, 0, prg, vec![0; 0x2000], true);
+            let generic = create_mapper(cart)
+                .unwrap()
+                .debug_state()
+                .starts_with("generic mapper=");
+            let listed = SUPPORTED_BOARDS
+                .iter()
+                .any(|board| board.mapper_id == mapper_id);
+            assert_eq!(listed, !generic, "mapper {mapper_id}");
+        }
+    }
+
+    #[test]
+    fn supported_boards_restore_their_saved_state() {
+        for board in SUPPORTED_BOARDS {
+            let cart = || {
+                let prg = patterned_banks(0x20000, 0x2000);
+                make_cart(board.mapper_id, 0, prg, vec![0; 0x2000], true)
+            };
+            let mut original = create_mapper(cart()).unwrap();
+            for (step, addr) in (0x4020..=0xFFFFu16).step_by(0x0111).enumerate() {
+                original.cpu_write(addr, (step as u8).wrapping_mul(37));
+                original.tick_cpu_cycle();
+            }
+            for addr in (0..0x2000u16).step_by(0x0101) {
+                original.ppu_write(addr, addr as u8 | 1);
+            }
+
+            let mut saved = Vec::new();
+            original.save_state(&mut saved).unwrap();
+            let mut restored = create_mapper(cart()).unwrap();
+            restored.load_state(&mut saved.as_slice()).unwrap();
+
+            let mut resaved = Vec::new();
+            restored.save_state(&mut resaved).unwrap();
+            let id = board.mapper_id;
+            assert_eq!(resaved, saved, "mapper {id}");
+            assert_eq!(restored.mirroring(), original.mirroring(), "mapper {id}");
+            for addr in (0x6000..=0xFFFFu16).step_by(0x0400) {
+                let (a, b) = (original.cpu_read(addr), restored.cpu_read(addr));
+                assert_eq!(b, a, "mapper {id} ${addr:04X}");
+            }
+            for addr in (0..0x2000u16).step_by(0x0080) {
+                let (a, b) = (original.ppu_read(addr), restored.ppu_read(addr));
+                assert_eq!(b, a, "mapper {id} PPU ${addr:04X}");
+            }
+        }
+    }
+
+    #[test]
+    fn mapper4_irq_a12_edge_filtering() {
+        let prg = patterned_banks(4 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper4::new(make_cart(4, 0, prg, chr, false));
+
+        mapper.cpu_write(0xC000, 0x01);
+        mapper.cpu_write(0xC001, 0x00);
+        mapper.cpu_write(0xE001, 0x00);
+
+        for _ in 0..8 {
+            mapper.notify_ppu_read_addr(0x0000);
+        }
+        mapper.notify_ppu_read_addr(0x1000);
+        assert!(!mapper.irq_pending());
+
+        for _ in 0..8 {
+            mapper.notify_ppu_read_addr(0x0000);
+        }
+        mapper.notify_ppu_read_addr(0x1000);
+        assert!(mapper.irq_pending());
+    }
+
+    #[test]
+    fn mapper4_ppu_fetches_clock_once_per_rendered_line_for_both_table_polarities() {
+        // 240 visible lines plus the pre-render line.
+        // BG=$0000, sprites=$1000.
+        assert_eq!(run_mmc3_frame_a12_clocks(0x08), 241);
+        // BG=$1000, sprites=$0000.
+        assert_eq!(run_mmc3_frame_a12_clocks(0x10), 241);
+    }
+
+    #[test]
+    fn mapper4_a12_bench_bg_low_sprites_high_clocks_once_per_scanline() {
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(&mut mapper, 0x08, &[0x10, 0x11, 0x12], 10);
+        assert_eq!(mapper.debug_irq_clocks, 10);
+    }
+
+    #[test]
+    fn mapper4_a12_bench_bg_high_sprites_low_clocks_once_per_scanline() {
+        // The rising edge comes from the BG prefetch at cycle 321, after the
+        // sprite fetches have held A12 low for the whole 257-320 window.
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(&mut mapper, 0x10, &[0x10, 0x11, 0x12], 10);
+        assert_eq!(mapper.debug_irq_clocks, 10);
+    }
+
+    #[test]
+    fn mapper4_a12_bench_same_table_never_clocks() {
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(&mut mapper, 0x00, &[0x10, 0x11], 10);
+        assert_eq!(mapper.debug_irq_clocks, 0);
+
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(&mut mapper, 0x18, &[0x10, 0x11], 10);
+        assert_eq!(mapper.debug_irq_clocks, 0);
+    }
+
+    #[test]
+    fn mapper4_a12_bench_8x16_sprites_select_table_from_tile_bit0() {
+        // All eight slots use even tiles from $0000: nothing raises A12.
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(&mut mapper, 0x20, &[0x02; 8], 10);
+        assert_eq!(mapper.debug_irq_clocks, 0);
+
+        // Empty slots fetch tile $FF, which lives in the $1000 table.
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(&mut mapper, 0x20, &[0x02, 0x04], 10);
+        assert_eq!(mapper.debug_irq_clocks, 10);
+
+        // Mixed tables within one scanline are filtered down to a single clock.
+        let mut mapper = mmc3_with_irq_latch(0xFF);
+        run_mmc3_scanlines(
+            &mut mapper,
+            0x20,
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+            10,
+        );
+        assert_eq!(mapper.debug_irq_clocks, 10);
+    }
+
+    #[test]
+    fn mapper4_a12_bench_irq_fires_after_latch_plus_one_scanlines() {
+        for ctrl in [0x08, 0x10, 0x20] {
+            let mut mapper = mmc3_with_irq_latch(3);
+            for scanline in 1..=3 {
+                run_mmc3_scanlines(&mut mapper, ctrl, &[0x02], 1);
+                assert!(
+                    !mapper.irq_pending(),
+                    "ctrl={ctrl:02X} fired early on scanline {scanline}"
+                );
+            }
+            run_mmc3_scanlines(&mut mapper, ctrl, &[0x02], 1);
+            assert!(mapper.irq_pending(), "ctrl={ctrl:02X} did not fire");
+        }
+    }
+
+    #[test]
+    fn mapper5_prg_banking_and_ram_protection() {
+        let prg = patterned_banks(16 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper5::new(make_cart(5, 0, prg, chr, false));
+
+        mapper.cpu_write(0x5100, 0x03);
+        mapper.cpu_write(0x5114, 0x82);
+        mapper.cpu_write(0x5115, 0x83);
+        mapper.cpu_write(0x5116, 0x84);
+        mapper.cpu_write(0x5117, 0x8F);
+
+        assert_eq!(mapper.cpu_read(0x8000), 3);
+        assert_eq!(mapper.cpu_read(0xA000), 4);
+        assert_eq!(mapper.cpu_read(0xC000), 5);
+        assert_eq!(mapper.cpu_read(0xE000), 16);
+
+        mapper.cpu_write(0x5102, 0x02);
+        mapper.cpu_write(0x5103, 0x01);
+        mapper.cpu_write(0x5113, 0x00);
+        mapper.cpu_write(0x6000, 0xAA);
+        assert_eq!(mapper.cpu_read(0x6000), 0xAA);
+
+        mapper.cpu_write(0x5102, 0x00);
+        mapper.cpu_write(0x6000, 0x55);
+        assert_eq!(mapper.cpu_read(0x6000), 0xAA);
+    }
+
+    #[test]
+    fn mapper5_nametable_modes_and_scanline_irq() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper5::new(make_cart(5, 0, prg, chr, false));
+        let mut vram = [0u8; 4096];
+
+        mapper.cpu_write(0x5105, 0b11_10_01_00);
+        mapper.cpu_write(0x5104, 0x00);
+        mapper.cpu_write(0x5106, 0x2A);
+        mapper.cpu_write(0x5107, 0x03);
+        mapper.cpu_write(0x5C12, 0x77);
+
+        vram[0x0005] = 0x11;
+        vram[0x0406] = 0x22;
+        assert_eq!(mapper.ppu_nametable_read(0x2005, &vram), Some(0x11));
+        assert_eq!(mapper.ppu_nametable_read(0x2406, &vram), Some(0x22));
+        assert_eq!(mapper.ppu_nametable_read(0x2812, &vram), Some(0x77));
+        assert_eq!(mapper.ppu_nametable_read(0x2C00, &vram), Some(0x2A));
+        assert_eq!(mapper.ppu_nametable_read(0x2FC0, &vram), Some(0xFF));
+        assert_eq!(
+            std::array::from_fn::<_, 4, _>(|table| mapper.nametable_source(table)),
+            [
+                NametableSource::Vram(0),
+                NametableSource::Vram(1),
+                NametableSource::ExRam,
+                NametableSource::Fill,
+            ]
+        );
+
+        mapper.cpu_write(0x5104, 0x02);
+        assert_eq!(mapper.ppu_nametable_read(0x2812, &vram), Some(0x00));
+
+        mapper.cpu_write(0x5203, 0x01);
+        mapper.cpu_write(0x5204, 0x80);
+        for _ in 0..3 {
+            mapper.notify_ppu_read_addr(0x2000);
+        }
+        mapper.notify_ppu_read_addr(0x23C0);
+        assert!(!mapper.irq_pending());
+
+        for _ in 0..3 {
+            mapper.notify_ppu_read_addr(0x2000);
+        }
+        mapper.notify_ppu_read_addr(0x23C0);
+        assert!(mapper.irq_pending());
+
+        let status = mapper.cpu_read(0x5204);
+        assert_eq!(status & 0x80, 0x80);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn mapper5_vertical_split_redirects_fetches() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper5::new(make_cart(5, 0, prg, chr, false));
+        let mut vram = [0u8; 4096];
+        vram[0x0002] = 0x11;
+
+        // Tiles left of column 4, scrolled down one row, from CHR 4 KB bank 1.
+        mapper.cpu_write(0x5200, 0x84);
+        mapper.cpu_write(0x5201, 0x08);
+        mapper.cpu_write(0x5202, 0x01);
+        mapper.cpu_write(0x5C22, 0x5A);
+        mapper.cpu_write(0x5FC0, 0b00_00_11_00);
+
+        mapper.set_fetch_phase(FetchPhase::Background { tile: 2, line: 0 });
+        assert_eq!(mapper.ppu_nametable_read(0x2002, &vram), Some(0x5A));
+        assert_eq!(mapper.ppu_nametable_read(0x23C0, &vram), Some(0xFF));
+        assert_eq!(mapper.ppu_read(0x0010), 5);
+
+        // Outside the split, and during sprite fetches, nothing changes.
+        mapper.set_fetch_phase(FetchPhase::Background { tile: 4, line: 0 });
+        assert_eq!(mapper.ppu_nametable_read(0x2002, &vram), Some(0x11));
+        assert_eq!(mapper.ppu_read(0x0010), 1);
+        mapper.set_fetch_phase(FetchPhase::Sprites);
+        assert_eq!(mapper.ppu_read(0x0010), 1);
+
+        // Bit 6 moves the split to the right of the threshold.
+        mapper.cpu_write(0x5200, 0xC4);
+        mapper.set_fetch_phase(FetchPhase::Background { tile: 2, line: 0 });
+        assert_eq!(mapper.ppu_nametable_read(0x2002, &vram), Some(0x11));
+        mapper.set_fetch_phase(FetchPhase::Background { tile: 6, line: 0 });
+        assert_eq!(mapper.ppu_read(0x0010), 5);
+    }
+
+    #[test]
+    fn mapper5_audio_pulses_and_pcm() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper5::new(make_cart(5, 0, prg, chr, false));
+        assert_eq!(mapper.cpu_read_expansion(0x5015), Some(0));
+
+        mapper.cpu_write(0x5015, 0x02);
+        mapper.cpu_write(0x5004, 0xBF);
+        mapper.cpu_write(0x5006, 0x20);
+        mapper.cpu_write(0x5007, 0x08);
+        assert_eq!(mapper.cpu_read(0x5015), 0x02);
+        let mut sounded = false;
+        for _ in 0..0x100 {
+            mapper.tick_cpu_cycle();
+            sounded |= mapper.audio_output() > 0.0;
+        }
+        assert!(sounded);
+        mapper.cpu_write(0x5015, 0x00);
+        assert_eq!(mapper.cpu_read(0x5015), 0x00);
+
+        mapper.cpu_write(0x5011, 0x80);
+        let level = mapper.audio_output();
+        assert!(level > 0.0);
+        mapper.cpu_write(0x5011, 0x00);
+        assert_eq!(mapper.audio_output(), level);
+
+        // Read mode latches PRG reads and raises the IRQ on a zero.
+        mapper.cpu_write(0x5114, 0x80);
+        mapper.cpu_write(0x5010, 0x81);
+        mapper.cpu_read(0x8000);
+        assert!(mapper.audio_output() < level);
+        assert!(!mapper.irq_pending());
+        mapper.prg_rom_mut()[5] = 0;
+        mapper.cpu_read(0x8005);
+        assert!(mapper.irq_pending());
+        assert_eq!(mapper.cpu_read(0x5010), 0x80);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn mapper7_switches_prg_and_onescreen_mirroring() {
+        let prg = patterned_banks(2 * 0x8000, 0x8000);
+        let chr = patterned_banks(0x2000, 0x2000);
+        let mut mapper = Mapper7::new(make_cart(7, 0, prg, chr, false));
+
+        mapper.cpu_write(0x8000, 0x11);
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+        assert_eq!(mapper.mirroring(), Mirroring::OneScreenUpper);
+    }
+
+    #[test]
+    fn mapper9_latches_control_chr_windows() {
+        let prg = patterned_banks(4 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x1000, 0x1000);
+        let mut mapper = Mapper9::new(make_cart(9, 0, prg, chr, false));
+
+        mapper.cpu_write(0xB000, 0x01);
+        mapper.cpu_write(0xC000, 0x02);
+        mapper.cpu_write(0xD000, 0x03);
+        mapper.cpu_write(0xE000, 0x04);
+
+        assert_eq!(mapper.ppu_read(0x0000), 3);
+        mapper.notify_ppu_read_addr(0x0FD8);
+        assert_eq!(mapper.ppu_read(0x0000), 2);
+
+        assert_eq!(mapper.ppu_read(0x1000), 5);
+        mapper.notify_ppu_read_addr(0x1FD8);
+        assert_eq!(mapper.ppu_read(0x1000), 4);
+    }
+
+    #[test]
+    fn mapper10_switches_prg_and_chr_latches() {
+        let prg = patterned_banks(3 * 0x4000, 0x4000);
+        let chr = patterned_banks(8 * 0x1000, 0x1000);
+        let mut mapper = Mapper10::new(make_cart(10, 0, prg, chr, false));
+
+        mapper.cpu_write(0xA000, 0x01);
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+        assert_eq!(mapper.cpu_read(0xC000), 3);
+
+        mapper.cpu_write(0xB000, 0x00);
+        mapper.cpu_write(0xC000, 0x01);
+        assert_eq!(mapper.ppu_read(0x0000), 2);
+        mapper.notify_ppu_read_addr(0x0FD8);
+        assert_eq!(mapper.ppu_read(0x0000), 1);
+    }
+
+    #[test]
+    fn mapper19_nametable_chr_mapping_and_irq_counter() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(16 * 0x0400, 0x0400);
+        let mut mapper = Mapper19::new(make_cart(19, 0, prg, chr, false));
+        let mut vram = [0u8; 4096];
+
+        mapper.cpu_write(0xC000, 0xE0);
+        vram[0x0010] = 0x33;
+        assert_eq!(mapper.ppu_nametable_read(0x2010, &vram), Some(0x33));
+        assert!(mapper.ppu_nametable_write(0x2020, 0x44, &mut vram));
+        assert_eq!(vram[0x0020], 0x44);
+
+        mapper.cpu_write(0xC000, 0x02);
+        assert_eq!(mapper.ppu_nametable_read(0x2010, &vram), Some(3));
+
+        mapper.cpu_write(0x5000, 0xFE);
+        mapper.cpu_write(0x5800, 0xFF);
+        mapper.tick_cpu_cycle();
+        assert!(mapper.irq_pending());
+        mapper.cpu_write(0x5000, 0x00);
+        assert!(!mapper.irq_pending());
+
+        mapper.cpu_write(0xF800, 0x40);
+        mapper.cpu_write(0x6000, 0xA5);
+        assert_eq!(mapper.cpu_read(0x6000), 0xA5);
+        mapper.cpu_write(0xF800, 0x4F);
+        mapper.cpu_write(0x6000, 0x5A);
+        assert_eq!(mapper.cpu_read(0x6000), 0xA5);
+    }
+
+    #[test]
+    fn mapper66_switches_prg_and_chr() {
+        let prg = patterned_banks(2 * 0x8000, 0x8000);
+        let chr = patterned_banks(2 * 0x2000, 0x2000);
+        let mut mapper = Mapper66::new(make_cart(66, 0, prg, chr, false));
+
+        mapper.cpu_write(0x8000, 0x11);
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+        assert_eq!(mapper.ppu_read(0x0000), 2);
+    }
+
+    #[test]
+    fn mapper69_prg_registers_and_irq_counter() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(8 * 0x0400, 0x0400);
+        let mut mapper = Mapper69::new(make_cart(69, 0, prg, chr, false));
+
+        mapper.cpu_write(0x8000, 0x09);
+        mapper.cpu_write(0xA000, 0x03);
+        mapper.cpu_write(0x8000, 0x0A);
+        mapper.cpu_write(0xA000, 0x04);
+        mapper.cpu_write(0x8000, 0x0B);
+        mapper.cpu_write(0xA000, 0x05);
+        assert_eq!(mapper.cpu_read(0x8000), 4);
+        assert_eq!(mapper.cpu_read(0xA000), 5);
+        assert_eq!(mapper.cpu_read(0xC000), 6);
+
+        mapper.cpu_write(0x8000, 0x0E);
+        mapper.cpu_write(0xA000, 0x00);
+        mapper.cpu_write(0x8000, 0x0F);
+        mapper.cpu_write(0xA000, 0x00);
+        mapper.cpu_write(0x8000, 0x0D);
+        mapper.cpu_write(0xA000, 0x81);
+        mapper.tick_cpu_cycle();
+        assert!(mapper.irq_pending());
+        mapper.cpu_write(0x8000, 0x0D);
+        mapper.cpu_write(0xA000, 0x81);
+        assert!(!mapper.irq_pending());
+        mapper.cpu_write(0x8000, 0x0F);
+        mapper.cpu_write(0xA000, 0x00);
+        mapper.cpu_write(0x8000, 0x0E);
+        mapper.cpu_write(0xA000, 0x01);
+        mapper.tick_cpu_cycle();
+        assert!(!mapper.irq_pending());
+        mapper.tick_cpu_cycle();
+        assert!(mapper.irq_pending());
+        mapper.clear_irq();
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn mapper71_submapper1_masks_bank_to_three_bits() {
+        let prg = patterned_banks(16 * 0x4000, 0x4000);
+        let chr = patterned_banks(0x2000, 0x2000);
+        let mut mapper = Mapper71::new(make_cart(71, 1, prg, chr, false));
+
+        mapper.cpu_write(0xC000, 0x0F);
+        assert_eq!(mapper.cpu_read(0x8000), 8);
+        mapper.cpu_write(0x9000, 0x10);
+        assert_eq!(mapper.mirroring(), Mirroring::OneScreenUpper);
+    }
+
+    #[test]
+    fn mapper71_submapper0_ignores_mirroring_writes_and_allows_chr_writes() {
+        let prg = patterned_banks(4 * 0x4000, 0x4000);
+        let chr = vec![0; 0x2000];
+        let mut mapper = Mapper71::new(make_cart(71, 0, prg, chr, false));
+
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        mapper.cpu_write(0x9000, 0x10);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+        mapper.ppu_write(0x0010, 0xA5);
+        assert_eq!(mapper.ppu_read(0x0010), 0xA5);
+    }
+
+    #[test]
+    fn mapper1_shift_register_programs_prg_bank() {
+        let prg = patterned_banks(4 * 0x4000, 0x4000);
+        let chr = patterned_banks(0x2000, 0x1000);
+        let mut mapper = Mapper1::new(make_cart(1, 0, prg, chr, false));
+
+        for bit in [1u8, 0, 0, 0, 0] {
+            mapper.cpu_write(0xE000, bit);
+        }
+
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+        assert_eq!(mapper.cpu_read(0xC000), 4);
+    }
+
+    #[test]
+    fn mapper1_ignores_consecutive_writes_and_honors_prg_ram_disable() {
+        let prg = patterned_banks(4 * 0x4000, 0x4000);
+        let chr = patterned_banks(0x2000, 0x1000);
+        let mut mapper = Mapper1::new(make_cart(1, 0, prg, chr, false));
+        let serial_write = |mapper: &mut Mapper1, addr: u16, value: u8| {
+            mapper.tick_cpu_cycle();
+            mapper.tick_cpu_cycle();
+            for bit in 0..5 {
+                mapper.cpu_write(addr, value >> bit & 1);
+                mapper.tick_cpu_cycle();
+                mapper.tick_cpu_cycle();
+            }
+        };
+
+        // INC $E000 on a $FF operand: the reset lands, the $00 after it
+        // doesn't, so the next five writes are a whole value.
+        mapper.cpu_write(0xE000, 0xFF);
+        mapper.tick_cpu_cycle();
+        mapper.cpu_write(0xE000, 0x00);
+        serial_write(&mut mapper, 0xE000, 0x01);
+        assert_eq!(mapper.cpu_read(0x8000), 2);
+
+        mapper.cpu_write(0x6000, 0x5A);
+        assert_eq!(mapper.cpu_read(0x6000), 0x5A);
+        serial_write(&mut mapper, 0xE000, 0x11);
+        assert!(!mapper.prg_drives_bus(0x6000));
+        assert!(mapper.prg_drives_bus(0x8000));
+        mapper.cpu_write(0x6000, 0xA5);
+        serial_write(&mut mapper, 0xE000, 0x01);
+        assert_eq!(mapper.cpu_read(0x6000), 0x5A);
+    }
+
+    #[test]
+    fn mapper1_512k_prg_uses_chr_bank_bit_4() {
+        let prg = patterned_banks(32 * 0x4000, 0x4000);
+        let mut cart = make_cart(1, 0, prg, vec![0; 0x2000], true);
+        cart.prg_ram_size = 0x8000;
+        let mut mapper = Mapper1::new(cart);
+        let serial_write = |mapper: &mut Mapper1, addr: u16, value: u8| {
+            for bit in 0..5 {
+                mapper.cpu_write(addr, value >> bit & 1);
+            }
+        };
+
+        serial_write(&mut mapper, 0xE000, 0x02);
+        assert_eq!(mapper.cpu_read(0x8000), 3);
+        assert_eq!(mapper.cpu_read(0xC000), 16);
+        serial_write(&mut mapper, 0xA000, 0x10);
+        assert_eq!(mapper.cpu_read(0x8000), 19);
+        assert_eq!(mapper.cpu_read(0xC000), 32);
+
+        // SXROM's RAM banks come from bits 2-3 of the same register.
+        mapper.cpu_write(0x6000, 0x11);
+        serial_write(&mut mapper, 0xA000, 0x1C);
+        assert_eq!(mapper.cpu_read(0x6000), 0x00);
+        mapper.cpu_write(0x6000, 0x33);
+        serial_write(&mut mapper, 0xA000, 0x10);
+        assert_eq!(mapper.cpu_read(0x6000), 0x11);
+        assert_eq!(mapper.prg_ram()[0x6000], 0x33);
+    }
+
+    #[test]
+    fn mapper4_prg_and_chr_modes_follow_bank_select() {
+        let prg = patterned_banks(8 * 0x2000, 0x2000);
+        let chr = patterned_banks(16 * 0x0400, 0x0400);
+        let mut mapper = Mapper4::new(make_cart(4, 0, prg, chr, false));
+        for (register, bank) in [
+            (0, 2),
+            (1, 4),
+            (2, 9),
+            (3, 10),
+            (4, 11),
+            (5, 12),
+            (6, 3),
+            (7, 5),
+        ] {
+            mapper.cpu_write(0x8000, register);
+            mapper.cpu_write(0x8001, bank);
+        }
+
+        let prg_windows = |mapper: &mut Mapper4| {
+            [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| mapper.cpu_read(addr))
+        };
+        let chr_windows = |mapper: &mut Mapper4| {
+            [0, 1, 2, 3, 4, 5, 6, 7].map(|slot| mapper.ppu_read(slot * 0x400))
+        };
+        assert_eq!(prg_windows(&mut mapper), [4, 6, 7, 8]);
+        assert_eq!(chr_windows(&mut mapper), [3, 4, 5, 6, 10, 11, 12, 13]);
+
+        // PRG and CHR inversion; bank numbers past the end wrap.
+        mapper.cpu_write(0x8000, 0xC6);
+        mapper.cpu_write(0x8001, 11);
+        assert_eq!(prg_windows(&mut mapper), [7, 6, 4, 8]);
+        assert_eq!(chr_windows(&mut mapper), [10, 11, 12, 13, 3, 4, 5, 6]);
+    }
+}
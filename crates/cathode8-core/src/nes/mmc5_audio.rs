@@ -0,0 +1,229 @@
+//! MMC5 audio: two pulse channels like the 2A03's, without the sweep
+//! unit, and an 8-bit PCM channel, at $5000-$5015. Castlevania III's
+//! Japanese release and Just Breed play their music on them.
+//!
+//! The pulses' timers run at the APU's rate, every other CPU cycle, but the
+//! MMC5 has no frame counter of its own: envelopes and length counters are
+//! clocked together at a fixed 240 Hz. The PCM channel takes raw writes to
+//! $5011, or in read mode latches every byte the CPU reads from
+//! $8000-$BFFF, raising its IRQ when that byte is zero.
+
+use super::apu::{DUTY_TABLE, LENGTH_TABLE};
+use super::state_field::{StateField, state_fields};
+
+/// CPU cycles between envelope and length counter clocks, 240 Hz.
+const FRAME_CYCLES: u16 = 7457;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Pulse {
+    enabled: bool,
+    duty: u8,
+    duty_step: u8,
+    timer_period: u16,
+    timer_counter: u16,
+    length_counter: u8,
+    length_halt: bool,
+    constant_volume: bool,
+    /// Constant volume, or the envelope's period.
+    volume: u8,
+    envelope_start: bool,
+    envelope_divider: u8,
+    envelope_decay: u8,
+}
+
+impl StateField for Pulse {
+    state_fields!(
+        enabled,
+        duty,
+        duty_step,
+        timer_period,
+        timer_counter,
+        length_counter,
+        length_halt,
+        constant_volume,
+        volume,
+        envelope_start,
+        envelope_divider,
+        envelope_decay,
+    );
+}
+
+impl Pulse {
+    fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.duty = value >> 6;
+                self.length_halt = value & 0x20 != 0;
+                self.constant_volume = value & 0x10 != 0;
+                self.volume = value & 0x0F;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | u16::from(value),
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | (u16::from(value & 0x07) << 8);
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[usize::from(value >> 3)];
+                }
+                self.duty_step = 0;
+                self.envelope_start = true;
+            }
+            // Register 1 is the sweep, which the MMC5 doesn't have.
+            _ => {}
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer_counter == 0 {
+            self.timer_counter = self.timer_period;
+            self.duty_step = (self.duty_step + 1) & 0x07;
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    fn clock_frame(&mut self) {
+        if self.envelope_start {
+            self.envelope_start = false;
+            self.envelope_decay = 15;
+            self.envelope_divider = self.volume;
+        } else if self.envelope_divider == 0 {
+            self.envelope_divider = self.volume;
+            if self.envelope_decay > 0 {
+                self.envelope_decay -= 1;
+            } else if self.length_halt {
+                self.envelope_decay = 15;
+            }
+        } else {
+            self.envelope_divider -= 1;
+        }
+        if !self.length_halt && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length_counter == 0
+            || DUTY_TABLE[usize::from(self.duty)][usize::from(self.duty_step)] == 0
+        {
+            return 0;
+        }
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.envelope_decay
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mmc5Audio {
+    pulses: [Pulse; 2],
+    pcm: u8,
+    pcm_read_mode: bool,
+    pcm_irq_enabled: bool,
+    pcm_irq_pending: bool,
+    frame_counter: u16,
+    odd_cycle: bool,
+}
+
+impl StateField for Mmc5Audio {
+    state_fields!(
+        pulses,
+        pcm,
+        pcm_read_mode,
+        pcm_irq_enabled,
+        pcm_irq_pending,
+        frame_counter,
+        odd_cycle,
+    );
+}
+
+impl Mmc5Audio {
+    /// Writes a register at $5000-$5015.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5007 => self.pulses[usize::from(addr >> 2 & 1)].write(addr & 0x03, value),
+            0x5010 => {
+                self.pcm_read_mode = value & 0x01 != 0;
+                self.pcm_irq_enabled = value & 0x80 != 0;
+            }
+            // Writing zero does nothing; a zero only comes in by read.
+            0x5011 if !self.pcm_read_mode && value != 0 => self.pcm = value,
+            0x5015 => {
+                self.pulses[0].set_enabled(value & 0x01 != 0);
+                self.pulses[1].set_enabled(value & 0x02 != 0);
+            }
+            _ => {}
+        }
+    }
+
+    /// $5010: bit 7 is the PCM IRQ, which the read acknowledges.
+    pub fn read_pcm_status(&mut self) -> u8 {
+        let status = u8::from(self.irq_pending()) << 7;
+        self.pcm_irq_pending = false;
+        status
+    }
+
+    /// $5015: which pulses have length left.
+    pub fn read_status(&self) -> u8 {
+        u8::from(self.pulses[0].length_counter > 0)
+            | u8::from(self.pulses[1].length_counter > 0) << 1
+    }
+
+    /// Sees a CPU read of `value` at $8000-$BFFF, which read mode latches.
+    pub fn observe_prg_read(&mut self, value: u8) {
+        if !self.pcm_read_mode {
+            return;
+        }
+        if value == 0 {
+            self.pcm_irq_pending = true;
+        } else {
+            self.pcm = value;
+        }
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.pcm_irq_pending && self.pcm_irq_enabled
+    }
+
+    pub fn tick(&mut self) {
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            for pulse in &mut self.pulses {
+                pulse.clock_timer();
+            }
+        }
+        self.frame_counter += 1;
+        if self.frame_counter >= FRAME_CYCLES {
+            self.frame_counter = 0;
+            for pulse in &mut self.pulses {
+                pulse.clock_frame();
+            }
+        }
+    }
+
+    /// Current output, in the APU mix's units. The pulses go through the
+    /// 2A03's pulse mixing curve and the PCM through its DMC one, the
+    /// 8-bit level landing where the DMC's 7-bit one would.
+    pub fn output(&self) -> f32 {
+        let pulse_sum = f32::from(self.pulses[0].output() + self.pulses[1].output());
+        let pulse_out = if pulse_sum > 0.0 {
+            95.88 / ((8128.0 / pulse_sum) + 100.0)
+        } else {
+            0.0
+        };
+        let pcm = f32::from(self.pcm) / 2.0;
+        let pcm_out = if pcm > 0.0 {
+            159.79 / ((22638.0 / pcm) + 100.0)
+        } else {
+            0.0
+        };
+        pulse_out + pcm_out
+    }
+}
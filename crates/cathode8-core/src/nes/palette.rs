@@ -0,0 +1,235 @@
+//! RGB palette generated from a model of the 2C02's composite video output.
+//!
+//! Each NES color is a square wave between two voltage levels whose phase
+//! selects the hue. The generator samples that wave at the 12 phases of the
+//! color subcarrier, applies the emphasis bits (which attenuate a third of
+//! the phases each), and decodes the result to YIQ and then RGB like an NTSC
+//! television would.
+
+/// 64 colors times the 8 combinations of the $2001 emphasis bits, indexed the
+/// same way as [`super::Nes::frame_buffer_indexed`].
+pub const PALETTE_ENTRIES: usize = 512;
+
+// Composite voltage levels relative to sync, from measurements of a 2C02.
+const SIGNAL_BLACK: f32 = 0.518;
+const SIGNAL_WHITE: f32 = 1.962;
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+const SIGNAL_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const SIGNAL_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+
+/// Subcarrier phase (in twelfths of a cycle) of the colorburst relative to
+/// hue 0, so that hue 0 with no tweak decodes to the usual colors.
+const BURST_PHASE: f32 = 3.75;
+
+/// Picture controls applied while decoding, like a TV's picture menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtscPaletteParams {
+    /// Chroma gain; 0.0 gives greyscale.
+    pub saturation: f32,
+    /// Hue rotation in degrees.
+    pub hue_degrees: f32,
+    /// Offset added to luma, in the 0.0-1.0 black-to-white range.
+    pub brightness: f32,
+    /// Luma gain.
+    pub contrast: f32,
+    /// Display gamma. 2.2 passes the signal through unchanged, lower values
+    /// brighten midtones.
+    pub gamma: f32,
+}
+
+impl Default for NtscPaletteParams {
+    fn default() -> Self {
+        Self {
+            saturation: 1.0,
+            hue_degrees: 0.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 2.2,
+        }
+    }
+}
+
+/// Fixed 64-color table the ROM test suite's screenshot hashes were captured
+/// with. It predates the generator and ignores the emphasis bits.
+const REFERENCE_PALETTE: [[u8; 3]; 64] = [
+    [84, 84, 84],
+    [0, 30, 116],
+    [8, 16, 144],
+    [48, 0, 136],
+    [68, 0, 100],
+    [92, 0, 48],
+    [84, 4, 0],
+    [60, 24, 0],
+    [32, 42, 0],
+    [8, 58, 0],
+    [0, 64, 0],
+    [0, 60, 0],
+    [0, 50, 60],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+    [152, 150, 152],
+    [8, 76, 196],
+    [48, 50, 236],
+    [92, 30, 228],
+    [136, 20, 176],
+    [160, 20, 100],
+    [152, 34, 32],
+    [120, 60, 0],
+    [84, 90, 0],
+    [40, 114, 0],
+    [8, 124, 0],
+    [0, 118, 40],
+    [0, 102, 120],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+    [236, 238, 236],
+    [76, 154, 236],
+    [120, 124, 236],
+    [176, 98, 236],
+    [228, 84, 236],
+    [236, 88, 180],
+    [236, 106, 100],
+    [212, 136, 32],
+    [160, 170, 0],
+    [116, 196, 0],
+    [76, 208, 32],
+    [56, 204, 108],
+    [56, 180, 204],
+    [60, 60, 60],
+    [0, 0, 0],
+    [0, 0, 0],
+    [236, 238, 236],
+    [168, 204, 236],
+    [188, 188, 236],
+    [212, 178, 236],
+    [236, 174, 236],
+    [236, 174, 212],
+    [236, 180, 176],
+    [228, 196, 144],
+    [204, 210, 120],
+    [180, 222, 120],
+    [168, 226, 144],
+    [152, 226, 180],
+    [160, 214, 228],
+    [160, 162, 160],
+    [0, 0, 0],
+    [0, 0, 0],
+];
+
+/// [`REFERENCE_PALETTE`] expanded to [`PALETTE_ENTRIES`], for tools that
+/// compare frames against hashes taken with it.
+pub fn reference_palette() -> Vec<[u8; 3]> {
+    REFERENCE_PALETTE.repeat(PALETTE_ENTRIES / REFERENCE_PALETTE.len())
+}
+
+/// Builds all [`PALETTE_ENTRIES`] colors for the given picture controls.
+pub fn generate_ntsc_palette(params: &NtscPaletteParams) -> Vec<[u8; 3]> {
+    (0..PALETTE_ENTRIES)
+        .map(|entry| decode_entry(entry as u16, params))
+        .collect()
+}
+
+fn decode_entry(entry: u16, params: &NtscPaletteParams) -> [u8; 3] {
+    let hue_phase = params.hue_degrees / 30.0;
+    let (mut y, mut i, mut q) = (0.0f32, 0.0f32, 0.0f32);
+    for phase in 0..12 {
+        let level = (composite_level(entry, phase) - SIGNAL_BLACK) / (SIGNAL_WHITE - SIGNAL_BLACK);
+        let angle = std::f32::consts::PI * (phase as f32 + BURST_PHASE + hue_phase) / 6.0;
+        y += level;
+        i += level * angle.cos();
+        q += level * angle.sin();
+    }
+
+    // Synchronous demodulation recovers half the chroma amplitude, hence /6.
+    let y = y / 12.0 * params.contrast + params.brightness;
+    let i = i / 6.0 * params.saturation;
+    let q = q / 6.0 * params.saturation;
+
+    let rgb = [
+        y + 0.946_882 * i + 0.623_557 * q,
+        y - 0.274_788 * i - 0.635_691 * q,
+        y - 1.108_545 * i + 1.709_007 * q,
+    ];
+    let exponent = 2.2 / params.gamma.max(0.1);
+    rgb.map(|channel| (channel.clamp(0.0, 1.0).powf(exponent) * 255.0).round() as u8)
+}
+
+/// Voltage of color `entry` (emphasis in bits 6-8) at subcarrier `phase`.
+fn composite_level(entry: u16, phase: u16) -> f32 {
+    let color = entry & 0x0F;
+    let emphasis = (entry >> 6) & 0x07;
+    // Columns $E and $F are always the level-1 low voltage (black).
+    let level = if color > 13 {
+        1
+    } else {
+        ((entry >> 4) & 0x03) as usize
+    };
+
+    let mut low = SIGNAL_LOW[level];
+    let mut high = SIGNAL_HIGH[level];
+    if color == 0 {
+        low = high;
+    }
+    if color > 12 {
+        high = low;
+    }
+
+    let in_color_phase = |hue: u16| (hue + phase) % 12 < 6;
+    let mut signal = if in_color_phase(color) { high } else { low };
+
+    let attenuated = (emphasis & 0x01 != 0 && in_color_phase(0))
+        || (emphasis & 0x02 != 0 && in_color_phase(4))
+        || (emphasis & 0x04 != 0 && in_color_phase(8));
+    if attenuated {
+        signal *= EMPHASIS_ATTENUATION;
+    }
+    signal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_palette() -> Vec<[u8; 3]> {
+        generate_ntsc_palette(&NtscPaletteParams::default())
+    }
+
+    fn dominant_channel(rgb: [u8; 3]) -> usize {
+        (0..3).max_by_key(|&c| rgb[c]).unwrap()
+    }
+
+    #[test]
+    fn default_palette_has_expected_anchors() {
+        let palette = default_palette();
+        assert_eq!(palette.len(), PALETTE_ENTRIES);
+        assert_eq!(palette[0x0F], [0, 0, 0]);
+        assert_eq!(palette[0x30], [255, 255, 255]);
+        assert_eq!(dominant_channel(palette[0x16]), 0);
+        assert_eq!(dominant_channel(palette[0x1A]), 1);
+        assert_eq!(dominant_channel(palette[0x12]), 2);
+    }
+
+    #[test]
+    fn emphasis_bits_tint_toward_their_channel() {
+        let palette = default_palette();
+        for (bit, channel) in [(0usize, 0usize), (1, 1), (2, 2)] {
+            let tinted = palette[0x30 | (1 << (6 + bit))];
+            assert_eq!(dominant_channel(tinted), channel, "emphasis bit {bit}");
+        }
+        let all = palette[0x30 | 0x1C0];
+        assert!(all[0] < 255 && all[0] == all[1] && all[1] == all[2]);
+    }
+
+    #[test]
+    fn zero_saturation_is_greyscale() {
+        let params = NtscPaletteParams {
+            saturation: 0.0,
+            ..NtscPaletteParams::default()
+        };
+        for rgb in generate_ntsc_palette(&params).into_iter().take(64) {
+            assert!(rgb[0] == rgb[1] && rgb[1] == rgb[2], "{rgb:?}");
+        }
+    }
+}
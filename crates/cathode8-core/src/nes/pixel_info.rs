@@ -0,0 +1,230 @@
+//! Everything known about one pixel of the last rendered frame, for a
+//! hover-to-inspect tooltip: its palette entry, the background tile and where
+//! it lives in the nametables, and the sprite drawn there. Also the
+//! background tile grid of the frame, traced from the same per-pixel record.
+
+use super::Nes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteInfo {
+    pub index: u8,
+    pub x: u8,
+    pub y: u8,
+    pub tile: u8,
+    pub attributes: u8,
+    /// False when the sprite is opaque here but hidden behind the background.
+    pub visible: bool,
+}
+
+/// One straight run of a grid edge, in frame pixels. A vertical line runs
+/// down from (`at`, `start`), a horizontal one across from (`start`, `at`);
+/// `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridLine {
+    pub vertical: bool,
+    pub at: u8,
+    pub start: u8,
+    pub end: u16,
+}
+
+/// The top-left pixel of one grid cell and the nametable tile there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridCell {
+    pub x: u8,
+    pub y: u8,
+    pub nametable: u8,
+    pub tile_column: u8,
+    pub tile_row: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TileGrid {
+    pub lines: Vec<GridLine>,
+    pub cells: Vec<GridCell>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelInfo {
+    pub x: u8,
+    pub y: u8,
+    /// $3F00-$3F1F.
+    pub palette_addr: u16,
+    /// NES color ($00-$3F) currently stored at `palette_addr`.
+    pub color: u8,
+    pub bg_tile: u8,
+    /// $2000-$2FFF.
+    pub nametable_addr: u16,
+    /// Logical nametable 0-3 and the tile's column/row within it.
+    pub nametable: u8,
+    pub tile_column: u8,
+    pub tile_row: u8,
+    pub attribute_addr: u16,
+    /// Pattern table address of the background tile, through the $2000
+    /// background table bit as it is now (end of frame).
+    pub bg_pattern_addr: u16,
+    /// The frontmost opaque sprite at the pixel.
+    pub sprite: Option<SpriteInfo>,
+}
+
+impl Nes {
+    /// Inspects pixel (`x`, `y`) of the most recently rendered frame.
+    pub fn inspect_pixel(&self, x: u8, y: u8) -> Option<PixelInfo> {
+        let source = self.ppu.pixel_source(x as usize, y as usize)?;
+        let palette_addr = 0x3F00 | source.palette_addr as u16;
+        let offset = source.bg_nametable_addr & 0x0FFF;
+        let tile_column = (offset & 0x1F) as u8;
+        let tile_row = ((offset >> 5) & 0x1F) as u8;
+        let bg_table = if self.ppu.debug_ctrl() & 0x10 != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+        let sprite = source.top_sprite.map(|index| {
+            let base = index as usize * 4;
+            SpriteInfo {
+                index,
+                y: self.ppu.debug_peek_oam(base),
+                tile: self.ppu.debug_peek_oam(base + 1),
+                attributes: self.ppu.debug_peek_oam(base + 2),
+                x: self.ppu.debug_peek_oam(base + 3),
+                visible: source.sprite == Some(index),
+            }
+        });
+        Some(PixelInfo {
+            x,
+            y,
+            palette_addr,
+            color: self.ppu.debug_peek_palette(source.palette_addr as usize) & 0x3F,
+            bg_tile: source.bg_tile,
+            nametable_addr: source.bg_nametable_addr,
+            nametable: (offset >> 10) as u8,
+            tile_column,
+            tile_row,
+            attribute_addr: 0x23C0
+                | (offset & 0x0C00)
+                | ((tile_row as u16 >> 2) << 3)
+                | (tile_column as u16 >> 2),
+            bg_pattern_addr: bg_table | (source.bg_tile as u16) << 4,
+            sprite,
+        })
+    }
+
+    /// The background grid of the most recently rendered frame: edges
+    /// between 8×8 tiles, or between 16×16 attribute areas when `cell` is
+    /// 16. Edges are found where neighboring pixels were fetched from
+    /// different cells, so the grid follows fine scrolling and mid-frame
+    /// scroll splits.
+    pub fn tile_grid(&self, cell: u8) -> TileGrid {
+        let shift = if cell >= 16 { 1 } else { 0 };
+        let key = |x: usize, y: usize| {
+            self.ppu.pixel_source(x, y).map(|source| {
+                let offset = source.bg_nametable_addr & 0x0FFF;
+                (
+                    offset >> 10,
+                    (offset & 0x1F) >> shift,
+                    ((offset >> 5) & 0x1F) >> shift,
+                )
+            })
+        };
+        let mut grid = TileGrid::default();
+        // Open vertical runs by x, extended while consecutive lines agree.
+        let mut open: Vec<Option<GridLine>> = vec![None; 256];
+        for y in 0..240 {
+            let mut run: Option<GridLine> = None;
+            for (x, column) in open.iter_mut().enumerate() {
+                let here = key(x, y);
+                let left_edge = x > 0 && here != key(x - 1, y);
+                let top_edge = y > 0 && here != key(x, y - 1);
+                if left_edge {
+                    let line = column.get_or_insert(GridLine {
+                        vertical: true,
+                        at: x as u8,
+                        start: y as u8,
+                        end: y as u16,
+                    });
+                    line.end = y as u16 + 1;
+                } else if let Some(line) = column.take() {
+                    grid.lines.push(line);
+                }
+                if top_edge {
+                    run.get_or_insert(GridLine {
+                        vertical: false,
+                        at: y as u8,
+                        start: x as u8,
+                        end: x as u16,
+                    })
+                    .end = x as u16 + 1;
+                } else if let Some(line) = run.take() {
+                    grid.lines.push(line);
+                }
+                if (x == 0 || left_edge)
+                    && (y == 0 || top_edge)
+                    && let Some(source) = self.ppu.pixel_source(x, y)
+                {
+                    let offset = source.bg_nametable_addr & 0x0FFF;
+                    grid.cells.push(GridCell {
+                        x: x as u8,
+                        y: y as u8,
+                        nametable: (offset >> 10) as u8,
+                        tile_column: (offset & 0x1F) as u8,
+                        tile_row: ((offset >> 5) & 0x1F) as u8,
+                    });
+                }
+            }
+            grid.lines.extend(run);
+        }
+        grid.lines.extend(open.into_iter().flatten());
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_grid_follows_fine_scroll() {
+        // Waits two vblanks, scrolls to (3, 5), shows the background.
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[..6].copy_from_slice(b"NES\x1A\x01\x01");
+        let program = [
+            0x2C, 0x02, 0x20, 0x10, 0xFB, // BIT $2002 / BPL
+            0x2C, 0x02, 0x20, 0x10, 0xFB, // BIT $2002 / BPL
+            0xA9, 0x03, 0x8D, 0x05, 0x20, // LDA #3 / STA $2005
+            0xA9, 0x05, 0x8D, 0x05, 0x20, // LDA #5 / STA $2005
+            0xA9, 0x0A, 0x8D, 0x01, 0x20, // LDA #$0A / STA $2001
+            0x4C, 0x19, 0xC0, // JMP *
+        ];
+        rom[16..16 + program.len()].copy_from_slice(&program);
+        rom[16 + 0x3FFA..16 + 0x4000].copy_from_slice(&[0x19, 0xC0, 0x00, 0xC0, 0x19, 0xC0]);
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        for _ in 0..4 {
+            nes.run_frame();
+        }
+
+        let grid = nes.tile_grid(8);
+        let mut columns: Vec<u8> = grid
+            .lines
+            .iter()
+            .filter(|line| line.vertical)
+            .map(|line| line.at)
+            .collect();
+        columns.sort_unstable();
+        columns.dedup();
+        assert_eq!(columns.first(), Some(&5));
+        assert!(columns.iter().all(|x| x % 8 == 5), "{columns:?}");
+        assert!(
+            grid.lines
+                .iter()
+                .filter(|line| !line.vertical)
+                .all(|line| line.at % 8 == 3 && line.start == 0 && line.end == 256)
+        );
+        let first = grid.cells[0];
+        assert_eq!(
+            (first.x, first.y, first.tile_column, first.tile_row),
+            (0, 0, 0, 0)
+        );
+        assert!(nes.tile_grid(16).cells.len() < grid.cells.len());
+    }
+}
@@ -0,0 +1,2047 @@
+use anyhow::{Result, bail};
+
+use super::mapper::{FetchPhase, Mapper, Mirroring, NametableSource};
+use super::palette::{NtscPaletteParams, PALETTE_ENTRIES, generate_ntsc_palette};
+use super::region::Region;
+
+pub const FRAME_WIDTH: usize = 256;
+pub const FRAME_HEIGHT: usize = 240;
+
+const CTRL_NMI_ENABLE: u8 = 0x80;
+const CTRL_VRAM_INC_32: u8 = 0x04;
+const CTRL_SPRITE_TABLE: u8 = 0x08;
+const CTRL_BG_TABLE: u8 = 0x10;
+const CTRL_SPRITE_SIZE_16: u8 = 0x20;
+
+const MASK_GREYSCALE: u8 = 0x01;
+const MASK_SHOW_BG_LEFT: u8 = 0x02;
+const MASK_SHOW_SPRITE_LEFT: u8 = 0x04;
+const MASK_SHOW_BG: u8 = 0x08;
+const MASK_SHOW_SPRITES: u8 = 0x10;
+
+const STATUS_SPRITE_OVERFLOW: u8 = 0x20;
+const STATUS_SPRITE_ZERO_HIT: u8 = 0x40;
+const STATUS_VBLANK: u8 = 0x80;
+const NMI_DELAY_CYCLES: u8 = 0;
+/// About 600 ms of NTSC frames.
+pub const DEFAULT_OPEN_BUS_DECAY_FRAMES: u8 = 36;
+
+/// Where a rendered pixel came from, recorded while the frame is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PixelSource {
+    /// Palette RAM offset ($3F00 + this) the pixel's color was read from.
+    pub palette_addr: u8,
+    /// Background tile index under the pixel and the nametable address it was fetched from.
+    pub bg_tile: u8,
+    pub bg_nametable_addr: u16,
+    /// OAM index of the sprite that won priority at this pixel, if any.
+    pub sprite: Option<u8>,
+    /// OAM index of the frontmost opaque sprite at this pixel, even one
+    /// hidden behind the background by its priority bit.
+    pub top_sprite: Option<u8>,
+}
+
+/// Per-scanline sprite selection of the current frame, for overlays. Reset
+/// on the pre-render line, so between frames it describes the frame that just
+/// finished.
+#[derive(Debug, Clone)]
+pub struct SpriteScanlineCapture {
+    /// OAM indices picked for each visible scanline, in evaluation order;
+    /// only the first `counts[line]` entries of a line are valid.
+    pub sprites: [[u8; 8]; FRAME_HEIGHT],
+    pub counts: [u8; FRAME_HEIGHT],
+    /// (scanline, cycle) where the overflow flag was set this frame.
+    pub overflow: Option<(i16, i16)>,
+    /// (scanline, cycle) of this frame's sprite 0 hit.
+    pub sprite0_hit: Option<(i16, i16)>,
+}
+
+impl Default for SpriteScanlineCapture {
+    fn default() -> Self {
+        Self {
+            sprites: [[0; 8]; FRAME_HEIGHT],
+            counts: [0; FRAME_HEIGHT],
+            overflow: None,
+            sprite0_hit: None,
+        }
+    }
+}
+
+impl SpriteScanlineCapture {
+    /// OAM indices of the sprites selected for `scanline`.
+    pub fn line(&self, scanline: usize) -> &[u8] {
+        &self.sprites[scanline][..self.counts[scanline] as usize]
+    }
+
+    fn clear(&mut self) {
+        self.counts = [0; FRAME_HEIGHT];
+        self.overflow = None;
+        self.sprite0_hit = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PpuDebugCounters {
+    pub ticks: u64,
+    pub vblank_entries: u64,
+    pub nmi_edges: u64,
+    pub nmi_fired: u64,
+    pub sprite_overflow_events: u64,
+    pub sprite_overflow_last_scanline: i16,
+    pub sprite_overflow_last_cycle: i16,
+    pub sprite0_hit_events: u64,
+    pub sprite0_hit_last_scanline: i16,
+    pub sprite0_hit_last_cycle: i16,
+    pub sprite0_nonzero_events: u64,
+    pub sprite0_nonzero_last_scanline: i16,
+    pub sprite0_nonzero_last_cycle: i16,
+    pub sprite0_nonzero_last_bg_pixel: u8,
+    pub sprite0_nonzero_last_bg_opaque: bool,
+    pub pattern_reads: u64,
+    pub nametable_reads: u64,
+    pub palette_reads: u64,
+    pub pattern_writes: u64,
+    pub nametable_writes: u64,
+    pub palette_writes: u64,
+    pub status_reads: u64,
+    pub status_read_last_scanline: i16,
+    pub status_read_last_cycle: i16,
+    pub status_overflow_reads: u64,
+    pub status_overflow_last_scanline: i16,
+    pub status_overflow_last_cycle: i16,
+    pub scroll_writes_2005: u64,
+    pub scroll_write_2005_last_scanline: i16,
+    pub scroll_write_2005_last_cycle: i16,
+    pub scroll_write_2005_last_value: u8,
+    pub scroll_write_2005_last_phase_second: bool,
+    pub scroll_write_2005_prev_scanline: i16,
+    pub scroll_write_2005_prev_cycle: i16,
+    pub scroll_write_2005_prev_value: u8,
+    pub scroll_write_2005_prev_phase_second: bool,
+    pub addr_writes_2006: u64,
+    pub addr_write_2006_last_scanline: i16,
+    pub addr_write_2006_last_cycle: i16,
+    pub addr_write_2006_last_value: u8,
+    pub addr_write_2006_last_phase_second: bool,
+    pub addr_write_2006_prev_scanline: i16,
+    pub addr_write_2006_prev_cycle: i16,
+    pub addr_write_2006_prev_value: u8,
+    pub addr_write_2006_prev_phase_second: bool,
+    pub last_read_addr: u16,
+    pub last_write_addr: u16,
+}
+
+#[derive(Clone)]
+pub struct Ppu {
+    ctrl: u8,
+    mask: u8,
+    status: u8,
+
+    oam_addr: u8,
+    oam: [u8; 256],
+
+    vram: [u8; 4096],
+    /// VRAM offset of each logical nametable under the cartridge's current
+    /// mirroring, set through [`Ppu::set_mirroring`].
+    nametable_bases: [usize; 4],
+    mirroring: Mirroring,
+    palette_ram: [u8; 32],
+
+    write_toggle: bool,
+    v: u16,
+    t: u16,
+    fine_x: u8,
+    read_buffer: u8,
+    open_bus: u8,
+    /// Frames since the I/O latch was last driven, and after how many it
+    /// reads back as zero. Real consoles lose it after roughly 600 ms.
+    open_bus_age: u8,
+    open_bus_decay_frames: u8,
+    ppuaddr_reload_pending: bool,
+    ppuaddr_reload_delay: u8,
+
+    scanline: i16,
+    cycle: i16,
+    odd_frame: bool,
+    region: Region,
+    rgb_palette: Vec<[u8; 3]>,
+    /// Skip priority, palette lookup and frame buffer writes, keeping only
+    /// what the CPU can observe (sprite 0 hit).
+    skip_composition: bool,
+    frame_complete: bool,
+    nmi_pending: bool,
+    vblank_suppress: bool,
+    nmi_line: bool,
+    nmi_delay: u8,
+
+    debug_mask_write_count: u64,
+    debug_last_mask_value: u8,
+
+    next_tile_id: u8,
+    next_tile_addr: u16,
+    next_tile_attr: u8,
+    next_tile_lsb: u8,
+    next_tile_msb: u8,
+    bg_shift_pattern_lo: u16,
+    bg_shift_pattern_hi: u16,
+    bg_shift_attr_lo: u16,
+    bg_shift_attr_hi: u16,
+    // Tile index/nametable address of the [current, next] tiles in the shifters.
+    bg_shift_tile_ids: [u8; 2],
+    bg_shift_tile_addrs: [u16; 2],
+    bg_shifts_since_load: u8,
+
+    sprite_count: usize,
+    sprite_patterns_lo: [u8; 8],
+    sprite_patterns_hi: [u8; 8],
+    sprite_x: [u8; 8],
+    sprite_attributes: [u8; 8],
+    sprite_indices: [u8; 8],
+
+    /// Sprites picked for the next scanline by evaluation on dots 65-256:
+    /// their four OAM bytes and OAM index, `sprite_eval_found` of them.
+    secondary_oam: [u8; 32],
+    secondary_indices: [u8; 8],
+    sprite_eval_active: bool,
+    sprite_eval_n: u8,
+    sprite_eval_m: u8,
+    sprite_eval_found: u8,
+    sprite_eval_copy_remaining: u8,
+    sprite_eval_bug_mode: bool,
+    sprite_eval_target_scanline: i16,
+
+    frame_buffer: [u8; FRAME_WIDTH * FRAME_HEIGHT * 4],
+    frame_buffer_indexed: Vec<u16>,
+    pixel_sources: Vec<PixelSource>,
+    sprite_capture: Box<SpriteScanlineCapture>,
+    /// Nametable sources as of the start of each visible scanline.
+    nametable_lines: Box<[[NametableSource; 4]; FRAME_HEIGHT]>,
+    debug: PpuDebugCounters,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_addr: 0,
+            oam: [0; 256],
+            vram: [0; 4096],
+            nametable_bases: Self::nametable_bases(Mirroring::Horizontal),
+            mirroring: Mirroring::Horizontal,
+            palette_ram: [0x0F; 32],
+            write_toggle: false,
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            read_buffer: 0,
+            open_bus: 0,
+            open_bus_age: 0,
+            open_bus_decay_frames: DEFAULT_OPEN_BUS_DECAY_FRAMES,
+            ppuaddr_reload_pending: false,
+            ppuaddr_reload_delay: 0,
+            scanline: 261,
+            cycle: 0,
+            odd_frame: false,
+            region: Region::Ntsc,
+            rgb_palette: generate_ntsc_palette(&NtscPaletteParams::default()),
+            skip_composition: false,
+            frame_complete: false,
+            nmi_pending: false,
+            vblank_suppress: false,
+            nmi_line: false,
+            nmi_delay: 0,
+            debug_mask_write_count: 0,
+            debug_last_mask_value: 0,
+            next_tile_id: 0,
+            next_tile_addr: 0,
+            next_tile_attr: 0,
+            next_tile_lsb: 0,
+            next_tile_msb: 0,
+            bg_shift_pattern_lo: 0,
+            bg_shift_pattern_hi: 0,
+            bg_shift_attr_lo: 0,
+            bg_shift_attr_hi: 0,
+            bg_shift_tile_ids: [0; 2],
+            bg_shift_tile_addrs: [0; 2],
+            bg_shifts_since_load: 0,
+            sprite_count: 0,
+            sprite_patterns_lo: [0; 8],
+            sprite_patterns_hi: [0; 8],
+            sprite_x: [0; 8],
+            sprite_attributes: [0; 8],
+            sprite_indices: [0; 8],
+            secondary_oam: [0xFF; 32],
+            secondary_indices: [0; 8],
+            sprite_eval_active: false,
+            sprite_eval_n: 0,
+            sprite_eval_m: 0,
+            sprite_eval_found: 0,
+            sprite_eval_copy_remaining: 0,
+            sprite_eval_bug_mode: false,
+            sprite_eval_target_scanline: 0,
+            frame_buffer: [0; FRAME_WIDTH * FRAME_HEIGHT * 4],
+            frame_buffer_indexed: vec![0; FRAME_WIDTH * FRAME_HEIGHT],
+            pixel_sources: vec![PixelSource::default(); FRAME_WIDTH * FRAME_HEIGHT],
+            sprite_capture: Box::default(),
+            nametable_lines: Box::new([[NametableSource::Vram(0); 4]; FRAME_HEIGHT]),
+            debug: PpuDebugCounters::default(),
+        }
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn reset(&mut self) {
+        self.ctrl = 0;
+        self.mask = 0;
+        self.status = 0;
+        self.oam_addr = 0;
+        self.write_toggle = false;
+        self.v = 0;
+        self.t = 0;
+        self.fine_x = 0;
+        self.read_buffer = 0;
+        self.open_bus = 0;
+        self.open_bus_age = 0;
+        self.ppuaddr_reload_pending = false;
+        self.ppuaddr_reload_delay = 0;
+        self.scanline = self.pre_render_scanline();
+        self.cycle = 0;
+        self.odd_frame = false;
+        self.frame_complete = false;
+        self.nmi_pending = false;
+        self.vblank_suppress = false;
+        self.nmi_line = false;
+        self.nmi_delay = 0;
+        self.debug_mask_write_count = 0;
+        self.debug_last_mask_value = self.mask;
+
+        self.next_tile_id = 0;
+        self.next_tile_addr = 0;
+        self.next_tile_attr = 0;
+        self.next_tile_lsb = 0;
+        self.next_tile_msb = 0;
+        self.bg_shift_pattern_lo = 0;
+        self.bg_shift_pattern_hi = 0;
+        self.bg_shift_attr_lo = 0;
+        self.bg_shift_attr_hi = 0;
+        self.bg_shift_tile_ids = [0; 2];
+        self.bg_shift_tile_addrs = [0; 2];
+        self.bg_shifts_since_load = 0;
+
+        self.sprite_count = 0;
+        self.sprite_patterns_lo = [0; 8];
+        self.sprite_patterns_hi = [0; 8];
+        self.sprite_x = [0; 8];
+        self.sprite_attributes = [0; 8];
+        self.sprite_indices = [0; 8];
+        self.secondary_oam = [0xFF; 32];
+        self.secondary_indices = [0; 8];
+        self.sprite_eval_active = false;
+        self.sprite_eval_n = 0;
+        self.sprite_eval_m = 0;
+        self.sprite_eval_found = 0;
+        self.sprite_eval_copy_remaining = 0;
+        self.sprite_eval_bug_mode = false;
+        self.sprite_eval_target_scanline = 0;
+        self.debug = PpuDebugCounters::default();
+
+        // Keep startup background black for deterministic test behavior.
+        self.palette_ram = [0x0F; 32];
+    }
+
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.frame_buffer
+    }
+
+    /// One entry per pixel: bits 0-5 are the NES color index (after greyscale),
+    /// bits 6-8 are the $2001 emphasis bits (red, green, blue) active when drawn.
+    pub fn frame_buffer_indexed(&self) -> &[u16] {
+        &self.frame_buffer_indexed
+    }
+
+    pub fn pixel_source(&self, x: usize, y: usize) -> Option<PixelSource> {
+        if x >= FRAME_WIDTH || y >= FRAME_HEIGHT {
+            return None;
+        }
+        Some(self.pixel_sources[y * FRAME_WIDTH + x])
+    }
+
+    pub fn debug_ctrl(&self) -> u8 {
+        self.ctrl
+    }
+
+    pub fn debug_mask(&self) -> u8 {
+        self.mask
+    }
+
+    pub fn debug_status(&self) -> u8 {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: u8) {
+        self.status = status;
+    }
+
+    pub fn debug_scanline_cycle(&self) -> (i16, i16) {
+        (self.scanline, self.cycle)
+    }
+
+    pub fn debug_mask_write_stats(&self) -> (u64, u8) {
+        (self.debug_mask_write_count, self.debug_last_mask_value)
+    }
+
+    pub fn debug_peek_vram(&self, index: usize) -> u8 {
+        self.vram[index % self.vram.len()]
+    }
+
+    pub fn debug_peek_palette(&self, index: usize) -> u8 {
+        self.palette_ram[index % self.palette_ram.len()]
+    }
+
+    /// Writes palette RAM entry `index` (0-31, $3F00 + index) the way a
+    /// $2007 write would, mirrors included, without touching any registers.
+    pub fn debug_poke_palette(&mut self, index: usize, value: u8) {
+        self.palette_ram[Self::palette_index(index as u16)] = value & 0x3F;
+    }
+
+    pub fn debug_peek_oam(&self, index: usize) -> u8 {
+        self.oam[index % self.oam.len()]
+    }
+
+    /// Nametable byte at `addr` ($2000-$2FFF) through CIRAM mirroring, without
+    /// the mapper side effects of a real PPU read.
+    pub(crate) fn rgb_palette(&self) -> &[[u8; 3]] {
+        &self.rgb_palette
+    }
+
+    /// Where each logical nametable came from at the start of every visible
+    /// scanline of the current (or just finished) frame.
+    pub fn nametable_lines(&self) -> &[[NametableSource; 4]; FRAME_HEIGHT] {
+        &self.nametable_lines
+    }
+
+    pub fn sprite_capture(&self) -> &SpriteScanlineCapture {
+        &self.sprite_capture
+    }
+
+    pub fn debug_counters(&self) -> PpuDebugCounters {
+        self.debug
+    }
+
+    pub fn zapper_light_sensed(&self, x: i16, y: i16) -> bool {
+        if x < 0 || y < 0 || x >= FRAME_WIDTH as i16 || y >= FRAME_HEIGHT as i16 {
+            return false;
+        }
+
+        let mut max_luma: u16 = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let sx = (x + dx).clamp(0, FRAME_WIDTH as i16 - 1) as usize;
+                let sy = (y + dy).clamp(0, FRAME_HEIGHT as i16 - 1) as usize;
+                let idx = (sy * FRAME_WIDTH + sx) * 4;
+                let r = self.frame_buffer[idx] as u16;
+                let g = self.frame_buffer[idx + 1] as u16;
+                let b = self.frame_buffer[idx + 2] as u16;
+                let luma = r + g + b;
+                if luma > max_luma {
+                    max_luma = luma;
+                }
+            }
+        }
+
+        max_luma >= 620
+    }
+
+    pub fn clear_frame_complete(&mut self) {
+        self.frame_complete = false;
+    }
+
+    pub fn frame_complete(&self) -> bool {
+        self.frame_complete
+    }
+
+    pub fn take_nmi(&mut self) -> bool {
+        let pending = self.nmi_pending;
+        self.nmi_pending = false;
+        pending
+    }
+
+    pub fn cpu_read_register(&mut self, addr: u16, mapper: &mut dyn Mapper) -> u8 {
+        let value = match addr {
+            0x2002 => {
+                self.debug.status_reads = self.debug.status_reads.wrapping_add(1);
+                self.debug.status_read_last_scanline = self.scanline;
+                self.debug.status_read_last_cycle = self.cycle;
+                if (self.status & STATUS_SPRITE_OVERFLOW) != 0 {
+                    self.debug.status_overflow_reads =
+                        self.debug.status_overflow_reads.wrapping_add(1);
+                    self.debug.status_overflow_last_scanline = self.scanline;
+                    self.debug.status_overflow_last_cycle = self.cycle;
+                }
+
+                // Reading $2002 around VBL start suppresses VBL/NMI for this frame.
+                if self.scanline == self.region.vblank_scanline() && self.cycle == 0 {
+                    self.vblank_suppress = true;
+                    self.nmi_delay = 0;
+                    self.nmi_pending = false;
+                }
+
+                let value = (self.status & 0xE0) | (self.open_bus & 0x1F);
+                self.status &= !STATUS_VBLANK;
+                self.write_toggle = false;
+                self.update_nmi_line();
+                value
+            }
+            0x2004 => self.oam[self.oam_addr as usize],
+            0x2007 => {
+                let ppu_addr = self.v & 0x3FFF;
+                let value = self.ppu_read(ppu_addr, mapper);
+                let result = if ppu_addr >= 0x3F00 {
+                    // Palette reads skip the buffer (which picks up the
+                    // nametable byte underneath instead). Palette RAM is
+                    // only six bits wide, so the top two come from the
+                    // open bus, and greyscale applies to reads as well.
+                    self.read_buffer = self.ppu_read((ppu_addr - 0x1000) & 0x3FFF, mapper);
+                    (self.open_bus & 0xC0) | self.apply_greyscale(value)
+                } else {
+                    let buffered = self.read_buffer;
+                    self.read_buffer = value;
+                    buffered
+                };
+
+                self.increment_vram_addr_cpu_access();
+                result
+            }
+            _ => self.open_bus,
+        };
+        self.open_bus = value;
+        self.open_bus_age = 0;
+        value
+    }
+
+    pub fn cpu_write_register(&mut self, addr: u16, value: u8, mapper: &mut dyn Mapper) {
+        self.open_bus = value;
+        self.open_bus_age = 0;
+        match addr {
+            0x2000 => {
+                self.ctrl = value;
+                self.t = (self.t & !0x0C00) | (((value as u16) & 0x03) << 10);
+                self.update_nmi_line();
+            }
+            0x2001 => {
+                self.mask = value;
+                self.debug_mask_write_count = self.debug_mask_write_count.wrapping_add(1);
+                self.debug_last_mask_value = value;
+            }
+            0x2003 => {
+                self.oam_addr = value;
+            }
+            0x2004 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            0x2005 => {
+                let second_phase = self.write_toggle;
+                self.debug.scroll_writes_2005 = self.debug.scroll_writes_2005.wrapping_add(1);
+                self.debug.scroll_write_2005_prev_scanline =
+                    self.debug.scroll_write_2005_last_scanline;
+                self.debug.scroll_write_2005_prev_cycle = self.debug.scroll_write_2005_last_cycle;
+                self.debug.scroll_write_2005_prev_value = self.debug.scroll_write_2005_last_value;
+                self.debug.scroll_write_2005_prev_phase_second =
+                    self.debug.scroll_write_2005_last_phase_second;
+                self.debug.scroll_write_2005_last_scanline = self.scanline;
+                self.debug.scroll_write_2005_last_cycle = self.cycle;
+                self.debug.scroll_write_2005_last_value = value;
+                self.debug.scroll_write_2005_last_phase_second = second_phase;
+                if !self.write_toggle {
+                    self.fine_x = value & 0x07;
+                    self.t = (self.t & !0x001F) | (((value as u16) >> 3) & 0x001F);
+                } else {
+                    self.t = (self.t & !0x03E0) | ((((value as u16) >> 3) & 0x001F) << 5);
+                    self.t = (self.t & !0x7000) | (((value as u16) & 0x07) << 12);
+                }
+                self.write_toggle = !self.write_toggle;
+            }
+            0x2006 => {
+                let second_phase = self.write_toggle;
+                self.debug.addr_writes_2006 = self.debug.addr_writes_2006.wrapping_add(1);
+                self.debug.addr_write_2006_prev_scanline = self.debug.addr_write_2006_last_scanline;
+                self.debug.addr_write_2006_prev_cycle = self.debug.addr_write_2006_last_cycle;
+                self.debug.addr_write_2006_prev_value = self.debug.addr_write_2006_last_value;
+                self.debug.addr_write_2006_prev_phase_second =
+                    self.debug.addr_write_2006_last_phase_second;
+                self.debug.addr_write_2006_last_scanline = self.scanline;
+                self.debug.addr_write_2006_last_cycle = self.cycle;
+                self.debug.addr_write_2006_last_value = value;
+                self.debug.addr_write_2006_last_phase_second = second_phase;
+                if !self.write_toggle {
+                    self.t = (self.t & 0x00FF) | (((value as u16) & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0x7F00) | (value as u16);
+                    // The t -> v copy lands on the following dot, not during the write.
+                    self.ppuaddr_reload_pending = true;
+                    self.ppuaddr_reload_delay = 1;
+                }
+                self.write_toggle = !self.write_toggle;
+            }
+            0x2007 => {
+                let ppu_addr = self.v & 0x3FFF;
+                self.ppu_write(ppu_addr, value, mapper);
+                self.increment_vram_addr_cpu_access();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn write_oam_dma(&mut self, bytes: &[u8; 256]) {
+        for byte in bytes {
+            self.oam[self.oam_addr as usize] = *byte;
+            self.oam_addr = self.oam_addr.wrapping_add(1);
+        }
+    }
+
+    pub fn tick(&mut self, mapper: &mut dyn Mapper) {
+        self.debug.ticks = self.debug.ticks.wrapping_add(1);
+
+        if self.nmi_delay > 0 {
+            self.nmi_delay = self.nmi_delay.saturating_sub(1);
+            if self.nmi_delay == 0 && self.nmi_line {
+                self.nmi_pending = true;
+                self.debug.nmi_fired = self.debug.nmi_fired.wrapping_add(1);
+            }
+        }
+
+        if self.ppuaddr_reload_pending {
+            if self.ppuaddr_reload_delay > 0 {
+                self.ppuaddr_reload_delay = self.ppuaddr_reload_delay.saturating_sub(1);
+            }
+            if self.ppuaddr_reload_delay == 0 {
+                self.v = self.t;
+                self.ppuaddr_reload_pending = false;
+            }
+        }
+
+        let visible_line = (0..240).contains(&self.scanline);
+        let pre_render = self.scanline == self.pre_render_scanline();
+        let render_line = visible_line || pre_render;
+        let rendering_enabled = self.rendering_enabled();
+
+        if pre_render && self.cycle == 1 {
+            self.status &= !(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW);
+            self.sprite_capture.clear();
+            self.frame_complete = false;
+            self.vblank_suppress = false;
+            self.update_nmi_line();
+        }
+
+        if self.scanline == self.region.vblank_scanline() && self.cycle == 1 {
+            self.frame_complete = true;
+            self.open_bus_age = self.open_bus_age.saturating_add(1);
+            if self.open_bus_age >= self.open_bus_decay_frames {
+                self.open_bus = 0;
+            }
+            self.debug.vblank_entries = self.debug.vblank_entries.wrapping_add(1);
+            if !self.vblank_suppress {
+                self.status |= STATUS_VBLANK;
+            }
+            self.vblank_suppress = false;
+            self.update_nmi_line();
+        }
+
+        if visible_line && self.cycle == 65 {
+            self.begin_sprite_evaluation(rendering_enabled);
+        }
+        if visible_line && (65..=256).contains(&self.cycle) {
+            self.clock_sprite_evaluation(rendering_enabled);
+        }
+
+        if visible_line && self.cycle == 0 {
+            self.nametable_lines[self.scanline as usize] =
+                std::array::from_fn(|table| mapper.nametable_source(table));
+        }
+
+        // Background shifters move on dots 2-257 and 322-337, one dot behind the
+        // reloads at 9, 17, ... and 329, so the pixel drawn on dot N has seen
+        // exactly N-1 shifts since the first tile of the line was loaded.
+        if render_line
+            && rendering_enabled
+            && ((2..=257).contains(&self.cycle) || (322..=337).contains(&self.cycle))
+        {
+            self.shift_background_registers();
+        }
+
+        if visible_line && (1..=256).contains(&self.cycle) {
+            self.render_pixel((self.cycle - 1) as usize, self.scanline as usize);
+        }
+
+        if render_line && !rendering_enabled && self.cycle == 257 {
+            // Nothing is fetched, so the next line has no sprites.
+            self.sprite_count = 0;
+        }
+
+        if render_line && rendering_enabled {
+            if (1..=256).contains(&self.cycle) || (321..=336).contains(&self.cycle) {
+                let phase = (self.cycle - 1) & 0x07;
+                match phase {
+                    0 => {
+                        if let Some(fetch) = self.background_fetch_phase() {
+                            mapper.set_fetch_phase(fetch);
+                        }
+                        self.load_background_shifters();
+                        self.next_tile_addr = 0x2000 | (self.v & 0x0FFF);
+                        self.next_tile_id = self.ppu_read(self.next_tile_addr, mapper);
+                    }
+                    2 => {
+                        let addr = 0x23C0
+                            | (self.v & 0x0C00)
+                            | ((self.v >> 4) & 0x0038)
+                            | ((self.v >> 2) & 0x0007);
+                        let attr = self.ppu_read(addr, mapper);
+                        let shift = ((self.v >> 4) & 0x04) | (self.v & 0x02);
+                        self.next_tile_attr = (attr >> shift) & 0x03;
+                    }
+                    4 => {
+                        let fine_y = (self.v >> 12) & 0x07;
+                        let table = if (self.ctrl & CTRL_BG_TABLE) != 0 {
+                            0x1000
+                        } else {
+                            0x0000
+                        };
+                        let addr = table + (self.next_tile_id as u16) * 16 + fine_y;
+                        self.next_tile_lsb = self.ppu_read(addr, mapper);
+                    }
+                    6 => {
+                        let fine_y = (self.v >> 12) & 0x07;
+                        let table = if (self.ctrl & CTRL_BG_TABLE) != 0 {
+                            0x1000
+                        } else {
+                            0x0000
+                        };
+                        let addr = table + (self.next_tile_id as u16) * 16 + fine_y + 8;
+                        self.next_tile_msb = self.ppu_read(addr, mapper);
+                    }
+                    7 => self.increment_coarse_x(),
+                    _ => {}
+                }
+            }
+
+            if visible_line && (1..=256).contains(&self.cycle) {
+                self.shift_sprite_registers();
+            }
+
+            if self.cycle == 256 {
+                self.increment_y();
+            }
+
+            if self.cycle == 257 {
+                mapper.set_fetch_phase(FetchPhase::Sprites);
+                self.load_background_shifters();
+                self.copy_horizontal_bits();
+            }
+
+            if (257..=320).contains(&self.cycle) {
+                self.fetch_sprite_slot(visible_line, mapper);
+            }
+
+            if pre_render && (280..=304).contains(&self.cycle) {
+                self.copy_vertical_bits();
+            }
+
+            if self.cycle == 338 || self.cycle == 340 {
+                self.next_tile_id = self.ppu_read(0x2000 | (self.v & 0x0FFF), mapper);
+            }
+        }
+
+        // NTSC odd-frame cycle skip: pre-render line drops one PPU cycle when rendering is on.
+        if pre_render
+            && rendering_enabled
+            && self.odd_frame
+            && self.region.has_odd_frame_skip()
+            && self.cycle == 339
+        {
+            self.cycle = 0;
+            self.scanline = 0;
+            self.odd_frame = false;
+            return;
+        }
+
+        self.cycle += 1;
+        if self.cycle > 340 {
+            self.cycle = 0;
+            self.scanline += 1;
+            if self.scanline >= self.region.scanlines_per_frame() {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+    }
+
+    /// While set, the frame buffers keep their previous contents; see
+    /// [`super::Nes::run_frame_skip_render`].
+    pub fn set_skip_composition(&mut self, skip: bool) {
+        self.skip_composition = skip;
+    }
+
+    /// Replaces the color lookup used for the RGBA frame buffer. Takes
+    /// [`PALETTE_ENTRIES`] colors indexed like the indexed frame buffer;
+    /// any other length is refused and the current palette kept.
+    pub fn set_rgb_palette(&mut self, palette: Vec<[u8; 3]>) -> Result<()> {
+        if palette.len() != PALETTE_ENTRIES {
+            bail!(
+                "palette has {} colors, but needs {PALETTE_ENTRIES} to cover every emphasis combination",
+                palette.len()
+            );
+        }
+        self.rgb_palette = palette;
+        Ok(())
+    }
+
+    /// Frames without a register access before the I/O latch decays to 0.
+    pub fn set_open_bus_decay_frames(&mut self, frames: u8) {
+        self.open_bus_decay_frames = frames.max(1);
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    fn pre_render_scanline(&self) -> i16 {
+        self.region.scanlines_per_frame() - 1
+    }
+
+    /// The tile whose fetches start on this dot, `None` for the pre-render
+    /// line's own tiles and the line after the last visible one.
+    fn background_fetch_phase(&self) -> Option<FetchPhase> {
+        let (tile, line) = if self.cycle >= 321 {
+            let next = if self.scanline == self.pre_render_scanline() {
+                0
+            } else {
+                self.scanline + 1
+            };
+            ((self.cycle - 321) / 8, next)
+        } else {
+            ((self.cycle - 1) / 8 + 2, self.scanline)
+        };
+        (0..240).contains(&line).then_some(FetchPhase::Background {
+            tile: tile as u8,
+            line: line as u8,
+        })
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        (self.mask & (MASK_SHOW_BG | MASK_SHOW_SPRITES)) != 0
+    }
+
+    fn update_nmi_line(&mut self) {
+        let line = (self.ctrl & CTRL_NMI_ENABLE) != 0 && (self.status & STATUS_VBLANK) != 0;
+        if line && !self.nmi_line {
+            // NMI edge is not observed by CPU immediately; short delay is suppressible.
+            self.nmi_delay = NMI_DELAY_CYCLES;
+            self.debug.nmi_edges = self.debug.nmi_edges.wrapping_add(1);
+            if self.nmi_delay == 0 {
+                self.nmi_pending = true;
+                self.debug.nmi_fired = self.debug.nmi_fired.wrapping_add(1);
+            }
+        } else if !line {
+            self.nmi_delay = 0;
+        }
+        self.nmi_line = line;
+    }
+
+    fn render_pixel(&mut self, x: usize, y: usize) {
+        let (bg_pixel, bg_palette, bg_opaque) = self.background_sample(x);
+        let sprite0_pixel = self.sprite0_pixel(x);
+
+        // Sprite 0 hit: opaque sprite 0 over opaque background on the same dot,
+        // never at x=255. Left-column clipping already blanks both samples.
+        if sprite0_pixel != 0 && x < 255 {
+            self.debug.sprite0_nonzero_events = self.debug.sprite0_nonzero_events.wrapping_add(1);
+            self.debug.sprite0_nonzero_last_scanline = self.scanline;
+            self.debug.sprite0_nonzero_last_cycle = self.cycle;
+            self.debug.sprite0_nonzero_last_bg_pixel = bg_pixel;
+            self.debug.sprite0_nonzero_last_bg_opaque = bg_opaque;
+
+            if bg_opaque {
+                if (self.status & STATUS_SPRITE_ZERO_HIT) == 0 {
+                    self.debug.sprite0_hit_events = self.debug.sprite0_hit_events.wrapping_add(1);
+                    self.debug.sprite0_hit_last_scanline = self.scanline;
+                    self.debug.sprite0_hit_last_cycle = self.cycle;
+                    self.sprite_capture.sprite0_hit = Some((self.scanline, self.cycle));
+                }
+                self.status |= STATUS_SPRITE_ZERO_HIT;
+            }
+        }
+
+        if self.skip_composition {
+            return;
+        }
+
+        let (spr_pixel, spr_palette, spr_behind_bg, spr_oam_index) = self.sprite_sample(x);
+        let sprite_wins = spr_pixel != 0 && (!bg_opaque || !spr_behind_bg);
+        let palette_index = if sprite_wins {
+            0x10 | ((spr_palette << 2) | spr_pixel)
+        } else if bg_opaque {
+            (bg_palette << 2) | bg_pixel
+        } else {
+            0
+        };
+
+        // Pixels taken from the upper shifter byte belong to the current tile.
+        let tile_slot = if (self.fine_x + self.bg_shifts_since_load) <= 7 {
+            0
+        } else {
+            1
+        };
+        self.pixel_sources[y * FRAME_WIDTH + x] = PixelSource {
+            palette_addr: palette_index,
+            bg_tile: self.bg_shift_tile_ids[tile_slot],
+            bg_nametable_addr: self.bg_shift_tile_addrs[tile_slot],
+            sprite: sprite_wins.then_some(spr_oam_index),
+            top_sprite: (spr_pixel != 0).then_some(spr_oam_index),
+        };
+
+        let color = self.palette_color(palette_index);
+        let indexed = color as u16 | (((self.mask >> 5) as u16) << 6);
+        self.frame_buffer_indexed[y * FRAME_WIDTH + x] = indexed;
+
+        let rgb = self.rgb_palette[indexed as usize];
+        let rgba = [rgb[0], rgb[1], rgb[2], 0xFF];
+        let pixel = (y * FRAME_WIDTH + x) * 4;
+        self.frame_buffer[pixel] = rgba[0];
+        self.frame_buffer[pixel + 1] = rgba[1];
+        self.frame_buffer[pixel + 2] = rgba[2];
+        self.frame_buffer[pixel + 3] = 0xFF;
+    }
+
+    fn background_sample(&self, x: usize) -> (u8, u8, bool) {
+        if (self.mask & MASK_SHOW_BG) == 0 {
+            return (0, 0, false);
+        }
+        if x < 8 && (self.mask & MASK_SHOW_BG_LEFT) == 0 {
+            return (0, 0, false);
+        }
+
+        let bit = 0x8000u16 >> self.fine_x;
+
+        let p0 = ((self.bg_shift_pattern_lo & bit) != 0) as u8;
+        let p1 = ((self.bg_shift_pattern_hi & bit) != 0) as u8;
+        let pixel = (p1 << 1) | p0;
+
+        let a0 = ((self.bg_shift_attr_lo & bit) != 0) as u8;
+        let a1 = ((self.bg_shift_attr_hi & bit) != 0) as u8;
+        let palette = (a1 << 1) | a0;
+
+        (pixel, palette, pixel != 0)
+    }
+
+    fn sprite_sample(&self, x: usize) -> (u8, u8, bool, u8) {
+        if (self.mask & MASK_SHOW_SPRITES) == 0 {
+            return (0, 0, false, 0);
+        }
+        if x < 8 && (self.mask & MASK_SHOW_SPRITE_LEFT) == 0 {
+            return (0, 0, false, 0);
+        }
+
+        for i in 0..self.sprite_count {
+            if self.sprite_x[i] != 0 {
+                continue;
+            }
+
+            let p0 = (self.sprite_patterns_lo[i] & 0x80) >> 7;
+            let p1 = (self.sprite_patterns_hi[i] & 0x80) >> 6;
+            let pixel = p0 | p1;
+            if pixel == 0 {
+                continue;
+            }
+
+            let palette = self.sprite_attributes[i] & 0x03;
+            let behind_bg = (self.sprite_attributes[i] & 0x20) != 0;
+            return (pixel, palette, behind_bg, self.sprite_indices[i]);
+        }
+
+        (0, 0, false, 0)
+    }
+
+    fn sprite0_pixel(&self, x: usize) -> u8 {
+        if (self.mask & MASK_SHOW_SPRITES) == 0 {
+            return 0;
+        }
+        if x < 8 && (self.mask & MASK_SHOW_SPRITE_LEFT) == 0 {
+            return 0;
+        }
+
+        for i in 0..self.sprite_count {
+            if self.sprite_indices[i] != 0 || self.sprite_x[i] != 0 {
+                continue;
+            }
+
+            let p0 = (self.sprite_patterns_lo[i] & 0x80) >> 7;
+            let p1 = (self.sprite_patterns_hi[i] & 0x80) >> 6;
+            return p0 | p1;
+        }
+
+        0
+    }
+
+    fn shift_background_registers(&mut self) {
+        self.bg_shifts_since_load = self.bg_shifts_since_load.saturating_add(1);
+        self.bg_shift_pattern_lo <<= 1;
+        self.bg_shift_pattern_hi <<= 1;
+        self.bg_shift_attr_lo <<= 1;
+        self.bg_shift_attr_hi <<= 1;
+    }
+
+    fn shift_sprite_registers(&mut self) {
+        for i in 0..self.sprite_count {
+            if self.sprite_x[i] > 0 {
+                self.sprite_x[i] = self.sprite_x[i].wrapping_sub(1);
+            } else {
+                self.sprite_patterns_lo[i] <<= 1;
+                self.sprite_patterns_hi[i] <<= 1;
+            }
+        }
+    }
+
+    fn load_background_shifters(&mut self) {
+        self.bg_shift_tile_ids = [self.bg_shift_tile_ids[1], self.next_tile_id];
+        self.bg_shift_tile_addrs = [self.bg_shift_tile_addrs[1], self.next_tile_addr];
+        self.bg_shifts_since_load = 0;
+        self.bg_shift_pattern_lo = (self.bg_shift_pattern_lo & 0xFF00) | self.next_tile_lsb as u16;
+        self.bg_shift_pattern_hi = (self.bg_shift_pattern_hi & 0xFF00) | self.next_tile_msb as u16;
+
+        let attr_lo = if (self.next_tile_attr & 0x01) != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+        let attr_hi = if (self.next_tile_attr & 0x02) != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+
+        self.bg_shift_attr_lo = (self.bg_shift_attr_lo & 0xFF00) | attr_lo;
+        self.bg_shift_attr_hi = (self.bg_shift_attr_hi & 0xFF00) | attr_hi;
+    }
+
+    fn increment_coarse_x(&mut self) {
+        if (self.v & 0x001F) == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v = self.v.wrapping_add(1);
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if (self.v & 0x7000) != 0x7000 {
+            self.v = self.v.wrapping_add(0x1000);
+            return;
+        }
+
+        self.v &= !0x7000;
+        let mut y = (self.v & 0x03E0) >> 5;
+        if y == 29 {
+            y = 0;
+            self.v ^= 0x0800;
+        } else if y == 31 {
+            y = 0;
+        } else {
+            y += 1;
+        }
+
+        self.v = (self.v & !0x03E0) | (y << 5);
+    }
+
+    fn copy_horizontal_bits(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    fn copy_vertical_bits(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+
+    fn begin_sprite_evaluation(&mut self, rendering_enabled: bool) {
+        self.sprite_eval_active = false;
+        self.sprite_eval_n = 0;
+        self.sprite_eval_m = 0;
+        self.sprite_eval_found = 0;
+        self.sprite_eval_copy_remaining = 0;
+        self.sprite_eval_bug_mode = false;
+
+        if !rendering_enabled {
+            return;
+        }
+        if !(0..=239).contains(&self.scanline) {
+            return;
+        }
+
+        self.sprite_eval_target_scanline = self.scanline + 1;
+        self.sprite_eval_active = true;
+    }
+
+    fn set_sprite_overflow_flag(&mut self) {
+        if (self.status & STATUS_SPRITE_OVERFLOW) == 0 {
+            self.debug.sprite_overflow_events = self.debug.sprite_overflow_events.wrapping_add(1);
+            self.debug.sprite_overflow_last_scanline = self.scanline;
+            self.debug.sprite_overflow_last_cycle = self.cycle;
+            self.sprite_capture.overflow = Some((self.scanline, self.cycle));
+        }
+        self.status |= STATUS_SPRITE_OVERFLOW;
+    }
+
+    fn sprite_match_scanline(byte: u8, scanline: i16, sprite_height: i16) -> bool {
+        let row = scanline - (byte as i16 + 1);
+        row >= 0 && row < sprite_height
+    }
+
+    fn clock_sprite_evaluation(&mut self, rendering_enabled: bool) {
+        if !self.sprite_eval_active {
+            return;
+        }
+        if !rendering_enabled {
+            self.sprite_eval_active = false;
+            return;
+        }
+        if ((self.cycle - 65) & 1) != 0 {
+            return;
+        }
+        if self.sprite_eval_n >= 64 {
+            self.sprite_eval_active = false;
+            return;
+        }
+
+        if self.sprite_eval_copy_remaining > 0 {
+            self.sprite_eval_copy_remaining = self.sprite_eval_copy_remaining.saturating_sub(1);
+            if self.sprite_eval_copy_remaining == 0 {
+                self.sprite_eval_n = self.sprite_eval_n.saturating_add(1);
+            }
+            return;
+        }
+
+        let n = self.sprite_eval_n as usize;
+        let m = self.sprite_eval_m as usize;
+        let byte = self.oam[n * 4 + m];
+        let sprite_height = if (self.ctrl & CTRL_SPRITE_SIZE_16) != 0 {
+            16
+        } else {
+            8
+        };
+        let in_range =
+            Self::sprite_match_scanline(byte, self.sprite_eval_target_scanline, sprite_height);
+
+        if !self.sprite_eval_bug_mode {
+            if in_range {
+                if self.sprite_eval_found < 8 {
+                    let slot = self.sprite_eval_found as usize;
+                    self.secondary_oam[slot * 4..slot * 4 + 4]
+                        .copy_from_slice(&self.oam[n * 4..n * 4 + 4]);
+                    self.secondary_indices[slot] = n as u8;
+                    self.sprite_eval_found = self.sprite_eval_found.saturating_add(1);
+                    self.sprite_eval_copy_remaining = 3;
+                    self.sprite_eval_m = 0;
+                    return;
+                }
+
+                self.set_sprite_overflow_flag();
+                self.sprite_eval_active = false;
+                return;
+            }
+
+            if self.sprite_eval_found < 8 {
+                self.sprite_eval_n = self.sprite_eval_n.saturating_add(1);
+                self.sprite_eval_m = 0;
+                return;
+            }
+
+            // After 8 sprites, hardware begins diagonal OAM scan (sprite overflow bug):
+            // m increments without carry with n, causing tile/attr/X bytes to be
+            // interpreted as Y for subsequent sprites.
+            self.sprite_eval_bug_mode = true;
+            self.sprite_eval_m = 1;
+            self.sprite_eval_n = self.sprite_eval_n.saturating_add(1);
+            if self.sprite_eval_n >= 64 {
+                self.sprite_eval_active = false;
+            }
+            return;
+        }
+
+        if in_range {
+            self.set_sprite_overflow_flag();
+            self.sprite_eval_active = false;
+            return;
+        }
+
+        self.sprite_eval_n = self.sprite_eval_n.saturating_add(1);
+        self.sprite_eval_m = (self.sprite_eval_m.wrapping_add(1)) & 0x03;
+        if self.sprite_eval_n >= 64 {
+            self.sprite_eval_active = false;
+        }
+    }
+
+    /// One dot of the sprite fetches on 257-320, which load the sprites
+    /// evaluation picked into the output units for the next line. Each of
+    /// the eight slots takes eight dots: two garbage nametable reads, then
+    /// the low and high pattern bytes. Empty slots fetch tile $FF and load
+    /// transparent pixels, as the PPU does, so mappers watching the pattern
+    /// bus (MMC3's A12) see the same addresses as on hardware.
+    fn fetch_sprite_slot(&mut self, visible_line: bool, mapper: &mut dyn Mapper) {
+        let slot = ((self.cycle - 257) / 8) as usize;
+        let phase = (self.cycle - 257) & 0x07;
+        if self.cycle == 257 {
+            // The pre-render line doesn't evaluate sprites, so line 0 has none.
+            self.sprite_count = if visible_line {
+                self.sprite_eval_found as usize
+            } else {
+                0
+            };
+            let next_line = (self.scanline + 1) as usize;
+            if visible_line && next_line < FRAME_HEIGHT {
+                self.sprite_capture.sprites[next_line] = self.secondary_indices;
+                self.sprite_capture.counts[next_line] = self.sprite_count as u8;
+            }
+        }
+        match phase {
+            0 | 2 => {
+                self.ppu_read(0x2000 | (self.v & 0x0FFF), mapper);
+            }
+            4 | 6 => {
+                let present = slot < self.sprite_count;
+                let [y, tile, attributes, x] = if present {
+                    let entry = &self.secondary_oam[slot * 4..];
+                    [entry[0], entry[1], entry[2], entry[3]]
+                } else {
+                    [0xFF; 4]
+                };
+                let target_line = if visible_line { self.scanline + 1 } else { 0 };
+                let addr = self.sprite_pattern_addr(y, tile, attributes, target_line);
+                let mut pattern = self.ppu_read(addr + if phase == 6 { 8 } else { 0 }, mapper);
+                if !present {
+                    pattern = 0;
+                } else if (attributes & 0x40) != 0 {
+                    pattern = pattern.reverse_bits();
+                }
+                if phase == 4 {
+                    self.sprite_patterns_lo[slot] = pattern;
+                } else {
+                    self.sprite_patterns_hi[slot] = pattern;
+                    self.sprite_x[slot] = if present { x } else { 0 };
+                    self.sprite_attributes[slot] = if present { attributes } else { 0 };
+                    self.sprite_indices[slot] = if present {
+                        self.secondary_indices[slot]
+                    } else {
+                        0
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Address of the low pattern byte of the sprite row `line` crosses.
+    /// Rows outside the sprite (empty slots) wrap, like the PPU's adder.
+    fn sprite_pattern_addr(&self, y: u8, tile: u8, attributes: u8, line: i16) -> u16 {
+        let tall = (self.ctrl & CTRL_SPRITE_SIZE_16) != 0;
+        let height: u16 = if tall { 16 } else { 8 };
+        let mut row = (line - (y as i16 + 1)) as u16 & (height - 1);
+        if (attributes & 0x80) != 0 {
+            row = height - 1 - row;
+        }
+        let (table, tile) = if tall {
+            (
+                u16::from(tile & 0x01) * 0x1000,
+                u16::from(tile & 0xFE) + row / 8,
+            )
+        } else if (self.ctrl & CTRL_SPRITE_TABLE) != 0 {
+            (0x1000, u16::from(tile))
+        } else {
+            (0x0000, u16::from(tile))
+        };
+        table + tile * 16 + (row & 0x07)
+    }
+
+    fn increment_vram_addr(&mut self) {
+        if (self.ctrl & CTRL_VRAM_INC_32) != 0 {
+            self.v = self.v.wrapping_add(32);
+        } else {
+            self.v = self.v.wrapping_add(1);
+        }
+    }
+
+    fn increment_vram_addr_cpu_access(&mut self) {
+        // $2007 accesses during rendering use the rendering increment path.
+        if self.rendering_enabled()
+            && ((0..240).contains(&self.scanline) || self.scanline == self.pre_render_scanline())
+        {
+            self.increment_coarse_x();
+            self.increment_y();
+        } else {
+            self.increment_vram_addr();
+        }
+    }
+
+    fn palette_color(&self, palette_index: u8) -> u8 {
+        let color = self.palette_ram[Self::palette_index(palette_index as u16)];
+        self.apply_greyscale(color)
+    }
+
+    fn apply_greyscale(&self, color: u8) -> u8 {
+        if (self.mask & MASK_GREYSCALE) != 0 {
+            color & 0x30
+        } else {
+            color & 0x3F
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mapper: &mut dyn Mapper) -> u8 {
+        let addr = addr & 0x3FFF;
+        self.debug.last_read_addr = addr;
+        let value = match addr {
+            0x0000..=0x1FFF => {
+                self.debug.pattern_reads = self.debug.pattern_reads.wrapping_add(1);
+                mapper.ppu_read(addr)
+            }
+            0x2000..=0x3EFF => {
+                self.debug.nametable_reads = self.debug.nametable_reads.wrapping_add(1);
+                let mirrored = 0x2000 + ((addr - 0x2000) % 0x1000);
+                if let Some(value) = mapper.ppu_nametable_read(mirrored, &self.vram) {
+                    value
+                } else {
+                    let index = self.mirrored_vram_index(mirrored);
+                    self.vram[index]
+                }
+            }
+            0x3F00..=0x3FFF => {
+                self.debug.palette_reads = self.debug.palette_reads.wrapping_add(1);
+                self.palette_ram[Self::palette_index(addr)] & 0x3F
+            }
+            _ => 0,
+        };
+
+        mapper.notify_ppu_read_addr(addr);
+        value
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mapper: &mut dyn Mapper) {
+        let addr = addr & 0x3FFF;
+        self.debug.last_write_addr = addr;
+        match addr {
+            0x0000..=0x1FFF => {
+                self.debug.pattern_writes = self.debug.pattern_writes.wrapping_add(1);
+                mapper.ppu_write(addr, value);
+            }
+            0x2000..=0x3EFF => {
+                self.debug.nametable_writes = self.debug.nametable_writes.wrapping_add(1);
+                let mirrored = 0x2000 + ((addr - 0x2000) % 0x1000);
+                if !mapper.ppu_nametable_write(mirrored, value, &mut self.vram) {
+                    let index = self.mirrored_vram_index(mirrored);
+                    self.vram[index] = value;
+                }
+            }
+            0x3F00..=0x3FFF => {
+                self.debug.palette_writes = self.debug.palette_writes.wrapping_add(1);
+                self.palette_ram[Self::palette_index(addr)] = value & 0x3F;
+            }
+            _ => {}
+        }
+
+        mapper.notify_ppu_write_addr(addr);
+    }
+
+    /// Palette RAM slot for a PPU address in $3F00-$3FFF (or a bare 5-bit
+    /// palette address). The 32 bytes repeat every $20, and the sprite
+    /// palettes' entry 0 ($3F10/$3F14/$3F18/$3F1C) is the same byte as the
+    /// matching background entry, for reads, writes and rendering alike.
+    fn palette_index(addr: u16) -> usize {
+        let index = (addr & 0x1F) as usize;
+        if index & 0x13 == 0x10 {
+            index & 0x0F
+        } else {
+            index
+        }
+    }
+
+    /// Called by the console whenever the mapper may have switched
+    /// mirroring, so nametable accesses don't have to ask it every time.
+    pub fn set_mirroring(&mut self, mirroring: Mirroring) {
+        if mirroring != self.mirroring {
+            self.mirroring = mirroring;
+            self.nametable_bases = Self::nametable_bases(mirroring);
+        }
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn nametable_bases(mirroring: Mirroring) -> [usize; 4] {
+        std::array::from_fn(|table| mirroring.vram_page(table) as usize * 0x400)
+    }
+
+    /// VRAM index of nametable address `addr` ($2000-$2FFF).
+    fn mirrored_vram_index(&self, addr: u16) -> usize {
+        let index = (addr & 0x0FFF) as usize;
+        self.nametable_bases[index >> 10] | (index & 0x03FF)
+    }
+
+    pub fn save_state(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        writer.write_all(&[self.ctrl, self.mask, self.status])?;
+        writer.write_all(&[self.oam_addr])?;
+        writer.write_all(&self.oam)?;
+        writer.write_all(&self.vram)?;
+        writer.write_all(&self.palette_ram)?;
+
+        let misc = (self.write_toggle as u8)
+            | ((self.ppuaddr_reload_pending as u8) << 1)
+            | ((self.ppuaddr_reload_delay) << 2);
+        writer.write_all(&[misc])?;
+
+        writer.write_all(&self.v.to_le_bytes())?;
+        writer.write_all(&self.t.to_le_bytes())?;
+        writer.write_all(&[self.fine_x, self.read_buffer, self.open_bus])?;
+        writer.write_all(&[self.open_bus_age, self.open_bus_decay_frames])?;
+
+        writer.write_all(&self.scanline.to_le_bytes())?;
+        writer.write_all(&self.cycle.to_le_bytes())?;
+        writer.write_all(&[
+            self.odd_frame as u8,
+            self.frame_complete as u8,
+            self.nmi_pending as u8,
+        ])?;
+        writer.write_all(&[self.nmi_delay, self.vblank_suppress as u8])?;
+
+        writer.write_all(&[
+            self.next_tile_id,
+            self.next_tile_attr,
+            self.next_tile_lsb,
+            self.next_tile_msb,
+        ])?;
+        writer.write_all(&self.bg_shift_pattern_lo.to_le_bytes())?;
+        writer.write_all(&self.bg_shift_pattern_hi.to_le_bytes())?;
+        writer.write_all(&self.bg_shift_attr_lo.to_le_bytes())?;
+        writer.write_all(&self.bg_shift_attr_hi.to_le_bytes())?;
+
+        writer.write_all(&(self.sprite_count as u8).to_le_bytes())?;
+        writer.write_all(&self.sprite_patterns_lo)?;
+        writer.write_all(&self.sprite_patterns_hi)?;
+        writer.write_all(&self.sprite_x)?;
+        writer.write_all(&self.sprite_attributes)?;
+        writer.write_all(&self.sprite_indices)?;
+
+        writer.write_all(&self.secondary_oam)?;
+        writer.write_all(&self.secondary_indices)?;
+        writer.write_all(&[
+            self.sprite_eval_active as u8,
+            self.sprite_eval_n,
+            self.sprite_eval_m,
+            self.sprite_eval_found,
+            self.sprite_eval_copy_remaining,
+            self.sprite_eval_bug_mode as u8,
+        ])?;
+        writer.write_all(&self.sprite_eval_target_scanline.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn load_state(&mut self, reader: &mut impl std::io::Read) -> std::io::Result<()> {
+        let mut buf = [0u8; 3];
+        reader.read_exact(&mut buf)?;
+        self.ctrl = buf[0];
+        self.mask = buf[1];
+        self.status = buf[2];
+
+        let mut oam_addr_buf = [0u8; 1];
+        reader.read_exact(&mut oam_addr_buf)?;
+        self.oam_addr = oam_addr_buf[0];
+
+        reader.read_exact(&mut self.oam)?;
+        reader.read_exact(&mut self.vram)?;
+        reader.read_exact(&mut self.palette_ram)?;
+
+        let mut misc = [0u8; 1];
+        reader.read_exact(&mut misc)?;
+        self.write_toggle = (misc[0] & 0x01) != 0;
+        self.ppuaddr_reload_pending = (misc[0] & 0x02) != 0;
+        self.ppuaddr_reload_delay = (misc[0] >> 2) & 0x3F;
+
+        let mut buf16 = [0u8; 2];
+        reader.read_exact(&mut buf16)?;
+        self.v = u16::from_le_bytes(buf16);
+        reader.read_exact(&mut buf16)?;
+        self.t = u16::from_le_bytes(buf16);
+
+        let mut buf3 = [0u8; 3];
+        reader.read_exact(&mut buf3)?;
+        self.fine_x = buf3[0];
+        self.read_buffer = buf3[1];
+        self.open_bus = buf3[2];
+        let mut decay = [0u8; 2];
+        reader.read_exact(&mut decay)?;
+        self.open_bus_age = decay[0];
+        self.open_bus_decay_frames = decay[1];
+
+        let mut buf_i16 = [0u8; 2];
+        reader.read_exact(&mut buf_i16)?;
+        self.scanline = i16::from_le_bytes(buf_i16);
+        reader.read_exact(&mut buf_i16)?;
+        self.cycle = i16::from_le_bytes(buf_i16);
+
+        let mut buf_bool = [0u8; 3];
+        reader.read_exact(&mut buf_bool)?;
+        self.odd_frame = buf_bool[0] != 0;
+        self.frame_complete = buf_bool[1] != 0;
+        self.nmi_pending = buf_bool[2] != 0;
+
+        let mut buf2 = [0u8; 2];
+        reader.read_exact(&mut buf2)?;
+        self.nmi_delay = buf2[0];
+        self.vblank_suppress = buf2[1] != 0;
+
+        let mut tile_buf = [0u8; 4];
+        reader.read_exact(&mut tile_buf)?;
+        self.next_tile_id = tile_buf[0];
+        self.next_tile_attr = tile_buf[1];
+        self.next_tile_lsb = tile_buf[2];
+        self.next_tile_msb = tile_buf[3];
+
+        reader.read_exact(&mut buf16)?;
+        self.bg_shift_pattern_lo = u16::from_le_bytes(buf16);
+        reader.read_exact(&mut buf16)?;
+        self.bg_shift_pattern_hi = u16::from_le_bytes(buf16);
+        reader.read_exact(&mut buf16)?;
+        self.bg_shift_attr_lo = u16::from_le_bytes(buf16);
+        reader.read_exact(&mut buf16)?;
+        self.bg_shift_attr_hi = u16::from_le_bytes(buf16);
+
+        let mut sprite_count_buf = [0u8; 1];
+        reader.read_exact(&mut sprite_count_buf)?;
+        self.sprite_count = (sprite_count_buf[0] as usize).min(8);
+
+        reader.read_exact(&mut self.sprite_patterns_lo)?;
+        reader.read_exact(&mut self.sprite_patterns_hi)?;
+        reader.read_exact(&mut self.sprite_x)?;
+        reader.read_exact(&mut self.sprite_attributes)?;
+        reader.read_exact(&mut self.sprite_indices)?;
+
+        reader.read_exact(&mut self.secondary_oam)?;
+        reader.read_exact(&mut self.secondary_indices)?;
+        let mut sprite_eval = [0u8; 6];
+        reader.read_exact(&mut sprite_eval)?;
+        self.sprite_eval_active = sprite_eval[0] != 0;
+        self.sprite_eval_n = sprite_eval[1];
+        self.sprite_eval_m = sprite_eval[2];
+        self.sprite_eval_found = sprite_eval[3].min(8);
+        self.sprite_eval_copy_remaining = sprite_eval[4];
+        self.sprite_eval_bug_mode = sprite_eval[5] != 0;
+
+        reader.read_exact(&mut buf_i16)?;
+        self.sprite_eval_target_scanline = i16::from_le_bytes(buf_i16);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::cartridge::Cartridge;
+    use crate::nes::mapper::create_mapper;
+
+    #[test]
+    fn indexed_frame_keeps_color_index_and_emphasis_bits() {
+        let mut ppu = Ppu::new();
+        ppu.palette_ram[0] = 0x16;
+        let pixel = 5 * FRAME_WIDTH + 3;
+        let rgb = |ppu: &Ppu| ppu.frame_buffer()[pixel * 4..pixel * 4 + 3].to_vec();
+
+        // Rendering off shows the backdrop, here with red and blue emphasis.
+        ppu.mask = 0xA0;
+        ppu.render_pixel(3, 5);
+        let indexed = ppu.frame_buffer_indexed()[pixel];
+        assert_eq!(indexed, 0x16 | (0b101 << 6));
+        assert_eq!(rgb(&ppu), ppu.rgb_palette[usize::from(indexed)]);
+
+        // Greyscale keeps only the brightness column, in both buffers.
+        ppu.mask = MASK_GREYSCALE | 0x40;
+        ppu.render_pixel(3, 5);
+        let indexed = ppu.frame_buffer_indexed()[pixel];
+        assert_eq!(indexed, 0x10 | (0b010 << 6));
+        assert_eq!(rgb(&ppu), ppu.rgb_palette[usize::from(indexed)]);
+    }
+
+    /// Every pixel is color 1.
+    const SOLID_TILE: u8 = 1;
+    /// Every pixel is color 2.
+    const SOLID_TILE_HI: u8 = 2;
+    /// Only the rightmost column is opaque (color 1).
+    const RIGHT_EDGE_TILE: u8 = 3;
+
+    /// NROM board with the test tiles in CHR, a blank nametable and all
+    /// sprites parked off screen.
+    fn test_board() -> (Ppu, Box<dyn Mapper>) {
+        let mut chr = vec![0u8; 0x2000];
+        let solid = SOLID_TILE as usize * 16;
+        chr[solid..solid + 8].fill(0xFF);
+        let solid_hi = SOLID_TILE_HI as usize * 16 + 8;
+        chr[solid_hi..solid_hi + 8].fill(0xFF);
+        let edge = RIGHT_EDGE_TILE as usize * 16;
+        chr[edge..edge + 8].fill(0x01);
+        let mapper = create_mapper(Cartridge {
+            mapper_id: 0,
+            submapper_id: 0,
+            mirroring: Mirroring::Horizontal,
+            four_screen: false,
+            has_battery_backed_ram: false,
+            prg_rom: vec![0; 0x4000],
+            chr_data: chr,
+            chr_is_ram: false,
+            prg_ram_size: 0,
+            region: None,
+        })
+        .unwrap();
+
+        let mut ppu = Ppu::new();
+        ppu.oam.fill(0xFF);
+        (ppu, mapper)
+    }
+
+    fn solid_background() -> (Ppu, Box<dyn Mapper>) {
+        let (mut ppu, mut mapper) = test_board();
+        for addr in 0x2000..0x23C0 {
+            ppu.ppu_write(addr, SOLID_TILE, mapper.as_mut());
+        }
+        (ppu, mapper)
+    }
+
+    /// Tile columns alternate between color 1 and color 2, and the attribute
+    /// palette alternates between 1 and 2 every 16 pixels.
+    fn striped_background() -> (Ppu, Box<dyn Mapper>) {
+        let (mut ppu, mut mapper) = test_board();
+        for addr in 0x2000..0x23C0u16 {
+            let tile = if addr % 2 == 0 {
+                SOLID_TILE
+            } else {
+                SOLID_TILE_HI
+            };
+            ppu.ppu_write(addr, tile, mapper.as_mut());
+        }
+        for addr in 0x23C0..0x2400 {
+            ppu.ppu_write(addr, 0b1001_1001, mapper.as_mut());
+        }
+        (ppu, mapper)
+    }
+
+    /// Palette address the striped background shows at screen `x` when
+    /// scrolled by `fine_x` pixels.
+    fn striped_palette_addr(x: usize, fine_x: u8) -> u8 {
+        let world_x = x + fine_x as usize;
+        let pixel = if (world_x / 8).is_multiple_of(2) {
+            1
+        } else {
+            2
+        };
+        let palette = if (world_x / 16).is_multiple_of(2) {
+            1
+        } else {
+            2
+        };
+        (palette << 2) | pixel
+    }
+
+    fn set_fine_x(ppu: &mut Ppu, mapper: &mut dyn Mapper, fine_x: u8) {
+        ppu.cpu_write_register(0x2005, fine_x, mapper);
+        ppu.cpu_write_register(0x2005, 0, mapper);
+    }
+
+    fn scanline_palette_addrs(ppu: &Ppu, y: usize) -> Vec<u8> {
+        (0..FRAME_WIDTH)
+            .map(|x| ppu.pixel_source(x, y).unwrap().palette_addr)
+            .collect()
+    }
+
+    fn run_to(ppu: &mut Ppu, mapper: &mut dyn Mapper, scanline: i16, cycle: i16) {
+        for _ in 0..341 * 312 {
+            if ppu.debug_scanline_cycle() == (scanline, cycle) {
+                return;
+            }
+            ppu.tick(mapper);
+        }
+        panic!("PPU never reached scanline {scanline} cycle {cycle}");
+    }
+
+    /// Renders scanline 10 with a solid sprite 0 at `sprite_x` and returns the
+    /// dot the hit was flagged on, if any.
+    fn sprite0_hit_dot(sprite_x: u8, mask: u8) -> Option<i16> {
+        let (mut ppu, mut mapper) = solid_background();
+        ppu.oam[0..4].copy_from_slice(&[9, SOLID_TILE, 0, sprite_x]);
+        ppu.cpu_write_register(0x2001, mask, mapper.as_mut());
+        run_to(&mut ppu, mapper.as_mut(), 11, 0);
+
+        let debug = ppu.debug_counters();
+        if (ppu.status & STATUS_SPRITE_ZERO_HIT) == 0 {
+            return None;
+        }
+        assert_eq!(debug.sprite0_hit_last_scanline, 10);
+        Some(debug.sprite0_hit_last_cycle)
+    }
+
+    const SHOW_ALL: u8 =
+        MASK_SHOW_BG | MASK_SHOW_SPRITES | MASK_SHOW_BG_LEFT | MASK_SHOW_SPRITE_LEFT;
+
+    #[test]
+    fn sprite0_hit_lands_on_first_overlapping_dot() {
+        // Pixel x is drawn on dot x + 1.
+        assert_eq!(sprite0_hit_dot(40, SHOW_ALL), Some(41));
+        assert_eq!(sprite0_hit_dot(0, SHOW_ALL), Some(1));
+    }
+
+    #[test]
+    fn sprite0_hit_never_fires_at_x_255() {
+        assert_eq!(sprite0_hit_dot(255, SHOW_ALL), None);
+        assert_eq!(sprite0_hit_dot(254, SHOW_ALL), Some(255));
+    }
+
+    #[test]
+    fn sprite0_hit_respects_left_column_clipping() {
+        let no_sprite_left = SHOW_ALL & !MASK_SHOW_SPRITE_LEFT;
+        let no_bg_left = SHOW_ALL & !MASK_SHOW_BG_LEFT;
+        assert_eq!(sprite0_hit_dot(0, no_sprite_left), None);
+        assert_eq!(sprite0_hit_dot(4, no_sprite_left), Some(9));
+        assert_eq!(sprite0_hit_dot(4, no_bg_left), Some(9));
+    }
+
+    #[test]
+    fn sprite0_hit_needs_both_layers_enabled() {
+        assert_eq!(
+            sprite0_hit_dot(40, MASK_SHOW_SPRITES | MASK_SHOW_SPRITE_LEFT),
+            None
+        );
+        assert_eq!(sprite0_hit_dot(40, MASK_SHOW_BG | MASK_SHOW_BG_LEFT), None);
+    }
+
+    #[test]
+    fn skipped_composition_still_detects_sprite0_hit() {
+        let (mut ppu, mut mapper) = solid_background();
+        ppu.oam[0..4].copy_from_slice(&[9, SOLID_TILE, 0, 40]);
+        ppu.cpu_write_register(0x2001, SHOW_ALL, mapper.as_mut());
+        ppu.set_skip_composition(true);
+        run_to(&mut ppu, mapper.as_mut(), 11, 0);
+
+        assert_ne!(ppu.status & STATUS_SPRITE_ZERO_HIT, 0);
+        assert!(ppu.frame_buffer_indexed().iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn sprite_capture_records_first_eight_sprites_and_overflow() {
+        let (mut ppu, mut mapper) = solid_background();
+        for sprite in 0..10u8 {
+            let base = sprite as usize * 4;
+            ppu.oam[base..base + 4].copy_from_slice(&[19, SOLID_TILE, 0, sprite * 16]);
+        }
+        ppu.cpu_write_register(0x2001, SHOW_ALL, mapper.as_mut());
+        run_to(&mut ppu, mapper.as_mut(), 30, 0);
+
+        let capture = ppu.sprite_capture();
+        assert_eq!(capture.line(20), &[0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(capture.line(19).is_empty());
+        let (overflow_line, _) = capture.overflow.expect("overflow not captured");
+        assert_eq!(overflow_line, 19);
+        assert!(capture.sprite0_hit.is_some_and(|(line, _)| line == 20));
+    }
+
+    #[test]
+    fn fine_x_scroll_shifts_pattern_and_attribute_together() {
+        for fine_x in 0..8 {
+            let (mut ppu, mut mapper) = striped_background();
+            set_fine_x(&mut ppu, mapper.as_mut(), fine_x);
+            ppu.cpu_write_register(0x2001, SHOW_ALL, mapper.as_mut());
+            run_to(&mut ppu, mapper.as_mut(), 11, 0);
+
+            let expected: Vec<u8> = (0..FRAME_WIDTH)
+                .map(|x| striped_palette_addr(x, fine_x))
+                .collect();
+            assert_eq!(
+                scanline_palette_addrs(&ppu, 10),
+                expected,
+                "fine_x={fine_x}"
+            );
+        }
+    }
+
+    #[test]
+    fn left_column_bg_clip_keeps_shifters_in_step() {
+        for fine_x in 0..8 {
+            let (mut ppu, mut mapper) = striped_background();
+            set_fine_x(&mut ppu, mapper.as_mut(), fine_x);
+            ppu.cpu_write_register(0x2001, SHOW_ALL & !MASK_SHOW_BG_LEFT, mapper.as_mut());
+            run_to(&mut ppu, mapper.as_mut(), 11, 0);
+
+            let expected: Vec<u8> = (0..FRAME_WIDTH)
+                .map(|x| {
+                    if x < 8 {
+                        0
+                    } else {
+                        striped_palette_addr(x, fine_x)
+                    }
+                })
+                .collect();
+            assert_eq!(
+                scanline_palette_addrs(&ppu, 10),
+                expected,
+                "fine_x={fine_x}"
+            );
+        }
+    }
+
+    #[test]
+    fn sprite0_hit_in_left_column_with_fine_x() {
+        for fine_x in 0..8 {
+            let (mut ppu, mut mapper) = solid_background();
+            set_fine_x(&mut ppu, mapper.as_mut(), fine_x);
+            ppu.oam[0..4].copy_from_slice(&[9, SOLID_TILE, 0, 2]);
+            ppu.cpu_write_register(0x2001, SHOW_ALL, mapper.as_mut());
+            run_to(&mut ppu, mapper.as_mut(), 11, 0);
+            assert_eq!(
+                ppu.debug_counters().sprite0_hit_last_cycle,
+                3,
+                "fine_x={fine_x}"
+            );
+        }
+    }
+
+    #[test]
+    fn sprite0_hit_ignores_opaque_pixels_under_left_clip() {
+        let hit_dot = |mask: u8| {
+            let (mut ppu, mut mapper) = solid_background();
+            ppu.oam[0..4].copy_from_slice(&[9, RIGHT_EDGE_TILE, 0, 0]);
+            ppu.cpu_write_register(0x2001, mask, mapper.as_mut());
+            run_to(&mut ppu, mapper.as_mut(), 11, 0);
+            ((ppu.status & STATUS_SPRITE_ZERO_HIT) != 0)
+                .then(|| ppu.debug_counters().sprite0_hit_last_cycle)
+        };
+
+        // The sprite's only opaque pixel sits at x=7, the last clipped column.
+        assert_eq!(hit_dot(SHOW_ALL), Some(8));
+        assert_eq!(hit_dot(SHOW_ALL & !MASK_SHOW_BG_LEFT), None);
+        assert_eq!(hit_dot(SHOW_ALL & !MASK_SHOW_SPRITE_LEFT), None);
+    }
+
+    fn dots_until_next_frame(ppu: &mut Ppu, mapper: &mut dyn Mapper) -> u32 {
+        run_to(ppu, mapper, 0, 0);
+        ppu.tick(mapper);
+        let mut dots = 1;
+        while ppu.debug_scanline_cycle() != (0, 0) {
+            ppu.tick(mapper);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn frame_length_follows_region() {
+        let cases = [
+            (Region::Ntsc, [341 * 262 - 1, 341 * 262]),
+            (Region::Pal, [341 * 312, 341 * 312]),
+            (Region::Dendy, [341 * 312, 341 * 312]),
+        ];
+        for (region, expected) in cases {
+            let (mut ppu, mut mapper) = solid_background();
+            ppu.set_region(region);
+            ppu.reset();
+            ppu.cpu_write_register(0x2001, SHOW_ALL, mapper.as_mut());
+            let mut lengths = [
+                dots_until_next_frame(&mut ppu, mapper.as_mut()),
+                dots_until_next_frame(&mut ppu, mapper.as_mut()),
+            ];
+            lengths.sort_unstable();
+            assert_eq!(lengths, expected, "{region:?}");
+        }
+    }
+
+    #[test]
+    fn dendy_sets_vblank_fifty_lines_late() {
+        let (mut ppu, mut mapper) = test_board();
+        ppu.set_region(Region::Dendy);
+        ppu.reset();
+        run_to(&mut ppu, mapper.as_mut(), 0, 0);
+        run_to(&mut ppu, mapper.as_mut(), 241, 2);
+        assert_eq!(ppu.status & STATUS_VBLANK, 0);
+        run_to(&mut ppu, mapper.as_mut(), 291, 1);
+        assert_eq!(ppu.status & STATUS_VBLANK, 0);
+        ppu.tick(mapper.as_mut());
+        assert_ne!(ppu.status & STATUS_VBLANK, 0);
+    }
+
+    /// Points v at `addr`, giving the delayed t -> v copy its dot.
+    fn set_vram_addr(ppu: &mut Ppu, mapper: &mut dyn Mapper, addr: u16) {
+        let [hi, lo] = addr.to_be_bytes();
+        ppu.cpu_write_register(0x2006, hi, mapper);
+        ppu.cpu_write_register(0x2006, lo, mapper);
+        ppu.tick(mapper);
+    }
+
+    fn palette_poke(ppu: &mut Ppu, mapper: &mut dyn Mapper, addr: u16, value: u8) {
+        set_vram_addr(ppu, mapper, addr);
+        ppu.cpu_write_register(0x2007, value, mapper);
+    }
+
+    fn palette_peek(ppu: &mut Ppu, mapper: &mut dyn Mapper, addr: u16) -> u8 {
+        set_vram_addr(ppu, mapper, addr);
+        ppu.cpu_read_register(0x2007, mapper)
+    }
+
+    /// The checks blargg's palette_ram ROM makes, through $2006/$2007 with
+    /// rendering off.
+    #[test]
+    fn palette_backdrop_mirrors_apply_to_reads_and_writes() {
+        let (mut ppu, mut mapper) = test_board();
+        let mapper = mapper.as_mut();
+        for (i, addr) in (0x3F00..0x3F20).enumerate() {
+            palette_poke(&mut ppu, mapper, addr, i as u8);
+        }
+        for (sprite, background) in [
+            (0x3F10, 0x3F00),
+            (0x3F14, 0x3F04),
+            (0x3F18, 0x3F08),
+            (0x3F1C, 0x3F0C),
+        ] {
+            assert_eq!(
+                palette_peek(&mut ppu, mapper, background),
+                (sprite & 0x1F) as u8
+            );
+            palette_poke(&mut ppu, mapper, background, 0x2A);
+            assert_eq!(palette_peek(&mut ppu, mapper, sprite), 0x2A);
+        }
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F11), 0x11);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F01), 0x01);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3FE5) & 0x3F, 0x05);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3FF0) & 0x3F, 0x2A);
+    }
+
+    #[test]
+    fn debug_palette_pokes_follow_the_mirrors() {
+        let (mut ppu, mut mapper) = test_board();
+        let mapper = mapper.as_mut();
+        ppu.debug_poke_palette(0x10, 0xE1);
+        ppu.debug_poke_palette(0x05, 0x16);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F00), 0x21);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F05), 0x16);
+    }
+
+    #[test]
+    fn palette_reads_are_six_bits_and_greyscaled() {
+        let (mut ppu, mut mapper) = test_board();
+        let mapper = mapper.as_mut();
+        palette_poke(&mut ppu, mapper, 0x3F03, 0xFD);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F03) & 0x3F, 0x3D);
+        // The top two bits are whatever was last on the bus ($2006 low byte).
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F03) & 0xC0, 0x00);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0xFFC3) & 0xC0, 0xC0);
+
+        ppu.cpu_write_register(0x2001, MASK_GREYSCALE, mapper);
+        assert_eq!(palette_peek(&mut ppu, mapper, 0x3F03) & 0x3F, 0x30);
+        assert_eq!(ppu.palette_color(0x03), 0x30);
+    }
+
+    #[test]
+    fn nametable_mirroring_follows_set_mirroring() {
+        let (mut ppu, mut mapper) = test_board();
+        let mapper = mapper.as_mut();
+        ppu.ppu_write(0x2005, 0x11, mapper);
+        ppu.ppu_write(0x2805, 0x22, mapper);
+        assert_eq!(ppu.ppu_read(0x2405, mapper), 0x11);
+        assert_eq!(ppu.ppu_read(0x2C05, mapper), 0x22);
+
+        ppu.set_mirroring(Mirroring::Vertical);
+        assert_eq!(ppu.ppu_read(0x2805, mapper), 0x11);
+        assert_eq!(ppu.ppu_read(0x2405, mapper), 0x22);
+        assert_eq!(ppu.ppu_read(0x3C05, mapper), 0x22);
+    }
+
+    /// Wraps a board and records every address the PPU puts on its bus.
+    struct BusLog {
+        board: Box<dyn Mapper>,
+        reads: Vec<u16>,
+    }
+
+    impl Mapper for BusLog {
+        fn cpu_read(&mut self, addr: u16) -> u8 {
+            self.board.cpu_read(addr)
+        }
+        fn cpu_write(&mut self, addr: u16, value: u8) {
+            self.board.cpu_write(addr, value);
+        }
+        fn ppu_read(&mut self, addr: u16) -> u8 {
+            self.board.ppu_read(addr)
+        }
+        fn ppu_write(&mut self, addr: u16, value: u8) {
+            self.board.ppu_write(addr, value);
+        }
+        fn mirroring(&self) -> Mirroring {
+            self.board.mirroring()
+        }
+        fn save_state(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+            self.board.save_state(writer)
+        }
+        fn load_state(&mut self, reader: &mut dyn std::io::Read) -> std::io::Result<()> {
+            self.board.load_state(reader)
+        }
+        fn notify_ppu_read_addr(&mut self, addr: u16) {
+            self.reads.push(addr);
+        }
+    }
+
+    #[test]
+    fn sprites_are_fetched_on_dots_257_to_320() {
+        let (mut ppu, board) = test_board();
+        let mut bus = BusLog {
+            board,
+            reads: Vec::new(),
+        };
+        // One flipped sprite whose row 3 is on line 11.
+        ppu.oam[8..12].copy_from_slice(&[7, SOLID_TILE, 0xC0, 40]);
+        ppu.cpu_write_register(0x2001, SHOW_ALL, &mut bus);
+        run_to(&mut ppu, &mut bus, 10, 257);
+        bus.reads.clear();
+        run_to(&mut ppu, &mut bus, 10, 321);
+
+        let mut expected = Vec::new();
+        for slot in 0..8 {
+            // Vertical flip: line 11 is row 3, fetched from row 4. Empty
+            // slots are Y, tile and attributes $FF, flip included.
+            let pattern = if slot == 0 {
+                SOLID_TILE as u16 * 16 + 4
+            } else {
+                0x0FF4
+            };
+            expected.extend([pattern, pattern + 8]);
+        }
+        let fetched: Vec<u16> = bus
+            .reads
+            .iter()
+            .copied()
+            .filter(|&addr| addr < 0x2000)
+            .collect();
+        assert_eq!(fetched, expected);
+        assert_eq!(bus.reads.len(), 32);
+        assert!(
+            bus.reads
+                .chunks(4)
+                .all(|slot| slot[0] >= 0x2000 && slot[1] >= 0x2000)
+        );
+
+        assert_eq!(ppu.sprite_count, 1);
+        assert_eq!(ppu.sprite_indices[0], 2);
+        assert_eq!(ppu.sprite_x[0], 40);
+        assert_eq!(ppu.sprite_patterns_lo[0], 0xFF);
+        assert_eq!(ppu.sprite_patterns_lo[1..], [0; 7]);
+    }
+}
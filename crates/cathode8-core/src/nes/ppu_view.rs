@@ -0,0 +1,322 @@
+//! RGBA renderings of PPU memory for debugger views: pattern tables,
+//! nametables, OAM and palette RAM, plus a sheet of every CHR bank that can
+//! be edited and imported back into CHR RAM.
+//!
+//! Everything is read through the debug peeks, so rendering a view never
+//! disturbs the running machine. Colors come from the active RGB palette
+//! without emphasis, so the images match what the palette RAM holds rather
+//! than one frame's tint.
+
+use anyhow::{Result, bail};
+
+use super::Nes;
+
+const TILE_SIZE: usize = 8;
+const TILE_BYTES: usize = 16;
+/// Tiles per row of the CHR sheet, as in the pattern table view.
+const SHEET_COLUMNS: usize = 16;
+
+/// An RGBA8 image, rows top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PpuImage {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl PpuImage {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            rgba: vec![0; width * height * 4],
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        let offset = (y * self.width + x) * 4;
+        self.rgba[offset..offset + 3].copy_from_slice(&rgb);
+        self.rgba[offset + 3] = 0xFF;
+    }
+}
+
+impl Nes {
+    /// Both pattern tables side by side (256x128), colored with palette
+    /// `palette` (0-3 background, 4-7 sprite).
+    pub fn render_pattern_tables(&self, palette: u8) -> PpuImage {
+        let mut image = PpuImage::new(256, 128);
+        for table in 0..2u16 {
+            for tile in 0..256u16 {
+                let x = table as usize * 128 + (tile as usize % 16) * TILE_SIZE;
+                let y = (tile as usize / 16) * TILE_SIZE;
+                self.draw_tile(&mut image, table * 0x1000 + tile * 16, palette, x, y);
+            }
+        }
+        image
+    }
+
+    /// The four logical nametables as one 512x480 image, each read from
+    /// wherever the cartridge maps it right now, with the background pattern
+    /// table selected in $2000.
+    pub fn render_nametables(&self) -> PpuImage {
+        let mut image = PpuImage::new(512, 480);
+        let pattern_base = if self.ppu.debug_ctrl() & 0x10 != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+
+        for table in 0..4u16 {
+            let base = 0x2000 + table * 0x400;
+            let origin_x = (table as usize % 2) * 256;
+            let origin_y = (table as usize / 2) * 240;
+            for row in 0..30u16 {
+                for col in 0..32u16 {
+                    let tile = self.debug_peek_nametable(base + row * 32 + col) as u16;
+                    let attribute =
+                        self.debug_peek_nametable(base + 0x3C0 + (row / 4) * 8 + col / 4);
+                    let shift = ((row & 0x02) << 1) | (col & 0x02);
+                    let palette = (attribute >> shift) & 0x03;
+                    self.draw_tile(
+                        &mut image,
+                        pattern_base + tile * 16,
+                        palette,
+                        origin_x + col as usize * TILE_SIZE,
+                        origin_y + row as usize * TILE_SIZE,
+                    );
+                }
+            }
+        }
+        image
+    }
+
+    /// The 64 OAM entries in an 8x8 grid of 8x16 cells, in OAM order. 8x8
+    /// sprites use the top half of their cell; transparent pixels stay
+    /// transparent. Flip bits are applied.
+    pub fn render_oam(&self) -> PpuImage {
+        let mut image = PpuImage::new(64, 128);
+        let ctrl = self.ppu.debug_ctrl();
+        let tall = ctrl & 0x20 != 0;
+
+        for sprite in 0..64usize {
+            let tile = self.debug_peek_oam(sprite * 4 + 1) as u16;
+            let attributes = self.debug_peek_oam(sprite * 4 + 2);
+            let palette = 4 + (attributes & 0x03);
+            let flip_h = attributes & 0x40 != 0;
+            let flip_v = attributes & 0x80 != 0;
+            let x = (sprite % 8) * TILE_SIZE;
+            let y = (sprite / 8) * TILE_SIZE * 2;
+
+            let halves: Vec<u16> = if tall {
+                let table = (tile & 0x01) * 0x1000;
+                let top = table + (tile & 0xFE) * 16;
+                if flip_v {
+                    vec![top + 16, top]
+                } else {
+                    vec![top, top + 16]
+                }
+            } else {
+                let table = if ctrl & 0x08 != 0 { 0x1000 } else { 0x0000 };
+                vec![table + tile * 16]
+            };
+            for (half, addr) in halves.into_iter().enumerate() {
+                for row in 0..TILE_SIZE {
+                    let src_row = if flip_v { 7 - row } else { row };
+                    let pixels = self.tile_row(addr, src_row);
+                    for col in 0..TILE_SIZE {
+                        let pixel = pixels[if flip_h { 7 - col } else { col }];
+                        if pixel != 0 {
+                            let rgb = self.palette_rgb(palette, pixel);
+                            image.put(x + col, y + half * TILE_SIZE + row, rgb);
+                        }
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// Palette RAM as two rows of 16 swatches (background, then sprites),
+    /// each 16x16 pixels.
+    pub fn render_palette(&self) -> PpuImage {
+        const SWATCH: usize = 16;
+        let mut image = PpuImage::new(16 * SWATCH, 2 * SWATCH);
+        for entry in 0..32usize {
+            // $3F10/$3F14/$3F18/$3F1C mirror the background entries.
+            let stored = if entry >= 16 && entry % 4 == 0 {
+                entry - 16
+            } else {
+                entry
+            };
+            let rgb = self.color_rgb(self.debug_peek_palette(stored));
+            let origin_x = (entry % 16) * SWATCH;
+            let origin_y = (entry / 16) * SWATCH;
+            for y in 0..SWATCH {
+                for x in 0..SWATCH {
+                    image.put(origin_x + x, origin_y + y, rgb);
+                }
+            }
+        }
+        image
+    }
+
+    /// All of CHR ROM/RAM, not just the banks mapped in now, as a sheet 16
+    /// tiles (128 pixels) wide and colored with `palette`.
+    pub fn render_chr_sheet(&self, palette: u8) -> PpuImage {
+        let chr = self.chr_data();
+        let tiles = chr.len() / TILE_BYTES;
+        let mut image = PpuImage::new(
+            SHEET_COLUMNS * TILE_SIZE,
+            tiles.div_ceil(SHEET_COLUMNS) * TILE_SIZE,
+        );
+        for (tile, data) in chr.chunks_exact(TILE_BYTES).enumerate() {
+            let x = (tile % SHEET_COLUMNS) * TILE_SIZE;
+            let y = (tile / SHEET_COLUMNS) * TILE_SIZE;
+            for row in 0..TILE_SIZE {
+                for (col, pixel) in decode_row(data[row], data[row + 8]).into_iter().enumerate() {
+                    image.put(x + col, y + row, self.palette_rgb(palette, pixel));
+                }
+            }
+        }
+        image
+    }
+
+    /// Reads a sheet laid out like [`Nes::render_chr_sheet`] back into CHR
+    /// RAM. Each pixel becomes the entry of `palette` nearest its color, so
+    /// edits should stick to the four colors the sheet was exported with.
+    pub fn import_chr_sheet(&mut self, image: &PpuImage, palette: u8) -> Result<()> {
+        let tiles = self.chr_data().len() / TILE_BYTES;
+        let expected = (
+            SHEET_COLUMNS * TILE_SIZE,
+            tiles.div_ceil(SHEET_COLUMNS) * TILE_SIZE,
+        );
+        if (image.width, image.height) != expected {
+            bail!(
+                "sheet is {}x{}, this cartridge's CHR needs {}x{}",
+                image.width,
+                image.height,
+                expected.0,
+                expected.1
+            );
+        }
+        let colors: [[u8; 3]; 4] =
+            std::array::from_fn(|pixel| self.palette_rgb(palette, pixel as u8));
+        let mut chr = vec![0u8; tiles * TILE_BYTES];
+        for (tile, data) in chr.chunks_exact_mut(TILE_BYTES).enumerate() {
+            let x = (tile % SHEET_COLUMNS) * TILE_SIZE;
+            let y = (tile / SHEET_COLUMNS) * TILE_SIZE;
+            for row in 0..TILE_SIZE {
+                for col in 0..TILE_SIZE {
+                    let offset = ((y + row) * image.width + x + col) * 4;
+                    let pixel = nearest_color(&colors, &image.rgba[offset..offset + 3]);
+                    let bit = 7 - col;
+                    data[row] |= (pixel & 0x01) << bit;
+                    data[row + 8] |= (pixel >> 1) << bit;
+                }
+            }
+        }
+        self.write_chr_ram(0, &chr)
+    }
+
+    fn draw_tile(&self, image: &mut PpuImage, addr: u16, palette: u8, x: usize, y: usize) {
+        for row in 0..TILE_SIZE {
+            let pixels = self.tile_row(addr, row);
+            for (col, &pixel) in pixels.iter().enumerate() {
+                image.put(x + col, y + row, self.palette_rgb(palette, pixel));
+            }
+        }
+    }
+
+    /// 2-bit pixel values of one row of the tile at pattern address `addr`.
+    fn tile_row(&self, addr: u16, row: usize) -> [u8; 8] {
+        let lo = self.debug_peek_chr(addr + row as u16);
+        let hi = self.debug_peek_chr(addr + row as u16 + 8);
+        decode_row(lo, hi)
+    }
+
+    /// Color of 2-bit `pixel` in `palette`; pixel 0 is the shared backdrop.
+    fn palette_rgb(&self, palette: u8, pixel: u8) -> [u8; 3] {
+        let entry = if pixel == 0 {
+            0
+        } else {
+            (palette as usize & 0x07) * 4 + pixel as usize
+        };
+        self.color_rgb(self.debug_peek_palette(entry))
+    }
+
+    /// RGB of NES color `color` ($00-$3F) under the current palette.
+    pub fn color_rgb(&self, color: u8) -> [u8; 3] {
+        self.ppu.rgb_palette()[(color & 0x3F) as usize]
+    }
+}
+
+/// 2-bit pixels of one tile row from its two bit planes.
+fn decode_row(lo: u8, hi: u8) -> [u8; 8] {
+    std::array::from_fn(|col| {
+        let bit = 7 - col;
+        (((hi >> bit) & 0x01) << 1) | ((lo >> bit) & 0x01)
+    })
+}
+
+/// Index of the color in `colors` closest to `rgb`. Palettes often repeat a
+/// color; the first match wins.
+fn nearest_color(colors: &[[u8; 3]; 4], rgb: &[u8]) -> u8 {
+    let distance = |color: &[u8; 3]| -> u32 {
+        color
+            .iter()
+            .zip(rgb)
+            .map(|(&a, &b)| (a as i32 - b as i32).unsigned_abs().pow(2))
+            .sum()
+    };
+    (0..4u8)
+        .min_by_key(|&index| distance(&colors[index as usize]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nametable_view_follows_mmc5_exram_and_fill_mode() {
+        // MMC5 with solid (color 1) tiles at $42 and $33 in CHR ROM.
+        let mut rom = vec![0u8; 16 + 0x8000 + 0x2000];
+        rom[..8].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 2, 1, 0x50, 0x00]);
+        let chr = 16 + 0x8000;
+        for tile in [0x42, 0x33] {
+            rom[chr + tile * TILE_BYTES..][..8].fill(0xFF);
+        }
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        for (index, color) in [(0, 0x0F), (1, 0x16), (9, 0x2A)] {
+            nes.debug_poke_palette(index, color);
+        }
+
+        // Tables: CIRAM 0, ExRAM, fill mode, CIRAM 1.
+        for (addr, value) in [
+            (0x5104, 0x00),
+            (0x5105, 0x78),
+            (0x5106, 0x33),
+            (0x5107, 0x02),
+        ] {
+            nes.debug_poke_cpu(addr, value).unwrap();
+        }
+        nes.debug_poke_cpu(0x5C00, 0x42).unwrap();
+        assert_eq!(nes.debug_peek_nametable(0x2400), 0x42);
+        assert_eq!(nes.debug_peek_nametable(0x2800), 0x33);
+        assert_eq!(nes.debug_peek_nametable(0x2BC0), 0xAA);
+
+        let image = nes.render_nametables();
+        let pixel = |x: usize, y: usize| {
+            let offset = (y * image.width + x) * 4;
+            [
+                image.rgba[offset],
+                image.rgba[offset + 1],
+                image.rgba[offset + 2],
+            ]
+        };
+        assert_eq!(pixel(0, 0), nes.palette_rgb(0, 0));
+        assert_eq!(pixel(256, 0), nes.palette_rgb(0, 1));
+        assert_eq!(pixel(0, 240), nes.palette_rgb(2, 1));
+    }
+}
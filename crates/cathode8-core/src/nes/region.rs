@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+/// Console timing variant.
+///
+/// All three share the same master-clock driven PPU dot, but differ in how
+/// the CPU clock is divided from it, how many scanlines a frame has and where
+/// vblank starts:
+///
+/// | Region | CPU : PPU | Scanlines | Vblank line | Odd-frame skip |
+/// |--------|-----------|-----------|-------------|----------------|
+/// | NTSC   | 1 : 3     | 262       | 241         | yes            |
+/// | PAL    | 1 : 3.2   | 312       | 241         | no             |
+/// | Dendy  | 1 : 3     | 312       | 291         | no             |
+///
+/// Dendy famiclones pair the PAL frame with an NTSC-style CPU divider and
+/// delay vblank by 50 lines so NTSC games keep roughly NTSC CPU time per
+/// vblank. Their APU behaves like the NTSC one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    pub const ALL: [Region; 3] = [Region::Ntsc, Region::Pal, Region::Dendy];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        }
+    }
+
+    pub fn cpu_clock_hz(self) -> f64 {
+        self.master_clock_hz() / self.cpu_clock_divider() as f64
+    }
+
+    pub fn frame_rate_hz(self) -> f64 {
+        let ppu_clock_hz = self.master_clock_hz() / self.ppu_clock_divider() as f64;
+        let dots_per_frame = 341.0 * self.scanlines_per_frame() as f64;
+        // The odd-frame skip removes one dot from every other frame.
+        let skipped = if self.has_odd_frame_skip() { 0.5 } else { 0.0 };
+        ppu_clock_hz / (dots_per_frame - skipped)
+    }
+
+    fn master_clock_hz(self) -> f64 {
+        match self {
+            Region::Ntsc => 21_477_272.727_272_73,
+            Region::Pal | Region::Dendy => 26_601_712.5,
+        }
+    }
+
+    /// Master clocks per CPU cycle.
+    pub(crate) fn cpu_clock_divider(self) -> u8 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    /// Master clocks per PPU dot.
+    pub(crate) fn ppu_clock_divider(self) -> u8 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5,
+        }
+    }
+
+    pub(crate) fn scanlines_per_frame(self) -> i16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    pub(crate) fn vblank_scanline(self) -> i16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    pub(crate) fn has_odd_frame_skip(self) -> bool {
+        self == Region::Ntsc
+    }
+
+    /// The 2A07 has its own noise/DMC period tables and frame counter steps.
+    pub(crate) fn uses_pal_apu(self) -> bool {
+        self == Region::Pal
+    }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => 2,
+        }
+    }
+
+    pub(crate) fn from_byte(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Region::Ntsc),
+            1 => Some(Region::Pal),
+            2 => Some(Region::Dendy),
+            _ => None,
+        }
+    }
+}
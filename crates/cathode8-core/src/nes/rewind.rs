@@ -0,0 +1,405 @@
+//! Rewind history: save states taken every few frames in a ring buffer of
+//! bounded length, for stepping back in time.
+//!
+//! Two states a few frames apart differ in a small part of RAM and video
+//! memory, so only every [`KEYFRAME_INTERVAL`]th snapshot is kept whole.
+//! The others store their XOR against the keyframe before them with runs of
+//! zeros squeezed out, which takes a few hundred bytes instead of ~20 KiB.
+
+use std::collections::VecDeque;
+
+use anyhow::{Result, anyhow, bail};
+
+use super::Nes;
+
+/// Snapshots between whole states. Restoring one decodes a single delta.
+pub const KEYFRAME_INTERVAL: usize = 30;
+
+#[derive(Debug, Clone)]
+enum Snapshot {
+    Full(Vec<u8>),
+    /// XOR against the nearest keyframe before it, see [`encode_delta`].
+    Delta(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    frame: u64,
+    snapshot: Snapshot,
+}
+
+/// Snapshots tagged with the frame they were taken on, oldest first.
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    interval: u64,
+    /// Snapshots since the newest keyframe.
+    since_keyframe: usize,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self::new(0, 1)
+    }
+}
+
+impl RewindBuffer {
+    /// Keeps up to `capacity` snapshots, one every `interval` frames. A
+    /// capacity of 0 disables rewind.
+    pub fn new(capacity: usize, interval: u64) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            interval: interval.max(1),
+            since_keyframe: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes held by the snapshots.
+    pub fn memory_used(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| match &entry.snapshot {
+                Snapshot::Full(data) | Snapshot::Delta(data) => data.len(),
+            })
+            .sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.since_keyframe = 0;
+    }
+
+    /// Whether a snapshot is due after `frame` ran.
+    pub fn wants(&self, frame: u64) -> bool {
+        self.capacity > 0 && frame.is_multiple_of(self.interval)
+    }
+
+    pub fn push(&mut self, frame: u64, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            self.pop_oldest();
+        }
+        let keyframe = self
+            .entries
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.snapshot {
+                Snapshot::Full(data) => Some(data),
+                Snapshot::Delta(_) => None,
+            });
+        let snapshot = match keyframe {
+            Some(keyframe)
+                if self.since_keyframe < KEYFRAME_INTERVAL && keyframe.len() == state.len() =>
+            {
+                self.since_keyframe += 1;
+                Snapshot::Delta(encode_delta(keyframe, &state))
+            }
+            _ => {
+                self.since_keyframe = 0;
+                Snapshot::Full(state)
+            }
+        };
+        self.entries.push_back(Entry { frame, snapshot });
+    }
+
+    /// The newest snapshot taken on or before `frame`, with its frame.
+    pub fn newest_at_or_before(&self, frame: u64) -> Result<Option<(u64, Vec<u8>)>> {
+        let Some(index) = self.entries.iter().rposition(|entry| entry.frame <= frame) else {
+            return Ok(None);
+        };
+        Ok(Some((self.entries[index].frame, self.decode(index)?)))
+    }
+
+    /// Drops snapshots newer than `frame`, which belong to a timeline that
+    /// was jumped away from.
+    pub fn truncate_after(&mut self, frame: u64) {
+        while self.entries.back().is_some_and(|entry| entry.frame > frame) {
+            self.entries.pop_back();
+        }
+        self.recount_since_keyframe();
+    }
+
+    fn recount_since_keyframe(&mut self) {
+        self.since_keyframe = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| matches!(entry.snapshot, Snapshot::Delta(_)))
+            .count();
+    }
+
+    fn decode(&self, index: usize) -> Result<Vec<u8>> {
+        match &self.entries[index].snapshot {
+            Snapshot::Full(data) => Ok(data.clone()),
+            Snapshot::Delta(delta) => {
+                let keyframe = self
+                    .entries
+                    .iter()
+                    .take(index)
+                    .rev()
+                    .find_map(|entry| match &entry.snapshot {
+                        Snapshot::Full(data) => Some(data),
+                        Snapshot::Delta(_) => None,
+                    })
+                    .ok_or_else(|| anyhow!("rewind snapshot without a keyframe"))?;
+                decode_delta(keyframe, delta)
+            }
+        }
+    }
+
+    /// Removes the oldest snapshot, which is always a keyframe. The deltas
+    /// based on it are rebased onto the next snapshot, which becomes whole.
+    fn pop_oldest(&mut self) {
+        let Some(Entry {
+            snapshot: Snapshot::Full(old_keyframe),
+            ..
+        }) = self.entries.pop_front()
+        else {
+            return;
+        };
+        let mut keyframe: Option<Vec<u8>> = None;
+        for entry in &mut self.entries {
+            let Snapshot::Delta(delta) = &entry.snapshot else {
+                break;
+            };
+            let Ok(state) = decode_delta(&old_keyframe, delta) else {
+                // Can't happen for deltas this buffer encoded; start over
+                // rather than restore garbage.
+                self.clear();
+                return;
+            };
+            entry.snapshot = match &keyframe {
+                Some(keyframe) => Snapshot::Delta(encode_delta(keyframe, &state)),
+                None => {
+                    keyframe = Some(state.clone());
+                    Snapshot::Full(state)
+                }
+            };
+        }
+        self.recount_since_keyframe();
+    }
+}
+
+/// `state` XOR `base` as (zero run, literal length, literals) triples of
+/// LEB128 lengths. Both are the same length.
+fn encode_delta(base: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at < state.len() {
+        let zeros = (at..state.len())
+            .take_while(|&index| base[index] == state[index])
+            .count();
+        at += zeros;
+        let literals = (at..state.len())
+            .take_while(|&index| base[index] != state[index])
+            .count();
+        push_len(&mut out, zeros);
+        push_len(&mut out, literals);
+        out.extend((at..at + literals).map(|index| base[index] ^ state[index]));
+        at += literals;
+    }
+    out
+}
+
+fn decode_delta(base: &[u8], mut delta: &[u8]) -> Result<Vec<u8>> {
+    let mut state = base.to_vec();
+    let mut at = 0;
+    while !delta.is_empty() {
+        at += read_len(&mut delta)?;
+        let literals = read_len(&mut delta)?;
+        if literals > delta.len() || at + literals > state.len() {
+            bail!("corrupt rewind snapshot");
+        }
+        for (byte, xor) in state[at..at + literals].iter_mut().zip(&delta[..literals]) {
+            *byte ^= xor;
+        }
+        delta = &delta[literals..];
+        at += literals;
+    }
+    Ok(state)
+}
+
+fn push_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+}
+
+fn read_len(input: &mut &[u8]) -> Result<usize> {
+    let mut len = 0usize;
+    for shift in (0..).step_by(7).take(5) {
+        let (&byte, rest) = input
+            .split_first()
+            .ok_or_else(|| anyhow!("corrupt rewind snapshot"))?;
+        *input = rest;
+        len |= usize::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(len);
+        }
+    }
+    bail!("corrupt rewind snapshot")
+}
+
+impl Nes {
+    /// Keeps a snapshot every `interval` frames, up to `capacity` of them,
+    /// for [`Nes::rewind`]. A capacity of 0 turns rewind off. Changing the
+    /// settings drops the history.
+    ///
+    /// Frontends recording movies should rewind through
+    /// [`crate::session::SessionTimeline`] instead, which keeps its own
+    /// history in step with the movie.
+    pub fn set_rewind(&mut self, capacity: usize, interval: u64) {
+        self.rewind = RewindBuffer::new(capacity, interval);
+    }
+
+    pub fn rewind_buffer(&self) -> &RewindBuffer {
+        &self.rewind
+    }
+
+    /// Steps back to the newest snapshot at least `frames` frames old.
+    /// Returns `false` when history doesn't reach that far. Snapshots newer
+    /// than the one restored are dropped.
+    pub fn rewind(&mut self, frames: u64) -> Result<bool> {
+        let target = self.debug.frame_count.saturating_sub(frames);
+        let Some((frame, state)) = self.rewind.newest_at_or_before(target)? else {
+            return Ok(false);
+        };
+        self.load_state_from_slice(&state)?;
+        self.debug.frame_count = frame;
+        self.rewind.truncate_after(frame);
+        Ok(true)
+    }
+
+    /// Takes a rewind snapshot if one is due after the frame just finished.
+    pub(super) fn capture_rewind_snapshot(&mut self) {
+        if !self.rewind.wants(self.debug.frame_count) {
+            return;
+        }
+        match self.save_state_to_vec() {
+            Ok(state) => self.rewind.push(self.debug.frame_count, state),
+            Err(err) => tracing::warn!("rewind snapshot failed: {err:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mapper::Mirroring;
+
+    #[test]
+    fn snapshots_round_trip_across_keyframes_and_eviction() {
+        let mut buffer = RewindBuffer::new(40, 1);
+        let state = |frame: u64| {
+            let mut data = vec![0u8; 3000];
+            data[100] = frame as u8;
+            data[2000..2004].copy_from_slice(&(frame as u32 * 1000).to_le_bytes());
+            data
+        };
+        for frame in 1..=100 {
+            buffer.push(frame, state(frame));
+        }
+        assert_eq!(buffer.len(), 40);
+        assert!(buffer.memory_used() < 40 * 3000 / 8);
+        for frame in [61, 75, 90, 100] {
+            assert_eq!(
+                buffer.newest_at_or_before(frame).unwrap(),
+                Some((frame, state(frame)))
+            );
+        }
+        assert_eq!(buffer.newest_at_or_before(60).unwrap(), None);
+
+        buffer.truncate_after(80);
+        assert_eq!(buffer.len(), 20);
+        buffer.push(81, state(7));
+        assert_eq!(
+            buffer.newest_at_or_before(200).unwrap(),
+            Some((81, state(7)))
+        );
+    }
+
+    #[test]
+    fn nes_rewind_restores_an_earlier_frame() {
+        // NROM: `loop: INC $10; JMP loop`.
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[..6].copy_from_slice(b"NES\x1A\x01\x01");
+        rom[16..21].copy_from_slice(&[0xE6, 0x10, 0x4C, 0x00, 0xC0]);
+        rom[16 + 0x3FFA..16 + 0x4000].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        nes.set_rewind(4, 2);
+
+        let mut counters = Vec::new();
+        for _ in 0..10 {
+            nes.run_frame();
+            counters.push(nes.ram[0x10]);
+        }
+        assert_eq!(nes.rewind_buffer().len(), 4);
+        assert!(nes.rewind(3).unwrap());
+        assert_eq!(nes.debug_counters().frame_count, 6);
+        assert_eq!(nes.ram[0x10], counters[5]);
+        assert!(!nes.rewind(10).unwrap());
+    }
+
+    #[test]
+    fn nes_rewind_restores_mapper_banks() {
+        // MMC3 with 64 KB of PRG, each 8 KB bank filled with its number,
+        // running `loop: INC $10; JMP loop` from the fixed bank at $E000.
+        let mut rom = vec![0u8; 16 + 0x10000 + 0x2000];
+        rom[..7].copy_from_slice(b"NES\x1A\x04\x01\x40");
+        for bank in 0..8 {
+            rom[16 + bank * 0x2000..16 + (bank + 1) * 0x2000].fill(bank as u8);
+        }
+        let fixed = 16 + 0xE000;
+        rom[fixed..fixed + 5].copy_from_slice(&[0xE6, 0x10, 0x4C, 0x00, 0xE0]);
+        rom[fixed + 0x1FFA..fixed + 0x2000].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x00, 0xE0]);
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        nes.set_rewind(8, 1);
+
+        nes.cpu_write(0x8000, 0x06);
+        nes.cpu_write(0x8001, 0x03);
+        nes.cpu_write(0xA000, 0x01);
+        nes.cpu_write(0x6000, 0x5A);
+        nes.run_frame();
+        let pc = nes.debug_pc();
+        let counter = nes.ram[0x10];
+
+        nes.cpu_write(0x8001, 0x05);
+        nes.cpu_write(0xA000, 0x00);
+        nes.cpu_write(0x6000, 0xA5);
+        nes.run_frame();
+        assert_eq!(nes.debug_peek_cpu(0x8000), 5);
+
+        assert!(nes.rewind(1).unwrap());
+        assert_eq!(nes.debug_pc(), pc);
+        assert_eq!(nes.ram[0x10], counter);
+        assert_eq!(nes.debug_peek_cpu(0x8000), 3);
+        assert_eq!(nes.debug_peek_cpu(0x6000), 0x5A);
+        let mapper = nes.mapper.as_ref().unwrap();
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+        assert_eq!(nes.ppu.mirroring(), Mirroring::Horizontal);
+    }
+}
@@ -0,0 +1,248 @@
+//! Byte-level comparison of two save states of the same game, for finding
+//! the variable behind some behavior: save before and after it happens,
+//! diff, and look at what changed.
+//!
+//! Only what a state stores is compared: CPU registers, internal RAM,
+//! nametable RAM, palette, OAM, developer scratch RAM and the mapper's
+//! block. The states are read without the cartridge, so the mapper block
+//! (bank registers, cartridge RAM, IRQ counters, in the order the board
+//! saves them) is compared as raw bytes.
+//!
+//! [`RamLabels`] names addresses from a Mesen `.mlb` or FCEUX `.nl` label
+//! file so the report reads `$0075 PlayerX` instead of a bare address.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+
+use super::{DEV_RAM_START, Nes};
+
+const CPU_REGISTERS: [&str; 7] = ["A", "X", "Y", "P", "SP", "PCL", "PCH"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StateRegion {
+    Cpu,
+    Ram,
+    Nametables,
+    Palette,
+    Oam,
+    DevRam,
+    Mapper,
+}
+
+impl StateRegion {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Ram => "RAM",
+            Self::Nametables => "Nametable RAM",
+            Self::Palette => "Palette",
+            Self::Oam => "OAM",
+            Self::DevRam => "Scratch RAM",
+            Self::Mapper => "Mapper",
+        }
+    }
+
+    /// Where `offset` lives, in the notation debuggers use for the region.
+    pub fn location(self, offset: usize) -> String {
+        match self {
+            Self::Cpu => CPU_REGISTERS
+                .get(offset)
+                .copied()
+                .unwrap_or("?")
+                .to_string(),
+            Self::Ram => format!("${offset:04X}"),
+            Self::Nametables => format!("CIRAM ${offset:03X}"),
+            Self::Palette => format!("${:04X}", 0x3F00 + offset),
+            Self::Oam => format!("OAM ${offset:02X}"),
+            Self::DevRam => format!("${:04X}", DEV_RAM_START as usize + offset),
+            Self::Mapper => format!("Mapper +${offset:04X}"),
+        }
+    }
+
+    /// The CPU address of `offset`, for regions the CPU sees directly.
+    pub fn cpu_addr(self, offset: usize) -> Option<u16> {
+        match self {
+            Self::Ram => Some(offset as u16),
+            Self::DevRam => Some(DEV_RAM_START + offset as u16),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteDiff {
+    pub region: StateRegion,
+    pub offset: usize,
+    pub before: u8,
+    pub after: u8,
+}
+
+/// Compares two states, region by region, in address order.
+pub fn diff_states(before: &[u8], after: &[u8]) -> Result<Vec<ByteDiff>> {
+    let before = state_regions(before).context("reading the first state")?;
+    let after = state_regions(after).context("reading the second state")?;
+    let mut diffs = Vec::new();
+    for ((region, old), (_, new)) in before.iter().zip(&after) {
+        for (offset, (&before, &after)) in old.iter().zip(new).enumerate() {
+            if before != after {
+                diffs.push(ByteDiff {
+                    region: *region,
+                    offset,
+                    before,
+                    after,
+                });
+            }
+        }
+    }
+    Ok(diffs)
+}
+
+/// Loads `state` into a scratch console (no cartridge needed) and copies
+/// out each region.
+fn state_regions(mut state: &[u8]) -> Result<Vec<(StateRegion, Vec<u8>)>> {
+    let mut nes = Nes::new();
+    let mapper = nes.read_console_state(&mut state)?;
+    let (a, x, y, p, sp, pc) = nes.debug_cpu_regs();
+    let [pcl, pch] = pc.to_le_bytes();
+    let dev_ram = nes.dev_ram.as_deref().map_or_else(Vec::new, <[u8]>::to_vec);
+    Ok(vec![
+        (StateRegion::Cpu, vec![a, x, y, p, sp, pcl, pch]),
+        (StateRegion::Ram, nes.ram.to_vec()),
+        (
+            StateRegion::Nametables,
+            (0..4096).map(|index| nes.debug_peek_vram(index)).collect(),
+        ),
+        (
+            StateRegion::Palette,
+            (0..32).map(|index| nes.debug_peek_palette(index)).collect(),
+        ),
+        (
+            StateRegion::Oam,
+            (0..256).map(|index| nes.debug_peek_oam(index)).collect(),
+        ),
+        (StateRegion::DevRam, dev_ram),
+        (StateRegion::Mapper, mapper),
+    ])
+}
+
+/// Names for CPU addresses from a debugger label file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RamLabels {
+    labels: BTreeMap<u16, String>,
+}
+
+impl RamLabels {
+    /// Reads Mesen labels (`R:0075:PlayerX:comment`, `R` for internal RAM,
+    /// `S`/`W` for work RAM at $6000) and FCEUX labels
+    /// (`$0075#PlayerX#comment`). Other lines are skipped, so either file
+    /// can be fed in whole.
+    pub fn parse(text: &str) -> Self {
+        let mut labels = BTreeMap::new();
+        for line in text.lines().map(str::trim) {
+            let parsed = if let Some(rest) = line.strip_prefix('$') {
+                let mut fields = rest.splitn(3, '#');
+                fields
+                    .next()
+                    .zip(fields.next())
+                    .map(|(addr, name)| (addr, 0, name))
+            } else {
+                let mut fields = line.splitn(4, ':');
+                match (fields.next(), fields.next(), fields.next()) {
+                    (Some("R" | "NesInternalRam"), Some(addr), Some(name)) => Some((addr, 0, name)),
+                    (Some("S" | "W" | "NesSaveRam" | "NesWorkRam"), Some(addr), Some(name)) => {
+                        Some((addr, 0x6000, name))
+                    }
+                    _ => None,
+                }
+            };
+            let Some((addr, base, name)) = parsed else {
+                continue;
+            };
+            // Mesen writes ranges as `0075-0076`; the label goes on the first.
+            let addr = addr.split('-').next().unwrap_or(addr);
+            if let Ok(addr) = u16::from_str_radix(addr, 16)
+                && !name.is_empty()
+            {
+                labels.insert(addr.wrapping_add(base), name.to_string());
+            }
+        }
+        Self { labels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn get(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    /// The label for the byte a diff is about, if the CPU can see it.
+    pub fn for_diff(&self, diff: &ByteDiff) -> Option<&str> {
+        self.get(diff.region.cpu_addr(diff.offset)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_changed_bytes_with_labels() {
+        let mut nes = Nes::new();
+        let before = nes.save_state_to_vec().unwrap();
+        nes.ram[0x75] = 9;
+        nes.x = 3;
+        let after = nes.save_state_to_vec().unwrap();
+
+        let diffs = diff_states(&before, &after).unwrap();
+        let found: Vec<_> = diffs
+            .iter()
+            .map(|diff| (diff.region.location(diff.offset), diff.before, diff.after))
+            .collect();
+        assert_eq!(
+            found,
+            [("X".to_string(), 0, 3), ("$0075".to_string(), 0, 9)]
+        );
+
+        let labels = RamLabels::parse("R:0075-0076:PlayerX:pixels\n$0010#Timer#\nP:8000:Reset\n");
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.for_diff(&diffs[1]), Some("PlayerX"));
+        assert_eq!(labels.for_diff(&diffs[0]), None);
+    }
+
+    #[test]
+    fn diff_compares_mapper_registers() {
+        // MMC3, 32 KB of PRG and 8 KB of CHR.
+        let mut rom = vec![0u8; 16 + 0x8000 + 0x2000];
+        rom[..7].copy_from_slice(b"NES\x1A\x02\x01\x40");
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        let before = nes.save_state_to_vec().unwrap();
+        nes.cpu_write(0x8000, 0x07);
+        nes.cpu_write(0x8001, 0x02);
+        let after = nes.save_state_to_vec().unwrap();
+
+        // The MMC3 saves its 8 KB of PRG RAM first (length-prefixed), then
+        // the bank select, R0-R7 and, further on, the resolved PRG banks.
+        let diffs = diff_states(&before, &after).unwrap();
+        let found: Vec<_> = diffs
+            .iter()
+            .map(|diff| (diff.region, diff.offset, diff.before, diff.after))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (StateRegion::Mapper, 0x2004, 0, 0x07),
+                (StateRegion::Mapper, 0x200C, 0, 0x02),
+                (StateRegion::Mapper, 0x201E, 0, 0x40),
+            ]
+        );
+        assert_eq!(diffs[1].region.location(diffs[1].offset), "Mapper +$200C");
+    }
+}
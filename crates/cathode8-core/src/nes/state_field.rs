@@ -0,0 +1,179 @@
+//! Save-state encoding for mapper and sound chip fields. Values are written
+//! little-endian one after another, in the order [`state_fields!`] lists
+//! them, like the hand-written CPU, PPU and APU states.
+
+use std::io::{self, Read, Write};
+
+pub(super) trait StateField {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()>;
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()>;
+}
+
+/// Implements `save_state` and `load_state` over the listed fields, inside
+/// an `impl StateField` or `impl Mapper` block. `field if flag` only stores
+/// the field when `self.flag` is set, for CHR that is RAM on some carts and
+/// ROM on others.
+macro_rules! state_fields {
+    ($($field:ident $(if $flag:ident)?),* $(,)?) => {
+        fn save_state(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+            $(
+                if true $(&& self.$flag)? {
+                    $crate::nes::state_field::StateField::save_state(&self.$field, writer)?;
+                }
+            )*
+            Ok(())
+        }
+
+        fn load_state(&mut self, reader: &mut dyn std::io::Read) -> std::io::Result<()> {
+            $(
+                if true $(&& self.$flag)? {
+                    $crate::nes::state_field::StateField::load_state(&mut self.$field, reader)?;
+                }
+            )*
+            Ok(())
+        }
+    };
+}
+pub(super) use state_fields;
+
+/// Stores a fieldless enum as the index of its variant in the list.
+macro_rules! enum_state_field {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl $crate::nes::state_field::StateField for $ty {
+            fn save_state(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+                const VARIANTS: &[$ty] = &[$($ty::$variant),*];
+                let index = VARIANTS.iter().position(|variant| variant == self).unwrap_or(0);
+                writer.write_all(&[index as u8])
+            }
+
+            fn load_state(&mut self, reader: &mut dyn std::io::Read) -> std::io::Result<()> {
+                const VARIANTS: &[$ty] = &[$($ty::$variant),*];
+                let mut index = [0u8];
+                reader.read_exact(&mut index)?;
+                *self = *VARIANTS.get(usize::from(index[0])).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        concat!("unknown ", stringify!($ty), " in save state"),
+                    )
+                })?;
+                Ok(())
+            }
+        }
+    };
+}
+pub(super) use enum_state_field;
+
+macro_rules! int_state_field {
+    ($($ty:ty),*) => {
+        $(
+            impl StateField for $ty {
+                fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+                    let mut bytes = self.to_le_bytes();
+                    reader.read_exact(&mut bytes)?;
+                    *self = <$ty>::from_le_bytes(bytes);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+int_state_field!(u8, u16, u32, u64, i16, f32);
+
+impl StateField for bool {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[u8::from(*self)])
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        *self = byte[0] != 0;
+        Ok(())
+    }
+}
+
+/// Offsets are stored as u64, so states don't depend on the pointer width.
+impl StateField for usize {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (*self as u64).save_state(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut value = 0u64;
+        value.load_state(reader)?;
+        *self = usize::try_from(value)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "offset out of range"))?;
+        Ok(())
+    }
+}
+
+impl<T: StateField, const N: usize> StateField for [T; N] {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.iter().try_for_each(|item| item.save_state(writer))
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        self.iter_mut().try_for_each(|item| item.load_state(reader))
+    }
+}
+
+/// RAM sized by the cartridge: a state only loads into a cart whose RAM
+/// is the same size.
+impl StateField for Vec<u8> {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (self.len() as u32).save_state(writer)?;
+        writer.write_all(self)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut len = 0u32;
+        len.load_state(reader)?;
+        if len as usize != self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "save state is for a cartridge with a different RAM size",
+            ));
+        }
+        reader.read_exact(self)
+    }
+}
+
+/// Optional chips, present or not depending on the cartridge.
+impl<T: StateField> StateField for Option<T> {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.is_some().save_state(writer)?;
+        match self {
+            Some(value) => value.save_state(writer),
+            None => Ok(()),
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut present = false;
+        present.load_state(reader)?;
+        match self {
+            Some(value) if present => value.load_state(reader),
+            None if !present => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "save state is for a different board",
+            )),
+        }
+    }
+}
+
+impl<A: StateField, B: StateField> StateField for (A, B) {
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.0.save_state(writer)?;
+        self.1.save_state(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        self.0.load_state(reader)?;
+        self.1.load_state(reader)
+    }
+}
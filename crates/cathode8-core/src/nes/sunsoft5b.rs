@@ -0,0 +1,236 @@
+//! Sunsoft 5B audio: the YM2149F inside the FME-7's big brother, with
+//! three square-wave tone channels, a noise generator and an envelope
+//! generator. The CPU picks one of its sixteen registers through $C000 and
+//! writes it through $E000; only Gimmick! puts more than the tones to use.
+//!
+//! The chip runs off the CPU clock with its /2 divider on, so tones are
+//! clocked every 16 CPU cycles, noise at half that rate, and the envelope
+//! moves one of its 32 levels every 16 tone clocks per unit of period.
+//! Volume is logarithmic, 1.5 dB per level.
+
+use super::state_field::{StateField, state_fields};
+
+/// CPU cycles per tone clock.
+const CLOCK_DIVIDER: u8 = 16;
+/// Tone clocks per envelope step, per unit of envelope period.
+const ENVELOPE_DIVIDER: u32 = 16;
+/// Output of one channel at full volume, in the APU mix's units. About a
+/// full-volume 2A03 pulse, which is how loud the 5B plays against it.
+const CHANNEL_FULL_SCALE: f32 = 0.15;
+
+#[derive(Debug, Clone)]
+pub struct Sunsoft5b {
+    registers: [u8; 16],
+    selected: u8,
+    divider: u8,
+    tone_counters: [u16; 3],
+    tone_high: [bool; 3],
+    noise_counter: u8,
+    /// Noise is clocked at half the tone rate.
+    noise_half: bool,
+    lfsr: u32,
+    envelope_counter: u32,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+    volume_table: [f32; 32],
+}
+
+impl StateField for Sunsoft5b {
+    state_fields!(
+        registers,
+        selected,
+        divider,
+        tone_counters,
+        tone_high,
+        noise_counter,
+        noise_half,
+        lfsr,
+        envelope_counter,
+        envelope_step,
+        envelope_attack,
+        envelope_holding,
+    );
+}
+
+impl Default for Sunsoft5b {
+    fn default() -> Self {
+        let mut volume_table = [0.0; 32];
+        for (level, volume) in volume_table.iter_mut().enumerate().skip(1) {
+            *volume = CHANNEL_FULL_SCALE * 10f32.powf((level as f32 - 31.0) * 1.5 / 20.0);
+        }
+        Self {
+            registers: [0; 16],
+            selected: 0,
+            divider: 0,
+            tone_counters: [0; 3],
+            tone_high: [false; 3],
+            noise_counter: 0,
+            noise_half: false,
+            lfsr: 1,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: true,
+            volume_table,
+        }
+    }
+}
+
+impl Sunsoft5b {
+    /// $C000: selects the register $E000 writes. Writes with the high
+    /// nibble set select nothing.
+    pub fn select(&mut self, value: u8) {
+        self.selected = value;
+    }
+
+    /// $E000: writes the selected register.
+    pub fn write(&mut self, value: u8) {
+        if self.selected & 0xF0 != 0 {
+            return;
+        }
+        let reg = self.selected as usize;
+        self.registers[reg] = value;
+        if reg == 13 {
+            self.envelope_counter = 0;
+            self.envelope_step = 0;
+            self.envelope_attack = value & 0x04 != 0;
+            self.envelope_holding = false;
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.divider += 1;
+        if self.divider < CLOCK_DIVIDER {
+            return;
+        }
+        self.divider = 0;
+        for channel in 0..3 {
+            self.tone_counters[channel] += 1;
+            if self.tone_counters[channel] >= self.tone_period(channel) {
+                self.tone_counters[channel] = 0;
+                self.tone_high[channel] = !self.tone_high[channel];
+            }
+        }
+        self.noise_half = !self.noise_half;
+        if self.noise_half {
+            self.noise_counter += 1;
+            if self.noise_counter >= (self.registers[6] & 0x1F).max(1) {
+                self.noise_counter = 0;
+                let feedback = (self.lfsr ^ (self.lfsr >> 3)) & 1;
+                self.lfsr = (self.lfsr >> 1) | (feedback << 16);
+            }
+        }
+        self.envelope_counter += 1;
+        if self.envelope_counter >= self.envelope_period() {
+            self.envelope_counter = 0;
+            self.step_envelope();
+        }
+    }
+
+    /// Current output, in the APU mix's units.
+    pub fn output(&self) -> f32 {
+        let mixer = self.registers[7];
+        let noise_high = self.lfsr & 1 != 0;
+        (0..3)
+            .map(|channel| {
+                let tone_on = self.tone_high[channel] || mixer & (1 << channel) != 0;
+                let noise_on = noise_high || mixer & (8 << channel) != 0;
+                if !(tone_on && noise_on) {
+                    return 0.0;
+                }
+                let amplitude = self.registers[8 + channel];
+                let level = if amplitude & 0x10 != 0 {
+                    self.envelope_level()
+                } else if amplitude & 0x0F == 0 {
+                    0
+                } else {
+                    (amplitude & 0x0F) * 2 + 1
+                };
+                self.volume_table[level as usize]
+            })
+            .sum()
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let low = u16::from(self.registers[channel * 2]);
+        let high = u16::from(self.registers[channel * 2 + 1] & 0x0F);
+        ((high << 8) | low).max(1)
+    }
+
+    /// Tone clocks per envelope step.
+    fn envelope_period(&self) -> u32 {
+        u32::from(u16::from_le_bytes([self.registers[11], self.registers[12]]).max(1))
+            * ENVELOPE_DIVIDER
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_attack {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    /// Register 13's bits: 3 continue, 2 attack, 1 alternate, 0 hold.
+    fn step_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+        if self.envelope_step < 31 {
+            self.envelope_step += 1;
+            return;
+        }
+        let shape = self.registers[13];
+        if shape & 0x08 == 0 {
+            // One ramp, then silence.
+            self.envelope_holding = true;
+            self.envelope_attack = false;
+        } else if shape & 0x01 != 0 {
+            self.envelope_holding = true;
+            if shape & 0x02 != 0 {
+                self.envelope_attack = !self.envelope_attack;
+            }
+        } else {
+            self.envelope_step = 0;
+            if shape & 0x02 != 0 {
+                self.envelope_attack = !self.envelope_attack;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_toggles_every_period_of_clocks() {
+        let mut chip = Sunsoft5b::default();
+        let mut write = |reg: u8, value: u8| {
+            chip.select(reg);
+            chip.write(value);
+        };
+        write(0, 2);
+        write(7, 0x3E);
+        write(8, 0x0F);
+        let mut highs = Vec::new();
+        for _ in 0..4 {
+            for _ in 0..u32::from(CLOCK_DIVIDER) * 2 {
+                chip.tick();
+            }
+            highs.push(chip.output() > 0.0);
+        }
+        assert_eq!(highs, [true, false, true, false]);
+        assert_eq!(chip.output(), 0.0);
+        for _ in 0..u32::from(CLOCK_DIVIDER) * 2 {
+            chip.tick();
+        }
+        assert!((chip.output() - CHANNEL_FULL_SCALE).abs() < 1e-6);
+
+        // A select with the high nibble set locks writes out.
+        chip.select(0x18);
+        chip.write(0x00);
+        assert!(chip.output() > 0.0);
+    }
+}
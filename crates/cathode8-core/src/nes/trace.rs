@@ -0,0 +1,252 @@
+//! CPU instruction trace in the format of nestest.log, the reference log
+//! for Kevin Horton's nestest ROM that most emulators can also write:
+//!
+//! ```text
+//! C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:261,  0 CYC:7
+//! ```
+//!
+//! One line per instruction with its bytes, disassembly, and the registers
+//! before it runs. Memory operands show the effective address and the
+//! value there (`STA $0200 = 7F`), read through [`Nes::debug_peek_cpu`], so
+//! PPU and APU registers show as 00. Unofficial opcodes are starred.
+//!
+//! CYC counts the seven cycles of the reset sequence like nestest.log, so
+//! the logs line up column for column up to PPU. The PPU column is this
+//! console's dot position, which powers on at the pre-render line, while
+//! the emulator that made nestest.log started at scanline 0.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+use super::disasm::Mode;
+use super::{FLAG_BREAK, FLAG_UNUSED, Nes};
+
+/// Cycles the CPU spends in reset before fetching the first instruction.
+const RESET_CYCLES: u64 = 7;
+
+/// A trace being recorded. See [`Nes::start_cpu_trace`].
+pub struct CpuTrace {
+    lines: VecDeque<String>,
+    capacity: usize,
+    file: Option<(PathBuf, BufWriter<File>)>,
+    /// The first failed file write; the file gets nothing after it.
+    error: Option<anyhow::Error>,
+    traced: u64,
+}
+
+impl CpuTrace {
+    /// The newest lines kept in memory, oldest first.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+
+    /// Instructions traced so far, including lines the memory ring dropped.
+    pub fn traced(&self) -> u64 {
+        self.traced
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    fn push(&mut self, line: String) {
+        self.traced += 1;
+        if let Some((path, file)) = self.file.as_mut()
+            && self.error.is_none()
+            && let Err(err) = writeln!(file, "{line}")
+        {
+            self.error =
+                Some(anyhow::Error::new(err).context(format!("writing {}", path.display())));
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() >= self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+}
+
+impl Nes {
+    /// Starts tracing every instruction, replacing any trace already
+    /// running. The last `capacity` lines are kept in memory; with `path`,
+    /// every line also goes to that file.
+    pub fn start_cpu_trace(&mut self, capacity: usize, path: Option<&Path>) -> Result<()> {
+        let file = match path {
+            Some(path) => {
+                let file =
+                    File::create(path).with_context(|| format!("creating {}", path.display()))?;
+                Some((path.to_path_buf(), BufWriter::new(file)))
+            }
+            None => None,
+        };
+        self.cpu_trace = Some(Box::new(CpuTrace {
+            lines: VecDeque::with_capacity(capacity.min(4096)),
+            capacity,
+            file,
+            error: None,
+            traced: 0,
+        }));
+        Ok(())
+    }
+
+    /// Stops tracing, flushing the file. Fails if any line couldn't be
+    /// written.
+    pub fn stop_cpu_trace(&mut self) -> Result<Option<CpuTrace>> {
+        let Some(mut trace) = self.cpu_trace.take() else {
+            return Ok(None);
+        };
+        if let Some(err) = trace.error.take() {
+            return Err(err);
+        }
+        if let Some((path, file)) = trace.file.as_mut() {
+            file.flush()
+                .with_context(|| format!("writing {}", path.display()))?;
+        }
+        Ok(Some(*trace))
+    }
+
+    pub fn cpu_trace(&self) -> Option<&CpuTrace> {
+        self.cpu_trace.as_deref()
+    }
+
+    /// Logs the instruction at PC, before it runs.
+    pub(super) fn trace_instruction(&mut self) {
+        let line = self.nestest_line();
+        if let Some(trace) = self.cpu_trace.as_mut() {
+            trace.push(line);
+        }
+    }
+
+    fn nestest_line(&mut self) -> String {
+        let instruction = self.debug_disassemble(self.pc);
+        let bytes: Vec<String> = instruction
+            .bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        // nestest.log spells ISC the older way.
+        let mnemonic = match instruction.mnemonic {
+            "ISC" => "ISB",
+            mnemonic => mnemonic,
+        };
+        let operand = instruction.operand();
+        let text = if operand.is_empty() {
+            mnemonic.to_string()
+        } else {
+            format!(
+                "{mnemonic} {operand}{}",
+                self.operand_annotation(instruction.mode, mnemonic, &instruction.bytes)
+            )
+        };
+        let (scanline, dot) = self.ppu.debug_scanline_cycle();
+        format!(
+            "{:04X}  {:<8} {}{text:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{scanline:>3},{dot:>3} CYC:{}",
+            self.pc,
+            bytes.join(" "),
+            if instruction.unofficial { '*' } else { ' ' },
+            self.a,
+            self.x,
+            self.y,
+            (self.p | FLAG_UNUSED) & !FLAG_BREAK,
+            self.sp,
+            self.total_cycles + RESET_CYCLES,
+        )
+    }
+
+    /// The effective address and value nestest.log writes after a memory
+    /// operand.
+    fn operand_annotation(&mut self, mode: Mode, mnemonic: &str, bytes: &[u8]) -> String {
+        let byte = bytes.get(1).copied().unwrap_or(0);
+        let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or(0)]);
+        let zero_page_word = |nes: &mut Nes, addr: u8| {
+            u16::from_le_bytes([
+                nes.debug_peek_cpu(u16::from(addr)),
+                nes.debug_peek_cpu(u16::from(addr.wrapping_add(1))),
+            ])
+        };
+        match mode {
+            Mode::Zp => format!(" = {:02X}", self.debug_peek_cpu(u16::from(byte))),
+            Mode::Zpx | Mode::Zpy => {
+                let index = if mode == Mode::Zpx { self.x } else { self.y };
+                let addr = u16::from(byte.wrapping_add(index));
+                format!(" @ {addr:02X} = {:02X}", self.debug_peek_cpu(addr))
+            }
+            Mode::Abs if !matches!(mnemonic, "JMP" | "JSR") => {
+                format!(" = {:02X}", self.debug_peek_cpu(word))
+            }
+            Mode::Abx | Mode::Aby => {
+                let index = if mode == Mode::Abx { self.x } else { self.y };
+                let addr = word.wrapping_add(u16::from(index));
+                format!(" @ {addr:04X} = {:02X}", self.debug_peek_cpu(addr))
+            }
+            Mode::Ind => {
+                // The pointer's high byte comes from the same page.
+                let high = (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF);
+                let target =
+                    u16::from_le_bytes([self.debug_peek_cpu(word), self.debug_peek_cpu(high)]);
+                format!(" = {target:04X}")
+            }
+            Mode::Izx => {
+                let pointer = byte.wrapping_add(self.x);
+                let addr = zero_page_word(self, pointer);
+                format!(
+                    " @ {pointer:02X} = {addr:04X} = {:02X}",
+                    self.debug_peek_cpu(addr)
+                )
+            }
+            Mode::Izy => {
+                let base = zero_page_word(self, byte);
+                let addr = base.wrapping_add(u16::from(self.y));
+                format!(
+                    " = {base:04X} @ {addr:04X} = {:02X}",
+                    self.debug_peek_cpu(addr)
+                )
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_match_nestest_log_layout() {
+        // NROM: LDX #$01; LDA ($7F,X); STA $0200,X; *NOP $04; JMP ($02FF)
+        let program = [
+            0xA2, 0x01, 0xA1, 0x7F, 0x9D, 0x00, 0x02, 0x04, 0x04, 0x6C, 0xFF, 0x02,
+        ];
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[..6].copy_from_slice(b"NES\x1A\x01\x01");
+        rom[16..16 + program.len()].copy_from_slice(&program);
+        rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+        nes.ram[0x80..0x82].copy_from_slice(&[0x34, 0x12]);
+        nes.ram[0x02FF] = 0x00;
+        nes.ram[0x0200] = 0xC0;
+
+        nes.start_cpu_trace(4, None).unwrap();
+        for _ in 0..5 {
+            nes.debug_step();
+        }
+        let trace = nes.stop_cpu_trace().unwrap().unwrap();
+        assert_eq!(trace.traced(), 5);
+        assert_eq!(
+            trace.lines().collect::<Vec<_>>(),
+            [
+                "C002  A1 7F     LDA ($7F,X) @ 80 = 1234 = 00    A:00 X:01 Y:00 P:24 SP:FD PPU:261,  6 CYC:9",
+                "C004  9D 00 02  STA $0200,X @ 0201 = 00         A:00 X:01 Y:00 P:26 SP:FD PPU:261, 24 CYC:15",
+                "C007  04 04    *NOP $04 = 00                    A:00 X:01 Y:00 P:26 SP:FD PPU:261, 39 CYC:20",
+                "C009  6C FF 02  JMP ($02FF) = C000              A:00 X:01 Y:00 P:26 SP:FD PPU:261, 48 CYC:23",
+            ]
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! VGM logging of the 2A03 sound registers, for playing recorded game
+//! music in chiptune players (VGMPlay, foobar2000 with vgmstream, ...).
+//!
+//! Every write to $4000-$4017 goes into the log with the CPU cycle it
+//! happened on, and the gaps between writes become VGM waits. VGM counts
+//! time in 44.1 kHz samples, so writes are placed to within 1/44100 s of
+//! where the game made them, which is finer than any player's APU needs.
+//!
+//! DMC samples are read from the game's ROM and stored as NES APU RAM data
+//! blocks ahead of the register write that can start them. A block is only
+//! written when the bytes at a sample's address differ from what the log
+//! already holds there, so a drum kit costs its size once.
+//!
+//! The format is VGM 1.61, the first version with the NES APU (command
+//! `B4 aa dd`, clock at header offset $84).
+
+use super::{Nes, Region};
+
+/// VGM's fixed time base.
+const VGM_SAMPLE_RATE: u64 = 44_100;
+const VGM_VERSION: u32 = 0x161;
+const HEADER_LEN: usize = 0x100;
+/// Data block type for NES APU RAM, loaded at a 16-bit CPU address.
+const NES_APU_RAM_BLOCK: u8 = 0xC2;
+
+/// A VGM log being recorded. See [`Nes::start_vgm_log`].
+#[derive(Debug, Clone)]
+pub struct VgmLog {
+    region: Region,
+    commands: Vec<u8>,
+    /// CPU cycle of the last event, and cycles logged up to it. Resets and
+    /// loaded states move the console's cycle counter; time only moves
+    /// forward in the log.
+    last_cycle: u64,
+    elapsed_cycles: u64,
+    samples_written: u64,
+    /// DMC sample memory as the player will have it, $8000-$FFFF.
+    sample_ram: Vec<Option<u8>>,
+    writes: u64,
+}
+
+impl VgmLog {
+    fn new(region: Region, cycle: u64) -> Self {
+        Self {
+            region,
+            commands: Vec::new(),
+            last_cycle: cycle,
+            elapsed_cycles: 0,
+            samples_written: 0,
+            sample_ram: vec![None; 0x8000],
+            writes: 0,
+        }
+    }
+
+    /// Register writes logged so far.
+    pub fn writes(&self) -> u64 {
+        self.writes
+    }
+
+    /// Length of the log in seconds.
+    pub fn seconds(&self) -> f64 {
+        self.samples_written as f64 / VGM_SAMPLE_RATE as f64
+    }
+
+    /// Adds the waits that bring the log up to `cycle`.
+    fn advance_to(&mut self, cycle: u64) {
+        self.elapsed_cycles += cycle.saturating_sub(self.last_cycle);
+        self.last_cycle = cycle;
+        let target = (self.elapsed_cycles as f64 * VGM_SAMPLE_RATE as f64
+            / self.region.cpu_clock_hz()) as u64;
+        let mut wait = target.saturating_sub(self.samples_written);
+        self.samples_written += wait;
+        while wait > 0 {
+            let step = match wait {
+                735 => {
+                    self.commands.push(0x62);
+                    735
+                }
+                882 => {
+                    self.commands.push(0x63);
+                    882
+                }
+                1..=16 => {
+                    self.commands.push(0x70 + wait as u8 - 1);
+                    wait
+                }
+                _ => {
+                    let step = wait.min(u64::from(u16::MAX));
+                    self.commands.push(0x61);
+                    self.commands
+                        .extend_from_slice(&(step as u16).to_le_bytes());
+                    step
+                }
+            };
+            wait -= step;
+        }
+    }
+
+    fn write(&mut self, cycle: u64, addr: u16, value: u8) {
+        self.advance_to(cycle);
+        self.commands
+            .extend_from_slice(&[0xB4, (addr - 0x4000) as u8, value]);
+        self.writes += 1;
+    }
+
+    /// Stores the DMC sample at `start` in the log if the player doesn't
+    /// already have those bytes. `read` gives the byte at a CPU address.
+    fn load_sample(&mut self, start: u16, len: usize, mut read: impl FnMut(u16) -> u8) {
+        let mut addr = start;
+        let mut block = (start, Vec::with_capacity(len), false);
+        for index in 0..len {
+            let byte = read(addr);
+            let slot = &mut self.sample_ram[usize::from(addr - 0x8000)];
+            block.2 |= *slot != Some(byte);
+            *slot = Some(byte);
+            block.1.push(byte);
+            // Samples running off the end of the address space continue
+            // at $8000, which needs a block of its own.
+            if addr == 0xFFFF || index + 1 == len {
+                let (block_start, bytes, changed) =
+                    std::mem::replace(&mut block, (0x8000, Vec::new(), false));
+                if changed {
+                    self.commands
+                        .extend_from_slice(&[0x67, 0x66, NES_APU_RAM_BLOCK]);
+                    self.commands
+                        .extend_from_slice(&(bytes.len() as u32 + 2).to_le_bytes());
+                    self.commands.extend_from_slice(&block_start.to_le_bytes());
+                    self.commands.extend_from_slice(&bytes);
+                }
+            }
+            addr = addr.checked_add(1).unwrap_or(0x8000);
+        }
+    }
+
+    /// The finished file. `game` goes into the GD3 tag that players show.
+    pub fn to_bytes(&self, game: &str) -> Vec<u8> {
+        let mut out = vec![0u8; HEADER_LEN];
+        out.extend_from_slice(&self.commands);
+        out.push(0x66);
+        let gd3_offset = out.len();
+        out.extend_from_slice(&gd3_tag(game));
+
+        let file_len = out.len();
+        let mut put = |offset: usize, value: u32| {
+            out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        put(0x00, u32::from_le_bytes(*b"Vgm "));
+        put(0x04, (file_len - 0x04) as u32);
+        put(0x08, VGM_VERSION);
+        put(0x14, (gd3_offset - 0x14) as u32);
+        put(0x18, self.samples_written as u32);
+        put(0x24, self.region.frame_rate_hz().round() as u32);
+        put(0x34, (HEADER_LEN - 0x34) as u32);
+        put(0x84, self.region.cpu_clock_hz().round() as u32);
+        out
+    }
+}
+
+/// GD3 tag: eleven NUL-terminated UTF-16 strings (track, game, system and
+/// author names in English and Japanese, then date, ripper and notes).
+fn gd3_tag(game: &str) -> Vec<u8> {
+    let fields = [
+        "",
+        "",
+        game,
+        "",
+        "Nintendo Entertainment System",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "Logged with cathode8",
+    ];
+    let mut text = Vec::new();
+    for field in fields {
+        for unit in field.encode_utf16().chain([0]) {
+            text.extend_from_slice(&unit.to_le_bytes());
+        }
+    }
+    let mut tag = b"Gd3 ".to_vec();
+    tag.extend_from_slice(&0x100u32.to_le_bytes());
+    tag.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    tag.extend_from_slice(&text);
+    tag
+}
+
+impl Nes {
+    /// Starts logging sound register writes, replacing any log already
+    /// running. Channels that are sounding have their registers written at
+    /// the start so a log begun mid-song plays from there.
+    pub fn start_vgm_log(&mut self) {
+        let cycle = self.bus_cycle();
+        let mut log = VgmLog::new(self.region, cycle);
+        let registers = *self.apu.last_register_writes();
+        let active = self.apu.active_channels();
+        let reg = |addr: u16| registers[usize::from(addr - 0x4000)];
+
+        log.write(cycle, 0x4017, reg(0x4017));
+        log.write(cycle, 0x4015, reg(0x4015) & 0x0F);
+        for addr in (0x4000..=0x4011).filter(|addr| addr & 0x03 != 0x03) {
+            log.write(cycle, addr, reg(addr));
+        }
+        // The high timer registers reload the length counter, so writing
+        // them for a finished note would play it again.
+        for (channel, addr) in [0x4003, 0x4007, 0x400B, 0x400F].into_iter().enumerate() {
+            if active & (1 << channel) != 0 {
+                log.write(cycle, addr, reg(addr));
+            }
+        }
+        log.write(cycle, 0x4012, reg(0x4012));
+        log.write(cycle, 0x4013, reg(0x4013));
+        if active & 0x10 != 0 {
+            self.log_dmc_sample(&mut log, reg(0x4012), reg(0x4013));
+            log.write(cycle, 0x4015, reg(0x4015) & active);
+        }
+        self.vgm_log = Some(Box::new(log));
+    }
+
+    /// Stops logging and returns the log, closed at the current cycle.
+    pub fn stop_vgm_log(&mut self) -> Option<VgmLog> {
+        let mut log = self.vgm_log.take()?;
+        log.advance_to(self.bus_cycle());
+        Some(*log)
+    }
+
+    pub fn vgm_log(&self) -> Option<&VgmLog> {
+        self.vgm_log.as_deref()
+    }
+
+    /// Logs a CPU write to a sound register, with the DMC sample it may
+    /// start.
+    pub(super) fn log_apu_write(&mut self, addr: u16, value: u8) {
+        let Some(mut log) = self.vgm_log.take() else {
+            return;
+        };
+        let cycle = self.bus_cycle();
+        log.advance_to(cycle);
+        self.vgm_log_pending_dmc(&mut log, addr, value);
+        log.write(cycle, addr, value);
+        self.vgm_log = Some(log);
+    }
+
+    fn vgm_log_pending_dmc(&mut self, log: &mut VgmLog, addr: u16, value: u8) {
+        let starts_sample = match addr {
+            0x4015 => value & 0x10 != 0,
+            // A looping sample picks up new address and length registers
+            // when it restarts.
+            0x4012 | 0x4013 => true,
+            _ => false,
+        };
+        if !starts_sample {
+            return;
+        }
+        let mut registers = *self.apu.last_register_writes();
+        registers[usize::from(addr - 0x4000)] = value;
+        self.log_dmc_sample(log, registers[0x12], registers[0x13]);
+    }
+
+    fn log_dmc_sample(&mut self, log: &mut VgmLog, addr_reg: u8, len_reg: u8) {
+        let start = 0xC000 | (u16::from(addr_reg) << 6);
+        let len = usize::from(len_reg) * 16 + 1;
+        log.load_sample(start, len, |addr| self.debug_peek_cpu(addr));
+    }
+
+    /// CPU cycles since power-on, counting the ones spent inside the
+    /// instruction being executed.
+    fn bus_cycle(&self) -> u64 {
+        self.total_cycles + u64::from(self.cpu_step_ticked_cycles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_has_timed_writes_and_sample_blocks() {
+        let mut log = VgmLog::new(Region::Ntsc, 1000);
+        log.write(1000, 0x4000, 0xBF);
+        // One NTSC frame of CPU time is 735 VGM samples.
+        log.write(1000 + 29_830, 0x4002, 0x40);
+        log.load_sample(0xFFFF, 3, |addr| addr as u8);
+        log.load_sample(0xFFFF, 3, |addr| addr as u8);
+        assert_eq!(
+            log.commands,
+            [
+                0xB4, 0x00, 0xBF, 0x62, 0xB4, 0x02, 0x40, //
+                0x67, 0x66, 0xC2, 3, 0, 0, 0, 0xFF, 0xFF, 0xFF, //
+                0x67, 0x66, 0xC2, 4, 0, 0, 0, 0x00, 0x80, 0x00, 0x01,
+            ]
+        );
+
+        let file = log.to_bytes("Test");
+        assert_eq!(&file[..4], b"Vgm ");
+        assert_eq!(
+            u32::from_le_bytes(file[4..8].try_into().unwrap()) as usize,
+            file.len() - 4
+        );
+        let gd3 = 0x14 + u32::from_le_bytes(file[0x14..0x18].try_into().unwrap()) as usize;
+        assert_eq!(&file[gd3..gd3 + 4], b"Gd3 ");
+        assert_eq!(file[gd3 - 1], 0x66);
+        assert_eq!(
+            u32::from_le_bytes(file[0x84..0x88].try_into().unwrap()),
+            1_789_773
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Konami VRC7 audio: a cut-down Yamaha YM2413 (OPLL) with six two-operator
+//! FM channels, fifteen instruments in ROM and one the game defines. The
+//! CPU picks a register through $9010 and writes it through $9030; only
+//! Lagrange Point uses it.
+//!
+//! Each channel is a modulator whose output bends the phase of a carrier
+//! sine, with feedback on the modulator and an ADSR envelope on both. The
+//! chip makes one sample every 36 CPU cycles, the 72 clocks of its 3.58 MHz
+//! oscillator that the OPLL takes to run all its operators once. Levels are
+//! tracked in decibels of attenuation and turned into amplitudes at output.
+
+use std::f32::consts::{PI, TAU};
+
+use super::state_field::{StateField, enum_state_field, state_fields};
+
+/// CPU cycles per OPLL sample.
+const SAMPLE_CYCLES: u8 = 36;
+/// OPLL samples per second.
+const SAMPLE_RATE: f32 = 1_789_773.0 / SAMPLE_CYCLES as f32;
+/// Output of one channel at full volume, in the APU mix's units.
+const CHANNEL_FULL_SCALE: f32 = 0.08;
+/// Envelope steps are 0.375 dB; 128 of them reach silence.
+const ENVELOPE_STEP_DB: f32 = 0.375;
+const ENVELOPE_SILENT: f32 = 128.0;
+const AM_RATE_HZ: f32 = 3.7;
+const AM_DEPTH_DB: f32 = 4.8;
+const VIBRATO_RATE_HZ: f32 = 6.4;
+/// Vibrato's frequency swing, about 7 cents either way.
+const VIBRATO_DEPTH: f32 = 0.004;
+
+/// The built-in instruments 1-15, in the layout of the custom one in
+/// registers $00-$07.
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+/// Frequency multipliers, doubled so the 1/2 setting stays an integer.
+const MULTIPLIERS: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+/// Key scaling attenuation in dB for block 7 by the top four F-number
+/// bits, at 6 dB per octave.
+const KEY_SCALE_DB: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25,
+    42.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+enum_state_field!(EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+});
+
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    /// Phase in cycles, 0 to 1.
+    phase: f32,
+    state: EnvelopeState,
+    /// Envelope attenuation in 0.375 dB steps.
+    envelope: f32,
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Self {
+            phase: 0.0,
+            state: EnvelopeState::Release,
+            envelope: ENVELOPE_SILENT,
+        }
+    }
+}
+
+impl Operator {
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.state = EnvelopeState::Attack;
+    }
+
+    /// Moves the envelope one sample along. `op` is 0 for the modulator
+    /// and 1 for the carrier; `rks` is the key scaling added to each rate.
+    fn clock_envelope(&mut self, patch: &[u8; 8], op: usize, rks: u8, sustain_on: bool) {
+        let sustained = patch[op] & 0x20 != 0;
+        let attack = patch[4 + op] >> 4;
+        let decay = patch[4 + op] & 0x0F;
+        let sustain_level = f32::from(patch[6 + op] >> 4) * 8.0;
+        let release = patch[6 + op] & 0x0F;
+        match self.state {
+            EnvelopeState::Attack => {
+                let rate = envelope_rate(attack, rks);
+                if rate >= 60 {
+                    self.envelope = 0.0;
+                } else {
+                    // The attack curve is exponential: fast at first, then
+                    // slowing as it nears full level.
+                    self.envelope -= envelope_steps(rate) * (self.envelope + 8.0) / 8.0;
+                }
+                if self.envelope <= 0.0 {
+                    self.envelope = 0.0;
+                    self.state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                self.envelope += envelope_steps(envelope_rate(decay, rks));
+                if self.envelope >= sustain_level {
+                    self.envelope = sustain_level;
+                    self.state = EnvelopeState::Sustain;
+                }
+            }
+            EnvelopeState::Sustain => {
+                // Percussive instruments keep falling at their release rate.
+                if !sustained {
+                    self.envelope += envelope_steps(envelope_rate(release, rks));
+                }
+            }
+            EnvelopeState::Release => {
+                let release = if sustain_on {
+                    5
+                } else if sustained {
+                    release
+                } else {
+                    7
+                };
+                self.envelope += envelope_steps(envelope_rate(release, rks));
+            }
+        }
+        self.envelope = self.envelope.min(ENVELOPE_SILENT);
+    }
+
+    /// The operator's output, -1 to 1, with `modulation` radians added to
+    /// its phase and `level_db` of attenuation on top of the envelope.
+    fn output(&self, modulation: f32, level_db: f32, rectified: bool) -> f32 {
+        if self.envelope >= ENVELOPE_SILENT {
+            return 0.0;
+        }
+        let sine = (self.phase * TAU + modulation).sin();
+        if rectified && sine < 0.0 {
+            return 0.0;
+        }
+        let attenuation = self.envelope * ENVELOPE_STEP_DB + level_db;
+        sine * 10f32.powf(-attenuation / 20.0)
+    }
+}
+
+/// The effective rate, 0-63, of a 4-bit envelope rate. Rate 0 never moves.
+fn envelope_rate(rate: u8, rks: u8) -> u8 {
+    if rate == 0 {
+        0
+    } else {
+        (rate * 4 + rks).min(63)
+    }
+}
+
+/// Envelope steps per sample at an effective rate: each four rates double
+/// the speed, with the two low bits in between.
+fn envelope_steps(rate: u8) -> f32 {
+    if rate == 0 {
+        return 0.0;
+    }
+    (1.0 + f32::from(rate & 0x03) / 4.0) * (1u32 << (rate >> 2)) as f32 / 16384.0
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Channel {
+    modulator: Operator,
+    carrier: Operator,
+    /// The modulator's last two outputs, averaged for feedback.
+    feedback: [f32; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct Vrc7 {
+    registers: [u8; 0x40],
+    selected: u8,
+    channels: [Channel; 6],
+    divider: u8,
+    /// Tremolo and vibrato phases, in cycles.
+    am_phase: f32,
+    vibrato_phase: f32,
+    muted: bool,
+    output: f32,
+}
+
+impl StateField for Operator {
+    state_fields!(phase, state, envelope);
+}
+
+impl StateField for Channel {
+    state_fields!(modulator, carrier, feedback);
+}
+
+impl StateField for Vrc7 {
+    state_fields!(
+        registers,
+        selected,
+        channels,
+        divider,
+        am_phase,
+        vibrato_phase,
+        muted,
+        output,
+    );
+}
+
+impl Default for Vrc7 {
+    fn default() -> Self {
+        Self {
+            registers: [0; 0x40],
+            selected: 0,
+            channels: [Channel::default(); 6],
+            divider: 0,
+            am_phase: 0.0,
+            vibrato_phase: 0.0,
+            muted: false,
+            output: 0.0,
+        }
+    }
+}
+
+impl Vrc7 {
+    /// $9010: selects the register $9030 writes.
+    pub fn select(&mut self, value: u8) {
+        self.selected = value & 0x3F;
+    }
+
+    /// $9030: writes the selected register. Keying a channel on restarts
+    /// both operators' attack; keying it off releases them.
+    pub fn write(&mut self, value: u8) {
+        let reg = usize::from(self.selected);
+        let old = self.registers[reg];
+        self.registers[reg] = value;
+        if let 0x20..=0x25 = reg {
+            let channel = &mut self.channels[reg - 0x20];
+            match (old & 0x10 != 0, value & 0x10 != 0) {
+                (false, true) => {
+                    channel.modulator.key_on();
+                    channel.carrier.key_on();
+                    channel.feedback = [0.0; 2];
+                }
+                (true, false) => {
+                    channel.modulator.state = EnvelopeState::Release;
+                    channel.carrier.state = EnvelopeState::Release;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// $E000 bit 6 holds the chip in reset: silent, with its registers
+    /// cleared.
+    pub fn set_muted(&mut self, muted: bool) {
+        if muted && !self.muted {
+            let selected = self.selected;
+            *self = Self::default();
+            self.selected = selected;
+        }
+        self.muted = muted;
+    }
+
+    pub fn tick(&mut self) {
+        self.divider += 1;
+        if self.divider < SAMPLE_CYCLES {
+            return;
+        }
+        self.divider = 0;
+        if self.muted {
+            return;
+        }
+        self.am_phase = (self.am_phase + AM_RATE_HZ / SAMPLE_RATE).fract();
+        self.vibrato_phase = (self.vibrato_phase + VIBRATO_RATE_HZ / SAMPLE_RATE).fract();
+        self.output = (0..6)
+            .map(|channel| self.clock_channel(channel))
+            .sum::<f32>()
+            * CHANNEL_FULL_SCALE;
+    }
+
+    /// Current output, in the APU mix's units.
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+
+    fn patch(&self, instrument: u8) -> [u8; 8] {
+        match instrument {
+            0 => self.registers[..8].try_into().unwrap(),
+            _ => PATCHES[usize::from(instrument) - 1],
+        }
+    }
+
+    /// Runs one channel for a sample and returns its carrier's output.
+    fn clock_channel(&mut self, index: usize) -> f32 {
+        let fnum = u32::from(self.registers[0x10 + index])
+            | u32::from(self.registers[0x20 + index] & 0x01) << 8;
+        let block = (self.registers[0x20 + index] >> 1) & 0x07;
+        let sustain_on = self.registers[0x20 + index] & 0x20 != 0;
+        let instrument = self.registers[0x30 + index] >> 4;
+        let volume_db = f32::from(self.registers[0x30 + index] & 0x0F) * 3.0;
+        let patch = self.patch(instrument);
+
+        let am_db = AM_DEPTH_DB * (1.0 - (self.am_phase * TAU).cos()) / 2.0;
+        let vibrato = 1.0 + VIBRATO_DEPTH * (self.vibrato_phase * TAU).sin();
+        let key_scale_db =
+            (KEY_SCALE_DB[(fnum >> 5) as usize] - 6.0 * f32::from(7 - block)).max(0.0);
+        let rks_full = (block << 1) | (fnum >> 8) as u8;
+        let base_increment = (fnum << block) as f32 / (1 << 20) as f32;
+
+        let channel = &mut self.channels[index];
+        let mut levels = [f32::from(patch[2] & 0x3F) * 0.75, volume_db];
+        for (op, level) in levels.iter_mut().enumerate() {
+            let flags = patch[op];
+            let key_scale = patch[2 + op] >> 6;
+            if key_scale != 0 {
+                *level += key_scale_db / f32::from(1u8 << (3 - key_scale));
+            }
+            if flags & 0x80 != 0 {
+                *level += am_db;
+            }
+            let rks = if flags & 0x10 != 0 {
+                rks_full
+            } else {
+                rks_full >> 2
+            };
+            let operator = if op == 0 {
+                &mut channel.modulator
+            } else {
+                &mut channel.carrier
+            };
+            operator.clock_envelope(&patch, op, rks, sustain_on);
+            let mut increment = base_increment * MULTIPLIERS[usize::from(flags & 0x0F)] as f32;
+            if flags & 0x40 != 0 {
+                increment *= vibrato;
+            }
+            operator.phase = (operator.phase + increment).fract();
+        }
+
+        let feedback = patch[3] & 0x07;
+        let self_modulation = if feedback == 0 {
+            0.0
+        } else {
+            2.0 * TAU * (channel.feedback[0] + channel.feedback[1])
+                / 2.0
+                / f32::from(1u8 << (8 - feedback))
+        };
+        let modulator = channel
+            .modulator
+            .output(self_modulation, levels[0], patch[3] & 0x08 != 0);
+        channel.feedback = [channel.feedback[1], modulator];
+        channel
+            .carrier
+            .output(4.0 * PI * modulator, levels[1], patch[3] & 0x10 != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_on_sounds_and_key_off_fades() {
+        let mut chip = Vrc7::default();
+        let mut write = |reg: u8, value: u8| {
+            chip.select(reg);
+            chip.write(value);
+        };
+        // Instrument 3 (piano) at full volume, A4 in block 4.
+        write(0x10, 0xF4);
+        write(0x30, 0x30);
+        write(0x20, 0x18);
+        let mut peak = 0.0f32;
+        for _ in 0..SAMPLE_CYCLES as usize * 500 {
+            chip.tick();
+            peak = peak.max(chip.output().abs());
+        }
+        assert!(peak > 0.01, "peak {peak}");
+        assert!(peak <= CHANNEL_FULL_SCALE);
+
+        chip.select(0x20);
+        chip.write(0x08);
+        for _ in 0..SAMPLE_CYCLES as usize * 50_000 {
+            chip.tick();
+        }
+        assert!(chip.output().abs() < 1e-4);
+
+        chip.set_muted(true);
+        assert_eq!(chip.output(), 0.0);
+    }
+}
@@ -0,0 +1,307 @@
+//! Debug probe protocol: a line-based text protocol that lets external
+//! processes (scripts, editor plugins, test harnesses) inspect and drive a
+//! running console. The frontend owns the transport and the pause state;
+//! this module turns one request line into a reply.
+//!
+//! Requests are a command word followed by hex arguments (`$` and `0x`
+//! prefixes are optional). Every request gets exactly one reply line,
+//! either `ok` with a payload or `error <message>`. Like a gdb stop reply,
+//! the frontend also sends an unsolicited `break <pc>` line when a
+//! breakpoint stops the console.
+//!
+//! ```text
+//! regs                  ok pc=C000 a=00 x=00 y=00 p=24 sp=FD cycles=7 frame=0 scanline=0 dot=21
+//! read <addr> [len]     ok 4C 00 C0          (len up to 256, default 1)
+//! write <addr> <bytes>  ok                   (bytes as hex, e.g. 01 FF or 01FF)
+//! step [count]          ok pc=... (pauses first, then runs count instructions, up to 10000)
+//! pause / continue      ok
+//! break <addr>          ok
+//! delete <addr>         ok
+//! breakpoints           ok C000 C123
+//! reset                 ok
+//! status                ok paused
+//! ```
+//!
+//! Reads go through [`Nes::debug_peek_cpu`], so PPU and APU registers read
+//! as 0 instead of being disturbed.
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::nes::Nes;
+
+/// Longest `read` in one request.
+pub const MAX_READ_LEN: usize = 256;
+
+/// Most instructions one `step` runs, a few frames' worth. Requests are
+/// handled between frames, so a huge count would hang the frontend.
+pub const MAX_STEP: u32 = 0x10000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeCommand {
+    Regs,
+    Read { addr: u16, len: usize },
+    Write { addr: u16, bytes: Vec<u8> },
+    Step(u32),
+    Pause,
+    Continue,
+    Break(u16),
+    Delete(u16),
+    Breakpoints,
+    Reset,
+    Status,
+}
+
+impl ProbeCommand {
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or_else(|| anyhow!("empty request"))?;
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| {
+            args.get(index)
+                .copied()
+                .ok_or_else(|| anyhow!("{command} needs more arguments"))
+        };
+        let parsed = match command {
+            "regs" => Self::Regs,
+            "read" => {
+                let len = match args.get(1) {
+                    Some(len) => parse_hex(len)? as usize,
+                    None => 1,
+                };
+                if !(1..=MAX_READ_LEN).contains(&len) {
+                    bail!("read length must be 1-{MAX_READ_LEN:X}");
+                }
+                Self::Read {
+                    addr: parse_addr(arg(0)?)?,
+                    len,
+                }
+            }
+            "write" => {
+                let digits: String = args.get(1..).unwrap_or_default().concat();
+                if digits.is_empty()
+                    || !digits.len().is_multiple_of(2)
+                    || !digits.bytes().all(|byte| byte.is_ascii_hexdigit())
+                {
+                    bail!("write needs whole bytes of hex data");
+                }
+                let bytes = (0..digits.len())
+                    .step_by(2)
+                    .map(|at| u8::from_str_radix(&digits[at..at + 2], 16))
+                    .collect::<Result<_, _>>()?;
+                Self::Write {
+                    addr: parse_addr(arg(0)?)?,
+                    bytes,
+                }
+            }
+            "step" => match args.first() {
+                Some(count) => {
+                    let count = parse_hex(count)?;
+                    if !(1..=MAX_STEP).contains(&count) {
+                        bail!("step count must be 1-{MAX_STEP:X}");
+                    }
+                    Self::Step(count)
+                }
+                None => Self::Step(1),
+            },
+            "pause" => Self::Pause,
+            "continue" => Self::Continue,
+            "break" => Self::Break(parse_addr(arg(0)?)?),
+            "delete" => Self::Delete(parse_addr(arg(0)?)?),
+            "breakpoints" => Self::Breakpoints,
+            "reset" => Self::Reset,
+            "status" => Self::Status,
+            other => bail!("unknown command {other:?}"),
+        };
+        Ok(parsed)
+    }
+}
+
+/// What applying a request means for the frontend besides the reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeReply {
+    pub line: String,
+    /// `Some(true)` to pause emulation, `Some(false)` to resume it.
+    pub pause: Option<bool>,
+}
+
+/// Parses and applies one request line. `paused` is whether the frontend
+/// is currently holding emulation, for `status`.
+pub fn handle_request(nes: &mut Nes, line: &str, paused: bool) -> ProbeReply {
+    match ProbeCommand::parse(line).and_then(|command| apply(nes, &command, paused)) {
+        Ok((payload, pause)) if payload.is_empty() => ProbeReply {
+            line: "ok".to_string(),
+            pause,
+        },
+        Ok((payload, pause)) => ProbeReply {
+            line: format!("ok {payload}"),
+            pause,
+        },
+        Err(err) => ProbeReply {
+            line: format!("error {err:#}"),
+            pause: None,
+        },
+    }
+}
+
+/// `pc=C000 a=00 ...`, the payload of `regs` and `step`.
+pub fn registers(nes: &Nes) -> String {
+    let (a, x, y, p, sp, pc) = nes.debug_cpu_regs();
+    let (scanline, dot) = nes.debug_ppu_scanline_cycle();
+    format!(
+        "pc={pc:04X} a={a:02X} x={x:02X} y={y:02X} p={p:02X} sp={sp:02X} cycles={} frame={} scanline={scanline} dot={dot}",
+        nes.debug_total_cycles(),
+        nes.debug_counters().frame_count,
+    )
+}
+
+fn apply(nes: &mut Nes, command: &ProbeCommand, paused: bool) -> Result<(String, Option<bool>)> {
+    if !nes.has_rom() && !matches!(command, ProbeCommand::Status) {
+        bail!("no ROM loaded");
+    }
+    Ok(match command {
+        ProbeCommand::Regs => (registers(nes), None),
+        ProbeCommand::Read { addr, len } => {
+            let bytes: Vec<String> = (0..*len)
+                .map(|offset| {
+                    format!(
+                        "{:02X}",
+                        nes.debug_peek_cpu(addr.wrapping_add(offset as u16))
+                    )
+                })
+                .collect();
+            (bytes.join(" "), None)
+        }
+        ProbeCommand::Write { addr, bytes } => {
+            for (offset, &value) in bytes.iter().enumerate() {
+                nes.debug_poke_cpu(addr.wrapping_add(offset as u16), value)?;
+            }
+            (String::new(), None)
+        }
+        ProbeCommand::Step(count) => {
+            for _ in 0..*count {
+                nes.debug_step();
+            }
+            (registers(nes), Some(true))
+        }
+        ProbeCommand::Pause => (String::new(), Some(true)),
+        ProbeCommand::Continue => (String::new(), Some(false)),
+        ProbeCommand::Break(addr) => {
+            nes.add_breakpoint(*addr);
+            (String::new(), None)
+        }
+        ProbeCommand::Delete(addr) => {
+            if !nes.remove_breakpoint(*addr) {
+                bail!("no breakpoint at ${addr:04X}");
+            }
+            (String::new(), None)
+        }
+        ProbeCommand::Breakpoints => {
+            let list: Vec<String> = nes
+                .breakpoints()
+                .map(|addr| format!("{addr:04X}"))
+                .collect();
+            (list.join(" "), None)
+        }
+        ProbeCommand::Reset => {
+            nes.reset();
+            (String::new(), None)
+        }
+        ProbeCommand::Status => {
+            let state = if paused { "paused" } else { "running" };
+            (state.to_string(), None)
+        }
+    })
+}
+
+fn parse_hex(text: &str) -> Result<u32> {
+    let digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u32::from_str_radix(digits, 16).map_err(|_| anyhow!("bad hex number {text:?}"))
+}
+
+fn parse_addr(text: &str) -> Result<u16> {
+    u16::try_from(parse_hex(text)?).map_err(|_| anyhow!("address {text:?} is out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NROM image: `loop: INC $10; JMP loop` at $C000.
+    fn counter_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[..6].copy_from_slice(b"NES\x1A\x01\x01");
+        rom[16..21].copy_from_slice(&[0xE6, 0x10, 0x4C, 0x00, 0xC0]);
+        rom[16 + 0x3FFA..16 + 0x4000].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+        rom
+    }
+
+    #[test]
+    fn requests_read_write_and_stop_on_breakpoints() {
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&counter_rom()).unwrap();
+
+        let reply = handle_request(&mut nes, "write $0300 12 34ab", false);
+        assert_eq!(reply.line, "ok");
+        assert_eq!(
+            handle_request(&mut nes, "read 300 3", false).line,
+            "ok 12 34 AB"
+        );
+        assert_eq!(handle_request(&mut nes, "read C000", false).line, "ok E6");
+        assert!(
+            handle_request(&mut nes, "write 2000 00", false)
+                .line
+                .starts_with("error")
+        );
+
+        assert_eq!(handle_request(&mut nes, "break C002", false).line, "ok");
+        nes.run_frame();
+        assert_eq!(nes.take_breakpoint_hit(), Some(0xC002));
+        assert_eq!(nes.debug_pc(), 0xC002);
+        // Resuming leaves the breakpoint and stops on it the next time round.
+        nes.run_frame();
+        assert_eq!(nes.take_breakpoint_hit(), Some(0xC002));
+        assert_eq!(nes.debug_peek_cpu(0x10), 2);
+
+        let reply = handle_request(&mut nes, "step 2", false);
+        assert_eq!(reply.pause, Some(true));
+        assert!(reply.line.starts_with("ok pc=C002"), "{}", reply.line);
+        assert_eq!(
+            handle_request(&mut nes, "breakpoints", true).line,
+            "ok C002"
+        );
+        assert_eq!(handle_request(&mut nes, "delete C002", true).line, "ok");
+        assert!(
+            handle_request(&mut nes, "frobnicate", true)
+                .line
+                .starts_with("error")
+        );
+        assert!(
+            handle_request(&mut nes, "step 10001", true)
+                .line
+                .starts_with("error")
+        );
+        assert_eq!(nes.debug_pc(), 0xC002);
+    }
+
+    #[test]
+    fn writes_to_mirroring_registers_reach_the_ppu() {
+        // MMC3, horizontal mirroring from the header.
+        let mut rom = vec![0u8; 16 + 0x8000 + 0x2000];
+        rom[..7].copy_from_slice(b"NES\x1A\x02\x01\x40");
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&rom).unwrap();
+
+        assert_eq!(handle_request(&mut nes, "write A000 00", true).line, "ok");
+        nes.cpu_write(0x2006, 0x24);
+        nes.cpu_write(0x2006, 0x00);
+        // The address reaches v a dot after the write.
+        nes.debug_step();
+        nes.cpu_write(0x2007, 0x77);
+        // Vertical: $2400 is the second page of CIRAM.
+        assert_eq!(nes.debug_peek_vram(0x400), 0x77);
+        assert_eq!(nes.debug_peek_vram(0x000), 0x00);
+    }
+}
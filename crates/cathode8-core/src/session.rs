@@ -0,0 +1,721 @@
+//! Session timeline: one place that decides how rewind, movies and save
+//! states interact, so frontends don't each invent their own rules.
+//!
+//! Every frame run through [`SessionTimeline::run_frame`] advances a frame
+//! counter; save states and rewind snapshots remember the frame they were
+//! taken on, and the active movie remembers the frame it started on. Jumping
+//! the machine to an earlier point then follows one policy:
+//!
+//! - While recording, loading a state or rewinding branches the movie: inputs
+//!   after the target frame are discarded and recording continues from there.
+//! - While playing back, the playback cursor follows the jump.
+//! - If the target lies outside the movie (before it started, or past the
+//!   inputs it holds) the movie can't stay in sync, so it is stopped. A
+//!   stopped recording keeps the inputs it already had.
+//!
+//! Rewind snapshots newer than a jump target belong to an abandoned branch
+//! and are dropped.
+//!
+//! Console resets go through [`SessionTimeline::reset_console`] so a
+//! recording stores them as events and playback repeats them on the same
+//! frame, instead of the reset silently desyncing the movie.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::nes::Nes;
+use crate::nes::rewind::RewindBuffer;
+
+/// Button letters of the text input format, from bit 7 down to bit 0.
+const TEXT_BUTTONS: [u8; 8] = *b"RLDUTSBA";
+
+/// Controller 1 inputs recorded from a known machine state.
+#[derive(Debug, Clone)]
+pub struct Movie {
+    start_state: Vec<u8>,
+    inputs: Vec<u8>,
+    /// Movie positions (frames into the movie) at which the console is reset
+    /// before that frame runs, ascending.
+    resets: Vec<usize>,
+}
+
+impl Movie {
+    pub fn start_state(&self) -> &[u8] {
+        &self.start_state
+    }
+
+    /// One controller byte per frame, in frame order.
+    pub fn inputs(&self) -> &[u8] {
+        &self.inputs
+    }
+
+    /// Frames before which the console was reset, ascending.
+    pub fn resets(&self) -> &[usize] {
+        &self.resets
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Frames of input captured so far. While recording this grows by one
+    /// per emulated frame, so a frontend or script can check that the
+    /// recording is live.
+    pub fn frames_recorded(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Original layout, without reset events. Still read.
+    const FILE_MAGIC_V1: [u8; 4] = *b"C8MV";
+    const FILE_MAGIC: [u8; 4] = *b"C8M2";
+
+    /// Writes the movie as the magic, the start state's length (u32 LE), the
+    /// start state, the reset count (u32 LE) and that many reset positions
+    /// (u32 LE each), and then one input byte per frame.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut bytes = Vec::with_capacity(
+            12 + self.start_state.len() + self.resets.len() * 4 + self.inputs.len(),
+        );
+        bytes.extend_from_slice(&Self::FILE_MAGIC);
+        bytes.extend_from_slice(&(self.start_state.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.start_state);
+        bytes.extend_from_slice(&(self.resets.len() as u32).to_le_bytes());
+        for &reset in &self.resets {
+            bytes.extend_from_slice(&(reset as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.inputs);
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)?;
+        let truncated = || anyhow!("Movie file is truncated");
+        let (magic, rest) = bytes.split_first_chunk::<4>().ok_or_else(truncated)?;
+        if *magic != Self::FILE_MAGIC && *magic != Self::FILE_MAGIC_V1 {
+            return Err(anyhow!("Invalid movie magic"));
+        }
+        let (state_len, rest) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+        let state_len = u32::from_le_bytes(*state_len) as usize;
+        if rest.len() < state_len {
+            return Err(truncated());
+        }
+        let (start_state, mut rest) = rest.split_at(state_len);
+        let mut resets = Vec::new();
+        if *magic == Self::FILE_MAGIC {
+            let (count, tail) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+            rest = tail;
+            for _ in 0..u32::from_le_bytes(*count) {
+                let (reset, tail) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+                resets.push(u32::from_le_bytes(*reset) as usize);
+                rest = tail;
+            }
+            if !resets.is_sorted() {
+                return Err(anyhow!("Movie reset events are out of order"));
+            }
+        }
+        Ok(Self {
+            start_state: start_state.to_vec(),
+            inputs: rest.to_vec(),
+            resets,
+        })
+    }
+
+    /// Inputs as text for hand-written or attached reproductions: one
+    /// `frame,buttons[,reset]` line per frame where the input changes or the
+    /// console is reset, plus the last frame. Buttons are the letters
+    /// `RLDUTSBA` (Right, Left, Down, Up, sTart, Select, B, A) with `.` for a
+    /// released button. The start state isn't included; text movies play
+    /// from power-on, with a `seed,N` line giving the session seed (see
+    /// [`Nes::set_session_seed`]) when it isn't zero.
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# Cathode-8 input: frame,buttons[,reset]\n");
+        let seed = Nes::state_session_seed(&self.start_state).unwrap_or(0);
+        if seed != 0 {
+            text.push_str(&format!("seed,{seed}\n"));
+        }
+        let mut previous = None;
+        for (frame, &input) in self.inputs.iter().enumerate() {
+            let reset = self.resets.contains(&frame);
+            if previous != Some(input) || reset || frame + 1 == self.inputs.len() {
+                let buttons: String = TEXT_BUTTONS
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &letter)| {
+                        if input & (0x80 >> index) != 0 {
+                            letter as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                text.push_str(&format!("{frame},{buttons}"));
+                if reset {
+                    text.push_str(",reset");
+                }
+                text.push('\n');
+            }
+            previous = Some(input);
+        }
+        text
+    }
+
+    /// Parses the [`Movie::to_text`] format. Lines may skip frames: each one
+    /// holds its buttons until the next, and the movie ends after the last
+    /// listed frame. Button letters are case-insensitive and in any order;
+    /// `#` starts a comment.
+    pub fn from_text(text: &str, start_state: Vec<u8>) -> Result<Self> {
+        Self::text_seed(text)?;
+        let mut inputs: Vec<u8> = Vec::new();
+        let mut resets = Vec::new();
+        let mut held = 0u8;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| anyhow!("line {}: {message}", index + 1);
+            let mut fields = line.split(',').map(str::trim);
+            let frame_field = fields.next().unwrap_or_default();
+            if frame_field.eq_ignore_ascii_case("seed") {
+                continue;
+            }
+            let frame: usize = frame_field
+                .parse()
+                .map_err(|_| error(format!("invalid frame {frame_field:?}")))?;
+            if frame < inputs.len() {
+                return Err(error(format!(
+                    "frame {frame} is not after the previous line"
+                )));
+            }
+            inputs.resize(frame, held);
+
+            held = 0;
+            for letter in fields.next().unwrap_or_default().chars() {
+                let letter = letter.to_ascii_uppercase();
+                if letter == '.' || letter == ' ' {
+                    continue;
+                }
+                let bit = TEXT_BUTTONS
+                    .iter()
+                    .position(|&known| known as char == letter)
+                    .ok_or_else(|| error(format!("unknown button {letter:?}")))?;
+                held |= 0x80 >> bit;
+            }
+            inputs.push(held);
+
+            match fields.next() {
+                None | Some("") => {}
+                Some(flag) if flag.eq_ignore_ascii_case("reset") => resets.push(frame),
+                Some(other) => return Err(error(format!("unknown flag {other:?}"))),
+            }
+            if fields.next().is_some() {
+                bail!("line {}: too many fields", index + 1);
+            }
+        }
+        Ok(Self {
+            start_state,
+            inputs,
+            resets,
+        })
+    }
+
+    /// Imports an FCEUX `.fm2` movie (text form, starting from power-on),
+    /// taking controller 1 from `port0`. Soft and hard resets in the command
+    /// column both become resets. Returns the movie and the header's
+    /// `romChecksum` (`base64:` + MD5 of PRG and CHR), if present.
+    pub fn from_fm2(text: &str, start_state: Vec<u8>) -> Result<(Self, Option<String>)> {
+        let mut inputs = Vec::new();
+        let mut resets = Vec::new();
+        let mut checksum = None;
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| anyhow!("line {}: {message}", index + 1);
+            let Some(record) = line.strip_prefix('|') else {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                match (key, value.trim()) {
+                    ("romChecksum", value) => checksum = Some(value.to_string()),
+                    ("binary", "1") => bail!("binary FM2 movies aren't supported"),
+                    ("savestate", _) => {
+                        bail!("FM2 movies starting from a savestate aren't supported")
+                    }
+                    _ => {}
+                }
+                continue;
+            };
+            let mut fields = record.split('|');
+            let commands: u32 = fields
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .parse()
+                .map_err(|_| error("invalid command field"))?;
+            // 1 = soft reset, 2 = power cycle.
+            if commands & 0x03 != 0 {
+                resets.push(inputs.len());
+            }
+            let port0 = fields.next().ok_or_else(|| error("missing port0"))?;
+            let mut buttons = 0u8;
+            for (bit, letter) in port0.chars().take(8).enumerate() {
+                if letter != '.' && letter != ' ' {
+                    buttons |= 0x80 >> bit;
+                }
+            }
+            inputs.push(buttons);
+        }
+        if inputs.is_empty() {
+            bail!("FM2 movie has no input records");
+        }
+        Ok((
+            Self {
+                start_state,
+                inputs,
+                resets,
+            },
+            checksum,
+        ))
+    }
+
+    /// The session seed a text movie asks for, 0 if it has no `seed` line.
+    /// Set it before the power cycle that produces the start state.
+    pub fn text_seed(text: &str) -> Result<u64> {
+        let mut seed = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(',') else {
+                continue;
+            };
+            if !key.trim().eq_ignore_ascii_case("seed") {
+                continue;
+            }
+            let value = value.trim();
+            let parsed = value
+                .parse()
+                .map_err(|_| anyhow!("line {}: invalid seed {value:?}", index + 1))?;
+            if seed.replace(parsed).is_some() {
+                bail!("line {}: seed given twice", index + 1);
+            }
+        }
+        Ok(seed.unwrap_or(0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieMode {
+    Idle,
+    Recording,
+    Playing,
+}
+
+/// A save state tagged with the timeline frame it was taken on.
+#[derive(Debug, Clone)]
+pub struct TimelineState {
+    frame: u64,
+    data: Vec<u8>,
+}
+
+impl TimelineState {
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+pub struct SessionTimeline {
+    frame: u64,
+    movie: Option<Movie>,
+    movie_start_frame: u64,
+    mode: MovieMode,
+    rewind: RewindBuffer,
+}
+
+impl SessionTimeline {
+    /// Keeps up to `rewind_capacity` snapshots, one every `rewind_interval`
+    /// frames. A capacity of 0 disables rewind.
+    pub fn new(rewind_capacity: usize, rewind_interval: u64) -> Self {
+        Self {
+            frame: 0,
+            movie: None,
+            movie_start_frame: 0,
+            mode: MovieMode::Idle,
+            rewind: RewindBuffer::new(rewind_capacity, rewind_interval),
+        }
+    }
+
+    /// Changes how much rewind history is kept, dropping what there is.
+    pub fn set_rewind(&mut self, capacity: usize, interval: u64) {
+        self.rewind = RewindBuffer::new(capacity, interval);
+    }
+
+    pub fn rewind_buffer(&self) -> &RewindBuffer {
+        &self.rewind
+    }
+
+    /// Forgets all history. Call after loading a different ROM.
+    pub fn reset(&mut self) {
+        self.frame = 0;
+        self.movie = None;
+        self.movie_start_frame = 0;
+        self.mode = MovieMode::Idle;
+        self.rewind.clear();
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn mode(&self) -> MovieMode {
+        self.mode
+    }
+
+    pub fn movie(&self) -> Option<&Movie> {
+        self.movie.as_ref()
+    }
+
+    /// Frames played back so far, or frames recorded so far while recording.
+    pub fn movie_position(&self) -> usize {
+        (self.frame.saturating_sub(self.movie_start_frame)) as usize
+    }
+
+    pub fn rewind_snapshots(&self) -> usize {
+        self.rewind.len()
+    }
+
+    pub fn start_recording(&mut self, nes: &Nes) -> Result<()> {
+        self.movie = Some(Movie {
+            start_state: nes.save_state_to_vec()?,
+            inputs: Vec::new(),
+            resets: Vec::new(),
+        });
+        self.movie_start_frame = self.frame;
+        self.mode = MovieMode::Recording;
+        Ok(())
+    }
+
+    /// Restores the movie's start state and replays its inputs from the next
+    /// frame on. Rewind history is cleared since the machine jumped.
+    pub fn start_playback(&mut self, nes: &mut Nes, movie: Movie) -> Result<()> {
+        nes.load_state_from_slice(&movie.start_state)?;
+        self.rewind.clear();
+        self.movie = Some(movie);
+        self.movie_start_frame = self.frame;
+        self.mode = MovieMode::Playing;
+        Ok(())
+    }
+
+    /// Ends recording or playback and hands back the movie, if any.
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        self.mode = MovieMode::Idle;
+        self.movie.take()
+    }
+
+    /// Presses reset on the console. A recording stores the reset so playback
+    /// repeats it; during playback the live reset isn't part of the movie, so
+    /// playback stops and the movie is kept.
+    pub fn reset_console(&mut self, nes: &mut Nes) {
+        nes.reset();
+        match self.mode {
+            MovieMode::Recording => {
+                let position = self.movie_position();
+                if let Some(movie) = self.movie.as_mut()
+                    && movie.resets.last() != Some(&position)
+                {
+                    movie.resets.push(position);
+                }
+            }
+            MovieMode::Playing => self.mode = MovieMode::Idle,
+            MovieMode::Idle => {}
+        }
+    }
+
+    /// Runs one frame. During playback the movie's input replaces `live_input`;
+    /// once the movie runs out, playback ends and live input takes over.
+    pub fn run_frame(&mut self, nes: &mut Nes, live_input: u8) -> Result<()> {
+        self.run_frame_inner(nes, live_input, true)
+    }
+
+    /// [`SessionTimeline::run_frame`] through [`Nes::run_frame_skip_render`].
+    pub fn run_frame_skip_render(&mut self, nes: &mut Nes, live_input: u8) -> Result<()> {
+        self.run_frame_inner(nes, live_input, false)
+    }
+
+    fn run_frame_inner(&mut self, nes: &mut Nes, live_input: u8, render: bool) -> Result<()> {
+        let mut input = live_input;
+        if self.mode == MovieMode::Playing {
+            let position = self.movie_position();
+            match self
+                .movie
+                .as_ref()
+                .and_then(|movie| Some((movie.inputs.get(position)?, movie)))
+            {
+                Some((&recorded, movie)) => {
+                    input = recorded;
+                    if movie.resets.binary_search(&position).is_ok() {
+                        nes.reset();
+                    }
+                }
+                None => self.mode = MovieMode::Idle,
+            }
+        }
+
+        nes.set_controller_state(input);
+        if render {
+            nes.run_frame();
+        } else {
+            nes.run_frame_skip_render();
+        }
+
+        if self.mode == MovieMode::Recording
+            && let Some(movie) = self.movie.as_mut()
+        {
+            movie.inputs.push(input);
+        }
+        self.frame += 1;
+
+        if self.rewind.wants(self.frame) {
+            self.rewind.push(self.frame, nes.save_state_to_vec()?);
+        }
+        Ok(())
+    }
+
+    pub fn save_state(&self, nes: &Nes) -> Result<TimelineState> {
+        Ok(TimelineState {
+            frame: self.frame,
+            data: nes.save_state_to_vec()?,
+        })
+    }
+
+    pub fn load_state(&mut self, nes: &mut Nes, state: &TimelineState) -> Result<()> {
+        nes.load_state_from_slice(&state.data)?;
+        self.jump_to(state.frame);
+        Ok(())
+    }
+
+    /// Steps back to the newest snapshot at least `frames` frames old.
+    /// Returns `false` when history doesn't reach that far.
+    pub fn rewind(&mut self, nes: &mut Nes, frames: u64) -> Result<bool> {
+        let target = self.frame.saturating_sub(frames);
+        let Some((frame, data)) = self.rewind.newest_at_or_before(target)? else {
+            return Ok(false);
+        };
+        self.load_state(nes, &TimelineState { frame, data })?;
+        Ok(true)
+    }
+
+    fn jump_to(&mut self, frame: u64) {
+        self.frame = frame;
+        self.rewind.truncate_after(frame);
+
+        if self.mode == MovieMode::Idle {
+            return;
+        }
+        let in_movie = frame >= self.movie_start_frame
+            && self
+                .movie
+                .as_ref()
+                .is_some_and(|movie| self.movie_position() <= movie.inputs.len());
+        if !in_movie {
+            self.mode = MovieMode::Idle;
+            return;
+        }
+        if self.mode == MovieMode::Recording {
+            let position = self.movie_position();
+            if let Some(movie) = self.movie.as_mut() {
+                movie.inputs.truncate(position);
+                movie.resets.retain(|&reset| reset < position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NROM-128 image whose reset handler increments $00 forever and whose NMI
+    /// handler copies the controller 1 A bit into $01 each frame.
+    fn counter_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 16 + 0x4000 + 0x2000];
+        rom[0..6].copy_from_slice(b"NES\x1A\x01\x01");
+        let program: &[u8] = &[
+            0xA9, 0x80, // LDA #$80
+            0x8D, 0x00, 0x20, // STA $2000 (enable NMI)
+            0xE6, 0x00, // loop: INC $00
+            0x4C, 0x05, 0xC0, // JMP loop
+            // NMI at $C00A
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x16, 0x40, // STA $4016
+            0xAD, 0x16, 0x40, // LDA $4016
+            0x29, 0x01, // AND #$01
+            0x85, 0x01, // STA $01
+            0x40, // RTI
+        ];
+        rom[16..16 + program.len()].copy_from_slice(program);
+        let vectors = 16 + 0x3FFA;
+        rom[vectors..vectors + 6].copy_from_slice(&[0x0A, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+        rom
+    }
+
+    fn booted_nes() -> Nes {
+        let mut nes = Nes::new();
+        nes.load_rom_from_bytes(&counter_rom()).unwrap();
+        nes
+    }
+
+    fn run(timeline: &mut SessionTimeline, nes: &mut Nes, inputs: &[u8]) {
+        for &input in inputs {
+            timeline.run_frame(nes, input).unwrap();
+        }
+    }
+
+    #[test]
+    fn text_inputs_round_trip_and_hold_between_lines() {
+        let movie = Movie::from_text(
+            "# repro\n0,\n2,a\n3,rT,reset\n5,R..U...A # jump\n",
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(movie.inputs(), &[0, 0, 0x01, 0x88, 0x88, 0x91]);
+        assert_eq!(movie.resets(), &[3]);
+
+        let text = movie.to_text();
+        assert!(text.contains("\n3,R...T...,reset\n"), "{text}");
+        let again = Movie::from_text(&text, Vec::new()).unwrap();
+        assert_eq!(again.inputs(), movie.inputs());
+        assert_eq!(again.resets(), movie.resets());
+
+        assert!(Movie::from_text("4,A\n2,B", Vec::new()).is_err());
+        assert!(Movie::from_text("0,X", Vec::new()).is_err());
+
+        let seeded = "seed,42\n0,A\n";
+        assert_eq!(Movie::text_seed(seeded).unwrap(), 42);
+        assert_eq!(Movie::from_text(seeded, Vec::new()).unwrap().inputs(), &[1]);
+        assert_eq!(Movie::text_seed(&text).unwrap(), 0);
+    }
+
+    #[test]
+    fn fm2_port0_buttons_and_resets_are_imported() {
+        let fm2 = "version 3\nromChecksum base64:AAAAAAAAAAAAAAAAAAAAAA==\n\
+                   |0|........|........||\n|1|R......A|........||\n|0|...U....|||\n";
+        let (movie, checksum) = Movie::from_fm2(fm2, Vec::new()).unwrap();
+        assert_eq!(movie.inputs(), &[0, 0x81, 0x10]);
+        assert_eq!(movie.resets(), &[1]);
+        assert_eq!(checksum.as_deref(), Some("base64:AAAAAAAAAAAAAAAAAAAAAA=="));
+        assert!(Movie::from_fm2("binary 1\n", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn loading_state_while_recording_branches_movie() {
+        let mut nes = booted_nes();
+        let mut timeline = SessionTimeline::new(0, 1);
+        timeline.start_recording(&nes).unwrap();
+        run(&mut timeline, &mut nes, &[1, 1, 0]);
+        let state = timeline.save_state(&nes).unwrap();
+        run(&mut timeline, &mut nes, &[1, 1, 1, 1]);
+        assert_eq!(timeline.movie().unwrap().len(), 7);
+
+        timeline.load_state(&mut nes, &state).unwrap();
+        assert_eq!(timeline.mode(), MovieMode::Recording);
+        assert_eq!(timeline.movie().unwrap().inputs(), &[1, 1, 0]);
+
+        run(&mut timeline, &mut nes, &[0, 1]);
+        assert_eq!(timeline.movie().unwrap().inputs(), &[1, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn loading_state_from_before_recording_stops_movie() {
+        let mut nes = booted_nes();
+        let mut timeline = SessionTimeline::new(0, 1);
+        run(&mut timeline, &mut nes, &[0]);
+        let early = timeline.save_state(&nes).unwrap();
+        run(&mut timeline, &mut nes, &[0]);
+        timeline.start_recording(&nes).unwrap();
+        run(&mut timeline, &mut nes, &[1, 1]);
+
+        timeline.load_state(&mut nes, &early).unwrap();
+        assert_eq!(timeline.mode(), MovieMode::Idle);
+        assert_eq!(timeline.movie().unwrap().inputs(), &[1, 1]);
+    }
+
+    #[test]
+    fn rewinding_while_recording_truncates_movie_and_history() {
+        let mut nes = booted_nes();
+        let mut timeline = SessionTimeline::new(8, 2);
+        timeline.start_recording(&nes).unwrap();
+        run(&mut timeline, &mut nes, &[1, 0, 1, 0, 1, 0, 1]);
+        assert_eq!(timeline.rewind_snapshots(), 3);
+
+        assert!(timeline.rewind(&mut nes, 3).unwrap());
+        assert_eq!(timeline.frame(), 4);
+        assert_eq!(timeline.movie().unwrap().inputs(), &[1, 0, 1, 0]);
+        assert_eq!(timeline.rewind_snapshots(), 2);
+
+        assert!(!timeline.rewind(&mut nes, 10).unwrap());
+        assert_eq!(timeline.frame(), 4);
+    }
+
+    #[test]
+    fn playback_follows_rewind_and_reproduces_recording() {
+        let mut nes = booted_nes();
+        let mut timeline = SessionTimeline::new(8, 1);
+        timeline.start_recording(&nes).unwrap();
+        let inputs = [1, 0, 0, 1, 1, 0];
+        run(&mut timeline, &mut nes, &inputs);
+        let recorded_ram = (
+            nes.debug_peek_internal_ram(0),
+            nes.debug_peek_internal_ram(1),
+        );
+        let movie = timeline.stop_movie().unwrap();
+
+        run(&mut timeline, &mut nes, &[0, 0, 0]);
+        timeline.start_playback(&mut nes, movie).unwrap();
+        run(&mut timeline, &mut nes, &[0; 4]);
+        assert!(timeline.rewind(&mut nes, 2).unwrap());
+        assert_eq!(timeline.mode(), MovieMode::Playing);
+        assert_eq!(timeline.movie_position(), 2);
+
+        run(&mut timeline, &mut nes, &[0; 4]);
+        assert_eq!(timeline.mode(), MovieMode::Playing);
+        assert_eq!(
+            (
+                nes.debug_peek_internal_ram(0),
+                nes.debug_peek_internal_ram(1)
+            ),
+            recorded_ram
+        );
+
+        run(&mut timeline, &mut nes, &[0]);
+        assert_eq!(timeline.mode(), MovieMode::Idle);
+    }
+
+    #[test]
+    fn recorded_reset_is_replayed_and_saved() {
+        let mut nes = booted_nes();
+        let mut timeline = SessionTimeline::new(0, 1);
+        timeline.start_recording(&nes).unwrap();
+        run(&mut timeline, &mut nes, &[1, 1]);
+        timeline.reset_console(&mut nes);
+        run(&mut timeline, &mut nes, &[0, 1, 0]);
+        let recorded_counter = nes.debug_peek_internal_ram(0);
+        let movie = timeline.stop_movie().unwrap();
+        assert_eq!(movie.resets(), &[2]);
+
+        let path = std::env::temp_dir().join(format!("cathode8-movie-{}.c8m", std::process::id()));
+        movie.save(&path).unwrap();
+        let loaded = Movie::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.resets(), movie.resets());
+        assert_eq!(loaded.inputs(), movie.inputs());
+
+        run(&mut timeline, &mut nes, &[0; 3]);
+        timeline.start_playback(&mut nes, loaded).unwrap();
+        run(&mut timeline, &mut nes, &[0; 5]);
+        assert_eq!(nes.debug_peek_internal_ram(0), recorded_counter);
+    }
+}
@@ -0,0 +1,3731 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions, emath::GuiRounding as _};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::AudioOutput;
+use crate::boards_view::BoardsView;
+use crate::corruptor::Corruptor;
+use crate::debugger_view::{DebuggerRequest, DebuggerView};
+use crate::frame_dump::FrameDump;
+use crate::gamepad::{Gamepads, HotPlug};
+use crate::heatmap_viewer::HeatmapViewer;
+use crate::hotkeys::{Action, HotkeyMap};
+use crate::input_macro::MacroBank;
+use crate::input_profile::{InputProfiles, KEYBOARD_DEVICE};
+use crate::library::{Library, LibraryConfig};
+use crate::logging::{self, LogHandle, Subsystem};
+use crate::ppu_viewer::{self, PpuViewer};
+use crate::practice::{PracticeConfig, PracticeLibrary, PracticeRequest};
+use crate::probe_server::{DEFAULT_PROBE_PORT, ProbeServer};
+use crate::source_view::{SourceRequest, SourceView};
+use crate::spectrum::SpectrumAnalyzer;
+use crate::speedrun::{self, LiveSplitLink, SpeedrunConfig, SpeedrunTimer};
+use crate::state_diff_viewer::StateDiffViewer;
+use crate::storage::{self, StorageDirs, StorageKind};
+use crate::video::{AspectRatio, FilterChain, FilterOrder, FrameBlender, Scaler, VideoPreset};
+use cathode8_core::autosplit::{AutoSplitter, SplitEvent};
+use cathode8_core::latency::{InputLatencyProbe, ProbeStatus};
+use cathode8_core::nes::apu::{ExpansionChip, ExpansionFilters};
+use cathode8_core::nes::debug_snapshot::DebugSnapshot;
+use cathode8_core::nes::frame_events::RasterEventKind;
+use cathode8_core::nes::input::{ControllerPort, PaddleInput};
+use cathode8_core::nes::palette::NtscPaletteParams;
+use cathode8_core::nes::ppu_view::PpuImage;
+use cathode8_core::nes::{
+    BUTTON_A, DEFAULT_FRAME_GUARD_STEPS, FrameGuardTrip, Nes, Region, VectorOverrides, WatchKind,
+};
+use cathode8_core::probe;
+use cathode8_core::session::{Movie, MovieMode, SessionTimeline, TimelineState};
+
+const HIGH_REFRESH_RATE_HZ: f64 = 240.0;
+const MAX_FRAMES_PER_UPDATE: u32 = 2;
+/// Catch-up limit with frame-skip on; only the last of these frames is drawn.
+const MAX_FRAMES_PER_UPDATE_SKIPPING: u32 = 4;
+const LOG_PANEL_LINES: usize = 400;
+const DEBUG_EVENT_LINES: usize = 8;
+/// Battery RAM writes are gathered for this long before hitting the disk;
+/// games rewrite their save area many times while saving.
+const SRAM_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+const VIDEO_PRESET_KEY: &str = "video_preset";
+const USER_PRESETS_KEY: &str = "video_user_presets";
+const STORAGE_DIRS_KEY: &str = "storage_dirs";
+const LIBRARY_KEY: &str = "library";
+const INPUT_PROFILES_KEY: &str = "input_profiles";
+const HOTKEYS_KEY: &str = "hotkeys";
+const FRAME_GUARD_KEY: &str = "frame_guard_steps";
+const CONFIRM_RESET_KEY: &str = "confirm_reset";
+const FORCE_UNSCALED_UI_KEY: &str = "force_unscaled_ui";
+const REWIND_KEY: &str = "rewind";
+const UNFOCUSED_KEY: &str = "unfocused_behavior";
+const SESSION_SEED_KEY: &str = "session_seed";
+const SPEEDRUN_KEY: &str = "speedrun";
+const PRACTICE_KEY: &str = "practice";
+const RASTER_FLASHES_KEY: &str = "raster_flashes";
+const PROBE_PORT_KEY: &str = "probe_port";
+const EXPANSION_FILTERS_KEY: &str = "expansion_filters";
+/// Muted background play runs at this fraction of full speed.
+const BACKGROUND_SLOWDOWN: u32 = 4;
+/// Recent core events listed in the stuck-frame dialog.
+const FRAME_GUARD_EVENT_LINES: usize = 16;
+/// Fast-forward runs this many frames per real frame.
+const FAST_FORWARD_SPEED: u32 = 4;
+/// Time spent running and writing frame-dump frames per update, so the
+/// window stays responsive while dumping.
+const FRAME_DUMP_BUDGET: Duration = Duration::from_millis(25);
+/// Numbered save-state files per ROM. Slot 0 keeps the original
+/// `<rom>.state` name.
+const STATE_SLOTS: usize = 10;
+/// Device in each player slot for profile auto-select. Gamepads drive
+/// players through their own fixed layout (see [`crate::gamepad`]), not
+/// profiles.
+const INPUT_DEVICES: [&str; 1] = [KEYBOARD_DEVICE];
+
+/// Maps between the on-screen game image and NES pixel coordinates.
+///
+/// `source` is the region of the 256x240 frame that is shown in `rect`, so
+/// cropping (overscan) or output scaling only has to update this mapping for
+/// every pointer consumer (zapper, viewers, pixel inspector) to stay correct.
+#[derive(Debug, Clone, Copy)]
+struct ScreenMapping {
+    rect: egui::Rect,
+    source: egui::Rect,
+}
+
+impl ScreenMapping {
+    /// NES pixel under `pos`, or `None` when the pointer is outside the image.
+    fn nes_pixel(&self, pos: egui::Pos2) -> Option<(u8, u8)> {
+        if !self.rect.contains(pos) || self.rect.width() <= 0.0 || self.rect.height() <= 0.0 {
+            return None;
+        }
+
+        let u = (pos.x - self.rect.left()) / self.rect.width();
+        let v = (pos.y - self.rect.top()) / self.rect.height();
+        let x = (self.source.left() + u * self.source.width()).floor();
+        let y = (self.source.top() + v * self.source.height()).floor();
+        Some((x.clamp(0.0, 255.0) as u8, y.clamp(0.0, 239.0) as u8))
+    }
+
+    /// Screen rectangle covering the NES pixel rectangle `nes`.
+    fn screen_rect(&self, nes: egui::Rect) -> egui::Rect {
+        let scale = self.rect.size() / self.source.size();
+        egui::Rect::from_min_max(
+            self.rect.min + (nes.min - self.source.min) * scale,
+            self.rect.min + (nes.max - self.source.min) * scale,
+        )
+    }
+}
+
+/// What emulation does while the window doesn't have focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum UnfocusedBehavior {
+    #[default]
+    KeepRunning,
+    Pause,
+    /// Runs at a reduced rate with audio off, so the game stays live without
+    /// competing for the sound device or the CPU.
+    MutedThrottled,
+}
+
+impl UnfocusedBehavior {
+    const ALL: [UnfocusedBehavior; 3] = [
+        UnfocusedBehavior::KeepRunning,
+        UnfocusedBehavior::Pause,
+        UnfocusedBehavior::MutedThrottled,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            UnfocusedBehavior::KeepRunning => "Keep running",
+            UnfocusedBehavior::Pause => "Pause",
+            UnfocusedBehavior::MutedThrottled => "Run muted at reduced speed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum FlashStyle {
+    #[default]
+    Off,
+    /// Tints the border around the picture, with a tick at each event's
+    /// scanline.
+    Border,
+    /// A small marker per event kind in the bottom-left corner.
+    Corner,
+}
+
+impl FlashStyle {
+    const ALL: [FlashStyle; 3] = [FlashStyle::Off, FlashStyle::Border, FlashStyle::Corner];
+
+    fn label(self) -> &'static str {
+        match self {
+            FlashStyle::Off => "Off",
+            FlashStyle::Border => "Border tint",
+            FlashStyle::Corner => "Corner marker",
+        }
+    }
+}
+
+/// How much rewind history is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct RewindConfig {
+    /// Seconds of history; 0 turns rewind off.
+    seconds: u32,
+    /// Frames between snapshots, which is also how far each rewound frame
+    /// steps back.
+    interval: u64,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            seconds: 20,
+            interval: 2,
+        }
+    }
+}
+
+impl RewindConfig {
+    fn snapshots(self) -> usize {
+        (u64::from(self.seconds) * 60 / self.interval.max(1)) as usize
+    }
+}
+
+/// Background grid drawn over the game picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum GridOverlay {
+    #[default]
+    Off,
+    Tiles,
+    /// 16×16 areas, the unit one attribute palette choice covers.
+    Attributes,
+}
+
+impl GridOverlay {
+    const ALL: [GridOverlay; 3] = [
+        GridOverlay::Off,
+        GridOverlay::Tiles,
+        GridOverlay::Attributes,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            GridOverlay::Off => "No grid",
+            GridOverlay::Tiles => "8×8 tile grid",
+            GridOverlay::Attributes => "16×16 attribute grid",
+        }
+    }
+}
+
+/// Which raster events flash for the frame they happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct RasterFlashes {
+    style: FlashStyle,
+    nmi: bool,
+    irq: bool,
+    sprite0: bool,
+}
+
+impl Default for RasterFlashes {
+    fn default() -> Self {
+        Self {
+            style: FlashStyle::Off,
+            nmi: true,
+            irq: true,
+            sprite0: true,
+        }
+    }
+}
+
+impl RasterFlashes {
+    fn shows(&self, kind: RasterEventKind) -> bool {
+        match kind {
+            RasterEventKind::Nmi => self.nmi,
+            RasterEventKind::Irq => self.irq,
+            RasterEventKind::Sprite0Hit => self.sprite0,
+        }
+    }
+}
+
+fn raster_event_color(kind: RasterEventKind) -> egui::Color32 {
+    match kind {
+        RasterEventKind::Nmi => egui::Color32::from_rgb(255, 140, 0),
+        RasterEventKind::Irq => egui::Color32::from_rgb(230, 60, 230),
+        RasterEventKind::Sprite0Hit => egui::Color32::YELLOW,
+    }
+}
+
+/// An input latency measurement in progress, plus the host-side timing the
+/// core probe can't see.
+struct LatencyRun {
+    probe: InputLatencyProbe,
+    /// When the synthetic press notionally arrives: right after the last
+    /// frame that still had the button released.
+    pressed_at: Option<Instant>,
+    /// How long the press then waited for a frame to pick it up.
+    poll_delay: Option<Duration>,
+}
+
+/// A frame that hit the CPU step limit, with the events leading up to it.
+struct FrameGuardReport {
+    trip: FrameGuardTrip,
+    mapper: String,
+    events: Vec<String>,
+}
+
+impl FrameGuardReport {
+    fn to_text(&self) -> String {
+        let trip = &self.trip;
+        let mut text = format!(
+            "Frame {} stopped after {} CPU steps\nPC=${:04X} scanline {} cycle {}\nMapper: {}\nRecent events (newest first):\n",
+            trip.frame, trip.steps, trip.pc, trip.scanline, trip.cycle, self.mapper
+        );
+        for event in &self.events {
+            text.push_str("  ");
+            text.push_str(event);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+pub struct NesApp {
+    nes: Nes,
+    frame_texture: Option<TextureHandle>,
+    status_line: String,
+    loaded_rom: Option<PathBuf>,
+    screen_mapping: Option<ScreenMapping>,
+    inspect_pixels: bool,
+    sprite_overlay: bool,
+    /// Where the captured mouse points, in NES pixels, while the paddle and
+    /// Zapper are driven by relative motion.
+    pointer_capture: Option<egui::Pos2>,
+    grid_overlay: GridOverlay,
+    /// Labels grid cells with their nametable column and row.
+    grid_coordinates: bool,
+    audio: Option<AudioOutput>,
+    frame_interval: Duration,
+    high_refresh_interval: Duration,
+    next_frame_at: Option<Instant>,
+    paused: bool,
+    latched_controller_state: u8,
+    controller_hold_until: Option<Instant>,
+    update_dt_ema: Option<f64>,
+    estimated_refresh_hz: f64,
+    audio_target_buffer_ms: usize,
+    audio_max_buffer_ms: usize,
+    log: LogHandle,
+    show_log_panel: bool,
+    show_video_window: bool,
+    show_debug_window: bool,
+    show_storage_window: bool,
+    show_library_window: bool,
+    show_boards_window: bool,
+    show_controls_window: bool,
+    show_hotkeys_window: bool,
+    debug_snapshot: Option<DebugSnapshot>,
+    latency_run: Option<LatencyRun>,
+    latency_report: Option<String>,
+    frame_guard_steps: usize,
+    /// Power-on seed for ROM loads; input scripts may bring their own.
+    session_seed: u64,
+    frame_guard_report: Option<FrameGuardReport>,
+    palette_params: NtscPaletteParams,
+    blend_enabled: bool,
+    frame_locked_audio: bool,
+    auto_frameskip: bool,
+    blender: FrameBlender,
+    filters: FilterChain,
+    video: VideoPreset,
+    user_presets: Vec<VideoPreset>,
+    storage: StorageDirs,
+    library: Library,
+    boards_view: BoardsView,
+    input_profiles: InputProfiles,
+    hotkeys: HotkeyMap,
+    preset_name: String,
+    spectrum: Option<SpectrumAnalyzer>,
+    ppu_viewer: Option<PpuViewer>,
+    heatmap_viewer: Option<HeatmapViewer>,
+    macros: MacroBank,
+    timeline: SessionTimeline,
+    quick_state: Option<TimelineState>,
+    last_movie: Option<Movie>,
+    rewinding: bool,
+    fast_forwarding: bool,
+    region_warning_dismissed: bool,
+    confirm_reset: bool,
+    /// Draws the UI at one point per physical pixel whatever the monitor's
+    /// scale factor, for fractional scaling that would blur it.
+    force_unscaled_ui: bool,
+    rewind: RewindConfig,
+    reset_prompt_open: bool,
+    state_slot: usize,
+    show_speedrun_window: bool,
+    speedrun: SpeedrunConfig,
+    speedrun_timer: SpeedrunTimer,
+    autosplitter: Option<AutoSplitter>,
+    autosplit_error: Option<String>,
+    livesplit: Option<LiveSplitLink>,
+    show_practice_window: bool,
+    practice: PracticeLibrary,
+    show_corruptor_window: bool,
+    corruptor: Corruptor,
+    raster_flashes: RasterFlashes,
+    unfocused_behavior: UnfocusedBehavior,
+    window_focused: bool,
+    /// Set when losing focus paused emulation, so regaining it resumes only
+    /// what it paused.
+    focus_paused: bool,
+    gamepads: Gamepads,
+    /// Set when the player's gamepad disconnecting paused emulation.
+    gamepad_paused: bool,
+    /// When battery RAM first changed since it was last written out.
+    sram_dirty_since: Option<Instant>,
+    probe: Option<ProbeServer>,
+    probe_port: u16,
+    show_source_window: bool,
+    source_view: SourceView,
+    show_debugger_window: bool,
+    debugger_view: DebuggerView,
+    show_state_diff_window: bool,
+    state_diff: StateDiffViewer,
+    frame_dump: Option<FrameDump>,
+    /// Frame dump options: frames to run before writing, frames to write
+    /// (0 for until the movie ends) and whether to replay the last movie.
+    dump_skip: u32,
+    dump_frames: u32,
+    dump_from_movie: bool,
+}
+
+/// Files named on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchArgs {
+    pub rom: Option<PathBuf>,
+    /// Text input script played from power-on once the ROM is loaded.
+    pub input_script: Option<PathBuf>,
+    /// Reset vector override, for booting snippets at a fixed address.
+    pub boot: Option<u16>,
+    /// Scratch RAM at $4018-$5FFF for test harnesses.
+    pub dev_ram: bool,
+    /// Port to start the debug probe server on.
+    pub probe: Option<u16>,
+}
+
+impl LaunchArgs {
+    /// `cathode8 [rom.nes] [--input script.txt] [--boot $ADDR] [--dev-ram]
+    /// [--probe PORT]`
+    pub fn parse(mut args: impl Iterator<Item = std::ffi::OsString>) -> anyhow::Result<Self> {
+        let mut launch = Self::default();
+        while let Some(arg) = args.next() {
+            if arg == "--input" {
+                let script = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--input needs a file"))?;
+                launch.input_script = Some(script.into());
+            } else if arg == "--dev-ram" {
+                launch.dev_ram = true;
+            } else if arg == "--probe" {
+                let port = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--probe needs a port"))?;
+                let port = port.to_string_lossy();
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("--probe: invalid port {port:?}"))?;
+                launch.probe = Some(port);
+            } else if arg == "--boot" {
+                let addr = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--boot needs an address"))?;
+                let addr = addr.to_string_lossy();
+                let digits = addr
+                    .trim_start_matches('$')
+                    .trim_start_matches("0x")
+                    .trim_start_matches("0X");
+                let boot = u16::from_str_radix(digits, 16)
+                    .map_err(|_| anyhow::anyhow!("--boot: invalid hex address {addr:?}"))?;
+                launch.boot = Some(boot);
+            } else {
+                launch.rom = Some(arg.into());
+            }
+        }
+        Ok(launch)
+    }
+}
+
+impl NesApp {
+    pub fn new(cc: &eframe::CreationContext<'_>, launch: LaunchArgs) -> Self {
+        let mut app = Self::with_settings(cc);
+        if launch.boot.is_some() {
+            app.nes.set_vector_overrides(VectorOverrides {
+                reset: launch.boot,
+                ..VectorOverrides::default()
+            });
+        }
+        app.nes.set_dev_ram_enabled(launch.dev_ram);
+        if let Some(port) = launch.probe {
+            app.probe_port = port;
+            app.start_probe();
+        }
+        if let Some(rom) = &launch.rom {
+            app.load_rom(rom);
+        }
+        if let Some(script) = &launch.input_script {
+            app.play_input_script(script);
+        }
+        app
+    }
+
+    fn with_settings(cc: &eframe::CreationContext<'_>) -> Self {
+        cc.egui_ctx.set_visuals(egui::Visuals::dark());
+
+        let mut nes = Nes::new();
+        let audio = AudioOutput::new().ok();
+        if let Some(audio_out) = &audio {
+            nes.set_audio_sample_rate(audio_out.sample_rate());
+        } else {
+            nes.set_audio_sample_rate(48_000);
+        }
+
+        let storage = cc.storage;
+        let video: VideoPreset = storage
+            .and_then(|storage| eframe::get_value(storage, VIDEO_PRESET_KEY))
+            .unwrap_or_default();
+        let user_presets: Vec<VideoPreset> = storage
+            .and_then(|storage| eframe::get_value(storage, USER_PRESETS_KEY))
+            .unwrap_or_default();
+        let storage: StorageDirs = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, STORAGE_DIRS_KEY))
+            .unwrap_or_default();
+        let library: LibraryConfig = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, LIBRARY_KEY))
+            .unwrap_or_default();
+        let mut input_profiles: InputProfiles = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, INPUT_PROFILES_KEY))
+            .unwrap_or_default();
+        input_profiles.select_for_devices(&INPUT_DEVICES);
+        let hotkeys = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<HotkeyMap>(storage, HOTKEYS_KEY))
+            .map(HotkeyMap::with_missing_defaults)
+            .unwrap_or_default();
+        let frame_guard_steps = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, FRAME_GUARD_KEY))
+            .unwrap_or(DEFAULT_FRAME_GUARD_STEPS);
+        nes.set_frame_guard_steps(frame_guard_steps);
+        let session_seed = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, SESSION_SEED_KEY))
+            .unwrap_or(0);
+        nes.set_session_seed(session_seed);
+        if let Some(filters) = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, EXPANSION_FILTERS_KEY))
+        {
+            nes.set_expansion_filters(filters);
+        }
+        let confirm_reset = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, CONFIRM_RESET_KEY))
+            .unwrap_or(true);
+        let rewind: RewindConfig = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, REWIND_KEY))
+            .unwrap_or_default();
+        let force_unscaled_ui = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, FORCE_UNSCALED_UI_KEY))
+            .unwrap_or(false);
+        let unfocused_behavior = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, UNFOCUSED_KEY))
+            .unwrap_or_default();
+        let speedrun: SpeedrunConfig = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, SPEEDRUN_KEY))
+            .unwrap_or_default();
+        let autosplitter = speedrun
+            .autosplit
+            .then(|| AutoSplitter::parse(&speedrun.script).ok())
+            .flatten();
+        let raster_flashes = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, RASTER_FLASHES_KEY))
+            .unwrap_or_default();
+        let probe_port = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, PROBE_PORT_KEY))
+            .unwrap_or(DEFAULT_PROBE_PORT);
+        let practice: PracticeConfig = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, PRACTICE_KEY))
+            .unwrap_or_default();
+
+        Self {
+            nes,
+            frame_texture: None,
+            status_line: "Drop a .nes file or click Open ROM".to_string(),
+            loaded_rom: None,
+            screen_mapping: None,
+            inspect_pixels: false,
+            sprite_overlay: false,
+            pointer_capture: None,
+            grid_overlay: GridOverlay::Off,
+            grid_coordinates: true,
+            audio,
+            frame_interval: Duration::from_secs_f64(1.0 / Region::Ntsc.frame_rate_hz()),
+            high_refresh_interval: Duration::from_secs_f64(1.0 / HIGH_REFRESH_RATE_HZ),
+            next_frame_at: None,
+            paused: false,
+            latched_controller_state: 0,
+            controller_hold_until: None,
+            update_dt_ema: None,
+            estimated_refresh_hz: 60.0,
+            audio_target_buffer_ms: 7,
+            audio_max_buffer_ms: 10,
+            log: logging::init(),
+            show_log_panel: false,
+            show_video_window: false,
+            show_debug_window: false,
+            show_storage_window: false,
+            show_library_window: false,
+            show_boards_window: false,
+            show_controls_window: false,
+            show_hotkeys_window: false,
+            debug_snapshot: None,
+            latency_run: None,
+            latency_report: None,
+            frame_guard_steps,
+            session_seed,
+            frame_guard_report: None,
+            palette_params: NtscPaletteParams::default(),
+            blend_enabled: false,
+            frame_locked_audio: false,
+            auto_frameskip: false,
+            blender: FrameBlender::new(0.5),
+            filters: FilterChain::new(video.filters),
+            video,
+            user_presets,
+            storage,
+            library: Library::new(library),
+            boards_view: BoardsView::default(),
+            input_profiles,
+            hotkeys,
+            preset_name: String::new(),
+            spectrum: None,
+            ppu_viewer: None,
+            heatmap_viewer: None,
+            macros: MacroBank::new(),
+            timeline: SessionTimeline::new(rewind.snapshots(), rewind.interval),
+            rewind,
+            quick_state: None,
+            last_movie: None,
+            rewinding: false,
+            fast_forwarding: false,
+            region_warning_dismissed: false,
+            confirm_reset,
+            force_unscaled_ui,
+            reset_prompt_open: false,
+            state_slot: 0,
+            show_speedrun_window: false,
+            speedrun,
+            speedrun_timer: SpeedrunTimer::default(),
+            autosplitter,
+            autosplit_error: None,
+            livesplit: None,
+            show_practice_window: false,
+            practice: PracticeLibrary::new(practice),
+            show_corruptor_window: false,
+            corruptor: Corruptor::default(),
+            raster_flashes,
+            unfocused_behavior,
+            window_focused: true,
+            focus_paused: false,
+            gamepads: Gamepads::new(),
+            gamepad_paused: false,
+            sram_dirty_since: None,
+            probe: None,
+            probe_port,
+            show_source_window: false,
+            source_view: SourceView::new(),
+            show_debugger_window: false,
+            debugger_view: DebuggerView::default(),
+            show_state_diff_window: false,
+            state_diff: StateDiffViewer::default(),
+            frame_dump: None,
+            dump_skip: 0,
+            dump_frames: 600,
+            dump_from_movie: false,
+        }
+    }
+
+    fn load_rom(&mut self, path: &Path) {
+        self.load_rom_with_patch(path, None);
+    }
+
+    /// Loads `path`, optionally with an IPS patch applied in memory.
+    fn load_rom_with_patch(&mut self, path: &Path, patch: Option<(&Path, &[u8])>) {
+        self.stop_frame_dump();
+        self.stop_vgm_log();
+        self.flush_battery_ram(true);
+        self.nes.set_session_seed(self.session_seed);
+        let result = match patch {
+            Some((_, ips)) => self.nes.load_patched_rom(path, ips),
+            None => self.nes.load_rom_from_path(path),
+        };
+        match result {
+            Ok(()) => {
+                if let Ok(bytes) = std::fs::read(path) {
+                    self.library.start_game(&bytes);
+                }
+                self.loaded_rom = Some(path.to_path_buf());
+                let patched = patch.map_or(String::new(), |(patch, _)| {
+                    format!(" patched with {}", file_label(patch))
+                });
+                self.status_line = format!(
+                    "Loaded {}{patched} using {}",
+                    file_label(path),
+                    self.nes.mapper_name()
+                );
+                self.frame_texture = None;
+                self.next_frame_at = None;
+                self.timeline.reset();
+                self.quick_state = None;
+                self.last_movie = None;
+                self.blender.clear();
+                self.practice.clear();
+                self.corruptor.clear();
+                self.region_warning_dismissed = false;
+                self.frame_guard_report = None;
+                self.state_diff.load_for_rom(path);
+                if let Some(note) = self.source_view.load_for_rom(path) {
+                    self.status_line.push_str(&format!(", {note}"));
+                }
+                self.load_battery_ram();
+            }
+            Err(err) => {
+                self.status_line = format!("Failed to load ROM: {err}");
+            }
+        }
+    }
+
+    fn open_rom_dialog(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("NES ROM", &["nes"])
+            .set_title("Open NES ROM")
+            .pick_file()
+        {
+            self.load_rom(&path);
+        }
+    }
+
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|input| input.raw.dropped_files.clone());
+        for path in dropped.into_iter().filter_map(|file| file.path) {
+            self.open_dropped_file(&path);
+        }
+    }
+
+    /// Sends a dropped file to whatever handles its kind, and always says
+    /// what happened, including why nothing did.
+    fn open_dropped_file(&mut self, path: &Path) {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        let needs_game = matches!(extension.as_str(), "state" | "c8m" | "fm2" | "ips" | "sav");
+        if extension == "nes" {
+            self.load_rom(path);
+        } else if is_input_script(path) {
+            self.play_input_script(path);
+        } else if needs_game && !self.nes.has_rom() {
+            self.status_line = format!("Load the game first, then drop {}", file_label(path));
+        } else {
+            match extension.as_str() {
+                "state" => self.load_dropped_state(path),
+                "c8m" => self.open_movie(path),
+                "fm2" => self.play_fm2(path),
+                "ips" => self.apply_ips_patch(path),
+                "sav" => {
+                    self.status_line = format!(
+                        "Can't use {}: battery saves aren't supported yet",
+                        file_label(path)
+                    );
+                }
+                _ => {
+                    self.status_line = format!(
+                        "Can't open {}: drop a .nes ROM, .state, .c8m or .fm2 movie, .ips patch or input script",
+                        file_label(path)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Whether `path` is named after the loaded ROM, like the state slots
+    /// (`<rom>.state`, `<rom>-3.state`).
+    fn named_after_loaded_rom(&self, path: &Path) -> bool {
+        let Some(rom) = self.loaded_rom.as_deref().and_then(Path::file_stem) else {
+            return false;
+        };
+        let (rom, stem) = (
+            rom.to_string_lossy(),
+            path.file_stem().unwrap_or_default().to_string_lossy(),
+        );
+        stem == rom
+            || stem
+                .strip_prefix(rom.as_ref())
+                .is_some_and(|rest| rest.starts_with('-'))
+    }
+
+    fn load_dropped_state(&mut self, path: &Path) {
+        if !self.named_after_loaded_rom(path) {
+            self.status_line = format!(
+                "Not loading {}: its name doesn't match the loaded game{}",
+                file_label(path),
+                self.loaded_rom
+                    .as_deref()
+                    .map_or(String::new(), |rom| format!(" ({})", file_label(rom))),
+            );
+            return;
+        }
+        let label = file_label(path);
+        self.load_state_from(path, &label);
+    }
+
+    fn apply_ips_patch(&mut self, path: &Path) {
+        let Some(rom) = self.loaded_rom.clone() else {
+            return;
+        };
+        match std::fs::read(path) {
+            Ok(patch) => self.load_rom_with_patch(&rom, Some((path, &patch))),
+            Err(err) => self.status_line = format!("Reading {} failed: {err}", file_label(path)),
+        }
+    }
+
+    /// Plays an FCEUX movie from power-on once its ROM checksum matches.
+    fn play_fm2(&mut self, path: &Path) {
+        let Some(rom) = self.loaded_rom.clone() else {
+            return;
+        };
+        let result = std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display()))
+            .and_then(|text| {
+                let (_, checksum) = Movie::from_fm2(&text, Vec::new())?;
+                let expected = fm2_checksum(&std::fs::read(&rom)?);
+                if checksum
+                    .as_ref()
+                    .is_some_and(|checksum| *checksum != expected)
+                {
+                    anyhow::bail!(
+                        "it was recorded on a different ROM than {}",
+                        file_label(&rom)
+                    );
+                }
+                self.nes.set_session_seed(0);
+                self.nes.power_cycle();
+                let (movie, _) = Movie::from_fm2(&text, self.nes.save_state_to_vec()?)?;
+                Ok((movie, checksum.is_some()))
+            });
+        match result {
+            Ok((movie, verified)) => {
+                self.timeline.reset();
+                self.quick_state = None;
+                self.blender.clear();
+                self.last_movie = Some(movie);
+                self.play_last_movie();
+                if !verified {
+                    self.status_line
+                        .push_str(" (no ROM checksum in the movie to verify)");
+                }
+            }
+            Err(err) => self.status_line = format!("Can't play {}: {err:#}", file_label(path)),
+        }
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if self.input_profiles.is_capturing() || self.hotkeys.is_capturing() {
+            self.rewinding = false;
+            self.fast_forwarding = false;
+            return;
+        }
+        let (pressed, rewind, fast_forward) = ctx.input(|i| {
+            (
+                self.hotkeys.pressed(i),
+                self.hotkeys.held(i, Action::Rewind),
+                self.hotkeys.held(i, Action::FastForward),
+            )
+        });
+        for action in pressed {
+            self.run_hotkey(ctx, action);
+        }
+        self.rewinding = self.nes.has_rom() && rewind;
+        self.fast_forwarding = self.nes.has_rom() && fast_forward;
+    }
+
+    fn run_hotkey(&mut self, ctx: &egui::Context, action: Action) {
+        match action {
+            Action::OpenRom => self.open_rom_dialog(),
+            Action::Fullscreen => {
+                let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!fullscreen));
+            }
+            _ if !self.nes.has_rom() => {}
+            Action::Pause => {
+                self.paused = !self.paused;
+                if !self.paused {
+                    self.controller_hold_until = Some(Instant::now() + Duration::from_secs(5));
+                }
+            }
+            Action::Reset => self.request_reset(),
+            Action::QuickSave => self.quick_save(),
+            Action::QuickLoad => self.quick_load(),
+            Action::SaveStateFile => self.save_state_file(),
+            Action::LoadStateFile => self.load_state_file(),
+            Action::LoadLatestState => self.load_latest_state(),
+            Action::Screenshot => self.save_screenshot(),
+            Action::PlayMacro(slot) => {
+                let slot_label = slot + 1;
+                self.status_line = if self.macros.start_playback(slot) {
+                    format!("Playing macro {slot_label}")
+                } else {
+                    format!("Macro {slot_label} is empty")
+                };
+            }
+            Action::RecordMacro(slot) => {
+                let slot_label = slot + 1;
+                self.status_line = if self.macros.toggle_recording(slot) {
+                    format!(
+                        "Recording macro {slot_label} ({} to stop)",
+                        self.hotkeys.label(action)
+                    )
+                } else {
+                    format!(
+                        "Macro {slot_label} saved ({} frames)",
+                        self.macros.frames_in_slot(slot)
+                    )
+                };
+            }
+            // Held actions are polled in `handle_shortcuts`.
+            Action::Rewind | Action::FastForward => {}
+        }
+    }
+
+    fn set_region(&mut self, region: Region) {
+        self.nes.set_region(region);
+        self.frame_interval = Duration::from_secs_f64(1.0 / region.frame_rate_hz());
+        self.next_frame_at = None;
+        self.apply_audio_sampling_mode();
+        // Movies and rewind history were captured with the old timing.
+        self.timeline.reset();
+        self.quick_state = None;
+        self.status_line = format!("Region set to {} (console reset)", region.label());
+    }
+
+    /// Frame-locked audio gives every frame the same sample count, nearest to
+    /// the output rate at the region's frame rate, so dumps stay in sync.
+    fn apply_audio_sampling_mode(&mut self) {
+        let samples_per_frame = self.frame_locked_audio.then(|| {
+            let rate = self.nes.audio_sample_rate() as f64;
+            (rate / self.nes.region().frame_rate_hz()).round() as u32
+        });
+        self.nes.set_audio_samples_per_frame(samples_per_frame);
+    }
+
+    fn request_reset(&mut self) {
+        if !self.nes.has_rom() {
+            return;
+        }
+        if self.confirm_reset {
+            self.reset_prompt_open = true;
+        } else {
+            self.reset_console();
+        }
+    }
+
+    fn reset_console(&mut self) {
+        let mode = self.timeline.mode();
+        self.timeline.reset_console(&mut self.nes);
+        self.next_frame_at = None;
+        self.status_line = match mode {
+            MovieMode::Idle => "Reset complete".to_string(),
+            MovieMode::Recording => "Reset complete, recorded in the movie".to_string(),
+            MovieMode::Playing => "Reset complete, movie playback stopped".to_string(),
+        };
+    }
+
+    fn show_reset_prompt(&mut self, ctx: &egui::Context) {
+        let mut open = self.reset_prompt_open;
+        let mut confirmed = false;
+        egui::Window::new("Reset console?")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Progress since the last save is lost.");
+                match self.timeline.mode() {
+                    MovieMode::Recording => {
+                        ui.label("The reset will be recorded in the movie.");
+                    }
+                    MovieMode::Playing => {
+                        ui.label("Movie playback will stop.");
+                    }
+                    MovieMode::Idle => {}
+                }
+                ui.checkbox(&mut self.confirm_reset, "Ask before resetting");
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Reset").clicked();
+                    if ui.button("Cancel").clicked()
+                        || ui.input(|input| input.key_pressed(egui::Key::Escape))
+                    {
+                        self.reset_prompt_open = false;
+                    }
+                });
+            });
+        self.reset_prompt_open &= open && !confirmed;
+        if confirmed {
+            self.reset_console();
+        }
+    }
+
+    fn quick_save(&mut self) {
+        match self.timeline.save_state(&self.nes) {
+            Ok(state) => {
+                self.status_line = format!("Quick-saved at frame {}", state.frame());
+                self.quick_state = Some(state);
+            }
+            Err(err) => self.status_line = format!("Quick save failed: {err}"),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        let Some(state) = self.quick_state.as_ref() else {
+            self.status_line = "No quick save yet (F5 to save)".to_string();
+            return;
+        };
+
+        let mode_before = self.timeline.mode();
+        if let Err(err) = self.timeline.load_state(&mut self.nes, state) {
+            self.status_line = format!("Quick load failed: {err}");
+            return;
+        }
+        self.status_line = match (mode_before, self.timeline.mode()) {
+            (MovieMode::Recording, MovieMode::Recording) => format!(
+                "Quick-loaded, movie branched at frame {}",
+                self.timeline.movie_position()
+            ),
+            (MovieMode::Idle, _) => "Quick-loaded".to_string(),
+            (_, MovieMode::Idle) => {
+                "Quick-loaded a state outside the movie, movie stopped".to_string()
+            }
+            _ => "Quick-loaded".to_string(),
+        };
+        self.next_frame_at = None;
+        self.practice.rearm();
+    }
+
+    fn toggle_movie_recording(&mut self) {
+        if self.timeline.mode() == MovieMode::Recording {
+            self.last_movie = self.timeline.stop_movie();
+            let frames = self.last_movie.as_ref().map_or(0, Movie::len);
+            self.status_line = format!("Movie recorded ({frames} frames)");
+            return;
+        }
+
+        self.timeline.stop_movie();
+        self.status_line = match self.timeline.start_recording(&self.nes) {
+            Ok(()) => "Recording movie".to_string(),
+            Err(err) => format!("Failed to start movie: {err}"),
+        };
+    }
+
+    fn play_last_movie(&mut self) {
+        let Some(movie) = self.last_movie.clone() else {
+            return;
+        };
+        self.status_line = match self.timeline.start_playback(&mut self.nes, movie) {
+            Ok(()) => "Playing movie".to_string(),
+            Err(err) => format!("Failed to play movie: {err}"),
+        };
+        self.next_frame_at = None;
+    }
+
+    /// `<kind dir>/<rom stem>/<rom stem><suffix>` for the loaded ROM.
+    fn rom_file(&self, kind: StorageKind, suffix: &str) -> anyhow::Result<PathBuf> {
+        let rom = self
+            .loaded_rom
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("no ROM loaded"))?;
+        let dir = self.storage.rom_dir(kind, rom)?;
+        let stem = dir.file_name().unwrap_or_default().to_string_lossy();
+        Ok(dir.join(format!("{stem}{suffix}")))
+    }
+
+    fn sram_file(&self) -> anyhow::Result<PathBuf> {
+        self.rom_file(StorageKind::Sram, ".sav")
+    }
+
+    /// Restores the loaded game's battery save, if it has one on disk. A
+    /// `game.sav` next to `game.nes` (as other emulators leave them) is
+    /// imported when the Battery saves folder has none yet; the next flush
+    /// copies it there.
+    fn load_battery_ram(&mut self) {
+        self.sram_dirty_since = None;
+        if self.nes.battery_ram().is_none() {
+            return;
+        }
+        let beside_rom = self
+            .loaded_rom
+            .as_deref()
+            .map(|rom| rom.with_extension("sav"));
+        let result = self.sram_file().and_then(|path| {
+            let (path, imported) = match beside_rom {
+                _ if path.exists() => (path, false),
+                Some(beside) if beside.is_file() => (beside, true),
+                _ => return Ok(None),
+            };
+            let data =
+                std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+            self.nes
+                .load_battery_ram(&data)
+                .with_context(|| format!("loading {}", path.display()))?;
+            Ok(Some(imported))
+        });
+        match result {
+            Ok(Some(false)) => self.status_line.push_str(", battery save restored"),
+            Ok(Some(true)) => {
+                self.status_line
+                    .push_str(", battery save imported from beside the ROM");
+                self.sram_dirty_since = Some(Instant::now());
+            }
+            Ok(None) => {}
+            Err(err) => self.status_line = format!("Battery save not loaded: {err:#}"),
+        }
+    }
+
+    /// Writes battery RAM out once it has been dirty for
+    /// [`SRAM_FLUSH_INTERVAL`], straight away while paused, or now when
+    /// `force` is set (before another ROM loads and on exit).
+    fn flush_battery_ram(&mut self, force: bool) {
+        if self.nes.take_battery_ram_dirty() {
+            self.sram_dirty_since.get_or_insert_with(Instant::now);
+        }
+        let Some(since) = self.sram_dirty_since else {
+            return;
+        };
+        if !force && !self.paused && since.elapsed() < SRAM_FLUSH_INTERVAL {
+            return;
+        }
+        let Some(ram) = self.nes.battery_ram() else {
+            self.sram_dirty_since = None;
+            return;
+        };
+        match self
+            .sram_file()
+            .and_then(|path| storage::write_atomic(&path, ram))
+        {
+            Ok(()) => self.sram_dirty_since = None,
+            Err(err) => {
+                // Stay dirty and try again after another interval.
+                self.status_line = format!("Battery save failed: {err:#}");
+                self.sram_dirty_since = Some(Instant::now());
+            }
+        }
+    }
+
+    fn state_slot_file(&self, slot: usize) -> anyhow::Result<PathBuf> {
+        if slot == 0 {
+            self.rom_file(StorageKind::States, ".state")
+        } else {
+            self.rom_file(StorageKind::States, &format!("-{slot}.state"))
+        }
+    }
+
+    /// Time since `slot` was last saved, or `None` if it is empty.
+    fn state_slot_age(&self, slot: usize) -> Option<Duration> {
+        let path = self.state_slot_file(slot).ok()?;
+        let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+        Some(
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The most recently written slot for the loaded ROM. File times are the
+    /// record, so this survives restarts and states copied in by hand.
+    fn latest_state_slot(&self) -> Option<usize> {
+        (0..STATE_SLOTS)
+            .filter_map(|slot| self.state_slot_age(slot).map(|age| (age, slot)))
+            .min()
+            .map(|(_, slot)| slot)
+    }
+
+    fn save_state_file(&mut self) {
+        let slot = self.state_slot;
+        let result = self
+            .state_slot_file(slot)
+            .and_then(|path| self.nes.save_state(&path).map(|()| path));
+        self.status_line = match result {
+            Ok(path) => format!("Saved slot {slot} to {}", path.display()),
+            Err(err) => format!("Saving state failed: {err:#}"),
+        };
+    }
+
+    fn load_state_file(&mut self) {
+        self.load_state_slot(self.state_slot);
+    }
+
+    fn load_latest_state(&mut self) {
+        match self.latest_state_slot() {
+            Some(slot) => {
+                self.state_slot = slot;
+                self.load_state_slot(slot);
+            }
+            None => self.status_line = "No saved states for this ROM yet".to_string(),
+        }
+    }
+
+    /// Loading from disk jumps outside the timeline's history, so movies and
+    /// rewind start over like after a region change.
+    fn load_state_slot(&mut self, slot: usize) {
+        match self.state_slot_file(slot) {
+            Ok(path) => self.load_state_from(&path, &format!("slot {slot}")),
+            Err(err) => self.status_line = format!("Loading state failed: {err:#}"),
+        }
+    }
+
+    fn load_state_from(&mut self, path: &Path, label: &str) {
+        self.status_line = match self.nes.load_state(path) {
+            Ok(()) => {
+                self.timeline.reset();
+                self.quick_state = None;
+                self.next_frame_at = None;
+                self.blender.clear();
+                self.practice.rearm();
+                format!("Loaded {label} from {}", path.display())
+            }
+            Err(err) => format!("Loading state failed: {err:#}"),
+        };
+    }
+
+    fn state_slot_label(&self, slot: usize) -> String {
+        match self.state_slot_age(slot) {
+            Some(age) => format!("Slot {slot} ({})", format_age(age)),
+            None => format!("Slot {slot} (empty)"),
+        }
+    }
+
+    fn save_screenshot(&mut self) {
+        let image = PpuImage {
+            width: 256,
+            height: 240,
+            rgba: self.nes.frame_buffer().to_vec(),
+        };
+        let result = self
+            .rom_file(StorageKind::Screenshots, &format!("-{}.png", unix_millis()))
+            .and_then(|path| ppu_viewer::save_png(&path, &image).map(|()| path));
+        self.status_line = match result {
+            Ok(path) => format!("Saved screenshot {}", path.display()),
+            Err(err) => format!("Screenshot failed: {err:#}"),
+        };
+    }
+
+    /// Starts writing every frame to a new folder beside the screenshots,
+    /// replaying the last movie from its start first when asked to.
+    fn start_frame_dump(&mut self) {
+        self.stop_frame_dump();
+        if self.dump_from_movie {
+            self.play_last_movie();
+            if self.timeline.mode() != MovieMode::Playing {
+                return;
+            }
+        }
+        let frames = if self.dump_from_movie && self.dump_frames == 0 {
+            None
+        } else {
+            Some(self.dump_frames.max(1))
+        };
+        let sample_rate = self.nes.audio_sample_rate();
+        let result = self
+            .rom_file(
+                StorageKind::Screenshots,
+                &format!("-dump-{}", unix_millis()),
+            )
+            .and_then(|dir| FrameDump::start(dir, sample_rate, self.dump_skip, frames));
+        match result {
+            Ok(dump) => {
+                self.status_line = format!("Dumping frames to {}", dump.dir().display());
+                self.frame_dump = Some(dump);
+                self.nes.take_audio_samples();
+                self.paused = false;
+                self.next_frame_at = None;
+            }
+            Err(err) => self.status_line = format!("Frame dump failed: {err:#}"),
+        }
+    }
+
+    /// Runs dump frames unpaced for up to [`FRAME_DUMP_BUDGET`]. Input comes
+    /// from the movie being played, or is released, so a dump never depends
+    /// on the keyboard.
+    fn run_frame_dump(&mut self) {
+        let started = Instant::now();
+        while started.elapsed() < FRAME_DUMP_BUDGET {
+            let Some(dump) = self.frame_dump.as_mut() else {
+                return;
+            };
+            let movie_over = self.dump_from_movie
+                && (self.timeline.mode() != MovieMode::Playing
+                    || self
+                        .timeline
+                        .movie()
+                        .is_some_and(|movie| self.timeline.movie_position() >= movie.len()));
+            if dump.is_done() || movie_over {
+                self.stop_frame_dump();
+                return;
+            }
+            let result = self.timeline.run_frame(&mut self.nes, 0);
+            let samples = self.nes.take_audio_samples();
+            if let Err(err) =
+                result.and_then(|()| dump.push_frame(self.nes.frame_buffer(), &samples))
+            {
+                self.frame_dump = None;
+                self.status_line = format!("Frame dump failed: {err:#}");
+                return;
+            }
+            if let Some(pc) = self
+                .nes
+                .take_breakpoint_hit()
+                .or_else(|| self.nes.take_watchpoint_hit().map(|hit| hit.pc))
+            {
+                self.stop_frame_dump();
+                self.paused = true;
+                self.status_line
+                    .push_str(&format!(", stopped at breakpoint ${pc:04X}"));
+                return;
+            }
+        }
+        if let Some(dump) = &self.frame_dump {
+            self.status_line = format!(
+                "Dumping frames to {}: {} written",
+                dump.dir().display(),
+                dump.written()
+            );
+        }
+    }
+
+    fn stop_frame_dump(&mut self) {
+        let Some(dump) = self.frame_dump.take() else {
+            return;
+        };
+        let dir = dump.dir().to_path_buf();
+        self.status_line = match dump.finish() {
+            Ok(frames) => format!("Dumped {frames} frames to {}", dir.display()),
+            Err(err) => format!("Frame dump failed: {err:#}"),
+        };
+        self.next_frame_at = None;
+    }
+
+    /// Ends the VGM log, if one is running, and writes it beside the
+    /// screenshots.
+    fn stop_vgm_log(&mut self) {
+        let Some(log) = self.nes.stop_vgm_log() else {
+            return;
+        };
+        let game = self
+            .loaded_rom
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let result = self
+            .rom_file(StorageKind::Screenshots, &format!("-{}.vgm", unix_millis()))
+            .and_then(|path| {
+                std::fs::write(&path, log.to_bytes(&game))
+                    .with_context(|| format!("writing {}", path.display()))
+                    .map(|()| path)
+            });
+        self.status_line = match result {
+            Ok(path) => format!(
+                "Saved {:.1} s of music to {}",
+                log.seconds(),
+                path.display()
+            ),
+            Err(err) => format!("Saving VGM failed: {err:#}"),
+        };
+    }
+
+    fn save_last_movie(&mut self) {
+        let Some(movie) = self.last_movie.as_ref() else {
+            return;
+        };
+        let result = self
+            .rom_file(StorageKind::Movies, &format!("-{}.c8m", unix_millis()))
+            .and_then(|path| movie.save(&path).map(|()| path));
+        self.status_line = match result {
+            Ok(path) => format!("Saved movie to {}", path.display()),
+            Err(err) => format!("Saving movie failed: {err:#}"),
+        };
+    }
+
+    fn export_last_movie_text(&mut self) {
+        let Some(movie) = self.last_movie.as_ref() else {
+            return;
+        };
+        let text = movie.to_text();
+        let result = self
+            .rom_file(StorageKind::Movies, &format!("-{}.txt", unix_millis()))
+            .and_then(|path| {
+                std::fs::write(&path, text)
+                    .with_context(|| format!("writing {}", path.display()))
+                    .map(|()| path)
+            });
+        self.status_line = match result {
+            Ok(path) => format!("Exported inputs to {}", path.display()),
+            Err(err) => format!("Exporting inputs failed: {err:#}"),
+        };
+    }
+
+    /// Plays a text input script from power-on, so a reproduction only needs
+    /// the ROM and the script.
+    fn play_input_script(&mut self, path: &Path) {
+        if !self.nes.has_rom() {
+            self.status_line = "Load a ROM before playing an input script".to_string();
+            return;
+        }
+        let result = std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display()))
+            .and_then(|text| {
+                self.nes.set_session_seed(Movie::text_seed(&text)?);
+                self.nes.power_cycle();
+                Movie::from_text(&text, self.nes.save_state_to_vec()?)
+            });
+        match result {
+            Ok(movie) => {
+                self.timeline.reset();
+                self.quick_state = None;
+                self.blender.clear();
+                self.last_movie = Some(movie);
+                self.play_last_movie();
+            }
+            Err(err) => self.status_line = format!("Failed to read input script: {err:#}"),
+        }
+    }
+
+    fn open_movie_dialog(&mut self) {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("Cathode-8 movie", &["c8m"])
+            .add_filter("Text input script", &["txt", "csv"])
+            .set_title("Open movie");
+        if let Some(rom) = &self.loaded_rom
+            && let Ok(dir) = self.storage.rom_dir(StorageKind::Movies, rom)
+        {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(path) = dialog.pick_file() else {
+            return;
+        };
+        if is_input_script(&path) {
+            self.play_input_script(&path);
+        } else {
+            self.open_movie(&path);
+        }
+    }
+
+    fn open_movie(&mut self, path: &Path) {
+        match Movie::load(path) {
+            Ok(movie) => {
+                self.last_movie = Some(movie);
+                self.play_last_movie();
+            }
+            Err(err) => self.status_line = format!("Failed to open movie: {err:#}"),
+        }
+    }
+
+    fn show_storage_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_storage_window;
+        egui::Window::new("Storage")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Files are kept in a subfolder per ROM inside these folders.");
+                egui::Grid::new("storage-dirs")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for kind in StorageKind::ALL {
+                            ui.label(kind.label());
+                            ui.monospace(self.storage.dir(kind).display().to_string());
+                            ui.horizontal(|ui| {
+                                if ui.button("Browse...").clicked()
+                                    && let Some(dir) = rfd::FileDialog::new()
+                                        .set_directory(self.storage.dir(kind))
+                                        .pick_folder()
+                                {
+                                    *self.storage.dir_mut(kind) = dir;
+                                }
+                                if ui.button("Default").clicked() {
+                                    self.storage.reset(kind);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+        self.show_storage_window = open;
+    }
+
+    /// Moves the timer and any LiveSplit connection along with a start, split
+    /// or reset, whether it came from the autosplitter or a button.
+    fn apply_split_event(&mut self, event: SplitEvent) {
+        let now = Instant::now();
+        let result = match event {
+            SplitEvent::Start => {
+                self.speedrun_timer.start(now);
+                self.livesplit.as_mut().map(LiveSplitLink::start)
+            }
+            SplitEvent::Split(index) => {
+                let last = self
+                    .autosplitter
+                    .as_ref()
+                    .is_some_and(|splitter| index + 1 == splitter.split_count());
+                self.speedrun_timer.split(now, last);
+                let igt = speedrun::frames_to_duration(
+                    self.speedrun_timer.igt_frames(),
+                    self.nes.region().frame_rate_hz(),
+                );
+                self.livesplit.as_mut().map(|link| link.split(igt))
+            }
+            SplitEvent::Reset => {
+                self.speedrun_timer.reset();
+                self.livesplit.as_mut().map(LiveSplitLink::reset)
+            }
+        };
+        if let Some(Err(err)) = result {
+            self.status_line = format!("LiveSplit disconnected: {err:#}");
+            self.livesplit = None;
+        }
+    }
+
+    fn show_speedrun_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_speedrun_window;
+        egui::Window::new("Speedrun")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                let frame_rate = self.nes.region().frame_rate_hz();
+                let igt = |frames| {
+                    speedrun::format_time(speedrun::frames_to_duration(frames, frame_rate))
+                };
+                ui.monospace(format!(
+                    "RTA {}   IGT {}",
+                    speedrun::format_time(self.speedrun_timer.rta(Instant::now())),
+                    igt(self.speedrun_timer.igt_frames())
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Start").clicked() {
+                        if let Some(splitter) = self.autosplitter.as_mut() {
+                            splitter.start_manually();
+                        }
+                        self.apply_split_event(SplitEvent::Start);
+                    }
+                    if ui
+                        .add_enabled(self.speedrun_timer.running(), egui::Button::new("Split"))
+                        .clicked()
+                    {
+                        let index = self.speedrun_timer.splits().len();
+                        if let Some(splitter) = self.autosplitter.as_mut() {
+                            splitter.skip_split();
+                        }
+                        self.apply_split_event(SplitEvent::Split(index));
+                    }
+                    if ui.button("Reset").clicked() {
+                        if let Some(splitter) = self.autosplitter.as_mut() {
+                            splitter.reset_manually();
+                        }
+                        self.apply_split_event(SplitEvent::Reset);
+                    }
+                    ui.checkbox(&mut self.speedrun.overlay, "Show on screen");
+                });
+                for (index, split) in self.speedrun_timer.splits().iter().enumerate() {
+                    ui.monospace(format!(
+                        "{:>2}. {}  {}",
+                        index + 1,
+                        speedrun::format_time(split.rta),
+                        igt(split.igt_frames)
+                    ));
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let mut autosplit = self.speedrun.autosplit;
+                    ui.checkbox(&mut autosplit, "Autosplit from RAM")
+                        .on_hover_text(
+                            "start/split/reset lines of `$addr op value` conditions on $0000-$07FF",
+                        );
+                    if ui.button("Apply script").clicked() || autosplit != self.speedrun.autosplit {
+                        self.speedrun.autosplit = autosplit;
+                        self.apply_autosplit_script();
+                    }
+                    if ui.button("Example").clicked() {
+                        self.speedrun.script = speedrun::EXAMPLE_SCRIPT.to_string();
+                    }
+                });
+                if let Some(err) = &self.autosplit_error {
+                    ui.colored_label(egui::Color32::YELLOW, err);
+                }
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.speedrun.script)
+                        .code_editor()
+                        .desired_rows(6)
+                        .desired_width(f32::INFINITY),
+                );
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("LiveSplit server");
+                    ui.add_enabled(
+                        self.livesplit.is_none(),
+                        egui::TextEdit::singleline(&mut self.speedrun.livesplit_addr)
+                            .desired_width(140.0),
+                    );
+                    if let Some(link) = &self.livesplit {
+                        let peer = link.peer();
+                        if ui.button("Disconnect").clicked() {
+                            self.livesplit = None;
+                            self.status_line = format!("Disconnected from LiveSplit at {peer}");
+                        }
+                    } else if ui.button("Connect").clicked() {
+                        match LiveSplitLink::connect(&self.speedrun.livesplit_addr) {
+                            Ok(link) => {
+                                self.status_line =
+                                    format!("Connected to LiveSplit at {}", link.peer());
+                                self.livesplit = Some(link);
+                            }
+                            Err(err) => self.status_line = format!("{err:#}"),
+                        }
+                    }
+                });
+            });
+        self.show_speedrun_window = open;
+    }
+
+    fn handle_practice_request(&mut self, request: PracticeRequest) {
+        self.status_line = match request {
+            PracticeRequest::CaptureNow => {
+                match self.practice.capture("manual", &self.nes, &self.timeline) {
+                    Ok(name) => format!("Practice point {name} captured"),
+                    Err(err) => format!("Capture failed: {err:#}"),
+                }
+            }
+            PracticeRequest::Load(index) => {
+                let Some((name, state)) = self.practice.state(index) else {
+                    return;
+                };
+                let name = name.to_string();
+                match self.timeline.load_state(&mut self.nes, state) {
+                    Ok(()) => {
+                        self.next_frame_at = None;
+                        self.blender.clear();
+                        self.practice.rearm();
+                        format!("Loaded practice point {name}")
+                    }
+                    Err(err) => format!("Loading practice point failed: {err:#}"),
+                }
+            }
+        };
+    }
+
+    fn apply_autosplit_script(&mut self) {
+        self.autosplit_error = None;
+        self.autosplitter = None;
+        if !self.speedrun.autosplit {
+            return;
+        }
+        match AutoSplitter::parse(&self.speedrun.script) {
+            Ok(mut splitter) => {
+                if self.speedrun_timer.running() {
+                    splitter.start_manually();
+                    for _ in 0..self.speedrun_timer.splits().len() {
+                        splitter.skip_split();
+                    }
+                }
+                self.autosplitter = Some(splitter);
+            }
+            Err(err) => self.autosplit_error = Some(format!("{err:#}")),
+        }
+    }
+
+    /// RTA and IGT in the top-right corner of the picture once a run has
+    /// started.
+    fn paint_speedrun_timer(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        if !self.speedrun.overlay || !self.speedrun_timer.started() {
+            return;
+        }
+        let igt = speedrun::frames_to_duration(
+            self.speedrun_timer.igt_frames(),
+            self.nes.region().frame_rate_hz(),
+        );
+        let text = format!(
+            "RTA {}\nIGT {}",
+            speedrun::format_time(self.speedrun_timer.rta(Instant::now())),
+            speedrun::format_time(igt)
+        );
+        let color = if self.speedrun_timer.running() {
+            egui::Color32::WHITE
+        } else {
+            egui::Color32::LIGHT_GREEN
+        };
+        let painter = painter.with_clip_rect(mapping.rect);
+        let galley = painter.layout_no_wrap(text, egui::FontId::monospace(14.0), color);
+        let text_pos = mapping.rect.right_top() + egui::vec2(-galley.size().x - 8.0, 8.0);
+        painter.rect_filled(
+            egui::Rect::from_min_size(text_pos, galley.size()).expand(3.0),
+            2.0,
+            egui::Color32::from_black_alpha(160),
+        );
+        painter.galley(text_pos, galley, color);
+    }
+
+    /// Drives the Zapper and the paddle from the mouse over the game image.
+    fn update_pointer_devices(&mut self, ctx: &egui::Context) {
+        self.nes.set_controller_ports(self.input_profiles.ports);
+        self.nes
+            .set_zapper_persistence(self.input_profiles.zapper_persistence);
+        let just_captured = self.update_pointer_capture(ctx);
+        // A click shorter than a host frame still pulls the trigger.
+        let trigger = !just_captured
+            && ctx.input(|input| input.pointer.primary_down() || input.pointer.primary_pressed());
+        let pixel = match self.pointer_capture {
+            Some(pos) => Some((pos.x as u8, pos.y as u8)),
+            None => {
+                let pointer = ctx.input(|input| input.pointer.hover_pos());
+                self.screen_mapping
+                    .zip(pointer)
+                    .and_then(|(mapping, pos)| mapping.nes_pixel(pos))
+            }
+        };
+
+        if self.input_profiles.ports.contains(&ControllerPort::Paddle) {
+            // Across the screen maps onto Arkanoid's knob range, $62-$F2.
+            let previous = self.nes.paddle_state().position;
+            let position = pixel.map_or(previous, |(x, _)| {
+                (0x62 + (x as u16 * (0xF2 - 0x62)) / 255) as u8
+            });
+            self.nes.set_paddle_state(PaddleInput {
+                position,
+                button: trigger,
+            });
+        }
+
+        match pixel {
+            Some((x, y)) => self.nes.set_zapper_state(x as i16, y as i16, trigger),
+            None => self.nes.set_zapper_state(-1, -1, trigger),
+        }
+    }
+
+    /// Grabs the mouse in fullscreen when the picture is clicked with a
+    /// paddle or Zapper plugged in, then moves the aim by relative motion
+    /// scaled to the picture, so it keeps the displayed aspect and never
+    /// stops at a screen edge. Escape, pausing, leaving fullscreen or losing
+    /// focus lets go. Returns true on the click that started a capture.
+    fn update_pointer_capture(&mut self, ctx: &egui::Context) -> bool {
+        let fullscreen = ctx.input(|input| input.viewport().fullscreen.unwrap_or(false));
+        let allowed = self.input_profiles.capture_pointer
+            && self.input_profiles.has_pointer_device()
+            && fullscreen
+            && self.window_focused
+            && !self.paused
+            && self.nes.has_rom();
+        let Some(mapping) = self.screen_mapping.filter(|_| allowed) else {
+            self.release_pointer(ctx);
+            return false;
+        };
+
+        let Some(pos) = self.pointer_capture else {
+            // Only a click on the picture itself, not on a window over it.
+            let Some(start) = ctx
+                .input(|input| {
+                    input
+                        .pointer
+                        .interact_pos()
+                        .filter(|_| input.pointer.primary_pressed())
+                })
+                .filter(|&pos| {
+                    ctx.layer_id_at(pos)
+                        .is_some_and(|layer| layer.order == egui::Order::Background)
+                })
+                .and_then(|pos| mapping.nes_pixel(pos))
+            else {
+                return false;
+            };
+            self.pointer_capture = Some(egui::pos2(f32::from(start.0), f32::from(start.1)));
+            let grab = if cfg!(target_os = "macos") {
+                egui::viewport::CursorGrab::Locked
+            } else {
+                egui::viewport::CursorGrab::Confined
+            };
+            ctx.send_viewport_cmd(egui::ViewportCommand::CursorGrab(grab));
+            ctx.send_viewport_cmd(egui::ViewportCommand::CursorVisible(false));
+            self.status_line = "Mouse captured (Esc releases it)".to_string();
+            return true;
+        };
+
+        if ctx.input(|input| input.key_pressed(egui::Key::Escape)) {
+            self.release_pointer(ctx);
+            return false;
+        }
+        // Raw motion isn't held back by the screen edge; integrations
+        // without it fall back to cursor movement.
+        let motion = ctx.input(|input| {
+            input
+                .pointer
+                .motion()
+                .map(|motion| motion / input.pixels_per_point())
+                .unwrap_or(input.pointer.delta())
+        });
+        let scale = mapping.rect.size() / mapping.source.size();
+        let moved = pos + motion * self.input_profiles.pointer_sensitivity / scale;
+        self.pointer_capture = Some(egui::pos2(
+            moved.x.clamp(0.0, 255.0),
+            moved.y.clamp(0.0, 239.0),
+        ));
+        false
+    }
+
+    fn release_pointer(&mut self, ctx: &egui::Context) {
+        if self.pointer_capture.take().is_some() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CursorGrab(
+                egui::viewport::CursorGrab::None,
+            ));
+            ctx.send_viewport_cmd(egui::ViewportCommand::CursorVisible(true));
+        }
+    }
+
+    fn update_texture(&mut self, ctx: &egui::Context) {
+        let blended = self.blend_enabled.then(|| self.blender.output()).flatten();
+        let frame = blended.unwrap_or(self.nes.frame_buffer());
+        let image = match self.filters.process(frame, [256, 240]) {
+            Some((size, filtered)) => ColorImage::from_rgba_unmultiplied(size, filtered),
+            None => ColorImage::from_rgba_unmultiplied([256, 240], frame),
+        };
+
+        if let Some(texture) = self.frame_texture.as_mut() {
+            texture.set(image, TextureOptions::NEAREST);
+        } else {
+            self.frame_texture =
+                Some(ctx.load_texture("nes-frame", image, TextureOptions::NEAREST));
+        }
+    }
+
+    /// With frame-skip on, a frame is only drawn if it is the last one this
+    /// update will run, i.e. emulation isn't a further frame behind.
+    fn should_render_frame(&self, next: Instant, ran_frames: u32, max_frames: u32) -> bool {
+        !self.auto_frameskip
+            || ran_frames + 1 >= max_frames
+            || Instant::now() < next + self.frame_interval
+    }
+
+    /// Advances one emulated frame, or steps back through rewind history while
+    /// the rewind key is held. Skipped frames (`render == false`) still run
+    /// CPU, APU and mappers, so audio stays continuous.
+    fn advance_frame(&mut self, ctx: &egui::Context, now: Instant, render: bool) {
+        if self.rewinding {
+            match self.timeline.rewind(&mut self.nes, self.rewind.interval) {
+                Ok(true) => {}
+                Ok(false) => self.status_line = "Rewind history exhausted".to_string(),
+                Err(err) => self.status_line = format!("Rewind failed: {err}"),
+            }
+            self.practice.rearm();
+            self.nes.take_audio_samples();
+            return;
+        }
+
+        let state = self.effective_controller_state(ctx, now);
+        let mut state = self.macros.next_frame(state);
+        self.nes.set_controller2_state(self.player2_state(ctx));
+        if let Some(run) = self.latency_run.as_mut() {
+            if run.probe.pressing() && run.poll_delay.is_none() {
+                run.poll_delay = run.pressed_at.map(|at| at.elapsed());
+            }
+            state = run.probe.input(state);
+        }
+        let result = if render {
+            self.timeline.run_frame(&mut self.nes, state)
+        } else {
+            self.timeline.run_frame_skip_render(&mut self.nes, state)
+        };
+        if let Err(err) = result {
+            self.status_line = format!("Frame failed: {err}");
+        }
+        self.speedrun_timer.tick_frame();
+        if let Some(event) = self
+            .autosplitter
+            .as_mut()
+            .and_then(|splitter| splitter.after_frame(&self.nes))
+        {
+            self.apply_split_event(event);
+        }
+        if let Some(name) = self.practice.after_frame(&self.nes, &self.timeline) {
+            self.status_line = format!("Practice point {name} captured");
+        }
+        if let Some(pc) = self.nes.take_breakpoint_hit() {
+            self.paused = true;
+            self.status_line = format!("Paused: breakpoint at ${pc:04X}");
+            if self.source_view.is_loaded() {
+                self.show_source_window = true;
+            }
+            if let Some(server) = &self.probe {
+                server.broadcast(&format!("break {pc:04X}"));
+            }
+        }
+        if let Some(hit) = self.nes.take_watchpoint_hit() {
+            self.paused = true;
+            let access = match hit.kind {
+                WatchKind::Read => "read",
+                WatchKind::Write => "written",
+            };
+            self.status_line = format!(
+                "Paused: ${:04X} {access} (${:02X}) by ${:04X}",
+                hit.addr, hit.value, hit.pc
+            );
+            self.debugger_view.record_hit(hit);
+            self.show_debugger_window = true;
+        }
+        if let Some(trip) = self.nes.take_frame_guard_trip() {
+            self.paused = true;
+            self.status_line = format!("Paused: frame stuck at PC=${:04X}", trip.pc);
+            self.frame_guard_report = Some(FrameGuardReport {
+                trip,
+                mapper: self.nes.debug_mapper_state(),
+                events: self.nes.debug_recent_events(FRAME_GUARD_EVENT_LINES),
+            });
+        }
+        if let Some(run) = self.latency_run.as_mut() {
+            let status = run.probe.after_frame(&mut self.nes);
+            if !run.probe.pressing() {
+                run.pressed_at = Some(Instant::now());
+            }
+            self.latency_report = match status {
+                ProbeStatus::Running => None,
+                ProbeStatus::Done(latency) => Some(format!(
+                    "UI poll {:.1} ms, latched on frame +{}, read on frame +{}, run-ahead {} frame(s)",
+                    run.poll_delay.unwrap_or_default().as_secs_f64() * 1000.0,
+                    latency.latch_frames,
+                    latency.read_frames,
+                    latency.run_ahead_frames
+                )),
+                ProbeStatus::TimedOut => {
+                    Some("The game didn't read the A button within 2 seconds".to_string())
+                }
+            };
+            if self.latency_report.is_some() {
+                self.latency_run = None;
+            }
+        }
+        if self.blend_enabled && render {
+            self.blender.push_frame(self.nes.frame_buffer());
+        }
+        let audio_samples = self.nes.take_audio_samples();
+        if let Some(spectrum) = self.spectrum.as_mut() {
+            spectrum.push_samples(&audio_samples);
+        }
+        if let Some(audio) = &self.audio
+            && !self.fast_forwarding
+            && !self.background_muted()
+        {
+            audio.push_samples(&audio_samples);
+        }
+    }
+
+    fn show_spectrum_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new("Spectrum")
+            .open(&mut open)
+            .default_size([320.0, 160.0])
+            .show(ctx, |ui| {
+                let Some(spectrum) = self.spectrum.as_ref() else {
+                    return;
+                };
+
+                let (rect, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), 120.0),
+                    egui::Sense::hover(),
+                );
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 2.0, egui::Color32::from_gray(16));
+
+                // Two narrow level meters on the left, spectrum bands on the right.
+                let meter_width = 10.0;
+                for (i, level) in [spectrum.rms(), spectrum.peak()].into_iter().enumerate() {
+                    let left = rect.left() + 2.0 + i as f32 * (meter_width + 2.0);
+                    let height = level.clamp(0.0, 1.0) * rect.height();
+                    let bar = egui::Rect::from_min_max(
+                        egui::pos2(left, rect.bottom() - height),
+                        egui::pos2(left + meter_width, rect.bottom()),
+                    );
+                    let color = if level >= 0.99 {
+                        egui::Color32::RED
+                    } else {
+                        egui::Color32::from_rgb(90, 200, 120)
+                    };
+                    painter.rect_filled(bar, 0.0, color);
+                }
+
+                let bands = spectrum.bands();
+                let bands_left = rect.left() + 2.0 * (meter_width + 2.0) + 6.0;
+                let band_width = (rect.right() - bands_left) / bands.len() as f32;
+                for (i, level) in bands.iter().enumerate() {
+                    let left = bands_left + i as f32 * band_width;
+                    let height = level * rect.height();
+                    let bar = egui::Rect::from_min_max(
+                        egui::pos2(left + 1.0, rect.bottom() - height),
+                        egui::pos2(left + band_width - 1.0, rect.bottom()),
+                    );
+                    painter.rect_filled(bar, 0.0, egui::Color32::from_rgb(80, 150, 230));
+                }
+
+                ui.monospace(format!(
+                    "RMS {:.3}  Peak {:.3}",
+                    spectrum.rms(),
+                    spectrum.peak()
+                ));
+            });
+        if !open {
+            self.spectrum = None;
+        }
+    }
+
+    fn video_preset_ui(&mut self, ui: &mut egui::Ui) {
+        let mut video = self.video.clone();
+        let builtin = VideoPreset::built_in();
+        let mut named = false;
+        egui::ComboBox::from_label("Preset")
+            .selected_text(&video.name)
+            .show_ui(ui, |ui| {
+                for preset in builtin.iter().chain(&self.user_presets) {
+                    if ui
+                        .selectable_label(video.name == preset.name, &preset.name)
+                        .clicked()
+                    {
+                        video = preset.clone();
+                        named = true;
+                    }
+                }
+            });
+
+        egui::ComboBox::from_label("Aspect")
+            .selected_text(video.aspect.label())
+            .show_ui(ui, |ui| {
+                for aspect in AspectRatio::ALL {
+                    ui.selectable_value(&mut video.aspect, aspect, aspect.label());
+                }
+            });
+        ui.checkbox(&mut video.integer_scaling, "Integer scaling");
+        ui.horizontal(|ui| {
+            ui.label("Overscan");
+            let overscan = &mut video.overscan;
+            for (value, label) in [
+                (&mut overscan.top, "T"),
+                (&mut overscan.bottom, "B"),
+                (&mut overscan.left, "L"),
+                (&mut overscan.right, "R"),
+            ] {
+                ui.add(egui::DragValue::new(value).range(0..=32).prefix(label));
+            }
+        });
+
+        let filters = &mut video.filters;
+        egui::ComboBox::from_label("Scaler")
+            .selected_text(filters.scaler.label())
+            .show_ui(ui, |ui| {
+                for scaler in Scaler::ALL {
+                    ui.selectable_value(&mut filters.scaler, scaler, scaler.label());
+                }
+            });
+        ui.add(egui::Slider::new(&mut filters.scanlines, 0.0..=1.0).text("Scanlines"));
+        ui.horizontal(|ui| {
+            ui.label("Order");
+            ui.radio_value(
+                &mut filters.order,
+                FilterOrder::ScalerThenScanlines,
+                "Scaler → scanlines",
+            );
+            ui.radio_value(
+                &mut filters.order,
+                FilterOrder::ScanlinesThenScaler,
+                "Scanlines → scaler",
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.preset_name);
+            let name = self.preset_name.trim().to_string();
+            let is_builtin = builtin.iter().any(|preset| preset.name == name);
+            if ui
+                .add_enabled(
+                    !name.is_empty() && !is_builtin,
+                    egui::Button::new("Save preset"),
+                )
+                .clicked()
+            {
+                video.name = name.clone();
+                named = true;
+                self.user_presets.retain(|preset| preset.name != name);
+                self.user_presets.push(video.clone());
+                self.status_line = format!("Saved video preset \"{name}\"");
+            }
+            let is_user = self
+                .user_presets
+                .iter()
+                .any(|preset| preset.name == video.name);
+            if ui
+                .add_enabled(is_user, egui::Button::new("Delete preset"))
+                .clicked()
+            {
+                self.user_presets.retain(|preset| preset.name != video.name);
+            }
+        });
+
+        if video != self.video {
+            // A manual tweak no longer matches the named preset.
+            if !named {
+                video.name = "Custom".to_string();
+            }
+            self.filters.set_settings(video.filters);
+            self.video = video;
+        }
+    }
+
+    fn show_debug_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_debug_window;
+        egui::Window::new("Debug Stats")
+            .open(&mut open)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                let Some(snapshot) = &self.debug_snapshot else {
+                    return;
+                };
+                let report = snapshot.to_string();
+                if ui.button("Copy to clipboard").clicked() {
+                    ui.ctx().copy_text(report.clone());
+                    self.status_line = "Debug snapshot copied".to_string();
+                }
+                ui.horizontal(|ui| {
+                    let can_measure = self.latency_run.is_none()
+                        && self.timeline.mode() == MovieMode::Idle
+                        && !self.paused;
+                    if ui
+                        .add_enabled(can_measure, egui::Button::new("Measure input latency"))
+                        .on_hover_text(
+                            "Presses A synthetically and times how long the game takes to read it",
+                        )
+                        .clicked()
+                    {
+                        self.latency_report = None;
+                        self.latency_run = Some(LatencyRun {
+                            probe: InputLatencyProbe::new(BUTTON_A),
+                            pressed_at: None,
+                            poll_delay: None,
+                        });
+                    }
+                    if self.latency_run.is_some() {
+                        ui.label("Measuring...");
+                    } else if let Some(report) = &self.latency_report {
+                        ui.monospace(report);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Frame guard");
+                    let guard = egui::DragValue::new(&mut self.frame_guard_steps)
+                        .range(100_000..=1_000_000_000)
+                        .speed(100_000)
+                        .suffix(" CPU steps");
+                    if ui
+                        .add(guard)
+                        .on_hover_text(
+                            "A frame taking longer than this is stopped and emulation pauses",
+                        )
+                        .changed()
+                    {
+                        self.nes.set_frame_guard_steps(self.frame_guard_steps);
+                    }
+                    if ui.button("Default").clicked() {
+                        self.frame_guard_steps = DEFAULT_FRAME_GUARD_STEPS;
+                        self.nes.set_frame_guard_steps(self.frame_guard_steps);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut overrides = self.nes.vector_overrides();
+                    ui.label("Override vectors");
+                    for (label, vector) in [
+                        ("NMI", &mut overrides.nmi),
+                        ("Reset", &mut overrides.reset),
+                        ("IRQ/BRK", &mut overrides.irq),
+                    ] {
+                        vector_override_ui(ui, label, vector);
+                    }
+                    if overrides != self.nes.vector_overrides() {
+                        self.nes.set_vector_overrides(overrides);
+                    }
+                    if ui
+                        .add_enabled(self.nes.has_rom(), egui::Button::new("Reset now"))
+                        .on_hover_text("Starts execution at the reset vector, overridden or not")
+                        .clicked()
+                    {
+                        self.reset_console();
+                    }
+                });
+                let mut dev_ram = self.nes.dev_ram_enabled();
+                if ui
+                    .checkbox(&mut dev_ram, "Scratch RAM at $4018-$5FFF")
+                    .on_hover_text(
+                        "Not real hardware: extra RAM for test ROMs, shadowing the cartridge there",
+                    )
+                    .changed()
+                {
+                    self.nes.set_dev_ram_enabled(dev_ram);
+                }
+                ui.horizontal(|ui| {
+                    let mut enabled = self.probe.is_some();
+                    let toggled = ui
+                        .checkbox(&mut enabled, "Debug probe on 127.0.0.1 port")
+                        .on_hover_text(
+                            "Line-based TCP protocol for external tools: regs, read, write, step, break...",
+                        )
+                        .changed();
+                    ui.add_enabled(
+                        self.probe.is_none(),
+                        egui::DragValue::new(&mut self.probe_port).range(1024..=u16::MAX),
+                    );
+                    if toggled {
+                        if enabled {
+                            self.start_probe();
+                        } else {
+                            self.probe = None;
+                            self.status_line = "Debug probe stopped".to_string();
+                        }
+                    }
+                    if let Some(server) = &self.probe {
+                        ui.label(format!("{} client(s)", server.client_count()));
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::horizontal().show(ui, |ui| {
+                    for line in report.lines() {
+                        ui.monospace(line);
+                    }
+                });
+            });
+        self.show_debug_window = open;
+    }
+
+    fn show_frame_guard_window(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.frame_guard_report else {
+            return;
+        };
+        let mut open = true;
+        let mut resume = false;
+        egui::Window::new("Frame stuck")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let trip = &report.trip;
+                ui.label(format!(
+                    "Frame {} ran {} CPU steps without reaching vblank, so emulation was paused.",
+                    trip.frame, trip.steps
+                ));
+                ui.monospace(format!(
+                    "PC=${:04X}  scanline {}  cycle {}",
+                    trip.pc, trip.scanline, trip.cycle
+                ));
+                ui.monospace(format!("Mapper: {}", report.mapper));
+                ui.separator();
+                ui.label("Recent events (newest first):");
+                egui::ScrollArea::vertical()
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        for event in &report.events {
+                            ui.monospace(event);
+                        }
+                    });
+                ui.separator();
+                ui.label(
+                    "This usually means the game is looping while it waits on hardware \
+                     behavior the emulator doesn't implement yet, such as a mapper IRQ or a \
+                     status flag. Please include this report and the ROM name when filing a bug.",
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Copy report").clicked() {
+                        ui.ctx().copy_text(report.to_text());
+                        self.status_line = "Frame guard report copied".to_string();
+                    }
+                    if ui.button("Resume").clicked() {
+                        resume = true;
+                    }
+                });
+            });
+        if resume {
+            self.paused = false;
+            self.next_frame_at = None;
+        }
+        if !open || resume {
+            self.frame_guard_report = None;
+        }
+    }
+
+    fn show_video_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_video_window;
+        let mut params = self.palette_params;
+        egui::Window::new("Video")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("NTSC palette");
+                ui.add(egui::Slider::new(&mut params.saturation, 0.0..=2.0).text("Saturation"));
+                ui.add(
+                    egui::Slider::new(&mut params.hue_degrees, -30.0..=30.0)
+                        .text("Hue")
+                        .suffix("°"),
+                );
+                ui.add(egui::Slider::new(&mut params.brightness, -0.5..=0.5).text("Brightness"));
+                ui.add(egui::Slider::new(&mut params.contrast, 0.5..=1.5).text("Contrast"));
+                ui.add(egui::Slider::new(&mut params.gamma, 1.6..=3.0).text("Gamma"));
+                if ui.button("Defaults").clicked() {
+                    params = NtscPaletteParams::default();
+                }
+
+                ui.separator();
+                if ui
+                    .checkbox(&mut self.blend_enabled, "Interframe blending")
+                    .changed()
+                {
+                    self.blender.clear();
+                }
+                let mut amount = self.blender.amount();
+                if ui
+                    .add_enabled(
+                        self.blend_enabled,
+                        egui::Slider::new(&mut amount, 0.1..=0.9).text("Previous frame weight"),
+                    )
+                    .changed()
+                {
+                    self.blender.set_amount(amount);
+                }
+
+                ui.separator();
+                self.video_preset_ui(ui);
+                ui.checkbox(
+                    &mut self.auto_frameskip,
+                    "Skip drawing frames when the host falls behind",
+                );
+                let native = ctx.native_pixels_per_point().unwrap_or(1.0);
+                if ui
+                    .checkbox(&mut self.force_unscaled_ui, "Force 100% UI scale")
+                    .on_hover_text(format!(
+                        "The monitor asks for {:.0}%. Fractional scales can blur text.",
+                        native * 100.0
+                    ))
+                    .changed()
+                    && !self.force_unscaled_ui
+                {
+                    ctx.set_zoom_factor(1.0);
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let mut rewind = self.rewind;
+                    ui.label("Rewind");
+                    ui.add(
+                        egui::DragValue::new(&mut rewind.seconds)
+                            .range(0..=600)
+                            .suffix(" s"),
+                    );
+                    ui.label("snapshot every");
+                    ui.add(
+                        egui::DragValue::new(&mut rewind.interval)
+                            .range(1..=60)
+                            .suffix(" frames"),
+                    );
+                    if rewind != self.rewind {
+                        self.rewind = rewind;
+                        self.timeline
+                            .set_rewind(rewind.snapshots(), rewind.interval);
+                    }
+                    let history = self.timeline.rewind_buffer();
+                    ui.label(format!(
+                        "{} snapshots, {:.1} MiB",
+                        history.len(),
+                        history.memory_used() as f64 / (1024.0 * 1024.0)
+                    ));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("When the window loses focus");
+                    egui::ComboBox::from_id_salt("unfocused_behavior")
+                        .selected_text(self.unfocused_behavior.label())
+                        .show_ui(ui, |ui| {
+                            for behavior in UnfocusedBehavior::ALL {
+                                ui.selectable_value(
+                                    &mut self.unfocused_behavior,
+                                    behavior,
+                                    behavior.label(),
+                                );
+                            }
+                        });
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Raster flashes");
+                    let flashes = &mut self.raster_flashes;
+                    egui::ComboBox::from_id_salt("raster_flashes")
+                        .selected_text(flashes.style.label())
+                        .show_ui(ui, |ui| {
+                            for style in FlashStyle::ALL {
+                                ui.selectable_value(&mut flashes.style, style, style.label());
+                            }
+                        });
+                    ui.add_enabled_ui(flashes.style != FlashStyle::Off, |ui| {
+                        ui.checkbox(&mut flashes.nmi, "NMI");
+                        ui.checkbox(&mut flashes.irq, "IRQ");
+                        ui.checkbox(&mut flashes.sprite0, "Sprite 0 hit");
+                    });
+                });
+
+                ui.separator();
+                ui.label("Cartridge audio filters").on_hover_text(
+                    "Applied to the loaded game's sound chip, ahead of the console's own filters",
+                );
+                let mut filters = self.nes.expansion_filters();
+                egui::Grid::new("expansion_filters")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for chip in ExpansionChip::ALL {
+                            ui.label(chip.name());
+                            let filter = filters.get_mut(chip);
+                            cutoff_ui(ui, "High-pass", &mut filter.high_pass_hz, 20.0..=2_000.0);
+                            cutoff_ui(ui, "Low-pass", &mut filter.low_pass_hz, 500.0..=20_000.0);
+                            ui.end_row();
+                        }
+                    });
+                if ui.button("Default filters").clicked() {
+                    filters = ExpansionFilters::default();
+                }
+                if filters != self.nes.expansion_filters() {
+                    self.nes.set_expansion_filters(filters);
+                }
+
+                ui.separator();
+                ui.label("Recording");
+                if ui
+                    .checkbox(
+                        &mut self.frame_locked_audio,
+                        "Exact audio samples per frame (for A/V dumps)",
+                    )
+                    .changed()
+                {
+                    self.apply_audio_sampling_mode();
+                }
+                if let Some(samples) = self.nes.audio_samples_per_frame() {
+                    ui.label(format!("{samples} samples per frame"));
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Frame dump: skip");
+                    ui.add(egui::DragValue::new(&mut self.dump_skip).suffix(" frames"));
+                    ui.label("then write");
+                    let min_frames = u32::from(!self.dump_from_movie);
+                    ui.add(
+                        egui::DragValue::new(&mut self.dump_frames)
+                            .range(min_frames..=u32::MAX)
+                            .suffix(" frames"),
+                    )
+                    .on_hover_text("0 writes until the movie ends");
+                });
+                ui.add_enabled(
+                    self.last_movie.is_some(),
+                    egui::Checkbox::new(&mut self.dump_from_movie, "Replay the last movie from its start"),
+                );
+                ui.horizontal(|ui| {
+                    if let Some(dump) = &self.frame_dump {
+                        ui.label(format!("{} frames written", dump.written()));
+                        if ui.button("Stop dump").clicked() {
+                            self.stop_frame_dump();
+                        }
+                    } else if ui
+                        .add_enabled(self.nes.has_rom(), egui::Button::new("Dump frames"))
+                        .on_hover_text(
+                            "Writes frame_000000.png, frame_000001.png, ... and audio.wav to a new \
+                             folder beside the screenshots, running as fast as the disk allows",
+                        )
+                        .clicked()
+                    {
+                        self.dump_from_movie &= self.last_movie.is_some();
+                        self.start_frame_dump();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if let Some(log) = self.nes.vgm_log() {
+                        ui.label(format!(
+                            "VGM: {:.1} s, {} writes",
+                            log.seconds(),
+                            log.writes()
+                        ));
+                        if ui.button("Stop and save VGM").clicked() {
+                            self.stop_vgm_log();
+                        }
+                    } else if ui
+                        .add_enabled(self.nes.has_rom(), egui::Button::new("Log music (VGM)"))
+                        .on_hover_text(
+                            "Records the sound register writes, with DMC samples, as a .vgm \
+                             file for chiptune players, saved beside the screenshots",
+                        )
+                        .clicked()
+                    {
+                        self.nes.start_vgm_log();
+                        self.status_line = "Logging music to VGM".to_string();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Power-on seed");
+                    let mut changed = ui
+                        .add(egui::DragValue::new(&mut self.session_seed))
+                        .on_hover_text(
+                            "Decides RAM contents, the noise LFSR and open-bus decay at power-on. \
+                             0 gives zeroed RAM. Saved in states and movies; used from the next power cycle.",
+                        )
+                        .changed();
+                    if ui.button("Randomize").clicked() {
+                        self.session_seed = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map_or(1, |elapsed| elapsed.as_nanos() as u64);
+                        changed = true;
+                    }
+                    if changed {
+                        self.nes.set_session_seed(self.session_seed);
+                    }
+                });
+            });
+        self.show_video_window = open;
+
+        if params != self.palette_params {
+            self.palette_params = params;
+            self.nes.set_ntsc_palette(&params);
+        }
+    }
+
+    /// Keeps one point per physical pixel while the UI scale is forced to
+    /// 100%, following the window to monitors with other scale factors.
+    fn apply_ui_scale(&self, ctx: &egui::Context) {
+        if !self.force_unscaled_ui {
+            return;
+        }
+        if let Some(native) = ctx.native_pixels_per_point() {
+            let zoom = 1.0 / native;
+            if (ctx.zoom_factor() - zoom).abs() > 0.001 {
+                ctx.set_zoom_factor(zoom);
+            }
+        }
+    }
+
+    fn background_muted(&self) -> bool {
+        !self.window_focused && self.unfocused_behavior == UnfocusedBehavior::MutedThrottled
+    }
+
+    /// Applies the unfocused behavior when the window gains or loses focus.
+    /// Queued audio is dropped on every transition so stale sound doesn't play
+    /// out of a pause or a rate change, and frame pacing restarts from now.
+    fn update_focus(&mut self, ctx: &egui::Context) {
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        if focused == self.window_focused {
+            return;
+        }
+        self.window_focused = focused;
+        if self.unfocused_behavior == UnfocusedBehavior::KeepRunning && !self.focus_paused {
+            return;
+        }
+
+        if let Some(audio) = &self.audio {
+            audio.clear();
+        }
+        self.nes.take_audio_samples();
+        self.next_frame_at = None;
+
+        if !focused {
+            if self.unfocused_behavior == UnfocusedBehavior::Pause && !self.paused {
+                self.paused = true;
+                self.focus_paused = true;
+            }
+        } else if self.focus_paused {
+            self.focus_paused = false;
+            if self.paused {
+                self.paused = false;
+                self.controller_hold_until = Some(Instant::now() + Duration::from_secs(5));
+            }
+        }
+    }
+
+    fn start_probe(&mut self) {
+        match ProbeServer::start(self.probe_port) {
+            Ok(server) => {
+                self.status_line = format!("Debug probe listening on {}", server.addr());
+                self.probe = Some(server);
+            }
+            Err(err) => self.status_line = format!("Debug probe failed: {err:#}"),
+        }
+    }
+
+    /// Applies requests from debug probe clients. `step`, `pause` and
+    /// `continue` drive the same pause state as the Pause key.
+    fn update_probe(&mut self) {
+        let Some(server) = &self.probe else {
+            return;
+        };
+        while let Some(request) = server.next_request() {
+            let reply = probe::handle_request(&mut self.nes, &request.line, self.paused);
+            match reply.pause {
+                Some(true) => self.paused = true,
+                Some(false) if self.paused => {
+                    self.paused = false;
+                    self.next_frame_at = None;
+                }
+                _ => {}
+            }
+            server.reply(&request, &reply.line);
+        }
+    }
+
+    /// Pauses while the player's gamepad is unplugged and resumes when one
+    /// is back, unless the user paused or unpaused in between.
+    fn update_gamepads(&mut self) {
+        match self.gamepads.poll() {
+            Some(HotPlug::Disconnected(name)) => {
+                self.status_line = format!("Controller disconnected: {name}");
+                if self.nes.has_rom() && !self.paused {
+                    self.paused = true;
+                    self.gamepad_paused = true;
+                }
+            }
+            Some(HotPlug::Reconnected(name)) => {
+                self.status_line = format!("Controller connected: {name}");
+                if self.gamepad_paused && self.paused {
+                    self.paused = false;
+                    self.next_frame_at = None;
+                }
+                self.gamepad_paused = false;
+            }
+            None => {
+                if !self.paused {
+                    self.gamepad_paused = false;
+                }
+            }
+        }
+    }
+
+    /// Crosshair for the captured mouse, whose own cursor is hidden.
+    fn paint_captured_pointer(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        let Some(pos) = self.pointer_capture else {
+            return;
+        };
+        let center = mapping
+            .screen_rect(egui::Rect::from_min_size(pos, egui::vec2(1.0, 1.0)))
+            .center();
+        let painter = painter.with_clip_rect(mapping.rect);
+        for (stroke, size) in [
+            (egui::Stroke::new(3.0, egui::Color32::BLACK), 9.0),
+            (egui::Stroke::new(1.0, egui::Color32::WHITE), 8.0),
+        ] {
+            painter.line_segment(
+                [
+                    center - egui::vec2(size, 0.0),
+                    center + egui::vec2(size, 0.0),
+                ],
+                stroke,
+            );
+            painter.line_segment(
+                [
+                    center - egui::vec2(0.0, size),
+                    center + egui::vec2(0.0, size),
+                ],
+                stroke,
+            );
+        }
+    }
+
+    fn paint_gamepad_prompt(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        let Some(name) = self.gamepads.missing() else {
+            return;
+        };
+        let painter = painter.with_clip_rect(mapping.rect);
+        painter.rect_filled(mapping.rect, 0.0, egui::Color32::from_black_alpha(170));
+        let text = if self.gamepad_paused {
+            format!("Controller disconnected\n{name}\n\nReconnect it to resume")
+        } else {
+            format!("Controller disconnected\n{name}")
+        };
+        painter.text(
+            mapping.rect.center(),
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::proportional(20.0),
+            egui::Color32::WHITE,
+        );
+    }
+
+    fn queued_audio_samples(&self) -> usize {
+        if let Some(audio) = &self.audio {
+            audio.queued_samples()
+        } else {
+            0
+        }
+    }
+
+    fn update_refresh_estimate_and_latency(&mut self, now: Instant) {
+        if let Some(prev) = self.next_frame_at {
+            let dt = now.saturating_duration_since(prev).as_secs_f64();
+            if (0.0005..=0.1).contains(&dt) {
+                let ema = self.update_dt_ema.unwrap_or(dt);
+                let next_ema = ema * 0.9 + dt * 0.1;
+                self.update_dt_ema = Some(next_ema);
+                let hz = (1.0 / next_ema).clamp(30.0, 360.0);
+                self.estimated_refresh_hz = hz;
+            }
+        }
+
+        let (target_ms, max_ms, poll_hz) = if self.estimated_refresh_hz >= 170.0 {
+            (4, 7, 1000.0)
+        } else if self.estimated_refresh_hz >= 110.0 {
+            (5, 8, 600.0)
+        } else if self.estimated_refresh_hz >= 80.0 {
+            (6, 9, 360.0)
+        } else {
+            (7, 10, 240.0)
+        };
+
+        self.audio_target_buffer_ms = target_ms;
+        self.audio_max_buffer_ms = max_ms;
+        self.high_refresh_interval = Duration::from_secs_f64(1.0 / poll_hz);
+    }
+
+    /// Red dot and frame count in the corner of the picture while a movie is
+    /// recording, so it is obvious at a glance (and on stream) that input is
+    /// being captured.
+    /// Tile or attribute grid of the last frame. Coordinates are thinned
+    /// out until they fit, keeping cells whose column and row are multiples
+    /// of the same power of two.
+    fn paint_grid_overlay(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        let cell = match self.grid_overlay {
+            GridOverlay::Off => return,
+            GridOverlay::Tiles => 8u8,
+            GridOverlay::Attributes => 16,
+        };
+        let grid = self.nes.tile_grid(cell);
+        let painter = painter.with_clip_rect(mapping.rect);
+        let stroke =
+            egui::Stroke::new(1.0, egui::Color32::from_rgba_unmultiplied(0, 255, 160, 110));
+        for line in &grid.lines {
+            let (at, start, end) = (
+                f32::from(line.at),
+                f32::from(line.start),
+                f32::from(line.end),
+            );
+            let rect = if line.vertical {
+                egui::Rect::from_min_max(egui::pos2(at, start), egui::pos2(at, end))
+            } else {
+                egui::Rect::from_min_max(egui::pos2(start, at), egui::pos2(end, at))
+            };
+            let rect = mapping.screen_rect(rect);
+            painter.line_segment([rect.min, rect.max], stroke);
+        }
+        if !self.grid_coordinates {
+            return;
+        }
+
+        let font = egui::FontId::monospace(9.0);
+        let cell_width = f32::from(cell) * mapping.rect.width() / mapping.source.width();
+        let label_width = painter
+            .layout_no_wrap("0:00,00".to_string(), font.clone(), egui::Color32::WHITE)
+            .size()
+            .x
+            + 4.0;
+        let mut step = 1u8;
+        while f32::from(step) * cell_width < label_width && step < 32 {
+            step *= 2;
+        }
+        let shift = cell.trailing_zeros() - 3;
+        for cell in &grid.cells {
+            if (cell.tile_column >> shift) % step != 0 || (cell.tile_row >> shift) % step != 0 {
+                continue;
+            }
+            let pos = mapping
+                .screen_rect(egui::Rect::from_min_size(
+                    egui::pos2(f32::from(cell.x), f32::from(cell.y)),
+                    egui::Vec2::ZERO,
+                ))
+                .min
+                + egui::vec2(1.0, 1.0);
+            let text = format!(
+                "{}:{:02},{:02}",
+                cell.nametable, cell.tile_column, cell.tile_row
+            );
+            let galley = painter.layout_no_wrap(text, font.clone(), egui::Color32::WHITE);
+            painter.rect_filled(
+                egui::Rect::from_min_size(pos, galley.size()),
+                0.0,
+                egui::Color32::from_black_alpha(140),
+            );
+            painter.galley(pos, galley, egui::Color32::WHITE);
+        }
+    }
+
+    fn paint_movie_indicator(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        if self.timeline.mode() != MovieMode::Recording {
+            return;
+        }
+        let frames = self.timeline.movie().map_or(0, Movie::frames_recorded);
+        let painter = painter.with_clip_rect(mapping.rect);
+        let dot = mapping.rect.left_top() + egui::vec2(12.0, 12.0);
+        painter.circle_filled(dot, 5.0, egui::Color32::RED);
+        let font = egui::FontId::monospace(12.0);
+        let text = format!("REC {frames}");
+        let galley = painter.layout_no_wrap(text, font, egui::Color32::WHITE);
+        let text_pos = dot + egui::vec2(10.0, -galley.size().y / 2.0);
+        painter.rect_filled(
+            egui::Rect::from_min_size(text_pos, galley.size()).expand(2.0),
+            2.0,
+            egui::Color32::from_black_alpha(160),
+        );
+        painter.galley(text_pos, galley, egui::Color32::WHITE);
+    }
+
+    /// Flashes for the last frame's NMI, IRQ and sprite 0 hit, so raster
+    /// timing is visible while playing.
+    fn paint_raster_flashes(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        let flashes = self.raster_flashes;
+        if flashes.style == FlashStyle::Off {
+            return;
+        }
+        let events: Vec<_> = self
+            .nes
+            .raster_events()
+            .iter()
+            .filter(|event| flashes.shows(event.kind))
+            .collect();
+        match flashes.style {
+            FlashStyle::Off => {}
+            FlashStyle::Border => {
+                // The picture is framed in the color of the frame's first
+                // event; every event gets a tick on its scanline.
+                if let Some(first) = events.first() {
+                    painter.rect_stroke(
+                        mapping.rect.expand(2.0),
+                        0.0,
+                        egui::Stroke::new(3.0, raster_event_color(first.kind)),
+                        egui::StrokeKind::Outside,
+                    );
+                }
+                for event in &events {
+                    let line = f32::from(event.scanline).clamp(0.0, 239.0);
+                    let y = mapping
+                        .screen_rect(egui::Rect::from_min_size(
+                            egui::pos2(0.0, line),
+                            egui::vec2(1.0, 1.0),
+                        ))
+                        .center()
+                        .y
+                        .clamp(mapping.rect.top(), mapping.rect.bottom());
+                    let x = mapping.rect.left() - 4.0;
+                    painter.line_segment(
+                        [egui::pos2(x - 10.0, y), egui::pos2(x, y)],
+                        egui::Stroke::new(3.0, raster_event_color(event.kind)),
+                    );
+                }
+            }
+            FlashStyle::Corner => {
+                let painter = painter.with_clip_rect(mapping.rect);
+                let kinds = [
+                    (RasterEventKind::Nmi, "N"),
+                    (RasterEventKind::Irq, "I"),
+                    (RasterEventKind::Sprite0Hit, "0"),
+                ];
+                let mut pos = mapping.rect.left_bottom() + egui::vec2(6.0, -22.0);
+                for (kind, label) in kinds {
+                    if !events.iter().any(|event| event.kind == kind) {
+                        continue;
+                    }
+                    let rect = egui::Rect::from_min_size(pos, egui::vec2(16.0, 16.0));
+                    painter.rect_filled(rect, 2.0, raster_event_color(kind));
+                    painter.text(
+                        rect.center(),
+                        egui::Align2::CENTER_CENTER,
+                        label,
+                        egui::FontId::monospace(12.0),
+                        egui::Color32::BLACK,
+                    );
+                    pos.x += 20.0;
+                }
+            }
+        }
+    }
+
+    /// Outlines the sprites the PPU selected in the last frame. Lines that hit
+    /// the 8-sprite limit get a tick in the left margin.
+    fn paint_sprite_overlay(&self, painter: &egui::Painter, mapping: ScreenMapping) {
+        let capture = self.nes.debug_sprite_capture();
+        let (ctrl, _, _) = self.nes.debug_ppu_regs();
+        let height = if ctrl & 0x20 != 0 { 16.0 } else { 8.0 };
+        let painter = painter.with_clip_rect(mapping.rect);
+        let sprite_rect = |index: u8| {
+            let base = index as usize * 4;
+            let y = self.nes.debug_peek_oam(base) as f32 + 1.0;
+            let x = self.nes.debug_peek_oam(base + 3) as f32;
+            mapping.screen_rect(egui::Rect::from_min_size(
+                egui::pos2(x, y),
+                egui::vec2(8.0, height),
+            ))
+        };
+        let nes_line = |y: f32, x0: f32, x1: f32| {
+            let rect = mapping.screen_rect(egui::Rect::from_min_max(
+                egui::pos2(x0, y),
+                egui::pos2(x1, y + 1.0),
+            ));
+            [rect.left_center(), rect.right_center()]
+        };
+
+        let mut selected = [false; 64];
+        for line in 0..240 {
+            let sprites = capture.line(line);
+            for &index in sprites {
+                selected[index as usize] = true;
+            }
+            if sprites.len() == 8 {
+                painter.line_segment(
+                    nes_line(line as f32, 0.0, 4.0),
+                    egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 160, 0)),
+                );
+            }
+        }
+        for index in 1..64u8 {
+            if selected[index as usize] {
+                painter.rect_stroke(
+                    sprite_rect(index),
+                    0.0,
+                    egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 200, 255)),
+                    egui::StrokeKind::Inside,
+                );
+            }
+        }
+        painter.rect_stroke(
+            sprite_rect(0),
+            0.0,
+            egui::Stroke::new(1.5, egui::Color32::YELLOW),
+            egui::StrokeKind::Inside,
+        );
+        if let Some((line, cycle)) = capture.sprite0_hit {
+            let dot = mapping.screen_rect(egui::Rect::from_min_size(
+                egui::pos2((cycle - 1) as f32, line as f32),
+                egui::vec2(1.0, 1.0),
+            ));
+            painter.circle_filled(dot.center(), 3.0, egui::Color32::RED);
+        }
+        if let Some((line, _)) = capture.overflow {
+            let [left, right] = nes_line(line as f32, 0.0, 256.0);
+            painter.line_segment([left, right], egui::Stroke::new(1.0, egui::Color32::RED));
+            painter.text(
+                right,
+                egui::Align2::RIGHT_BOTTOM,
+                format!("overflow @ {line}"),
+                egui::FontId::monospace(10.0),
+                egui::Color32::RED,
+            );
+        }
+    }
+
+    fn pixel_inspector_ui(&self, ui: &mut egui::Ui, x: u8, y: u8) {
+        ui.monospace(format!("Pixel ({x}, {y})"));
+        let Some(info) = self.nes.inspect_pixel(x, y) else {
+            return;
+        };
+
+        ui.monospace(format!(
+            "Palette ${:04X} -> color ${:02X}",
+            info.palette_addr, info.color
+        ));
+        ui.monospace(format!(
+            "BG tile ${:02X} (pattern ${:04X}) @ ${:04X}",
+            info.bg_tile, info.bg_pattern_addr, info.nametable_addr
+        ));
+        ui.monospace(format!(
+            "Nametable {} col {} row {}, attribute ${:04X}",
+            info.nametable, info.tile_column, info.tile_row, info.attribute_addr
+        ));
+        if let Some(sprite) = info.sprite {
+            ui.monospace(format!(
+                "Sprite #{} tile ${:02X} attr ${:02X} @ ({}, {}){}",
+                sprite.index,
+                sprite.tile,
+                sprite.attributes,
+                sprite.x,
+                sprite.y,
+                if sprite.visible { "" } else { " behind BG" }
+            ));
+        } else {
+            ui.monospace("No sprite");
+        }
+    }
+
+    fn show_log_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_log_panel;
+        egui::Window::new("Log")
+            .open(&mut open)
+            .default_size([560.0, 320.0])
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for subsystem in Subsystem::ALL {
+                        let current = self.log.level(subsystem);
+                        let mut selected = current;
+                        egui::ComboBox::from_label(subsystem.label())
+                            .selected_text(level_label(current))
+                            .show_ui(ui, |ui| {
+                                for option in LOG_LEVEL_OPTIONS {
+                                    ui.selectable_value(&mut selected, option, level_label(option));
+                                }
+                            });
+                        if selected != current {
+                            self.log.set_level(subsystem, selected);
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Clear").clicked() {
+                        self.log.clear();
+                    }
+                    if self.log.is_capturing_to_file() {
+                        if ui.button("Stop capture").clicked() {
+                            self.log.stop_file_capture();
+                            self.status_line = "Log capture stopped".to_string();
+                        }
+                    } else if ui.button("Capture to file...").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Log", &["log", "txt"])
+                            .set_file_name("cathode8.log")
+                            .save_file()
+                    {
+                        self.status_line = match self.log.start_file_capture(&path) {
+                            Ok(()) => format!("Capturing log to {}", path.display()),
+                            Err(err) => format!("Failed to capture log: {err}"),
+                        };
+                    }
+                });
+
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for entry in self.log.recent(LOG_PANEL_LINES) {
+                            ui.monospace(format!(
+                                "{:5} [{}] {}",
+                                entry.level,
+                                entry.subsystem.label(),
+                                entry.message
+                            ));
+                        }
+                    });
+            });
+        self.show_log_panel = open;
+    }
+
+    fn effective_controller_state(&mut self, ctx: &egui::Context, now: Instant) -> u8 {
+        if let Some(until) = self.controller_hold_until {
+            if now < until {
+                return self.latched_controller_state;
+            }
+            self.controller_hold_until = None;
+        }
+
+        let frame = self.nes.debug_counters().frame_count;
+        let live = self.input_profiles.state(0, ctx, frame) | self.gamepads.state(0);
+        self.latched_controller_state = live;
+        live
+    }
+
+    /// Player 2 has no hold or macros; the keyboard profile and pad only.
+    fn player2_state(&self, ctx: &egui::Context) -> u8 {
+        let frame = self.nes.debug_counters().frame_count;
+        self.input_profiles.state(1, ctx, frame) | self.gamepads.state(1)
+    }
+}
+
+impl eframe::App for NesApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, VIDEO_PRESET_KEY, &self.video);
+        eframe::set_value(storage, USER_PRESETS_KEY, &self.user_presets);
+        eframe::set_value(storage, STORAGE_DIRS_KEY, &self.storage);
+        eframe::set_value(storage, LIBRARY_KEY, self.library.config());
+        eframe::set_value(storage, INPUT_PROFILES_KEY, &self.input_profiles);
+        eframe::set_value(storage, HOTKEYS_KEY, &self.hotkeys);
+        eframe::set_value(storage, FRAME_GUARD_KEY, &self.frame_guard_steps);
+        eframe::set_value(storage, CONFIRM_RESET_KEY, &self.confirm_reset);
+        eframe::set_value(storage, FORCE_UNSCALED_UI_KEY, &self.force_unscaled_ui);
+        eframe::set_value(storage, REWIND_KEY, &self.rewind);
+        eframe::set_value(storage, UNFOCUSED_KEY, &self.unfocused_behavior);
+        eframe::set_value(storage, SESSION_SEED_KEY, &self.session_seed);
+        eframe::set_value(storage, SPEEDRUN_KEY, &self.speedrun);
+        eframe::set_value(storage, PRACTICE_KEY, self.practice.config());
+        eframe::set_value(storage, RASTER_FLASHES_KEY, &self.raster_flashes);
+        eframe::set_value(storage, PROBE_PORT_KEY, &self.probe_port);
+        eframe::set_value(
+            storage,
+            EXPANSION_FILTERS_KEY,
+            &self.nes.expansion_filters(),
+        );
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.stop_frame_dump();
+        self.stop_vgm_log();
+        self.flush_battery_ram(true);
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.apply_ui_scale(ctx);
+        self.handle_dropped_files(ctx);
+        self.handle_shortcuts(ctx);
+        self.update_pointer_devices(ctx);
+        self.update_focus(ctx);
+        self.update_gamepads();
+        self.update_probe();
+
+        let now = Instant::now();
+        self.update_refresh_estimate_and_latency(now);
+
+        if self.frame_dump.is_some() {
+            self.run_frame_dump();
+        } else if self.nes.has_rom() && !self.paused {
+            let mut next = self.next_frame_at.unwrap_or(now);
+            let mut ran_frames = 0u32;
+            let (interval, max_frames) = if self.background_muted() {
+                (self.frame_interval * BACKGROUND_SLOWDOWN, 1)
+            } else if self.fast_forwarding {
+                (
+                    self.frame_interval / FAST_FORWARD_SPEED,
+                    MAX_FRAMES_PER_UPDATE * FAST_FORWARD_SPEED,
+                )
+            } else if self.auto_frameskip {
+                (self.frame_interval, MAX_FRAMES_PER_UPDATE_SKIPPING)
+            } else {
+                (self.frame_interval, MAX_FRAMES_PER_UPDATE)
+            };
+
+            // Fast-forward outruns the audio device, so it isn't paced by it;
+            // muted background play doesn't feed it at all.
+            let sample_rate = self
+                .audio
+                .as_ref()
+                .filter(|_| !self.fast_forwarding && !self.background_muted())
+                .map(|audio| audio.sample_rate() as usize);
+            if let Some(sample_rate) = sample_rate {
+                let max_samples = sample_rate * self.audio_max_buffer_ms / 1000;
+
+                while Instant::now() >= next
+                    && !self.paused
+                    && self.queued_audio_samples() < max_samples
+                    && ran_frames < max_frames
+                {
+                    self.advance_frame(
+                        ctx,
+                        now,
+                        self.should_render_frame(next, ran_frames, max_frames),
+                    );
+                    ran_frames += 1;
+                    next += interval;
+                }
+            } else {
+                while Instant::now() >= next && !self.paused && ran_frames < max_frames {
+                    self.advance_frame(
+                        ctx,
+                        now,
+                        self.should_render_frame(next, ran_frames, max_frames),
+                    );
+                    ran_frames += 1;
+                    next += interval;
+                }
+            }
+
+            self.library.add_play_time(self.frame_interval * ran_frames);
+            if ran_frames == 0 && now > next + interval {
+                next = now;
+            }
+
+            self.next_frame_at = Some(next);
+        } else if self.paused {
+            let state = self.effective_controller_state(ctx, now);
+            self.nes.set_controller_state(state);
+            self.nes.set_controller2_state(self.player2_state(ctx));
+        }
+        self.flush_battery_ram(false);
+
+        self.update_texture(ctx);
+        self.debug_snapshot = self
+            .show_debug_window
+            .then(|| self.nes.debug_snapshot(DEBUG_EVENT_LINES));
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Open ROM").clicked() {
+                    self.open_rom_dialog();
+                }
+
+                let reset_enabled = self.nes.has_rom();
+                let reset_label = format!("Reset ({})", self.hotkeys.label(Action::Reset));
+                let reset = ui
+                    .add_enabled(reset_enabled, egui::Button::new(reset_label))
+                    .on_hover_text("Right-click for options");
+                reset.context_menu(|ui| {
+                    ui.checkbox(&mut self.confirm_reset, "Ask before resetting");
+                });
+                if reset.clicked() {
+                    self.request_reset();
+                }
+
+                if ui
+                    .add_enabled(
+                        self.nes.has_rom(),
+                        egui::Button::new(if self.paused {
+                            "Resume (P)"
+                        } else {
+                            "Pause (P)"
+                        }),
+                    )
+                    .clicked()
+                {
+                    self.paused = !self.paused;
+                    if !self.paused {
+                        self.controller_hold_until = Some(Instant::now() + Duration::from_secs(5));
+                    }
+                }
+
+                let recording = self.timeline.mode() == MovieMode::Recording;
+                if ui
+                    .add_enabled(
+                        self.nes.has_rom(),
+                        egui::Button::new(if recording {
+                            "Stop movie"
+                        } else {
+                            "Record movie"
+                        }),
+                    )
+                    .clicked()
+                {
+                    self.toggle_movie_recording();
+                }
+                if ui
+                    .add_enabled(
+                        self.last_movie.is_some() && !recording,
+                        egui::Button::new("Play movie"),
+                    )
+                    .clicked()
+                {
+                    self.play_last_movie();
+                }
+                if ui
+                    .add_enabled(
+                        self.last_movie.is_some() && !recording,
+                        egui::Button::new("Save movie"),
+                    )
+                    .clicked()
+                {
+                    self.save_last_movie();
+                }
+                if ui
+                    .add_enabled(
+                        self.last_movie.is_some() && !recording,
+                        egui::Button::new("Export inputs"),
+                    )
+                    .on_hover_text("Write the movie's inputs as a text script")
+                    .clicked()
+                {
+                    self.export_last_movie_text();
+                }
+                if ui
+                    .add_enabled(
+                        self.nes.has_rom() && !recording,
+                        egui::Button::new("Open movie..."),
+                    )
+                    .clicked()
+                {
+                    self.open_movie_dialog();
+                }
+
+                if self.nes.has_rom() {
+                    ui.separator();
+                    let mut slot = self.state_slot;
+                    egui::ComboBox::from_id_salt("state_slot")
+                        .selected_text(self.state_slot_label(slot))
+                        .show_ui(ui, |ui| {
+                            for candidate in 0..STATE_SLOTS {
+                                let label = self.state_slot_label(candidate);
+                                ui.selectable_value(&mut slot, candidate, label);
+                            }
+                        })
+                        .response
+                        .on_hover_text(format!(
+                            "Slot for {} / {}",
+                            self.hotkeys.label(Action::SaveStateFile),
+                            self.hotkeys.label(Action::LoadStateFile)
+                        ));
+                    self.state_slot = slot;
+                    if ui
+                        .button("Load latest")
+                        .on_hover_text(self.hotkeys.label(Action::LoadLatestState))
+                        .clicked()
+                    {
+                        self.load_latest_state();
+                    }
+                    ui.separator();
+                }
+
+                let current_region = self.nes.region();
+                let mut selected_region = current_region;
+                egui::ComboBox::from_id_salt("region")
+                    .selected_text(current_region.label())
+                    .show_ui(ui, |ui| {
+                        for region in Region::ALL {
+                            ui.selectable_value(&mut selected_region, region, region.label());
+                        }
+                    });
+                if selected_region != current_region {
+                    self.set_region(selected_region);
+                }
+
+                ui.toggle_value(&mut self.show_video_window, "Video");
+                ui.toggle_value(&mut self.show_log_panel, "Log");
+                ui.toggle_value(&mut self.show_debug_window, "Debug");
+                ui.toggle_value(&mut self.show_debugger_window, "Debugger");
+                ui.toggle_value(&mut self.show_source_window, "Source");
+                ui.toggle_value(&mut self.show_state_diff_window, "State diff");
+                ui.toggle_value(&mut self.show_storage_window, "Storage");
+                ui.toggle_value(&mut self.show_library_window, "Library");
+                ui.toggle_value(&mut self.show_boards_window, "Boards")
+                    .on_hover_text("Supported mappers and their known issues");
+                ui.toggle_value(&mut self.show_controls_window, "Controls");
+                ui.toggle_value(&mut self.show_hotkeys_window, "Hotkeys");
+                ui.toggle_value(&mut self.show_speedrun_window, "Speedrun");
+                ui.toggle_value(&mut self.show_practice_window, "Practice");
+                ui.toggle_value(&mut self.show_corruptor_window, "Corruptor");
+                ui.toggle_value(&mut self.inspect_pixels, "Inspect pixel")
+                    .on_hover_text("Always on while paused");
+                ui.toggle_value(&mut self.sprite_overlay, "Sprite overlay")
+                    .on_hover_text(
+                        "Sprites picked per scanline (cyan), sprite 0 (yellow), its hit (red dot) and the overflow line (red)",
+                    );
+
+                egui::ComboBox::from_id_salt("grid_overlay")
+                    .selected_text(self.grid_overlay.label())
+                    .show_ui(ui, |ui| {
+                        for grid in GridOverlay::ALL {
+                            ui.selectable_value(&mut self.grid_overlay, grid, grid.label());
+                        }
+                        ui.separator();
+                        ui.checkbox(&mut self.grid_coordinates, "Nametable coordinates");
+                    })
+                    .response
+                    .on_hover_text("Follows scrolling; labels read nametable:column,row");
+
+                let mut show_spectrum = self.spectrum.is_some();
+                if ui.toggle_value(&mut show_spectrum, "Spectrum").changed() {
+                    self.spectrum =
+                        show_spectrum.then(|| SpectrumAnalyzer::new(self.nes.audio_sample_rate()));
+                }
+
+                let mut show_ppu_viewer = self.ppu_viewer.is_some();
+                if ui.toggle_value(&mut show_ppu_viewer, "PPU").changed() {
+                    self.ppu_viewer = show_ppu_viewer.then(PpuViewer::new);
+                }
+                let mut show_heatmap = self.heatmap_viewer.is_some();
+                if ui.toggle_value(&mut show_heatmap, "Heatmap").changed() {
+                    self.heatmap_viewer = if show_heatmap {
+                        Some(HeatmapViewer::new(&mut self.nes))
+                    } else {
+                        self.nes.disable_access_heatmap();
+                        None
+                    };
+                }
+
+                if let Some(path) = &self.loaded_rom {
+                    ui.separator();
+                    ui.label(path.display().to_string());
+                }
+            });
+        });
+
+        if let Some(rom_region) = self.nes.rom_region_mismatch()
+            && !self.region_warning_dismissed
+        {
+            egui::TopBottomPanel::top("region-warning").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "This ROM is marked {} but is running with {} timing.",
+                            rom_region.label(),
+                            self.nes.region().label()
+                        ),
+                    );
+                    if ui
+                        .button(format!("Switch to {} and reset", rom_region.label()))
+                        .clicked()
+                    {
+                        self.set_region(rom_region);
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        self.region_warning_dismissed = true;
+                    }
+                });
+            });
+        }
+
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(&self.status_line);
+                ui.separator();
+                ui.label(format!("Mapper: {}", self.nes.mapper_name()));
+                ui.separator();
+                ui.label(format!("Core: {}", self.nes.accuracy_profile()));
+                ui.separator();
+                match self.timeline.mode() {
+                    MovieMode::Recording => {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            format!("REC movie {}", self.timeline.movie_position()),
+                        );
+                        ui.separator();
+                    }
+                    MovieMode::Playing => {
+                        let total = self.timeline.movie().map_or(0, Movie::len);
+                        ui.label(format!(
+                            "Movie {}/{}",
+                            self.timeline.movie_position(),
+                            total
+                        ));
+                        ui.separator();
+                    }
+                    MovieMode::Idle => {}
+                }
+                if self.rewinding {
+                    ui.label("Rewinding");
+                    ui.separator();
+                }
+                if let Some(slot) = self.macros.recording_slot() {
+                    ui.colored_label(egui::Color32::RED, format!("REC macro {}", slot + 1));
+                    ui.separator();
+                } else if let Some(slot) = self.macros.playing_slot() {
+                    ui.label(format!("Macro {} playing", slot + 1));
+                    ui.separator();
+                }
+                if let Some(audio) = &self.audio {
+                    ui.label(format!(
+                        "Audio: {} Hz (queue {} ms, target {}-{} ms, display ~{:.0} Hz)",
+                        audio.sample_rate(),
+                        (audio.queued_samples() * 1000) / audio.sample_rate() as usize,
+                        self.audio_target_buffer_ms,
+                        self.audio_max_buffer_ms,
+                        self.estimated_refresh_hz
+                    ));
+                } else {
+                    ui.label("Audio: unavailable");
+                }
+                ui.separator();
+                let hotkeys = &self.hotkeys;
+                ui.label(format!(
+                    "Controls: see Controls window, Mouse=Zapper | {}=Pause, {}/{}=Quick save/load, {}=Rewind, {}=Fast-forward, {}=Screenshot, more under Hotkeys",
+                    hotkeys.label(Action::Pause),
+                    hotkeys.label(Action::QuickSave),
+                    hotkeys.label(Action::QuickLoad),
+                    hotkeys.label(Action::Rewind),
+                    hotkeys.label(Action::FastForward),
+                    hotkeys.label(Action::Screenshot),
+                ));
+            });
+        });
+
+        if self.show_log_panel {
+            self.show_log_window(ctx);
+        }
+        if self.spectrum.is_some() {
+            self.show_spectrum_window(ctx);
+        }
+        if self.show_video_window {
+            self.show_video_window(ctx);
+        }
+        if self.show_debug_window {
+            self.show_debug_window(ctx);
+        }
+        self.show_frame_guard_window(ctx);
+        if self.reset_prompt_open {
+            self.show_reset_prompt(ctx);
+        }
+        if self.show_storage_window {
+            self.show_storage_window(ctx);
+        }
+        if self.show_speedrun_window {
+            self.show_speedrun_window(ctx);
+        }
+        if self.show_practice_window
+            && self.nes.has_rom()
+            && let Some(request) = self.practice.show(ctx, &mut self.show_practice_window)
+        {
+            self.handle_practice_request(request);
+        }
+        if self.show_source_window
+            && let Some(request) =
+                self.source_view
+                    .show(ctx, &mut self.show_source_window, &mut self.nes)
+        {
+            match request {
+                SourceRequest::Step => {
+                    self.paused = true;
+                    self.nes.debug_step();
+                }
+                SourceRequest::Continue => {
+                    self.paused = false;
+                    self.next_frame_at = None;
+                }
+            }
+        }
+        if self.show_debugger_window
+            && let Some(request) = self.debugger_view.show(
+                ctx,
+                &mut self.show_debugger_window,
+                &mut self.nes,
+                self.paused,
+            )
+        {
+            match request {
+                DebuggerRequest::Pause => self.paused = true,
+                DebuggerRequest::Step => {
+                    self.paused = true;
+                    self.nes.debug_step();
+                }
+                DebuggerRequest::Continue => {
+                    self.paused = false;
+                    self.next_frame_at = None;
+                }
+            }
+        }
+        if self.show_state_diff_window {
+            let state_dir = self
+                .loaded_rom
+                .as_deref()
+                .and_then(|rom| self.storage.rom_dir(StorageKind::States, rom).ok());
+            self.state_diff
+                .show(ctx, &mut self.show_state_diff_window, &self.nes, state_dir);
+        }
+        if self.show_corruptor_window && self.nes.has_rom() {
+            self.corruptor.show(
+                ctx,
+                &mut self.show_corruptor_window,
+                &mut self.nes,
+                &mut self.status_line,
+            );
+        }
+        if self.show_controls_window {
+            self.input_profiles
+                .show(ctx, &mut self.show_controls_window, &INPUT_DEVICES);
+        }
+        if self.show_hotkeys_window {
+            let controller_keys = self.input_profiles.assigned_keys();
+            self.hotkeys
+                .show(ctx, &mut self.show_hotkeys_window, &controller_keys);
+        }
+        if self.show_library_window
+            && let Some(path) =
+                self.library
+                    .show(ctx, &mut self.show_library_window, &mut self.status_line)
+        {
+            self.load_rom(&path);
+        }
+        if self.show_boards_window {
+            self.boards_view
+                .show(ctx, &mut self.show_boards_window, self.nes.mapper_id());
+        }
+        if let Some(viewer) = self.ppu_viewer.as_mut()
+            && !viewer.show(ctx, &mut self.nes, &mut self.status_line)
+        {
+            self.ppu_viewer = None;
+        }
+        if let Some(viewer) = self.heatmap_viewer.as_mut()
+            && !viewer.show(ctx, &mut self.nes)
+        {
+            self.heatmap_viewer = None;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                // Leave room for the hint line below the image.
+                let available = ui.available_size() - egui::vec2(0.0, 24.0);
+                let target = egui::Vec2::from(
+                    self.video
+                        .display_size(available.into(), ctx.pixels_per_point()),
+                );
+                let [left, top, right, bottom] = self.video.visible_rect();
+                let source =
+                    egui::Rect::from_min_max(egui::pos2(left, top), egui::pos2(right, bottom));
+                let uv = egui::Rect::from_min_max(
+                    egui::pos2(left / 256.0, top / 240.0),
+                    egui::pos2(right / 256.0, bottom / 240.0),
+                );
+
+                if let Some(texture) = &self.frame_texture {
+                    // Snapped to physical pixels, so nearest-neighbor
+                    // sampling gives every NES pixel the same width.
+                    let (rect, response) = ui.allocate_exact_size(target, egui::Sense::hover());
+                    let rect = rect.round_to_pixels(ui.pixels_per_point());
+                    egui::Image::new(texture).uv(uv).paint_at(ui, rect);
+                    let mapping = ScreenMapping { rect, source };
+                    self.screen_mapping = Some(mapping);
+
+                    self.paint_grid_overlay(ui.painter(), mapping);
+                    if self.sprite_overlay {
+                        self.paint_sprite_overlay(ui.painter(), mapping);
+                    }
+                    self.paint_movie_indicator(ui.painter(), mapping);
+                    self.paint_speedrun_timer(ui.painter(), mapping);
+                    self.paint_raster_flashes(ui.painter(), mapping);
+                    self.paint_captured_pointer(ui.painter(), mapping);
+                    self.paint_gamepad_prompt(ui.painter(), mapping);
+                    if (self.inspect_pixels || self.paused)
+                        && let Some((x, y)) =
+                            response.hover_pos().and_then(|pos| mapping.nes_pixel(pos))
+                    {
+                        response.on_hover_ui_at_pointer(|ui| self.pixel_inspector_ui(ui, x, y));
+                    }
+                }
+
+                ui.add_space(8.0);
+                ui.label(
+                    "Drag/drop ROM. For Zapper games, aim with mouse and hold left click to fire.",
+                );
+            });
+        });
+
+        if let Some(next) = self.next_frame_at {
+            let wait = next.saturating_duration_since(Instant::now());
+            ctx.request_repaint_after(wait.min(self.high_refresh_interval));
+        } else {
+            ctx.request_repaint_after(self.high_refresh_interval);
+        }
+    }
+}
+
+const LOG_LEVEL_OPTIONS: [Option<tracing::Level>; 6] = [
+    None,
+    Some(tracing::Level::ERROR),
+    Some(tracing::Level::WARN),
+    Some(tracing::Level::INFO),
+    Some(tracing::Level::DEBUG),
+    Some(tracing::Level::TRACE),
+];
+
+fn level_label(level: Option<tracing::Level>) -> &'static str {
+    match level {
+        None => "off",
+        Some(level) => level.as_str(),
+    }
+}
+
+fn file_label(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// FCEUX's `romChecksum` for an iNES file: MD5 of PRG and CHR (header and
+/// trainer skipped), base64-encoded.
+fn fm2_checksum(rom: &[u8]) -> String {
+    use base64::Engine as _;
+    use md5::Digest as _;
+
+    let trainer = rom.get(6).is_some_and(|flags| flags & 0x04 != 0);
+    let skip = 16 + if trainer { 512 } else { 0 };
+    let digest = md5::Md5::digest(rom.get(skip..).unwrap_or_default());
+    format!(
+        "base64:{}",
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    )
+}
+
+/// Text input scripts, see [`Movie::from_text`].
+fn is_input_script(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt") || ext.eq_ignore_ascii_case("csv"))
+}
+
+/// Checkbox plus address for one [`VectorOverrides`] entry.
+fn vector_override_ui(ui: &mut egui::Ui, label: &str, vector: &mut Option<u16>) {
+    let mut enabled = vector.is_some();
+    ui.checkbox(&mut enabled, label);
+    let mut addr = vector.unwrap_or(0x8000);
+    ui.add_enabled(
+        enabled,
+        egui::DragValue::new(&mut addr)
+            .hexadecimal(4, false, true)
+            .prefix("$"),
+    );
+    *vector = enabled.then_some(addr);
+}
+
+/// A filter cutoff that can be switched off; switching it on starts from
+/// the geometric middle of `range`.
+fn cutoff_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    cutoff: &mut Option<f32>,
+    range: std::ops::RangeInclusive<f32>,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = cutoff.is_some();
+        ui.checkbox(&mut enabled, label);
+        let mut hz = cutoff.unwrap_or((range.start() * range.end()).sqrt());
+        ui.add_enabled(
+            enabled,
+            egui::DragValue::new(&mut hz)
+                .range(range)
+                .speed(10.0)
+                .suffix(" Hz"),
+        );
+        *cutoff = enabled.then_some(hz);
+    });
+}
+
+/// Coarse "how long ago" text for slot pickers.
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..10 => "just now".to_string(),
+        10..120 => format!("{secs} s ago"),
+        120..7_200 => format!("{} min ago", secs / 60),
+        7_200..172_800 => format!("{} h ago", secs / 3_600),
+        _ => format!("{} days ago", secs / 86_400),
+    }
+}
+
+/// Keeps repeated screenshots and movie saves from overwriting each other.
+fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis())
+}
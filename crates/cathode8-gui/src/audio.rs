@@ -106,6 +106,14 @@ impl AudioOutput {
         queue.extend(samples.iter().map(|s| s.clamp(-1.0, 1.0)));
     }
 
+    /// Drops everything still queued, so audio from before a pause or a
+    /// speed change doesn't play after it.
+    pub fn clear(&self) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.clear();
+        }
+    }
+
     pub fn queued_samples(&self) -> usize {
         if let Ok(queue) = self.queue.lock() {
             queue.len()
@@ -0,0 +1,106 @@
+//! Supported boards window: every mapper with its own implementation, the
+//! submappers it tells apart, its sound chip and what doesn't work yet,
+//! listed straight from the core's registry. Mappers not in the list run
+//! on the generic mapper, which only manages the simplest games.
+
+use cathode8_core::nes::apu::ExpansionChip;
+use cathode8_core::nes::mapper::{BoardInfo, SUPPORTED_BOARDS};
+use eframe::egui;
+
+#[derive(Default)]
+pub struct BoardsView {
+    filter: String,
+    audio_only: bool,
+}
+
+impl BoardsView {
+    /// Draws the window, marking the board of the loaded ROM.
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool, loaded_mapper: Option<u16>) {
+        egui::Window::new("Supported boards")
+            .open(open)
+            .default_size([560.0, 420.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.filter)
+                        .on_hover_text("Mapper number or part of the board name");
+                    ui.checkbox(&mut self.audio_only, "With audio");
+                });
+                if let Some(mapper_id) = loaded_mapper {
+                    match SUPPORTED_BOARDS.iter().find(|b| b.mapper_id == mapper_id) {
+                        Some(board) if board.notes.is_empty() => {
+                            ui.label(format!("The loaded game's board, mapper {mapper_id}, is supported."));
+                        }
+                        Some(board) => {
+                            ui.colored_label(
+                                egui::Color32::YELLOW,
+                                format!("Mapper {mapper_id} is supported; {}.", board.notes),
+                            );
+                        }
+                        None => {
+                            ui.colored_label(
+                                egui::Color32::YELLOW,
+                                format!(
+                                    "Mapper {mapper_id} runs on the generic mapper and may not work."
+                                ),
+                            );
+                        }
+                    }
+                }
+                ui.separator();
+
+                let filter = self.filter.trim().to_lowercase();
+                let boards: Vec<&BoardInfo> = SUPPORTED_BOARDS
+                    .iter()
+                    .filter(|board| !self.audio_only || board.audio.is_some())
+                    .filter(|board| {
+                        filter.is_empty()
+                            || board.mapper_id.to_string() == filter
+                            || board.name.to_lowercase().contains(&filter)
+                    })
+                    .collect();
+                ui.label(format!(
+                    "{} of {} boards",
+                    boards.len(),
+                    SUPPORTED_BOARDS.len()
+                ));
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("supported-boards")
+                        .striped(true)
+                        .num_columns(5)
+                        .show(ui, |ui| {
+                            ui.strong("Mapper");
+                            ui.strong("Board");
+                            ui.strong("Submappers");
+                            ui.strong("Audio");
+                            ui.strong("Known issues");
+                            ui.end_row();
+                            for board in boards {
+                                let mut id = egui::RichText::new(board.mapper_id.to_string())
+                                    .monospace();
+                                if loaded_mapper == Some(board.mapper_id) {
+                                    id = id.strong().color(egui::Color32::LIGHT_GREEN);
+                                }
+                                ui.label(id);
+                                ui.label(board.name);
+                                ui.label(submapper_list(board.submappers));
+                                ui.label(board.audio.map_or("—", ExpansionChip::name));
+                                ui.label(board.notes);
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+}
+
+fn submapper_list(submappers: &[u8]) -> String {
+    if submappers.is_empty() {
+        return "—".to_string();
+    }
+    submappers
+        .iter()
+        .map(u8::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,206 @@
+//! Corruptor window: random bit flips or byte changes in a PRG or CHR
+//! range of the running game, with an undo stack. Corruptions live in the
+//! loaded copy only; reloading the ROM restores it.
+
+use cathode8_core::Nes;
+use cathode8_core::nes::corruptor::{CorruptMode, CorruptRegion, Corruption, CorruptionUndo};
+use eframe::egui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModeKind {
+    BitFlip,
+    Randomize,
+    Increment,
+    Shift,
+}
+
+impl ModeKind {
+    const ALL: [Self; 4] = [Self::BitFlip, Self::Randomize, Self::Increment, Self::Shift];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::BitFlip => "Flip a bit",
+            Self::Randomize => "Random byte",
+            Self::Increment => "Add",
+            Self::Shift => "Copy from offset",
+        }
+    }
+}
+
+pub struct Corruptor {
+    region: CorruptRegion,
+    start: usize,
+    end: usize,
+    count: usize,
+    mode: ModeKind,
+    amount: i32,
+    seed: u64,
+    /// Picks a fresh seed after every pass, so repeated clicks differ.
+    advance_seed: bool,
+    undo: Vec<CorruptionUndo>,
+}
+
+impl Default for Corruptor {
+    fn default() -> Self {
+        Self {
+            region: CorruptRegion::Prg,
+            start: 0,
+            end: 0x8000,
+            count: 8,
+            mode: ModeKind::BitFlip,
+            amount: 1,
+            seed: 1,
+            advance_seed: true,
+            undo: Vec::new(),
+        }
+    }
+}
+
+impl Corruptor {
+    /// Undo records only make sense for the ROM they were taken on.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+    }
+
+    fn corruption(&self) -> Corruption {
+        let mode = match self.mode {
+            ModeKind::BitFlip => CorruptMode::BitFlip,
+            ModeKind::Randomize => CorruptMode::Randomize,
+            ModeKind::Increment => CorruptMode::Increment(self.amount as u8),
+            ModeKind::Shift => CorruptMode::Shift(self.amount),
+        };
+        Corruption {
+            region: self.region,
+            start: self.start,
+            end: self.end,
+            count: self.count,
+            mode,
+            seed: self.seed,
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        nes: &mut Nes,
+        status: &mut String,
+    ) {
+        egui::Window::new("Corruptor")
+            .open(open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let prg_len = nes.corrupt_region_len(CorruptRegion::Prg);
+                let chr_len = nes.corrupt_region_len(CorruptRegion::Chr);
+                ui.horizontal(|ui| {
+                    ui.selectable_value(
+                        &mut self.region,
+                        CorruptRegion::Prg,
+                        format!("PRG ({} KiB)", prg_len / 1024),
+                    );
+                    ui.add_enabled_ui(chr_len > 0, |ui| {
+                        ui.selectable_value(
+                            &mut self.region,
+                            CorruptRegion::Chr,
+                            format!("CHR ({} KiB)", chr_len / 1024),
+                        );
+                    });
+                });
+                let len = match self.region {
+                    CorruptRegion::Prg => prg_len,
+                    CorruptRegion::Chr => chr_len,
+                };
+                ui.horizontal(|ui| {
+                    ui.label("Range $");
+                    ui.add(
+                        egui::DragValue::new(&mut self.start)
+                            .range(0..=len.saturating_sub(1))
+                            .hexadecimal(5, false, true),
+                    );
+                    ui.label("to $");
+                    ui.add(
+                        egui::DragValue::new(&mut self.end)
+                            .range(0..=len)
+                            .hexadecimal(5, false, true),
+                    );
+                    if ui.small_button("All").clicked() {
+                        self.start = 0;
+                        self.end = len;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("corrupt_mode")
+                        .selected_text(self.mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in ModeKind::ALL {
+                                ui.selectable_value(&mut self.mode, mode, mode.label());
+                            }
+                        });
+                    match self.mode {
+                        ModeKind::Increment => {
+                            ui.add(egui::DragValue::new(&mut self.amount).range(1..=255));
+                        }
+                        ModeKind::Shift => {
+                            ui.add(egui::DragValue::new(&mut self.amount).range(-256..=256));
+                        }
+                        ModeKind::BitFlip | ModeKind::Randomize => {}
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.count)
+                            .range(1..=4096)
+                            .suffix(" bytes"),
+                    );
+                    ui.label("Seed");
+                    ui.add(egui::DragValue::new(&mut self.seed));
+                    ui.checkbox(&mut self.advance_seed, "Advance");
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Corrupt").clicked() {
+                        match nes.corrupt(&self.corruption()) {
+                            Ok(undo) => {
+                                *status = format!(
+                                    "Corrupted {} bytes with seed {}",
+                                    undo.changed_bytes(),
+                                    self.seed
+                                );
+                                self.undo.push(undo);
+                                if self.advance_seed {
+                                    self.seed = self.seed.wrapping_add(1);
+                                }
+                            }
+                            Err(err) => *status = format!("Corruption failed: {err:#}"),
+                        }
+                    }
+                    let can_undo = !self.undo.is_empty();
+                    if ui
+                        .add_enabled(can_undo, egui::Button::new("Undo"))
+                        .clicked()
+                    {
+                        self.undo_last(nes, status);
+                    }
+                    if ui
+                        .add_enabled(can_undo, egui::Button::new("Undo all"))
+                        .clicked()
+                    {
+                        while !self.undo.is_empty() {
+                            self.undo_last(nes, status);
+                        }
+                    }
+                    ui.label(format!("{} passes applied", self.undo.len()));
+                });
+            });
+    }
+
+    fn undo_last(&mut self, nes: &mut Nes, status: &mut String) {
+        if let Some(undo) = self.undo.pop() {
+            *status = match nes.undo_corruption(&undo) {
+                Ok(()) => format!("Restored {} bytes", undo.changed_bytes()),
+                Err(err) => format!("Undo failed: {err:#}"),
+            };
+        }
+    }
+}
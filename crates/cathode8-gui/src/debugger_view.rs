@@ -0,0 +1,203 @@
+//! Debugger window: registers, a disassembly from the PC with clickable
+//! breakpoints, and watchpoints that stop the frame on a CPU read or write,
+//! e.g. to find the code that writes a PPU register.
+
+use std::collections::BTreeSet;
+
+use cathode8_core::Nes;
+use cathode8_core::nes::{WatchKind, WatchpointHit};
+use eframe::egui;
+
+/// Instructions listed from the PC.
+const DISASSEMBLY_LINES: usize = 20;
+
+pub enum DebuggerRequest {
+    Pause,
+    /// Pause if running, then run one instruction.
+    Step,
+    Continue,
+}
+
+pub struct DebuggerView {
+    breakpoint_addr: u16,
+    watch_addr: u16,
+    watch_reads: bool,
+    watch_writes: bool,
+    last_hit: Option<WatchpointHit>,
+}
+
+impl Default for DebuggerView {
+    fn default() -> Self {
+        Self {
+            breakpoint_addr: 0x8000,
+            watch_addr: 0x2000,
+            watch_reads: false,
+            watch_writes: true,
+            last_hit: None,
+        }
+    }
+}
+
+impl DebuggerView {
+    /// Remembers the access that stopped the last frame, shown until the
+    /// next one.
+    pub fn record_hit(&mut self, hit: WatchpointHit) {
+        self.last_hit = Some(hit);
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        nes: &mut Nes,
+        paused: bool,
+    ) -> Option<DebuggerRequest> {
+        let mut request = None;
+        egui::Window::new("Debugger")
+            .open(open)
+            .default_size([520.0, 460.0])
+            .show(ctx, |ui| {
+                if !nes.has_rom() {
+                    ui.label("No ROM loaded.");
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    if paused {
+                        if ui.button("Continue").clicked() {
+                            request = Some(DebuggerRequest::Continue);
+                        }
+                    } else if ui.button("Pause").clicked() {
+                        request = Some(DebuggerRequest::Pause);
+                    }
+                    if ui.button("Step").clicked() {
+                        request = Some(DebuggerRequest::Step);
+                    }
+                });
+                let (a, x, y, p, sp, pc) = nes.debug_cpu_regs();
+                let (scanline, dot) = nes.debug_ppu_scanline_cycle();
+                ui.monospace(format!(
+                    "PC:{pc:04X} A:{a:02X} X:{x:02X} Y:{y:02X} P:{p:02X} SP:{sp:02X}  \
+                     line {scanline} dot {dot}  frame {}",
+                    nes.debug_counters().frame_count
+                ));
+                ui.separator();
+
+                let breakpoints: BTreeSet<u16> = nes.breakpoints().collect();
+                let mut toggle = None;
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(260.0);
+                        let mut addr = pc;
+                        for _ in 0..DISASSEMBLY_LINES {
+                            let instruction = nes.debug_disassemble(addr);
+                            let armed = breakpoints.contains(&addr);
+                            let marker = if armed { "●" } else { " " };
+                            let mut text =
+                                egui::RichText::new(format!("{marker}{addr:04X}  {instruction}"))
+                                    .monospace();
+                            if addr == pc {
+                                text = text.background_color(egui::Color32::from_rgb(70, 60, 10));
+                            }
+                            if armed {
+                                text = text.color(egui::Color32::from_rgb(255, 120, 120));
+                            }
+                            if ui
+                                .add(egui::Label::new(text).sense(egui::Sense::click()))
+                                .on_hover_text("Click to toggle a breakpoint")
+                                .clicked()
+                            {
+                                toggle = Some(addr);
+                            }
+                            addr = instruction.next_addr();
+                        }
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        self.show_breakpoints(ui, nes, &breakpoints);
+                        ui.separator();
+                        self.show_watchpoints(ui, nes);
+                    });
+                });
+                if let Some(addr) = toggle
+                    && !nes.remove_breakpoint(addr)
+                {
+                    nes.add_breakpoint(addr);
+                }
+            });
+        request
+    }
+
+    fn show_breakpoints(&mut self, ui: &mut egui::Ui, nes: &mut Nes, breakpoints: &BTreeSet<u16>) {
+        ui.strong("Breakpoints");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.breakpoint_addr)
+                    .hexadecimal(4, false, true)
+                    .prefix("$"),
+            );
+            if ui.button("Add").clicked() {
+                nes.add_breakpoint(self.breakpoint_addr);
+            }
+        });
+        for &addr in breakpoints {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("${addr:04X}"));
+                if ui.small_button("Remove").clicked() {
+                    nes.remove_breakpoint(addr);
+                }
+            });
+        }
+    }
+
+    fn show_watchpoints(&mut self, ui: &mut egui::Ui, nes: &mut Nes) {
+        ui.strong("Watchpoints");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.watch_addr)
+                    .hexadecimal(4, false, true)
+                    .prefix("$"),
+            );
+            ui.checkbox(&mut self.watch_reads, "Read");
+            ui.checkbox(&mut self.watch_writes, "Write");
+            if ui.button("Add").clicked() {
+                if self.watch_reads {
+                    nes.add_watchpoint(self.watch_addr, WatchKind::Read);
+                }
+                if self.watch_writes {
+                    nes.add_watchpoint(self.watch_addr, WatchKind::Write);
+                }
+            }
+        });
+        let watchpoints: Vec<(u16, WatchKind)> = nes.watchpoints().collect();
+        for (addr, kind) in watchpoints {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("${addr:04X} {}", kind_label(kind)));
+                if ui.small_button("Remove").clicked() {
+                    nes.remove_watchpoint(addr, kind);
+                }
+            });
+        }
+        if let Some(hit) = self.last_hit {
+            ui.separator();
+            ui.label("Last hit");
+            ui.monospace(format!(
+                "${:04X} {} {:02X}",
+                hit.addr,
+                kind_label(hit.kind),
+                hit.value
+            ));
+            ui.monospace(format!(
+                "by {:04X}  {}",
+                hit.pc,
+                nes.debug_disassemble(hit.pc)
+            ));
+        }
+    }
+}
+
+fn kind_label(kind: WatchKind) -> &'static str {
+    match kind {
+        WatchKind::Read => "read",
+        WatchKind::Write => "write",
+    }
+}
@@ -0,0 +1,148 @@
+//! Frame dump: writes every emulated frame as a numbered PNG plus one WAV
+//! of the audio, for compositing or encoding outside the emulator. Frames
+//! run as fast as they can be written, not in real time, so the output is
+//! the same however slow the disk is.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use cathode8_core::nes::ppu_view::PpuImage;
+
+use crate::ppu_viewer;
+
+const WAV_HEADER_LEN: u32 = 44;
+
+pub struct FrameDump {
+    dir: PathBuf,
+    /// Frames still to run before the first one is written.
+    skip: u32,
+    /// Frames left to write, `None` to write until the movie ends.
+    remaining: Option<u32>,
+    written: u32,
+    wav: WavWriter,
+}
+
+impl FrameDump {
+    /// Creates `dir` and the WAV in it. `frames == None` dumps until the
+    /// caller stops it, e.g. at the end of movie playback.
+    pub fn start(dir: PathBuf, sample_rate: u32, skip: u32, frames: Option<u32>) -> Result<Self> {
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        let wav = WavWriter::create(&dir.join("audio.wav"), sample_rate)?;
+        Ok(Self {
+            dir,
+            skip,
+            remaining: frames,
+            written: 0,
+            wav,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn written(&self) -> u32 {
+        self.written
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    /// Takes one emulated frame: its RGBA picture and the audio produced
+    /// while it ran. Frames inside the skip range are dropped.
+    pub fn push_frame(&mut self, rgba: &[u8], samples: &[f32]) -> Result<()> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return Ok(());
+        }
+        if self.is_done() {
+            return Ok(());
+        }
+        let image = PpuImage {
+            width: 256,
+            height: 240,
+            rgba: rgba.to_vec(),
+        };
+        let path = self.dir.join(format!("frame_{:06}.png", self.written));
+        ppu_viewer::save_png(&path, &image)?;
+        self.wav.write_samples(samples)?;
+        self.written += 1;
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        Ok(())
+    }
+
+    /// Fills in the WAV sizes. Returns the number of frames written.
+    pub fn finish(self) -> Result<u32> {
+        self.wav.finish()?;
+        Ok(self.written)
+    }
+}
+
+/// 16-bit mono PCM. The header goes out with zero sizes and is patched on
+/// [`WavWriter::finish`], so the length doesn't need to be known up front.
+struct WavWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path, sample_rate: u32) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        let mut writer = Self {
+            file: BufWriter::new(file),
+            path: path.to_path_buf(),
+            data_len: 0,
+        };
+        let mut header = Vec::with_capacity(WAV_HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        header.extend_from_slice(&1u16.to_le_bytes()); // mono
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        writer.write(&header)?;
+        Ok(writer)
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|&sample| ((sample.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+            .collect();
+        self.write(&bytes)?;
+        self.data_len += bytes.len() as u32;
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file
+            .write_all(bytes)
+            .with_context(|| format!("writing {}", self.path.display()))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        let context = || format!("finishing {}", self.path.display());
+        self.file
+            .seek(SeekFrom::Start(4))
+            .and_then(|_| {
+                self.file
+                    .write_all(&(WAV_HEADER_LEN - 8 + self.data_len).to_le_bytes())
+            })
+            .and_then(|()| self.file.seek(SeekFrom::Start(40)))
+            .and_then(|_| self.file.write_all(&self.data_len.to_le_bytes()))
+            .and_then(|()| self.file.flush())
+            .with_context(context)
+    }
+}
@@ -0,0 +1,155 @@
+//! Gamepads through gilrs, with hot-plug tracking.
+//!
+//! Pads take player slots in the order they connect or first press a
+//! button: the first drives player 1 alongside the keyboard, the second
+//! player 2. A pad pressing a button while both slots are taken becomes
+//! player 1. All pads use a fixed layout: D-pad or left stick, East = A,
+//! South = B, Select and Start. When player 1's pad goes away the app is
+//! told, so it can pause until a pad comes back.
+
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+use crate::input_profile::PLAYERS;
+use cathode8_core::nes::{
+    BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START,
+    BUTTON_UP,
+};
+
+/// How far the stick has to lean to count as a D-pad press.
+const STICK_THRESHOLD: f32 = 0.5;
+
+const BUTTONS: [(Button, u8); 8] = [
+    (Button::DPadUp, BUTTON_UP),
+    (Button::DPadDown, BUTTON_DOWN),
+    (Button::DPadLeft, BUTTON_LEFT),
+    (Button::DPadRight, BUTTON_RIGHT),
+    (Button::East, BUTTON_A),
+    (Button::South, BUTTON_B),
+    (Button::Select, BUTTON_SELECT),
+    (Button::Start, BUTTON_START),
+];
+
+/// A change to the pad player 1 is using.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotPlug {
+    Disconnected(String),
+    /// A pad arrived while the previous one was missing.
+    Reconnected(String),
+}
+
+pub struct Gamepads {
+    /// `None` when gilrs couldn't start (no backend on this platform).
+    gilrs: Option<Gilrs>,
+    /// The pad driving each player.
+    slots: [Option<GamepadId>; PLAYERS],
+    /// Name of player 1's pad that disconnected, until one returns.
+    missing: Option<String>,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .inspect_err(|err| tracing::warn!("gamepad support unavailable: {err}"))
+            .ok();
+        let mut slots = [None; PLAYERS];
+        if let Some(gilrs) = &gilrs {
+            for (slot, (id, _)) in slots.iter_mut().zip(gilrs.gamepads()) {
+                *slot = Some(id);
+            }
+        }
+        Self {
+            gilrs,
+            slots,
+            missing: None,
+        }
+    }
+
+    /// Drains gilrs events. Call once per UI update.
+    pub fn poll(&mut self) -> Option<HotPlug> {
+        let gilrs = self.gilrs.as_mut()?;
+        let mut change = None;
+        while let Some(event) = gilrs.next_event() {
+            let name = gilrs.gamepad(event.id).name().to_string();
+            match event.event {
+                EventType::Connected => {
+                    if self.missing.take().is_some() {
+                        self.slots[0] = Some(event.id);
+                        change = Some(HotPlug::Reconnected(name));
+                    } else {
+                        seat(&mut self.slots, event.id);
+                    }
+                }
+                EventType::Disconnected if self.slots[0] == Some(event.id) => {
+                    self.slots[0] = None;
+                    self.missing = Some(name.clone());
+                    change = Some(HotPlug::Disconnected(name));
+                }
+                EventType::Disconnected => {
+                    for slot in &mut self.slots {
+                        if *slot == Some(event.id) {
+                            *slot = None;
+                        }
+                    }
+                }
+                EventType::ButtonPressed(..) if !seat(&mut self.slots, event.id) => {
+                    self.slots[0] = Some(event.id);
+                }
+                _ => {}
+            }
+        }
+        change
+    }
+
+    /// Name of the pad that disconnected, while it is still gone.
+    pub fn missing(&self) -> Option<&str> {
+        self.missing.as_deref()
+    }
+
+    /// Buttons held on `player`'s pad, as a controller bitmask.
+    pub fn state(&self, player: usize) -> u8 {
+        let (Some(gilrs), Some(Some(id))) = (self.gilrs.as_ref(), self.slots.get(player).copied())
+        else {
+            return 0;
+        };
+        let Some(pad) = gilrs.connected_gamepad(id) else {
+            return 0;
+        };
+        let mut state = BUTTONS
+            .iter()
+            .filter(|(button, _)| pad.is_pressed(*button))
+            .fold(0, |state, (_, bit)| state | bit);
+        let (x, y) = (pad.value(Axis::LeftStickX), pad.value(Axis::LeftStickY));
+        if x <= -STICK_THRESHOLD {
+            state |= BUTTON_LEFT;
+        } else if x >= STICK_THRESHOLD {
+            state |= BUTTON_RIGHT;
+        }
+        if y >= STICK_THRESHOLD {
+            state |= BUTTON_UP;
+        } else if y <= -STICK_THRESHOLD {
+            state |= BUTTON_DOWN;
+        }
+        state
+    }
+}
+
+/// Puts a pad without a slot in the first free one. False when every slot
+/// is taken by another pad.
+fn seat(slots: &mut [Option<GamepadId>; PLAYERS], id: GamepadId) -> bool {
+    if slots.contains(&Some(id)) {
+        return true;
+    }
+    match slots.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(id);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,153 @@
+//! CPU bus access heatmap: one row per 256-byte page, reads in green and
+//! writes in red, log-scaled against the busiest bucket of the window.
+
+use cathode8_core::nes::Nes;
+use cathode8_core::nes::heatmap::AccessHeatmap;
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
+
+const BUCKET_SIZES: [usize; 5] = [1, 4, 16, 64, 256];
+const DEFAULT_BUCKET_SIZE: usize = 16;
+const DEFAULT_WINDOW_FRAMES: u32 = 60;
+/// Each of the 256 pages is drawn this many pixels tall and 256 wide.
+const ZOOM: f32 = 2.0;
+
+pub struct HeatmapViewer {
+    bucket_size: usize,
+    window_frames: u32,
+    texture: Option<TextureHandle>,
+}
+
+impl HeatmapViewer {
+    /// Creates the viewer and starts collecting on `nes`.
+    pub fn new(nes: &mut Nes) -> Self {
+        nes.enable_access_heatmap(DEFAULT_BUCKET_SIZE, DEFAULT_WINDOW_FRAMES);
+        Self {
+            bucket_size: DEFAULT_BUCKET_SIZE,
+            window_frames: DEFAULT_WINDOW_FRAMES,
+            texture: None,
+        }
+    }
+
+    /// Draws the window. Returns `false` once closed, after which collection
+    /// has been switched off again.
+    pub fn show(&mut self, ctx: &egui::Context, nes: &mut Nes) -> bool {
+        let mut open = true;
+        let mut settings_changed = false;
+        egui::Window::new("Memory Heatmap")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Bucket")
+                        .selected_text(format!("{} B", self.bucket_size))
+                        .show_ui(ui, |ui| {
+                            for size in BUCKET_SIZES {
+                                settings_changed |= ui
+                                    .selectable_value(
+                                        &mut self.bucket_size,
+                                        size,
+                                        format!("{size} B"),
+                                    )
+                                    .changed();
+                            }
+                        });
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut self.window_frames, 1..=600)
+                                .text("Window (frames)"),
+                        )
+                        .changed();
+                });
+
+                let Some(heatmap) = nes.access_heatmap() else {
+                    return;
+                };
+                let image = render(heatmap);
+                let texture = match self.texture.as_mut() {
+                    Some(texture) => {
+                        texture.set(image, TextureOptions::NEAREST);
+                        texture
+                    }
+                    None => self.texture.insert(ctx.load_texture(
+                        "access-heatmap",
+                        image,
+                        TextureOptions::NEAREST,
+                    )),
+                };
+                let response = ui.add(
+                    egui::Image::new(&*texture)
+                        .fit_to_exact_size(egui::vec2(256.0, 256.0) * ZOOM)
+                        .sense(egui::Sense::hover()),
+                );
+                if let Some(pos) = response.hover_pos() {
+                    let offset = (pos - response.rect.min) / ZOOM;
+                    let addr = ((offset.y.clamp(0.0, 255.0) as usize) << 8)
+                        | offset.x.clamp(0.0, 255.0) as usize;
+                    let bucket = addr / heatmap.bucket_size();
+                    let start = heatmap.bucket_addr(bucket);
+                    let end = start as usize + heatmap.bucket_size() - 1;
+                    response.on_hover_text(format!(
+                        "${start:04X}-${end:04X} ({})\nreads {}  writes {}",
+                        region_name(start),
+                        heatmap.reads()[bucket],
+                        heatmap.writes()[bucket]
+                    ));
+                }
+                ui.label(format!(
+                    "Counts over the last {} frame(s). Green: reads, red: writes.",
+                    heatmap.window_frames()
+                ));
+            });
+
+        if !open {
+            nes.disable_access_heatmap();
+        } else if settings_changed {
+            nes.enable_access_heatmap(self.bucket_size, self.window_frames);
+        }
+        open
+    }
+}
+
+fn render(heatmap: &AccessHeatmap) -> ColorImage {
+    let reads = heatmap.reads();
+    let writes = heatmap.writes();
+    let peak = reads
+        .iter()
+        .chain(writes)
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let scale = |count: u32| {
+        if count == 0 {
+            0
+        } else {
+            // Log scale so a tight loop doesn't wash out everything else.
+            let level = (count as f32).ln_1p() / (peak as f32).ln_1p();
+            (48.0 + level * 207.0) as u8
+        }
+    };
+
+    let bucket_size = heatmap.bucket_size();
+    let pixels = (0..0x10000usize)
+        .map(|addr| {
+            let bucket = addr / bucket_size;
+            egui::Color32::from_rgb(scale(writes[bucket]), scale(reads[bucket]), 0)
+        })
+        .collect();
+    ColorImage {
+        size: [256, 256],
+        pixels,
+    }
+}
+
+fn region_name(addr: u16) -> &'static str {
+    match addr {
+        0x0000..=0x1FFF => "internal RAM",
+        0x2000..=0x3FFF => "PPU registers",
+        0x4000..=0x401F => "APU/IO",
+        0x4020..=0x5FFF => "expansion",
+        0x6000..=0x7FFF => "PRG-RAM",
+        _ => "PRG-ROM",
+    }
+}
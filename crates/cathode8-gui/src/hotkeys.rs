@@ -0,0 +1,339 @@
+//! Remappable hotkey table. Every frontend shortcut is an [`Action`] looked
+//! up here, so the settings page, conflict warnings and the help line all see
+//! the same bindings.
+
+use eframe::egui::{self, InputState, Key, Modifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::input_macro::MACRO_SLOTS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    OpenRom,
+    Pause,
+    Reset,
+    QuickSave,
+    QuickLoad,
+    SaveStateFile,
+    LoadStateFile,
+    LoadLatestState,
+    /// Held.
+    Rewind,
+    /// Held.
+    FastForward,
+    Screenshot,
+    Fullscreen,
+    PlayMacro(usize),
+    RecordMacro(usize),
+}
+
+impl Action {
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::OpenRom,
+            Action::Pause,
+            Action::Reset,
+            Action::QuickSave,
+            Action::QuickLoad,
+            Action::SaveStateFile,
+            Action::LoadStateFile,
+            Action::LoadLatestState,
+            Action::Rewind,
+            Action::FastForward,
+            Action::Screenshot,
+            Action::Fullscreen,
+        ];
+        actions.extend((0..MACRO_SLOTS).map(Action::PlayMacro));
+        actions.extend((0..MACRO_SLOTS).map(Action::RecordMacro));
+        actions
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Action::OpenRom => "Open ROM".to_string(),
+            Action::Pause => "Pause".to_string(),
+            Action::Reset => "Reset".to_string(),
+            Action::QuickSave => "Quick save".to_string(),
+            Action::QuickLoad => "Quick load".to_string(),
+            Action::SaveStateFile => "Save state to file".to_string(),
+            Action::LoadStateFile => "Load state from file".to_string(),
+            Action::LoadLatestState => "Load latest state".to_string(),
+            Action::Rewind => "Rewind (hold)".to_string(),
+            Action::FastForward => "Fast-forward (hold)".to_string(),
+            Action::Screenshot => "Screenshot".to_string(),
+            Action::Fullscreen => "Fullscreen".to_string(),
+            Action::PlayMacro(slot) => format!("Play macro {}", slot + 1),
+            Action::RecordMacro(slot) => format!("Record macro {}", slot + 1),
+        }
+    }
+
+    /// Held actions stay active while their key is down and tolerate extra
+    /// modifiers, so e.g. rewinding still works while Select (Shift) is held.
+    pub fn is_held(self) -> bool {
+        matches!(self, Action::Rewind | Action::FastForward)
+    }
+
+    fn default_hotkey(self) -> Option<Hotkey> {
+        const MACRO_KEYS: [Key; MACRO_SLOTS] = [Key::F1, Key::F2, Key::F3, Key::F4];
+        Some(match self {
+            Action::OpenRom => Hotkey::command(Key::O),
+            Action::Pause => Hotkey::new(Key::P),
+            Action::Reset => Hotkey::command(Key::R),
+            Action::QuickSave => Hotkey::new(Key::F5),
+            Action::QuickLoad => Hotkey::new(Key::F9),
+            Action::SaveStateFile => Hotkey::shift(Key::F5),
+            Action::LoadStateFile => Hotkey::shift(Key::F9),
+            Action::LoadLatestState => Hotkey::command(Key::F9),
+            Action::Rewind => Hotkey::new(Key::Backspace),
+            Action::FastForward => Hotkey::new(Key::Tab),
+            Action::Screenshot => Hotkey::new(Key::F12),
+            Action::Fullscreen => Hotkey::new(Key::F11),
+            Action::PlayMacro(slot) => Hotkey::new(*MACRO_KEYS.get(slot)?),
+            Action::RecordMacro(slot) => Hotkey::command(*MACRO_KEYS.get(slot)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub key: Key,
+    pub command: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Hotkey {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            command: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    fn command(key: Key) -> Self {
+        Self {
+            command: true,
+            ..Self::new(key)
+        }
+    }
+
+    fn shift(key: Key) -> Self {
+        Self {
+            shift: true,
+            ..Self::new(key)
+        }
+    }
+
+    fn with_modifiers(key: Key, modifiers: Modifiers) -> Self {
+        Self {
+            key,
+            command: modifiers.command,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+        }
+    }
+
+    fn modifiers_exact(&self, modifiers: Modifiers) -> bool {
+        modifiers.command == self.command
+            && modifiers.shift == self.shift
+            && modifiers.alt == self.alt
+    }
+
+    fn modifiers_held(&self, modifiers: Modifiers) -> bool {
+        (!self.command || modifiers.command)
+            && (!self.shift || modifiers.shift)
+            && (!self.alt || modifiers.alt)
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.command {
+            label.push_str(if cfg!(target_os = "macos") {
+                "Cmd+"
+            } else {
+                "Ctrl+"
+            });
+        }
+        if self.alt {
+            label.push_str("Alt+");
+        }
+        if self.shift {
+            label.push_str("Shift+");
+        }
+        label.push_str(self.key.name());
+        label
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotkeyMap {
+    bindings: Vec<(Action, Option<Hotkey>)>,
+    #[serde(skip)]
+    capturing: Option<Action>,
+}
+
+impl Default for HotkeyMap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::all()
+                .into_iter()
+                .map(|action| (action, action.default_hotkey()))
+                .collect(),
+            capturing: None,
+        }
+    }
+}
+
+impl HotkeyMap {
+    /// Adds defaults for actions a saved map predates and drops actions that
+    /// no longer exist.
+    pub fn with_missing_defaults(mut self) -> Self {
+        let actions = Action::all();
+        self.bindings.retain(|(action, _)| actions.contains(action));
+        for action in actions {
+            if !self.bindings.iter().any(|(bound, _)| *bound == action) {
+                self.bindings.push((action, action.default_hotkey()));
+            }
+        }
+        self.bindings
+            .sort_by_key(|(action, _)| Action::all().iter().position(|a| a == action));
+        self
+    }
+
+    pub fn hotkey(&self, action: Action) -> Option<Hotkey> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .and_then(|(_, hotkey)| *hotkey)
+    }
+
+    /// The hotkey's label, or "unbound", for help text.
+    pub fn label(&self, action: Action) -> String {
+        self.hotkey(action)
+            .map_or_else(|| "unbound".to_string(), |hotkey| hotkey.label())
+    }
+
+    /// Whether the editor is waiting for a key, so hotkeys should stay quiet.
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.is_some()
+    }
+
+    /// Non-held actions whose hotkey was pressed this frame.
+    pub fn pressed(&self, input: &InputState) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(action, _)| !action.is_held())
+            .filter_map(|(action, hotkey)| {
+                let hotkey = (*hotkey)?;
+                (input.key_pressed(hotkey.key) && hotkey.modifiers_exact(input.modifiers))
+                    .then_some(*action)
+            })
+            .collect()
+    }
+
+    pub fn held(&self, input: &InputState, action: Action) -> bool {
+        self.hotkey(action).is_some_and(|hotkey| {
+            input.key_down(hotkey.key) && hotkey.modifiers_held(input.modifiers)
+        })
+    }
+
+    /// Other actions bound to the same hotkey as `action`.
+    fn conflicts(&self, action: Action) -> Vec<Action> {
+        let Some(hotkey) = self.hotkey(action) else {
+            return Vec::new();
+        };
+        self.bindings
+            .iter()
+            .filter(|(other, bound)| *other != action && *bound == Some(hotkey))
+            .map(|(other, _)| *other)
+            .collect()
+    }
+
+    /// Draws the settings page. `controller_keys` are keys bound in the
+    /// active controller profiles; hotkeys using them are flagged too.
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool, controller_keys: &[Key]) {
+        egui::Window::new("Hotkeys")
+            .open(open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if let Some(action) = self.capturing
+                    && let Some((key, modifiers)) = ui.input(|input| {
+                        input.events.iter().find_map(|event| match event {
+                            egui::Event::Key {
+                                key,
+                                pressed: true,
+                                modifiers,
+                                ..
+                            } => Some((*key, *modifiers)),
+                            _ => None,
+                        })
+                    })
+                {
+                    if key != Key::Escape {
+                        self.set(action, Some(Hotkey::with_modifiers(key, modifiers)));
+                    }
+                    self.capturing = None;
+                }
+
+                egui::Grid::new("hotkeys")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for action in Action::all() {
+                            ui.label(action.label());
+                            let capturing = self.capturing == Some(action);
+                            let text = if capturing {
+                                "Press keys...".to_string()
+                            } else {
+                                self.label(action)
+                            };
+                            ui.horizontal(|ui| {
+                                if ui.selectable_label(capturing, text).clicked() {
+                                    self.capturing = (!capturing).then_some(action);
+                                }
+                                if ui.small_button("x").on_hover_text("Unbind").clicked() {
+                                    self.set(action, None);
+                                }
+                            });
+                            let conflicts = self.conflicts(action);
+                            if !conflicts.is_empty() {
+                                let names: Vec<String> =
+                                    conflicts.into_iter().map(Action::label).collect();
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    format!("also: {}", names.join(", ")),
+                                );
+                            } else if let Some(hotkey) = self.hotkey(action)
+                                && !hotkey.command
+                                && !hotkey.alt
+                                && controller_keys.contains(&hotkey.key)
+                            {
+                                ui.colored_label(egui::Color32::YELLOW, "also a controller key");
+                            } else {
+                                ui.label("");
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Restore defaults").clicked() {
+                        *self = Self::default();
+                    }
+                    if self.capturing.is_some() {
+                        ui.label("Esc cancels.");
+                    }
+                });
+            });
+        if !*open {
+            self.capturing = None;
+        }
+    }
+
+    fn set(&mut self, action: Action, hotkey: Option<Hotkey>) {
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(bound, _)| *bound == action) {
+            *bound = hotkey;
+        }
+    }
+}
@@ -0,0 +1,77 @@
+pub const MACRO_SLOTS: usize = 4;
+
+// One minute of input at 60 fps; longer sequences belong in a movie file.
+const MAX_MACRO_FRAMES: usize = 60 * 60;
+
+/// Short recorded controller sequences replayed one entry per emulated frame.
+#[derive(Debug, Default)]
+pub struct MacroBank {
+    slots: [Vec<u8>; MACRO_SLOTS],
+    recording: Option<usize>,
+    playback: Option<(usize, usize)>,
+}
+
+impl MacroBank {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts recording into `slot`, or stops if that slot is already recording.
+    /// Returns `true` when recording started.
+    pub fn toggle_recording(&mut self, slot: usize) -> bool {
+        if slot >= MACRO_SLOTS {
+            return false;
+        }
+        if self.recording == Some(slot) {
+            self.recording = None;
+            return false;
+        }
+
+        self.playback = None;
+        self.recording = Some(slot);
+        self.slots[slot].clear();
+        true
+    }
+
+    /// Starts replaying `slot` from its first frame. Returns `false` if it is empty.
+    pub fn start_playback(&mut self, slot: usize) -> bool {
+        if slot >= MACRO_SLOTS || self.slots[slot].is_empty() || self.recording.is_some() {
+            return false;
+        }
+        self.playback = Some((slot, 0));
+        true
+    }
+
+    pub fn recording_slot(&self) -> Option<usize> {
+        self.recording
+    }
+
+    pub fn playing_slot(&self) -> Option<usize> {
+        self.playback.map(|(slot, _)| slot)
+    }
+
+    pub fn frames_in_slot(&self, slot: usize) -> usize {
+        self.slots.get(slot).map_or(0, Vec::len)
+    }
+
+    /// Advances one emulated frame. While recording the live state is captured;
+    /// during playback the macro's buttons are held on top of the live state.
+    pub fn next_frame(&mut self, live: u8) -> u8 {
+        if let Some(slot) = self.recording {
+            let frames = &mut self.slots[slot];
+            frames.push(live);
+            if frames.len() >= MAX_MACRO_FRAMES {
+                self.recording = None;
+            }
+            return live;
+        }
+
+        let Some((slot, pos)) = self.playback else {
+            return live;
+        };
+        let frames = &self.slots[slot];
+        let state = frames.get(pos).copied().unwrap_or(0);
+        self.playback = (pos + 1 < frames.len()).then_some((slot, pos + 1));
+        live | state
+    }
+}
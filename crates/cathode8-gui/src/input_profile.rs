@@ -0,0 +1,520 @@
+//! Named controller binding profiles, assignable to player 1 and 2.
+//!
+//! A profile can name the device it's meant for; with auto-select on, each
+//! player gets the first profile whose device pattern matches the name of
+//! the device connected in that player's slot. Bindings are keyboard keys;
+//! gamepads use a fixed layout instead, so the keyboard is the only device
+//! reported today.
+
+use cathode8_core::nes::input::{ControllerPort, MAX_ZAPPER_PERSISTENCE};
+use cathode8_core::nes::{
+    BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START,
+    BUTTON_UP,
+};
+use eframe::egui::{self, InputState, Key};
+use serde::{Deserialize, Serialize};
+
+pub const PLAYERS: usize = 2;
+pub const KEYBOARD_DEVICE: &str = "Keyboard";
+
+/// Buttons in the order the editor lists them.
+const BUTTONS: [(u8, &str); 8] = [
+    (BUTTON_UP, "Up"),
+    (BUTTON_DOWN, "Down"),
+    (BUTTON_LEFT, "Left"),
+    (BUTTON_RIGHT, "Right"),
+    (BUTTON_A, "A"),
+    (BUTTON_B, "B"),
+    (BUTTON_SELECT, "Select"),
+    (BUTTON_START, "Start"),
+];
+
+/// Buttons with a turbo binding, which presses them on and off while held.
+const TURBO_BUTTONS: [(u8, &str); 2] = [(BUTTON_A, "Turbo A"), (BUTTON_B, "Turbo B")];
+
+/// Frames a turbo button spends pressed, then released: 15 presses a second.
+pub const DEFAULT_TURBO_FRAMES: u8 = 2;
+pub const MAX_TURBO_FRAMES: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(Key),
+    /// Modifiers never arrive as key events in egui, so they get their own
+    /// variants.
+    Shift,
+    Ctrl,
+    Alt,
+}
+
+impl Binding {
+    fn is_down(self, input: &InputState) -> bool {
+        match self {
+            Binding::Key(key) => input.key_down(key),
+            Binding::Shift => input.modifiers.shift,
+            Binding::Ctrl => input.modifiers.ctrl,
+            Binding::Alt => input.modifiers.alt,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Binding::Key(key) => key.name(),
+            Binding::Shift => "Shift",
+            Binding::Ctrl => "Ctrl",
+            Binding::Alt => "Alt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputProfile {
+    pub name: String,
+    /// Case-insensitive part of a device name this profile is auto-selected
+    /// for. Empty never matches.
+    pub device_match: String,
+    /// `(button bit, bindings)` pairs; any binding being down presses the
+    /// button.
+    pub bindings: Vec<(u8, Vec<Binding>)>,
+    /// Turbo bindings in the same form, for A and B.
+    #[serde(default)]
+    pub turbo: Vec<(u8, Vec<Binding>)>,
+}
+
+impl InputProfile {
+    /// WASD or arrows, Space/Z for A, X for B, C and V for turbo A and B,
+    /// Enter for Start and Shift for Select.
+    pub fn keyboard() -> Self {
+        let keys = |keys: &[Key]| keys.iter().copied().map(Binding::Key).collect();
+        Self {
+            name: "Keyboard".to_string(),
+            device_match: KEYBOARD_DEVICE.to_string(),
+            bindings: vec![
+                (BUTTON_UP, keys(&[Key::W, Key::ArrowUp])),
+                (BUTTON_DOWN, keys(&[Key::S, Key::ArrowDown])),
+                (BUTTON_LEFT, keys(&[Key::A, Key::ArrowLeft])),
+                (BUTTON_RIGHT, keys(&[Key::D, Key::ArrowRight])),
+                (BUTTON_A, keys(&[Key::Space, Key::Z])),
+                (BUTTON_B, keys(&[Key::X])),
+                (BUTTON_SELECT, vec![Binding::Shift]),
+                (BUTTON_START, keys(&[Key::Enter])),
+            ],
+            turbo: vec![(BUTTON_A, keys(&[Key::C])), (BUTTON_B, keys(&[Key::V]))],
+        }
+    }
+
+    /// A second set clear of [`InputProfile::keyboard`] and the hotkeys:
+    /// IJKL, Period for A, Comma for B, Slash and M for turbo A and B, O for
+    /// Start and U for Select.
+    pub fn keyboard_player2() -> Self {
+        let keys = |keys: &[Key]| keys.iter().copied().map(Binding::Key).collect();
+        Self {
+            name: "Keyboard 2".to_string(),
+            device_match: String::new(),
+            bindings: vec![
+                (BUTTON_UP, keys(&[Key::I])),
+                (BUTTON_DOWN, keys(&[Key::K])),
+                (BUTTON_LEFT, keys(&[Key::J])),
+                (BUTTON_RIGHT, keys(&[Key::L])),
+                (BUTTON_A, keys(&[Key::Period])),
+                (BUTTON_B, keys(&[Key::Comma])),
+                (BUTTON_SELECT, keys(&[Key::U])),
+                (BUTTON_START, keys(&[Key::O])),
+            ],
+            turbo: vec![(BUTTON_A, keys(&[Key::Slash])), (BUTTON_B, keys(&[Key::M]))],
+        }
+    }
+
+    /// Buttons held down. Turbo buttons count only while `turbo_on`.
+    pub fn state(&self, input: &InputState, turbo_on: bool) -> u8 {
+        let held = |bindings: &[(u8, Vec<Binding>)]| {
+            bindings
+                .iter()
+                .filter(|(_, bindings)| bindings.iter().any(|binding| binding.is_down(input)))
+                .fold(0, |state, (button, _)| state | button)
+        };
+        let turbo = if turbo_on { held(&self.turbo) } else { 0 };
+        held(&self.bindings) | turbo
+    }
+
+    fn bindings_mut(&mut self, button: u8, turbo: bool) -> &mut Vec<Binding> {
+        let list = if turbo {
+            &mut self.turbo
+        } else {
+            &mut self.bindings
+        };
+        let index = match list.iter().position(|(bit, _)| *bit == button) {
+            Some(index) => index,
+            None => {
+                list.push((button, Vec::new()));
+                list.len() - 1
+            }
+        };
+        &mut list[index].1
+    }
+
+    fn matches_device(&self, device: &str) -> bool {
+        !self.device_match.is_empty()
+            && device
+                .to_lowercase()
+                .contains(&self.device_match.to_lowercase())
+    }
+}
+
+/// All profiles and which one each player uses. Players refer to profiles by
+/// name so renaming or deleting one can't silently shift assignments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputProfiles {
+    pub profiles: Vec<InputProfile>,
+    pub assigned: [Option<String>; PLAYERS],
+    pub auto_select: bool,
+    /// What is plugged into each console port.
+    #[serde(default = "default_ports")]
+    pub ports: [ControllerPort; PLAYERS],
+    /// Extra frames the Zapper keeps seeing light; see
+    /// `Nes::set_zapper_persistence`.
+    #[serde(default)]
+    pub zapper_persistence: u8,
+    /// Grab the mouse for the paddle and Zapper in fullscreen and move them
+    /// by relative motion, so the cursor never stops at a screen edge.
+    #[serde(default)]
+    pub capture_pointer: bool,
+    /// Scale on captured mouse motion; 1.0 moves as far as the cursor would.
+    #[serde(default = "default_pointer_sensitivity")]
+    pub pointer_sensitivity: f32,
+    /// Frames a turbo button stays pressed and then released.
+    #[serde(default = "default_turbo_frames")]
+    pub turbo_frames: u8,
+    #[serde(skip)]
+    editing: usize,
+    /// The button, and whether it's the turbo binding, waiting for a key.
+    #[serde(skip)]
+    capturing: Option<(u8, bool)>,
+}
+
+impl Default for InputProfiles {
+    fn default() -> Self {
+        let keyboard = InputProfile::keyboard();
+        let keyboard2 = InputProfile::keyboard_player2();
+        Self {
+            assigned: [Some(keyboard.name.clone()), Some(keyboard2.name.clone())],
+            profiles: vec![keyboard, keyboard2],
+            auto_select: false,
+            ports: default_ports(),
+            zapper_persistence: 0,
+            capture_pointer: false,
+            pointer_sensitivity: default_pointer_sensitivity(),
+            turbo_frames: DEFAULT_TURBO_FRAMES,
+            editing: 0,
+            capturing: None,
+        }
+    }
+}
+
+impl InputProfiles {
+    pub fn profile(&self, player: usize) -> Option<&InputProfile> {
+        let name = self.assigned.get(player)?.as_ref()?;
+        self.profiles.iter().find(|profile| &profile.name == name)
+    }
+
+    /// Controller state for `player` on emulated frame `frame`, which sets
+    /// the turbo phase. 0 when no profile is assigned.
+    pub fn state(&self, player: usize, ctx: &egui::Context, frame: u64) -> u8 {
+        let turbo_on = (frame / u64::from(self.turbo_frames.max(1))).is_multiple_of(2);
+        self.profile(player).map_or(0, |profile| {
+            ctx.input(|input| profile.state(input, turbo_on))
+        })
+    }
+
+    /// Keyboard keys bound in the profiles players are using.
+    pub fn assigned_keys(&self) -> Vec<Key> {
+        (0..PLAYERS)
+            .filter_map(|player| self.profile(player))
+            .flat_map(|profile| profile.bindings.iter().chain(&profile.turbo))
+            .flat_map(|(_, bindings)| bindings)
+            .filter_map(|binding| match binding {
+                Binding::Key(key) => Some(*key),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether a port has a device aimed or turned with the mouse.
+    pub fn has_pointer_device(&self) -> bool {
+        self.ports.iter().any(|port| {
+            matches!(
+                port,
+                ControllerPort::Zapper | ControllerPort::VsZapper | ControllerPort::Paddle
+            )
+        })
+    }
+
+    /// Whether the editor is waiting for a key, so hotkeys should stay quiet.
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.is_some()
+    }
+
+    /// Assigns each player the first profile matching the device in its
+    /// slot, leaving players without a matching device alone.
+    pub fn select_for_devices(&mut self, devices: &[&str]) {
+        if !self.auto_select {
+            return;
+        }
+        for (player, device) in devices.iter().take(PLAYERS).enumerate() {
+            if let Some(profile) = self
+                .profiles
+                .iter()
+                .find(|profile| profile.matches_device(device))
+            {
+                self.assigned[player] = Some(profile.name.clone());
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool, devices: &[&str]) {
+        egui::Window::new("Controls")
+            .open(open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("controller-ports").show(ui, |ui| {
+                    for (port, device) in self.ports.iter_mut().enumerate() {
+                        ui.label(format!("Port {}", port + 1));
+                        egui::ComboBox::from_id_salt(("controller-port", port))
+                            .selected_text(device.label())
+                            .show_ui(ui, |ui| {
+                                for option in ControllerPort::ALL {
+                                    ui.selectable_value(device, option, option.label());
+                                }
+                            });
+                        ui.end_row();
+                    }
+                    if self.ports.iter().any(|port| {
+                        matches!(port, ControllerPort::Zapper | ControllerPort::VsZapper)
+                    }) {
+                        ui.label("Light persistence");
+                        ui.add(
+                            egui::Slider::new(
+                                &mut self.zapper_persistence,
+                                0..=MAX_ZAPPER_PERSISTENCE,
+                            )
+                            .suffix(" frames"),
+                        )
+                        .on_hover_text(
+                            "Keep a lit target reading as light for a few more frames, in case \
+                             the flash lands between mouse updates. Games that check for a \
+                             dark frame first may need 0.",
+                        );
+                        ui.end_row();
+                    }
+                    if self.has_pointer_device() {
+                        ui.label("Fullscreen mouse");
+                        ui.checkbox(&mut self.capture_pointer, "Capture on click")
+                            .on_hover_text(
+                                "In fullscreen, clicking the picture hides and grabs the mouse so \
+                                 it can't run into the screen edge. Esc releases it.",
+                            );
+                        ui.end_row();
+                        ui.label("Sensitivity");
+                        ui.add_enabled(
+                            self.capture_pointer,
+                            egui::Slider::new(&mut self.pointer_sensitivity, 0.1..=4.0)
+                                .logarithmic(true),
+                        );
+                        ui.end_row();
+                    }
+                    ui.label("Turbo");
+                    ui.add(
+                        egui::Slider::new(&mut self.turbo_frames, 1..=MAX_TURBO_FRAMES)
+                            .suffix(" frames"),
+                    )
+                    .on_hover_text(
+                        "Frames a turbo button stays pressed, then released. Some games \
+                         miss presses shorter than 2 frames.",
+                    );
+                    ui.end_row();
+                });
+                ui.separator();
+                for player in 0..PLAYERS {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "Player {} ({})",
+                            player + 1,
+                            devices.get(player).copied().unwrap_or("no device")
+                        ));
+                        let assigned = &mut self.assigned[player];
+                        egui::ComboBox::from_id_salt(("input-profile", player))
+                            .selected_text(assigned.as_deref().unwrap_or("None"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(assigned, None, "None");
+                                for profile in &self.profiles {
+                                    ui.selectable_value(
+                                        assigned,
+                                        Some(profile.name.clone()),
+                                        &profile.name,
+                                    );
+                                }
+                            });
+                    });
+                }
+                if ui
+                    .checkbox(&mut self.auto_select, "Pick profiles by device name")
+                    .changed()
+                {
+                    self.select_for_devices(devices);
+                }
+                ui.separator();
+                self.editor_ui(ui);
+            });
+        if !*open {
+            self.capturing = None;
+        }
+    }
+
+    fn editor_ui(&mut self, ui: &mut egui::Ui) {
+        self.editing = self.editing.min(self.profiles.len().saturating_sub(1));
+        ui.horizontal(|ui| {
+            ui.label("Edit");
+            let selected = self
+                .profiles
+                .get(self.editing)
+                .map_or("", |profile| profile.name.as_str());
+            egui::ComboBox::from_id_salt("input-profile-edit")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (index, profile) in self.profiles.iter().enumerate() {
+                        ui.selectable_value(&mut self.editing, index, &profile.name);
+                    }
+                });
+            if ui.button("New").clicked() {
+                let mut profile = self
+                    .profiles
+                    .get(self.editing)
+                    .cloned()
+                    .unwrap_or_else(InputProfile::keyboard);
+                profile.name = self.unique_name(&profile.name);
+                profile.device_match.clear();
+                self.profiles.push(profile);
+                self.editing = self.profiles.len() - 1;
+            }
+            if ui
+                .add_enabled(self.profiles.len() > 1, egui::Button::new("Delete"))
+                .clicked()
+            {
+                let removed = self.profiles.remove(self.editing);
+                for assigned in &mut self.assigned {
+                    if assigned.as_ref() == Some(&removed.name) {
+                        *assigned = None;
+                    }
+                }
+            }
+        });
+
+        let Some(profile) = self.profiles.get_mut(self.editing) else {
+            return;
+        };
+        let old_name = profile.name.clone();
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut profile.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Device match");
+            ui.text_edit_singleline(&mut profile.device_match);
+        });
+        if profile.name != old_name {
+            for assigned in &mut self.assigned {
+                if assigned.as_ref() == Some(&old_name) {
+                    *assigned = Some(profile.name.clone());
+                }
+            }
+        }
+
+        if let Some((button, turbo)) = self.capturing
+            && let Some(binding) = ui.input(captured_binding)
+        {
+            let bindings = profile.bindings_mut(button, turbo);
+            if binding != Binding::Key(Key::Escape) && !bindings.contains(&binding) {
+                bindings.push(binding);
+            }
+            self.capturing = None;
+        }
+
+        egui::Grid::new("input-bindings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                let rows = BUTTONS
+                    .map(|(button, label)| (button, false, label))
+                    .into_iter()
+                    .chain(TURBO_BUTTONS.map(|(button, label)| (button, true, label)));
+                for (button, turbo, label) in rows {
+                    ui.label(label);
+                    ui.horizontal(|ui| {
+                        let bindings = profile.bindings_mut(button, turbo);
+                        let mut remove = None;
+                        for (index, binding) in bindings.iter().enumerate() {
+                            if ui
+                                .small_button(binding.label())
+                                .on_hover_text("Click to remove")
+                                .clicked()
+                            {
+                                remove = Some(index);
+                            }
+                        }
+                        if let Some(index) = remove {
+                            bindings.remove(index);
+                        }
+                        let capturing = self.capturing == Some((button, turbo));
+                        let text = if capturing { "Press a key..." } else { "+" };
+                        if ui.selectable_label(capturing, text).clicked() {
+                            self.capturing = (!capturing).then_some((button, turbo));
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        if self.capturing.is_some() {
+            ui.label("Esc cancels.");
+        }
+    }
+
+    fn unique_name(&self, base: &str) -> String {
+        (2..)
+            .map(|n| format!("{base} {n}"))
+            .find(|name| self.profiles.iter().all(|profile| &profile.name != name))
+            .unwrap_or_default()
+    }
+}
+
+fn default_pointer_sensitivity() -> f32 {
+    1.0
+}
+
+fn default_turbo_frames() -> u8 {
+    DEFAULT_TURBO_FRAMES
+}
+
+/// A controller and the Zapper, which a fresh console had before ports
+/// were configurable.
+fn default_ports() -> [ControllerPort; PLAYERS] {
+    [ControllerPort::Gamepad, ControllerPort::Zapper]
+}
+
+fn captured_binding(input: &InputState) -> Option<Binding> {
+    let key = input.events.iter().find_map(|event| match event {
+        egui::Event::Key {
+            key, pressed: true, ..
+        } => Some(Binding::Key(*key)),
+        _ => None,
+    });
+    key.or_else(|| {
+        let pressed = input.modifiers;
+        if pressed.shift {
+            Some(Binding::Shift)
+        } else if pressed.ctrl {
+            Some(Binding::Ctrl)
+        } else if pressed.alt {
+            Some(Binding::Alt)
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,22 @@
+pub mod app;
+pub mod audio;
+pub mod boards_view;
+pub mod corruptor;
+pub mod debugger_view;
+pub mod frame_dump;
+pub mod gamepad;
+pub mod heatmap_viewer;
+pub mod hotkeys;
+pub mod input_macro;
+pub mod input_profile;
+pub mod library;
+pub mod logging;
+pub mod ppu_viewer;
+pub mod practice;
+pub mod probe_server;
+pub mod source_view;
+pub mod spectrum;
+pub mod speedrun;
+pub mod state_diff_viewer;
+pub mod storage;
+pub mod video;
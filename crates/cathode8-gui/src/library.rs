@@ -0,0 +1,450 @@
+//! ROM library: scans configured folders for `.nes` files, names them from an
+//! optional No-Intro DAT by CRC32, shows optional box art and remembers when
+//! each game was last played and for how long.
+//!
+//! Box art is looked up as `<rom stem>.png` next to the ROM or in a `boxart`
+//! folder of the library folder, also under the database title.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+
+const THUMBNAIL_SIZE: f32 = 112.0;
+/// Folders nested deeper than this below a library folder aren't scanned.
+const MAX_SCAN_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameStats {
+    /// Unix seconds.
+    pub last_played: Option<u64>,
+    pub play_seconds: f64,
+}
+
+/// The persisted part of the library. Stats are keyed by the ROM's CRC32 in
+/// hex so they follow a game across renames and folders.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibraryConfig {
+    pub folders: Vec<PathBuf>,
+    pub database: Option<PathBuf>,
+    pub grid: bool,
+    pub stats: HashMap<String, GameStats>,
+}
+
+#[derive(Debug, Clone)]
+struct LibraryEntry {
+    path: PathBuf,
+    crc: u32,
+    title: String,
+    box_art: Option<PathBuf>,
+}
+
+pub struct Library {
+    config: LibraryConfig,
+    /// Titles from the DAT, by headerless CRC32.
+    titles: HashMap<u32, String>,
+    entries: Vec<LibraryEntry>,
+    filter: String,
+    box_art: HashMap<PathBuf, Option<TextureHandle>>,
+    /// CRC of the game that's running, for play time and last-played.
+    current: Option<u32>,
+}
+
+impl Library {
+    /// Loads the database and scans the folders of `config` right away.
+    pub fn new(config: LibraryConfig) -> Self {
+        let mut library = Self {
+            config,
+            titles: HashMap::new(),
+            entries: Vec::new(),
+            filter: String::new(),
+            box_art: HashMap::new(),
+            current: None,
+        };
+        if let Some(path) = library.config.database.clone()
+            && let Ok(titles) = load_dat(&path)
+        {
+            library.titles = titles;
+        }
+        library.rescan();
+        library
+    }
+
+    pub fn config(&self) -> &LibraryConfig {
+        &self.config
+    }
+
+    /// Marks `rom` as the running game and stamps its last-played time.
+    pub fn start_game(&mut self, rom: &[u8]) {
+        let crc = rom_crc32(rom);
+        self.current = Some(crc);
+        self.stats_mut(crc).last_played = Some(unix_seconds());
+    }
+
+    /// Adds time the running game was actually emulated.
+    pub fn add_play_time(&mut self, elapsed: Duration) {
+        if let Some(crc) = self.current {
+            self.stats_mut(crc).play_seconds += elapsed.as_secs_f64();
+        }
+    }
+
+    fn stats_mut(&mut self, crc: u32) -> &mut GameStats {
+        self.config.stats.entry(format!("{crc:08X}")).or_default()
+    }
+
+    fn stats(&self, crc: u32) -> Option<&GameStats> {
+        self.config.stats.get(&format!("{crc:08X}"))
+    }
+
+    pub fn rescan(&mut self) {
+        let mut entries = Vec::new();
+        for folder in &self.config.folders {
+            let mut roms = Vec::new();
+            collect_roms(folder, MAX_SCAN_DEPTH, &mut roms);
+            for path in roms {
+                let Ok(bytes) = fs::read(&path) else {
+                    continue;
+                };
+                let crc = rom_crc32(&bytes);
+                let stem = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let title = self.titles.get(&crc).cloned().unwrap_or(stem);
+                let box_art = find_box_art(folder, &path, &title);
+                entries.push(LibraryEntry {
+                    path,
+                    crc,
+                    title,
+                    box_art,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| entry.title.to_lowercase());
+        self.entries = entries;
+    }
+
+    /// Draws the library window. Returns the ROM the user double-clicked.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        status: &mut String,
+    ) -> Option<PathBuf> {
+        let mut launch = None;
+        egui::Window::new("Library")
+            .open(open)
+            .default_size([560.0, 420.0])
+            .show(ctx, |ui| {
+                self.settings_ui(ui, status);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.filter);
+                    ui.selectable_value(&mut self.config.grid, false, "List");
+                    ui.selectable_value(&mut self.config.grid, true, "Grid");
+                });
+
+                let filter = self.filter.to_lowercase();
+                let visible: Vec<usize> = (0..self.entries.len())
+                    .filter(|&index| self.entries[index].title.to_lowercase().contains(&filter))
+                    .collect();
+                if visible.is_empty() {
+                    ui.label("No games. Add a folder containing .nes files.");
+                }
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        launch = if self.config.grid {
+                            self.grid_ui(ui, &visible)
+                        } else {
+                            self.list_ui(ui, &visible)
+                        };
+                    });
+            });
+        launch
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui, status: &mut String) {
+        let mut remove = None;
+        for (index, folder) in self.config.folders.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui
+                    .small_button("x")
+                    .on_hover_text("Remove folder")
+                    .clicked()
+                {
+                    remove = Some(index);
+                }
+                ui.monospace(folder.display().to_string());
+            });
+        }
+        let mut rescan = false;
+        if let Some(index) = remove {
+            self.config.folders.remove(index);
+            rescan = true;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add folder...").clicked()
+                && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                && !self.config.folders.contains(&folder)
+            {
+                self.config.folders.push(folder);
+                rescan = true;
+            }
+            if ui.button("Load database...").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("No-Intro DAT", &["dat", "xml"])
+                    .pick_file()
+            {
+                match load_dat(&path) {
+                    Ok(titles) => {
+                        *status = format!("Loaded {} titles from {}", titles.len(), path.display());
+                        self.titles = titles;
+                        self.config.database = Some(path);
+                        rescan = true;
+                    }
+                    Err(err) => *status = format!("Failed to load database: {err:#}"),
+                }
+            }
+            if ui.button("Rescan").clicked() {
+                rescan = true;
+            }
+            ui.label(format!(
+                "{} game(s), {} database title(s)",
+                self.entries.len(),
+                self.titles.len()
+            ));
+        });
+        if rescan {
+            self.rescan();
+        }
+    }
+
+    fn list_ui(&self, ui: &mut egui::Ui, visible: &[usize]) -> Option<PathBuf> {
+        let mut launch = None;
+        let now = unix_seconds();
+        egui::Grid::new("library-list")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Title");
+                ui.strong("Last played");
+                ui.strong("Play time");
+                ui.end_row();
+                for &index in visible {
+                    let entry = &self.entries[index];
+                    let stats = self.stats(entry.crc);
+                    let response = ui
+                        .selectable_label(false, &entry.title)
+                        .on_hover_text(entry.path.display().to_string());
+                    if response.double_clicked() {
+                        launch = Some(entry.path.clone());
+                    }
+                    ui.label(
+                        stats
+                            .and_then(|stats| stats.last_played)
+                            .map_or("never".to_string(), |at| format_ago(now.saturating_sub(at))),
+                    );
+                    ui.label(format_play_time(
+                        stats.map_or(0.0, |stats| stats.play_seconds),
+                    ));
+                    ui.end_row();
+                }
+            });
+        launch
+    }
+
+    fn grid_ui(&mut self, ui: &mut egui::Ui, visible: &[usize]) -> Option<PathBuf> {
+        let mut launch = None;
+        ui.horizontal_wrapped(|ui| {
+            for &index in visible {
+                let texture = self.entries[index]
+                    .box_art
+                    .clone()
+                    .and_then(|path| self.box_art_texture(ui.ctx(), &path));
+                let entry = &self.entries[index];
+                let response = ui
+                    .allocate_ui(egui::vec2(THUMBNAIL_SIZE, THUMBNAIL_SIZE + 36.0), |ui| {
+                        ui.vertical_centered(|ui| {
+                            let size = egui::vec2(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+                            match &texture {
+                                Some(texture) => {
+                                    ui.add(egui::Image::new(texture).max_size(size));
+                                }
+                                None => {
+                                    let (rect, _) =
+                                        ui.allocate_exact_size(size, egui::Sense::hover());
+                                    ui.painter().rect_filled(
+                                        rect,
+                                        4.0,
+                                        egui::Color32::from_gray(40),
+                                    );
+                                }
+                            }
+                            ui.add(egui::Label::new(&entry.title).truncate());
+                        })
+                    })
+                    .response
+                    .interact(egui::Sense::click())
+                    .on_hover_text(entry.path.display().to_string());
+                if response.double_clicked() {
+                    launch = Some(entry.path.clone());
+                }
+            }
+        });
+        launch
+    }
+
+    /// Box art is decoded once per path; a file that fails to decode is
+    /// remembered as missing.
+    fn box_art_texture(&mut self, ctx: &egui::Context, path: &Path) -> Option<TextureHandle> {
+        self.box_art
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let image = load_png(path).ok()?;
+                Some(ctx.load_texture(
+                    format!("boxart-{}", path.display()),
+                    image,
+                    TextureOptions::LINEAR,
+                ))
+            })
+            .clone()
+    }
+}
+
+/// CRC32 of the ROM without its iNES header and trainer, which is what
+/// No-Intro DATs list.
+pub fn rom_crc32(rom: &[u8]) -> u32 {
+    let mut skip = 0;
+    if rom.starts_with(b"NES\x1A") && rom.len() >= 16 {
+        skip = 16;
+        if rom[6] & 0x04 != 0 {
+            skip += 512;
+        }
+    }
+    crc32fast::hash(rom.get(skip..).unwrap_or_default())
+}
+
+/// Reads `<game name="...">` titles keyed by the CRC of their `<rom>`.
+fn load_dat(path: &Path) -> Result<HashMap<u32, String>> {
+    let xml = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut reader = Reader::from_str(&xml);
+    reader.config_mut().trim_text(true);
+
+    let mut titles = HashMap::new();
+    let mut game = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let name = e.name();
+                let attr = |key: &[u8]| {
+                    e.attributes().flatten().find_map(|attr| {
+                        (attr.key.as_ref() == key).then(|| {
+                            attr.decode_and_unescape_value(reader.decoder())
+                                .map(|value| value.into_owned())
+                                .unwrap_or_default()
+                        })
+                    })
+                };
+                if name.as_ref() == b"game" {
+                    game = attr(b"name");
+                } else if name.as_ref() == b"rom"
+                    && let (Some(title), Some(crc)) = (&game, attr(b"crc"))
+                    && let Ok(crc) = u32::from_str_radix(&crc, 16)
+                {
+                    titles.insert(crc, title.clone());
+                }
+            }
+            Ok(Event::End(e)) if e.name().as_ref() == b"game" => game = None,
+            Ok(Event::Eof) => break,
+            Err(err) => return Err(err).context("parsing DAT"),
+            _ => {}
+        }
+    }
+    Ok(titles)
+}
+
+fn collect_roms(dir: &Path, depth: usize, roms: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 0 {
+                collect_roms(&path, depth - 1, roms);
+            }
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("nes"))
+        {
+            roms.push(path);
+        }
+    }
+}
+
+fn find_box_art(folder: &Path, rom: &Path, title: &str) -> Option<PathBuf> {
+    let boxart = folder.join("boxart");
+    let mut candidates = vec![rom.with_extension("png")];
+    if let Some(stem) = rom.file_stem() {
+        candidates.push(boxart.join(stem).with_extension("png"));
+    }
+    candidates.push(boxart.join(format!("{title}.png")));
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+fn load_png(path: &Path) -> Result<ColorImage> {
+    let file = fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut buffer)?;
+    let size = [info.width as usize, info.height as usize];
+    let pixels = &buffer[..info.buffer_size()];
+    Ok(match info.color_type {
+        png::ColorType::Rgba => ColorImage::from_rgba_unmultiplied(size, pixels),
+        png::ColorType::Rgb => ColorImage::from_rgb(size, pixels),
+        png::ColorType::GrayscaleAlpha => {
+            let rgba: Vec<u8> = pixels
+                .chunks_exact(2)
+                .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+                .collect();
+            ColorImage::from_rgba_unmultiplied(size, &rgba)
+        }
+        _ => ColorImage::from_gray(size, pixels),
+    })
+}
+
+fn unix_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn format_ago(seconds: u64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} min ago", seconds / 60),
+        3600..86_400 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86_400),
+    }
+}
+
+fn format_play_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0) as u64;
+    if minutes < 60 {
+        format!("{minutes} min")
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
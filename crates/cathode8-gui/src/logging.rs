@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer, SubscriberExt};
+
+use cathode8_core::nes::log_target;
+
+const MAX_LOG_ENTRIES: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Cpu,
+    Ppu,
+    Apu,
+    Mapper,
+    Dma,
+    Other,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 6] = [
+        Subsystem::Cpu,
+        Subsystem::Ppu,
+        Subsystem::Apu,
+        Subsystem::Mapper,
+        Subsystem::Dma,
+        Subsystem::Other,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Subsystem::Cpu => "cpu",
+            Subsystem::Ppu => "ppu",
+            Subsystem::Apu => "apu",
+            Subsystem::Mapper => "mapper",
+            Subsystem::Dma => "dma",
+            Subsystem::Other => "other",
+        }
+    }
+
+    fn from_target(target: &str) -> Self {
+        match target {
+            log_target::CPU => Subsystem::Cpu,
+            log_target::PPU => Subsystem::Ppu,
+            log_target::APU => Subsystem::Apu,
+            log_target::MAPPER => Subsystem::Mapper,
+            log_target::DMA => Subsystem::Dma,
+            _ => Subsystem::Other,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub sequence: u64,
+    pub level: Level,
+    pub subsystem: Subsystem,
+    pub message: String,
+}
+
+struct LogShared {
+    levels: [AtomicU8; 6],
+    next_sequence: AtomicU64,
+    entries: Mutex<VecDeque<LogEntry>>,
+    file: Mutex<Option<BufWriter<File>>>,
+}
+
+impl LogShared {
+    fn allows(&self, metadata: &Metadata<'_>) -> bool {
+        let subsystem = Subsystem::from_target(metadata.target());
+        let max = self.levels[subsystem.index()].load(Ordering::Relaxed);
+        level_to_u8(Some(*metadata.level())) <= max
+    }
+}
+
+/// Cheap, cloneable handle to the runtime log filters and captured entries.
+#[derive(Clone)]
+pub struct LogHandle {
+    shared: Arc<LogShared>,
+}
+
+impl LogHandle {
+    fn new() -> Self {
+        let default_level = |subsystem: Subsystem| {
+            let level = if subsystem == Subsystem::Other {
+                Level::INFO
+            } else {
+                Level::WARN
+            };
+            AtomicU8::new(level_to_u8(Some(level)))
+        };
+
+        Self {
+            shared: Arc::new(LogShared {
+                levels: Subsystem::ALL.map(default_level),
+                next_sequence: AtomicU64::new(0),
+                entries: Mutex::new(VecDeque::with_capacity(MAX_LOG_ENTRIES)),
+                file: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Maximum level captured for `subsystem`; `None` means the subsystem is silenced.
+    pub fn level(&self, subsystem: Subsystem) -> Option<Level> {
+        u8_to_level(self.shared.levels[subsystem.index()].load(Ordering::Relaxed))
+    }
+
+    pub fn set_level(&self, subsystem: Subsystem, level: Option<Level>) {
+        self.shared.levels[subsystem.index()].store(level_to_u8(level), Ordering::Relaxed);
+    }
+
+    pub fn recent(&self, limit: usize) -> Vec<LogEntry> {
+        let Ok(entries) = self.shared.entries.lock() else {
+            return Vec::new();
+        };
+        let skip = entries.len().saturating_sub(limit);
+        entries.iter().skip(skip).cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.shared.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    pub fn start_file_capture(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create log file: {}", path.display()))?;
+        if let Ok(mut slot) = self.shared.file.lock() {
+            *slot = Some(BufWriter::new(file));
+        }
+        Ok(())
+    }
+
+    pub fn stop_file_capture(&self) {
+        if let Ok(mut slot) = self.shared.file.lock()
+            && let Some(mut writer) = slot.take()
+        {
+            let _ = writer.flush();
+        }
+    }
+
+    pub fn is_capturing_to_file(&self) -> bool {
+        self.shared
+            .file
+            .lock()
+            .map(|slot| slot.is_some())
+            .unwrap_or(false)
+    }
+}
+
+/// Installs the global tracing subscriber on first call and returns its handle.
+/// Later calls return the same handle.
+pub fn init() -> LogHandle {
+    static HANDLE: OnceLock<LogHandle> = OnceLock::new();
+    HANDLE
+        .get_or_init(|| {
+            let handle = LogHandle::new();
+            let layer = CaptureLayer {
+                shared: Arc::clone(&handle.shared),
+            };
+            let subscriber = tracing_subscriber::registry().with(layer);
+            // Another subscriber may already be installed (e.g. by a test harness);
+            // the handle still works, it just won't receive events.
+            let _ = tracing::subscriber::set_global_default(subscriber);
+            handle
+        })
+        .clone()
+}
+
+struct CaptureLayer {
+    shared: Arc<LogShared>,
+}
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // Filters change at runtime, so every callsite must be re-checked.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: LayerContext<'_, S>) -> bool {
+        self.shared.allows(metadata)
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let entry = LogEntry {
+            sequence: self.shared.next_sequence.fetch_add(1, Ordering::Relaxed),
+            level: *metadata.level(),
+            subsystem: Subsystem::from_target(metadata.target()),
+            message: visitor.message,
+        };
+
+        if let Ok(mut file) = self.shared.file.lock()
+            && let Some(writer) = file.as_mut()
+        {
+            let _ = writeln!(
+                writer,
+                "{} {:5} [{}] {}",
+                entry.sequence,
+                entry.level,
+                entry.subsystem.label(),
+                entry.message
+            );
+        }
+
+        if let Ok(mut entries) = self.shared.entries.lock() {
+            if entries.len() >= MAX_LOG_ENTRIES {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.message, " {}={value:?}", field.name());
+        }
+    }
+}
+
+fn level_to_u8(level: Option<Level>) -> u8 {
+    match level {
+        None => 0,
+        Some(Level::ERROR) => 1,
+        Some(Level::WARN) => 2,
+        Some(Level::INFO) => 3,
+        Some(Level::DEBUG) => 4,
+        Some(_) => 5,
+    }
+}
+
+fn u8_to_level(value: u8) -> Option<Level> {
+    match value {
+        1 => Some(Level::ERROR),
+        2 => Some(Level::WARN),
+        3 => Some(Level::INFO),
+        4 => Some(Level::DEBUG),
+        5 => Some(Level::TRACE),
+        _ => None,
+    }
+}
@@ -1,6 +1,7 @@
-use cathode8::app;
+use cathode8_gui::app;
 
 fn main() -> anyhow::Result<()> {
+    let launch = app::LaunchArgs::parse(std::env::args_os().skip(1))?;
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
             .with_inner_size([1024.0, 720.0])
@@ -14,7 +15,7 @@ fn main() -> anyhow::Result<()> {
     eframe::run_native(
         "Cathode-8",
         options,
-        Box::new(|cc| Ok(Box::new(app::NesApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(app::NesApp::new(cc, launch)))),
     )
     .map_err(|err| anyhow::anyhow!("failed to run app: {err}"))
 }
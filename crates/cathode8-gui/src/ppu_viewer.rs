@@ -0,0 +1,380 @@
+//! Nametable, pattern table, OAM and palette viewers, with PNG export so the
+//! exact PPU state can be dropped into documentation.
+//!
+//! The pattern view can also dump every CHR bank, as raw `.chr` or as a PNG
+//! sheet, and for CHR RAM games load edited tiles back in while running.
+//! The palette view lists all eight palettes with their $3F00-$3F1F values
+//! and lets any entry be changed live for quick mockups.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use cathode8_core::nes::Nes;
+use cathode8_core::nes::ppu_view::PpuImage;
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Nametables,
+    Patterns,
+    Oam,
+    Palette,
+}
+
+impl View {
+    const ALL: [View; 4] = [View::Nametables, View::Patterns, View::Oam, View::Palette];
+
+    fn label(self) -> &'static str {
+        match self {
+            View::Nametables => "Nametables",
+            View::Patterns => "Patterns",
+            View::Oam => "OAM",
+            View::Palette => "Palette",
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            View::Nametables => "nametables.png",
+            View::Patterns => "patterns.png",
+            View::Oam => "oam.png",
+            View::Palette => "palette.png",
+        }
+    }
+
+    /// On-screen magnification; the exported PNG is always 1:1.
+    fn zoom(self) -> f32 {
+        match self {
+            View::Nametables => 1.0,
+            View::Patterns => 2.0,
+            View::Oam => 3.0,
+            View::Palette => 2.0,
+        }
+    }
+}
+
+pub struct PpuViewer {
+    view: View,
+    /// Palette used to color the pattern tables, 0-3 background, 4-7 sprite.
+    pattern_palette: u8,
+    /// Palette RAM entry (0-31) the color picker edits.
+    editing_palette_entry: Option<usize>,
+    texture: Option<TextureHandle>,
+}
+
+impl Default for PpuViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PpuViewer {
+    pub fn new() -> Self {
+        Self {
+            view: View::Nametables,
+            pattern_palette: 0,
+            editing_palette_entry: None,
+            texture: None,
+        }
+    }
+
+    /// Draws the viewer window. Returns `false` once the user closes it, and
+    /// reports export results through `status`.
+    pub fn show(&mut self, ctx: &egui::Context, nes: &mut Nes, status: &mut String) -> bool {
+        let mut open = true;
+        egui::Window::new("PPU Viewer")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for view in View::ALL {
+                        ui.selectable_value(&mut self.view, view, view.label());
+                    }
+                });
+                if self.view == View::Nametables {
+                    nametable_mapping_ui(ui, nes);
+                }
+                if self.view == View::Patterns {
+                    ui.add(egui::Slider::new(&mut self.pattern_palette, 0..=7).text("Palette"));
+                    self.chr_tools_ui(ui, nes, status);
+                }
+
+                let image = self.render(nes);
+                let size = egui::vec2(image.width as f32, image.height as f32) * self.view.zoom();
+                let color_image =
+                    ColorImage::from_rgba_unmultiplied([image.width, image.height], &image.rgba);
+                let texture = match self.texture.as_mut() {
+                    Some(texture) => {
+                        texture.set(color_image, TextureOptions::NEAREST);
+                        texture
+                    }
+                    None => self.texture.insert(ctx.load_texture(
+                        "ppu-viewer",
+                        color_image,
+                        TextureOptions::NEAREST,
+                    )),
+                };
+                if self.view == View::Palette {
+                    self.palette_editor_ui(ui, nes);
+                } else {
+                    ui.add(egui::Image::new(&*texture).fit_to_exact_size(size));
+                }
+
+                if ui.button("Export PNG...").clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .add_filter("PNG image", &["png"])
+                        .set_file_name(self.view.file_name())
+                        .save_file()
+                {
+                    *status = match save_png(&path, &image) {
+                        Ok(()) => format!("Saved {}", path.display()),
+                        Err(err) => format!("PNG export failed: {err:#}"),
+                    };
+                }
+            });
+        open
+    }
+
+    /// Whole-CHR export, and import into CHR RAM. Sheets use the palette
+    /// picked above, which an imported sheet must have been exported with.
+    fn chr_tools_ui(&self, ui: &mut egui::Ui, nes: &mut Nes, status: &mut String) {
+        let has_chr_ram = nes.has_chr_ram();
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "CHR {}: {} KB",
+                if has_chr_ram { "RAM" } else { "ROM" },
+                nes.chr_data().len() / 1024
+            ));
+            if ui.button("Export .chr...").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CHR data", &["chr"])
+                    .set_file_name("chr.chr")
+                    .save_file()
+            {
+                *status = match std::fs::write(&path, nes.chr_data()) {
+                    Ok(()) => format!("Saved {}", path.display()),
+                    Err(err) => format!("CHR export failed: {err}"),
+                };
+            }
+            if ui.button("Export sheet PNG...").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("PNG image", &["png"])
+                    .set_file_name("chr-sheet.png")
+                    .save_file()
+            {
+                let sheet = nes.render_chr_sheet(self.pattern_palette);
+                *status = match save_png(&path, &sheet) {
+                    Ok(()) => format!("Saved {}", path.display()),
+                    Err(err) => format!("PNG export failed: {err:#}"),
+                };
+            }
+            if ui
+                .add_enabled(has_chr_ram, egui::Button::new("Import..."))
+                .on_disabled_hover_text("CHR ROM can't be changed")
+                .on_hover_text("Load a .chr dump or an edited sheet PNG into CHR RAM")
+                .clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CHR data or sheet", &["chr", "bin", "png"])
+                    .pick_file()
+            {
+                *status = match import_chr(nes, &path, self.pattern_palette) {
+                    Ok(()) => format!("Imported {} into CHR RAM", path.display()),
+                    Err(err) => format!("CHR import failed: {err:#}"),
+                };
+            }
+        });
+    }
+
+    /// Swatches for the eight palettes; clicking one opens a picker of the
+    /// 64 NES colors that writes through [`Nes::debug_poke_palette`].
+    fn palette_editor_ui(&mut self, ui: &mut egui::Ui, nes: &mut Nes) {
+        egui::Grid::new("palette-editor")
+            .spacing([6.0, 4.0])
+            .show(ui, |ui| {
+                for palette in 0..8usize {
+                    let kind = if palette < 4 { "BG" } else { "SPR" };
+                    ui.monospace(format!(
+                        "{kind}{} ${:04X}",
+                        palette % 4,
+                        0x3F00 + palette * 4
+                    ));
+                    for entry in palette * 4..palette * 4 + 4 {
+                        let value = nes.debug_peek_palette(stored_palette_entry(entry)) & 0x3F;
+                        let selected = self.editing_palette_entry == Some(entry);
+                        let swatch = egui::Button::new(
+                            egui::RichText::new(format!("{value:02X}"))
+                                .monospace()
+                                .color(text_color(nes.color_rgb(value))),
+                        )
+                        .fill(color32(nes.color_rgb(value)))
+                        .min_size(egui::vec2(36.0, 22.0))
+                        .selected(selected);
+                        if ui
+                            .add(swatch)
+                            .on_hover_text(format!("${:04X} = ${value:02X}", 0x3F00 + entry))
+                            .clicked()
+                        {
+                            self.editing_palette_entry = (!selected).then_some(entry);
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+        let Some(entry) = self.editing_palette_entry else {
+            ui.label("Click a swatch to change it.");
+            return;
+        };
+        ui.separator();
+        ui.label(format!("${:04X}:", 0x3F00 + entry));
+        let current = nes.debug_peek_palette(stored_palette_entry(entry)) & 0x3F;
+        egui::Grid::new("palette-picker")
+            .spacing([2.0, 2.0])
+            .show(ui, |ui| {
+                for color in 0..0x40u8 {
+                    let swatch = egui::Button::new("")
+                        .fill(color32(nes.color_rgb(color)))
+                        .min_size(egui::vec2(18.0, 18.0))
+                        .selected(color == current);
+                    if ui
+                        .add(swatch)
+                        .on_hover_text(format!("${color:02X}"))
+                        .clicked()
+                    {
+                        nes.debug_poke_palette(entry, color);
+                    }
+                    if color % 16 == 15 {
+                        ui.end_row();
+                    }
+                }
+            });
+    }
+
+    fn render(&self, nes: &Nes) -> PpuImage {
+        match self.view {
+            View::Nametables => nes.render_nametables(),
+            View::Patterns => nes.render_pattern_tables(self.pattern_palette),
+            View::Oam => nes.render_oam(),
+            View::Palette => nes.render_palette(),
+        }
+    }
+}
+
+/// $3F10/$3F14/$3F18/$3F1C are stored in the background entries.
+fn stored_palette_entry(entry: usize) -> usize {
+    if entry >= 16 && entry.is_multiple_of(4) {
+        entry - 16
+    } else {
+        entry
+    }
+}
+
+fn color32([r, g, b]: [u8; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(r, g, b)
+}
+
+/// Black or white, whichever reads better on `rgb`.
+fn text_color([r, g, b]: [u8; 3]) -> egui::Color32 {
+    let luma = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+    if luma > 128.0 {
+        egui::Color32::BLACK
+    } else {
+        egui::Color32::WHITE
+    }
+}
+
+/// Mirroring plus where each logical table lives, and any remapping the
+/// mapper did partway through the last frame.
+fn nametable_mapping_ui(ui: &mut egui::Ui, nes: &Nes) {
+    let mirroring = nes
+        .mirroring()
+        .map_or("none", |mirroring| mirroring.label());
+    let sources = nes.nametable_sources();
+    ui.horizontal(|ui| {
+        ui.label(format!("Mirroring: {mirroring}"));
+        egui::Grid::new("nametable-sources").show(ui, |ui| {
+            for (row, pair) in sources.chunks(2).enumerate() {
+                for (column, source) in pair.iter().enumerate() {
+                    let table = row * 2 + column;
+                    ui.monospace(format!("${:04X}: {source}", 0x2000 + table * 0x400));
+                }
+                ui.end_row();
+            }
+        });
+    });
+
+    let lines = nes.debug_nametable_lines();
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for line in 0..lines.len() {
+        match runs.last_mut() {
+            Some((_, end)) if lines[*end] == lines[line] => *end = line,
+            _ => runs.push((line, line)),
+        }
+    }
+    if runs.len() > 1 {
+        ui.label("Changed during the last frame:");
+        for (start, end) in runs {
+            let tables: Vec<String> = lines[start].iter().map(ToString::to_string).collect();
+            ui.monospace(format!("lines {start:3}-{end:3}: {}", tables.join(" | ")));
+        }
+    }
+}
+
+/// Raw data is written from the start of CHR RAM; a PNG is read as a sheet
+/// colored with `palette`.
+fn import_chr(nes: &mut Nes, path: &Path, palette: u8) -> Result<()> {
+    let is_png = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png {
+        nes.import_chr_sheet(&load_png(path)?, palette)
+    } else {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        nes.write_chr_ram(0, &data)
+    }
+}
+
+/// Decodes an 8-bit RGB or RGBA PNG.
+fn load_png(path: &Path) -> Result<PpuImage> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut buffer)?;
+    if info.bit_depth != png::BitDepth::Eight {
+        bail!("expected an 8-bit PNG");
+    }
+    let pixels = &buffer[..info.buffer_size()];
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+            .collect(),
+        other => bail!("unsupported PNG color type {other:?}"),
+    };
+    Ok(PpuImage {
+        width: info.width as usize,
+        height: info.height as usize,
+        rgba,
+    })
+}
+
+pub fn save_png(path: &Path, image: &PpuImage) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.rgba)?;
+    writer.finish()?;
+    Ok(())
+}
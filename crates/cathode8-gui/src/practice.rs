@@ -0,0 +1,184 @@
+//! Practice mode: RAM triggers (the autosplitter's condition syntax, one
+//! named line each) capture a state whenever they fire, e.g. on every room
+//! or level change, and the panel jumps back to any captured point.
+//!
+//! Captured states live in memory for the session; the oldest are dropped
+//! once the library is full.
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use cathode8_core::Nes;
+use cathode8_core::autosplit::RamTriggers;
+use cathode8_core::session::{SessionTimeline, TimelineState};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+const MAX_POINTS: usize = 100;
+
+const EXAMPLE_SCRIPT: &str = "# name: conditions, as in the speedrun autosplitter\n\
+level: $0760 changes\n\
+world: $075F changes\n";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PracticeConfig {
+    pub script: String,
+    pub enabled: bool,
+}
+
+impl Default for PracticeConfig {
+    fn default() -> Self {
+        Self {
+            script: EXAMPLE_SCRIPT.to_string(),
+            enabled: false,
+        }
+    }
+}
+
+struct PracticePoint {
+    name: String,
+    state: TimelineState,
+}
+
+pub enum PracticeRequest {
+    Load(usize),
+    CaptureNow,
+}
+
+pub struct PracticeLibrary {
+    config: PracticeConfig,
+    triggers: Option<RamTriggers>,
+    error: Option<String>,
+    points: VecDeque<PracticePoint>,
+    captured: usize,
+}
+
+impl PracticeLibrary {
+    pub fn new(config: PracticeConfig) -> Self {
+        let mut library = Self {
+            config,
+            triggers: None,
+            error: None,
+            points: VecDeque::new(),
+            captured: 0,
+        };
+        library.apply_script();
+        library
+    }
+
+    pub fn config(&self) -> &PracticeConfig {
+        &self.config
+    }
+
+    /// Checks the triggers after a frame and captures a state if any fired.
+    /// Returns the name of the new point.
+    pub fn after_frame(&mut self, nes: &Nes, timeline: &SessionTimeline) -> Option<String> {
+        let fired = self.triggers.as_mut()?.after_frame(nes).join(", ");
+        if fired.is_empty() {
+            return None;
+        }
+        self.capture(&fired, nes, timeline).ok()
+    }
+
+    pub fn capture(&mut self, name: &str, nes: &Nes, timeline: &SessionTimeline) -> Result<String> {
+        let state = timeline.save_state(nes)?;
+        self.captured += 1;
+        let name = format!("#{} {name}", self.captured);
+        if self.points.len() == MAX_POINTS {
+            self.points.pop_front();
+        }
+        self.points.push_back(PracticePoint {
+            name: name.clone(),
+            state,
+        });
+        Ok(name)
+    }
+
+    pub fn state(&self, index: usize) -> Option<(&str, &TimelineState)> {
+        self.points
+            .get(index)
+            .map(|point| (point.name.as_str(), &point.state))
+    }
+
+    /// Call after any jump (state load, rewind) so it isn't taken for a
+    /// room change.
+    pub fn rearm(&mut self) {
+        if let Some(triggers) = self.triggers.as_mut() {
+            triggers.rearm();
+        }
+    }
+
+    /// Points from a different game are useless, so a ROM change drops them.
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.captured = 0;
+        self.rearm();
+    }
+
+    fn apply_script(&mut self) {
+        self.error = None;
+        self.triggers = None;
+        if !self.config.enabled {
+            return;
+        }
+        match RamTriggers::parse(&self.config.script) {
+            Ok(triggers) => self.triggers = Some(triggers),
+            Err(err) => self.error = Some(format!("{err:#}")),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool) -> Option<PracticeRequest> {
+        let mut request = None;
+        egui::Window::new("Practice")
+            .open(open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let mut enabled = self.config.enabled;
+                    ui.checkbox(&mut enabled, "Capture on triggers");
+                    if ui.button("Apply triggers").clicked() || enabled != self.config.enabled {
+                        self.config.enabled = enabled;
+                        self.apply_script();
+                    }
+                    if ui.button("Capture now").clicked() {
+                        request = Some(PracticeRequest::CaptureNow);
+                    }
+                });
+                if let Some(err) = &self.error {
+                    ui.colored_label(egui::Color32::YELLOW, err);
+                }
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.config.script)
+                        .code_editor()
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+
+                ui.separator();
+                if self.points.is_empty() {
+                    ui.label("No practice points captured yet.");
+                }
+                let mut remove = None;
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for (index, point) in self.points.iter().enumerate().rev() {
+                            ui.horizontal(|ui| {
+                                if ui.button("Load").clicked() {
+                                    request = Some(PracticeRequest::Load(index));
+                                }
+                                if ui.small_button("x").on_hover_text("Delete").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.label(format!("{} (frame {})", point.name, point.state.frame()));
+                            });
+                        }
+                    });
+                if let Some(index) = remove {
+                    self.points.remove(index);
+                }
+            });
+        request
+    }
+}
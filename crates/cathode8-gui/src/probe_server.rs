@@ -0,0 +1,172 @@
+//! TCP transport for the debug probe protocol in
+//! [`cathode8_core::probe`]: external tools connect to a localhost port and
+//! send one request per line. Any number of clients may connect; each gets
+//! its own replies, and `break` notices go to all of them.
+//!
+//! Sockets are serviced on background threads, but requests are applied on
+//! the UI thread between frames, so the console is never touched mid-frame
+//! from outside.
+
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+pub const DEFAULT_PROBE_PORT: u16 = 6502;
+/// How often the accept loop checks whether the server was stopped.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct ProbeRequest {
+    client: usize,
+    pub line: String,
+}
+
+type Clients = Arc<Mutex<Vec<(usize, TcpStream)>>>;
+
+pub struct ProbeServer {
+    addr: SocketAddr,
+    requests: Receiver<ProbeRequest>,
+    clients: Clients,
+    stop: Arc<AtomicBool>,
+}
+
+impl ProbeServer {
+    /// Listens on 127.0.0.1 only; the protocol has no authentication.
+    pub fn start(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("listening on port {port}"))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let (sender, requests) = mpsc::channel();
+        let clients = Clients::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let clients = clients.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("debug-probe".to_string())
+                .spawn(move || accept_loop(&listener, &sender, &clients, &stop))?;
+        }
+        tracing::info!("debug probe listening on {addr}");
+        Ok(Self {
+            addr,
+            requests,
+            clients,
+            stop,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().map_or(0, |clients| clients.len())
+    }
+
+    pub fn next_request(&self) -> Option<ProbeRequest> {
+        self.requests.try_recv().ok()
+    }
+
+    pub fn reply(&self, request: &ProbeRequest, line: &str) {
+        self.send(Some(request.client), line);
+    }
+
+    /// Sends an unsolicited line, such as a breakpoint stop, to everyone.
+    pub fn broadcast(&self, line: &str) {
+        self.send(None, line);
+    }
+
+    fn send(&self, to: Option<usize>, line: &str) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        // A client that can't take a line is treated as gone.
+        clients.retain_mut(|(id, stream)| {
+            if to.is_some_and(|to| to != *id) {
+                return true;
+            }
+            stream
+                .write_all(format!("{line}\n").as_bytes())
+                .inspect_err(|err| tracing::warn!("debug probe client {id} dropped: {err}"))
+                .is_ok()
+        });
+    }
+}
+
+impl Drop for ProbeServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Ok(clients) = self.clients.lock() {
+            for (_, stream) in clients.iter() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+fn accept_loop(
+    listener: &TcpListener,
+    sender: &Sender<ProbeRequest>,
+    clients: &Clients,
+    stop: &AtomicBool,
+) {
+    let mut next_id = 0;
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let id = next_id;
+                next_id += 1;
+                if let Err(err) = add_client(id, stream, sender.clone(), clients.clone()) {
+                    tracing::warn!("debug probe client {peer} rejected: {err:#}");
+                    continue;
+                }
+                tracing::info!("debug probe client {id} connected from {peer}");
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(err) => {
+                tracing::warn!("debug probe stopped accepting: {err}");
+                return;
+            }
+        }
+    }
+}
+
+fn add_client(
+    id: usize,
+    stream: TcpStream,
+    sender: Sender<ProbeRequest>,
+    clients: Clients,
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let reader = BufReader::new(stream.try_clone()?);
+    if let Ok(mut clients) = clients.lock() {
+        clients.push((id, stream));
+    }
+    thread::Builder::new()
+        .name(format!("debug-probe-{id}"))
+        .spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                let line = line.trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                if sender.send(ProbeRequest { client: id, line }).is_err() {
+                    break;
+                }
+            }
+            if let Ok(mut clients) = clients.lock() {
+                clients.retain(|(client, _)| *client != id);
+            }
+        })?;
+    Ok(())
+}
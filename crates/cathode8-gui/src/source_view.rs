@@ -0,0 +1,269 @@
+//! Source window for homebrew built with cc65: when `game.dbg` (from
+//! `ld65 --dbgfile`) sits next to `game.nes`, shows the source line the CPU
+//! is on beside a disassembly, and toggles breakpoints by clicking lines.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use cathode8_core::Nes;
+use cathode8_core::cc65_dbg::DebugInfo;
+use eframe::egui;
+
+/// Instructions listed in the disassembly column, starting at the PC.
+const DISASSEMBLY_LINES: usize = 24;
+
+pub enum SourceRequest {
+    /// Pause if running, then run one instruction.
+    Step,
+    Continue,
+}
+
+struct LoadedSource {
+    dbg_path: PathBuf,
+    info: DebugInfo,
+    /// Lines of each file in `info.files()`, `None` if it wasn't found.
+    texts: Vec<Option<Vec<String>>>,
+}
+
+#[derive(Default)]
+pub struct SourceView {
+    loaded: Option<LoadedSource>,
+    file: usize,
+    /// Switches file and scrolls to wherever the PC goes.
+    follow_pc: bool,
+    /// PC the view last scrolled to, so following doesn't fight manual
+    /// scrolling while the PC stays put.
+    scrolled_to: Option<u16>,
+}
+
+impl SourceView {
+    pub fn new() -> Self {
+        Self {
+            follow_pc: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.is_some()
+    }
+
+    /// Loads the debug info next to `rom`, if there is any. Returns a note
+    /// for the status line when something was found.
+    pub fn load_for_rom(&mut self, rom: &Path) -> Option<String> {
+        self.loaded = None;
+        self.file = 0;
+        self.scrolled_to = None;
+        let dbg_path = rom.with_extension("dbg");
+        if !dbg_path.exists() {
+            return None;
+        }
+        let result = std::fs::read_to_string(&dbg_path)
+            .with_context(|| format!("reading {}", dbg_path.display()))
+            .and_then(|text| DebugInfo::parse(&text));
+        match result {
+            Ok(info) => {
+                let dir = dbg_path.parent().unwrap_or(Path::new("."));
+                let texts: Vec<_> = info
+                    .files()
+                    .iter()
+                    .map(|name| {
+                        let path = resolve_source(dir, name)?;
+                        let text = std::fs::read_to_string(path).ok()?;
+                        Some(text.lines().map(str::to_string).collect())
+                    })
+                    .collect();
+                let found = texts.iter().flatten().count();
+                let note = format!("source info for {found} of {} files", texts.len());
+                self.loaded = Some(LoadedSource {
+                    dbg_path,
+                    info,
+                    texts,
+                });
+                Some(note)
+            }
+            Err(err) => Some(format!("debug info not loaded: {err:#}")),
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        nes: &mut Nes,
+    ) -> Option<SourceRequest> {
+        let mut request = None;
+        egui::Window::new("Source")
+            .open(open)
+            .default_size([760.0, 480.0])
+            .show(ctx, |ui| {
+                let Some(loaded) = &self.loaded else {
+                    ui.label("No debug info. Build with ld65 --dbgfile and keep game.dbg next to game.nes.");
+                    return;
+                };
+                let pc = nes.debug_pc();
+                let here = loaded.info.locate(nes, pc).first().map(|line| (line.file, line.line));
+                if self.follow_pc
+                    && let Some((file, _)) = here
+                {
+                    self.file = file;
+                }
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("source_file")
+                        .selected_text(file_label(&loaded.info, self.file))
+                        .show_ui(ui, |ui| {
+                            for index in 0..loaded.info.files().len() {
+                                ui.selectable_value(
+                                    &mut self.file,
+                                    index,
+                                    file_label(&loaded.info, index),
+                                );
+                            }
+                        });
+                    ui.checkbox(&mut self.follow_pc, "Follow PC");
+                    if ui.button("Step").clicked() {
+                        request = Some(SourceRequest::Step);
+                    }
+                    if ui.button("Continue").clicked() {
+                        request = Some(SourceRequest::Continue);
+                    }
+                    let location = here.map_or("no source".to_string(), |(file, line)| {
+                        format!("{}:{line}", file_label(&loaded.info, file))
+                    });
+                    ui.monospace(format!("PC ${pc:04X}  {location}"));
+                });
+                ui.label(
+                    egui::RichText::new(loaded.dbg_path.display().to_string())
+                        .small()
+                        .weak(),
+                );
+                ui.separator();
+
+                let breakpoints: BTreeSet<u16> = nes.breakpoints().collect();
+                let mut toggle = None;
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width((ui.available_width() - 260.0).max(200.0));
+                        let Some(Some(lines)) = loaded.texts.get(self.file) else {
+                            ui.label("Source file not found next to the .dbg file.");
+                            return;
+                        };
+                        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                        let mut scroll = egui::ScrollArea::both()
+                            .id_salt("source_lines")
+                            .auto_shrink(false);
+                        let current_line = here
+                            .filter(|&(file, _)| file == self.file)
+                            .map(|(_, line)| line);
+                        if self.follow_pc
+                            && self.scrolled_to != Some(pc)
+                            && let Some(line) = current_line
+                        {
+                            let spacing = ui.spacing().item_spacing.y;
+                            let row = line.saturating_sub(8) as f32;
+                            scroll = scroll.vertical_scroll_offset(row * (row_height + spacing));
+                            self.scrolled_to = Some(pc);
+                        }
+                        scroll.show_rows(ui, row_height, lines.len(), |ui, rows| {
+                            for index in rows {
+                                let number = index as u32 + 1;
+                                let has_code = loaded.info.has_code(self.file, number);
+                                let addrs = if has_code {
+                                    loaded.info.addresses(self.file, number)
+                                } else {
+                                    Vec::new()
+                                };
+                                let armed = addrs.iter().any(|addr| breakpoints.contains(addr));
+                                let marker = if armed { "●" } else { " " };
+                                let mut text = egui::RichText::new(format!(
+                                    "{marker}{number:>5}  {}",
+                                    lines[index]
+                                ))
+                                .monospace();
+                                if current_line == Some(number) {
+                                    text = text.background_color(egui::Color32::from_rgb(70, 60, 10));
+                                } else if !has_code {
+                                    text = text.weak();
+                                }
+                                if armed {
+                                    text = text.color(egui::Color32::from_rgb(255, 120, 120));
+                                }
+                                let response = ui.add(
+                                    egui::Label::new(text)
+                                        .extend()
+                                        .sense(egui::Sense::click()),
+                                );
+                                if has_code {
+                                    let response = response.on_hover_text(format!(
+                                        "Click to toggle a breakpoint at {}",
+                                        addrs
+                                            .iter()
+                                            .map(|addr| format!("${addr:04X}"))
+                                            .collect::<Vec<_>>()
+                                            .join(", ")
+                                    ));
+                                    if response.clicked() {
+                                        toggle = Some((armed, addrs));
+                                    }
+                                }
+                            }
+                        });
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        let mut addr = pc;
+                        for _ in 0..DISASSEMBLY_LINES {
+                            let instruction = nes.debug_disassemble(addr);
+                            let bytes: Vec<String> = instruction
+                                .bytes
+                                .iter()
+                                .map(|byte| format!("{byte:02X}"))
+                                .collect();
+                            let marker = if breakpoints.contains(&addr) { "●" } else { " " };
+                            let mut text = egui::RichText::new(format!(
+                                "{marker}{addr:04X}  {:<8}  {instruction}",
+                                bytes.join(" ")
+                            ))
+                            .monospace();
+                            if addr == pc {
+                                text = text.background_color(egui::Color32::from_rgb(70, 60, 10));
+                            }
+                            ui.label(text);
+                            addr = instruction.next_addr();
+                        }
+                    });
+                });
+                if let Some((armed, addrs)) = toggle {
+                    for addr in addrs {
+                        if armed {
+                            nes.remove_breakpoint(addr);
+                        } else {
+                            nes.add_breakpoint(addr);
+                        }
+                    }
+                }
+            });
+        request
+    }
+}
+
+fn file_label(info: &DebugInfo, file: usize) -> String {
+    info.files()
+        .get(file)
+        .map_or_else(String::new, |name| name.clone())
+}
+
+/// Finds a source file named in the debug info: as written (absolute, or
+/// relative to the .dbg), or by file name alone next to the .dbg.
+fn resolve_source(dir: &Path, name: &str) -> Option<PathBuf> {
+    let named = Path::new(name);
+    [
+        dir.join(named),
+        dir.join(named.file_name()?),
+        named.to_path_buf(),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
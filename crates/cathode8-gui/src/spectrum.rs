@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+const FFT_SIZE: usize = 1024;
+const BAND_COUNT: usize = 24;
+const MIN_BAND_HZ: f32 = 40.0;
+const BAND_DECAY: f32 = 0.85;
+const METER_DECAY: f32 = 0.9;
+
+/// Realtime spectrum and level meter fed from copies of the mixed APU output.
+pub struct SpectrumAnalyzer {
+    sample_rate: u32,
+    history: VecDeque<f32>,
+    window: Vec<f32>,
+    bands: [f32; BAND_COUNT],
+    rms: f32,
+    peak: f32,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(sample_rate: u32) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())
+            .collect();
+
+        Self {
+            sample_rate: sample_rate.max(8_000),
+            history: VecDeque::with_capacity(FFT_SIZE),
+            window,
+            bands: [0.0; BAND_COUNT],
+            rms: 0.0,
+            peak: 0.0,
+        }
+    }
+
+    pub fn push_samples(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        let mut sum_sq = 0.0f32;
+        let mut peak = 0.0f32;
+        for &sample in samples {
+            sum_sq += sample * sample;
+            peak = peak.max(sample.abs());
+        }
+        let rms = (sum_sq / samples.len() as f32).sqrt();
+        self.rms = rms.max(self.rms * METER_DECAY);
+        self.peak = peak.max(self.peak * METER_DECAY);
+
+        for &sample in samples {
+            if self.history.len() == FFT_SIZE {
+                self.history.pop_front();
+            }
+            self.history.push_back(sample);
+        }
+
+        if self.history.len() == FFT_SIZE {
+            self.update_bands();
+        }
+    }
+
+    /// Band magnitudes in the 0.0..=1.0 range, lowest frequency first.
+    pub fn bands(&self) -> &[f32] {
+        &self.bands
+    }
+
+    pub fn rms(&self) -> f32 {
+        self.rms
+    }
+
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    fn update_bands(&mut self) {
+        let mut re: Vec<f32> = self
+            .history
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, w)| sample * w)
+            .collect();
+        let mut im = vec![0.0f32; FFT_SIZE];
+        fft_in_place(&mut re, &mut im);
+
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let bin_hz = self.sample_rate as f32 / FFT_SIZE as f32;
+        let ratio = (nyquist / MIN_BAND_HZ).powf(1.0 / BAND_COUNT as f32);
+
+        for (band, value) in self.bands.iter_mut().enumerate() {
+            let lo_hz = MIN_BAND_HZ * ratio.powi(band as i32);
+            let hi_hz = lo_hz * ratio;
+            let lo_bin = ((lo_hz / bin_hz) as usize).max(1);
+            let hi_bin = ((hi_hz / bin_hz) as usize).clamp(lo_bin + 1, FFT_SIZE / 2);
+
+            let magnitude = (lo_bin..hi_bin)
+                .map(|bin| (re[bin] * re[bin] + im[bin] * im[bin]).sqrt())
+                .fold(0.0f32, f32::max);
+            // Normalize against a full-scale sine through the Hann window, then map
+            // a 60 dB range onto 0..1.
+            let normalized = magnitude / (FFT_SIZE as f32 / 4.0);
+            let db = 20.0 * normalized.max(1e-6).log10();
+            let level = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+
+            *value = level.max(*value * BAND_DECAY);
+        }
+    }
+}
+
+/// Iterative radix-2 Cooley-Tukey FFT. `re.len()` must be a power of two.
+fn fft_in_place(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    let mut j = 0usize;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while (j & bit) != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let mut cur_re = 1.0f32;
+            let mut cur_im = 0.0f32;
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,185 @@
+//! Speedrun timer with real time (RTA) and in-game time (IGT), plus a link
+//! to a LiveSplit Server so splits made here also drive external splits.
+//!
+//! IGT counts emulated frames while the run is going, so pauses, slowdown
+//! and fast-forward don't affect it. RTA is wall-clock time from the start.
+//! The link speaks the LiveSplit Server text protocol (one command per line
+//! over TCP, port 16834 by default), which LiveSplit's Server component and
+//! LiveSplit One desktop builds accept.
+
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIVESPLIT_ADDR: &str = "127.0.0.1:16834";
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Example for the script editor; RAM addresses are for Super Mario Bros.
+pub const EXAMPLE_SCRIPT: &str = "# start, split or reset, then conditions joined with &&\n\
+start $0770 == 1\n\
+split $075F changes\n\
+reset $0770 == 0\n";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedrunConfig {
+    pub script: String,
+    pub livesplit_addr: String,
+    pub overlay: bool,
+    /// Whether the script drives the timer.
+    pub autosplit: bool,
+}
+
+impl Default for SpeedrunConfig {
+    fn default() -> Self {
+        Self {
+            script: EXAMPLE_SCRIPT.to_string(),
+            livesplit_addr: DEFAULT_LIVESPLIT_ADDR.to_string(),
+            overlay: true,
+            autosplit: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Split {
+    pub rta: Duration,
+    pub igt_frames: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct SpeedrunTimer {
+    started_at: Option<Instant>,
+    /// RTA frozen when the run finished.
+    finished_rta: Option<Duration>,
+    igt_frames: u64,
+    splits: Vec<Split>,
+}
+
+impl SpeedrunTimer {
+    pub fn running(&self) -> bool {
+        self.started_at.is_some() && self.finished_rta.is_none()
+    }
+
+    pub fn started(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn start(&mut self, now: Instant) {
+        *self = Self {
+            started_at: Some(now),
+            ..Self::default()
+        };
+    }
+
+    /// Records a split; `last` stops the clocks.
+    pub fn split(&mut self, now: Instant, last: bool) {
+        if !self.running() {
+            return;
+        }
+        let rta = self.rta(now);
+        self.splits.push(Split {
+            rta,
+            igt_frames: self.igt_frames,
+        });
+        if last {
+            self.finished_rta = Some(rta);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Counts one emulated frame toward IGT.
+    pub fn tick_frame(&mut self) {
+        if self.running() {
+            self.igt_frames += 1;
+        }
+    }
+
+    pub fn rta(&self, now: Instant) -> Duration {
+        match (self.started_at, self.finished_rta) {
+            (_, Some(finished)) => finished,
+            (Some(started), None) => now.saturating_duration_since(started),
+            (None, None) => Duration::ZERO,
+        }
+    }
+
+    pub fn igt_frames(&self) -> u64 {
+        self.igt_frames
+    }
+
+    pub fn splits(&self) -> &[Split] {
+        &self.splits
+    }
+}
+
+/// `frames` of emulated time at `frame_rate_hz`.
+pub fn frames_to_duration(frames: u64, frame_rate_hz: f64) -> Duration {
+    Duration::from_secs_f64(frames as f64 / frame_rate_hz)
+}
+
+/// `m:ss.cc`, with hours only once the run reaches them.
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    let (hours, minutes, seconds) = (centis / 360_000, centis / 6_000 % 60, centis / 100 % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}.{:02}", centis % 100)
+    } else {
+        format!("{minutes}:{seconds:02}.{:02}", centis % 100)
+    }
+}
+
+pub struct LiveSplitLink {
+    stream: TcpStream,
+    peer: SocketAddr,
+}
+
+impl LiveSplitLink {
+    pub fn connect(addr: &str) -> Result<Self> {
+        let peer = addr
+            .to_socket_addrs()
+            .with_context(|| format!("resolving {addr}"))?
+            .next()
+            .ok_or_else(|| anyhow!("{addr} did not resolve"))?;
+        let stream = TcpStream::connect_timeout(&peer, CONNECT_TIMEOUT)
+            .with_context(|| format!("connecting to LiveSplit at {peer}"))?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(CONNECT_TIMEOUT))?;
+        Ok(Self { stream, peer })
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Starts LiveSplit's timer with game time driven from here.
+    pub fn start(&mut self) -> Result<()> {
+        self.send(&["starttimer", "initgametime", "pausegametime"])
+    }
+
+    /// Sends the IGT first so LiveSplit records it with the split.
+    pub fn split(&mut self, igt: Duration) -> Result<()> {
+        let game_time = format!("setgametime {:.3}", igt.as_secs_f64());
+        self.send(&[&game_time, "split"])
+    }
+
+    pub fn reset(&mut self) -> Result<()> {
+        self.send(&["reset"])
+    }
+
+    fn send(&mut self, commands: &[&str]) -> Result<()> {
+        let mut text = String::new();
+        for command in commands {
+            text.push_str(command);
+            text.push_str("\r\n");
+        }
+        self.stream
+            .write_all(text.as_bytes())
+            .with_context(|| format!("sending to LiveSplit at {}", self.peer))
+    }
+}
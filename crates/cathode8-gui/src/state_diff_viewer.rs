@@ -0,0 +1,238 @@
+//! State diff window: pick two save states (or the running console) and
+//! list every byte that differs, named from a label file when one is
+//! loaded. Labels next to the ROM (`game.mlb` from Mesen, `game.nes.ram.nl`
+//! from FCEUX) are picked up when it loads.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use cathode8_core::Nes;
+use cathode8_core::nes::state_diff::{self, ByteDiff, RamLabels, StateRegion};
+use eframe::egui;
+
+/// Rows drawn at most; the copied report always has all of them.
+const MAX_ROWS: usize = 2000;
+
+struct StateInput {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct StateDiffViewer {
+    before: Option<StateInput>,
+    after: Option<StateInput>,
+    labels: RamLabels,
+    labels_from: Option<String>,
+    diffs: Vec<ByteDiff>,
+    error: Option<String>,
+    show_video: bool,
+    labeled_only: bool,
+}
+
+impl StateDiffViewer {
+    /// Drops the picked states and loads the labels next to a newly loaded
+    /// ROM, if it has any.
+    pub fn load_for_rom(&mut self, rom: &Path) {
+        *self = Self {
+            show_video: self.show_video,
+            labeled_only: self.labeled_only,
+            ..Self::default()
+        };
+        let mut nl_name = rom.file_name().unwrap_or_default().to_os_string();
+        nl_name.push(".ram.nl");
+        for path in [rom.with_extension("mlb"), rom.with_file_name(nl_name)] {
+            if path.is_file() {
+                self.load_labels(&path);
+                break;
+            }
+        }
+    }
+
+    fn load_labels(&mut self, path: &Path) {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                self.labels = RamLabels::parse(&text);
+                self.labels_from = Some(file_name(path));
+            }
+            Err(err) => self.error = Some(format!("Reading {}: {err}", path.display())),
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        nes: &Nes,
+        state_dir: Option<PathBuf>,
+    ) {
+        egui::Window::new("State diff")
+            .open(open)
+            .default_size([460.0, 420.0])
+            .show(ctx, |ui| {
+                let mut changed = false;
+                egui::Grid::new("state_diff_inputs")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (label, slot) in [("A", &mut self.before), ("B", &mut self.after)] {
+                            ui.label(label);
+                            ui.horizontal(|ui| {
+                                if ui.button("State file...").clicked() {
+                                    let mut dialog = rfd::FileDialog::new()
+                                        .add_filter("Save state", &["state"])
+                                        .set_title("Pick a save state");
+                                    if let Some(dir) = &state_dir {
+                                        dialog = dialog.set_directory(dir);
+                                    }
+                                    if let Some(path) = dialog.pick_file() {
+                                        match std::fs::read(&path)
+                                            .with_context(|| format!("reading {}", path.display()))
+                                        {
+                                            Ok(bytes) => {
+                                                *slot = Some(StateInput {
+                                                    name: file_name(&path),
+                                                    bytes,
+                                                });
+                                                changed = true;
+                                            }
+                                            Err(err) => self.error = Some(format!("{err:#}")),
+                                        }
+                                    }
+                                }
+                                if ui
+                                    .add_enabled(nes.has_rom(), egui::Button::new("Current"))
+                                    .on_hover_text("Snapshot the running console now")
+                                    .clicked()
+                                {
+                                    match nes.save_state_to_vec() {
+                                        Ok(bytes) => {
+                                            *slot = Some(StateInput {
+                                                name: format!(
+                                                    "current (frame {})",
+                                                    nes.debug_counters().frame_count
+                                                ),
+                                                bytes,
+                                            });
+                                            changed = true;
+                                        }
+                                        Err(err) => self.error = Some(format!("{err:#}")),
+                                    }
+                                }
+                            });
+                            ui.label(slot.as_ref().map_or("-", |input| input.name.as_str()));
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Labels...").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Labels", &["mlb", "nl"])
+                            .pick_file()
+                    {
+                        self.load_labels(&path);
+                    }
+                    match &self.labels_from {
+                        Some(name) => ui.label(format!("{} labels from {name}", self.labels.len())),
+                        None => ui.label("No labels"),
+                    };
+                });
+                if changed {
+                    self.compare();
+                }
+                if let Some(err) = &self.error {
+                    ui.colored_label(egui::Color32::YELLOW, err);
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.show_video, "Nametables, palette and OAM");
+                    ui.add_enabled(
+                        !self.labels.is_empty(),
+                        egui::Checkbox::new(&mut self.labeled_only, "Labeled only"),
+                    );
+                });
+                let rows: Vec<&ByteDiff> = self
+                    .diffs
+                    .iter()
+                    .filter(|diff| self.visible(diff))
+                    .collect();
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} bytes differ ({} shown)",
+                        self.diffs.len(),
+                        rows.len()
+                    ));
+                    if ui
+                        .add_enabled(!rows.is_empty(), egui::Button::new("Copy report"))
+                        .clicked()
+                    {
+                        let report: Vec<String> =
+                            rows.iter().map(|diff| self.describe(diff)).collect();
+                        ui.ctx().copy_text(report.join("\n"));
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        egui::Grid::new("state_diff_rows")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for diff in rows.iter().take(MAX_ROWS) {
+                                    ui.monospace(diff.region.location(diff.offset));
+                                    ui.label(self.labels.for_diff(diff).unwrap_or(""));
+                                    ui.monospace(format!(
+                                        "{:02X} -> {:02X}",
+                                        diff.before, diff.after
+                                    ));
+                                    ui.monospace(format!(
+                                        "{:+}",
+                                        i16::from(diff.after) - i16::from(diff.before)
+                                    ));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+    }
+
+    fn compare(&mut self) {
+        self.diffs.clear();
+        self.error = None;
+        let (Some(before), Some(after)) = (&self.before, &self.after) else {
+            return;
+        };
+        match state_diff::diff_states(&before.bytes, &after.bytes) {
+            Ok(diffs) => self.diffs = diffs,
+            Err(err) => self.error = Some(format!("{err:#}")),
+        }
+    }
+
+    fn visible(&self, diff: &ByteDiff) -> bool {
+        let video = matches!(
+            diff.region,
+            StateRegion::Nametables | StateRegion::Palette | StateRegion::Oam
+        );
+        (self.show_video || !video) && (!self.labeled_only || self.labels.for_diff(diff).is_some())
+    }
+
+    fn describe(&self, diff: &ByteDiff) -> String {
+        let mut line = format!(
+            "{:<14} {:02X} -> {:02X}",
+            diff.region.location(diff.offset),
+            diff.before,
+            diff.after
+        );
+        if let Some(label) = self.labels.for_diff(diff) {
+            line.push_str(&format!("  {label}"));
+        }
+        line
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
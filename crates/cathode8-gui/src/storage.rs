@@ -0,0 +1,131 @@
+//! Where save states, battery SRAM, screenshots and movies are written.
+//!
+//! Each kind has its own base directory, defaulting to a folder under the
+//! platform data directory rather than next to the ROM, which may live on
+//! read-only media. Files for a ROM go into a subfolder named after it so
+//! games don't overwrite each other's slots.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Matches the title passed to `eframe::run_native`, so data lands next to
+/// the persisted app settings.
+const APP_ID: &str = "Cathode-8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    States,
+    Sram,
+    Screenshots,
+    Movies,
+}
+
+impl StorageKind {
+    pub const ALL: [StorageKind; 4] = [
+        StorageKind::States,
+        StorageKind::Sram,
+        StorageKind::Screenshots,
+        StorageKind::Movies,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StorageKind::States => "Save states",
+            StorageKind::Sram => "Battery saves",
+            StorageKind::Screenshots => "Screenshots",
+            StorageKind::Movies => "Movies",
+        }
+    }
+
+    fn default_folder(self) -> &'static str {
+        match self {
+            StorageKind::States => "states",
+            StorageKind::Sram => "sram",
+            StorageKind::Screenshots => "screenshots",
+            StorageKind::Movies => "movies",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageDirs {
+    pub states: PathBuf,
+    pub sram: PathBuf,
+    pub screenshots: PathBuf,
+    pub movies: PathBuf,
+}
+
+impl Default for StorageDirs {
+    fn default() -> Self {
+        Self {
+            states: default_dir(StorageKind::States),
+            sram: default_dir(StorageKind::Sram),
+            screenshots: default_dir(StorageKind::Screenshots),
+            movies: default_dir(StorageKind::Movies),
+        }
+    }
+}
+
+impl StorageDirs {
+    pub fn dir(&self, kind: StorageKind) -> &Path {
+        match kind {
+            StorageKind::States => &self.states,
+            StorageKind::Sram => &self.sram,
+            StorageKind::Screenshots => &self.screenshots,
+            StorageKind::Movies => &self.movies,
+        }
+    }
+
+    pub fn dir_mut(&mut self, kind: StorageKind) -> &mut PathBuf {
+        match kind {
+            StorageKind::States => &mut self.states,
+            StorageKind::Sram => &mut self.sram,
+            StorageKind::Screenshots => &mut self.screenshots,
+            StorageKind::Movies => &mut self.movies,
+        }
+    }
+
+    pub fn reset(&mut self, kind: StorageKind) {
+        *self.dir_mut(kind) = default_dir(kind);
+    }
+
+    /// The per-ROM subfolder of `kind` for `rom`, created if missing.
+    pub fn rom_dir(&self, kind: StorageKind, rom: &Path) -> Result<PathBuf> {
+        let name = rom
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or("unknown");
+        let dir = self.dir(kind).join(name);
+        fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        Ok(dir)
+    }
+}
+
+/// `<platform data dir>/<kind>`, or a folder in the working directory on
+/// platforms without one.
+fn default_dir(kind: StorageKind) -> PathBuf {
+    eframe::storage_dir(APP_ID)
+        .unwrap_or_else(|| PathBuf::from("cathode8-data"))
+        .join(kind.default_folder())
+}
+
+/// Writes `bytes` to `path` through a temporary file in the same folder,
+/// synced and then renamed over the target, so a crash mid-write leaves the
+/// previous file intact instead of a truncated one.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    let mut file =
+        fs::File::create(&temp).with_context(|| format!("creating {}", temp.display()))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("writing {}", temp.display()))?;
+    drop(file);
+    fs::rename(&temp, path).with_context(|| format!("replacing {}", path.display()))
+}
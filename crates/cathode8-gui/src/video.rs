@@ -0,0 +1,506 @@
+//! Presentation-side image processing. Nothing here feeds back into the
+//! emulated machine; it only changes what is drawn to the window.
+
+use serde::{Deserialize, Serialize};
+
+/// Mixes each new frame with the one before it, approximating CRT phosphor
+/// persistence. Games that flicker sprites on alternate frames to fake
+/// transparency read as a steady half-transparent image instead.
+pub struct FrameBlender {
+    /// Weight of the previous frame, 0.0 (off) to 1.0.
+    amount: f32,
+    previous: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl FrameBlender {
+    pub fn new(amount: f32) -> Self {
+        Self {
+            amount: amount.clamp(0.0, 1.0),
+            previous: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount.clamp(0.0, 1.0);
+    }
+
+    /// Forgets the previous frame, e.g. after loading a ROM or a state.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.output.clear();
+    }
+
+    /// Feeds the next emulated RGBA frame and blends it with the last one.
+    pub fn push_frame(&mut self, frame: &[u8]) {
+        if self.previous.len() != frame.len() {
+            self.previous = frame.to_vec();
+        }
+
+        let keep = self.amount;
+        let take = 1.0 - keep;
+        self.output.clear();
+        self.output.extend(
+            frame
+                .iter()
+                .zip(&self.previous)
+                .map(|(&cur, &prev)| (cur as f32 * take + prev as f32 * keep).round() as u8),
+        );
+        self.previous.copy_from_slice(frame);
+    }
+
+    /// The blended image, or `None` before the first frame was pushed.
+    pub fn output(&self) -> Option<&[u8]> {
+        (!self.output.is_empty()).then_some(self.output.as_slice())
+    }
+}
+
+/// Pixel-art upscaler applied to the emulated frame before upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Scaler {
+    #[default]
+    None,
+    /// AdvMAME Scale2x: copies neighbours into corners along clean edges,
+    /// never invents new colors.
+    Scale2x,
+    /// hq2x's corner and edge interpolation with its YUV similarity test,
+    /// without the full 256-pattern case table.
+    Hq2x,
+    /// Hyllian's 2xBR (level 1), the edge detector xBRZ is built on.
+    Xbr2x,
+}
+
+impl Scaler {
+    pub const ALL: [Scaler; 4] = [Scaler::None, Scaler::Scale2x, Scaler::Hq2x, Scaler::Xbr2x];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scaler::None => "None",
+            Scaler::Scale2x => "Scale2x",
+            Scaler::Hq2x => "HQ2x",
+            Scaler::Xbr2x => "xBR 2x",
+        }
+    }
+
+    pub fn factor(self) -> usize {
+        match self {
+            Scaler::None => 1,
+            Scaler::Scale2x | Scaler::Hq2x | Scaler::Xbr2x => 2,
+        }
+    }
+}
+
+/// Where the scanline pass sits relative to the scaler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FilterOrder {
+    /// Scale first, then darken every other output row: thin lines.
+    #[default]
+    ScalerThenScanlines,
+    /// Darken every other source row, then scale: one line per NES line.
+    ScanlinesThenScaler,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FilterSettings {
+    pub scaler: Scaler,
+    /// How much the scanline rows are darkened, 0.0 (off) to 1.0 (black).
+    pub scanlines: f32,
+    pub order: FilterOrder,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            scaler: Scaler::None,
+            scanlines: 0.0,
+            order: FilterOrder::default(),
+        }
+    }
+}
+
+/// Shape of the displayed image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AspectRatio {
+    /// Square pixels, as the frame buffer stores them.
+    #[default]
+    Square,
+    /// The 8:7 pixel aspect of an NTSC console's output.
+    Ntsc,
+    /// The whole visible image shaped like a 4:3 CRT.
+    Crt4x3,
+    /// Fill the window.
+    Stretch,
+}
+
+impl AspectRatio {
+    pub const ALL: [AspectRatio; 4] = [
+        AspectRatio::Square,
+        AspectRatio::Ntsc,
+        AspectRatio::Crt4x3,
+        AspectRatio::Stretch,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AspectRatio::Square => "Square pixels",
+            AspectRatio::Ntsc => "NTSC 8:7",
+            AspectRatio::Crt4x3 => "4:3",
+            AspectRatio::Stretch => "Stretch",
+        }
+    }
+
+    /// Display width over height for a `[width, height]` source, or `None`
+    /// when the image takes whatever shape the window has.
+    fn display_ratio(self, [width, height]: [f32; 2]) -> Option<f32> {
+        match self {
+            AspectRatio::Square => Some(width / height),
+            AspectRatio::Ntsc => Some(width * 8.0 / 7.0 / height),
+            AspectRatio::Crt4x3 => Some(4.0 / 3.0),
+            AspectRatio::Stretch => None,
+        }
+    }
+}
+
+/// NES pixels hidden at each edge, like the bezel of a TV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Overscan {
+    pub top: u8,
+    pub bottom: u8,
+    pub left: u8,
+    pub right: u8,
+}
+
+/// A named bundle of everything that decides how the frame is presented.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoPreset {
+    pub name: String,
+    pub aspect: AspectRatio,
+    /// Only scale by whole multiples, so every NES pixel gets the same size.
+    pub integer_scaling: bool,
+    pub overscan: Overscan,
+    pub filters: FilterSettings,
+}
+
+impl Default for VideoPreset {
+    fn default() -> Self {
+        Self::pixel_perfect()
+    }
+}
+
+impl VideoPreset {
+    pub fn pixel_perfect() -> Self {
+        Self {
+            name: "Pixel Perfect".to_string(),
+            aspect: AspectRatio::Square,
+            integer_scaling: true,
+            overscan: Overscan::default(),
+            filters: FilterSettings::default(),
+        }
+    }
+
+    /// Presets that ship with the emulator. User presets may not reuse
+    /// their names.
+    pub fn built_in() -> Vec<VideoPreset> {
+        vec![
+            Self::pixel_perfect(),
+            VideoPreset {
+                name: "CRT 4:3".to_string(),
+                aspect: AspectRatio::Crt4x3,
+                integer_scaling: false,
+                overscan: Overscan {
+                    top: 8,
+                    bottom: 8,
+                    left: 0,
+                    right: 0,
+                },
+                filters: FilterSettings {
+                    scaler: Scaler::None,
+                    scanlines: 0.35,
+                    order: FilterOrder::ScanlinesThenScaler,
+                },
+            },
+            VideoPreset {
+                name: "Smooth".to_string(),
+                aspect: AspectRatio::Ntsc,
+                integer_scaling: false,
+                overscan: Overscan::default(),
+                filters: FilterSettings {
+                    scaler: Scaler::Xbr2x,
+                    ..FilterSettings::default()
+                },
+            },
+        ]
+    }
+
+    /// Visible part of the 256x240 frame as `[left, top, right, bottom]`.
+    pub fn visible_rect(&self) -> [f32; 4] {
+        let Overscan {
+            top,
+            bottom,
+            left,
+            right,
+        } = self.overscan;
+        let left = (left as f32).min(120.0);
+        let top = (top as f32).min(112.0);
+        [
+            left,
+            top,
+            256.0 - (right as f32).min(120.0),
+            240.0 - (bottom as f32).min(112.0),
+        ]
+    }
+
+    /// On-screen `[width, height]` of the image within `available` space,
+    /// in points. Sizes are worked out in physical pixels, so integer
+    /// scaling means whole multiples of the monitor's pixels even when the
+    /// UI is scaled by a fractional `pixels_per_point`.
+    pub fn display_size(&self, available: [f32; 2], pixels_per_point: f32) -> [f32; 2] {
+        let [left, top, right, bottom] = self.visible_rect();
+        let source = [right - left, bottom - top];
+        let available = available.map(|points| points * pixels_per_point);
+        let to_points = |size: [f32; 2]| size.map(|pixels| pixels.floor() / pixels_per_point);
+        let Some(ratio) = self.aspect.display_ratio(source) else {
+            if self.integer_scaling {
+                return to_points(
+                    [0, 1].map(|axis| {
+                        (available[axis] / source[axis]).floor().max(1.0) * source[axis]
+                    }),
+                );
+            }
+            return to_points(available);
+        };
+
+        let mut height = available[1].min(available[0] / ratio);
+        if self.integer_scaling {
+            // Whole multiples of the source height keep scanlines even; the
+            // width follows from the aspect ratio.
+            height = ((height / source[1]).floor().max(1.0)) * source[1];
+        }
+        to_points([(height * ratio).round(), height])
+    }
+}
+
+/// Runs the configured scaler and scanline passes over RGBA frames, reusing
+/// its buffers between frames.
+#[derive(Default)]
+pub struct FilterChain {
+    settings: FilterSettings,
+    scratch: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl FilterChain {
+    pub fn new(settings: FilterSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    pub fn settings(&self) -> FilterSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: FilterSettings) {
+        self.settings = settings;
+    }
+
+    /// Filters a `size` RGBA frame and returns the output size and pixels,
+    /// or `None` when no pass is enabled and the frame can be used as is.
+    pub fn process(&mut self, frame: &[u8], size: [usize; 2]) -> Option<([usize; 2], &[u8])> {
+        let FilterSettings {
+            scaler,
+            scanlines,
+            order,
+        } = self.settings;
+        let scanlines = scanlines.clamp(0.0, 1.0);
+        if scaler == Scaler::None && scanlines <= 0.0 {
+            return None;
+        }
+
+        self.scratch.clear();
+        self.scratch.extend_from_slice(frame);
+        if order == FilterOrder::ScanlinesThenScaler {
+            darken_odd_rows(&mut self.scratch, size[0], scanlines);
+        }
+        let factor = scaler.factor();
+        let out_size = [size[0] * factor, size[1] * factor];
+        self.output.clear();
+        match scaler {
+            Scaler::None => self.output.extend_from_slice(&self.scratch),
+            Scaler::Scale2x => scale2x(&self.scratch, size, &mut self.output),
+            Scaler::Hq2x => hq2x(&self.scratch, size, &mut self.output),
+            Scaler::Xbr2x => xbr2x(&self.scratch, size, &mut self.output),
+        }
+        if order == FilterOrder::ScalerThenScanlines {
+            darken_odd_rows(&mut self.output, out_size[0], scanlines);
+        }
+        Some((out_size, &self.output))
+    }
+}
+
+fn darken_odd_rows(rgba: &mut [u8], width: usize, amount: f32) {
+    if amount <= 0.0 {
+        return;
+    }
+    let keep = 1.0 - amount;
+    for row in rgba.chunks_exact_mut(width * 4).skip(1).step_by(2) {
+        for pixel in row.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as f32 * keep) as u8;
+            }
+        }
+    }
+}
+
+type Rgba = [u8; 4];
+
+/// Clamped-edge access to an RGBA frame.
+struct Source<'a> {
+    rgba: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Source<'_> {
+    fn get(&self, x: usize, y: usize, dx: isize, dy: isize) -> Rgba {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        let offset = (y * self.width + x) * 4;
+        self.rgba[offset..offset + 4].try_into().unwrap()
+    }
+}
+
+/// Calls `block` for every source pixel and writes the 2x2 block it returns.
+fn scale_2x_with(
+    rgba: &[u8],
+    [width, height]: [usize; 2],
+    output: &mut Vec<u8>,
+    block: impl Fn(&Source, usize, usize) -> [Rgba; 4],
+) {
+    let source = Source {
+        rgba,
+        width,
+        height,
+    };
+    output.resize(width * height * 16, 0);
+    let out_width = width * 2;
+    for y in 0..height {
+        for x in 0..width {
+            let pixels = block(&source, x, y);
+            for (i, pixel) in pixels.iter().enumerate() {
+                let (ox, oy) = (x * 2 + i % 2, y * 2 + i / 2);
+                let offset = (oy * out_width + ox) * 4;
+                output[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+}
+
+fn scale2x(rgba: &[u8], size: [usize; 2], output: &mut Vec<u8>) {
+    scale_2x_with(rgba, size, output, |src, x, y| {
+        let e = src.get(x, y, 0, 0);
+        let b = src.get(x, y, 0, -1);
+        let d = src.get(x, y, -1, 0);
+        let f = src.get(x, y, 1, 0);
+        let h = src.get(x, y, 0, 1);
+        if b == h || d == f {
+            return [e; 4];
+        }
+        [
+            if d == b { d } else { e },
+            if b == f { f } else { e },
+            if d == h { d } else { e },
+            if h == f { f } else { e },
+        ]
+    });
+}
+
+fn yuv(pixel: Rgba) -> [f32; 3] {
+    let [r, g, b] = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.169 * r - 0.331 * g + 0.5 * b,
+        0.5 * r - 0.419 * g - 0.081 * b,
+    ]
+}
+
+/// hq2x's "different color" test: thresholds on Y, U and V separately.
+fn hq_differs(a: Rgba, b: Rgba) -> bool {
+    let (a, b) = (yuv(a), yuv(b));
+    (a[0] - b[0]).abs() > 48.0 || (a[1] - b[1]).abs() > 7.0 || (a[2] - b[2]).abs() > 6.0
+}
+
+/// Weighted average of `pixels`, alpha taken from the first one.
+fn mix(pixels: &[(Rgba, u32)]) -> Rgba {
+    let total: u32 = pixels.iter().map(|&(_, weight)| weight).sum();
+    let mut out = pixels[0].0;
+    for (channel, value) in out.iter_mut().take(3).enumerate() {
+        let sum: u32 = pixels
+            .iter()
+            .map(|&(pixel, weight)| pixel[channel] as u32 * weight)
+            .sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+    out
+}
+
+/// Corner offsets of the 2x2 output block: top-left, top-right, bottom-left,
+/// bottom-right.
+const CORNERS: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+fn hq2x(rgba: &[u8], size: [usize; 2], output: &mut Vec<u8>) {
+    scale_2x_with(rgba, size, output, |src, x, y| {
+        let e = src.get(x, y, 0, 0);
+        CORNERS.map(|(cx, cy)| {
+            let vertical = src.get(x, y, 0, cy);
+            let horizontal = src.get(x, y, cx, 0);
+            let diagonal = src.get(x, y, cx, cy);
+            if !hq_differs(vertical, horizontal) && hq_differs(e, vertical) {
+                // An edge runs across this corner.
+                mix(&[(e, 2), (vertical, 1), (horizontal, 1)])
+            } else if hq_differs(e, diagonal) {
+                mix(&[(e, 3), (diagonal, 1)])
+            } else {
+                e
+            }
+        })
+    });
+}
+
+fn xbr_distance(a: Rgba, b: Rgba) -> f32 {
+    let (a, b) = (yuv(a), yuv(b));
+    48.0 * (a[0] - b[0]).abs() + 7.0 * (a[1] - b[1]).abs() + 6.0 * (a[2] - b[2]).abs()
+}
+
+fn xbr2x(rgba: &[u8], size: [usize; 2], output: &mut Vec<u8>) {
+    scale_2x_with(rgba, size, output, |src, x, y| {
+        let e = src.get(x, y, 0, 0);
+        CORNERS.map(|(cx, cy)| {
+            // Neighbourhood written for the bottom-right corner and mirrored
+            // into the others: `at(1, 0)` is the horizontal neighbour toward
+            // the corner, `at(0, 1)` the vertical one.
+            let at = |dx: isize, dy: isize| src.get(x, y, dx * cx, dy * cy);
+            let (f, h, i) = (at(1, 0), at(0, 1), at(1, 1));
+            if e == f || e == h {
+                return e;
+            }
+            let d = xbr_distance;
+            let across =
+                d(e, at(1, -1)) + d(e, at(-1, 1)) + d(i, at(2, 0)) + d(i, at(0, 2)) + 4.0 * d(h, f);
+            let along =
+                d(h, at(-1, 0)) + d(h, at(1, 2)) + d(f, at(2, 1)) + d(f, at(0, -1)) + 4.0 * d(e, i);
+            if across < along {
+                let toward = if d(e, f) <= d(e, h) { f } else { h };
+                mix(&[(e, 1), (toward, 1)])
+            } else {
+                e
+            }
+        })
+    });
+}
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Downloads the homebrew ROMs listed in test-fixtures/roms/manifest.txt and
+# checks them against their SHA-256s. Safe to rerun: ROMs already present
+# are only re-checked.
+set -eu
+
+dir="$(dirname "$0")/../test-fixtures/roms"
+grep -v '^#' "$dir/manifest.txt" | while read -r url sha256 _frame _hash; do
+    [ -n "$url" ] || continue
+    file="$dir/$(basename "$url")"
+    [ -f "$file" ] || curl -fsSL -o "$file" "$url"
+    echo "$sha256  $file" | sha256sum -c -
+done
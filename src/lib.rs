@@ -1,3 +0,0 @@
-pub mod app;
-pub mod audio;
-pub mod nes;